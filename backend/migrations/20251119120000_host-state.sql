@@ -0,0 +1,2 @@
+ALTER TABLE hosts
+ADD COLUMN IF NOT EXISTS state TEXT NOT NULL DEFAULT '"Active"';
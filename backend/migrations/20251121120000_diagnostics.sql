@@ -0,0 +1,17 @@
+CREATE TABLE diagnostics (
+    id UUID PRIMARY KEY,
+    network_id UUID NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    daemon_id UUID REFERENCES daemons(id) ON DELETE SET NULL,
+    kind TEXT NOT NULL,
+    target TEXT NOT NULL,
+    success BOOLEAN NOT NULL,
+    detail TEXT,
+    error TEXT,
+    correlation_id UUID,
+    tags JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_diagnostics_network ON diagnostics(network_id);
+CREATE INDEX idx_diagnostics_correlation ON diagnostics(correlation_id) WHERE correlation_id IS NOT NULL;
@@ -0,0 +1,4 @@
+ALTER TABLE diagnostics
+ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_diagnostics_target ON diagnostics(network_id, target);
@@ -0,0 +1,5 @@
+-- API keys are stored as SHA-256 hashes; the plaintext is only shown when a key is issued
+ALTER TABLE api_keys RENAME COLUMN key TO key_hash;
+UPDATE api_keys SET key_hash = encode(sha256(convert_to(key_hash, 'UTF8')), 'hex');
+
+ALTER INDEX idx_api_keys_key RENAME TO idx_api_keys_key_hash;
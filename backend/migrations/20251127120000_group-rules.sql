@@ -0,0 +1,14 @@
+ALTER TABLE hosts ADD COLUMN group_ids JSONB NOT NULL DEFAULT '[]';
+
+CREATE TABLE group_rules (
+    id UUID PRIMARY KEY,
+    network_id UUID NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    group_id UUID NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    priority INTEGER NOT NULL DEFAULT 0,
+    matcher JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_group_rules_network ON group_rules(network_id);
@@ -0,0 +1,13 @@
+CREATE TABLE check_dependencies (
+    id UUID PRIMARY KEY,
+    network_id UUID NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    target TEXT NOT NULL,
+    depends_on TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (network_id, target, depends_on)
+);
+
+CREATE INDEX idx_check_dependencies_network ON check_dependencies(network_id);
+
+ALTER TABLE diagnostics ADD COLUMN suppressed_by TEXT;
@@ -0,0 +1,14 @@
+CREATE TABLE daemon_metric_rollups (
+    id UUID PRIMARY KEY,
+    daemon_id UUID NOT NULL REFERENCES daemons(id) ON DELETE CASCADE,
+    network_id UUID NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    bucket_start TIMESTAMPTZ NOT NULL,
+    heartbeats INTEGER NOT NULL DEFAULT 0,
+    max_heartbeat_gap_secs INTEGER,
+    last_heartbeat_at TIMESTAMPTZ,
+    sessions INTEGER NOT NULL DEFAULT 0,
+    session_secs INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (daemon_id, bucket_start)
+);
@@ -0,0 +1,2 @@
+-- Imported discovery runs have no daemon
+ALTER TABLE discovery ALTER COLUMN daemon_id DROP NOT NULL;
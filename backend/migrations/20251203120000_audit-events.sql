@@ -0,0 +1,13 @@
+-- actor_id and impersonated_user_id aren't foreign keys: the trail outlives the users in it
+CREATE TABLE audit_events (
+    id UUID PRIMARY KEY,
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    actor_id UUID NOT NULL,
+    impersonated_user_id UUID,
+    kind TEXT NOT NULL,
+    details JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_audit_events_organization ON audit_events(organization_id, created_at);
@@ -0,0 +1,6 @@
+-- How hosts on each network are recognized as the same host: mac, ip or hostname
+ALTER TABLE networks ADD COLUMN identity_key TEXT NOT NULL DEFAULT 'mac';
+
+-- Hostnames are compared case-insensitively and without a trailing dot
+UPDATE hosts SET hostname = LOWER(RTRIM(TRIM(hostname), '.')) WHERE hostname IS NOT NULL;
+UPDATE hosts SET hostname = NULL WHERE hostname = '';
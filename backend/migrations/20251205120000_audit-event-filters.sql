@@ -0,0 +1,6 @@
+-- Audit queries filter by organization and optionally actor or kind, then page through in
+-- (created_at, id) order, so each filter gets an index that serves the ordering too
+DROP INDEX idx_audit_events_organization;
+CREATE INDEX idx_audit_events_organization ON audit_events(organization_id, created_at, id);
+CREATE INDEX idx_audit_events_actor ON audit_events(organization_id, actor_id, created_at, id);
+CREATE INDEX idx_audit_events_kind ON audit_events(organization_id, kind, created_at, id);
@@ -0,0 +1,13 @@
+CREATE TABLE check_suites (
+    id UUID PRIMARY KEY,
+    network_id UUID NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    daemon_id UUID NOT NULL REFERENCES daemons(id) ON DELETE CASCADE,
+    definition JSONB NOT NULL,
+    interval_secs INTEGER,
+    last_run_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_check_suites_network ON check_suites(network_id);
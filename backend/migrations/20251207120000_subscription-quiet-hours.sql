@@ -0,0 +1,2 @@
+-- NULL or JSON null when the subscription has no quiet hours
+ALTER TABLE notification_subscriptions ADD COLUMN quiet_hours JSONB;
@@ -0,0 +1,14 @@
+CREATE TABLE discovery_webhooks (
+    id UUID PRIMARY KEY,
+    network_id UUID NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    daemon_id UUID NOT NULL REFERENCES daemons(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    signing_secret TEXT NOT NULL,
+    payload TEXT NOT NULL DEFAULT 'summary',
+    include_cancelled BOOLEAN NOT NULL DEFAULT FALSE,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_discovery_webhooks_daemon ON discovery_webhooks(daemon_id);
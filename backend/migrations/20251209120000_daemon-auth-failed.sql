@@ -0,0 +1 @@
+ALTER TABLE daemons ADD COLUMN auth_failed_at TIMESTAMPTZ;
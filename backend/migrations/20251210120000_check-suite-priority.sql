@@ -0,0 +1 @@
+ALTER TABLE check_suites ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
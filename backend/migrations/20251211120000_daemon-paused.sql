@@ -0,0 +1 @@
+ALTER TABLE daemons ADD COLUMN paused BOOLEAN NOT NULL DEFAULT FALSE;
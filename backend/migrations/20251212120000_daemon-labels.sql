@@ -0,0 +1 @@
+ALTER TABLE daemons ADD COLUMN labels JSONB NOT NULL DEFAULT '{}';
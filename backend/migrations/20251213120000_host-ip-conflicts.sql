@@ -0,0 +1 @@
+ALTER TABLE hosts ADD COLUMN ip_conflicts JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,9 @@
+-- Latest system metrics each daemon reported, plus their per-bucket aggregates
+ALTER TABLE daemons ADD COLUMN system_metrics JSONB;
+
+ALTER TABLE daemon_metric_rollups
+    ADD COLUMN system_samples INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN cpu_percent_sum DOUBLE PRECISION NOT NULL DEFAULT 0,
+    ADD COLUMN max_cpu_percent DOUBLE PRECISION,
+    ADD COLUMN max_memory_percent DOUBLE PRECISION,
+    ADD COLUMN max_disk_percent DOUBLE PRECISION;
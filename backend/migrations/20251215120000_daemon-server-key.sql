@@ -0,0 +1 @@
+ALTER TABLE daemons ADD COLUMN server_key TEXT;
@@ -0,0 +1,2 @@
+-- Latest result of each check, for the check metrics export
+CREATE INDEX IF NOT EXISTS idx_diagnostics_latest ON diagnostics(network_id, target, kind, created_at DESC);
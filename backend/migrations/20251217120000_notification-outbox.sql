@@ -0,0 +1,11 @@
+-- Deliveries deferred by quiet hours, so they survive restarts
+CREATE TABLE notification_outbox (
+    id UUID PRIMARY KEY,
+    channel_id UUID NOT NULL REFERENCES notification_channels(id) ON DELETE CASCADE,
+    notification JSONB NOT NULL,
+    due_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_notification_outbox_due ON notification_outbox(due_at);
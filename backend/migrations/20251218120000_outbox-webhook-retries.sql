@@ -0,0 +1,8 @@
+-- Discovery webhooks and failed deliveries are retried from the notification outbox
+ALTER TABLE notification_outbox
+    ALTER COLUMN channel_id DROP NOT NULL,
+    ALTER COLUMN notification DROP NOT NULL,
+    ADD COLUMN webhook_id UUID REFERENCES discovery_webhooks(id) ON DELETE CASCADE,
+    ADD COLUMN payload JSONB,
+    ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0,
+    ADD CONSTRAINT notification_outbox_one_target CHECK ((channel_id IS NULL) <> (webhook_id IS NULL));
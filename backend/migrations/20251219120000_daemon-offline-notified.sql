@@ -0,0 +1,2 @@
+-- When a daemon was last notified as offline, so outages aren't notified again after a restart
+ALTER TABLE daemons ADD COLUMN offline_notified_at TIMESTAMPTZ;
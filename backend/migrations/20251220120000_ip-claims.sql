@@ -0,0 +1,14 @@
+-- Which MACs discovery saw on each IP, so conflicts are found across restarts and servers
+CREATE TABLE ip_claims (
+    id UUID PRIMARY KEY,
+    network_id UUID NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    ip TEXT NOT NULL,
+    mac TEXT NOT NULL,
+    host_id UUID NOT NULL,
+    seen_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (network_id, ip, mac)
+);
+
+CREATE INDEX idx_ip_claims_seen ON ip_claims(network_id, seen_at);
@@ -0,0 +1,11 @@
+-- Which user each login session belongs to, so per-user session limits hold across restarts
+-- and server instances. Whether a session is still live is up to the session store.
+CREATE TABLE user_sessions (
+    id UUID PRIMARY KEY,
+    session_id TEXT NOT NULL UNIQUE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_user_sessions_user ON user_sessions(user_id, created_at);
@@ -0,0 +1 @@
+ALTER TABLE hosts ADD COLUMN tags JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,2 @@
+-- Label filters are containment queries (labels @> ...)
+CREATE INDEX idx_daemons_labels ON daemons USING GIN (labels jsonb_path_ops);
@@ -0,0 +1,9 @@
+-- Each organization's own daemon source allowlist, stored so every server instance enforces the
+-- same one and it survives restarts
+CREATE TABLE daemon_ip_allowlists (
+    id UUID PRIMARY KEY,
+    organization_id UUID NOT NULL UNIQUE REFERENCES organizations(id) ON DELETE CASCADE,
+    cidrs JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
@@ -48,13 +48,14 @@ async fn main() -> anyhow::Result<()> {
     let server_addr = &config_store.get_server_url().await?;
     let network_id = &config_store.get_network_id().await?;
     let api_key = &config_store.get_api_key().await?;
+    let bootstrap_token = &config_store.get_bootstrap_token().await?;
     let mode = &config_store.get_mode().await?;
 
     let state = DaemonAppState::new(config_store, utils).await?;
     let runtime_service = state.services.runtime_service.clone();
 
     // Create HTTP server with config values
-    let api_router = create_router().with_state(state);
+    let api_router = create_router(state.clone()).with_state(state);
 
     let app = Router::new().merge(api_router).layer(
         ServiceBuilder::new()
@@ -79,22 +80,36 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("📁 Config file: {:?}", path_str);
     tracing::info!("🔗 Server at {}", server_addr);
 
-    if let Some(network_id) = network_id {
+    if let (Some(network_id), Some(api_key)) = (network_id, api_key) {
         tracing::info!("Network ID available: {}", network_id);
-        if let Some(api_key) = api_key {
-            tracing::info!("API key available: [redacted]");
-            runtime_service
-                .initialize_services(*network_id, api_key.clone())
-                .await?;
-        } else {
-            tracing::warn!(
-                "Daemon is missing an API key. Go to discovery tab in UI to generate an API key."
-            );
+        tracing::info!("API key available: [redacted]");
+        runtime_service
+            .initialize_services(*network_id, api_key.clone())
+            .await?;
+    } else if bootstrap_token.is_some() {
+        tracing::info!("Bootstrap token available: [redacted]");
+        if network_id.is_none() {
+            tracing::info!("Missing network ID - registering to the server's default network");
         }
+        runtime_service
+            .initialize_with_bootstrap_token(*network_id)
+            .await?;
+    } else if let Some(network_id) = network_id {
+        tracing::info!("Network ID available: {}", network_id);
+        tracing::warn!(
+            "Daemon is missing an API key. Go to discovery tab in UI to generate an API key."
+        );
     } else {
         tracing::info!("Missing network ID - waiting for server to hit /api/initialize...");
     }
 
+    let monitor_service = runtime_service.clone();
+    tokio::spawn(async move {
+        if let Err(e) = monitor_service.monitor_certificates().await {
+            tracing::warn!("Certificate monitoring stopped: {}", e);
+        }
+    });
+
     if *mode == DaemonMode::Push {
         tracing::info!("Daemon running in Push mode");
         // Spawn heartbeat task in background
@@ -1,20 +1,30 @@
-use std::{sync::Arc, time::Duration};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 use axum::{
     Extension, Router,
     http::{HeaderValue, Method},
+    middleware,
 };
 use clap::Parser;
 use netvisor::server::{
+    auth::impersonation::audit_impersonation,
     billing::types::base::{BillingPlan, BillingRate, Price},
     config::{AppState, CliArgs, ServerConfig},
+    discovery::r#impl::retention::SessionRetentionPolicy,
     organizations::r#impl::base::{Organization, OrganizationBase},
     shared::{
+        deadline::request_deadline,
+        fields::sparse_fields,
         handlers::{cache::AppCache, factory::create_router},
+        self_test::{SelfTest, SelfTestMode},
         services::traits::CrudService,
-        storage::{filter::EntityFilter, traits::StorableEntity},
+        slo::track_slo,
+        storage::{filter::EntityFilter, pools::track_storage_client, traits::StorableEntity},
+    },
+    users::r#impl::{
+        base::{User, UserBase},
+        permissions::UserOrgPermissions,
     },
-    users::r#impl::base::{User, UserBase},
 };
 use reqwest::header;
 use tower::ServiceBuilder;
@@ -153,15 +163,33 @@ async fn main() -> anyhow::Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    if config.scan_empty_allowlist_undecided() {
+        tracing::warn!(
+            "No scan allowlist is configured, so daemons may be asked to scan any address. Set \
+             scan_allow_cidrs, or set NETVISOR_SCAN_EMPTY_ALLOWLIST to allow_all or deny_all to \
+             choose explicitly."
+        );
+    }
+
+    if config.self_test != SelfTestMode::Off {
+        let report = SelfTest::new(&config, &listen_addr).run().await;
+        report.log();
+        if report.blocks_startup(config.self_test) {
+            anyhow::bail!("Startup self-test failed: {}", report);
+        }
+    }
+
     // Create app state
     let state = AppState::new(config).await?;
     let user_service = state.services.user_service.clone();
     let discovery_service = state.services.discovery_service.clone();
     let organization_service = state.services.organization_service.clone();
     let billing_service = state.services.billing_service.clone();
+    let seed_user_emails = state.config.seed_user_emails.clone();
 
     // Create discovery cleanup task
     let discovery_cleanup_state = state.clone();
+    let retention_policy = SessionRetentionPolicy::from(&state.config);
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(300));
         loop {
@@ -170,11 +198,11 @@ async fn main() -> anyhow::Result<()> {
             // Check for timeouts (fail sessions running > 10 minutes)
             // discovery_cleanup_state.discovery_manager.check_timeouts(10).await;
 
-            // Clean up old sessions (remove completed sessions > 24 hours old)
+            // Clean up finished sessions that have outlived their retention
             discovery_cleanup_state
                 .services
                 .discovery_service
-                .cleanup_old_sessions(24)
+                .cleanup_old_sessions(&retention_policy)
                 .await;
         }
     });
@@ -193,6 +221,74 @@ async fn main() -> anyhow::Result<()> {
         }
     });
 
+    // Roll up daemon metrics as their buckets complete
+    let daemon_metrics_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(5 * 60)); // 5 minutes
+        loop {
+            interval.tick().await;
+            if let Err(e) = daemon_metrics_state
+                .services
+                .daemon_service
+                .flush_metrics(chrono::Utc::now())
+                .await
+            {
+                tracing::warn!("Failed to roll up daemon metrics: {}", e);
+            }
+        }
+    });
+
+    // Run scheduled check suites as they come due
+    let check_suite_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(30));
+        loop {
+            interval.tick().await;
+            if let Err(e) = check_suite_state
+                .services
+                .check_suite_service
+                .run_due(chrono::Utc::now())
+                .await
+            {
+                tracing::warn!("Failed to run scheduled check suites: {}", e);
+            }
+        }
+    });
+
+    // Send outbox deliveries deferred by quiet hours or waiting for a retry
+    let notification_outbox_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            if let Err(e) = notification_outbox_state
+                .services
+                .notification_service
+                .send_due(chrono::Utc::now())
+                .await
+            {
+                tracing::warn!("Failed to send outbox deliveries: {}", e);
+            }
+        }
+    });
+
+    // Notify about daemons that stopped sending heartbeats
+    let daemon_offline_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            if let Err(e) = daemon_offline_state
+                .services
+                .daemon_service
+                .notify_offline_daemons(chrono::Utc::now())
+                .await
+            {
+                tracing::warn!("Failed to check for offline daemons: {}", e);
+            }
+        }
+    });
+
     // Create invite link cleanup task
     let organization_service_invite_cleanup = organization_service.clone();
     tokio::spawn(async move {
@@ -205,11 +301,23 @@ async fn main() -> anyhow::Result<()> {
 
     let session_store = state.storage.sessions.clone();
 
-    let api_router = if let Some(static_path) = &web_external_path {
-        // First create the API router
-        let router = create_router().layer(session_store).with_state(state);
+    let router = create_router()
+        .route_layer(middleware::from_fn_with_state(state.slo.clone(), track_slo))
+        .layer(middleware::from_fn_with_state(state.clone(), sparse_fields))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            request_deadline,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            audit_impersonation,
+        ))
+        .layer(middleware::from_fn(track_storage_client))
+        .layer(session_store)
+        .with_state(state);
 
-        // Then add static file serving with SPA fallback
+    let api_router = if let Some(static_path) = &web_external_path {
+        // Add static file serving with SPA fallback
         router.fallback_service(
             ServeDir::new(static_path)
                 .append_index_html_on_directories(true)
@@ -220,7 +328,7 @@ async fn main() -> anyhow::Result<()> {
         )
     } else {
         tracing::info!("Server is not serving web assets due to no web_external_path");
-        create_router().layer(session_store).with_state(state)
+        router
     };
 
     let cors = if cfg!(debug_assertions) {
@@ -266,7 +374,12 @@ async fn main() -> anyhow::Result<()> {
 
     // Spawn server in background
     tokio::spawn(async move {
-        axum::serve(listener, app).await.unwrap();
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
     });
 
     // Start cron for discovery scheduler
@@ -317,6 +430,16 @@ async fn main() -> anyhow::Result<()> {
         user_service
             .create_user(User::new(UserBase::new_seed(organization.id)))
             .await?;
+
+        for email in seed_user_emails {
+            user_service
+                .create_user(User::new(UserBase::new_named_seed(
+                    email,
+                    organization.id,
+                    UserOrgPermissions::Admin,
+                )))
+                .await?;
+        }
     } else {
         tracing::debug!("Server already has data, skipping seed data");
     }
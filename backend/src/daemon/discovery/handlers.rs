@@ -1,6 +1,9 @@
 use crate::daemon::runtime::types::DaemonAppState;
 use crate::server::{
-    daemons::r#impl::api::{DaemonDiscoveryRequest, DaemonDiscoveryResponse},
+    daemons::r#impl::api::{
+        DAEMON_PROTOCOL_VERSION, DaemonDiscoveryRequest, DaemonDiscoveryResponse,
+        LEGACY_DAEMON_PROTOCOL_VERSION,
+    },
     shared::types::api::{ApiError, ApiResponse, ApiResult},
 };
 use axum::{Router, extract::State, response::Json, routing::post};
@@ -18,6 +21,22 @@ async fn handle_discovery_request(
     Json(request): Json<DaemonDiscoveryRequest>,
 ) -> ApiResult<Json<ApiResponse<DaemonDiscoveryResponse>>> {
     let session_id = request.session_id;
+    // Refuse a request shaped for a newer protocol than ours before starting anything, rather
+    // than run it without the fields we don't understand
+    let protocol_version = request
+        .protocol_version
+        .unwrap_or(LEGACY_DAEMON_PROTOCOL_VERSION);
+    if protocol_version > DAEMON_PROTOCOL_VERSION {
+        tracing::warn!(
+            session_id = %session_id,
+            protocol_version,
+            "Refused discovery request shaped for a newer protocol"
+        );
+        return Err(ApiError::conflict(&format!(
+            "Discovery request is for protocol {}, but this daemon speaks {}",
+            protocol_version, DAEMON_PROTOCOL_VERSION
+        )));
+    }
     tracing::info!(
         "Received {} discovery request, session ID {}",
         request.discovery_type,
@@ -32,6 +51,7 @@ async fn handle_discovery_request(
 
     Ok(Json(ApiResponse::success(DaemonDiscoveryResponse {
         session_id,
+        protocol_version: Some(protocol_version),
     })))
 }
 
@@ -34,6 +34,18 @@ impl DaemonDiscoverySessionManager {
             "Initiating discovery"
         );
 
+        // Imports and NDP scans have nothing to run, and must not displace a running session
+        if matches!(
+            request.discovery_type,
+            DiscoveryType::Import { .. } | DiscoveryType::Ndp { .. }
+        ) {
+            tracing::warn!(
+                session_id = %request.session_id,
+                "Ignoring request to run an imported discovery"
+            );
+            return;
+        }
+
         let cancel_token = self.start_new_session().await;
 
         let handle = match &request.discovery_type {
@@ -61,15 +73,26 @@ impl DaemonDiscoverySessionManager {
             DiscoveryType::Network {
                 subnet_ids,
                 host_naming_fallback,
+                scan_rate,
+                snmp_credential,
+                targets,
             } => self.clone().spawn_discovery(
                 DiscoveryRunner::new(
                     self.discovery_service.clone(),
                     self.clone(),
-                    NetworkScanDiscovery::new(subnet_ids.clone(), *host_naming_fallback),
+                    NetworkScanDiscovery::new(
+                        subnet_ids.clone(),
+                        *host_naming_fallback,
+                        *scan_rate,
+                        snmp_credential.clone(),
+                        targets.clone(),
+                    ),
                 ),
                 request.clone(),
                 cancel_token,
             ),
+            // Rejected above
+            DiscoveryType::Import { .. } | DiscoveryType::Ndp { .. } => return,
         };
 
         self.set_current_task(handle).await;
@@ -1,6 +1,6 @@
 use std::{
     net::IpAddr,
-    sync::{Arc, atomic::AtomicUsize},
+    sync::{Arc, Mutex, atomic::AtomicUsize},
 };
 
 use crate::{
@@ -8,7 +8,7 @@ use crate::{
         manager::DaemonDiscoverySessionManager, types::base::DiscoveryCriticalError,
     },
     server::{
-        discovery::r#impl::types::{DiscoveryType, HostNamingFallback},
+        discovery::r#impl::types::{DiscoveryType, HostNamingFallback, TargetResolution},
         groups::r#impl::base::Group,
         services::{
             definitions::docker_container::DockerContainer,
@@ -36,13 +36,16 @@ use crate::{
     daemon::{
         discovery::types::base::{DiscoveryPhase, DiscoverySessionInfo, DiscoverySessionUpdate},
         shared::config::ConfigStore,
-        utils::base::{PlatformDaemonUtils, create_system_utils},
+        utils::{
+            base::{PlatformDaemonUtils, create_system_utils},
+            signing::SignedJsonExt,
+        },
     },
     server::{
         daemons::r#impl::api::{DaemonDiscoveryRequest, DiscoveryUpdatePayload},
         hosts::r#impl::{
             api::HostWithServicesRequest,
-            base::{Host, HostBase},
+            base::{Host, HostBase, HostState},
             ports::{Port, PortBase},
             targets::HostTarget,
         },
@@ -54,7 +57,10 @@ use crate::{
                 definitions::{ServiceDefinition, ServiceDefinitionExt},
             },
         },
-        shared::types::{api::ApiResponse, metadata::HasId},
+        shared::{
+            sync::lock,
+            types::{api::ApiResponse, metadata::HasId},
+        },
         subnets::r#impl::base::Subnet,
     },
 };
@@ -84,6 +90,13 @@ pub struct DiscoverySession {
     pub info: DiscoverySessionInfo,
     pub gateway_ips: Vec<IpAddr>,
     pub processed_count: Arc<AtomicUsize>,
+    /// Concurrent host scans in use, reported to the server when set (non-zero)
+    pub effective_scan_rate: Arc<AtomicUsize>,
+    /// How each explicit target of a network discovery resolved, reported to the server once set
+    pub target_results: Arc<Mutex<Vec<TargetResolution>>>,
+    /// Hosts the server has accepted from this session so far, reported with every update so a
+    /// cancelled session keeps what it found
+    pub discovered_host_ids: Arc<Mutex<Vec<Uuid>>>,
 }
 
 impl DiscoverySession {
@@ -92,6 +105,9 @@ impl DiscoverySession {
             info,
             gateway_ips,
             processed_count: Arc::new(AtomicUsize::new(0)),
+            effective_scan_rate: Arc::new(AtomicUsize::new(0)),
+            target_results: Arc::new(Mutex::new(Vec::new())),
+            discovered_host_ids: Arc::new(Mutex::new(Vec::new())),
         }
     }
 }
@@ -151,12 +167,21 @@ pub trait RunsDiscovery: AsRef<DaemonDiscoveryService> + Send + Sync {
             .get_api_key()
             .await?
             .ok_or_else(|| anyhow::anyhow!("API key not set"))?;
+        let compress = self.as_ref().config_store.get_compress_results().await?;
 
-        let payload = DiscoveryUpdatePayload::from_state_and_update(
+        let mut payload = DiscoveryUpdatePayload::from_state_and_update(
             discovery_type,
             session.info.clone(),
             update,
         );
+        payload.effective_scan_rate = Some(
+            session
+                .effective_scan_rate
+                .load(std::sync::atomic::Ordering::Relaxed),
+        )
+        .filter(|rate| *rate > 0);
+        payload.target_results = lock(&session.target_results).clone();
+        payload.discovered_host_ids = lock(&session.discovered_host_ids).clone();
 
         let response = self
             .as_ref()
@@ -166,7 +191,7 @@ pub trait RunsDiscovery: AsRef<DaemonDiscoveryService> + Send + Sync {
                 server_target, session.info.session_id
             ))
             .header("Authorization", format!("Bearer {}", api_key))
-            .json(&payload)
+            .signed_json_compressed(&api_key, &payload, compress)?
             .send()
             .await?;
 
@@ -369,10 +394,17 @@ pub trait DiscoversNetworkedEntities:
             services: Vec::new(),
             ports: Vec::new(),
             source: EntitySource::Discovery {
-                metadata: vec![DiscoveryMetadata::new(discovery_type.clone(), daemon_id)],
+                metadata: vec![DiscoveryMetadata::new(
+                    discovery_type.clone(),
+                    Some(daemon_id),
+                )],
             },
             virtualization: None,
             hidden: false,
+            state: HostState::Pending,
+            group_ids: Vec::new(),
+            tags: Vec::new(),
+            ip_conflicts: Vec::new(),
         });
 
         let services = self.discover_services(
@@ -605,16 +637,21 @@ pub trait CreatesDiscoveredEntities:
             .get_api_key()
             .await?
             .ok_or_else(|| anyhow::anyhow!("API key not set"))?;
+        let compress = self.as_ref().config_store.get_compress_results().await?;
 
         let response = self
             .as_ref()
             .client
             .post(format!("{}/api/hosts", server_target))
             .header("Authorization", format!("Bearer {}", api_key))
-            .json(&HostWithServicesRequest {
-                host,
-                services: Some(services),
-            })
+            .signed_json_compressed(
+                &api_key,
+                &HostWithServicesRequest {
+                    host,
+                    services: Some(services),
+                },
+                compress,
+            )?
             .send()
             .await?;
 
@@ -640,6 +677,10 @@ pub trait CreatesDiscoveredEntities:
 
         let services = services.unwrap_or(vec![]);
 
+        if let Ok(session) = self.as_ref().get_session().await {
+            lock(&session.discovered_host_ids).push(host.id);
+        }
+
         Ok((host, services))
     }
 
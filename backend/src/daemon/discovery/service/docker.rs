@@ -271,7 +271,7 @@ impl DiscoveryRunner<DockerScanDiscovery> {
             source: EntitySource::DiscoveryWithMatch {
                 metadata: vec![DiscoveryMetadata::new(
                     DiscoveryType::SelfReport { host_id },
-                    daemon_id,
+                    Some(daemon_id),
                 )],
                 details: MatchDetails::new_certain("Docker daemon self-report"),
             },
@@ -281,7 +281,10 @@ impl DiscoveryRunner<DockerScanDiscovery> {
         temp_docker_daemon_host.id = self.domain.host_id;
         temp_docker_daemon_host.base.network_id = network_id;
         temp_docker_daemon_host.base.source = EntitySource::Discovery {
-            metadata: vec![DiscoveryMetadata::new(self.discovery_type(), daemon_id)],
+            metadata: vec![DiscoveryMetadata::new(
+                self.discovery_type(),
+                Some(daemon_id),
+            )],
         };
         temp_docker_daemon_host.base.services = vec![docker_service.id];
 
@@ -776,7 +779,7 @@ impl DiscoveryRunner<DockerScanDiscovery> {
                                 source: EntitySource::Discovery {
                                     metadata: vec![DiscoveryMetadata::new(
                                         self.discovery_type(),
-                                        daemon_id,
+                                        Some(daemon_id),
                                     )],
                                 },
                             }));
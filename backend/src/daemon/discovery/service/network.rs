@@ -2,13 +2,19 @@ use crate::daemon::discovery::service::base::{
     CreatesDiscoveredEntities, DiscoversNetworkedEntities, DiscoveryRunner, RunsDiscovery,
 };
 use crate::daemon::discovery::types::base::{DiscoveryCriticalError, DiscoverySessionUpdate};
+use crate::daemon::utils::network_checks::reverse_dns::{Resolver, SystemResolver};
+use crate::daemon::utils::scan_rate::AdaptiveScanRate;
 use crate::daemon::utils::scanner::scan_ports_and_endpoints;
-use crate::server::discovery::r#impl::types::{DiscoveryType, HostNamingFallback};
+use crate::server::discovery::r#impl::types::{
+    DiscoveryType, HostNamingFallback, ScanRate, TargetResolution, normalize_targets,
+};
 use crate::server::hosts::r#impl::{
     interfaces::{Interface, InterfaceBase},
     ports::PortBase,
 };
+use crate::server::secrets::r#impl::base::ResolvedSecrets;
 use crate::server::services::r#impl::base::ServiceMatchBaselineParams;
+use crate::server::shared::sync::lock;
 use crate::server::shared::types::api::ApiResponse;
 use crate::server::subnets::r#impl::types::{SubnetType, SubnetTypeDiscriminants};
 use crate::{
@@ -25,6 +31,7 @@ use futures::{
     future::try_join_all,
     stream::{self, StreamExt},
 };
+use std::collections::HashSet;
 use std::result::Result::Ok;
 use std::time::Duration;
 use std::{net::IpAddr, sync::Arc};
@@ -37,15 +44,108 @@ use uuid::Uuid;
 pub struct NetworkScanDiscovery {
     subnet_ids: Option<Vec<Uuid>>,
     host_naming_fallback: HostNamingFallback,
+    scan_rate: ScanRate,
+    /// Name of the SNMP community secret to probe with, resolved from the request
+    snmp_credential: Option<String>,
+    /// IPs or hostnames to scan instead of whole subnets
+    targets: Vec<String>,
 }
 
 impl NetworkScanDiscovery {
-    pub fn new(subnet_ids: Option<Vec<Uuid>>, host_naming_fallback: HostNamingFallback) -> Self {
+    pub fn new(
+        subnet_ids: Option<Vec<Uuid>>,
+        host_naming_fallback: HostNamingFallback,
+        scan_rate: ScanRate,
+        snmp_credential: Option<String>,
+        targets: Vec<String>,
+    ) -> Self {
         Self {
             subnet_ids,
             host_naming_fallback,
+            scan_rate,
+            snmp_credential,
+            targets: normalize_targets(&targets),
         }
     }
+
+    /// The SNMP community to probe with. The server resolves referenced secrets at dispatch, so
+    /// one missing here was deleted or isn't an SNMP community; probing with the default instead
+    /// would quietly report the wrong thing.
+    fn snmp_community(&self, secrets: &ResolvedSecrets) -> Result<Option<String>, Error> {
+        let Some(name) = &self.snmp_credential else {
+            return Ok(None);
+        };
+
+        secrets
+            .get(name)
+            .and_then(|secret| secret.snmp_community())
+            .map(|community| Some(community.to_string()))
+            .ok_or_else(|| anyhow::anyhow!("SNMP community secret '{}' was not supplied", name))
+    }
+}
+
+/// How long a daemon waits on DNS for one explicit target
+const TARGET_RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Resolve explicit targets to the address to scan with the system resolver, keeping a result per
+/// target (see [`resolve_targets_with`])
+pub async fn resolve_targets(targets: &[String]) -> Vec<TargetResolution> {
+    resolve_targets_with(&SystemResolver, targets).await
+}
+
+/// IPs are taken as given; hostnames are looked up, preferring an IPv4 address
+pub async fn resolve_targets_with(
+    resolver: &dyn Resolver,
+    targets: &[String],
+) -> Vec<TargetResolution> {
+    stream::iter(targets)
+        .then(|target| resolve_target(resolver, target))
+        .collect()
+        .await
+}
+
+async fn resolve_target(resolver: &dyn Resolver, target: &str) -> TargetResolution {
+    if let Ok(ip) = target.parse::<IpAddr>() {
+        return TargetResolution::resolved(target, ip);
+    }
+
+    match timeout(TARGET_RESOLVE_TIMEOUT, resolver.forward(target)).await {
+        Ok(Ok(ips)) => match ips.iter().find(|ip| ip.is_ipv4()).or(ips.first()) {
+            Some(ip) => TargetResolution::resolved(target, *ip),
+            None => TargetResolution::failed(target, "Hostname did not resolve"),
+        },
+        Ok(Err(e)) => TargetResolution::failed(target, e.to_string()),
+        Err(_) => TargetResolution::failed(target, "Timed out resolving hostname"),
+    }
+}
+
+/// Pair each resolved target with the subnet containing it, scanning each address once. Targets
+/// outside every given subnet are marked failed, as the daemon may only scan those.
+pub fn assign_targets(
+    resolutions: &mut [TargetResolution],
+    subnets: &[Subnet],
+) -> Vec<(IpAddr, Subnet)> {
+    let mut seen = HashSet::new();
+    let mut assigned = Vec::new();
+
+    for resolution in resolutions.iter_mut() {
+        let Some(ip) = resolution.address else {
+            continue;
+        };
+
+        match subnets.iter().find(|subnet| subnet.base.cidr.contains(&ip)) {
+            Some(subnet) => {
+                if seen.insert(ip) {
+                    assigned.push((ip, subnet.clone()));
+                }
+            }
+            None => {
+                resolution.error = Some(format!("{} is not in a subnet targeted by this scan", ip));
+            }
+        }
+    }
+
+    assigned
 }
 
 impl CreatesDiscoveredEntities for DiscoveryRunner<NetworkScanDiscovery> {}
@@ -56,6 +156,9 @@ impl RunsDiscovery for DiscoveryRunner<NetworkScanDiscovery> {
         DiscoveryType::Network {
             subnet_ids: None,
             host_naming_fallback: HostNamingFallback::BestService,
+            scan_rate: self.domain.scan_rate,
+            snmp_credential: self.domain.snmp_credential.clone(),
+            targets: self.domain.targets.clone(),
         }
     }
 
@@ -64,6 +167,10 @@ impl RunsDiscovery for DiscoveryRunner<NetworkScanDiscovery> {
         request: DaemonDiscoveryRequest,
         cancel: CancellationToken,
     ) -> Result<(), Error> {
+        if !self.domain.targets.is_empty() {
+            return self.discover_targets(request, cancel).await;
+        }
+
         // Ignore docker bridge subnets, they are discovered through Docker Discovery
         let subnets: Vec<Subnet> = self.discover_create_subnets().await?;
 
@@ -72,13 +179,18 @@ impl RunsDiscovery for DiscoveryRunner<NetworkScanDiscovery> {
             .map(|subnet| subnet.base.cidr.iter().count())
             .sum();
 
+        let snmp_community = self.domain.snmp_community(&request.secrets);
+
         self.start_discovery(total_ips_across_subnets, request)
             .await?;
 
-        let discovery_result = self
-            .scan_and_process_hosts(subnets, cancel.clone())
-            .await
-            .map(|_| ());
+        let discovery_result = match snmp_community {
+            Ok(snmp_community) => self
+                .scan_and_process_hosts(subnets, snmp_community.as_deref(), cancel.clone())
+                .await
+                .map(|_| ()),
+            Err(e) => Err(e),
+        };
 
         self.finish_discovery(discovery_result, cancel.clone())
             .await?;
@@ -149,10 +261,80 @@ impl DiscoversNetworkedEntities for DiscoveryRunner<NetworkScanDiscovery> {
 }
 
 impl DiscoveryRunner<NetworkScanDiscovery> {
+    /// Scan just the explicit targets, within the targeted subnets or any the server knows of.
+    /// A target that doesn't resolve or isn't in one of those subnets is reported as failed in
+    /// the session's target results rather than failing the whole session.
+    async fn discover_targets(
+        &self,
+        request: DaemonDiscoveryRequest,
+        cancel: CancellationToken,
+    ) -> Result<(), Error> {
+        let mut subnets = self.get_subnets().await?;
+        if let Some(subnet_ids) = &self.domain.subnet_ids {
+            subnets.retain(|subnet| subnet_ids.contains(&subnet.id));
+        }
+
+        let mut resolutions = resolve_targets(&self.domain.targets).await;
+        let ips_with_subnets = assign_targets(&mut resolutions, &subnets);
+        for failure in resolutions.iter().filter(|r| r.error.is_some()) {
+            tracing::warn!(
+                target = %failure.target,
+                error = ?failure.error,
+                "Skipping discovery target"
+            );
+        }
+
+        let total_targets = resolutions.len();
+        let skipped = total_targets - ips_with_subnets.len();
+        let snmp_community = self.domain.snmp_community(&request.secrets);
+
+        self.start_discovery(total_targets, request).await?;
+
+        let session = self.as_ref().get_session().await?;
+        *lock(&session.target_results) = resolutions;
+        session
+            .processed_count
+            .fetch_add(skipped, std::sync::atomic::Ordering::Relaxed);
+
+        let discovery_result = match snmp_community {
+            Ok(snmp_community) => self
+                .scan_and_process_ips(ips_with_subnets, snmp_community.as_deref(), cancel.clone())
+                .await
+                .map(|_| ()),
+            Err(e) => Err(e),
+        };
+
+        self.finish_discovery(discovery_result, cancel.clone())
+            .await?;
+
+        Ok(())
+    }
+
     /// Scan subnet concurrently and process hosts immediately as they're discovered
     async fn scan_and_process_hosts(
         &self,
         subnets: Vec<Subnet>,
+        snmp_community: Option<&str>,
+        cancel: CancellationToken,
+    ) -> Result<Vec<Host>, Error> {
+        let all_ips_with_subnets: Vec<(IpAddr, Subnet)> = subnets
+            .iter()
+            .flat_map(|subnet| {
+                self.determine_scan_order(&subnet.base.cidr)
+                    .map(move |ip| (ip, subnet.clone()))
+            })
+            .collect();
+
+        self.scan_and_process_ips(all_ips_with_subnets, snmp_community, cancel)
+            .await
+    }
+
+    /// Scan addresses concurrently, each within its subnet, and process hosts immediately as
+    /// they're discovered
+    async fn scan_and_process_ips(
+        &self,
+        all_ips_with_subnets: Vec<(IpAddr, Subnet)>,
+        snmp_community: Option<&str>,
         cancel: CancellationToken,
     ) -> Result<Vec<Host>, Error> {
         let configured_concurrent_scans = self.as_ref().config_store.get_concurrent_scans().await?;
@@ -162,20 +344,16 @@ impl DiscoveryRunner<NetworkScanDiscovery> {
             .get_optimal_concurrent_scans(configured_concurrent_scans)
             .await?;
 
+        let rate = AdaptiveScanRate::new(self.domain.scan_rate, concurrent_scans);
+
         let session = self.as_ref().get_session().await?;
         let scanned_count = session.processed_count.clone();
+        let effective_scan_rate = session.effective_scan_rate.clone();
+        effective_scan_rate.store(rate.current(), std::sync::atomic::Ordering::Relaxed);
 
         self.report_discovery_update(DiscoverySessionUpdate::scanning(0))
             .await?;
 
-        let all_ips_with_subnets: Vec<(IpAddr, Subnet)> = subnets
-            .iter()
-            .flat_map(|subnet| {
-                self.determine_scan_order(&subnet.base.cidr)
-                    .map(move |ip| (ip, subnet.clone()))
-            })
-            .collect();
-
         let total_ips = all_ips_with_subnets.len();
         tracing::info!("Total IPs to scan: {}", total_ips);
 
@@ -184,10 +362,20 @@ impl DiscoveryRunner<NetworkScanDiscovery> {
                 let cancel = cancel.clone();
                 let subnet = subnet.clone();
                 let scanned_count = scanned_count.clone();
+                let rate = &rate;
 
                 async move {
+                    let _permit = rate.acquire().await;
+
                     match self
-                        .scan_host(ip, scanned_count, cancel, subnet.base.cidr)
+                        .scan_host(
+                            ip,
+                            scanned_count,
+                            cancel,
+                            subnet.base.cidr,
+                            rate,
+                            snmp_community,
+                        )
                         .await
                     {
                         Ok(None) => {
@@ -274,7 +462,7 @@ impl DiscoveryRunner<NetworkScanDiscovery> {
                     }
                 }
             })
-            .buffer_unordered(concurrent_scans);
+            .buffer_unordered(rate.max());
 
         let mut stream_pin = Box::pin(results);
         let mut last_reported_processed_count: usize = 0;
@@ -283,6 +471,7 @@ impl DiscoveryRunner<NetworkScanDiscovery> {
 
         while let Some(result) = stream_pin.next().await {
             scanned += 1;
+            effective_scan_rate.store(rate.current(), std::sync::atomic::Ordering::Relaxed);
 
             if cancel.is_cancelled() {
                 tracing::warn!("Discovery session was cancelled");
@@ -314,6 +503,7 @@ impl DiscoveryRunner<NetworkScanDiscovery> {
             total_ips = %total_ips,
             scanned = %scanned,
             discovered = %successful_discoveries.len(),
+            scan_rate = %rate.current(),
             "📊 Scan complete"
         );
 
@@ -326,6 +516,8 @@ impl DiscoveryRunner<NetworkScanDiscovery> {
         scanned_count: Arc<std::sync::atomic::AtomicUsize>,
         cancel: CancellationToken,
         cidr: IpCidr,
+        rate: &AdaptiveScanRate,
+        snmp_community: Option<&str>,
     ) -> Result<Option<(Vec<PortBase>, Vec<EndpointResponse>)>, Error> {
         // Check cancellation at the start
         if cancel.is_cancelled() {
@@ -341,10 +533,23 @@ impl DiscoveryRunner<NetworkScanDiscovery> {
             .await?;
 
         // Scan ports and endpoints
-        let scan_result =
-            scan_ports_and_endpoints(ip, cancel.clone(), port_scan_batch_size, cidr, gateway_ips)
-                .await
-                .map_err(|e| anyhow::anyhow!("Scan task panicked: {}", e));
+        let scan_result = scan_ports_and_endpoints(
+            ip,
+            cancel.clone(),
+            port_scan_batch_size,
+            cidr,
+            gateway_ips,
+            snmp_community,
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!("Scan task panicked: {}", e));
+
+        // Timed out probes are loss for the scan rate. Failed scans were cancelled or failed
+        // locally, which says nothing about the network.
+        let scan_result = scan_result.map(|(open_ports, endpoint_responses, outcomes)| {
+            rate.record(outcomes);
+            (open_ports, endpoint_responses)
+        });
 
         // Check cancellation after network operation
         if cancel.is_cancelled() {
@@ -490,3 +695,121 @@ impl DiscoveryRunner<NetworkScanDiscovery> {
         Ok(subnets)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::utils::network_checks::types::CheckError;
+    use crate::server::daemons::r#impl::api::DiscoveryUpdatePayload;
+
+    /// Resolves only `nas.local`
+    struct FakeResolver;
+
+    #[async_trait]
+    impl Resolver for FakeResolver {
+        async fn reverse(&self, _ip: IpAddr) -> Result<Option<String>, CheckError> {
+            Ok(None)
+        }
+
+        async fn forward(&self, name: &str) -> Result<Vec<IpAddr>, CheckError> {
+            Ok(match name {
+                "nas.local" => vec!["fd00::20".parse().unwrap(), "10.0.0.20".parse().unwrap()],
+                _ => Vec::new(),
+            })
+        }
+    }
+
+    fn subnet(cidr: &str) -> Subnet {
+        let mut subnet = crate::tests::subnet(&Uuid::new_v4());
+        subnet.base.cidr = cidr.parse().unwrap();
+        subnet
+    }
+
+    #[tokio::test]
+    async fn test_explicit_targets_resolve_per_target() {
+        let request = DaemonDiscoveryRequest {
+            session_id: Uuid::new_v4(),
+            discovery_type: DiscoveryType::Network {
+                subnet_ids: None,
+                host_naming_fallback: HostNamingFallback::default(),
+                scan_rate: ScanRate::default(),
+                snmp_credential: None,
+                targets: [
+                    "10.0.0.5",
+                    " NAS.local",
+                    "10.0.0.5",
+                    "bogus.invalid",
+                    "192.168.9.9",
+                ]
+                .map(String::from)
+                .to_vec(),
+            },
+            secrets: Default::default(),
+            protocol_version: None,
+        };
+
+        let json = serde_json::to_string(&request).unwrap();
+        let parsed: DaemonDiscoveryRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.discovery_type, request.discovery_type);
+
+        // Duplicates collapse before anything is resolved
+        let targets = normalize_targets(parsed.discovery_type.explicit_targets());
+        assert_eq!(
+            targets,
+            ["10.0.0.5", "nas.local", "bogus.invalid", "192.168.9.9"].map(String::from)
+        );
+
+        let lan = subnet("10.0.0.0/24");
+        let mut results = resolve_targets_with(&FakeResolver, &targets).await;
+        let scanned = assign_targets(&mut results, std::slice::from_ref(&lan));
+
+        assert_eq!(
+            scanned
+                .iter()
+                .map(|(ip, subnet)| (ip.to_string(), subnet.id))
+                .collect::<Vec<_>>(),
+            vec![
+                ("10.0.0.5".to_string(), lan.id),
+                ("10.0.0.20".to_string(), lan.id)
+            ]
+        );
+        assert_eq!(
+            results[..2],
+            [
+                TargetResolution::resolved("10.0.0.5", "10.0.0.5".parse().unwrap()),
+                TargetResolution::resolved("nas.local", "10.0.0.20".parse().unwrap()),
+            ]
+        );
+        assert_eq!(
+            results[2],
+            TargetResolution::failed("bogus.invalid", "Hostname did not resolve")
+        );
+        assert_eq!(results[3].address, Some("192.168.9.9".parse().unwrap()));
+        assert!(
+            results[3]
+                .error
+                .as_ref()
+                .unwrap()
+                .contains("not in a subnet")
+        );
+
+        // Every target's outcome travels back to the server with session updates
+        let mut payload: DiscoveryUpdatePayload = serde_json::from_value(serde_json::json!({
+            "session_id": Uuid::new_v4(),
+            "daemon_id": Uuid::new_v4(),
+            "network_id": Uuid::new_v4(),
+            "phase": "Scanning",
+            "discovery_type": request.discovery_type,
+            "processed": 2,
+            "total_to_process": 4,
+            "error": null,
+            "started_at": null,
+            "finished_at": null,
+        }))
+        .unwrap();
+        payload.target_results = results.clone();
+        let parsed: DiscoveryUpdatePayload =
+            serde_json::from_str(&serde_json::to_string(&payload).unwrap()).unwrap();
+        assert_eq!(parsed.target_results, results);
+    }
+}
@@ -5,8 +5,11 @@ use crate::{
         },
         types::base::{DiscoveryPhase, DiscoverySessionInfo, DiscoverySessionUpdate},
     },
+    daemon::utils::network_checks::{arp::raw_sockets_available, suite::supported_checks},
     server::{
-        daemons::r#impl::api::{DaemonCapabilities, DaemonDiscoveryRequest},
+        daemons::r#impl::api::{
+            DAEMON_PROTOCOL_VERSION, DaemonCapabilities, DaemonDiscoveryRequest,
+        },
         discovery::r#impl::types::DiscoveryType,
         hosts::r#impl::{
             interfaces::{ALL_INTERFACES_IP, Interface},
@@ -33,7 +36,7 @@ use crate::{
     daemon::utils::base::DaemonUtils,
     server::{
         hosts::r#impl::{
-            base::{Host, HostBase},
+            base::{Host, HostBase, HostState},
             targets::HostTarget,
         },
         services::r#impl::base::Service,
@@ -184,10 +187,17 @@ impl RunsDiscovery for DiscoveryRunner<SelfReportDiscovery> {
             interfaces: interfaces.clone(),
             ports: vec![own_port],
             source: EntitySource::Discovery {
-                metadata: vec![DiscoveryMetadata::new(self.discovery_type(), daemon_id)],
+                metadata: vec![DiscoveryMetadata::new(
+                    self.discovery_type(),
+                    Some(daemon_id),
+                )],
             },
             hidden: false,
+            state: HostState::Pending,
             virtualization: None,
+            group_ids: Vec::new(),
+            tags: Vec::new(),
+            ip_conflicts: Vec::new(),
         };
 
         let mut host = Host::new(host_base);
@@ -213,7 +223,10 @@ impl RunsDiscovery for DiscoveryRunner<SelfReportDiscovery> {
             host_id: host.id,
             virtualization: None,
             source: EntitySource::DiscoveryWithMatch {
-                metadata: vec![DiscoveryMetadata::new(self.discovery_type(), daemon_id)],
+                metadata: vec![DiscoveryMetadata::new(
+                    self.discovery_type(),
+                    Some(daemon_id),
+                )],
                 details: MatchDetails::new_certain("NetVisor Daemon self-report"),
             },
         });
@@ -248,9 +261,23 @@ impl DiscoveryRunner<SelfReportDiscovery> {
     ) -> Result<(), Error> {
         let server_target = self.as_ref().config_store.get_server_url().await?;
 
+        // Reported so the server can estimate how long a network discovery would take
+        let configured_concurrent_scans = self.as_ref().config_store.get_concurrent_scans().await?;
+        let concurrent_scans = self
+            .as_ref()
+            .utils
+            .get_optimal_concurrent_scans(configured_concurrent_scans)
+            .await?;
+        let port_batch_size = self.as_ref().utils.get_optimal_port_batch_size().await?;
+
         let capabilities = DaemonCapabilities {
             has_docker_socket,
             interfaced_subnet_ids,
+            concurrent_scans: Some(concurrent_scans),
+            port_batch_size: Some(port_batch_size),
+            has_raw_socket_access: raw_sockets_available(),
+            supported_checks: supported_checks(),
+            protocol_version: Some(DAEMON_PROTOCOL_VERSION),
         };
 
         let api_key = self
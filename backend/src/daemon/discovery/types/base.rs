@@ -4,7 +4,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Copy)]
+#[derive(Debug, Clone, Serialize, Deserialize, Copy, PartialEq, Eq)]
 pub enum DiscoveryPhase {
     Pending, // Initial state, set by server; all subsequent states until Finished are set by Daemon
     Starting,
@@ -13,6 +13,8 @@ pub enum DiscoveryPhase {
     Complete,
     Failed,
     Cancelled,
+    /// Cancelled after finding some hosts, which are kept like a completed session's
+    CancelledWithPartialResults,
 }
 
 #[derive(Debug, Clone)]
@@ -54,6 +56,9 @@ impl std::fmt::Display for DiscoveryPhase {
             DiscoveryPhase::Scanning => write!(f, "Scanning for active hosts"),
             DiscoveryPhase::Complete => write!(f, "Discovery complete"),
             DiscoveryPhase::Cancelled => write!(f, "Discovery cancelled"),
+            DiscoveryPhase::CancelledWithPartialResults => {
+                write!(f, "Discovery cancelled, partial results kept")
+            }
             DiscoveryPhase::Failed => write!(f, "Discovery failed"),
         }
     }
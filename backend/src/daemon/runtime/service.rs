@@ -1,7 +1,19 @@
 use crate::daemon::discovery::manager::DaemonDiscoverySessionManager;
 use crate::daemon::utils::base::DaemonUtils;
 use crate::daemon::utils::base::{PlatformDaemonUtils, create_system_utils};
-use crate::server::daemons::r#impl::api::{DaemonCapabilities, DiscoveryUpdatePayload};
+use crate::daemon::utils::network_checks::{
+    arp::raw_sockets_available,
+    suite::supported_checks,
+    tls::tls_check,
+    types::{CheckOptions, display_target},
+};
+use crate::daemon::utils::signing::SignedJsonExt;
+use crate::daemon::utils::system_metrics::SystemMetricsSampler;
+use crate::server::daemons::r#impl::api::{
+    DAEMON_PROTOCOL_VERSION, DaemonCapabilities, DaemonHeartbeatRequest, DaemonHeartbeatResponse,
+    DiscoveryUpdatePayload,
+};
+use crate::server::diagnostics::r#impl::base::{DiagnosticBase, DiagnosticKind};
 use crate::{
     daemon::shared::config::ConfigStore,
     server::{
@@ -123,6 +135,42 @@ impl DaemonRuntimeService {
         }
     }
 
+    /// Make an authenticated call to the server to confirm it accepts this daemon's API key
+    pub async fn verify_server_auth(&self) -> Result<()> {
+        let daemon_id = self.config_store.get_id().await?;
+        let api_key = self
+            .config_store
+            .get_api_key()
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("API key not set"))?;
+        let server_target = self.config_store.get_server_url().await?;
+
+        let response = self
+            .client
+            .post(format!(
+                "{}/api/daemons/{}/auth-check",
+                server_target, daemon_id
+            ))
+            .header("Authorization", format!("Bearer {}", api_key))
+            .send()
+            .await?;
+
+        let status = response.status();
+        let api_response: ApiResponse<()> = response.json().await?;
+
+        if !status.is_success() || !api_response.success {
+            anyhow::bail!(
+                "Server rejected API key: HTTP {}: {}",
+                status,
+                api_response
+                    .error
+                    .unwrap_or_else(|| "Unknown error".to_string())
+            );
+        }
+
+        Ok(())
+    }
+
     pub async fn heartbeat(&self) -> Result<()> {
         let daemon_id = self.config_store.get_id().await?;
         let api_key = self
@@ -136,6 +184,7 @@ impl DaemonRuntimeService {
         interval_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
         let server_target = self.config_store.get_server_url().await?;
+        let mut system_metrics = SystemMetricsSampler::default();
 
         loop {
             interval_timer.tick().await;
@@ -148,6 +197,14 @@ impl DaemonRuntimeService {
                         server_target, daemon_id
                     ))
                     .header("Authorization", format!("Bearer {}", api_key))
+                    .signed_json(
+                        &api_key,
+                        &DaemonHeartbeatRequest {
+                            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+                            system_metrics: system_metrics.sample(),
+                            ..Default::default()
+                        },
+                    )?
                     .send()
                     .await?;
 
@@ -166,6 +223,27 @@ impl DaemonRuntimeService {
                         error = %error_msg,
                         "Heartbeat failed - check network connectivity"
                     );
+                } else if let Ok(ApiResponse {
+                    data: Some(heartbeat),
+                    ..
+                }) = response
+                    .json::<ApiResponse<DaemonHeartbeatResponse>>()
+                    .await
+                {
+                    if let Some(warning) = heartbeat.api_key_expiry {
+                        tracing::warn!(
+                            api_key_id = %warning.api_key_id,
+                            expires_at = %warning.expires_at,
+                            "API key expires in {} days - rotate it before then or the daemon \
+                             will be locked out",
+                            warning.days_left
+                        );
+                    }
+                    if let Some(server_key) = heartbeat.server_key
+                        && let Err(e) = self.config_store.set_server_key(server_key).await
+                    {
+                        tracing::warn!("Failed to store server key: {}", e);
+                    }
                 }
 
                 if let Err(e) = self.config_store.update_heartbeat().await {
@@ -180,6 +258,84 @@ impl DaemonRuntimeService {
         }
     }
 
+    /// Check the configured TLS endpoints on a schedule and report each result to the server,
+    /// which tracks certificate expiry over time and alerts as it approaches
+    pub async fn monitor_certificates(&self) -> Result<()> {
+        let targets = self.config_store.get_tls_monitors().await?;
+        if targets.is_empty() {
+            return Ok(());
+        }
+
+        let daemon_id = self.config_store.get_id().await?;
+        let api_key = self
+            .config_store
+            .get_api_key()
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("API key not set"))?;
+        let interval = Duration::from_secs(self.config_store.get_tls_monitor_interval().await?);
+        let server_target = self.config_store.get_server_url().await?;
+
+        let mut interval_timer = tokio::time::interval(interval);
+        interval_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            interval_timer.tick().await;
+
+            let Some(network_id) = self.config_store.get_network_id().await? else {
+                tracing::warn!(
+                    daemon_id = %daemon_id,
+                    "Certificate check skipped - network_id not configured"
+                );
+                continue;
+            };
+            let options = CheckOptions::default()
+                .with_proxy(None, self.config_store.get_check_proxy().await?);
+
+            for (host, port) in &targets {
+                let result = tls_check(host, *port, &options).await;
+
+                let diagnostic = DiagnosticBase {
+                    network_id,
+                    daemon_id: Some(daemon_id),
+                    kind: DiagnosticKind::Tls,
+                    target: display_target(host, *port),
+                    success: result.is_ok(),
+                    detail: result.as_ref().ok().and_then(|o| o.detail.clone()),
+                    error: result.as_ref().err().map(|e| e.to_string()),
+                    correlation_id: None,
+                    tags: vec!["certificate-monitor".to_string()],
+                    expires_at: match &result {
+                        Ok(outcome) => outcome.expires_at,
+                        Err(e) => e.expires_at(),
+                    },
+                    suppressed_by: None,
+                };
+
+                let response = self
+                    .client
+                    .post(format!("{}/api/diagnostics", server_target))
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .signed_json(&api_key, &diagnostic)?
+                    .send()
+                    .await;
+
+                match response {
+                    Ok(response) if response.status().is_success() => {}
+                    Ok(response) => tracing::warn!(
+                        target = %diagnostic.target,
+                        status = %response.status(),
+                        "Server rejected certificate check result"
+                    ),
+                    Err(e) => tracing::warn!(
+                        target = %diagnostic.target,
+                        "Failed to report certificate check result: {}",
+                        e
+                    ),
+                }
+            }
+        }
+    }
+
     /// Initialize daemon services (called immediately or via /initialize endpoint)
     pub async fn initialize_services(&self, network_id: Uuid, api_key: String) -> Result<()> {
         // Ensure network_id is stored
@@ -197,7 +353,7 @@ impl DaemonRuntimeService {
 
         tracing::info!("Registering with server...");
 
-        self.register_with_server(daemon_id, network_id, has_docker_client)
+        self.register_with_server(daemon_id, Some(network_id), has_docker_client)
             .await?;
 
         tracing::info!(
@@ -210,13 +366,40 @@ impl DaemonRuntimeService {
         Ok(())
     }
 
-    /// Register daemon with server and return assigned ID
+    /// Initialize daemon services using a one-time bootstrap token instead of an API key. The
+    /// server issues a permanent API key during registration, which is stored for later requests.
+    /// Without a network ID the server assigns its default network, which must be the token's.
+    pub async fn initialize_with_bootstrap_token(&self, network_id: Option<Uuid>) -> Result<()> {
+        if let Some(network_id) = network_id {
+            self.config_store.set_network_id(network_id).await?;
+        }
+
+        let daemon_id = self.config_store.get_id().await?;
+        let has_docker_client = self.utils.get_own_docker_socket().await?;
+
+        tracing::info!("Registering with server using bootstrap token...");
+
+        let network_id = self
+            .register_with_server(daemon_id, network_id, has_docker_client)
+            .await?;
+
+        tracing::info!(
+            daemon_id = %daemon_id,
+            network_id = %network_id,
+            has_docker = %has_docker_client,
+            "Daemon fully initialized"
+        );
+
+        Ok(())
+    }
+
+    /// Register daemon with server and return the network it was assigned to
     pub async fn register_with_server(
         &self,
         daemon_id: Uuid,
-        network_id: Uuid,
+        network_id: Option<Uuid>,
         has_docker_socket: bool,
-    ) -> Result<()> {
+    ) -> Result<Uuid> {
         let bind_address = self.config_store.get_bind_address().await?;
         let mode = self.config_store.get_mode().await?;
 
@@ -231,61 +414,92 @@ impl DaemonRuntimeService {
         };
 
         let daemon_port = self.config_store.get_port().await?;
-        if let Some(api_key) = self.config_store.get_api_key().await? {
-            tracing::info!("Registering daemon with ID: {}", daemon_id,);
-            let registration_request = DaemonRegistrationRequest {
-                daemon_id,
-                network_id,
-                daemon_ip,
-                daemon_port,
-                mode,
-                capabilities: DaemonCapabilities {
-                    has_docker_socket,
-                    interfaced_subnet_ids: Vec::new(),
-                },
-            };
 
-            let server_target = self.config_store.get_server_url().await?;
+        // Bootstrap tokens are only used until the server has issued a permanent API key
+        let api_key = self.config_store.get_api_key().await?;
+        let bootstrap_token = match api_key {
+            Some(_) => None,
+            None => self.config_store.get_bootstrap_token().await?,
+        };
 
-            let response = self
-                .client
-                .post(format!("{}/api/daemons/register", server_target))
-                .header("Authorization", format!("Bearer {}", api_key))
-                .json(&registration_request)
-                .send()
-                .await?;
+        if api_key.is_none() && bootstrap_token.is_none() {
+            anyhow::bail!("API key not set for daemon. Registration failed.")
+        }
 
-            let status = response.status();
-            let api_response: ApiResponse<DaemonRegistrationResponse> = response.json().await?;
+        tracing::info!("Registering daemon with ID: {}", daemon_id,);
+        let registration_request = DaemonRegistrationRequest {
+            daemon_id,
+            network_id,
+            daemon_ip,
+            daemon_port,
+            mode,
+            capabilities: DaemonCapabilities {
+                has_docker_socket,
+                interfaced_subnet_ids: Vec::new(),
+                concurrent_scans: None,
+                port_batch_size: None,
+                has_raw_socket_access: raw_sockets_available(),
+                supported_checks: supported_checks(),
+                protocol_version: Some(DAEMON_PROTOCOL_VERSION),
+            },
+            bootstrap_token,
+            labels: self.config_store.get_labels().await?,
+        };
 
-            if !status.is_success() {
-                anyhow::bail!(
-                    "Registration failed: {}",
-                    api_response.error.unwrap_or("Unknown Error".to_string())
-                );
-            }
+        let server_target = self.config_store.get_server_url().await?;
 
-            if !api_response.success {
-                let error_msg = api_response
-                    .error
-                    .unwrap_or_else(|| "Unknown registration error".to_string());
-                anyhow::bail!("Registration failed: {}", error_msg);
-            }
+        let mut request = self
+            .client
+            .post(format!("{}/api/daemons/register", server_target))
+            .json(&registration_request);
+
+        if let Some(api_key) = &api_key {
+            request = request.header("Authorization", format!("Bearer {}", api_key));
+        }
 
-            let response = api_response
-                .data
-                .ok_or_else(|| anyhow::anyhow!("No daemon data in successful response"))?;
+        let response = request.send().await?;
 
-            self.config_store.set_host_id(response.host_id).await?;
+        let status = response.status();
+        let api_response: ApiResponse<DaemonRegistrationResponse> = response.json().await?;
 
-            tracing::info!(
-                "Successfully registered with server, assigned ID: {}",
-                response.daemon.id
+        if !status.is_success() {
+            anyhow::bail!(
+                "Registration failed: {}",
+                api_response.error.unwrap_or("Unknown Error".to_string())
             );
+        }
 
-            Ok(())
-        } else {
-            anyhow::bail!("API key not set for daemon. Registration failed.")
+        if !api_response.success {
+            let error_msg = api_response
+                .error
+                .unwrap_or_else(|| "Unknown registration error".to_string());
+            anyhow::bail!("Registration failed: {}", error_msg);
+        }
+
+        let response = api_response
+            .data
+            .ok_or_else(|| anyhow::anyhow!("No daemon data in successful response"))?;
+
+        if let Some(issued_api_key) = response.api_key {
+            tracing::info!("Received API key from server in exchange for bootstrap token");
+            self.config_store.set_api_key(issued_api_key).await?;
+            self.config_store.clear_bootstrap_token().await?;
+        }
+
+        if let Some(server_key) = response.server_key {
+            self.config_store.set_server_key(server_key).await?;
         }
+
+        self.config_store.set_host_id(response.host_id).await?;
+        self.config_store
+            .set_network_id(response.daemon.base.network_id)
+            .await?;
+
+        tracing::info!(
+            "Successfully registered with server, assigned ID: {}",
+            response.daemon.id
+        );
+
+        Ok(response.daemon.base.network_id)
     }
 }
@@ -1,13 +1,19 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::daemon::{
-    shared::{config::ConfigStore, services::DaemonServiceFactory},
-    utils::base::PlatformDaemonUtils,
+use crate::{
+    daemon::{
+        shared::{config::ConfigStore, services::DaemonServiceFactory},
+        utils::base::PlatformDaemonUtils,
+    },
+    server::daemons::r#impl::signing::ResultSignatureVerifier,
 };
 
+/// How far the server's clock may drift from the daemon's before its signed requests are refused
+const SERVER_SIGNATURE_MAX_SKEW: Duration = Duration::from_secs(300);
+
 #[derive(Serialize, Deserialize)]
 pub struct InitializeDaemonRequest {
     pub network_id: Uuid,
@@ -18,6 +24,8 @@ pub struct DaemonAppState {
     pub config: Arc<ConfigStore>,
     pub services: Arc<DaemonServiceFactory>,
     pub utils: PlatformDaemonUtils,
+    /// Checks requests were signed by the server, remembering nonces so none is replayed
+    pub server_signatures: ResultSignatureVerifier,
 }
 
 impl DaemonAppState {
@@ -31,6 +39,7 @@ impl DaemonAppState {
             config,
             services,
             utils,
+            server_signatures: ResultSignatureVerifier::new(true, SERVER_SIGNATURE_MAX_SKEW),
         }))
     }
 }
@@ -0,0 +1,229 @@
+use crate::{
+    daemon::runtime::types::DaemonAppState,
+    server::shared::types::api::{ApiError, ApiResult},
+};
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// Largest request body the server sends a daemon, a discovery request or check suite
+const MAX_SIGNED_BODY_BYTES: usize = 1024 * 1024;
+
+/// Only let requests through that the server signed with this daemon's server key, so nobody
+/// else who can reach the daemon's port can have it scan or probe the network, and the server
+/// learns when the key it holds for the daemon is no longer accepted
+pub async fn require_server_signature(
+    State(state): State<Arc<DaemonAppState>>,
+    request: Request,
+    next: Next,
+) -> ApiResult<Response> {
+    let server_key = state.config.get_server_key().await?.ok_or_else(|| {
+        ApiError::unauthorized(
+            "Daemon has no server key yet, it's issued on registration and heartbeats".to_string(),
+        )
+    })?;
+
+    let (parts, body) = request.into_parts();
+    let body = to_bytes(body, MAX_SIGNED_BODY_BYTES).await.map_err(|_| {
+        ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "Request body exceeds the maximum size of {} bytes",
+                MAX_SIGNED_BODY_BYTES
+            ),
+        )
+    })?;
+
+    state
+        .server_signatures
+        .verify(&server_key, &parts.headers, &body)?;
+
+    Ok(next.run(Request::from_parts(parts, Body::from(body))).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        daemon::{
+            shared::{
+                config::{AppConfig, ConfigStore},
+                handlers::create_router,
+            },
+            utils::base::{DaemonUtils, PlatformDaemonUtils},
+        },
+        server::{
+            daemons::r#impl::signing::signature_headers,
+            discovery::r#impl::target_policy::ScanTargetPolicy,
+        },
+    };
+    use axum::http::Method;
+    use tower::Service as _;
+    use uuid::Uuid;
+
+    const SERVER_KEY: &str = "test-server-key";
+
+    async fn daemon_router() -> axum::Router {
+        let config = AppConfig {
+            server_key: Some(SERVER_KEY.to_string()),
+            ..Default::default()
+        };
+        let path = std::env::temp_dir().join(format!("netvisor-daemon-{}.json", Uuid::new_v4()));
+        let state = DaemonAppState::new(
+            Arc::new(ConfigStore::new(path, config)),
+            PlatformDaemonUtils::new(),
+        )
+        .await
+        .unwrap();
+
+        create_router(state.clone()).with_state(state)
+    }
+
+    async fn post(
+        app: &mut axum::Router,
+        uri: &str,
+        body: serde_json::Value,
+        key: Option<&str>,
+    ) -> StatusCode {
+        let body = serde_json::to_vec(&body).unwrap();
+
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header("content-type", "application/json");
+        if let Some(key) = key {
+            for (name, value) in signature_headers(key, &body) {
+                request = request.header(name, value);
+            }
+        }
+
+        app.call(request.body(Body::from(body)).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    async fn arp_scan(app: &mut axum::Router, key: Option<&str>) -> StatusCode {
+        // Denied by policy, so a request that gets through never touches the network
+        let body = serde_json::json!({
+            "interface": "eth0",
+            "cidr": "10.0.0.0/24",
+            "scan_policy": ScanTargetPolicy {
+                deny: vec!["10.0.0.0/8".parse().unwrap()],
+                ..ScanTargetPolicy::unrestricted()
+            },
+        });
+        post(app, "/api/network-checks/arp-scan", body, key).await
+    }
+
+    fn loopback_denied() -> ScanTargetPolicy {
+        ScanTargetPolicy {
+            deny: vec!["127.0.0.0/8".parse().unwrap(), "::1/128".parse().unwrap()],
+            ..ScanTargetPolicy::unrestricted()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_network_checks_only_run_for_the_server() {
+        let mut app = daemon_router().await;
+
+        assert_eq!(arp_scan(&mut app, None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            arp_scan(&mut app, Some("not-the-server")).await,
+            StatusCode::UNAUTHORIZED
+        );
+
+        // Signed by the server it gets through, and the daemon holds it to the scan policy itself
+        assert_eq!(
+            arp_scan(&mut app, Some(SERVER_KEY)).await,
+            StatusCode::FORBIDDEN
+        );
+
+        // Discovery and the connectivity test's server-side steps are the server's alone too
+        for uri in [
+            "/api/discovery/cancel",
+            "/api/self-test/auth",
+            "/api/self-test/probe",
+        ] {
+            for key in [None, Some("not-the-server")] {
+                let body = serde_json::json!({});
+                assert_eq!(
+                    post(&mut app, uri, body, key).await,
+                    StatusCode::UNAUTHORIZED,
+                    "{}",
+                    uri
+                );
+            }
+        }
+
+        let health = Request::builder()
+            .uri("/api/health")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.call(health).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_named_check_targets_held_to_policy_once_resolved() {
+        let mut app = daemon_router().await;
+
+        // The server can't tell where a name points, so the daemon checks what it resolves to
+        let suite = serde_json::json!({
+            "suite": {
+                "checks": [
+                    { "kind": "tcp", "host": "192.0.2.1", "port": 443 },
+                    { "kind": "tcp", "host": "localhost", "port": 22 },
+                ],
+            },
+            "scan_policy": loopback_denied(),
+        });
+        assert_eq!(
+            post(
+                &mut app,
+                "/api/network-checks/suite",
+                suite,
+                Some(SERVER_KEY)
+            )
+            .await,
+            StatusCode::FORBIDDEN
+        );
+
+        let check = serde_json::json!({
+            "check": { "kind": "tls", "host": "localhost", "port": 443 },
+            "scan_policy": loopback_denied(),
+        });
+        assert_eq!(
+            post(
+                &mut app,
+                "/api/network-checks/check",
+                check,
+                Some(SERVER_KEY)
+            )
+            .await,
+            StatusCode::FORBIDDEN
+        );
+
+        // Nor can a name that doesn't resolve be checked against it
+        let suite = serde_json::json!({
+            "suite": {
+                "checks": [{ "kind": "http", "url": "http://unresolvable.invalid/" }],
+            },
+            "scan_policy": loopback_denied(),
+        });
+        assert_eq!(
+            post(
+                &mut app,
+                "/api/network-checks/suite",
+                suite,
+                Some(SERVER_KEY)
+            )
+            .await,
+            StatusCode::FORBIDDEN
+        );
+    }
+}
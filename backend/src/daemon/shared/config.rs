@@ -11,7 +11,10 @@ use std::{path::PathBuf, sync::Arc};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
-use crate::server::daemons::r#impl::base::DaemonMode;
+use crate::{
+    daemon::utils::network_checks::proxy::ProxyConfig,
+    server::daemons::r#impl::base::{DaemonLabels, DaemonMode, parse_labels},
+};
 
 #[derive(Parser)]
 #[command(name = "netvisor-daemon")]
@@ -57,12 +60,36 @@ pub struct DaemonCli {
     #[arg(long)]
     daemon_api_key: Option<String>,
 
+    /// One-time bootstrap token, exchanged for an API key on first registration
+    #[arg(long)]
+    bootstrap_token: Option<String>,
+
     /// Docker socket proxy
     #[arg(long)]
     docker_proxy: Option<String>,
 
+    /// SOCKS5 or HTTP CONNECT proxy URL that network checks are routed through by default
+    #[arg(long)]
+    check_proxy: Option<String>,
+
     #[arg(long)]
     mode: Option<DaemonMode>,
+
+    /// Gzip discovery results sent to the server
+    #[arg(long)]
+    compress_results: Option<bool>,
+
+    /// Comma separated host:port TLS endpoints whose certificates are checked for expiry
+    #[arg(long, value_delimiter = ',')]
+    tls_monitors: Option<Vec<String>>,
+
+    /// Certificate expiry check interval in seconds
+    #[arg(long)]
+    tls_monitor_interval: Option<u64>,
+
+    /// Comma separated key=value labels sent to the server on registration, e.g. site=nyc
+    #[arg(long)]
+    labels: Option<String>,
 }
 
 /// Unified configuration struct that handles both startup and runtime config
@@ -93,9 +120,45 @@ pub struct AppConfig {
     #[serde(default)]
     pub daemon_api_key: Option<String>,
     #[serde(default)]
+    pub bootstrap_token: Option<String>,
+    /// Key the server signs its requests to this daemon with, issued by the server
+    #[serde(default)]
+    pub server_key: Option<String>,
+    #[serde(default)]
     pub docker_proxy: Option<String>,
     #[serde(default)]
+    pub check_proxy: Option<String>,
+    #[serde(default)]
     pub mode: DaemonMode,
+    /// host:port TLS endpoints whose certificates are checked for expiry on a schedule
+    #[serde(default)]
+    pub tls_monitors: Vec<String>,
+    #[serde(default = "default_tls_monitor_interval")]
+    pub tls_monitor_interval: u64,
+    /// Gzip discovery results sent to the server, for constrained links. Needs a server that
+    /// accepts compressed request bodies.
+    #[serde(default)]
+    pub compress_results: bool,
+    /// Labels the daemon registers with, e.g. `site=nyc`
+    #[serde(default)]
+    pub labels: DaemonLabels,
+}
+
+fn default_tls_monitor_interval() -> u64 {
+    6 * 60 * 60
+}
+
+/// Split a `host:port` monitor target, unbracketing IPv6 literals
+fn parse_monitor_target(target: &str) -> Result<(String, u16)> {
+    let (host, port) = target
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow::anyhow!("TLS monitor '{}' must be host:port", target))?;
+    let port = port
+        .parse()
+        .map_err(|_| anyhow::anyhow!("TLS monitor '{}' has an invalid port", target))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    Ok((host.to_string(), port))
 }
 
 impl Default for AppConfig {
@@ -112,11 +175,18 @@ impl Default for AppConfig {
             last_heartbeat: None,
             host_id: None,
             daemon_api_key: None,
+            bootstrap_token: None,
+            server_key: None,
             concurrent_scans: 15,
             docker_proxy: None,
+            check_proxy: None,
             mode: DaemonMode::Push,
             server_port: None,
             server_target: None,
+            tls_monitors: Vec::new(),
+            tls_monitor_interval: default_tls_monitor_interval(),
+            compress_results: false,
+            labels: DaemonLabels::new(),
         }
     }
 }
@@ -171,17 +241,42 @@ impl AppConfig {
         if let Some(daemon_api_key) = cli_args.daemon_api_key {
             figment = figment.merge(("daemon_api_key", daemon_api_key));
         }
+        if let Some(bootstrap_token) = cli_args.bootstrap_token {
+            figment = figment.merge(("bootstrap_token", bootstrap_token));
+        }
         if let Some(docker_proxy) = cli_args.docker_proxy {
             figment = figment.merge(("docker_proxy", docker_proxy));
         }
+        if let Some(check_proxy) = cli_args.check_proxy {
+            figment = figment.merge(("check_proxy", check_proxy));
+        }
         if let Some(mode) = cli_args.mode {
             figment = figment.merge(("mode", mode));
         }
+        if let Some(compress_results) = cli_args.compress_results {
+            figment = figment.merge(("compress_results", compress_results));
+        }
+        if let Some(tls_monitors) = cli_args.tls_monitors {
+            figment = figment.merge(("tls_monitors", tls_monitors));
+        }
+        if let Some(tls_monitor_interval) = cli_args.tls_monitor_interval {
+            figment = figment.merge(("tls_monitor_interval", tls_monitor_interval));
+        }
+        if let Some(labels) = cli_args.labels {
+            figment = figment.merge(("labels", parse_labels(&labels).map_err(Error::msg)?));
+        }
 
         let config: AppConfig = figment
             .extract()
             .map_err(|e| Error::msg(format!("Configuration error: {}", e)))?;
 
+        if let Some(check_proxy) = &config.check_proxy {
+            check_proxy.parse::<ProxyConfig>()?;
+        }
+        for target in &config.tls_monitors {
+            parse_monitor_target(target)?;
+        }
+
         Ok(config)
     }
 }
@@ -273,6 +368,53 @@ impl ConfigStore {
         self.save(&config.clone()).await
     }
 
+    pub async fn get_bootstrap_token(&self) -> Result<Option<String>> {
+        let config = self.config.read().await;
+        Ok(config.bootstrap_token.clone())
+    }
+
+    pub async fn clear_bootstrap_token(&self) -> Result<()> {
+        let mut config = self.config.write().await;
+        config.bootstrap_token = None;
+        self.save(&config.clone()).await
+    }
+
+    pub async fn get_server_key(&self) -> Result<Option<String>> {
+        let config = self.config.read().await;
+        Ok(config.server_key.clone())
+    }
+
+    /// Store the server's signing key, skipping the write when it hasn't changed
+    pub async fn set_server_key(&self, server_key: String) -> Result<()> {
+        let mut config = self.config.write().await;
+        if config.server_key.as_ref() == Some(&server_key) {
+            return Ok(());
+        }
+        config.server_key = Some(server_key);
+        self.save(&config.clone()).await
+    }
+
+    /// Daemon-wide proxy for network checks, used when a check doesn't set its own
+    pub async fn get_check_proxy(&self) -> Result<Option<ProxyConfig>> {
+        let config = self.config.read().await;
+        config.check_proxy.as_deref().map(str::parse).transpose()
+    }
+
+    /// TLS endpoints to monitor for certificate expiry, as (host, port)
+    pub async fn get_tls_monitors(&self) -> Result<Vec<(String, u16)>> {
+        let config = self.config.read().await;
+        config
+            .tls_monitors
+            .iter()
+            .map(|t| parse_monitor_target(t))
+            .collect()
+    }
+
+    pub async fn get_tls_monitor_interval(&self) -> Result<u64> {
+        let config = self.config.read().await;
+        Ok(config.tls_monitor_interval)
+    }
+
     pub async fn get_host_id(&self) -> Result<Option<Uuid>> {
         let config = self.config.read().await;
         Ok(config.host_id)
@@ -284,6 +426,11 @@ impl ConfigStore {
         self.save(&config.clone()).await
     }
 
+    pub async fn get_labels(&self) -> Result<DaemonLabels> {
+        let config = self.config.read().await;
+        Ok(config.labels.clone())
+    }
+
     pub async fn get_port(&self) -> Result<u16> {
         let config = self.config.read().await;
         Ok(config.daemon_port)
@@ -331,6 +478,11 @@ impl ConfigStore {
         }
     }
 
+    pub async fn get_compress_results(&self) -> Result<bool> {
+        let config = self.config.read().await;
+        Ok(config.compress_results)
+    }
+
     pub async fn get_concurrent_scans(&self) -> Result<usize> {
         let config = self.config.read().await;
         Ok(config.concurrent_scans)
@@ -2,21 +2,60 @@ use crate::{
     daemon::{
         discovery::handlers as discovery_handlers,
         runtime::types::{DaemonAppState, InitializeDaemonRequest},
+        shared::auth::require_server_signature,
+        utils::network_checks::{
+            arp::{ArpScanResult, arp_scan},
+            ndp::{NdpScanResult, ndp_scan},
+            suite::{
+                CheckRunner, SuiteCheck, SuiteCheckResult, SuiteMember, SuiteReport,
+                SystemCheckRunner, run_suite,
+            },
+            tcp::tcp_connect,
+            types::{CheckError, CheckOptions, CheckOutcome},
+        },
+    },
+    server::{
+        daemons::r#impl::api::{
+            DaemonArpScanRequest, DaemonCheckRequest, DaemonNdpScanRequest, DaemonProbeRequest,
+            DaemonSuiteRequest,
+        },
+        discovery::r#impl::target_policy::{ScanTargetDenied, ScanTargetPolicy},
+        shared::types::api::{ApiError, ApiResponse, ApiResult},
     },
-    server::shared::types::api::{ApiResponse, ApiResult},
 };
 use axum::{
     Json, Router,
     extract::State,
+    middleware,
     routing::{get, post},
 };
-use std::sync::Arc;
+use cidr::IpCidr;
+use std::{net::IpAddr, sync::Arc};
+use tokio::net::lookup_host;
 
-pub fn create_router() -> Router<Arc<DaemonAppState>> {
-    Router::new()
+pub fn create_router(state: Arc<DaemonAppState>) -> Router<Arc<DaemonAppState>> {
+    let network_checks = Router::new()
+        .route("/arp-scan", post(run_arp_scan))
+        .route("/ndp-scan", post(run_ndp_scan))
+        .route("/suite", post(run_check_suite))
+        .route("/check", post(run_check));
+
+    // Anything that has the daemon touch the network or call the server only takes orders from
+    // the server, so a daemon holding a stale server key answers 401 to all of them
+    let from_server = Router::new()
         .nest("/api/discovery", discovery_handlers::create_router())
+        .route("/api/self-test/auth", post(self_test_auth))
+        .route("/api/self-test/probe", post(self_test_probe))
+        .nest("/api/network-checks", network_checks)
+        .route_layer(middleware::from_fn_with_state(
+            state,
+            require_server_signature,
+        ));
+
+    Router::new()
         .route("/api/health", get(get_health))
         .route("/api/initialize", post(initialize))
+        .merge(from_server)
 }
 
 async fn get_health() -> ApiResult<Json<ApiResponse<String>>> {
@@ -46,3 +85,157 @@ async fn initialize(
         "Daemon initialized successfully".to_string(),
     )))
 }
+
+/// Connectivity test step: call back to the server with this daemon's API key
+async fn self_test_auth(
+    State(state): State<Arc<DaemonAppState>>,
+) -> ApiResult<Json<ApiResponse<()>>> {
+    state
+        .services
+        .runtime_service
+        .verify_server_auth()
+        .await
+        .map_err(|e| ApiError::bad_gateway(&e.to_string()))?;
+
+    Ok(Json(ApiResponse::success(())))
+}
+
+/// Connectivity test step: minimal scan of a single port on localhost
+async fn self_test_probe(
+    Json(request): Json<DaemonProbeRequest>,
+) -> ApiResult<Json<ApiResponse<CheckOutcome>>> {
+    let outcome = tcp_connect("127.0.0.1", request.port, &CheckOptions::default())
+        .await
+        .map_err(|e| ApiError::bad_gateway(&e.to_string()))?;
+
+    Ok(Json(ApiResponse::success(outcome)))
+}
+
+/// ARP scan a range on one of this daemon's local segments, on the server's behalf
+async fn run_arp_scan(
+    Json(request): Json<DaemonArpScanRequest>,
+) -> ApiResult<Json<ApiResponse<ArpScanResult>>> {
+    if let Some(policy) = &request.scan_policy {
+        policy
+            .check_cidr(&IpCidr::V4(request.cidr))
+            .map_err(|e| ApiError::forbidden(&e.to_string()))?;
+    }
+
+    let result = arp_scan(&request.interface, request.cidr, &CheckOptions::default())
+        .await
+        .map_err(|e| match e {
+            CheckError::NotLocal { .. } | CheckError::TooManyTargets { .. } => {
+                ApiError::bad_request(&e.to_string())
+            }
+            _ => ApiError::bad_gateway(&e.to_string()),
+        })?;
+
+    Ok(Json(ApiResponse::success(result)))
+}
+
+/// Find the IPv6 neighbors on one of this daemon's local segments, on the server's behalf.
+/// Neighbors the server's scan target policy doesn't permit aren't reported.
+async fn run_ndp_scan(
+    Json(request): Json<DaemonNdpScanRequest>,
+) -> ApiResult<Json<ApiResponse<NdpScanResult>>> {
+    if let (Some(policy), Some(prefix)) = (&request.scan_policy, request.prefix) {
+        policy
+            .check_cidr(&IpCidr::V6(prefix))
+            .map_err(|e| ApiError::forbidden(&e.to_string()))?;
+    }
+
+    let mut result = ndp_scan(&request.interface, request.prefix, &CheckOptions::default())
+        .await
+        .map_err(|e| match e {
+            CheckError::NotLocal { .. } => ApiError::bad_request(&e.to_string()),
+            _ => ApiError::bad_gateway(&e.to_string()),
+        })?;
+
+    if let Some(policy) = &request.scan_policy {
+        result
+            .neighbors
+            .retain(|n| policy.permits_address(IpAddr::V6(n.ip)));
+    }
+
+    Ok(Json(ApiResponse::success(result)))
+}
+
+/// Hold a check's target to the server's scan target policy. Names are resolved here and every
+/// address one resolves to must be permitted; a name that doesn't resolve can't be held to the
+/// policy, so is refused.
+async fn check_target_policy(policy: &ScanTargetPolicy, check: &SuiteCheck) -> ApiResult<()> {
+    if !policy.is_restrictive() {
+        return Ok(());
+    }
+
+    let addresses: Vec<IpAddr> = match (check.ip(), check.host()) {
+        (Some(ip), _) => vec![ip],
+        (None, Some(host)) => lookup_host((host.as_str(), 0))
+            .await
+            .map(|addresses| addresses.map(|a| a.ip()).collect())
+            .unwrap_or_default(),
+        (None, None) => Vec::new(),
+    };
+    if addresses.is_empty() {
+        return Err(ApiError::forbidden(
+            &ScanTargetDenied {
+                target: check.target(),
+            }
+            .to_string(),
+        ));
+    }
+
+    for ip in addresses {
+        policy
+            .check_cidr(&IpCidr::new_host(ip))
+            .map_err(|e| ApiError::forbidden(&e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Run a check suite on the server's behalf, returning the combined report. No check runs unless
+/// every one's target is permitted.
+async fn run_check_suite(
+    State(state): State<Arc<DaemonAppState>>,
+    Json(request): Json<DaemonSuiteRequest>,
+) -> ApiResult<Json<ApiResponse<SuiteReport>>> {
+    let suite = request.suite;
+    suite.validate().map_err(|e| ApiError::bad_request(&e))?;
+
+    if let Some(policy) = &request.scan_policy {
+        for member in &suite.checks {
+            check_target_policy(policy, &member.check).await?;
+        }
+    }
+
+    let options = CheckOptions::default().with_proxy(None, state.config.get_check_proxy().await?);
+    let report = run_suite(&suite, &options).await;
+
+    Ok(Json(ApiResponse::success(report)))
+}
+
+/// Run a single check on the server's behalf. A failing check is still a successful response,
+/// with the failure on the result.
+async fn run_check(
+    State(state): State<Arc<DaemonAppState>>,
+    Json(request): Json<DaemonCheckRequest>,
+) -> ApiResult<Json<ApiResponse<SuiteCheckResult>>> {
+    let check = request.check;
+    check.validate().map_err(|e| ApiError::bad_request(&e))?;
+
+    if let Some(policy) = &request.scan_policy {
+        check_target_policy(policy, &check).await?;
+    }
+
+    let options = CheckOptions::default().with_proxy(None, state.config.get_check_proxy().await?);
+    let result = SystemCheckRunner.run(&check, &options).await;
+
+    Ok(Json(ApiResponse::success(SuiteCheckResult::new(
+        &SuiteMember {
+            check,
+            advisory: false,
+        },
+        result,
+    ))))
+}
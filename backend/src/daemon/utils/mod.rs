@@ -1,5 +1,9 @@
 pub mod base;
 pub mod linux;
 pub mod macos;
+pub mod network_checks;
+pub mod scan_rate;
 pub mod scanner;
+pub mod signing;
+pub mod system_metrics;
 pub mod windows;
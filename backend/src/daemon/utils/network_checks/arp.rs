@@ -0,0 +1,451 @@
+use cidr::{Ipv4Cidr, Ipv4Inet};
+use mac_address::MacAddress;
+use pnet::{
+    datalink::{self, Channel, DataLinkReceiver, DataLinkSender},
+    packet::{
+        MutablePacket, Packet,
+        arp::{ArpHardwareTypes, ArpOperations, ArpPacket, MutableArpPacket},
+        ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket},
+    },
+    util::MacAddr,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr},
+    time::{Duration, Instant},
+};
+use tokio::task::spawn_blocking;
+
+use crate::daemon::utils::network_checks::types::{CheckError, CheckOptions};
+
+/// Most addresses one scan may cover, a /16
+pub const MAX_ARP_TARGETS: u64 = 1 << 16;
+
+/// How long a read on the segment blocks before the scan checks its deadline
+const READ_POLL: Duration = Duration::from_millis(50);
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const ARP_PACKET_LEN: usize = 28;
+
+/// A MAC address that answered ARP requests, with every address it answered for
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ArpResponder {
+    pub mac: MacAddress,
+    pub ips: Vec<Ipv4Addr>,
+    /// Whether the MAC answered for more than one address, e.g. a host with aliases or a router
+    /// doing proxy ARP
+    pub multiple_ips: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ArpScanResult {
+    pub interface: String,
+    pub cidr: Ipv4Cidr,
+    /// Addresses a request was sent for
+    pub probed: u64,
+    pub responders: Vec<ArpResponder>,
+}
+
+/// An interface the daemon can send ARP requests from
+#[derive(Debug, Clone)]
+pub struct LocalInterface {
+    pub name: String,
+    pub mac: MacAddress,
+    pub addresses: Vec<Ipv4Inet>,
+}
+
+impl LocalInterface {
+    /// Address to send requests for `cidr` from, when the whole range is on a segment attached to
+    /// this interface
+    fn source_for(&self, cidr: &Ipv4Cidr) -> Option<Ipv4Addr> {
+        self.addresses
+            .iter()
+            .find(|inet| {
+                inet.network_length() <= cidr.network_length()
+                    && inet.network().contains(&cidr.first_address())
+            })
+            .map(|inet| inet.address())
+    }
+}
+
+/// Raw access to the Ethernet segment behind an interface. Calls block, so scans run on a
+/// blocking thread.
+pub trait ArpTransport: Send {
+    /// Broadcast a who-has request for `target` from `source`
+    fn request(&mut self, source: Ipv4Addr, target: Ipv4Addr) -> Result<(), CheckError>;
+
+    /// Next ARP reply seen on the segment, or None when nothing arrived within a short poll
+    fn reply(&mut self) -> Result<Option<(Ipv4Addr, MacAddress)>, CheckError>;
+}
+
+/// A layer 2 channel on a real interface. Opening one needs raw socket privileges, e.g.
+/// CAP_NET_RAW on Linux.
+pub struct DatalinkTransport {
+    mac: MacAddr,
+    tx: Box<dyn DataLinkSender>,
+    rx: Box<dyn DataLinkReceiver>,
+}
+
+impl DatalinkTransport {
+    pub fn open(interface: &LocalInterface) -> Result<Self, CheckError> {
+        let (tx, rx) = open_ethernet(&interface.name, "ARP")?;
+
+        Ok(Self {
+            mac: MacAddr::from(interface.mac.bytes()),
+            tx,
+            rx,
+        })
+    }
+}
+
+/// Sending and receiving halves of a layer 2 channel
+pub(crate) type EthernetChannel = (Box<dyn DataLinkSender>, Box<dyn DataLinkReceiver>);
+
+/// Open a layer 2 channel on the interface named `name` for a `check` scan
+pub(crate) fn open_ethernet(
+    name: &str,
+    check: &'static str,
+) -> Result<EthernetChannel, CheckError> {
+    let network_interface = datalink::interfaces()
+        .into_iter()
+        .find(|i| i.name == name)
+        .ok_or_else(|| CheckError::Protocol {
+            target: name.to_string(),
+            reason: "interface no longer exists".to_string(),
+        })?;
+
+    let config = datalink::Config {
+        read_timeout: Some(READ_POLL),
+        ..Default::default()
+    };
+
+    match datalink::channel(&network_interface, config) {
+        Ok(Channel::Ethernet(tx, rx)) => Ok((tx, rx)),
+        Ok(_) => Err(CheckError::Protocol {
+            target: name.to_string(),
+            reason: "not an Ethernet interface".to_string(),
+        }),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            Err(CheckError::InsufficientPrivileges {
+                check,
+                reason: e.to_string(),
+            })
+        }
+        Err(e) => Err(CheckError::Protocol {
+            target: name.to_string(),
+            reason: format!("failed to open a raw socket: {}", e),
+        }),
+    }
+}
+
+impl ArpTransport for DatalinkTransport {
+    fn request(&mut self, source: Ipv4Addr, target: Ipv4Addr) -> Result<(), CheckError> {
+        let mut arp_buffer = [0u8; ARP_PACKET_LEN];
+        let mut ethernet_buffer = [0u8; ETHERNET_HEADER_LEN + ARP_PACKET_LEN];
+
+        // Both buffers are sized for their packets, so these can't fail
+        if let (Some(mut arp), Some(mut ethernet)) = (
+            MutableArpPacket::new(&mut arp_buffer),
+            MutableEthernetPacket::new(&mut ethernet_buffer),
+        ) {
+            arp.set_hardware_type(ArpHardwareTypes::Ethernet);
+            arp.set_protocol_type(EtherTypes::Ipv4);
+            arp.set_hw_addr_len(6);
+            arp.set_proto_addr_len(4);
+            arp.set_operation(ArpOperations::Request);
+            arp.set_sender_hw_addr(self.mac);
+            arp.set_sender_proto_addr(source);
+            arp.set_target_hw_addr(MacAddr::zero());
+            arp.set_target_proto_addr(target);
+
+            ethernet.set_destination(MacAddr::broadcast());
+            ethernet.set_source(self.mac);
+            ethernet.set_ethertype(EtherTypes::Arp);
+            ethernet.set_payload(arp.packet_mut());
+        }
+
+        match self.tx.send_to(&ethernet_buffer, None) {
+            Some(Err(e)) => Err(CheckError::Protocol {
+                target: target.to_string(),
+                reason: format!("failed to send ARP request: {}", e),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn reply(&mut self) -> Result<Option<(Ipv4Addr, MacAddress)>, CheckError> {
+        match self.rx.next() {
+            Ok(frame) => Ok(parse_reply(frame)),
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => Ok(None),
+            Err(e) => Err(CheckError::Protocol {
+                target: "ARP".to_string(),
+                reason: format!("failed to read from the segment: {}", e),
+            }),
+        }
+    }
+}
+
+fn parse_reply(frame: &[u8]) -> Option<(Ipv4Addr, MacAddress)> {
+    let ethernet = EthernetPacket::new(frame)?;
+    if ethernet.get_ethertype() != EtherTypes::Arp {
+        return None;
+    }
+
+    let arp = ArpPacket::new(ethernet.payload())?;
+    (arp.get_operation() == ArpOperations::Reply).then(|| {
+        (
+            arp.get_sender_proto_addr(),
+            MacAddress::new(arp.get_sender_hw_addr().octets()),
+        )
+    })
+}
+
+/// Non-loopback interfaces with a hardware address and at least one IPv4 address
+pub fn local_interfaces() -> Vec<LocalInterface> {
+    datalink::interfaces()
+        .into_iter()
+        .filter(|i| !i.is_loopback())
+        .filter_map(|i| {
+            let mac = i.mac.filter(|mac| *mac != MacAddr::zero())?;
+            let addresses: Vec<Ipv4Inet> = i
+                .ips
+                .iter()
+                .filter_map(|ip| match ip.ip() {
+                    IpAddr::V4(addr) => Ipv4Inet::new(addr, ip.prefix()).ok(),
+                    IpAddr::V6(_) => None,
+                })
+                .collect();
+
+            (!addresses.is_empty()).then(|| LocalInterface {
+                name: i.name,
+                mac: MacAddress::new(mac.octets()),
+                addresses,
+            })
+        })
+        .collect()
+}
+
+/// Whether this process can open raw sockets, reported to the server so it only asks for ARP
+/// scans the daemon can run
+pub fn raw_sockets_available() -> bool {
+    local_interfaces()
+        .first()
+        .is_some_and(|interface| DatalinkTransport::open(interface).is_ok())
+}
+
+/// Check `cidr` can be ARP scanned from `interface`, returning the address to send from
+fn scan_source(
+    interface: &LocalInterface,
+    cidr: &Ipv4Cidr,
+    options: &CheckOptions,
+) -> Result<Ipv4Addr, CheckError> {
+    // ARP never leaves the segment, so there's nothing a proxy could do with it
+    if options.proxy.is_some() {
+        return Err(CheckError::ProxyNotSupported { check: "ARP" });
+    }
+
+    let source = interface
+        .source_for(cidr)
+        .ok_or_else(|| CheckError::NotLocal {
+            target: cidr.to_string(),
+            interface: interface.name.clone(),
+        })?;
+
+    let targets = 1u64 << (32 - u32::from(cidr.network_length()));
+    if targets > MAX_ARP_TARGETS {
+        return Err(CheckError::TooManyTargets {
+            target: cidr.to_string(),
+            max: MAX_ARP_TARGETS,
+        });
+    }
+
+    Ok(source)
+}
+
+/// Send an ARP request for every address in `cidr` out of `interface` and collect the replies.
+/// `cidr` must be on a segment the interface is attached to.
+pub async fn arp_scan(
+    interface: &str,
+    cidr: Ipv4Cidr,
+    options: &CheckOptions,
+) -> Result<ArpScanResult, CheckError> {
+    let interface = local_interfaces()
+        .into_iter()
+        .find(|i| i.name == interface)
+        .ok_or_else(|| CheckError::NotLocal {
+            target: cidr.to_string(),
+            interface: interface.to_string(),
+        })?;
+
+    // Reject a bad range before asking for privileges it won't need
+    scan_source(&interface, &cidr, options)?;
+
+    let options = options.clone();
+    spawn_blocking(move || {
+        let mut transport = DatalinkTransport::open(&interface)?;
+        arp_scan_with(&mut transport, &interface, cidr, &options)
+    })
+    .await
+    .map_err(|e| CheckError::Protocol {
+        target: cidr.to_string(),
+        reason: e.to_string(),
+    })?
+}
+
+/// Scan `cidr` over `transport`. Replies are collected until `options.timeout` after the last
+/// request; replies for addresses outside `cidr` are ignored.
+pub fn arp_scan_with(
+    transport: &mut dyn ArpTransport,
+    interface: &LocalInterface,
+    cidr: Ipv4Cidr,
+    options: &CheckOptions,
+) -> Result<ArpScanResult, CheckError> {
+    let source = scan_source(interface, &cidr, options)?;
+
+    let mut probed = 0;
+    for target in cidr.iter().addresses().filter(|ip| *ip != source) {
+        transport.request(source, target)?;
+        probed += 1;
+    }
+
+    let mut seen: BTreeMap<MacAddress, BTreeSet<Ipv4Addr>> = BTreeMap::new();
+    let deadline = Instant::now() + options.timeout;
+    while Instant::now() < deadline {
+        if let Some((ip, mac)) = transport.reply()?
+            && ip != source
+            && cidr.contains(&ip)
+        {
+            seen.entry(mac).or_default().insert(ip);
+        }
+    }
+
+    let mut responders: Vec<ArpResponder> = seen
+        .into_iter()
+        .map(|(mac, ips)| ArpResponder {
+            mac,
+            multiple_ips: ips.len() > 1,
+            ips: ips.into_iter().collect(),
+        })
+        .collect();
+    responders.sort_by_key(|r| r.ips.first().copied());
+
+    Ok(ArpScanResult {
+        interface: interface.name.clone(),
+        cidr,
+        probed,
+        responders,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::VecDeque, thread::sleep};
+
+    /// An Ethernet segment where each host answers requests for the addresses it owns
+    struct SimulatedSegment {
+        hosts: Vec<(MacAddress, Vec<Ipv4Addr>)>,
+        replies: VecDeque<(Ipv4Addr, MacAddress)>,
+        requested: Vec<Ipv4Addr>,
+    }
+
+    impl SimulatedSegment {
+        fn new(hosts: Vec<(MacAddress, Vec<Ipv4Addr>)>) -> Self {
+            Self {
+                hosts,
+                replies: VecDeque::new(),
+                requested: Vec::new(),
+            }
+        }
+    }
+
+    impl ArpTransport for SimulatedSegment {
+        fn request(&mut self, _source: Ipv4Addr, target: Ipv4Addr) -> Result<(), CheckError> {
+            self.requested.push(target);
+            if let Some((mac, _)) = self.hosts.iter().find(|(_, ips)| ips.contains(&target)) {
+                self.replies.push_back((target, *mac));
+            }
+            Ok(())
+        }
+
+        fn reply(&mut self) -> Result<Option<(Ipv4Addr, MacAddress)>, CheckError> {
+            let reply = self.replies.pop_front();
+            if reply.is_none() {
+                sleep(Duration::from_millis(1));
+            }
+            Ok(reply)
+        }
+    }
+
+    fn ip(s: &str) -> Ipv4Addr {
+        s.parse().unwrap()
+    }
+
+    fn mac(last: u8) -> MacAddress {
+        MacAddress::new([0x02, 0, 0, 0, 0, last])
+    }
+
+    fn eth0() -> LocalInterface {
+        LocalInterface {
+            name: "eth0".to_string(),
+            mac: mac(1),
+            addresses: vec!["192.168.1.5/24".parse().unwrap()],
+        }
+    }
+
+    fn options() -> CheckOptions {
+        CheckOptions::new(Duration::from_millis(20))
+    }
+
+    #[test]
+    fn test_responders_captured_with_macs() {
+        let mut segment = SimulatedSegment::new(vec![
+            (mac(0x10), vec![ip("192.168.1.10")]),
+            (mac(0x20), vec![ip("192.168.1.21"), ip("192.168.1.20")]),
+            // Answers on the segment, but outside the scanned range
+            (mac(0x30), vec![ip("192.168.1.200")]),
+        ]);
+
+        let result = arp_scan_with(
+            &mut segment,
+            &eth0(),
+            "192.168.1.0/27".parse().unwrap(),
+            &options(),
+        )
+        .unwrap();
+
+        // Every address but the daemon's own
+        assert_eq!(result.probed, 31);
+        assert!(!segment.requested.contains(&ip("192.168.1.5")));
+
+        assert_eq!(
+            result.responders,
+            vec![
+                ArpResponder {
+                    mac: mac(0x10),
+                    ips: vec![ip("192.168.1.10")],
+                    multiple_ips: false,
+                },
+                ArpResponder {
+                    mac: mac(0x20),
+                    ips: vec![ip("192.168.1.20"), ip("192.168.1.21")],
+                    multiple_ips: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_non_local_cidr_rejected() {
+        let mut segment = SimulatedSegment::new(Vec::new());
+
+        for cidr in ["10.0.0.0/24", "192.168.0.0/16"] {
+            let err = arp_scan_with(&mut segment, &eth0(), cidr.parse().unwrap(), &options())
+                .unwrap_err();
+            assert!(matches!(err, CheckError::NotLocal { .. }), "{}", cidr);
+        }
+        assert!(segment.requested.is_empty());
+    }
+}
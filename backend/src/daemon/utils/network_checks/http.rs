@@ -0,0 +1,98 @@
+use std::time::Instant;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    time::timeout,
+};
+use url::Url;
+
+use crate::daemon::utils::network_checks::{
+    proxy::open_stream,
+    tls::handshake,
+    types::{CheckError, CheckOptions, CheckOutcome, display_target},
+};
+
+/// Check that `url` answers an HTTP(S) GET. Any status counts as reachable; the status is
+/// returned in the outcome's detail for the caller to judge.
+pub async fn http_check(url: &Url, options: &CheckOptions) -> Result<CheckOutcome, CheckError> {
+    let host = url
+        .host_str()
+        .unwrap_or_default()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = url.port_or_known_default().unwrap_or(80);
+    let target = display_target(&host, port);
+
+    if host.is_empty() || !matches!(url.scheme(), "http" | "https") {
+        return Err(CheckError::Protocol {
+            target,
+            reason: format!("unsupported URL '{}'", url),
+        });
+    }
+
+    let started = Instant::now();
+
+    let status = timeout(options.timeout, async {
+        let stream = open_stream(&host, port, options).await?;
+        if url.scheme() == "https" {
+            let stream = handshake(stream, &host, port).await?;
+            request_status(stream, url, &target).await
+        } else {
+            request_status(stream, url, &target).await
+        }
+    })
+    .await
+    .map_err(|_| CheckError::Timeout(options.timeout))??;
+
+    Ok(CheckOutcome {
+        target,
+        latency_ms: started.elapsed().as_millis() as u64,
+        proxied: options.proxy.is_some(),
+        detail: Some(status.to_string()),
+        source: None,
+        expires_at: None,
+    })
+}
+
+async fn request_status<S>(stream: S, url: &Url, target: &str) -> Result<u16, CheckError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let protocol_error = |reason: String| CheckError::Protocol {
+        target: target.to_string(),
+        reason,
+    };
+
+    let mut path = url.path().to_string();
+    if let Some(query) = url.query() {
+        path.push('?');
+        path.push_str(query);
+    }
+    let host_header = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+
+    let request = format!(
+        "GET {path} HTTP/1.1\r\nHost: {host_header}\r\nUser-Agent: netvisor-daemon\r\nConnection: close\r\n\r\n"
+    );
+
+    let mut stream = BufReader::new(stream);
+    stream
+        .get_mut()
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| protocol_error(e.to_string()))?;
+
+    let mut status_line = String::new();
+    stream
+        .read_line(&mut status_line)
+        .await
+        .map_err(|e| protocol_error(e.to_string()))?;
+
+    status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or_else(|| protocol_error(format!("malformed status line '{}'", status_line.trim())))
+}
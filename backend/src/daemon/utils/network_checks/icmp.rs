@@ -0,0 +1,63 @@
+use std::{net::IpAddr, time::Instant};
+use tokio::{process::Command, time::timeout};
+
+use crate::daemon::utils::network_checks::types::{CheckError, CheckOptions, CheckOutcome};
+
+/// Whether the system `ping` ICMP checks run is on the `PATH`
+pub fn ping_available() -> bool {
+    let binary = if cfg!(target_os = "windows") {
+        "ping.exe"
+    } else {
+        "ping"
+    };
+
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(binary).is_file()))
+}
+
+/// Check that `ip` answers an ICMP echo, using the system `ping` so no raw socket privileges are
+/// needed. ICMP can't be tunnelled through a TCP proxy, so a configured proxy is an error rather
+/// than silently ignored.
+pub async fn icmp_check(ip: IpAddr, options: &CheckOptions) -> Result<CheckOutcome, CheckError> {
+    if options.proxy.is_some() {
+        return Err(CheckError::ProxyNotSupported { check: "ICMP" });
+    }
+
+    let wait_secs = options.timeout.as_secs().max(1).to_string();
+    let mut command = Command::new("ping");
+    if cfg!(target_os = "windows") {
+        let wait_ms = options.timeout.as_millis().max(1).to_string();
+        command.args(["-n", "1", "-w", &wait_ms]);
+    } else if cfg!(target_os = "macos") {
+        command.args(["-c", "1", "-t", &wait_secs]);
+    } else {
+        command.args(["-c", "1", "-W", &wait_secs]);
+    }
+    command.arg(ip.to_string()).kill_on_drop(true);
+
+    let started = Instant::now();
+
+    let output = timeout(options.timeout, command.output())
+        .await
+        .map_err(|_| CheckError::Timeout(options.timeout))?
+        .map_err(|e| CheckError::Protocol {
+            target: ip.to_string(),
+            reason: format!("failed to run ping: {}", e),
+        })?;
+
+    if !output.status.success() {
+        return Err(CheckError::TargetUnreachable {
+            target: ip.to_string(),
+            reason: "no echo reply".to_string(),
+        });
+    }
+
+    Ok(CheckOutcome {
+        target: ip.to_string(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        proxied: false,
+        detail: None,
+        source: None,
+        expires_at: None,
+    })
+}
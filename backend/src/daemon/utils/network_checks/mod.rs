@@ -0,0 +1,11 @@
+pub mod arp;
+pub mod http;
+pub mod icmp;
+pub mod ndp;
+pub mod proxy;
+pub mod reverse_dns;
+pub mod suite;
+pub mod tcp;
+pub mod tls;
+pub mod types;
+pub mod whois;
@@ -0,0 +1,519 @@
+use cidr::{Ipv6Cidr, Ipv6Inet};
+use mac_address::MacAddress;
+use pnet::{
+    datalink::{self, DataLinkReceiver, DataLinkSender},
+    packet::{
+        MutablePacket, Packet,
+        ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket},
+        icmpv6::{
+            self, Icmpv6Packet, Icmpv6Types, MutableIcmpv6Packet,
+            ndp::{NeighborAdvertFlags, NeighborAdvertPacket},
+        },
+        ip::IpNextHeaderProtocols,
+        ipv6::{Ipv6Packet, MutableIpv6Packet},
+    },
+    util::MacAddr,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    net::{IpAddr, Ipv6Addr},
+    time::Instant,
+};
+use tokio::task::spawn_blocking;
+
+use crate::daemon::utils::network_checks::{
+    arp::open_ethernet,
+    types::{CheckError, CheckOptions},
+};
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const IPV6_HEADER_LEN: usize = 40;
+const ECHO_REQUEST_LEN: usize = 8;
+
+/// Every node on the link listens on this group
+const ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+/// Ethernet address IPv6 multicast to `ALL_NODES` is sent to
+const ALL_NODES_MAC: MacAddr = MacAddr(0x33, 0x33, 0, 0, 0, 1);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Ipv6Scope {
+    /// fe80::/10, only meaningful on the segment it was seen on
+    LinkLocal,
+    /// fc00::/7, routable within a site but not on the internet
+    UniqueLocal,
+    Global,
+}
+
+impl From<&Ipv6Addr> for Ipv6Scope {
+    fn from(ip: &Ipv6Addr) -> Self {
+        if ip.is_unicast_link_local() {
+            Ipv6Scope::LinkLocal
+        } else if ip.is_unique_local() {
+            Ipv6Scope::UniqueLocal
+        } else {
+            Ipv6Scope::Global
+        }
+    }
+}
+
+impl std::fmt::Display for Ipv6Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ipv6Scope::LinkLocal => write!(f, "link-local"),
+            Ipv6Scope::UniqueLocal => write!(f, "unique-local"),
+            Ipv6Scope::Global => write!(f, "global"),
+        }
+    }
+}
+
+/// An IPv6 address seen on the segment, with the link-layer address that answered for it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NdpNeighbor {
+    pub ip: Ipv6Addr,
+    pub mac: MacAddress,
+    pub scope: Ipv6Scope,
+    /// Whether the neighbor advertised itself as a router
+    pub router: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NdpScanResult {
+    pub interface: String,
+    /// Prefix non-link-local neighbors were limited to, if any
+    pub prefix: Option<Ipv6Cidr>,
+    pub neighbors: Vec<NdpNeighbor>,
+}
+
+/// An interface the daemon can run NDP scans from
+#[derive(Debug, Clone)]
+pub struct LocalIpv6Interface {
+    pub name: String,
+    pub mac: MacAddress,
+    pub addresses: Vec<Ipv6Inet>,
+}
+
+/// A neighbor heard from on the segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NeighborSighting {
+    pub ip: Ipv6Addr,
+    pub mac: MacAddress,
+    pub router: bool,
+}
+
+/// Raw access to the Ethernet segment behind an interface. Calls block, so scans run on a
+/// blocking thread.
+pub trait NdpTransport: Send {
+    /// Send an echo request from `source` to every node on the link. Nodes answer from an address
+    /// of the same scope, so soliciting from each of the interface's addresses finds link-local
+    /// and global addresses alike.
+    fn solicit(&mut self, source: Ipv6Addr) -> Result<(), CheckError>;
+
+    /// Next neighbor heard from on the segment, or None when nothing arrived within a short poll
+    fn sighting(&mut self) -> Result<Option<NeighborSighting>, CheckError>;
+}
+
+/// A layer 2 channel on a real interface. Opening one needs raw socket privileges, e.g.
+/// CAP_NET_RAW on Linux.
+pub struct DatalinkNdpTransport {
+    mac: MacAddr,
+    tx: Box<dyn DataLinkSender>,
+    rx: Box<dyn DataLinkReceiver>,
+}
+
+impl DatalinkNdpTransport {
+    pub fn open(interface: &LocalIpv6Interface) -> Result<Self, CheckError> {
+        let (tx, rx) = open_ethernet(&interface.name, "NDP")?;
+
+        Ok(Self {
+            mac: MacAddr::from(interface.mac.bytes()),
+            tx,
+            rx,
+        })
+    }
+}
+
+impl NdpTransport for DatalinkNdpTransport {
+    fn solicit(&mut self, source: Ipv6Addr) -> Result<(), CheckError> {
+        let mut echo_buffer = [0u8; ECHO_REQUEST_LEN];
+        let mut ipv6_buffer = [0u8; IPV6_HEADER_LEN + ECHO_REQUEST_LEN];
+        let mut ethernet_buffer = [0u8; ETHERNET_HEADER_LEN + IPV6_HEADER_LEN + ECHO_REQUEST_LEN];
+
+        // Every buffer is sized for its packet, so these can't fail
+        if let (Some(mut echo), Some(mut ipv6), Some(mut ethernet)) = (
+            MutableIcmpv6Packet::new(&mut echo_buffer),
+            MutableIpv6Packet::new(&mut ipv6_buffer),
+            MutableEthernetPacket::new(&mut ethernet_buffer),
+        ) {
+            // Identifier and sequence number are left zero; replies are matched by sender
+            echo.set_icmpv6_type(Icmpv6Types::EchoRequest);
+            echo.set_checksum(icmpv6::checksum(&echo.to_immutable(), &source, &ALL_NODES));
+
+            ipv6.set_version(6);
+            ipv6.set_payload_length(ECHO_REQUEST_LEN as u16);
+            ipv6.set_next_header(IpNextHeaderProtocols::Icmpv6);
+            ipv6.set_hop_limit(255);
+            ipv6.set_source(source);
+            ipv6.set_destination(ALL_NODES);
+            ipv6.set_payload(echo.packet_mut());
+
+            ethernet.set_destination(ALL_NODES_MAC);
+            ethernet.set_source(self.mac);
+            ethernet.set_ethertype(EtherTypes::Ipv6);
+            ethernet.set_payload(ipv6.packet_mut());
+        }
+
+        match self.tx.send_to(&ethernet_buffer, None) {
+            Some(Err(e)) => Err(CheckError::Protocol {
+                target: ALL_NODES.to_string(),
+                reason: format!("failed to send echo request: {}", e),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn sighting(&mut self) -> Result<Option<NeighborSighting>, CheckError> {
+        match self.rx.next() {
+            Ok(frame) => Ok(parse_sighting(frame)),
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => Ok(None),
+            Err(e) => Err(CheckError::Protocol {
+                target: "NDP".to_string(),
+                reason: format!("failed to read from the segment: {}", e),
+            }),
+        }
+    }
+}
+
+/// The neighbor behind an echo reply, neighbor advertisement or router advertisement
+fn parse_sighting(frame: &[u8]) -> Option<NeighborSighting> {
+    let ethernet = EthernetPacket::new(frame)?;
+    if ethernet.get_ethertype() != EtherTypes::Ipv6 {
+        return None;
+    }
+
+    let ipv6 = Ipv6Packet::new(ethernet.payload())?;
+    if ipv6.get_next_header() != IpNextHeaderProtocols::Icmpv6 {
+        return None;
+    }
+
+    let icmpv6 = Icmpv6Packet::new(ipv6.payload())?;
+    let (ip, router) = match icmpv6.get_icmpv6_type() {
+        Icmpv6Types::EchoReply => (ipv6.get_source(), false),
+        Icmpv6Types::RouterAdvert => (ipv6.get_source(), true),
+        Icmpv6Types::NeighborAdvert => {
+            let advert = NeighborAdvertPacket::new(ipv6.payload())?;
+            (
+                advert.get_target_addr(),
+                advert.get_flags() & NeighborAdvertFlags::Router != 0,
+            )
+        }
+        _ => return None,
+    };
+
+    Some(NeighborSighting {
+        ip,
+        mac: MacAddress::new(ethernet.get_source().octets()),
+        router,
+    })
+}
+
+/// Non-loopback interfaces with a hardware address and at least one IPv6 address
+pub fn local_ipv6_interfaces() -> Vec<LocalIpv6Interface> {
+    datalink::interfaces()
+        .into_iter()
+        .filter(|i| !i.is_loopback())
+        .filter_map(|i| {
+            let mac = i.mac.filter(|mac| *mac != MacAddr::zero())?;
+            let addresses: Vec<Ipv6Inet> = i
+                .ips
+                .iter()
+                .filter_map(|ip| match ip.ip() {
+                    IpAddr::V6(addr) => Ipv6Inet::new(addr, ip.prefix()).ok(),
+                    IpAddr::V4(_) => None,
+                })
+                .collect();
+
+            (!addresses.is_empty()).then(|| LocalIpv6Interface {
+                name: i.name,
+                mac: MacAddress::new(mac.octets()),
+                addresses,
+            })
+        })
+        .collect()
+}
+
+/// Check `interface` can NDP scan `prefix`, returning the addresses to solicit from: its
+/// link-local address, and its other addresses within `prefix` when given
+fn scan_sources(
+    interface: &LocalIpv6Interface,
+    prefix: Option<&Ipv6Cidr>,
+    options: &CheckOptions,
+) -> Result<Vec<Ipv6Addr>, CheckError> {
+    // NDP never leaves the segment, so there's nothing a proxy could do with it
+    if options.proxy.is_some() {
+        return Err(CheckError::ProxyNotSupported { check: "NDP" });
+    }
+
+    let link_local = interface
+        .addresses
+        .iter()
+        .map(|inet| inet.address())
+        .find(Ipv6Addr::is_unicast_link_local)
+        .ok_or_else(|| CheckError::Protocol {
+            target: interface.name.clone(),
+            reason: "interface has no link-local address to run NDP from".to_string(),
+        })?;
+
+    if let Some(prefix) = prefix {
+        let on_link = interface.addresses.iter().any(|inet| {
+            inet.network_length() <= prefix.network_length()
+                && inet.network().contains(&prefix.first_address())
+        });
+        if !on_link {
+            return Err(CheckError::NotLocal {
+                target: prefix.to_string(),
+                interface: interface.name.clone(),
+            });
+        }
+    }
+
+    let mut sources = vec![link_local];
+    sources.extend(
+        interface
+            .addresses
+            .iter()
+            .map(|inet| inet.address())
+            .filter(|ip| !ip.is_unicast_link_local())
+            .filter(|ip| prefix.is_none_or(|prefix| prefix.contains(ip))),
+    );
+
+    Ok(sources)
+}
+
+/// Find the IPv6 neighbors on the segment `interface` is attached to. Link-local neighbors are
+/// always reported; other neighbors only within `prefix` when given, which must be on-link.
+pub async fn ndp_scan(
+    interface: &str,
+    prefix: Option<Ipv6Cidr>,
+    options: &CheckOptions,
+) -> Result<NdpScanResult, CheckError> {
+    let interface = local_ipv6_interfaces()
+        .into_iter()
+        .find(|i| i.name == interface)
+        .ok_or_else(|| CheckError::NotLocal {
+            target: prefix.map_or_else(|| "IPv6".to_string(), |p| p.to_string()),
+            interface: interface.to_string(),
+        })?;
+
+    // Reject a bad scope before asking for privileges it won't need
+    scan_sources(&interface, prefix.as_ref(), options)?;
+
+    let options = options.clone();
+    spawn_blocking(move || {
+        let mut transport = DatalinkNdpTransport::open(&interface)?;
+        ndp_scan_with(&mut transport, &interface, prefix, &options)
+    })
+    .await
+    .map_err(|e| CheckError::Protocol {
+        target: "NDP".to_string(),
+        reason: e.to_string(),
+    })?
+}
+
+/// Scan over `transport`, collecting neighbors until `options.timeout` after soliciting.
+/// Sightings of the interface's own addresses are ignored.
+pub fn ndp_scan_with(
+    transport: &mut dyn NdpTransport,
+    interface: &LocalIpv6Interface,
+    prefix: Option<Ipv6Cidr>,
+    options: &CheckOptions,
+) -> Result<NdpScanResult, CheckError> {
+    let sources = scan_sources(interface, prefix.as_ref(), options)?;
+    for source in &sources {
+        transport.solicit(*source)?;
+    }
+
+    let own: Vec<Ipv6Addr> = interface.addresses.iter().map(|i| i.address()).collect();
+    let mut seen: BTreeMap<(MacAddress, Ipv6Addr), bool> = BTreeMap::new();
+    let deadline = Instant::now() + options.timeout;
+    while Instant::now() < deadline {
+        if let Some(sighting) = transport.sighting()?
+            && !own.contains(&sighting.ip)
+            && !sighting.ip.is_multicast()
+            && !sighting.ip.is_unspecified()
+            && (sighting.ip.is_unicast_link_local()
+                || prefix.is_none_or(|prefix| prefix.contains(&sighting.ip)))
+        {
+            *seen.entry((sighting.mac, sighting.ip)).or_default() |= sighting.router;
+        }
+    }
+
+    let mut neighbors: Vec<NdpNeighbor> = seen
+        .into_iter()
+        .map(|((mac, ip), router)| NdpNeighbor {
+            ip,
+            mac,
+            scope: Ipv6Scope::from(&ip),
+            router,
+        })
+        .collect();
+    neighbors.sort_by_key(|n| (n.mac, n.scope, n.ip));
+
+    Ok(NdpScanResult {
+        interface: interface.name.clone(),
+        prefix,
+        neighbors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::VecDeque, thread::sleep, time::Duration};
+
+    /// An Ethernet segment where each host answers a solicitation from each of its addresses of
+    /// the same scope as the solicitation's source
+    struct SimulatedSegment {
+        hosts: Vec<(MacAddress, Vec<Ipv6Addr>, bool)>,
+        sightings: VecDeque<NeighborSighting>,
+        solicited_from: Vec<Ipv6Addr>,
+    }
+
+    impl SimulatedSegment {
+        fn new(hosts: Vec<(MacAddress, Vec<Ipv6Addr>, bool)>) -> Self {
+            Self {
+                hosts,
+                sightings: VecDeque::new(),
+                solicited_from: Vec::new(),
+            }
+        }
+    }
+
+    impl NdpTransport for SimulatedSegment {
+        fn solicit(&mut self, source: Ipv6Addr) -> Result<(), CheckError> {
+            self.solicited_from.push(source);
+            for (mac, ips, router) in &self.hosts {
+                for ip in ips
+                    .iter()
+                    .filter(|ip| ip.is_unicast_link_local() == source.is_unicast_link_local())
+                {
+                    self.sightings.push_back(NeighborSighting {
+                        ip: *ip,
+                        mac: *mac,
+                        router: *router,
+                    });
+                }
+            }
+            Ok(())
+        }
+
+        fn sighting(&mut self) -> Result<Option<NeighborSighting>, CheckError> {
+            let sighting = self.sightings.pop_front();
+            if sighting.is_none() {
+                sleep(Duration::from_millis(1));
+            }
+            Ok(sighting)
+        }
+    }
+
+    fn ip(s: &str) -> Ipv6Addr {
+        s.parse().unwrap()
+    }
+
+    fn mac(last: u8) -> MacAddress {
+        MacAddress::new([0x02, 0, 0, 0, 0, last])
+    }
+
+    fn eth0() -> LocalIpv6Interface {
+        LocalIpv6Interface {
+            name: "eth0".to_string(),
+            mac: mac(1),
+            addresses: vec![
+                "fe80::1/64".parse().unwrap(),
+                "2001:db8:1::1/64".parse().unwrap(),
+            ],
+        }
+    }
+
+    fn options() -> CheckOptions {
+        CheckOptions::new(Duration::from_millis(20))
+    }
+
+    fn segment() -> SimulatedSegment {
+        SimulatedSegment::new(vec![
+            (
+                mac(0x10),
+                vec![ip("fe80::10"), ip("2001:db8:1::10"), ip("2001:db8:1::11")],
+                false,
+            ),
+            (mac(0x20), vec![ip("fe80::20"), ip("2001:db8:9::20")], true),
+            // The daemon hears its own solicitations
+            (mac(1), vec![ip("fe80::1"), ip("2001:db8:1::1")], false),
+        ])
+    }
+
+    #[test]
+    fn test_neighbors_captured_with_link_layer_addresses() {
+        let mut segment = segment();
+
+        let result = ndp_scan_with(
+            &mut segment,
+            &eth0(),
+            Some("2001:db8:1::/64".parse().unwrap()),
+            &options(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            segment.solicited_from,
+            vec![ip("fe80::1"), ip("2001:db8:1::1")]
+        );
+        let neighbor = |ip: Ipv6Addr, mac, scope, router| NdpNeighbor {
+            ip,
+            mac,
+            scope,
+            router,
+        };
+        assert_eq!(
+            result.neighbors,
+            vec![
+                neighbor(ip("fe80::10"), mac(0x10), Ipv6Scope::LinkLocal, false),
+                neighbor(ip("2001:db8:1::10"), mac(0x10), Ipv6Scope::Global, false),
+                neighbor(ip("2001:db8:1::11"), mac(0x10), Ipv6Scope::Global, false),
+                // Its global address is outside the prefix scanned
+                neighbor(ip("fe80::20"), mac(0x20), Ipv6Scope::LinkLocal, true),
+            ]
+        );
+
+        // Without a prefix every neighbor is reported
+        let result = ndp_scan_with(&mut segment, &eth0(), None, &options()).unwrap();
+        assert_eq!(result.neighbors.len(), 5);
+    }
+
+    #[test]
+    fn test_non_local_prefix_rejected() {
+        let mut segment = segment();
+
+        for prefix in ["2001:db8:2::/64", "2001:db8::/32"] {
+            let err =
+                ndp_scan_with(&mut segment, &eth0(), prefix.parse().ok(), &options()).unwrap_err();
+            assert!(matches!(err, CheckError::NotLocal { .. }), "{}", prefix);
+        }
+
+        // NDP runs from the link-local address, so an interface without one can't scan
+        let mut global_only = eth0();
+        global_only.addresses.remove(0);
+        assert!(ndp_scan_with(&mut segment, &global_only, None, &options()).is_err());
+        assert!(segment.solicited_from.is_empty());
+    }
+
+    #[test]
+    fn test_scope_labels() {
+        assert_eq!(Ipv6Scope::from(&ip("fe80::1")), Ipv6Scope::LinkLocal);
+        assert_eq!(Ipv6Scope::from(&ip("fd00::1")), Ipv6Scope::UniqueLocal);
+        assert_eq!(Ipv6Scope::from(&ip("2001:db8::1")), Ipv6Scope::Global);
+    }
+}
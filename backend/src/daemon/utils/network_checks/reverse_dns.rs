@@ -0,0 +1,231 @@
+use async_trait::async_trait;
+use dns_lookup::{LookupErrorKind, getnameinfo, lookup_host};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use tokio::{task::spawn_blocking, time::timeout};
+
+use crate::daemon::utils::network_checks::types::{CheckError, CheckOptions};
+
+/// Outcome of forward-confirming a PTR name
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Fcrdns {
+    /// The PTR name resolves back to the original IP
+    Pass,
+    /// The PTR name resolves only to other IPs, or not at all
+    Fail,
+    /// Not requested, or there was no PTR name to confirm
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReverseDnsResult {
+    pub ip: IpAddr,
+    /// PTR name without the trailing dot; None when the IP has no PTR record
+    pub ptr: Option<String>,
+    pub fcrdns: Fcrdns,
+    /// Addresses the PTR name resolved to, when forward confirmation ran
+    pub forward_ips: Vec<IpAddr>,
+}
+
+/// Name resolution behind the reverse DNS check
+#[async_trait]
+pub trait Resolver: Send + Sync {
+    /// PTR name for `ip`, or None when it has none
+    async fn reverse(&self, ip: IpAddr) -> Result<Option<String>, CheckError>;
+
+    /// A/AAAA records for `name`, empty when it doesn't resolve
+    async fn forward(&self, name: &str) -> Result<Vec<IpAddr>, CheckError>;
+}
+
+/// The operating system's resolver, so results match what the daemon host itself sees
+pub struct SystemResolver;
+
+#[async_trait]
+impl Resolver for SystemResolver {
+    async fn reverse(&self, ip: IpAddr) -> Result<Option<String>, CheckError> {
+        let result = spawn_blocking(move || getnameinfo(&SocketAddr::new(ip, 0), 0))
+            .await
+            .map_err(|e| lookup_error(&ip.to_string(), e.to_string()))?;
+
+        match result {
+            // Without a PTR record getnameinfo falls back to the numeric address
+            Ok((name, _)) if name.parse::<IpAddr>().is_ok() => Ok(None),
+            Ok((name, _)) => Ok(Some(name)),
+            Err(e) if matches!(e.kind(), LookupErrorKind::NoName | LookupErrorKind::NoData) => {
+                Ok(None)
+            }
+            Err(e) => Err(lookup_error(&ip.to_string(), format!("{:?}", e.kind()))),
+        }
+    }
+
+    async fn forward(&self, name: &str) -> Result<Vec<IpAddr>, CheckError> {
+        let host = name.to_string();
+        let result = spawn_blocking(move || lookup_host(&host))
+            .await
+            .map_err(|e| lookup_error(name, e.to_string()))?;
+
+        // A name that doesn't resolve simply fails confirmation
+        Ok(result.map(|ips| ips.collect()).unwrap_or_default())
+    }
+}
+
+fn lookup_error(target: &str, reason: String) -> CheckError {
+    CheckError::Protocol {
+        target: target.to_string(),
+        reason: format!("DNS lookup failed: {}", reason),
+    }
+}
+
+/// Look up the PTR name for `ip` using the system resolver, optionally forward-confirming it
+/// (FCrDNS). An IP without a PTR record is a normal result, not an error.
+pub async fn reverse_dns(
+    ip: IpAddr,
+    confirm: bool,
+    options: &CheckOptions,
+) -> Result<ReverseDnsResult, CheckError> {
+    reverse_dns_with(&SystemResolver, ip, confirm, options).await
+}
+
+pub async fn reverse_dns_with(
+    resolver: &dyn Resolver,
+    ip: IpAddr,
+    confirm: bool,
+    options: &CheckOptions,
+) -> Result<ReverseDnsResult, CheckError> {
+    // Lookups go through the system resolver, which can't be pointed at a proxy
+    if options.proxy.is_some() {
+        return Err(CheckError::ProxyNotSupported {
+            check: "Reverse DNS",
+        });
+    }
+
+    timeout(options.timeout, async {
+        let ptr = resolver
+            .reverse(ip)
+            .await?
+            .map(|name| name.trim_end_matches('.').to_string())
+            .filter(|name| !name.is_empty());
+
+        let Some(name) = ptr.as_deref().filter(|_| confirm) else {
+            return Ok(ReverseDnsResult {
+                ip,
+                ptr,
+                fcrdns: Fcrdns::Skipped,
+                forward_ips: Vec::new(),
+            });
+        };
+
+        let forward_ips = resolver.forward(name).await?;
+        let fcrdns = if forward_ips.contains(&ip) {
+            Fcrdns::Pass
+        } else {
+            Fcrdns::Fail
+        };
+
+        Ok(ReverseDnsResult {
+            ip,
+            ptr,
+            fcrdns,
+            forward_ips,
+        })
+    })
+    .await
+    .map_err(|_| CheckError::Timeout(options.timeout))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Static zone data standing in for DNS
+    #[derive(Default)]
+    struct FakeResolver {
+        ptr: HashMap<IpAddr, String>,
+        a: HashMap<String, Vec<IpAddr>>,
+    }
+
+    #[async_trait]
+    impl Resolver for FakeResolver {
+        async fn reverse(&self, ip: IpAddr) -> Result<Option<String>, CheckError> {
+            Ok(self.ptr.get(&ip).cloned())
+        }
+
+        async fn forward(&self, name: &str) -> Result<Vec<IpAddr>, CheckError> {
+            Ok(self.a.get(name).cloned().unwrap_or_default())
+        }
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn resolver() -> FakeResolver {
+        let mut resolver = FakeResolver::default();
+        resolver
+            .ptr
+            .insert(ip("192.168.1.10"), "nas.lan.example.".to_string());
+        resolver.a.insert(
+            "nas.lan.example".to_string(),
+            vec![ip("192.168.1.10"), ip("fd00::10")],
+        );
+        resolver
+            .ptr
+            .insert(ip("192.168.1.20"), "spoofed.example.".to_string());
+        resolver
+            .a
+            .insert("spoofed.example".to_string(), vec![ip("203.0.113.5")]);
+        resolver
+    }
+
+    #[tokio::test]
+    async fn test_matching_ptr_passes_fcrdns() {
+        let result = reverse_dns_with(
+            &resolver(),
+            ip("192.168.1.10"),
+            true,
+            &CheckOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.ptr.as_deref(), Some("nas.lan.example"));
+        assert_eq!(result.fcrdns, Fcrdns::Pass);
+        assert!(result.forward_ips.contains(&ip("192.168.1.10")));
+    }
+
+    #[tokio::test]
+    async fn test_missing_ptr_is_not_an_error() {
+        let result = reverse_dns_with(
+            &resolver(),
+            ip("192.168.1.99"),
+            true,
+            &CheckOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.ptr, None);
+        assert_eq!(result.fcrdns, Fcrdns::Skipped);
+    }
+
+    #[tokio::test]
+    async fn test_ptr_resolving_elsewhere_fails_fcrdns() {
+        let options = CheckOptions::default();
+        let resolver = resolver();
+
+        let result = reverse_dns_with(&resolver, ip("192.168.1.20"), true, &options)
+            .await
+            .unwrap();
+        assert_eq!(result.ptr.as_deref(), Some("spoofed.example"));
+        assert_eq!(result.fcrdns, Fcrdns::Fail);
+
+        // Without confirmation the PTR is reported as-is
+        let result = reverse_dns_with(&resolver, ip("192.168.1.20"), false, &options)
+            .await
+            .unwrap();
+        assert_eq!(result.fcrdns, Fcrdns::Skipped);
+        assert!(result.forward_ips.is_empty());
+    }
+}
@@ -0,0 +1,545 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, stream::FuturesUnordered};
+use serde::{Deserialize, Serialize};
+use std::{net::IpAddr, time::Instant};
+use url::Url;
+
+use crate::{
+    daemon::utils::network_checks::{
+        http::http_check,
+        icmp::{icmp_check, ping_available},
+        reverse_dns::{Fcrdns, reverse_dns},
+        tcp::tcp_connect,
+        tls::tls_check,
+        types::{CheckError, CheckOptions, CheckOutcome, display_target},
+    },
+    server::diagnostics::r#impl::base::DiagnosticKind,
+};
+
+pub const MAX_SUITE_CHECKS: usize = 32;
+
+/// One check a suite runs
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SuiteCheck {
+    Icmp {
+        ip: IpAddr,
+    },
+    /// Reverse DNS, failing when `ip` has no PTR record or, with `confirm`, when the PTR name
+    /// doesn't resolve back to `ip`
+    Dns {
+        ip: IpAddr,
+        #[serde(default)]
+        confirm: bool,
+    },
+    Tcp {
+        host: String,
+        port: u16,
+    },
+    Tls {
+        host: String,
+        port: u16,
+    },
+    Http {
+        url: String,
+    },
+}
+
+impl SuiteCheck {
+    pub fn kind(&self) -> DiagnosticKind {
+        match self {
+            SuiteCheck::Icmp { .. } => DiagnosticKind::Icmp,
+            SuiteCheck::Dns { .. } => DiagnosticKind::ReverseDns,
+            SuiteCheck::Tcp { .. } => DiagnosticKind::Tcp,
+            SuiteCheck::Tls { .. } => DiagnosticKind::Tls,
+            SuiteCheck::Http { .. } => DiagnosticKind::Http,
+        }
+    }
+
+    /// Target as recorded on the check's diagnostic
+    pub fn target(&self) -> String {
+        match self {
+            SuiteCheck::Icmp { ip } | SuiteCheck::Dns { ip, .. } => ip.to_string(),
+            SuiteCheck::Tcp { host, port } | SuiteCheck::Tls { host, port } => {
+                display_target(host, *port)
+            }
+            SuiteCheck::Http { url } => url.clone(),
+        }
+    }
+
+    /// The name or address connected to, for checks that take one rather than a bare IP
+    pub fn host(&self) -> Option<String> {
+        match self {
+            SuiteCheck::Icmp { .. } | SuiteCheck::Dns { .. } => None,
+            SuiteCheck::Tcp { host, .. } | SuiteCheck::Tls { host, .. } => {
+                Some(host.trim_matches(['[', ']']).to_string())
+            }
+            SuiteCheck::Http { url } => Some(
+                Url::parse(url)
+                    .ok()?
+                    .host_str()?
+                    .trim_matches(['[', ']'])
+                    .to_string(),
+            ),
+        }
+    }
+
+    /// The address checked, when the target is an IP literal rather than a name
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self {
+            SuiteCheck::Icmp { ip } | SuiteCheck::Dns { ip, .. } => Some(*ip),
+            _ => self.host()?.parse().ok(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            SuiteCheck::Tcp { host, .. } | SuiteCheck::Tls { host, .. }
+                if host.trim().is_empty() =>
+            {
+                Err("Check host can't be empty".to_string())
+            }
+            SuiteCheck::Http { url } => match Url::parse(url) {
+                Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(()),
+                Ok(_) => Err(format!("{} is not an HTTP(S) URL", url)),
+                Err(e) => Err(format!("Invalid URL {}: {}", url, e)),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SuiteMember {
+    #[serde(flatten)]
+    pub check: SuiteCheck,
+    /// A failure of an advisory check only warns, and never short-circuits the suite
+    #[serde(default)]
+    pub advisory: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SuiteExecution {
+    /// One check at a time, in order
+    #[default]
+    Sequential,
+    /// All checks at once
+    Parallel,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SuiteFailurePolicy {
+    /// Run every check regardless
+    #[default]
+    Continue,
+    /// Stop at the first failing check, skipping those not yet run. In parallel, checks still in
+    /// flight are abandoned.
+    ShortCircuit,
+}
+
+/// Checks run together against a target as one job
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SuiteDefinition {
+    pub checks: Vec<SuiteMember>,
+    #[serde(default)]
+    pub execution: SuiteExecution,
+    #[serde(default)]
+    pub on_failure: SuiteFailurePolicy,
+}
+
+impl SuiteDefinition {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.checks.is_empty() {
+            return Err("A suite needs at least one check".to_string());
+        }
+        if self.checks.len() > MAX_SUITE_CHECKS {
+            return Err(format!(
+                "A suite can have at most {} checks",
+                MAX_SUITE_CHECKS
+            ));
+        }
+        self.checks.iter().try_for_each(|m| m.check.validate())
+    }
+}
+
+/// Status of a check or a whole suite, ordered so the worst status is the greatest
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SuiteStatus {
+    /// Not run because the suite short-circuited
+    Skipped,
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl SuiteStatus {
+    /// Worst status wins: any failure fails the suite, otherwise any warning warns it. Skipped
+    /// checks don't count, since a suite only skips checks after another has failed.
+    pub fn rollup(statuses: impl IntoIterator<Item = SuiteStatus>) -> SuiteStatus {
+        statuses.into_iter().max().unwrap_or(SuiteStatus::Skipped)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SuiteCheckResult {
+    #[serde(flatten)]
+    pub member: SuiteMember,
+    pub status: SuiteStatus,
+    #[serde(default)]
+    pub outcome: Option<CheckOutcome>,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Expiry of the certificate presented, for TLS checks, including ones that failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl SuiteCheckResult {
+    pub fn new(member: &SuiteMember, result: Result<CheckOutcome, CheckError>) -> Self {
+        let status = match (&result, member.advisory) {
+            (Ok(_), _) => SuiteStatus::Pass,
+            (Err(_), true) => SuiteStatus::Warn,
+            (Err(_), false) => SuiteStatus::Fail,
+        };
+
+        Self {
+            member: member.clone(),
+            status,
+            error: result.as_ref().err().map(|e| e.to_string()),
+            expires_at: match &result {
+                Ok(outcome) => outcome.expires_at,
+                Err(e) => e.expires_at(),
+            },
+            outcome: result.ok(),
+        }
+    }
+
+    fn skipped(member: &SuiteMember) -> Self {
+        Self {
+            member: member.clone(),
+            status: SuiteStatus::Skipped,
+            outcome: None,
+            error: None,
+            expires_at: None,
+        }
+    }
+}
+
+/// Combined result of a suite run, with a result per check in definition order
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SuiteReport {
+    pub status: SuiteStatus,
+    pub results: Vec<SuiteCheckResult>,
+}
+
+impl SuiteReport {
+    pub fn new(results: Vec<SuiteCheckResult>) -> Self {
+        Self {
+            status: SuiteStatus::rollup(results.iter().map(|r| r.status)),
+            results,
+        }
+    }
+}
+
+/// Kinds of check this daemon can run, reported to the server so it only asks for those
+pub fn supported_checks() -> Vec<DiagnosticKind> {
+    let mut kinds = vec![
+        DiagnosticKind::Tcp,
+        DiagnosticKind::Tls,
+        DiagnosticKind::Http,
+        DiagnosticKind::ReverseDns,
+    ];
+    if ping_available() {
+        kinds.push(DiagnosticKind::Icmp);
+    }
+    kinds
+}
+
+/// Runs a single check of a suite
+#[async_trait]
+pub trait CheckRunner: Send + Sync {
+    async fn run(
+        &self,
+        check: &SuiteCheck,
+        options: &CheckOptions,
+    ) -> Result<CheckOutcome, CheckError>;
+}
+
+/// Runs checks for real from the daemon host
+pub struct SystemCheckRunner;
+
+#[async_trait]
+impl CheckRunner for SystemCheckRunner {
+    async fn run(
+        &self,
+        check: &SuiteCheck,
+        options: &CheckOptions,
+    ) -> Result<CheckOutcome, CheckError> {
+        // A daemon-wide proxy only applies to the checks that can be routed through one
+        let direct = CheckOptions {
+            proxy: None,
+            ..options.clone()
+        };
+
+        match check {
+            SuiteCheck::Icmp { ip } => icmp_check(*ip, &direct).await,
+            SuiteCheck::Dns { ip, confirm } => {
+                let started = Instant::now();
+                let result = reverse_dns(*ip, *confirm, &direct).await?;
+                let failure = |reason: String| CheckError::Protocol {
+                    target: ip.to_string(),
+                    reason,
+                };
+
+                let ptr = result
+                    .ptr
+                    .ok_or_else(|| failure("no PTR record".to_string()))?;
+                if result.fcrdns == Fcrdns::Fail {
+                    return Err(failure(format!("{} doesn't resolve back to {}", ptr, ip)));
+                }
+
+                Ok(CheckOutcome {
+                    target: ip.to_string(),
+                    latency_ms: started.elapsed().as_millis() as u64,
+                    proxied: false,
+                    detail: Some(ptr),
+                    source: None,
+                    expires_at: None,
+                })
+            }
+            SuiteCheck::Tcp { host, port } => tcp_connect(host, *port, options).await,
+            SuiteCheck::Tls { host, port } => tls_check(host, *port, options).await,
+            SuiteCheck::Http { url } => {
+                let url = Url::parse(url).map_err(|e| CheckError::Protocol {
+                    target: url.clone(),
+                    reason: e.to_string(),
+                })?;
+                http_check(&url, options).await
+            }
+        }
+    }
+}
+
+pub async fn run_suite(suite: &SuiteDefinition, options: &CheckOptions) -> SuiteReport {
+    run_suite_with(&SystemCheckRunner, suite, options).await
+}
+
+pub async fn run_suite_with(
+    runner: &dyn CheckRunner,
+    suite: &SuiteDefinition,
+    options: &CheckOptions,
+) -> SuiteReport {
+    let short_circuit = suite.on_failure == SuiteFailurePolicy::ShortCircuit;
+    let mut results: Vec<Option<SuiteCheckResult>> = vec![None; suite.checks.len()];
+
+    match suite.execution {
+        SuiteExecution::Sequential => {
+            for (slot, member) in results.iter_mut().zip(&suite.checks) {
+                let result =
+                    SuiteCheckResult::new(member, runner.run(&member.check, options).await);
+                let failed = result.status == SuiteStatus::Fail;
+                *slot = Some(result);
+                if failed && short_circuit {
+                    break;
+                }
+            }
+        }
+        SuiteExecution::Parallel => {
+            let mut pending: FuturesUnordered<_> = suite
+                .checks
+                .iter()
+                .enumerate()
+                .map(|(index, member)| async move {
+                    let result = runner.run(&member.check, options).await;
+                    (index, SuiteCheckResult::new(member, result))
+                })
+                .collect();
+
+            while let Some((index, result)) = pending.next().await {
+                let failed = result.status == SuiteStatus::Fail;
+                results[index] = Some(result);
+                if failed && short_circuit {
+                    break;
+                }
+            }
+        }
+    }
+
+    SuiteReport::new(
+        results
+            .into_iter()
+            .zip(&suite.checks)
+            .map(|(result, member)| result.unwrap_or_else(|| SuiteCheckResult::skipped(member)))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Passes every check except DNS. DNS is slower than ping and TCP slower again, so parallel
+    /// runs finish in a known order.
+    struct FakeRunner;
+
+    #[async_trait]
+    impl CheckRunner for FakeRunner {
+        async fn run(
+            &self,
+            check: &SuiteCheck,
+            _options: &CheckOptions,
+        ) -> Result<CheckOutcome, CheckError> {
+            match check {
+                SuiteCheck::Dns { ip, .. } => {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    Err(CheckError::Protocol {
+                        target: ip.to_string(),
+                        reason: "no PTR record".to_string(),
+                    })
+                }
+                SuiteCheck::Tcp { .. } => {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Ok(outcome(check))
+                }
+                _ => Ok(outcome(check)),
+            }
+        }
+    }
+
+    fn outcome(check: &SuiteCheck) -> CheckOutcome {
+        CheckOutcome {
+            target: check.target(),
+            latency_ms: 1,
+            proxied: false,
+            detail: None,
+            source: None,
+            expires_at: None,
+        }
+    }
+
+    fn member(check: SuiteCheck) -> SuiteMember {
+        SuiteMember {
+            check,
+            advisory: false,
+        }
+    }
+
+    fn suite(execution: SuiteExecution, on_failure: SuiteFailurePolicy) -> SuiteDefinition {
+        let ip: IpAddr = "192.168.1.10".parse().unwrap();
+        SuiteDefinition {
+            checks: vec![
+                member(SuiteCheck::Icmp { ip }),
+                member(SuiteCheck::Dns { ip, confirm: true }),
+                member(SuiteCheck::Tcp {
+                    host: ip.to_string(),
+                    port: 443,
+                }),
+            ],
+            execution,
+            on_failure,
+        }
+    }
+
+    fn statuses(report: &SuiteReport) -> Vec<SuiteStatus> {
+        report.results.iter().map(|r| r.status).collect()
+    }
+
+    #[tokio::test]
+    async fn test_failing_dns_with_passing_ping_fails_suite() {
+        let options = CheckOptions::default();
+
+        let report = run_suite_with(
+            &FakeRunner,
+            &suite(SuiteExecution::Sequential, SuiteFailurePolicy::Continue),
+            &options,
+        )
+        .await;
+        assert_eq!(report.status, SuiteStatus::Fail);
+        assert_eq!(
+            statuses(&report),
+            vec![SuiteStatus::Pass, SuiteStatus::Fail, SuiteStatus::Pass]
+        );
+        assert!(report.results[1].error.is_some());
+
+        // An advisory DNS check only warns
+        let mut advisory = suite(SuiteExecution::Sequential, SuiteFailurePolicy::ShortCircuit);
+        advisory.checks[1].advisory = true;
+        let report = run_suite_with(&FakeRunner, &advisory, &options).await;
+        assert_eq!(report.status, SuiteStatus::Warn);
+        assert_eq!(
+            statuses(&report),
+            vec![SuiteStatus::Pass, SuiteStatus::Warn, SuiteStatus::Pass]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_short_circuit_skips_remaining_checks() {
+        let options = CheckOptions::default();
+
+        let report = run_suite_with(
+            &FakeRunner,
+            &suite(SuiteExecution::Sequential, SuiteFailurePolicy::ShortCircuit),
+            &options,
+        )
+        .await;
+        assert_eq!(report.status, SuiteStatus::Fail);
+        assert_eq!(
+            statuses(&report),
+            vec![SuiteStatus::Pass, SuiteStatus::Fail, SuiteStatus::Skipped]
+        );
+
+        // In parallel the slower TCP check is abandoned, but results stay in definition order
+        let report = run_suite_with(
+            &FakeRunner,
+            &suite(SuiteExecution::Parallel, SuiteFailurePolicy::ShortCircuit),
+            &options,
+        )
+        .await;
+        assert_eq!(report.status, SuiteStatus::Fail);
+        assert_eq!(
+            statuses(&report),
+            vec![SuiteStatus::Pass, SuiteStatus::Fail, SuiteStatus::Skipped]
+        );
+    }
+
+    #[test]
+    fn test_rollup_is_worst_wins() {
+        use SuiteStatus::*;
+
+        assert_eq!(SuiteStatus::rollup([Pass, Pass]), Pass);
+        assert_eq!(SuiteStatus::rollup([Pass, Warn, Pass]), Warn);
+        assert_eq!(SuiteStatus::rollup([Warn, Fail, Skipped]), Fail);
+        assert_eq!(SuiteStatus::rollup([Pass, Skipped]), Pass);
+    }
+
+    #[test]
+    fn test_definition_round_trips_and_validates() {
+        let definition: SuiteDefinition = serde_json::from_value(serde_json::json!({
+            "checks": [
+                { "kind": "icmp", "ip": "10.0.0.1" },
+                { "kind": "http", "url": "https://10.0.0.1/health", "advisory": true },
+            ],
+            "execution": "parallel",
+        }))
+        .unwrap();
+
+        assert_eq!(definition.on_failure, SuiteFailurePolicy::Continue);
+        assert!(definition.checks[1].advisory);
+        assert_eq!(definition.checks[1].check.ip(), "10.0.0.1".parse().ok());
+        assert!(definition.validate().is_ok());
+
+        assert!(SuiteDefinition::default().validate().is_err());
+        let bad_url = SuiteDefinition {
+            checks: vec![member(SuiteCheck::Http {
+                url: "ftp://10.0.0.1".to_string(),
+            })],
+            ..Default::default()
+        };
+        assert!(bad_url.validate().is_err());
+    }
+}
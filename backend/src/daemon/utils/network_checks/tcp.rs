@@ -0,0 +1,76 @@
+use std::time::Instant;
+use tokio::time::timeout;
+
+use crate::daemon::utils::network_checks::{
+    proxy::open_stream,
+    types::{CheckError, CheckOptions, CheckOutcome, display_target},
+};
+
+/// Check that a TCP connection to `host:port` can be established, from `options.source` if set.
+/// The local address actually used is reported in the outcome.
+pub async fn tcp_connect(
+    host: &str,
+    port: u16,
+    options: &CheckOptions,
+) -> Result<CheckOutcome, CheckError> {
+    let started = Instant::now();
+
+    let stream = timeout(options.timeout, open_stream(host, port, options))
+        .await
+        .map_err(|_| CheckError::Timeout(options.timeout))??;
+
+    Ok(CheckOutcome {
+        target: display_target(host, port),
+        latency_ms: started.elapsed().as_millis() as u64,
+        proxied: options.proxy.is_some(),
+        detail: None,
+        source: stream.local_addr().ok(),
+        expires_at: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+    use tokio::net::TcpListener;
+
+    fn options_from(source: &str) -> CheckOptions {
+        CheckOptions::default().with_source(Some(source.parse().unwrap()))
+    }
+
+    #[tokio::test]
+    async fn test_connect_from_bound_source() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let outcome = tcp_connect("127.0.0.1", port, &options_from("127.0.0.1"))
+            .await
+            .unwrap();
+        let (_, peer) = listener.accept().await.unwrap();
+
+        let source = outcome.source.unwrap();
+        assert_eq!(source.ip(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(source, peer);
+    }
+
+    #[tokio::test]
+    async fn test_unusable_source_rejected() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // TEST-NET-1, never assigned to a local interface
+        let err = tcp_connect("127.0.0.1", port, &options_from("192.0.2.1"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CheckError::SourceUnavailable { .. }), "{err}");
+
+        let err = tcp_connect("::1", port, &options_from("127.0.0.1"))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, CheckError::AddressFamilyMismatch { .. }),
+            "{err}"
+        );
+    }
+}
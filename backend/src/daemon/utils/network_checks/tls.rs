@@ -0,0 +1,299 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rustls::{
+    Certificate, ClientConfig, Error as TlsError, OwnedTrustAnchor, RootCertStore, ServerName,
+    client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier},
+};
+use std::{
+    sync::{Arc, Mutex, OnceLock},
+    time::{Instant, SystemTime},
+};
+use tokio::{net::TcpStream, time::timeout};
+use tokio_rustls::{TlsConnector, client::TlsStream};
+
+use crate::daemon::utils::network_checks::{
+    proxy::open_stream,
+    types::{CheckError, CheckOptions, CheckOutcome, display_target},
+};
+use crate::server::shared::sync::lock;
+
+fn roots() -> Arc<RootCertStore> {
+    static ROOTS: OnceLock<Arc<RootCertStore>> = OnceLock::new();
+
+    ROOTS
+        .get_or_init(|| {
+            let mut roots = RootCertStore::empty();
+            roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    ta.subject,
+                    ta.spki,
+                    ta.name_constraints,
+                )
+            }));
+            Arc::new(roots)
+        })
+        .clone()
+}
+
+fn client_config() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+
+    CONFIG
+        .get_or_init(|| {
+            Arc::new(
+                ClientConfig::builder()
+                    .with_safe_defaults()
+                    .with_root_certificates(roots())
+                    .with_no_client_auth(),
+            )
+        })
+        .clone()
+}
+
+/// Standard webpki verification that keeps the leaf certificate it was shown, so its expiry can
+/// be read even when verification fails, e.g. because it has expired or is self-signed
+struct RecordingVerifier {
+    inner: WebPkiVerifier,
+    leaf: Mutex<Option<Certificate>>,
+}
+
+impl RecordingVerifier {
+    fn new() -> Self {
+        Self {
+            inner: WebPkiVerifier::new(roots(), None),
+            leaf: Mutex::new(None),
+        }
+    }
+
+    fn expires_at(&self) -> Option<DateTime<Utc>> {
+        lock(&self.leaf)
+            .as_ref()
+            .and_then(|leaf| certificate_expiry(&leaf.0))
+    }
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, TlsError> {
+        *lock(&self.leaf) = Some(end_entity.clone());
+        self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )
+    }
+}
+
+/// Perform a TLS handshake for `host` over an already open (possibly proxied) stream
+pub async fn handshake(
+    stream: TcpStream,
+    host: &str,
+    port: u16,
+) -> Result<TlsStream<TcpStream>, CheckError> {
+    handshake_with(client_config(), stream, host, port).await
+}
+
+async fn handshake_with(
+    config: Arc<ClientConfig>,
+    stream: TcpStream,
+    host: &str,
+    port: u16,
+) -> Result<TlsStream<TcpStream>, CheckError> {
+    let target = display_target(host, port);
+    let tls_error = |reason: String| CheckError::Tls {
+        target: target.clone(),
+        reason,
+        expires_at: None,
+    };
+
+    let server_name = ServerName::try_from(host).map_err(|e| tls_error(e.to_string()))?;
+
+    TlsConnector::from(config)
+        .connect(server_name, stream)
+        .await
+        .map_err(|e| tls_error(e.to_string()))
+}
+
+/// Check that `host:port` completes a TLS handshake with a certificate valid for `host`. The
+/// certificate's expiry is reported whether or not it passes verification.
+pub async fn tls_check(
+    host: &str,
+    port: u16,
+    options: &CheckOptions,
+) -> Result<CheckOutcome, CheckError> {
+    let started = Instant::now();
+
+    let verifier = Arc::new(RecordingVerifier::new());
+    let config = Arc::new(
+        ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(verifier.clone())
+            .with_no_client_auth(),
+    );
+
+    let stream = timeout(options.timeout, async {
+        let stream = open_stream(host, port, options).await?;
+        handshake_with(config, stream, host, port).await
+    })
+    .await
+    .map_err(|_| CheckError::Timeout(options.timeout))?
+    .map_err(|e| match e {
+        CheckError::Tls { target, reason, .. } => CheckError::Tls {
+            target,
+            reason,
+            expires_at: verifier.expires_at(),
+        },
+        e => e,
+    })?;
+
+    let version = stream
+        .get_ref()
+        .1
+        .protocol_version()
+        .and_then(|v| v.as_str())
+        .map(str::to_owned);
+
+    Ok(CheckOutcome {
+        target: display_target(host, port),
+        latency_ms: started.elapsed().as_millis() as u64,
+        proxied: options.proxy.is_some(),
+        detail: version,
+        source: None,
+        expires_at: verifier.expires_at(),
+    })
+}
+
+/// Split one DER element off the front of `input`: (tag, contents, remainder)
+fn der_element(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let octets = (first & 0x7f) as usize;
+        if octets == 0 || octets > 4 || rest.len() < octets {
+            return None;
+        }
+        let len = rest[..octets]
+            .iter()
+            .fold(0usize, |len, &b| (len << 8) | b as usize);
+        (len, &rest[octets..])
+    };
+
+    (rest.len() >= len).then(|| (tag, &rest[..len], &rest[len..]))
+}
+
+/// The `notAfter` of a DER encoded X.509 certificate. Only walks as far as the validity period,
+/// so nothing beyond the fixed leading fields of the certificate needs understanding.
+pub fn certificate_expiry(der: &[u8]) -> Option<DateTime<Utc>> {
+    const SEQUENCE: u8 = 0x30;
+    const EXPLICIT_VERSION: u8 = 0xa0;
+    const UTC_TIME: u8 = 0x17;
+    const GENERALIZED_TIME: u8 = 0x18;
+
+    let (SEQUENCE, certificate, _) = der_element(der)? else {
+        return None;
+    };
+    let (SEQUENCE, mut tbs, _) = der_element(certificate)? else {
+        return None;
+    };
+
+    // Optional version, then serial number, signature algorithm and issuer
+    if tbs.first() == Some(&EXPLICIT_VERSION) {
+        tbs = der_element(tbs)?.2;
+    }
+    for _ in 0..3 {
+        tbs = der_element(tbs)?.2;
+    }
+
+    let (SEQUENCE, validity, _) = der_element(tbs)? else {
+        return None;
+    };
+    let (_, _, validity) = der_element(validity)?;
+    let (tag, not_after, _) = der_element(validity)?;
+
+    let not_after = std::str::from_utf8(not_after).ok()?;
+    let not_after = match tag {
+        // Two digit years: 50-99 are 19xx, 00-49 are 20xx (RFC 5280)
+        UTC_TIME => {
+            let century = if not_after.get(..2)? >= "50" {
+                "19"
+            } else {
+                "20"
+            };
+            format!("{}{}", century, not_after)
+        }
+        GENERALIZED_TIME => not_after.to_string(),
+        _ => return None,
+    };
+
+    NaiveDateTime::parse_from_str(&not_after, "%Y%m%d%H%M%SZ")
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(tag: u8, contents: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        if contents.len() < 0x80 {
+            out.push(contents.len() as u8);
+        } else {
+            out.extend([0x82, (contents.len() >> 8) as u8, contents.len() as u8]);
+        }
+        out.extend_from_slice(contents);
+        out
+    }
+
+    /// Skeleton certificate with just enough structure to reach the validity period
+    fn certificate(not_before: (u8, &str), not_after: (u8, &str)) -> Vec<u8> {
+        let validity = [
+            element(not_before.0, not_before.1.as_bytes()),
+            element(not_after.0, not_after.1.as_bytes()),
+        ]
+        .concat();
+
+        let tbs = [
+            element(0xa0, &element(0x02, &[2])),
+            element(0x02, &[0x01, 0x23]),
+            element(0x30, &element(0x06, &[0x2a, 0x86, 0x48])),
+            // Long enough to need a multi-byte length
+            element(0x30, &[0u8; 200]),
+            element(0x30, &validity),
+            element(0x30, &[]),
+        ]
+        .concat();
+
+        element(0x30, &[element(0x30, &tbs), element(0x30, &[])].concat())
+    }
+
+    #[test]
+    fn test_certificate_expiry() {
+        let der = certificate((0x17, "250101000000Z"), (0x17, "260315120000Z"));
+        assert_eq!(
+            certificate_expiry(&der),
+            Some("2026-03-15T12:00:00Z".parse().unwrap())
+        );
+
+        let der = certificate((0x17, "991231000000Z"), (0x18, "20510101000000Z"));
+        assert_eq!(
+            certificate_expiry(&der),
+            Some("2051-01-01T00:00:00Z".parse().unwrap())
+        );
+
+        assert_eq!(certificate_expiry(&der[..der.len() / 2]), None);
+        assert_eq!(certificate_expiry(b"not a certificate"), None);
+    }
+}
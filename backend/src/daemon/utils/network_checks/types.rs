@@ -0,0 +1,145 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use crate::daemon::utils::network_checks::proxy::ProxyConfig;
+
+/// Settings shared by every check
+#[derive(Debug, Clone)]
+pub struct CheckOptions {
+    pub timeout: Duration,
+    pub proxy: Option<ProxyConfig>,
+    /// Local address to originate connections from, to test one path on a multi-homed host
+    pub source: Option<IpAddr>,
+}
+
+impl CheckOptions {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            proxy: None,
+            source: None,
+        }
+    }
+
+    /// Route the check through a proxy. A proxy set on the check itself wins over the daemon-wide
+    /// one.
+    pub fn with_proxy(
+        mut self,
+        check_proxy: Option<ProxyConfig>,
+        global_proxy: Option<ProxyConfig>,
+    ) -> Self {
+        self.proxy = check_proxy.or(global_proxy);
+        self
+    }
+
+    pub fn with_source(mut self, source: Option<IpAddr>) -> Self {
+        self.source = source;
+        self
+    }
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(5))
+    }
+}
+
+/// Successful check result
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CheckOutcome {
+    pub target: String,
+    pub latency_ms: u64,
+    /// Whether the check went through a proxy rather than directly to the target
+    pub proxied: bool,
+    /// Check-specific detail, e.g. the HTTP status or negotiated TLS version
+    pub detail: Option<String>,
+    /// Local address the connection was made from, for checks that report it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<SocketAddr>,
+    /// Expiry of the certificate presented, for TLS checks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Why a check failed. Proxy failures are kept apart from target failures so a broken proxy isn't
+/// reported as the target being down.
+#[derive(Debug, thiserror::Error)]
+pub enum CheckError {
+    #[error("Proxy {proxy} is unreachable: {reason}")]
+    ProxyUnreachable { proxy: String, reason: String },
+
+    #[error("Proxy {proxy} rejected the supplied credentials")]
+    ProxyAuthFailed { proxy: String },
+
+    #[error("Proxy {proxy} refused the request: {reason}")]
+    ProxyRejected { proxy: String, reason: String },
+
+    #[error("{check} checks can't be routed through a proxy")]
+    ProxyNotSupported { check: &'static str },
+
+    #[error("Target {target} is unreachable: {reason}")]
+    TargetUnreachable { target: String, reason: String },
+
+    #[error("TLS handshake with {target} failed: {reason}")]
+    Tls {
+        target: String,
+        reason: String,
+        /// Expiry of the certificate presented, if the handshake got that far
+        expires_at: Option<DateTime<Utc>>,
+    },
+
+    #[error("Unexpected response from {target}: {reason}")]
+    Protocol { target: String, reason: String },
+
+    #[error("Source address {address} can't be used: {reason}")]
+    SourceUnavailable { address: IpAddr, reason: String },
+
+    #[error("Source address {address} is a different address family to {target}")]
+    AddressFamilyMismatch { address: IpAddr, target: String },
+
+    #[error("{target} is not on a segment local to interface {interface}")]
+    NotLocal { target: String, interface: String },
+
+    #[error("{target} has more than {max} addresses to check")]
+    TooManyTargets { target: String, max: u64 },
+
+    #[error("{check} checks need raw socket privileges: {reason}")]
+    InsufficientPrivileges { check: &'static str, reason: String },
+
+    #[error("Check timed out after {0:?}")]
+    Timeout(Duration),
+}
+
+impl CheckError {
+    /// Expiry of the certificate the target presented before failing, for TLS checks
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        match self {
+            CheckError::Tls { expires_at, .. } => *expires_at,
+            _ => None,
+        }
+    }
+
+    /// Whether the failure lies with the proxy rather than the target
+    pub fn is_proxy_failure(&self) -> bool {
+        matches!(
+            self,
+            CheckError::ProxyUnreachable { .. }
+                | CheckError::ProxyAuthFailed { .. }
+                | CheckError::ProxyRejected { .. }
+                | CheckError::ProxyNotSupported { .. }
+        )
+    }
+}
+
+/// `host:port`, bracketing IPv6 literals
+pub fn display_target(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
@@ -0,0 +1,427 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::{Duration, Instant},
+};
+use tokio::{sync::Mutex, time::timeout};
+
+use crate::daemon::utils::network_checks::types::{CheckError, CheckOptions};
+
+/// Public RDAP bootstrap service, which redirects to the registry responsible for an address
+pub const DEFAULT_RDAP_URL: &str = "https://rdap.org";
+
+/// Registries are asked at most once a day per address
+pub const DEFAULT_WHOIS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Failed lookups are retried sooner than successful ones are refreshed
+const FAILURE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Ownership details for a public address. Registries vary in what they publish, so any field
+/// may be missing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WhoisRecord {
+    /// Organization the address is registered to, or the network name when no registrant is listed
+    pub owner: Option<String>,
+    pub asn: Option<u32>,
+    /// ISO 3166 country code
+    pub country: Option<String>,
+    /// Email address for reporting abuse
+    pub abuse_contact: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum WhoisResult {
+    /// Private or reserved address, which no registry knows about, so no lookup was made
+    Private,
+    Found(WhoisRecord),
+    /// The registry couldn't be reached or didn't answer in time
+    Unknown {
+        reason: String,
+    },
+}
+
+/// Registry lookups behind the whois check
+#[async_trait]
+pub trait RdapClient: Send + Sync {
+    async fn lookup(&self, ip: IpAddr) -> Result<WhoisRecord, CheckError>;
+}
+
+/// RDAP over HTTPS, starting from a bootstrap service that redirects to the right registry
+pub struct HttpRdapClient {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl HttpRdapClient {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+impl Default for HttpRdapClient {
+    fn default() -> Self {
+        Self::new(DEFAULT_RDAP_URL)
+    }
+}
+
+#[async_trait]
+impl RdapClient for HttpRdapClient {
+    async fn lookup(&self, ip: IpAddr) -> Result<WhoisRecord, CheckError> {
+        let target = ip.to_string();
+        let protocol_error = |reason: String| CheckError::Protocol {
+            target: target.clone(),
+            reason,
+        };
+
+        let response = self
+            .client
+            .get(format!("{}/ip/{}", self.base_url, ip))
+            .header(reqwest::header::ACCEPT, "application/rdap+json")
+            .send()
+            .await
+            .map_err(|e| CheckError::TargetUnreachable {
+                target: self.base_url.clone(),
+                reason: e.to_string(),
+            })?;
+
+        if !response.status().is_success() {
+            return Err(protocol_error(format!(
+                "RDAP server returned {}",
+                response.status()
+            )));
+        }
+
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|e| protocol_error(format!("Invalid RDAP response: {}", e)))?;
+
+        Ok(parse_rdap(&body))
+    }
+}
+
+/// Pull ownership details out of an RDAP IP network object
+pub fn parse_rdap(body: &Value) -> WhoisRecord {
+    let entities = body["entities"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or(&[]);
+
+    let owner = find_entity(entities, "registrant")
+        .and_then(|e| vcard_field(e, "fn"))
+        .or_else(|| body["name"].as_str().map(str::to_string));
+
+    // Origin ASNs aren't part of core RDAP; ARIN publishes them through an extension
+    let asn = body["arin_originas0_originautnums"]
+        .as_array()
+        .and_then(|asns| asns.first())
+        .and_then(Value::as_u64)
+        .and_then(|asn| u32::try_from(asn).ok());
+
+    WhoisRecord {
+        owner,
+        asn,
+        country: body["country"].as_str().map(str::to_string),
+        abuse_contact: find_entity(entities, "abuse").and_then(|e| vcard_field(e, "email")),
+    }
+}
+
+/// First entity with `role`, searching nested entities too since registries often list the abuse
+/// contact under the registrant
+fn find_entity<'a>(entities: &'a [Value], role: &str) -> Option<&'a Value> {
+    entities.iter().find_map(|entity| {
+        let has_role = entity["roles"]
+            .as_array()
+            .is_some_and(|roles| roles.iter().any(|r| r.as_str() == Some(role)));
+
+        if has_role {
+            Some(entity)
+        } else {
+            find_entity(
+                entity["entities"]
+                    .as_array()
+                    .map(Vec::as_slice)
+                    .unwrap_or(&[]),
+                role,
+            )
+        }
+    })
+}
+
+/// Text value of a jCard property, e.g. `["fn", {}, "text", "Example Org"]`
+fn vcard_field(entity: &Value, name: &str) -> Option<String> {
+    entity["vcardArray"][1]
+        .as_array()?
+        .iter()
+        .find(|property| property[0].as_str() == Some(name))
+        .and_then(|property| property[3].as_str())
+        .map(str::to_string)
+}
+
+/// Whether an address is globally routable, and so worth asking a registry about
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // Shared address space (carrier-grade NAT)
+        || (a == 100 && (64..128).contains(&b))
+        // Benchmarking
+        || (a == 198 && (b == 18 || b == 19))
+        // "This network" and reserved for future use
+        || a == 0
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local
+        || (first & 0xfe00) == 0xfc00
+        // Link-local
+        || (first & 0xffc0) == 0xfe80
+        // Documentation
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+/// Whois lookups for public addresses, cached so repeated discoveries don't hammer registries
+pub struct Whois {
+    client: Box<dyn RdapClient>,
+    ttl: Duration,
+    cache: Mutex<HashMap<IpAddr, (Instant, WhoisResult)>>,
+}
+
+impl Whois {
+    pub fn new(client: Box<dyn RdapClient>, ttl: Duration) -> Self {
+        Self {
+            client,
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Owner, ASN, country and abuse contact for `ip`. Never fails: private addresses
+    /// short-circuit, and registry errors or timeouts come back as [`WhoisResult::Unknown`] so a
+    /// discovery isn't failed over missing context.
+    pub async fn lookup(&self, ip: IpAddr, options: &CheckOptions) -> WhoisResult {
+        if !is_public(ip) {
+            return WhoisResult::Private;
+        }
+
+        // Lookups go straight to the registry
+        if options.proxy.is_some() {
+            return WhoisResult::Unknown {
+                reason: CheckError::ProxyNotSupported { check: "Whois" }.to_string(),
+            };
+        }
+
+        if let Some((cached_at, result)) = self.cache.lock().await.get(&ip) {
+            let ttl = match result {
+                WhoisResult::Unknown { .. } => FAILURE_TTL.min(self.ttl),
+                _ => self.ttl,
+            };
+            if cached_at.elapsed() < ttl {
+                return result.clone();
+            }
+        }
+
+        let result = match timeout(options.timeout, self.client.lookup(ip)).await {
+            Ok(Ok(record)) => WhoisResult::Found(record),
+            Ok(Err(e)) => WhoisResult::Unknown {
+                reason: e.to_string(),
+            },
+            Err(_) => WhoisResult::Unknown {
+                reason: CheckError::Timeout(options.timeout).to_string(),
+            },
+        };
+
+        if let WhoisResult::Unknown { reason } = &result {
+            tracing::debug!(ip = %ip, reason = %reason, "Whois lookup failed");
+        }
+
+        self.cache
+            .lock()
+            .await
+            .insert(ip, (Instant::now(), result.clone()));
+
+        result
+    }
+}
+
+impl Default for Whois {
+    fn default() -> Self {
+        Self::new(Box::new(HttpRdapClient::default()), DEFAULT_WHOIS_TTL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    /// Registry stand-in that counts how often it's asked
+    struct FakeRdap {
+        calls: Arc<AtomicUsize>,
+        response: Option<WhoisRecord>,
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl RdapClient for FakeRdap {
+        async fn lookup(&self, ip: IpAddr) -> Result<WhoisRecord, CheckError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.response.clone().ok_or(CheckError::Protocol {
+                target: ip.to_string(),
+                reason: "RDAP server returned 503 Service Unavailable".to_string(),
+            })
+        }
+    }
+
+    fn whois(response: Option<WhoisRecord>, delay: Duration) -> (Whois, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let client = FakeRdap {
+            calls: calls.clone(),
+            response,
+            delay,
+        };
+        (Whois::new(Box::new(client), DEFAULT_WHOIS_TTL), calls)
+    }
+
+    fn record() -> WhoisRecord {
+        WhoisRecord {
+            owner: Some("Example Hosting Ltd".to_string()),
+            asn: Some(64500),
+            country: Some("GB".to_string()),
+            abuse_contact: Some("abuse@example.net".to_string()),
+        }
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_private_addresses_short_circuit() {
+        let (whois, calls) = whois(Some(record()), Duration::ZERO);
+
+        for address in [
+            "192.168.1.10",
+            "10.1.2.3",
+            "172.16.0.1",
+            "127.0.0.1",
+            "169.254.1.1",
+            "100.64.0.1",
+            "fd00::10",
+            "fe80::1",
+            "::ffff:192.168.1.10",
+        ] {
+            assert_eq!(
+                whois.lookup(ip(address), &CheckOptions::default()).await,
+                WhoisResult::Private,
+                "{}",
+                address
+            );
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_public_lookup_returns_owner_and_is_cached() {
+        let (whois, calls) = whois(Some(record()), Duration::ZERO);
+        let options = CheckOptions::default();
+
+        let result = whois.lookup(ip("8.8.8.8"), &options).await;
+        assert_eq!(result, WhoisResult::Found(record()));
+        assert_eq!(whois.lookup(ip("8.8.8.8"), &options).await, result);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        whois.lookup(ip("2606:4700::1111"), &options).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_registry_errors_and_timeouts_are_unknown() {
+        let (whois_failing, calls) = whois(None, Duration::ZERO);
+        let options = CheckOptions::default();
+
+        let result = whois_failing.lookup(ip("8.8.8.8"), &options).await;
+        assert!(
+            matches!(&result, WhoisResult::Unknown { reason } if reason.contains("503")),
+            "{:?}",
+            result
+        );
+        // Failures are cached too, rather than retried on every lookup
+        whois_failing.lookup(ip("8.8.8.8"), &options).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let (whois_slow, _) = whois(Some(record()), Duration::from_secs(5));
+        let result = whois_slow
+            .lookup(ip("8.8.8.8"), &CheckOptions::new(Duration::from_millis(20)))
+            .await;
+        assert!(
+            matches!(&result, WhoisResult::Unknown { reason } if reason.contains("timed out")),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_parse_rdap_network() {
+        let body = serde_json::json!({
+            "objectClassName": "ip network",
+            "name": "EXAMPLE-NET",
+            "country": "GB",
+            "arin_originas0_originautnums": [64500],
+            "entities": [{
+                "roles": ["registrant"],
+                "vcardArray": ["vcard", [
+                    ["version", {}, "text", "4.0"],
+                    ["fn", {}, "text", "Example Hosting Ltd"]
+                ]],
+                "entities": [{
+                    "roles": ["abuse"],
+                    "vcardArray": ["vcard", [
+                        ["fn", {}, "text", "Abuse Desk"],
+                        ["email", {}, "text", "abuse@example.net"]
+                    ]]
+                }]
+            }]
+        });
+        assert_eq!(parse_rdap(&body), record());
+
+        // Partial answers keep what they have
+        let partial = parse_rdap(&serde_json::json!({ "name": "EXAMPLE-NET" }));
+        assert_eq!(partial.owner.as_deref(), Some("EXAMPLE-NET"));
+        assert_eq!(partial.asn, None);
+        assert_eq!(partial.abuse_contact, None);
+    }
+}
@@ -0,0 +1,261 @@
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{
+    daemon::utils::scanner::ProbeOutcomes,
+    server::{discovery::r#impl::types::ScanRate, shared::sync::lock},
+};
+
+/// Share of lost probes in a window above which the rate is halved
+const BACKOFF_LOSS: f64 = 0.10;
+
+/// Share of lost probes in a window below which the rate climbs
+const RAMP_UP_LOSS: f64 = 0.02;
+
+/// Concurrency limit for host scans. Fixed rates hold the daemon's concurrency for the whole
+/// scan; adaptive rates climb by one scan per window of answered probes and halve when too many
+/// time out. A window is as many host scans as the current limit.
+pub struct AdaptiveScanRate {
+    permits: Arc<Semaphore>,
+    state: Mutex<RateState>,
+    max: usize,
+    adaptive: bool,
+}
+
+struct RateState {
+    limit: usize,
+    hosts: usize,
+    answered: usize,
+    lost: usize,
+    /// Permits to drop as they're released, when a backoff found them in use
+    owed: usize,
+}
+
+impl AdaptiveScanRate {
+    /// `concurrent_scans` is the daemon's own (file descriptor safe) concurrency, which is the
+    /// fixed rate and also caps how far an adaptive rate may climb
+    pub fn new(scan_rate: ScanRate, concurrent_scans: usize) -> Self {
+        let concurrent_scans = concurrent_scans.max(1);
+        let (limit, max, adaptive) = match scan_rate.capped(concurrent_scans) {
+            ScanRate::Fixed => (concurrent_scans, concurrent_scans, false),
+            ScanRate::Adaptive { initial, max } => (initial, max, true),
+        };
+
+        Self {
+            permits: Arc::new(Semaphore::new(limit)),
+            state: Mutex::new(RateState {
+                limit,
+                hosts: 0,
+                answered: 0,
+                lost: 0,
+                owed: 0,
+            }),
+            max,
+            adaptive,
+        }
+    }
+
+    /// Most scans that can ever run at once, for sizing the scan stream
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Scans currently allowed to run at once
+    pub fn current(&self) -> usize {
+        lock(&self.state).limit
+    }
+
+    /// Wait for a scan slot, held until the permit is dropped
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        loop {
+            let permit = self
+                .permits
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("Scan rate semaphore is never closed");
+
+            let mut state = lock(&self.state);
+            if state.owed == 0 {
+                return permit;
+            }
+            state.owed -= 1;
+            permit.forget();
+        }
+    }
+
+    /// Record the probe outcomes of a host scan, adjusting the rate at the end of a window. A host
+    /// that answered nothing is taken to be absent rather than behind a lossy path, so it counts
+    /// towards the window without its timeouts.
+    pub fn record(&self, outcomes: ProbeOutcomes) {
+        if !self.adaptive {
+            return;
+        }
+
+        let mut state = lock(&self.state);
+        state.hosts += 1;
+        if outcomes.answered > 0 {
+            state.answered += outcomes.answered;
+            state.lost += outcomes.lost;
+        }
+
+        if state.hosts < state.limit {
+            return;
+        }
+
+        let limit = next_limit(state.limit, self.max, state.answered, state.lost);
+        state.hosts = 0;
+        state.answered = 0;
+        state.lost = 0;
+
+        if limit > state.limit {
+            let added = limit - state.limit;
+            let repaid = added.min(state.owed);
+            state.owed -= repaid;
+            self.permits.add_permits(added - repaid);
+        } else if limit < state.limit {
+            let removed = state.limit - limit;
+            state.owed += removed - self.permits.forget_permits(removed);
+        }
+
+        if limit != state.limit {
+            tracing::debug!(from = state.limit, to = limit, "Adjusted host scan rate");
+        }
+        state.limit = limit;
+    }
+}
+
+/// Limit for the next window given the probe outcomes of the last one. A window without any
+/// answers saw no loss either.
+fn next_limit(limit: usize, max: usize, answered: usize, lost: usize) -> usize {
+    let total = answered + lost;
+    let loss = if total == 0 {
+        0.0
+    } else {
+        lost as f64 / total as f64
+    };
+    if loss > BACKOFF_LOSS {
+        (limit / 2).max(1)
+    } else if loss < RAMP_UP_LOSS {
+        (limit + 1).min(max)
+    } else {
+        limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn outcomes(answered: usize, lost: usize) -> ProbeOutcomes {
+        ProbeOutcomes { answered, lost }
+    }
+
+    #[test]
+    fn test_next_limit() {
+        // Clean windows ramp up, but never past the cap
+        assert_eq!(next_limit(4, 16, 4, 0), 5);
+        assert_eq!(next_limit(16, 16, 16, 0), 16);
+        // Moderate loss holds the rate
+        assert_eq!(next_limit(20, 32, 19, 1), 20);
+        // High loss backs off rather than climbing, down to a single scan
+        assert_eq!(next_limit(16, 32, 12, 4), 8);
+        assert_eq!(next_limit(1, 32, 0, 1), 1);
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_rate_backs_off_under_loss() {
+        let rate = AdaptiveScanRate::new(
+            ScanRate::Adaptive {
+                initial: 4,
+                max: 100,
+            },
+            8,
+        );
+        // Capped by the daemon's own concurrency
+        assert_eq!(rate.max(), 8);
+        assert_eq!(rate.current(), 4);
+
+        for _ in 0..4 {
+            rate.record(outcomes(2, 0));
+        }
+        assert_eq!(rate.current(), 5);
+
+        // Timed out probes are loss. Backing off while every slot is in use only takes effect as
+        // scans finish.
+        let held: Vec<_> = futures::future::join_all((0..5).map(|_| rate.acquire())).await;
+        for _ in 0..5 {
+            rate.record(outcomes(2, 1));
+        }
+        assert_eq!(rate.current(), 2);
+        drop(held);
+        let _running = (rate.acquire().await, rate.acquire().await);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), rate.acquire())
+                .await
+                .is_err()
+        );
+
+        // Addresses where nothing answers are absent hosts, not loss
+        let sparse = AdaptiveScanRate::new(ScanRate::Adaptive { initial: 2, max: 8 }, 8);
+        for _ in 0..2 {
+            sparse.record(outcomes(0, 3));
+        }
+        assert_eq!(sparse.current(), 3);
+
+        let fixed = AdaptiveScanRate::new(ScanRate::Fixed, 8);
+        for _ in 0..16 {
+            fixed.record(outcomes(2, 1));
+        }
+        assert_eq!(fixed.current(), 8);
+    }
+
+    /// Probes of an open port, a closed one, and one whose accept queue is full are answered,
+    /// answered and lost. Only Linux drops connections to a full queue rather than refusing them.
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_probe_outcomes_count_timeouts_as_lost() {
+        use crate::{
+            daemon::utils::scanner::probe_tcp_ports, server::hosts::r#impl::ports::PortBase,
+        };
+        use std::net::Ipv4Addr;
+        use tokio::net::{TcpListener, TcpSocket, TcpStream};
+        use tokio_util::sync::CancellationToken;
+
+        let open = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed_port = closed.local_addr().unwrap().port();
+        drop(closed);
+
+        let full = TcpSocket::new_v4().unwrap();
+        full.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let full = full.listen(0).unwrap();
+        let full_addr = full.local_addr().unwrap();
+        let mut queued = Vec::new();
+        while let Ok(Ok(stream)) =
+            tokio::time::timeout(Duration::from_millis(300), TcpStream::connect(full_addr)).await
+        {
+            queued.push(stream);
+        }
+
+        let ports = [
+            open.local_addr().unwrap().port(),
+            closed_port,
+            full_addr.port(),
+        ]
+        .map(PortBase::new_tcp)
+        .to_vec();
+        let (open_ports, probed) = probe_tcp_ports(
+            Ipv4Addr::LOCALHOST.into(),
+            ports.clone(),
+            CancellationToken::new(),
+            ports.len(),
+        )
+        .await;
+
+        assert_eq!(open_ports.len(), 1);
+        assert_eq!(open_ports[0].0, ports[0]);
+        assert_eq!(probed, outcomes(2, 1));
+    }
+}
@@ -94,7 +94,8 @@ pub async fn scan_ports_and_endpoints(
     port_scan_batch_size: usize,
     cidr: IpCidr,
     gateway_ips: Vec<IpAddr>,
-) -> Result<(Vec<PortBase>, Vec<EndpointResponse>), Error> {
+    snmp_community: Option<&str>,
+) -> Result<(Vec<PortBase>, Vec<EndpointResponse>, ProbeOutcomes), Error> {
     if cancel.is_cancelled() {
         return Err(anyhow!("Operation cancelled"));
     }
@@ -103,7 +104,8 @@ pub async fn scan_ports_and_endpoints(
     let mut endpoint_responses = Vec::new();
 
     // Scan TCP ports with batching
-    let tcp_ports = scan_tcp_ports(ip, cancel.clone(), port_scan_batch_size).await?;
+    let (tcp_ports, probe_outcomes) =
+        scan_tcp_ports(ip, cancel.clone(), port_scan_batch_size).await?;
 
     let use_https_ports: HashMap<u16, bool> =
         tcp_ports.iter().map(|(p, h)| (p.number(), *h)).collect();
@@ -116,8 +118,15 @@ pub async fn scan_ports_and_endpoints(
     }
 
     // Scan UDP ports with batching
-    let udp_ports =
-        scan_udp_ports(ip, cancel.clone(), port_scan_batch_size, cidr, gateway_ips).await?;
+    let udp_ports = scan_udp_ports(
+        ip,
+        cancel.clone(),
+        port_scan_batch_size,
+        cidr,
+        gateway_ips,
+        snmp_community,
+    )
+    .await?;
     open_ports.extend(udp_ports);
 
     if cancel.is_cancelled() {
@@ -167,14 +176,31 @@ pub async fn scan_ports_and_endpoints(
         "Host scan complete"
     );
 
-    Ok((open_ports, endpoint_responses))
+    Ok((open_ports, endpoint_responses, probe_outcomes))
+}
+
+/// How many of a host's probes were answered, open or refused, and how many timed out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProbeOutcomes {
+    pub answered: usize,
+    pub lost: usize,
+}
+
+/// Outcome of probing a single TCP port
+enum TcpProbe {
+    /// Open, and whether it looks like it speaks HTTPS
+    Open(PortBase, bool),
+    Closed,
+    TimedOut,
+    /// Failed locally or unreachable, which says nothing about loss on the path
+    Failed,
 }
 
 pub async fn scan_tcp_ports(
     ip: IpAddr,
     cancel: CancellationToken,
     batch_size: usize,
-) -> Result<Vec<(PortBase, bool)>, Error> {
+) -> Result<(Vec<(PortBase, bool)>, ProbeOutcomes), Error> {
     let discovery_ports = Service::all_discovery_ports();
     let ports: Vec<PortBase> = discovery_ports
         .iter()
@@ -187,7 +213,20 @@ pub async fn scan_tcp_ports(
         })
         .collect();
 
-    let open_ports = batch_scan(ports.clone(), batch_size, cancel, move |port| async move {
+    Ok(probe_tcp_ports(ip, ports, cancel, batch_size).await)
+}
+
+/// Try to connect to each of `ports` on `ip`, returning the open ones and how many probes were
+/// answered or lost
+pub async fn probe_tcp_ports(
+    ip: IpAddr,
+    ports: Vec<PortBase>,
+    cancel: CancellationToken,
+    batch_size: usize,
+) -> (Vec<(PortBase, bool)>, ProbeOutcomes) {
+    let ports_scanned = ports.len();
+
+    let probes = batch_scan(ports, batch_size, cancel, move |port| async move {
         let socket = SocketAddr::new(ip, port.number());
 
         // Try connection with timeout, retry once on timeout for slow hosts
@@ -234,7 +273,7 @@ pub async fn scan_tcp_ports(
                     );
 
                     drop(stream);
-                    return Some((
+                    return Some(TcpProbe::Open(
                         PortBase::new_tcp(port.number()),
                         use_https || port.is_https(),
                     ));
@@ -242,8 +281,13 @@ pub async fn scan_tcp_ports(
                 Ok(Err(e)) => {
                     if DiscoveryCriticalError::is_critical_error(e.to_string()) {
                         tracing::error!("Critical error scanning {}:{}: {}", socket.ip(), port, e);
+                        return Some(TcpProbe::Failed);
                     }
-                    return None;
+                    // Only a refusal came from the host, anything else is no answer at all
+                    if e.kind() == std::io::ErrorKind::ConnectionRefused {
+                        return Some(TcpProbe::Closed);
+                    }
+                    return Some(TcpProbe::Failed);
                 }
                 Err(_) => {
                     let elapsed = start.elapsed();
@@ -262,7 +306,7 @@ pub async fn scan_tcp_ports(
                         continue;
                     } else {
                         tracing::trace!("Port {}:{} timeout after {} attempts", ip, port, attempts);
-                        return None;
+                        return Some(TcpProbe::TimedOut);
                     }
                 }
             }
@@ -270,14 +314,29 @@ pub async fn scan_tcp_ports(
     })
     .await;
 
+    let mut open_ports = Vec::new();
+    let mut outcomes = ProbeOutcomes::default();
+    for probe in probes {
+        match probe {
+            TcpProbe::Open(port, use_https) => {
+                open_ports.push((port, use_https));
+                outcomes.answered += 1;
+            }
+            TcpProbe::Closed => outcomes.answered += 1,
+            TcpProbe::TimedOut => outcomes.lost += 1,
+            TcpProbe::Failed => {}
+        }
+    }
+
     tracing::debug!(
         ip = %ip,
-        ports_scanned = %ports.len(),
+        ports_scanned = %ports_scanned,
         responses = %open_ports.len(),
-        "UDP port scan complete"
+        lost = %outcomes.lost,
+        "TCP port scan complete"
     );
 
-    Ok(open_ports)
+    (open_ports, outcomes)
 }
 
 pub async fn scan_udp_ports(
@@ -286,6 +345,7 @@ pub async fn scan_udp_ports(
     batch_size: usize,
     cidr: IpCidr,
     gateway_ips: Vec<IpAddr>,
+    snmp_community: Option<&str>,
 ) -> Result<Vec<PortBase>, Error> {
     let discovery_ports = Service::all_discovery_ports();
     let ports: Vec<u16> = discovery_ports
@@ -299,32 +359,37 @@ pub async fn scan_udp_ports(
 
     let is_gateway = gateway_ips.contains(&ip);
 
-    let open_ports = batch_scan(ports.clone(), udp_batch_size, cancel, |port| async move {
-        let result = match port {
-            53 => test_dns_service(ip).await,
-            123 => test_ntp_service(ip).await,
-            161 => test_snmp_service(ip).await,
-            67 => {
-                if is_gateway {
-                    test_dhcp_service(ip, &cidr).await
-                } else {
-                    Ok(None)
+    let snmp_community = snmp_community.unwrap_or("public").to_string();
+
+    let open_ports = batch_scan(ports.clone(), udp_batch_size, cancel, |port| {
+        let snmp_community = snmp_community.clone();
+        async move {
+            let result = match port {
+                53 => test_dns_service(ip).await,
+                123 => test_ntp_service(ip).await,
+                161 => test_snmp_service(ip, &snmp_community).await,
+                67 => {
+                    if is_gateway {
+                        test_dhcp_service(ip, &cidr).await
+                    } else {
+                        Ok(None)
+                    }
                 }
-            }
-            _ => Ok(None),
-        };
+                _ => Ok(None),
+            };
 
-        match result {
-            Ok(Some(detected_port)) => {
-                tracing::trace!("Found open UDP port {}:{}", ip, detected_port);
-                Some(PortBase::new_udp(detected_port))
-            }
-            Ok(None) => None,
-            Err(e) => {
-                if DiscoveryCriticalError::is_critical_error(e.to_string()) {
-                    tracing::error!("Critical error scanning UDP {}:{}: {}", ip, port, e);
+            match result {
+                Ok(Some(detected_port)) => {
+                    tracing::trace!("Found open UDP port {}:{}", ip, detected_port);
+                    Some(PortBase::new_udp(detected_port))
+                }
+                Ok(None) => None,
+                Err(e) => {
+                    if DiscoveryCriticalError::is_critical_error(e.to_string()) {
+                        tracing::error!("Critical error scanning UDP {}:{}: {}", ip, port, e);
+                    }
+                    None
                 }
-                None
             }
         }
     })
@@ -526,11 +591,10 @@ pub async fn test_ntp_service(ip: IpAddr) -> Result<Option<u16>, Error> {
     }
 }
 
-pub async fn test_snmp_service(ip: IpAddr) -> Result<Option<u16>, Error> {
+pub async fn test_snmp_service(ip: IpAddr, community: &str) -> Result<Option<u16>, Error> {
     let target = format!("{}:161", ip);
-    let community = b"public";
 
-    match AsyncSession::new_v2c(&target, community, 0).await {
+    match AsyncSession::new_v2c(&target, community.as_bytes(), 0).await {
         Ok(mut session) => {
             let sys_descr_oid = Oid::from(&[1, 3, 6, 1, 2, 1, 1, 1, 0])
                 .map_err(|e| anyhow!("Invalid Oid: {:?}", e))?;
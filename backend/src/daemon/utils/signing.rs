@@ -0,0 +1,62 @@
+use anyhow::Result;
+use flate2::{Compression, write::GzEncoder};
+use reqwest::{
+    RequestBuilder,
+    header::{CONTENT_ENCODING, CONTENT_TYPE},
+};
+use serde::Serialize;
+use std::io::Write;
+
+use crate::server::daemons::r#impl::signing::signature_headers;
+
+pub trait SignedJsonExt: Sized {
+    /// Like `.json(payload)`, additionally signing the serialized body with the daemon's API key
+    /// so the server can verify the results weren't altered in transit
+    fn signed_json<T: Serialize + ?Sized>(self, api_key: &str, payload: &T) -> Result<Self>;
+
+    /// Like [`signed_json`](Self::signed_json), gzipping the body when `compress` is set. The
+    /// signature covers the uncompressed body, which is what the server verifies once decoded.
+    fn signed_json_compressed<T: Serialize + ?Sized>(
+        self,
+        api_key: &str,
+        payload: &T,
+        compress: bool,
+    ) -> Result<Self>;
+}
+
+impl SignedJsonExt for RequestBuilder {
+    fn signed_json<T: Serialize + ?Sized>(self, api_key: &str, payload: &T) -> Result<Self> {
+        let body = serde_json::to_vec(payload)?;
+
+        let request = signature_headers(api_key, &body)
+            .into_iter()
+            .fold(self, |request, (name, value)| request.header(name, value));
+
+        Ok(request.header(CONTENT_TYPE, "application/json").body(body))
+    }
+
+    fn signed_json_compressed<T: Serialize + ?Sized>(
+        self,
+        api_key: &str,
+        payload: &T,
+        compress: bool,
+    ) -> Result<Self> {
+        if !compress {
+            return self.signed_json(api_key, payload);
+        }
+
+        let body = serde_json::to_vec(payload)?;
+
+        let request = signature_headers(api_key, &body)
+            .into_iter()
+            .fold(self, |request, (name, value)| request.header(name, value));
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&body)?;
+
+        Ok(request
+            .header(CONTENT_TYPE, "application/json")
+            .header(CONTENT_ENCODING, "gzip")
+            .body(encoder.finish()?))
+    }
+}
@@ -0,0 +1,105 @@
+use crate::server::daemons::r#impl::metrics::DaemonSystemMetrics;
+#[cfg(target_os = "linux")]
+use crate::server::daemons::r#impl::metrics::{DaemonInterfaceStats, MAX_REPORTED_INTERFACES};
+
+/// Samples the health of the host the daemon runs on for its heartbeats. CPU usage is measured
+/// between consecutive samples, so the sampler lives as long as the heartbeat loop.
+#[derive(Default)]
+pub struct SystemMetricsSampler {
+    /// Busy and total CPU ticks at the previous sample
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    previous_cpu: Option<(u64, u64)>,
+}
+
+impl SystemMetricsSampler {
+    /// None where the platform isn't supported or the metrics couldn't be read; the heartbeat is
+    /// then sent without them.
+    pub fn sample(&mut self) -> Option<DaemonSystemMetrics> {
+        #[cfg(target_os = "linux")]
+        {
+            match self.sample_linux() {
+                Ok(metrics) => Some(metrics),
+                Err(e) => {
+                    tracing::debug!(error = %e, "Failed to sample system metrics");
+                    None
+                }
+            }
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        None
+    }
+
+    #[cfg(target_os = "linux")]
+    fn sample_linux(&mut self) -> anyhow::Result<DaemonSystemMetrics> {
+        use procfs::{Current, CurrentSI, KernelStats, Meminfo};
+
+        let cpu = KernelStats::current()?.total;
+        let idle = cpu.idle + cpu.iowait.unwrap_or(0);
+        let total = cpu.user
+            + cpu.nice
+            + cpu.system
+            + idle
+            + cpu.irq.unwrap_or(0)
+            + cpu.softirq.unwrap_or(0)
+            + cpu.steal.unwrap_or(0);
+        let busy = total - idle;
+
+        // The first sample covers everything since boot
+        let (previous_busy, previous_total) = self.previous_cpu.unwrap_or((0, 0));
+        self.previous_cpu = Some((busy, total));
+        let elapsed = total.saturating_sub(previous_total);
+        let cpu_percent = if elapsed == 0 {
+            0.0
+        } else {
+            (busy.saturating_sub(previous_busy) as f64 * 100.0 / elapsed as f64).clamp(0.0, 100.0)
+        };
+
+        let memory = Meminfo::current()?;
+        let memory_free = memory.mem_available.unwrap_or(memory.mem_free);
+
+        let (disk_used_bytes, disk_total_bytes) = disk_usage("/")?;
+
+        let mut interfaces: Vec<DaemonInterfaceStats> = procfs::net::dev_status()?
+            .into_values()
+            .filter(|status| status.name != "lo")
+            .map(|status| DaemonInterfaceStats {
+                name: status.name,
+                rx_bytes: status.recv_bytes,
+                tx_bytes: status.sent_bytes,
+                rx_errors: status.recv_errs,
+                tx_errors: status.sent_errs,
+            })
+            .collect();
+        interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+        interfaces.truncate(MAX_REPORTED_INTERFACES);
+
+        Ok(DaemonSystemMetrics {
+            cpu_percent,
+            memory_used_bytes: memory.mem_total.saturating_sub(memory_free),
+            memory_total_bytes: memory.mem_total,
+            disk_used_bytes,
+            disk_total_bytes,
+            interfaces,
+        })
+    }
+}
+
+/// Used and total bytes of the filesystem holding `path`
+#[cfg(target_os = "linux")]
+#[allow(clippy::unnecessary_cast)] // The statvfs fields are narrower on 32-bit targets
+fn disk_usage(path: &str) -> anyhow::Result<(u64, u64)> {
+    use std::ffi::CString;
+
+    let path = CString::new(path)?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    let block_size = stat.f_frsize as u64;
+    let total = stat.f_blocks as u64 * block_size;
+    let free = stat.f_bfree as u64 * block_size;
+    Ok((total.saturating_sub(free), total))
+}
@@ -39,7 +39,7 @@ pub async fn create_handler(
     );
 
     let service = ApiKey::get_service(&state);
-    let api_key = service.create(api_key).await.map_err(|e| {
+    let (api_key, key) = service.create(api_key).await.map_err(|e| {
         tracing::error!(
             error = %e,
             user_id = %user.user_id,
@@ -55,10 +55,7 @@ pub async fn create_handler(
         "API key created via API (key shown to user)"
     );
 
-    Ok(Json(ApiResponse::success(ApiKeyResponse {
-        key: api_key.base.key.clone(),
-        api_key,
-    })))
+    Ok(Json(ApiResponse::success(ApiKeyResponse { key, api_key })))
 }
 
 pub async fn rotate_key_handler(
@@ -129,7 +126,7 @@ pub async fn update_handler(
         })?;
 
     // Preserve the key - don't allow it to be changed via update
-    request.base.key = existing.base.key;
+    request.base.key_hash = existing.base.key_hash;
 
     let updated = service.update(&mut request).await.map_err(|e| {
         tracing::error!(
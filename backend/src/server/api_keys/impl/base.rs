@@ -6,13 +6,18 @@ use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiKeyBase {
-    #[serde(serialize_with = "serialize_api_key_status")]
-    pub key: String,
+    /// SHA-256 of the key. The key itself is only returned once, when it's issued.
+    #[serde(rename = "key", serialize_with = "serialize_api_key_status")]
+    pub key_hash: String,
     pub name: String,
     pub last_used: Option<DateTime<Utc>>,
     pub expires_at: Option<DateTime<Utc>>,
     pub network_id: Uuid,
     pub is_enabled: bool,
+    /// When the key was last rotated. The key's age is counted from here, or from its creation
+    /// if it was never rotated.
+    #[serde(default)]
+    pub rotated_at: Option<DateTime<Utc>>,
 }
 
 fn serialize_api_key_status<S>(_key: &String, serializer: S) -> Result<S::Ok, S::Error>
@@ -31,6 +36,13 @@ pub struct ApiKey {
     pub base: ApiKeyBase,
 }
 
+impl ApiKey {
+    /// When the current key value was issued
+    pub fn issued_at(&self) -> DateTime<Utc> {
+        self.base.rotated_at.unwrap_or(self.created_at)
+    }
+}
+
 impl Display for ApiKey {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}: {}", self.base.name, self.id)
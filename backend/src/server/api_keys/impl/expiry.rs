@@ -0,0 +1,117 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::server::{api_keys::r#impl::base::ApiKey, config::ServerConfig};
+
+/// How long API keys may be used for, and when their users are warned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApiKeyExpiryPolicy {
+    /// Age at which every key expires, counted from when it was last issued. Without one, keys
+    /// without an explicit expiry never expire.
+    pub max_age: Option<Duration>,
+    /// How long before expiry daemons and subscribers start being warned
+    pub warn_before: Duration,
+}
+
+impl Default for ApiKeyExpiryPolicy {
+    fn default() -> Self {
+        Self {
+            max_age: None,
+            warn_before: Duration::days(14),
+        }
+    }
+}
+
+impl From<&ServerConfig> for ApiKeyExpiryPolicy {
+    fn from(config: &ServerConfig) -> Self {
+        Self {
+            max_age: config
+                .api_key_max_age_days
+                .map(|days| Duration::days(days as i64)),
+            warn_before: Duration::days(config.api_key_expiry_warning_days as i64),
+        }
+    }
+}
+
+/// Sent to a daemon whose key is about to expire, so it can be rotated in time
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ApiKeyExpiryWarning {
+    pub api_key_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+    pub days_left: i64,
+}
+
+impl ApiKeyExpiryPolicy {
+    /// When `key` expires: its own expiry, or its max age under the policy, whichever is sooner
+    pub fn expires_at(&self, key: &ApiKey) -> Option<DateTime<Utc>> {
+        let aged_out = self.max_age.map(|max_age| key.issued_at() + max_age);
+
+        match (key.base.expires_at, aged_out) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    pub fn is_expired(&self, key: &ApiKey, now: DateTime<Utc>) -> bool {
+        self.expires_at(key)
+            .is_some_and(|expires_at| now >= expires_at)
+    }
+
+    /// A warning when `key` expires within the warning window
+    pub fn warning(&self, key: &ApiKey, now: DateTime<Utc>) -> Option<ApiKeyExpiryWarning> {
+        let expires_at = self.expires_at(key).filter(|e| *e > now)?;
+
+        (expires_at - now <= self.warn_before).then(|| ApiKeyExpiryWarning {
+            api_key_id: key.id,
+            expires_at,
+            days_left: (expires_at - now).num_days(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{
+        api_keys::r#impl::base::ApiKeyBase, shared::storage::traits::StorableEntity,
+    };
+
+    fn key(expires_at: Option<DateTime<Utc>>) -> ApiKey {
+        ApiKey::new(ApiKeyBase {
+            key_hash: String::new(),
+            name: "Daemon key".to_string(),
+            last_used: None,
+            expires_at,
+            network_id: Uuid::new_v4(),
+            is_enabled: true,
+            rotated_at: None,
+        })
+    }
+
+    #[test]
+    fn test_max_age_counts_from_issue() {
+        let now = Utc::now();
+        let policy = ApiKeyExpiryPolicy {
+            max_age: Some(Duration::days(90)),
+            ..Default::default()
+        };
+
+        // A legacy key without an expiry only expires under a max age
+        let mut legacy = key(None);
+        legacy.created_at = now - Duration::days(91);
+        assert!(!ApiKeyExpiryPolicy::default().is_expired(&legacy, now));
+        assert!(policy.is_expired(&legacy, now));
+
+        // Rotation restarts the clock
+        legacy.base.rotated_at = Some(now - Duration::days(80));
+        assert!(!policy.is_expired(&legacy, now));
+        let warning = policy.warning(&legacy, now).unwrap();
+        assert_eq!(warning.days_left, 10);
+
+        // An explicit expiry sooner than the max age wins
+        let soon = key(Some(now + Duration::days(30)));
+        assert_eq!(policy.expires_at(&soon), soon.base.expires_at);
+        assert!(policy.warning(&soon, now).is_none());
+    }
+}
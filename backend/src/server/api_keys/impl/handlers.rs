@@ -1,6 +1,6 @@
 use crate::server::api_keys::r#impl::base::ApiKey;
 use crate::server::api_keys::service::ApiKeyService;
-use crate::server::shared::handlers::traits::CrudHandlers;
+use crate::server::shared::handlers::traits::{CrudHandlers, EntityScope};
 
 impl CrudHandlers for ApiKey {
     type Service = ApiKeyService;
@@ -8,4 +8,8 @@ impl CrudHandlers for ApiKey {
     fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
         &state.services.api_key_service
     }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.base.network_id)
+    }
 }
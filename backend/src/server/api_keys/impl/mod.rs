@@ -1,4 +1,5 @@
 pub mod api;
 pub mod base;
+pub mod expiry;
 pub mod handlers;
 pub mod storage;
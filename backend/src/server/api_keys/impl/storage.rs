@@ -53,12 +53,13 @@ impl StorableEntity for ApiKey {
             updated_at,
             base:
                 Self::BaseData {
-                    key,
+                    key_hash,
                     name,
                     last_used,
                     expires_at,
                     network_id,
                     is_enabled,
+                    rotated_at,
                 },
         } = self.clone();
 
@@ -72,7 +73,8 @@ impl StorableEntity for ApiKey {
                 "network_id",
                 "name",
                 "is_enabled",
-                "key",
+                "key_hash",
+                "rotated_at",
             ],
             vec![
                 SqlValue::Uuid(id),
@@ -83,7 +85,8 @@ impl StorableEntity for ApiKey {
                 SqlValue::Uuid(network_id),
                 SqlValue::String(name),
                 SqlValue::Bool(is_enabled),
-                SqlValue::String(key),
+                SqlValue::String(key_hash),
+                SqlValue::OptionTimestamp(rotated_at),
             ],
         ))
     }
@@ -97,9 +100,10 @@ impl StorableEntity for ApiKey {
                 last_used: row.get("last_used"),
                 expires_at: row.get("expires_at"),
                 name: row.get("name"),
-                key: row.get("key"),
+                key_hash: row.get("key_hash"),
                 is_enabled: row.get("is_enabled"),
                 network_id: row.get("network_id"),
+                rotated_at: row.get("rotated_at"),
             },
         })
     }
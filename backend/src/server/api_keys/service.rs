@@ -1,21 +1,40 @@
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
-use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgConnection;
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex, OnceLock},
+};
 use uuid::Uuid;
 
 use crate::server::{
-    api_keys::r#impl::base::{ApiKey, ApiKeyBase},
+    api_keys::r#impl::{
+        base::{ApiKey, ApiKeyBase},
+        expiry::{ApiKeyExpiryPolicy, ApiKeyExpiryWarning},
+    },
+    notifications::{
+        r#impl::base::{Notification, NotificationKind},
+        service::NotificationService,
+    },
     shared::{
         services::traits::CrudService,
         storage::{
+            filter::EntityFilter,
             generic::GenericPostgresStorage,
             traits::{StorableEntity, Storage},
         },
+        sync::lock,
     },
 };
 
 pub struct ApiKeyService {
     storage: Arc<GenericPostgresStorage<ApiKey>>,
+    policy: ApiKeyExpiryPolicy,
+    notification_service: OnceLock<Arc<NotificationService>>,
+    /// Keys already notified about as expiring, so each warning goes out once per issue
+    warned: Mutex<HashSet<Uuid>>,
 }
 
 #[async_trait]
@@ -26,15 +45,80 @@ impl CrudService<ApiKey> for ApiKeyService {
 }
 
 impl ApiKeyService {
-    pub fn new(storage: Arc<GenericPostgresStorage<ApiKey>>) -> Self {
-        Self { storage }
+    pub fn new(storage: Arc<GenericPostgresStorage<ApiKey>>, policy: ApiKeyExpiryPolicy) -> Self {
+        Self {
+            storage,
+            policy,
+            notification_service: OnceLock::new(),
+            warned: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn set_notification_service(
+        &self,
+        notification_service: Arc<NotificationService>,
+    ) -> Result<(), Arc<NotificationService>> {
+        self.notification_service.set(notification_service)
+    }
+
+    pub fn policy(&self) -> &ApiKeyExpiryPolicy {
+        &self.policy
+    }
+
+    /// Warning for a key presented by a daemon that is close to expiry. The first time a key
+    /// enters the warning window its network is notified too.
+    pub fn check_expiry(
+        &self,
+        api_key: &ApiKey,
+        now: DateTime<Utc>,
+    ) -> Option<ApiKeyExpiryWarning> {
+        let warning = self.policy.warning(api_key, now)?;
+
+        if lock(&self.warned).insert(api_key.id)
+            && let Some(notification_service) = self.notification_service.get()
+        {
+            notification_service.notify(Notification::new(
+                api_key.base.network_id,
+                NotificationKind::ApiKeyExpiring {
+                    api_key_id: api_key.id,
+                    name: api_key.base.name.clone(),
+                    expires_at: warning.expires_at,
+                },
+            ));
+        }
+
+        Some(warning)
     }
 
     pub fn generate_api_key(&self) -> String {
         Uuid::new_v4().simple().to_string()
     }
 
-    pub async fn create(&self, api_key: ApiKey) -> Result<ApiKey> {
+    pub fn hash_key(key: &str) -> String {
+        hex::encode(Sha256::digest(key.as_bytes()))
+    }
+
+    /// Look up the key whose hash is `key_hash`, as presented by a daemon
+    pub async fn get_by_api_key_hash(&self, key_hash: &str) -> Result<Option<ApiKey>> {
+        self.storage
+            .get_one(EntityFilter::unfiltered().api_key_hash(key_hash.to_string()))
+            .await
+    }
+
+    /// Issue a new key, returning the stored record and the plaintext key (shown only once)
+    pub async fn create(&self, api_key: ApiKey) -> Result<(ApiKey, String)> {
+        let mut tx = self.storage.begin().await?;
+        let created = self.create_in(&mut tx, api_key).await?;
+        tx.commit().await?;
+        Ok(created)
+    }
+
+    /// [`Self::create`] on the caller's connection, so the key only exists if the caller commits
+    pub async fn create_in(
+        &self,
+        conn: &mut PgConnection,
+        api_key: ApiKey,
+    ) -> Result<(ApiKey, String)> {
         let key = self.generate_api_key();
 
         tracing::debug!(
@@ -44,15 +128,16 @@ impl ApiKeyService {
         );
 
         let api_key = ApiKey::new(ApiKeyBase {
-            key: key.clone(),
+            key_hash: Self::hash_key(&key),
             name: api_key.base.name,
             last_used: None,
             expires_at: api_key.base.expires_at,
             network_id: api_key.base.network_id,
             is_enabled: true,
+            rotated_at: None,
         });
 
-        let created = self.storage.create(&api_key).await?;
+        let created = self.storage.create_in(conn, &api_key).await?;
 
         tracing::info!(
             api_key_id = %created.id,
@@ -61,7 +146,7 @@ impl ApiKeyService {
             "API key created"
         );
 
-        Ok(created)
+        Ok((created, key))
     }
 
     pub async fn rotate_key(&self, api_key_id: Uuid) -> Result<String> {
@@ -73,9 +158,21 @@ impl ApiKeyService {
         if let Some(mut api_key) = self.get_by_id(&api_key_id).await? {
             let new_key = self.generate_api_key();
 
-            api_key.base.key = new_key.clone();
+            // A fresh key gets a fresh lifetime. A key that already expired is re-enabled, as
+            // expiry is what disabled it.
+            let now = Utc::now();
+            if self.policy.is_expired(&api_key, now) {
+                api_key.base.is_enabled = true;
+            }
+            api_key.base.expires_at = api_key
+                .base
+                .expires_at
+                .map(|expires_at| now + (expires_at - api_key.issued_at()));
+            api_key.base.rotated_at = Some(now);
+            api_key.base.key_hash = Self::hash_key(&new_key);
 
             self.update(&mut api_key).await?;
+            lock(&self.warned).remove(&api_key.id);
 
             tracing::info!(
                 api_key_id = %api_key_id,
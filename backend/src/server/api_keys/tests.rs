@@ -0,0 +1,174 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{Request, StatusCode, header},
+};
+use chrono::{Duration, Utc};
+use serial_test::serial;
+
+use crate::{
+    server::{
+        api_keys::{
+            r#impl::base::{ApiKey, ApiKeyBase},
+            service::ApiKeyService,
+        },
+        auth::middleware::{API_KEY_HEADER, AuthenticatedDaemon},
+        config::{AppState, ServerConfig},
+        shared::{
+            services::traits::CrudService, storage::traits::StorableEntity, types::api::ApiError,
+        },
+    },
+    tests::*,
+};
+
+async fn authenticate(
+    state: &std::sync::Arc<AppState>,
+    headers: &[(&str, &[u8])],
+) -> Result<uuid::Uuid, StatusCode> {
+    let mut request = Request::builder();
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let (mut parts, _) = request.body(()).unwrap().into_parts();
+
+    AuthenticatedDaemon::from_request_parts(&mut parts, state)
+        .await
+        .map(|AuthenticatedDaemon(network_id)| network_id)
+        .map_err(|e| ApiError::from(e).status)
+}
+
+#[tokio::test]
+#[serial]
+async fn test_daemon_resolved_by_api_key_hash() {
+    let (state, _container) = test_app_state().await;
+    let services = &state.services;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+
+    let (api_key, key) = services
+        .api_key_service
+        .create(ApiKey::new(ApiKeyBase {
+            key_hash: String::new(),
+            name: "Test key".to_string(),
+            last_used: None,
+            expires_at: None,
+            network_id: network.id,
+            is_enabled: true,
+            rotated_at: None,
+        }))
+        .await
+        .unwrap();
+
+    // Only the hash is kept
+    assert_ne!(api_key.base.key_hash, key);
+    assert_eq!(api_key.base.key_hash, ApiKeyService::hash_key(&key));
+    let found = services
+        .api_key_service
+        .get_by_api_key_hash(&ApiKeyService::hash_key(&key))
+        .await
+        .unwrap();
+    assert_eq!(found.map(|k| k.id), Some(api_key.id));
+
+    let bearer = format!("Bearer {}", key);
+    assert_eq!(
+        authenticate(
+            &state,
+            &[(header::AUTHORIZATION.as_str(), bearer.as_bytes())]
+        )
+        .await,
+        Ok(network.id)
+    );
+    assert_eq!(
+        authenticate(&state, &[(API_KEY_HEADER, key.as_bytes())]).await,
+        Ok(network.id)
+    );
+
+    // Presenting the stored hash itself must not work
+    let hash = api_key.base.key_hash.as_bytes();
+    let rejected: [&[(&str, &[u8])]; 6] = [
+        &[(API_KEY_HEADER, b"not-a-key")],
+        &[(API_KEY_HEADER, hash)],
+        &[(header::AUTHORIZATION.as_str(), b"Bearer not-a-key")],
+        &[(header::AUTHORIZATION.as_str(), b"Bearer ")],
+        &[(API_KEY_HEADER, b"\xff\xfe")],
+        &[],
+    ];
+    for headers in rejected {
+        assert_eq!(
+            authenticate(&state, headers).await,
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_expired_keys_rejected() {
+    let (state, _container) = test_app_state_with_config(ServerConfig {
+        api_key_max_age_days: Some(90),
+        ..Default::default()
+    })
+    .await;
+    let services = &state.services;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+
+    let create = |name: &str, expires_at| {
+        services.api_key_service.create(ApiKey::new(ApiKeyBase {
+            key_hash: String::new(),
+            name: name.to_string(),
+            last_used: None,
+            expires_at,
+            network_id: network.id,
+            is_enabled: true,
+            rotated_at: None,
+        }))
+    };
+    let (_, expired) = create("Expired", Some(Utc::now() - Duration::minutes(1)))
+        .await
+        .unwrap();
+    let (_, current) = create("Current", Some(Utc::now() + Duration::days(30)))
+        .await
+        .unwrap();
+    let (_, legacy) = create("Legacy", None).await.unwrap();
+    let (mut aged, aged_key) = create("Aged", None).await.unwrap();
+    aged.created_at = Utc::now() - Duration::days(91);
+    services.api_key_service.update(&mut aged).await.unwrap();
+
+    assert_eq!(
+        authenticate(&state, &[(API_KEY_HEADER, expired.as_bytes())]).await,
+        Err(StatusCode::UNAUTHORIZED)
+    );
+    assert_eq!(
+        authenticate(&state, &[(API_KEY_HEADER, current.as_bytes())]).await,
+        Ok(network.id)
+    );
+
+    // Keys without an expiry of their own only expire once past the max age, counted from
+    // their creation
+    assert_eq!(
+        authenticate(&state, &[(API_KEY_HEADER, legacy.as_bytes())]).await,
+        Ok(network.id)
+    );
+    assert_eq!(
+        authenticate(&state, &[(API_KEY_HEADER, aged_key.as_bytes())]).await,
+        Err(StatusCode::UNAUTHORIZED)
+    );
+}
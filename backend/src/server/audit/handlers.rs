@@ -0,0 +1,78 @@
+use crate::server::{
+    audit::r#impl::api::{AuditExportFormat, AuditExportParams, AuditPage, AuditQuery},
+    auth::middleware::RequireAdmin,
+    config::AppState,
+    shared::{
+        handlers::{csv::csv_response, ndjson::ndjson_response},
+        storage::cursor::PageCursor,
+        types::api::{ApiError, ApiResponse, ApiResult},
+    },
+};
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    response::Response,
+    routing::get,
+};
+use std::sync::Arc;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_audit_events))
+        .route("/export", get(export_audit_events))
+}
+
+/// Reject a cursor the client made up or mangled, rather than failing the query with it
+fn check_cursor(query: &AuditQuery) -> ApiResult<()> {
+    if let Some(cursor) = &query.cursor {
+        cursor
+            .parse::<PageCursor>()
+            .map_err(|e| ApiError::bad_request(&e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// A page of the organization's audit trail, oldest first
+async fn get_audit_events(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(admin): RequireAdmin,
+    Query(query): Query<AuditQuery>,
+) -> ApiResult<Json<ApiResponse<AuditPage>>> {
+    check_cursor(&query)?;
+
+    let page = state
+        .services
+        .audit_service
+        .page(&admin.organization_id, &query)
+        .await?;
+
+    Ok(Json(ApiResponse::success(page)))
+}
+
+/// The whole of the organization's audit trail matching the query, streamed as NDJSON or CSV for
+/// bulk extraction
+async fn export_audit_events(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(admin): RequireAdmin,
+    Query(query): Query<AuditQuery>,
+    Query(params): Query<AuditExportParams>,
+) -> ApiResult<Response> {
+    check_cursor(&query)?;
+
+    let events = state
+        .services
+        .audit_service
+        .stream(&admin.organization_id, &query)?;
+
+    tracing::info!(
+        admin_id = %admin.user_id,
+        organization_id = %admin.organization_id,
+        format = ?params.format,
+        "Audit trail exported"
+    );
+
+    Ok(match params.format {
+        AuditExportFormat::Ndjson => ndjson_response(events),
+        AuditExportFormat::Csv => csv_response("audit-events.csv", events),
+    })
+}
@@ -0,0 +1,115 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::server::{
+    audit::r#impl::base::{AuditEvent, AuditEventKind},
+    shared::{
+        handlers::csv::CsvRow,
+        storage::{cursor::PageCursor, filter::EntityFilter},
+    },
+};
+
+pub const DEFAULT_AUDIT_PAGE_SIZE: usize = 100;
+pub const MAX_AUDIT_PAGE_SIZE: usize = 1000;
+
+/// Which audit events to list, oldest first. Every filter is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditQuery {
+    /// Events at or after this time
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>,
+    /// Events strictly before this time
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>,
+    /// Events by this user. For impersonated actions, the admin impersonating.
+    #[serde(default)]
+    pub actor: Option<Uuid>,
+    #[serde(default)]
+    pub kind: Option<AuditEventKind>,
+    /// `next_cursor` of the previous page, to continue after it
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Events per page, at most `MAX_AUDIT_PAGE_SIZE`
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    /// The query's filters for `organization_id`'s trail, pushed down to SQL
+    pub fn filter(&self, organization_id: &Uuid) -> Result<EntityFilter, anyhow::Error> {
+        let mut filter = EntityFilter::unfiltered().organization_id(organization_id);
+        if let Some(from) = self.from {
+            filter = filter.created_from(from);
+        }
+        if let Some(to) = self.to {
+            filter = filter.created_before(to);
+        }
+        if let Some(actor) = &self.actor {
+            filter = filter.actor_id(actor);
+        }
+        if let Some(kind) = self.kind {
+            filter = filter.audit_event_kind(kind);
+        }
+        if let Some(cursor) = &self.cursor {
+            filter = filter.after(&cursor.parse::<PageCursor>()?);
+        }
+        Ok(filter)
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_AUDIT_PAGE_SIZE)
+            .clamp(1, MAX_AUDIT_PAGE_SIZE)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditPage {
+    pub events: Vec<AuditEvent>,
+    /// Pass as `cursor` to get the next page. Absent on the last page.
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditExportFormat {
+    #[default]
+    Ndjson,
+    Csv,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditExportParams {
+    #[serde(default)]
+    pub format: AuditExportFormat,
+}
+
+impl CsvRow for AuditEvent {
+    fn headers() -> &'static [&'static str] {
+        &[
+            "id",
+            "created_at",
+            "organization_id",
+            "actor_id",
+            "impersonated_user_id",
+            "kind",
+            "details",
+        ]
+    }
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.created_at.to_rfc3339(),
+            self.base.organization_id.to_string(),
+            self.base.actor_id.to_string(),
+            self.base
+                .impersonated_user_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            self.base.kind.to_string(),
+            self.base.details.to_string(),
+        ]
+    }
+}
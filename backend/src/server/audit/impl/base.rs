@@ -0,0 +1,51 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use strum::{Display as StrumDisplay, EnumString};
+use uuid::Uuid;
+
+/// What an audit event records
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, StrumDisplay, EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AuditEventKind {
+    ImpersonationStarted,
+    ImpersonationEnded,
+    /// A request made by an admin while impersonating another user
+    ImpersonatedRequest,
+    /// A daemon moved to another network, possibly with its discovered data
+    DaemonTransferred,
+}
+
+/// An entry in an organization's audit trail. Never updated once written.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEventBase {
+    pub organization_id: Uuid,
+    /// The user who acted. When impersonating, the admin rather than the user impersonated.
+    pub actor_id: Uuid,
+    pub impersonated_user_id: Option<Uuid>,
+    pub kind: AuditEventKind,
+    pub details: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub base: AuditEventBase,
+}
+
+impl Display for AuditEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Audit event {} by {}: {}",
+            self.base.kind, self.base.actor_id, self.id
+        )
+    }
+}
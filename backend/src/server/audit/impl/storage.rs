@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+use uuid::Uuid;
+
+use crate::server::{
+    audit::r#impl::base::{AuditEvent, AuditEventBase},
+    shared::storage::traits::{SqlValue, StorableEntity},
+};
+
+impl StorableEntity for AuditEvent {
+    type BaseData = AuditEventBase;
+
+    fn table_name() -> &'static str {
+        "audit_events"
+    }
+
+    fn get_base(&self) -> Self::BaseData {
+        self.base.clone()
+    }
+
+    fn new(base: Self::BaseData) -> Self {
+        let now = chrono::Utc::now();
+
+        Self {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: now,
+            base,
+        }
+    }
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn set_updated_at(&mut self, time: DateTime<Utc>) {
+        self.updated_at = time;
+    }
+
+    fn to_params(&self) -> Result<(Vec<&'static str>, Vec<SqlValue>), anyhow::Error> {
+        let Self {
+            id,
+            created_at,
+            updated_at,
+            base:
+                Self::BaseData {
+                    organization_id,
+                    actor_id,
+                    impersonated_user_id,
+                    kind,
+                    details,
+                },
+        } = self.clone();
+
+        Ok((
+            vec![
+                "id",
+                "created_at",
+                "updated_at",
+                "organization_id",
+                "actor_id",
+                "impersonated_user_id",
+                "kind",
+                "details",
+            ],
+            vec![
+                SqlValue::Uuid(id),
+                SqlValue::Timestamp(created_at),
+                SqlValue::Timestamp(updated_at),
+                SqlValue::Uuid(organization_id),
+                SqlValue::Uuid(actor_id),
+                SqlValue::OptionalUuid(impersonated_user_id),
+                SqlValue::String(kind.to_string()),
+                SqlValue::Json(details),
+            ],
+        ))
+    }
+
+    fn from_row(row: &PgRow) -> Result<Self, anyhow::Error> {
+        let kind = row
+            .get::<String, _>("kind")
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Failed to parse audit event kind: {}", e))?;
+
+        Ok(AuditEvent {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            base: AuditEventBase {
+                organization_id: row.get("organization_id"),
+                actor_id: row.get("actor_id"),
+                impersonated_user_id: row.get("impersonated_user_id"),
+                kind,
+                details: row.get("details"),
+            },
+        })
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, stream::BoxStream};
+use sqlx::PgConnection;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::server::{
+    audit::r#impl::{
+        api::{AuditPage, AuditQuery},
+        base::{AuditEvent, AuditEventBase, AuditEventKind},
+    },
+    shared::{
+        services::traits::CrudService,
+        storage::{
+            cursor::{PageCursor, STREAM_PAGE_SIZE, paginate},
+            filter::EntityFilter,
+            generic::GenericPostgresStorage,
+            traits::{StorableEntity, Storage},
+        },
+    },
+};
+
+pub struct AuditService {
+    storage: Arc<GenericPostgresStorage<AuditEvent>>,
+}
+
+#[async_trait]
+impl CrudService<AuditEvent> for AuditService {
+    fn storage(&self) -> &Arc<GenericPostgresStorage<AuditEvent>> {
+        &self.storage
+    }
+}
+
+impl AuditService {
+    pub fn new(storage: Arc<GenericPostgresStorage<AuditEvent>>) -> Self {
+        Self { storage }
+    }
+
+    /// Append an event to the trail. Record before taking the action audited, and don't take it if
+    /// this fails, so nothing audited happens without a record of it.
+    pub async fn record(&self, event: AuditEventBase) -> Result<()> {
+        if let Err(e) = self.storage.create(&AuditEvent::new(event.clone())).await {
+            tracing::error!(
+                organization_id = %event.organization_id,
+                actor_id = %event.actor_id,
+                impersonated_user_id = ?event.impersonated_user_id,
+                kind = %event.kind,
+                details = %event.details,
+                "Failed to record audit event: {}",
+                e
+            );
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Append an event as part of the caller's transaction, so it is only kept if the change it
+    /// records is. Unlike [`Self::record`], failures are returned so the change can be rolled back.
+    pub async fn record_in(&self, conn: &mut PgConnection, event: AuditEventBase) -> Result<()> {
+        self.storage
+            .create_in(conn, &AuditEvent::new(event))
+            .await?;
+        Ok(())
+    }
+
+    /// Events of `kind` taken by `actor_id` since `since`, oldest first
+    pub async fn events_by_actor_since(
+        &self,
+        actor_id: &Uuid,
+        kind: AuditEventKind,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<AuditEvent>> {
+        self.storage
+            .get_all(
+                EntityFilter::unfiltered()
+                    .actor_id(actor_id)
+                    .audit_event_kind(kind)
+                    .created_from(since),
+            )
+            .await
+    }
+
+    pub async fn events_for_organization(&self, organization_id: &Uuid) -> Result<Vec<AuditEvent>> {
+        self.storage
+            .get_all(EntityFilter::unfiltered().organization_id(organization_id))
+            .await
+    }
+
+    /// One page of `organization_id`'s events matching `query`, oldest first
+    pub async fn page(&self, organization_id: &Uuid, query: &AuditQuery) -> Result<AuditPage> {
+        let limit = query.page_size();
+        // One extra row says whether there's a next page without another round trip
+        let mut events = self
+            .storage
+            .get_page(query.filter(organization_id)?, None, limit + 1)
+            .await?;
+
+        let next_cursor = if events.len() > limit {
+            events.truncate(limit);
+            events.last().map(|e| PageCursor::of(e).to_string())
+        } else {
+            None
+        };
+
+        Ok(AuditPage {
+            events,
+            next_cursor,
+        })
+    }
+
+    /// Every event of `organization_id`'s matching `query`, oldest first, fetched a page at a
+    /// time so exporting a long range holds at most one page in memory. `query.limit` is ignored.
+    pub fn stream(
+        &self,
+        organization_id: &Uuid,
+        query: &AuditQuery,
+    ) -> Result<BoxStream<'static, Result<AuditEvent>>> {
+        let filter = query.filter(organization_id)?;
+        let storage = self.storage.clone();
+        Ok(paginate(STREAM_PAGE_SIZE, PageCursor::of, move |after| {
+            let storage = storage.clone();
+            let filter = filter.clone();
+            async move { storage.get_page(filter, after, STREAM_PAGE_SIZE).await }
+        })
+        .boxed())
+    }
+}
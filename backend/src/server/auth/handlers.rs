@@ -1,16 +1,21 @@
 use crate::server::{
     api_keys,
     auth::{
+        impersonation::{Impersonation, ImpersonationError, Impersonations},
         r#impl::api::{
             ForgotPasswordRequest, LoginRequest, OidcAuthorizeParams, OidcCallbackParams,
-            RegisterRequest, ResetPasswordRequest, UpdateEmailPasswordRequest,
+            RegisterRequest, ResetPasswordRequest, RevokeAllSessionsRequest,
+            RevokeAllSessionsResponse, UpdateEmailPasswordRequest,
         },
+        middleware::{AuthenticatedUser, NotImpersonated, RequireAdmin},
         oidc::OidcPendingAuth,
         service::hash_password,
+        sessions::SessionLimitReached,
     },
     config::AppState,
     organizations::handlers::process_pending_invite,
     shared::{
+        extractors::LimitedJson,
         services::traits::CrudService,
         types::api::{ApiError, ApiResponse, ApiResult},
     },
@@ -32,16 +37,33 @@ pub fn create_router() -> Router<Arc<AppState>> {
         .route("/register", post(register))
         .route("/login", post(login))
         .route("/logout", post(logout))
+        .route("/sessions/revoke-all", post(revoke_all_sessions))
         .route("/me", post(get_current_user))
         .nest("/keys", api_keys::handlers::create_router())
         .route("/update", post(update_password_auth))
         .route("/oidc/authorize", get(oidc_authorize))
         .route("/oidc/callback", get(oidc_callback))
         .route("/oidc/unlink", post(unlink_oidc_account))
+        .route("/impersonation", get(get_impersonation))
+        .route("/impersonation/end", post(end_impersonation))
         .route("/forgot-password", post(forgot_password))
         .route("/reset-password", post(reset_password))
 }
 
+/// Log `user` in on `session`, subject to the per-user session limit
+async fn start_session(state: &AppState, session: &Session, user: &User) -> ApiResult<()> {
+    state
+        .services
+        .auth_service
+        .sessions
+        .start(session, user.id)
+        .await
+        .map_err(|e| match e.downcast_ref::<SessionLimitReached>() {
+            Some(limit) => ApiError::conflict(&limit.to_string()),
+            None => ApiError::internal_error(&format!("Failed to save session: {}", e)),
+        })
+}
+
 async fn register(
     State(state): State<Arc<AppState>>,
     session: Session,
@@ -68,10 +90,7 @@ async fn register(
         .register(request, org_id, permissions)
         .await?;
 
-    session
-        .insert("user_id", user.id)
-        .await
-        .map_err(|e| ApiError::internal_error(&format!("Failed to save session: {}", e)))?;
+    start_session(&state, &session, &user).await?;
 
     Ok(Json(ApiResponse::success(user)))
 }
@@ -83,33 +102,77 @@ async fn login(
 ) -> ApiResult<Json<ApiResponse<User>>> {
     let user = state.services.auth_service.login(request).await?;
 
-    session
-        .insert("user_id", user.id)
-        .await
-        .map_err(|e| ApiError::internal_error(&format!("Failed to save session: {}", e)))?;
+    start_session(&state, &session, &user).await?;
 
     Ok(Json(ApiResponse::success(user)))
 }
 
-async fn logout(session: Session) -> ApiResult<Json<ApiResponse<()>>> {
-    session
-        .delete()
+async fn logout(
+    State(state): State<Arc<AppState>>,
+    session: Session,
+) -> ApiResult<Json<ApiResponse<()>>> {
+    state
+        .services
+        .auth_service
+        .sessions
+        .end(&session)
         .await
         .map_err(|e| ApiError::internal_error(&format!("Failed to delete session: {}", e)))?;
 
     Ok(Json(ApiResponse::success(())))
 }
 
-async fn get_current_user(
+/// Log every user in the admin's organization out, e.g. after a security incident. Each user's
+/// revocation time is compared with the session's start at auth time, so the session store
+/// doesn't need enumerating.
+async fn revoke_all_sessions(
     State(state): State<Arc<AppState>>,
+    RequireAdmin(admin): RequireAdmin,
     session: Session,
-) -> ApiResult<Json<ApiResponse<User>>> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| ApiError::internal_error(&format!("Failed to read session: {}", e)))?
-        .ok_or_else(|| ApiError::unauthorized("Not authenticated".to_string()))?;
+    request: Option<LimitedJson<RevokeAllSessionsRequest>>,
+) -> ApiResult<Json<ApiResponse<RevokeAllSessionsResponse>>> {
+    if admin.impersonated_by.is_some() {
+        return Err(ApiError::forbidden(
+            &ImpersonationError::Forbidden.to_string(),
+        ));
+    }
+
+    let request = request.map(|LimitedJson(r)| r).unwrap_or_default();
+    let sessions = &state.services.auth_service.sessions;
+
+    let users = state
+        .services
+        .user_service
+        .revoke_sessions(&admin.organization_id)
+        .await?;
+
+    // Free tracked session slots too, so revoked sessions don't count towards session limits
+    let keep = session.id().filter(|_| request.preserve_current_session);
+    for user in &users {
+        let keep = keep.filter(|_| user.id == admin.user_id);
+        sessions.end_all(&user.id, keep).await?;
+    }
 
+    if keep.is_some() {
+        sessions.renew(&session).await?;
+    }
+
+    tracing::warn!(
+        admin_id = %admin.user_id,
+        organization_id = %admin.organization_id,
+        preserved_current_session = keep.is_some(),
+        "All sessions revoked"
+    );
+
+    Ok(Json(ApiResponse::success(RevokeAllSessionsResponse {
+        users: users.len(),
+    })))
+}
+
+async fn get_current_user(
+    State(state): State<Arc<AppState>>,
+    AuthenticatedUser { user_id, .. }: AuthenticatedUser,
+) -> ApiResult<Json<ApiResponse<User>>> {
     let user = state
         .services
         .user_service
@@ -120,17 +183,36 @@ async fn get_current_user(
     Ok(Json(ApiResponse::success(user)))
 }
 
-async fn update_password_auth(
+/// The impersonation in progress on the caller's session, if any
+async fn get_impersonation(
+    _user: AuthenticatedUser,
+    session: Session,
+) -> ApiResult<Json<ApiResponse<Option<Impersonation>>>> {
+    Ok(Json(ApiResponse::success(
+        Impersonations::current(&session).await?,
+    )))
+}
+
+/// Stop impersonating and hand the session back to the admin
+async fn end_impersonation(
     State(state): State<Arc<AppState>>,
     session: Session,
+) -> ApiResult<Json<ApiResponse<Option<Impersonation>>>> {
+    let ended = state
+        .services
+        .auth_service
+        .impersonations
+        .end(&session, "ended")
+        .await?;
+
+    Ok(Json(ApiResponse::success(ended)))
+}
+
+async fn update_password_auth(
+    State(state): State<Arc<AppState>>,
+    NotImpersonated(AuthenticatedUser { user_id, .. }): NotImpersonated,
     Json(request): Json<UpdateEmailPasswordRequest>,
 ) -> ApiResult<Json<ApiResponse<User>>> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| ApiError::internal_error(&format!("Failed to read session: {}", e)))?
-        .ok_or_else(|| ApiError::unauthorized("Not authenticated".to_string()))?;
-
     let mut user = state
         .services
         .user_service
@@ -175,10 +257,7 @@ async fn reset_password(
         .complete_password_reset(&request.token, &request.password)
         .await?;
 
-    session
-        .insert("user_id", user.id)
-        .await
-        .map_err(|e| ApiError::internal_error(&format!("Failed to save session: {}", e)))?;
+    start_session(&state, &session, &user).await?;
 
     Ok(Json(ApiResponse::success(user)))
 }
@@ -194,6 +273,13 @@ async fn oidc_authorize(
         .as_ref()
         .ok_or_else(|| ApiError::internal_error("OIDC not configured"))?;
 
+    let is_linking = params.link.unwrap_or(false);
+    if is_linking && Impersonations::current(&session).await?.is_some() {
+        return Err(ApiError::forbidden(
+            &ImpersonationError::Forbidden.to_string(),
+        ));
+    }
+
     let (auth_url, pending_auth) = oidc_service
         .authorize_url()
         .await
@@ -205,7 +291,7 @@ async fn oidc_authorize(
         .await
         .map_err(|e| ApiError::internal_error(&format!("Failed to save session: {}", e)))?;
     session
-        .insert("oidc_is_linking", params.link.unwrap_or(false))
+        .insert("oidc_is_linking", is_linking)
         .await
         .map_err(|e| ApiError::internal_error(&format!("Failed to save session: {}", e)))?;
     session
@@ -339,12 +425,12 @@ async fn oidc_callback(
             .await
         {
             Ok(user) => {
-                if let Err(e) = session.insert("user_id", user.id).await {
-                    tracing::error!("Failed to save session: {}", e);
+                if let Err(e) = start_session(&state, &session, &user).await {
+                    tracing::error!("Failed to save session: {}", e.message);
                     return Err(Redirect::to(&format!(
                         "{}?error={}",
                         return_url,
-                        urlencoding::encode(&format!("Failed to create session: {}", e))
+                        urlencoding::encode(&format!("Failed to create session: {}", e.message))
                     )));
                 }
 
@@ -369,7 +455,7 @@ async fn oidc_callback(
 
 async fn unlink_oidc_account(
     State(state): State<Arc<AppState>>,
-    session: Session,
+    NotImpersonated(AuthenticatedUser { user_id, .. }): NotImpersonated,
 ) -> ApiResult<Json<ApiResponse<User>>> {
     let oidc_service = state
         .services
@@ -377,12 +463,6 @@ async fn unlink_oidc_account(
         .as_ref()
         .ok_or_else(|| ApiError::internal_error("OIDC not configured"))?;
 
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| ApiError::internal_error(&format!("Failed to read session: {}", e)))?
-        .ok_or_else(|| ApiError::unauthorized("Not authenticated".to_string()))?;
-
     let updated_user = oidc_service
         .unlink_from_user(&user_id)
         .await
@@ -0,0 +1,315 @@
+use anyhow::Result;
+use axum::{
+    extract::{Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::server::{
+    audit::{
+        r#impl::base::{AuditEventBase, AuditEventKind},
+        service::AuditService,
+    },
+    auth::middleware::AuthenticatedUser,
+    config::{AppState, ServerConfig},
+    shared::types::api::ApiError,
+    users::r#impl::base::User,
+};
+
+/// Session key holding the impersonation in progress on the session, if any
+const IMPERSONATION_KEY: &str = "impersonation";
+
+/// Set on responses to impersonated requests, to the id of the admin impersonating
+pub const IMPERSONATED_BY_HEADER: &str = "x-impersonated-by";
+
+/// An admin acting as another user of their organization on the admin's own session
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Impersonation {
+    pub impersonator_id: Uuid,
+    pub user_id: Uuid,
+    pub organization_id: Uuid,
+    pub started_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Impersonation {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ImpersonationError {
+    #[error("Impersonation can't be nested. End the current impersonation first.")]
+    Nested,
+    #[error("You can't impersonate yourself")]
+    SelfTarget,
+    #[error("You can only impersonate users with lower permissions than you")]
+    NotPermitted,
+    #[error("Disabled users can't be impersonated")]
+    Disabled,
+    #[error("Impersonation limit reached. Try again in {0} minutes.")]
+    RateLimited(i64),
+    #[error("This action can't be taken while impersonating another user")]
+    Forbidden,
+}
+
+/// How long impersonations last and how often admins may start them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImpersonationPolicy {
+    pub ttl: Duration,
+    pub max_per_hour: usize,
+}
+
+impl Default for ImpersonationPolicy {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::minutes(15),
+            max_per_hour: 5,
+        }
+    }
+}
+
+impl From<&ServerConfig> for ImpersonationPolicy {
+    fn from(config: &ServerConfig) -> Self {
+        Self {
+            ttl: Duration::seconds(config.impersonation_ttl_secs as i64),
+            max_per_hour: config.impersonation_max_per_hour,
+        }
+    }
+}
+
+/// Starts and ends impersonations, rate limiting them per admin and recording each in the
+/// audit trail. The trail is also what the rate limit counts, so it holds across restarts and
+/// instances.
+pub struct Impersonations {
+    policy: ImpersonationPolicy,
+    audit_service: Arc<AuditService>,
+    /// Serializes starts, so two at once can't both take an admin's last slot
+    starting: Mutex<()>,
+}
+
+impl Impersonations {
+    pub fn new(policy: ImpersonationPolicy, audit_service: Arc<AuditService>) -> Self {
+        Self {
+            policy,
+            audit_service,
+            starting: Mutex::new(()),
+        }
+    }
+
+    /// The impersonation in progress on `session`, expired or not
+    pub async fn current(session: &Session) -> Result<Option<Impersonation>> {
+        Ok(session.get(IMPERSONATION_KEY).await?)
+    }
+
+    /// Whether an admin who started impersonations at `starts` may start another at `now`
+    fn check_allowance(
+        &self,
+        starts: &[DateTime<Utc>],
+        now: DateTime<Utc>,
+    ) -> Result<(), ImpersonationError> {
+        let recent: Vec<&DateTime<Utc>> = starts
+            .iter()
+            .filter(|t| **t > now - Duration::hours(1))
+            .collect();
+
+        if recent.len() >= self.policy.max_per_hour {
+            let retry_at = recent
+                .iter()
+                .min()
+                .map(|t| **t + Duration::hours(1))
+                .unwrap_or(now + Duration::hours(1));
+            return Err(ImpersonationError::RateLimited(
+                (retry_at - now).num_minutes() + 1,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Switch `admin`'s `session` to act as `target` until the policy's TTL is up
+    pub async fn start(
+        &self,
+        session: &Session,
+        admin: &AuthenticatedUser,
+        target: &User,
+        now: DateTime<Utc>,
+    ) -> Result<Impersonation> {
+        if admin.impersonated_by.is_some() || Self::current(session).await?.is_some() {
+            return Err(ImpersonationError::Nested.into());
+        }
+        if target.id == admin.user_id {
+            return Err(ImpersonationError::SelfTarget.into());
+        }
+        if target.base.permissions >= admin.permissions {
+            return Err(ImpersonationError::NotPermitted.into());
+        }
+        if !target.base.enabled {
+            return Err(ImpersonationError::Disabled.into());
+        }
+
+        let _starting = self.starting.lock().await;
+        let starts: Vec<DateTime<Utc>> = self
+            .audit_service
+            .events_by_actor_since(
+                &admin.user_id,
+                AuditEventKind::ImpersonationStarted,
+                now - Duration::hours(1),
+            )
+            .await?
+            .into_iter()
+            .map(|e| e.created_at)
+            .collect();
+        self.check_allowance(&starts, now)?;
+
+        let impersonation = Impersonation {
+            impersonator_id: admin.user_id,
+            user_id: target.id,
+            organization_id: target.base.organization_id,
+            started_at: now,
+            expires_at: now + self.policy.ttl,
+        };
+        // Recorded first, so an impersonation that can't be audited never starts
+        self.audit_service
+            .record(AuditEventBase {
+                organization_id: impersonation.organization_id,
+                actor_id: impersonation.impersonator_id,
+                impersonated_user_id: Some(impersonation.user_id),
+                kind: AuditEventKind::ImpersonationStarted,
+                details: serde_json::json!({ "expires_at": impersonation.expires_at }),
+            })
+            .await?;
+
+        session.insert(IMPERSONATION_KEY, impersonation).await?;
+        session.insert("user_id", target.id).await?;
+        session.save().await?;
+
+        tracing::warn!(
+            admin_id = %admin.user_id,
+            user_id = %target.id,
+            expires_at = %impersonation.expires_at,
+            "Impersonation started"
+        );
+
+        Ok(impersonation)
+    }
+
+    /// Hand `session` back to the admin who was impersonating on it, if anyone was
+    pub async fn end(&self, session: &Session, reason: &str) -> Result<Option<Impersonation>> {
+        let Some(impersonation) = Self::current(session).await? else {
+            return Ok(None);
+        };
+        self.audit_service
+            .record(AuditEventBase {
+                organization_id: impersonation.organization_id,
+                actor_id: impersonation.impersonator_id,
+                impersonated_user_id: Some(impersonation.user_id),
+                kind: AuditEventKind::ImpersonationEnded,
+                details: serde_json::json!({ "reason": reason }),
+            })
+            .await?;
+
+        session.remove::<Impersonation>(IMPERSONATION_KEY).await?;
+        session
+            .insert("user_id", impersonation.impersonator_id)
+            .await?;
+        session.save().await?;
+
+        tracing::warn!(
+            admin_id = %impersonation.impersonator_id,
+            user_id = %impersonation.user_id,
+            reason = %reason,
+            "Impersonation ended"
+        );
+
+        Ok(Some(impersonation))
+    }
+}
+
+/// Record every request made while impersonating in the audit trail, against both the admin and
+/// the user impersonated, and mark its response as impersonated. Requests are recorded before
+/// they're handled, and refused if they can't be.
+pub async fn audit_impersonation(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let impersonation = match request.extensions().get::<Session>() {
+        Some(session) => Impersonations::current(session).await.ok().flatten(),
+        None => None,
+    };
+    let Some(impersonation) = impersonation else {
+        return next.run(request).await;
+    };
+
+    let recorded = state
+        .services
+        .audit_service
+        .record(AuditEventBase {
+            organization_id: impersonation.organization_id,
+            actor_id: impersonation.impersonator_id,
+            impersonated_user_id: Some(impersonation.user_id),
+            kind: AuditEventKind::ImpersonatedRequest,
+            details: serde_json::json!({
+                "method": request.method().as_str(),
+                "path": request.uri().path(),
+            }),
+        })
+        .await;
+    if recorded.is_err() {
+        return ApiError::internal_error("Impersonated request couldn't be audited")
+            .into_response();
+    }
+
+    let mut response = next.run(request).await;
+
+    if let Ok(value) = HeaderValue::from_str(&impersonation.impersonator_id.to_string()) {
+        response.headers_mut().insert(IMPERSONATED_BY_HEADER, value);
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::shared::storage::generic::GenericPostgresStorage;
+
+    #[tokio::test]
+    async fn test_starts_rate_limited_per_admin() {
+        // Only the allowance is exercised, which never touches storage
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let impersonations = Impersonations::new(
+            ImpersonationPolicy {
+                max_per_hour: 2,
+                ..Default::default()
+            },
+            Arc::new(AuditService::new(Arc::new(GenericPostgresStorage::new(
+                pool,
+            )))),
+        );
+        let now = Utc::now();
+
+        assert!(impersonations.check_allowance(&[], now).is_ok());
+        assert!(impersonations.check_allowance(&[now], now).is_ok());
+        assert_eq!(
+            impersonations.check_allowance(&[now, now], now + Duration::minutes(30)),
+            Err(ImpersonationError::RateLimited(31))
+        );
+
+        // Slots free up an hour after they were taken
+        assert!(
+            impersonations
+                .check_allowance(&[now, now], now + Duration::minutes(61))
+                .is_ok()
+        );
+    }
+}
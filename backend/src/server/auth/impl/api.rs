@@ -24,6 +24,32 @@ pub struct RegisterRequest {
     pub password: String,
 }
 
+/// Request to log everyone in the caller's organization out of all sessions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevokeAllSessionsRequest {
+    /// Keep the caller's current session so they aren't logged out too
+    #[serde(default = "default_preserve_current_session")]
+    pub preserve_current_session: bool,
+}
+
+fn default_preserve_current_session() -> bool {
+    true
+}
+
+impl Default for RevokeAllSessionsRequest {
+    fn default() -> Self {
+        Self {
+            preserve_current_session: default_preserve_current_session(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevokeAllSessionsResponse {
+    /// Number of users whose sessions were revoked
+    pub users: usize,
+}
+
 /// Validate password complexity requirements
 fn validate_password_complexity(password: &str) -> Result<(), validator::ValidationError> {
     let has_uppercase = password.chars().any(|c| c.is_uppercase());
@@ -0,0 +1,58 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use cidr::IpCidr;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A login session of a user, counted towards their session limit while the session store still
+/// has it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserSessionBase {
+    /// The session store's id for the session
+    pub session_id: String,
+    pub user_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserSession {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub base: UserSessionBase,
+}
+
+impl Display for UserSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Session of user {}: {}", self.base.user_id, self.id)
+    }
+}
+
+/// Source addresses an organization's daemons may connect from. Applies on top of the
+/// server-wide `daemon_ip_allowlist` from config, and only to the organization's own daemons.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizationIpAllowlistBase {
+    pub organization_id: Uuid,
+    /// Empty allows every address the server-wide allowlist does
+    pub cidrs: Vec<IpCidr>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizationIpAllowlist {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub base: OrganizationIpAllowlistBase,
+}
+
+impl Display for OrganizationIpAllowlist {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Daemon IP allowlist of organization {}: {}",
+            self.base.organization_id, self.id
+        )
+    }
+}
@@ -0,0 +1,164 @@
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+use uuid::Uuid;
+
+use crate::server::{
+    auth::r#impl::base::{
+        OrganizationIpAllowlist, OrganizationIpAllowlistBase, UserSession, UserSessionBase,
+    },
+    shared::storage::traits::{SqlValue, StorableEntity},
+};
+
+impl StorableEntity for UserSession {
+    type BaseData = UserSessionBase;
+
+    fn table_name() -> &'static str {
+        "user_sessions"
+    }
+
+    fn get_base(&self) -> Self::BaseData {
+        self.base.clone()
+    }
+
+    fn new(base: Self::BaseData) -> Self {
+        let now = chrono::Utc::now();
+
+        Self {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: now,
+            base,
+        }
+    }
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn set_updated_at(&mut self, time: DateTime<Utc>) {
+        self.updated_at = time;
+    }
+
+    fn to_params(&self) -> Result<(Vec<&'static str>, Vec<SqlValue>), anyhow::Error> {
+        let Self {
+            id,
+            created_at,
+            updated_at,
+            base:
+                Self::BaseData {
+                    session_id,
+                    user_id,
+                },
+        } = self.clone();
+
+        Ok((
+            vec!["id", "created_at", "updated_at", "session_id", "user_id"],
+            vec![
+                SqlValue::Uuid(id),
+                SqlValue::Timestamp(created_at),
+                SqlValue::Timestamp(updated_at),
+                SqlValue::String(session_id),
+                SqlValue::Uuid(user_id),
+            ],
+        ))
+    }
+
+    fn from_row(row: &PgRow) -> Result<Self, anyhow::Error> {
+        Ok(UserSession {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            base: UserSessionBase {
+                session_id: row.get("session_id"),
+                user_id: row.get("user_id"),
+            },
+        })
+    }
+}
+
+impl StorableEntity for OrganizationIpAllowlist {
+    type BaseData = OrganizationIpAllowlistBase;
+
+    fn table_name() -> &'static str {
+        "daemon_ip_allowlists"
+    }
+
+    fn get_base(&self) -> Self::BaseData {
+        self.base.clone()
+    }
+
+    fn new(base: Self::BaseData) -> Self {
+        let now = chrono::Utc::now();
+
+        Self {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: now,
+            base,
+        }
+    }
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn set_updated_at(&mut self, time: DateTime<Utc>) {
+        self.updated_at = time;
+    }
+
+    fn to_params(&self) -> Result<(Vec<&'static str>, Vec<SqlValue>), anyhow::Error> {
+        let Self {
+            id,
+            created_at,
+            updated_at,
+            base:
+                Self::BaseData {
+                    organization_id,
+                    cidrs,
+                },
+        } = self.clone();
+
+        Ok((
+            vec!["id", "created_at", "updated_at", "organization_id", "cidrs"],
+            vec![
+                SqlValue::Uuid(id),
+                SqlValue::Timestamp(created_at),
+                SqlValue::Timestamp(updated_at),
+                SqlValue::Uuid(organization_id),
+                SqlValue::Json(serde_json::to_value(cidrs)?),
+            ],
+        ))
+    }
+
+    fn from_row(row: &PgRow) -> Result<Self, anyhow::Error> {
+        let cidrs = serde_json::from_value(row.get::<serde_json::Value, _>("cidrs"))
+            .map_err(|e| anyhow::anyhow!("Failed to deserialize allowlist: {}", e))?;
+
+        Ok(OrganizationIpAllowlist {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            base: OrganizationIpAllowlistBase {
+                organization_id: row.get("organization_id"),
+                cidrs,
+            },
+        })
+    }
+}
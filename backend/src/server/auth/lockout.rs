@@ -0,0 +1,106 @@
+use crate::server::config::ServerConfig;
+use std::time::Duration;
+
+/// Progressive backoff applied to failed logins for an email address.
+///
+/// The first `threshold - 1` failures cost nothing, so the odd typo isn't punished. From the
+/// `threshold`th failure on the required wait starts at `base` and doubles with every further
+/// failure, never exceeding `max`. A successful login resets the count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoginBackoff {
+    pub threshold: u32,
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl Default for LoginBackoff {
+    fn default() -> Self {
+        Self {
+            threshold: 5,
+            base: Duration::from_secs(30),
+            max: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+impl From<&ServerConfig> for LoginBackoff {
+    fn from(config: &ServerConfig) -> Self {
+        Self {
+            threshold: config.login_lockout_threshold,
+            base: Duration::from_secs(config.login_lockout_base_secs),
+            max: Duration::from_secs(config.login_lockout_max_secs),
+        }
+    }
+}
+
+impl LoginBackoff {
+    /// Wait required after `failed_attempts` consecutive failures
+    pub fn wait_for(&self, failed_attempts: u32) -> Duration {
+        if failed_attempts < self.threshold.max(1) {
+            return Duration::ZERO;
+        }
+
+        let doublings = failed_attempts - self.threshold.max(1);
+        let factor = 1u32.checked_shl(doublings).unwrap_or(u32::MAX);
+
+        self.base.saturating_mul(factor).min(self.max)
+    }
+
+    /// Wait still outstanding given the failure count and the time since the last failure
+    pub fn remaining(&self, failed_attempts: u32, since_last_attempt: Duration) -> Duration {
+        self.wait_for(failed_attempts)
+            .saturating_sub(since_last_attempt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn test_wait_follows_curve_up_to_cap() {
+        let backoff = LoginBackoff::default();
+
+        let expected = [
+            (0, 0),
+            (1, 0),
+            (4, 0),
+            (5, 30),
+            (6, 60),
+            (7, 120),
+            (8, 240),
+            (9, 480),
+            (10, 900),
+            (11, 900),
+            (50, 900),
+            (u32::MAX, 900),
+        ];
+
+        for (attempts, wait) in expected {
+            assert_eq!(
+                backoff.wait_for(attempts),
+                secs(wait),
+                "{} attempts",
+                attempts
+            );
+        }
+    }
+
+    #[test]
+    fn test_remaining_counts_down_from_last_attempt() {
+        let backoff = LoginBackoff {
+            threshold: 3,
+            base: secs(10),
+            max: secs(60),
+        };
+
+        assert_eq!(backoff.remaining(2, secs(0)), Duration::ZERO);
+        assert_eq!(backoff.remaining(3, secs(4)), secs(6));
+        assert_eq!(backoff.remaining(4, secs(25)), Duration::ZERO);
+        assert_eq!(backoff.remaining(9, secs(0)), secs(60));
+    }
+}
@@ -1,13 +1,22 @@
 use crate::server::{
+    api_keys::service::ApiKeyService,
+    auth::{
+        impersonation::{ImpersonationError, Impersonations},
+        sessions,
+    },
     billing::types::base::BillingPlan,
     config::AppState,
     organizations::r#impl::base::Organization,
-    shared::{services::traits::CrudService, storage::filter::EntityFilter, types::api::ApiError},
+    shared::{
+        services::traits::CrudService,
+        storage::{filter::EntityFilter, pools::read_from_primary},
+        types::api::ApiError,
+    },
     users::r#impl::permissions::UserOrgPermissions,
 };
 use axum::{
     extract::FromRequestParts,
-    http::request::Parts,
+    http::{HeaderMap, header, request::Parts},
     response::{IntoResponse, Response},
 };
 use chrono::Utc;
@@ -16,6 +25,44 @@ use uuid::Uuid;
 
 pub struct AuthError(ApiError);
 
+/// Header daemons may use to present their API key instead of `Authorization: Bearer`
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// The API key presented in `headers`, if any. A key header which is present but unreadable or
+/// empty is rejected outright rather than falling back to session auth.
+pub fn api_key_from_headers(headers: &HeaderMap) -> Result<Option<&str>, AuthError> {
+    let malformed = || AuthError(ApiError::unauthorized("Malformed API key".to_string()));
+
+    if let Some(value) = headers.get(API_KEY_HEADER) {
+        let key = value.to_str().map_err(|_| malformed())?.trim();
+        return if key.is_empty() {
+            Err(malformed())
+        } else {
+            Ok(Some(key))
+        };
+    }
+
+    let Some(value) = headers.get(header::AUTHORIZATION) else {
+        return Ok(None);
+    };
+    match value
+        .to_str()
+        .map_err(|_| malformed())?
+        .strip_prefix("Bearer ")
+    {
+        Some(key) if !key.trim().is_empty() => Ok(Some(key.trim())),
+        Some(_) => Err(malformed()),
+        // Some other scheme, not an API key
+        None => Ok(None),
+    }
+}
+
+impl From<AuthError> for ApiError {
+    fn from(value: AuthError) -> Self {
+        value.0
+    }
+}
+
 impl IntoResponse for AuthError {
     fn into_response(self) -> Response {
         self.0.into_response()
@@ -30,6 +77,8 @@ pub enum AuthenticatedEntity {
         organization_id: Uuid,
         permissions: UserOrgPermissions,
         network_ids: Vec<Uuid>,
+        /// The admin acting as this user, if the session is being impersonated
+        impersonated_by: Option<Uuid>,
     },
     Daemon(Uuid), // network_id
 }
@@ -77,95 +126,174 @@ where
     type Rejection = AuthError;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let app_state = state.as_ref();
-
-        // Try daemon authentication first (Authorization header)
-        if let Some(auth_header) = parts.headers.get(axum::http::header::AUTHORIZATION)
-            && let Ok(auth_str) = auth_header.to_str()
-            && let Some(api_key) = auth_str.strip_prefix("Bearer ")
-        {
-            let api_key_filter = EntityFilter::unfiltered().api_key(api_key.to_owned());
-            // Get API key record by key
-            if let Ok(Some(mut api_key)) = app_state
-                .services
-                .api_key_service
-                .get_one(api_key_filter)
-                .await
-            {
-                let network_id = api_key.base.network_id;
-                let service = app_state.services.api_key_service.clone();
-
-                // Check expiration
-                if let Some(expires_at) = api_key.base.expires_at
-                    && chrono::Utc::now() > expires_at
-                {
-                    // Update enabled asynchronously (don't block auth)
-                    api_key.base.is_enabled = false;
-                    tokio::spawn(async move {
-                        let _ = service.update(&mut api_key).await;
-                    });
-                    return Err(AuthError(ApiError::unauthorized(
-                        "API key has expired".to_string(),
-                    )));
-                }
+        // Whether a key, user or session is still allowed is read from the primary, so revoking
+        // or disabling one takes effect on the next request rather than after replica lag
+        read_from_primary(authenticate(parts, state)).await
+    }
+}
 
-                if !api_key.base.is_enabled {
-                    return Err(AuthError(ApiError::unauthorized(
-                        "API key is not enabled".to_string(),
-                    )));
-                }
+async fn authenticate<S>(parts: &mut Parts, state: &S) -> Result<AuthenticatedEntity, AuthError>
+where
+    S: Send + Sync + AsRef<AppState>,
+{
+    let app_state = state.as_ref();
 
-                // Update last used asynchronously (don't block auth)
-                api_key.base.last_used = Some(Utc::now());
+    // Try daemon authentication first (Authorization or X-Api-Key header)
+    if let Some(api_key) = api_key_from_headers(&parts.headers)? {
+        // Only the hash is stored, so look the key up by its hash
+        if let Ok(Some(mut api_key)) = app_state
+            .services
+            .api_key_service
+            .get_by_api_key_hash(&ApiKeyService::hash_key(api_key))
+            .await
+        {
+            let network_id = api_key.base.network_id;
+            let service = app_state.services.api_key_service.clone();
+
+            // Check expiration, including keys past the max age policy
+            let now = Utc::now();
+            if service.policy().is_expired(&api_key, now) {
+                // Update enabled asynchronously (don't block auth)
+                api_key.base.is_enabled = false;
                 tokio::spawn(async move {
                     let _ = service.update(&mut api_key).await;
                 });
+                return Err(AuthError(ApiError::unauthorized(
+                    "API key has expired".to_string(),
+                )));
+            }
 
-                return Ok(AuthenticatedEntity::Daemon(network_id));
+            if !api_key.base.is_enabled {
+                return Err(AuthError(ApiError::unauthorized(
+                    "API key is not enabled".to_string(),
+                )));
             }
-            // Invalid API key
-            return Err(AuthError(ApiError::unauthorized(
-                "Invalid API key".to_string(),
-            )));
-        }
 
-        // Try user authentication (session cookie)
-        let session = Session::from_request_parts(parts, state)
-            .await
-            .map_err(|_| AuthError(ApiError::unauthorized("Not authenticated".to_string())))?;
+            app_state
+                .services
+                .daemon_ip_allowlist
+                .check_request(&network_id, parts)
+                .await
+                .map_err(AuthError)?;
 
-        let user_id: Uuid = session
-            .get("user_id")
-            .await
-            .map_err(|_| AuthError(ApiError::unauthorized("Not authenticated".to_string())))?
-            .ok_or_else(|| AuthError(ApiError::unauthorized("Not authenticated".to_string())))?;
+            // Passed on so heartbeat responses can tell the daemon to rotate its key
+            if let Some(warning) = service.check_expiry(&api_key, now) {
+                parts.extensions.insert(warning);
+            }
 
-        let user = app_state
-            .services
-            .user_service
-            .get_by_id(&user_id)
-            .await
-            .map_err(|_| AuthError(ApiError::unauthorized("User not found".to_string())))?
-            .ok_or_else(|| AuthError(ApiError::unauthorized("User not found".to_string())))?;
+            // Update last used asynchronously (don't block auth)
+            api_key.base.last_used = Some(now);
+            tokio::spawn(async move {
+                let _ = service.update(&mut api_key).await;
+            });
 
-        let org_filter = EntityFilter::unfiltered().organization_id(&user.base.organization_id);
-        let network_ids: Vec<Uuid> = app_state
-            .services
-            .network_service
-            .get_all(org_filter)
-            .await
-            .map_err(|_| AuthError(ApiError::internal_error("Failed to load networks")))?
-            .iter()
-            .map(|n| n.id)
-            .collect();
-
-        Ok(AuthenticatedEntity::User {
-            user_id: user.id,
-            organization_id: user.base.organization_id,
-            permissions: user.base.permissions,
-            network_ids,
-        })
+            return Ok(AuthenticatedEntity::Daemon(network_id));
+        }
+        // Invalid API key
+        return Err(AuthError(ApiError::unauthorized(
+            "Invalid API key".to_string(),
+        )));
+    }
+
+    // Try user authentication (session cookie)
+    let session = Session::from_request_parts(parts, state)
+        .await
+        .map_err(|_| AuthError(ApiError::unauthorized("Not authenticated".to_string())))?;
+
+    let user_id: Uuid = session
+        .get("user_id")
+        .await
+        .map_err(|_| AuthError(ApiError::unauthorized("Not authenticated".to_string())))?
+        .ok_or_else(|| AuthError(ApiError::unauthorized("Not authenticated".to_string())))?;
+
+    let user = app_state
+        .services
+        .user_service
+        .get_by_id(&user_id)
+        .await
+        .map_err(|_| AuthError(ApiError::unauthorized("User not found".to_string())))?
+        .ok_or_else(|| AuthError(ApiError::unauthorized("User not found".to_string())))?;
+
+    // Sessions started before the user was disabled may not have been ended
+    if !user.base.enabled {
+        tracing::warn!(user_id = %user.id, "Request rejected: account disabled");
+        return Err(AuthError(ApiError::unauthorized(
+            "Not authenticated".to_string(),
+        )));
     }
+
+    let impersonation = Impersonations::current(&session)
+        .await
+        .map_err(|_| AuthError(ApiError::unauthorized("Not authenticated".to_string())))?;
+
+    let impersonated_by = match impersonation {
+        Some(impersonation) => {
+            // The session belongs to the impersonating admin, so it lives only as long as
+            // they could still start this impersonation
+            let impersonator = app_state
+                .services
+                .user_service
+                .get_by_id(&impersonation.impersonator_id)
+                .await
+                .ok()
+                .flatten();
+            let ended = match &impersonator {
+                _ if impersonation.is_expired(Utc::now()) => Some("expired"),
+                None => Some("impersonator_deleted"),
+                Some(admin) if !admin.base.enabled => Some("impersonator_disabled"),
+                Some(admin) if admin.base.permissions <= user.base.permissions => {
+                    Some("impersonator_permissions_changed")
+                }
+                Some(admin)
+                    if sessions::is_revoked(&session, admin.base.sessions_revoked_at).await =>
+                {
+                    Some("session_revoked")
+                }
+                _ => None,
+            };
+
+            if let Some(reason) = ended {
+                let _ = app_state
+                    .services
+                    .auth_service
+                    .impersonations
+                    .end(&session, reason)
+                    .await;
+                return Err(AuthError(ApiError::unauthorized(
+                    "Impersonation has ended".to_string(),
+                )));
+            }
+            Some(impersonation.impersonator_id)
+        }
+        None => {
+            if sessions::is_revoked(&session, user.base.sessions_revoked_at).await {
+                tracing::warn!(user_id = %user.id, "Request rejected: session revoked");
+                return Err(AuthError(ApiError::unauthorized(
+                    "Not authenticated".to_string(),
+                )));
+            }
+            None
+        }
+    };
+
+    let org_filter = EntityFilter::unfiltered().organization_id(&user.base.organization_id);
+    let network_ids: Vec<Uuid> = app_state
+        .services
+        .network_service
+        .get_all(org_filter)
+        .await
+        .map_err(|_| AuthError(ApiError::internal_error("Failed to load networks")))?
+        .iter()
+        .map(|n| n.id)
+        .collect();
+
+    Ok(AuthenticatedEntity::User {
+        user_id: user.id,
+        organization_id: user.base.organization_id,
+        permissions: user.base.permissions,
+        network_ids,
+        impersonated_by,
+    })
 }
 
 /// Extractor that only accepts authenticated users (rejects daemons)
@@ -174,6 +302,7 @@ pub struct AuthenticatedUser {
     pub organization_id: Uuid,
     pub permissions: UserOrgPermissions,
     pub network_ids: Vec<Uuid>,
+    pub impersonated_by: Option<Uuid>,
 }
 
 impl From<AuthenticatedUser> for AuthenticatedEntity {
@@ -183,6 +312,7 @@ impl From<AuthenticatedUser> for AuthenticatedEntity {
             organization_id: value.organization_id,
             permissions: value.permissions,
             network_ids: value.network_ids,
+            impersonated_by: value.impersonated_by,
         }
     }
 }
@@ -202,11 +332,13 @@ where
                 organization_id,
                 permissions,
                 network_ids,
+                impersonated_by,
             } => Ok(AuthenticatedUser {
                 user_id,
                 organization_id,
                 permissions,
                 network_ids,
+                impersonated_by,
             }),
             AuthenticatedEntity::Daemon(_) => Err(AuthError(ApiError::unauthorized(
                 "User authentication required".to_string(),
@@ -215,6 +347,29 @@ where
     }
 }
 
+/// Extractor for actions an admin impersonating a user must not take on their behalf, such as
+/// changing their credentials
+pub struct NotImpersonated(pub AuthenticatedUser);
+
+impl<S> FromRequestParts<S> for NotImpersonated
+where
+    S: Send + Sync + AsRef<AppState>,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = AuthenticatedUser::from_request_parts(parts, state).await?;
+
+        if user.impersonated_by.is_some() {
+            return Err(AuthError(ApiError::forbidden(
+                &ImpersonationError::Forbidden.to_string(),
+            )));
+        }
+
+        Ok(NotImpersonated(user))
+    }
+}
+
 /// Extractor that only accepts authenticated daemons (rejects users)
 pub struct AuthenticatedDaemon(pub Uuid);
 
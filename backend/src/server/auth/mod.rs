@@ -1,5 +1,9 @@
 pub mod handlers;
+pub mod impersonation;
 pub mod r#impl;
+pub mod lockout;
 pub mod middleware;
 pub mod oidc;
 pub mod service;
+pub mod sessions;
+pub mod source_ip;
@@ -12,6 +12,7 @@ use uuid::Uuid;
 
 use crate::server::{
     auth::service::AuthService,
+    shared::storage::pools::read_from_primary,
     users::r#impl::{base::User, permissions::UserOrgPermissions},
 };
 
@@ -191,13 +192,19 @@ impl OidcService {
     ) -> Result<User> {
         let user_info = self.exchange_code(code, pending_auth).await?;
 
-        // Check if user exists with this OIDC account
-        if let Some(user) = self
-            .auth_service
-            .user_service
-            .get_user_by_oidc(&user_info.subject)
-            .await?
+        // Check if user exists with this OIDC account, against the primary so a just-disabled
+        // account can't get in
+        if let Some(user) = read_from_primary(
+            self.auth_service
+                .user_service
+                .get_user_by_oidc(&user_info.subject),
+        )
+        .await?
         {
+            if !user.base.enabled {
+                tracing::warn!(user_id = %user.id, "OIDC login rejected: account disabled");
+                return Err(anyhow!("Authentication failed"));
+            }
             return Ok(user);
         }
 
@@ -1,5 +1,10 @@
 use crate::server::{
-    auth::r#impl::api::{LoginRequest, RegisterRequest},
+    auth::{
+        impersonation::Impersonations,
+        r#impl::api::{LoginRequest, RegisterRequest},
+        lockout::LoginBackoff,
+        sessions::SessionLimiter,
+    },
     email::service::EmailService,
     organizations::{
         r#impl::base::{Organization, OrganizationBase},
@@ -7,7 +12,7 @@ use crate::server::{
     },
     shared::{
         services::traits::CrudService,
-        storage::{filter::EntityFilter, traits::StorableEntity},
+        storage::{filter::EntityFilter, pools::read_from_primary, traits::StorableEntity},
     },
     users::{
         r#impl::{base::User, permissions::UserOrgPermissions},
@@ -31,16 +36,21 @@ pub struct AuthService {
     email_service: Option<Arc<EmailService>>,
     login_attempts: Arc<RwLock<HashMap<EmailAddress, (u32, Instant)>>>,
     password_reset_tokens: Arc<RwLock<HashMap<String, (Uuid, Instant)>>>,
+    claim_unnamed_seed_users: bool,
+    login_backoff: LoginBackoff,
+    pub sessions: SessionLimiter,
+    pub impersonations: Impersonations,
 }
 
 impl AuthService {
-    const MAX_LOGIN_ATTEMPTS: u32 = 5;
-    const LOCKOUT_DURATION_SECS: u64 = 15 * 60; // 15 minutes
-
     pub fn new(
         user_service: Arc<UserService>,
         organization_service: Arc<OrganizationService>,
         email_service: Option<Arc<EmailService>>,
+        claim_unnamed_seed_users: bool,
+        login_backoff: LoginBackoff,
+        sessions: SessionLimiter,
+        impersonations: Impersonations,
     ) -> Self {
         Self {
             user_service,
@@ -48,6 +58,10 @@ impl AuthService {
             email_service,
             login_attempts: Arc::new(RwLock::new(HashMap::new())),
             password_reset_tokens: Arc::new(RwLock::new(HashMap::new())),
+            claim_unnamed_seed_users,
+            login_backoff,
+            sessions,
+            impersonations,
         }
     }
 
@@ -120,15 +134,14 @@ impl AuthService {
             .get_all(EntityFilter::unfiltered())
             .await?;
 
-        // Find seed user (only exists if NO users have been created yet)
-        let seed_user: Option<User> = all_users
-            .iter()
-            .find(|u| u.base.password_hash.is_none() && u.base.oidc_subject.is_none())
-            .cloned();
+        let seed_user =
+            Self::select_seed_user(&all_users, &email, self.claim_unnamed_seed_users).cloned();
 
         if let Some(mut seed_user) = seed_user {
-            // First user ever - claim seed user
-            tracing::info!("First user registration - claiming seed user");
+            tracing::info!(
+                seed_user_id = %seed_user.id,
+                "Registration matched seed user - claiming it"
+            );
             seed_user.base.email = email;
 
             if let Some(hash) = password_hash {
@@ -179,6 +192,30 @@ impl AuthService {
         }
     }
 
+    /// Pick the seed user a registration should claim.
+    ///
+    /// Seed users provisioned for a specific email are only ever claimed by that email. Unnamed
+    /// seeds (placeholder email, e.g. the one created on first startup) are claimed by the first
+    /// registration when `claim_unnamed` is set.
+    fn select_seed_user<'a>(
+        users: &'a [User],
+        email: &EmailAddress,
+        claim_unnamed: bool,
+    ) -> Option<&'a User> {
+        let seeds = || users.iter().filter(|u| u.base.is_seed());
+
+        seeds()
+            .find(|u| {
+                !u.base.is_unnamed_seed()
+                    && u.base.email.as_str().eq_ignore_ascii_case(email.as_str())
+            })
+            .or_else(|| {
+                claim_unnamed
+                    .then(|| seeds().find(|u| u.base.is_unnamed_seed()))
+                    .flatten()
+            })
+    }
+
     /// Login with username and password
     pub async fn login(&self, request: LoginRequest) -> Result<User> {
         request
@@ -188,8 +225,8 @@ impl AuthService {
         // Check if account is locked due to too many failed attempts
         self.check_login_lockout(&request.email).await?;
 
-        // Attempt login
-        let result = self.try_login(&request).await;
+        // Attempt login, against the primary so a just-disabled account can't get in
+        let result = read_from_primary(self.try_login(&request)).await;
 
         // Update login attempts based on result
         match result {
@@ -215,15 +252,21 @@ impl AuthService {
     /// Check if user is locked out due to too many login attempts
     async fn check_login_lockout(&self, email: &EmailAddress) -> Result<()> {
         let attempts = self.login_attempts.read().await;
-        if let Some((count, last_attempt)) = attempts.get(email)
-            && *count >= Self::MAX_LOGIN_ATTEMPTS
-        {
-            let elapsed = last_attempt.elapsed().as_secs();
-            if elapsed < Self::LOCKOUT_DURATION_SECS {
-                let remaining = (Self::LOCKOUT_DURATION_SECS - elapsed) / 60;
+        if let Some((count, last_attempt)) = attempts.get(email) {
+            let remaining = self
+                .login_backoff
+                .remaining(*count, last_attempt.elapsed())
+                .as_secs();
+
+            if remaining >= 60 {
                 return Err(anyhow!(
                     "Too many failed login attempts. Try again in {} minutes.",
-                    remaining + 1
+                    remaining.div_ceil(60)
+                ));
+            } else if remaining > 0 {
+                return Err(anyhow!(
+                    "Too many failed login attempts. Try again in {} seconds.",
+                    remaining
                 ));
             }
         }
@@ -252,6 +295,12 @@ impl AuthService {
         // Verify password
         verify_password(&request.password, password_hash)?;
 
+        // Disabled accounts get the same error as bad credentials so their state isn't revealed
+        if !user.base.enabled {
+            tracing::warn!(user_id = %user.id, "Login rejected: account disabled");
+            return Err(anyhow!("Invalid email or password"));
+        }
+
         Ok(user.clone())
     }
 
@@ -327,9 +376,8 @@ impl AuthService {
     pub async fn cleanup_old_login_attempts(&self) {
         let mut attempts = self.login_attempts.write().await;
 
-        attempts.retain(|_, (_, last_attempt)| {
-            last_attempt.elapsed().as_secs() < Self::LOCKOUT_DURATION_SECS
-        });
+        // Once the longest possible wait has passed without another failure, start afresh
+        attempts.retain(|_, (_, last_attempt)| last_attempt.elapsed() < self.login_backoff.max);
 
         tracing::debug!("Cleaned up old login attempts");
     }
@@ -357,3 +405,57 @@ pub fn verify_password(password: &str, hash: &str) -> Result<()> {
         .verify_password(password.as_bytes(), &parsed_hash)
         .map_err(|_| anyhow!("Invalid username or password"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::users::r#impl::base::UserBase;
+
+    fn named_seed(email: &str) -> User {
+        User::new(UserBase::new_named_seed(
+            EmailAddress::new_unchecked(email),
+            Uuid::new_v4(),
+            UserOrgPermissions::Admin,
+        ))
+    }
+
+    #[test]
+    fn test_register_claims_matching_named_seed() {
+        let bob = named_seed("bob@example.com");
+        let alice = named_seed("alice@example.com");
+        let unnamed = User::new(UserBase::new_seed(Uuid::new_v4()));
+        let users = vec![unnamed, bob, alice.clone()];
+
+        let claimed = AuthService::select_seed_user(
+            &users,
+            &EmailAddress::new_unchecked("Alice@example.com"),
+            true,
+        )
+        .expect("alice seed should be claimed");
+
+        assert_eq!(claimed.id, alice.id);
+    }
+
+    #[test]
+    fn test_register_falls_back_to_unnamed_seed_only_when_enabled() {
+        let unnamed = User::new(UserBase::new_seed(Uuid::new_v4()));
+        let users = vec![named_seed("bob@example.com"), unnamed.clone()];
+        let email = EmailAddress::new_unchecked("carol@example.com");
+
+        let claimed = AuthService::select_seed_user(&users, &email, true);
+        assert_eq!(claimed.map(|u| u.id), Some(unnamed.id));
+
+        assert!(AuthService::select_seed_user(&users, &email, false).is_none());
+    }
+
+    #[test]
+    fn test_claimed_seed_is_not_reclaimed() {
+        let mut alice = named_seed("alice@example.com");
+        alice.set_password("hash".to_string());
+
+        let users = vec![alice];
+        let email = EmailAddress::new_unchecked("alice@example.com");
+
+        assert!(AuthService::select_seed_user(&users, &email, true).is_none());
+    }
+}
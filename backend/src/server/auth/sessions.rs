@@ -0,0 +1,294 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, sync::Arc};
+use tokio::sync::Mutex;
+use tower_sessions::{Session, SessionStore, session::Id};
+use uuid::Uuid;
+
+use crate::server::{
+    auth::r#impl::base::{UserSession, UserSessionBase},
+    shared::storage::{
+        filter::EntityFilter,
+        generic::GenericPostgresStorage,
+        traits::{StorableEntity, Storage},
+    },
+};
+
+/// What happens when a user logs in while already at the session limit
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionLimitPolicy {
+    /// Refuse the new login
+    Reject,
+    /// Invalidate the user's oldest session to make room
+    #[default]
+    EvictOldest,
+}
+
+/// Session key holding when the user logged in on the session
+const STARTED_AT_KEY: &str = "started_at";
+
+/// Whether `session` was started before `revoked_at`. Sessions from before start times were
+/// recorded count as revoked.
+pub async fn is_revoked(session: &Session, revoked_at: Option<DateTime<Utc>>) -> bool {
+    let Some(revoked_at) = revoked_at else {
+        return false;
+    };
+
+    match session.get::<DateTime<Utc>>(STARTED_AT_KEY).await {
+        Ok(Some(started_at)) => started_at <= revoked_at,
+        _ => true,
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Maximum number of active sessions reached. Log out of another session and try again.")]
+pub struct SessionLimitReached;
+
+/// Tracks which sessions belong to which user so a per-user session limit can be enforced. The
+/// sessions are recorded in the database, so the limit holds across restarts and instances.
+pub struct SessionLimiter {
+    store: Arc<dyn SessionStore>,
+    storage: Arc<GenericPostgresStorage<UserSession>>,
+    max_per_user: Option<usize>,
+    policy: SessionLimitPolicy,
+    /// Serializes logins, so two at once can't both take the last slot
+    starting: Mutex<()>,
+}
+
+impl SessionLimiter {
+    pub fn new(
+        store: Arc<dyn SessionStore>,
+        storage: Arc<GenericPostgresStorage<UserSession>>,
+        max_per_user: Option<usize>,
+        policy: SessionLimitPolicy,
+    ) -> Self {
+        Self {
+            store,
+            storage,
+            max_per_user,
+            policy,
+            starting: Mutex::new(()),
+        }
+    }
+
+    /// `user_id`'s sessions the session store still has, oldest first. Sessions can end without
+    /// us seeing it (expiry, deletion), so records of ones that have are dropped, as is any of
+    /// `current`.
+    async fn live_sessions(&self, user_id: &Uuid, current: Option<Id>) -> Result<Vec<UserSession>> {
+        let mut live = Vec::new();
+        for tracked in self
+            .storage
+            .get_all(EntityFilter::unfiltered().user_id(user_id))
+            .await?
+        {
+            let id = tracked.base.session_id.parse::<Id>().ok();
+            let is_live = match id {
+                Some(id) if Some(id) != current => self.store.load(&id).await?.is_some(),
+                _ => false,
+            };
+
+            if is_live {
+                live.push(tracked);
+            } else {
+                self.storage.delete(&tracked.id).await?;
+            }
+        }
+
+        Ok(live)
+    }
+
+    /// End a tracked session, in the session store and in the records
+    async fn end_tracked(&self, tracked: &UserSession) -> Result<()> {
+        if let Ok(id) = tracked.base.session_id.parse::<Id>() {
+            self.store.delete(&id).await?;
+        }
+        self.storage.delete(&tracked.id).await
+    }
+
+    /// Log `user_id` in on `session`, enforcing the session limit
+    pub async fn start(&self, session: &Session, user_id: Uuid) -> Result<()> {
+        let _starting = self.starting.lock().await;
+        let mut sessions = VecDeque::from(self.live_sessions(&user_id, session.id()).await?);
+
+        if let Some(max) = self.max_per_user {
+            while sessions.len() >= max.max(1) {
+                if self.policy == SessionLimitPolicy::Reject {
+                    tracing::warn!(user_id = %user_id, "Login rejected: session limit reached");
+                    return Err(SessionLimitReached.into());
+                }
+
+                let Some(oldest) = sessions.pop_front() else {
+                    break;
+                };
+                self.end_tracked(&oldest).await?;
+                tracing::info!(user_id = %user_id, "Evicted oldest session: session limit reached");
+            }
+        }
+
+        session.insert("user_id", user_id).await?;
+        session.insert(STARTED_AT_KEY, Utc::now()).await?;
+        // Persist now so the session has an id to track
+        session.save().await?;
+
+        let id = session
+            .id()
+            .ok_or_else(|| anyhow!("Session has no id after saving"))?;
+        self.storage
+            .create(&UserSession::new(UserSessionBase {
+                session_id: id.to_string(),
+                user_id,
+            }))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Log out of `session`, freeing its slot
+    pub async fn end(&self, session: &Session) -> Result<()> {
+        let id = session.id();
+
+        session.delete().await?;
+
+        if let Some(id) = id {
+            self.storage
+                .delete_where(EntityFilter::unfiltered().session_id(&id.to_string()))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Log `user_id` out of every session, other than `keep`
+    pub async fn end_all(&self, user_id: &Uuid, keep: Option<Id>) -> Result<()> {
+        let keep = keep.map(|id| id.to_string());
+        let sessions: Vec<UserSession> = self
+            .storage
+            .get_all(EntityFilter::unfiltered().user_id(user_id))
+            .await?
+            .into_iter()
+            .filter(|s| Some(&s.base.session_id) != keep.as_ref())
+            .collect();
+
+        for tracked in &sessions {
+            self.end_tracked(tracked).await?;
+        }
+
+        if !sessions.is_empty() {
+            tracing::info!(user_id = %user_id, "Ended {} sessions", sessions.len());
+        }
+
+        Ok(())
+    }
+
+    /// Treat `session` as started now, so it survives a revocation made before this call
+    pub async fn renew(&self, session: &Session) -> Result<()> {
+        session.insert(STARTED_AT_KEY, Utc::now()).await?;
+        session.save().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        server::shared::storage::factory::StorageFactory,
+        tests::{organization, test_storage, user},
+    };
+    use serial_test::serial;
+    use tower_sessions_sqlx_store::PostgresStore;
+
+    fn new_session(store: &PostgresStore, id: Option<Id>) -> Session {
+        Session::new(id, Arc::new(store.clone()), None)
+    }
+
+    fn limiter(storage: &StorageFactory, policy: SessionLimitPolicy) -> SessionLimiter {
+        SessionLimiter::new(
+            Arc::new(storage.session_store.clone()),
+            storage.user_sessions.clone(),
+            Some(1),
+            policy,
+        )
+    }
+
+    async fn user_id(storage: &StorageFactory) -> Uuid {
+        let organization = storage.organizations.create(&organization()).await.unwrap();
+        storage
+            .users
+            .create(&user(&organization.id))
+            .await
+            .unwrap()
+            .id
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_second_login_evicts_first_session() {
+        let (storage, _container) = test_storage().await;
+        let store = &storage.session_store;
+        let limiter = limiter(&storage, SessionLimitPolicy::EvictOldest);
+        let user_id = user_id(&storage).await;
+
+        let first = new_session(store, None);
+        limiter.start(&first, user_id).await.unwrap();
+        let first_id = first.id().unwrap();
+
+        let second = new_session(store, None);
+        limiter.start(&second, user_id).await.unwrap();
+
+        // A request presenting the first session's cookie no longer finds a user
+        let reloaded_first = new_session(store, Some(first_id));
+        assert_eq!(reloaded_first.get::<Uuid>("user_id").await.unwrap(), None);
+
+        let reloaded_second = new_session(store, second.id());
+        assert_eq!(
+            reloaded_second.get::<Uuid>("user_id").await.unwrap(),
+            Some(user_id)
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_reject_policy_and_logout_frees_slot() {
+        let (storage, _container) = test_storage().await;
+        let store = &storage.session_store;
+        let limiter = limiter(&storage, SessionLimitPolicy::Reject);
+        let user_id = user_id(&storage).await;
+
+        let first = new_session(store, None);
+        limiter.start(&first, user_id).await.unwrap();
+
+        let second = new_session(store, None);
+        let err = limiter.start(&second, user_id).await.unwrap_err();
+        assert!(err.is::<SessionLimitReached>());
+
+        limiter.end(&first).await.unwrap();
+        limiter.start(&second, user_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_session_limit_holds_across_restarts() {
+        let (storage, _container) = test_storage().await;
+        let store = &storage.session_store;
+        let user_id = user_id(&storage).await;
+
+        let first = new_session(store, None);
+        limiter(&storage, SessionLimitPolicy::Reject)
+            .start(&first, user_id)
+            .await
+            .unwrap();
+
+        // A restarted server, or another instance, still counts the first session
+        let restarted = limiter(&storage, SessionLimitPolicy::Reject);
+        let second = new_session(store, None);
+        let err = restarted.start(&second, user_id).await.unwrap_err();
+        assert!(err.is::<SessionLimitReached>());
+
+        // Sessions that ended without logging out stop counting
+        store.delete(&first.id().unwrap()).await.unwrap();
+        restarted.start(&second, user_id).await.unwrap();
+    }
+}
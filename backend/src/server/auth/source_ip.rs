@@ -0,0 +1,239 @@
+use anyhow::Result;
+use axum::{
+    extract::ConnectInfo,
+    http::{HeaderMap, request::Parts},
+};
+use cidr::IpCidr;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+use uuid::Uuid;
+
+use crate::server::{
+    auth::r#impl::base::{OrganizationIpAllowlist, OrganizationIpAllowlistBase},
+    networks::r#impl::Network,
+    shared::{
+        storage::{
+            filter::EntityFilter,
+            generic::GenericPostgresStorage,
+            traits::{StorableEntity, Storage},
+        },
+        types::api::ApiError,
+    },
+};
+
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+
+/// The address a request came from. The peer address is used unless it is one of
+/// `trusted_proxies`, in which case `X-Forwarded-For` is read from the right, skipping further
+/// trusted proxies, so a client can't spoof its address by prepending entries of its own.
+pub fn client_ip(
+    peer: Option<IpAddr>,
+    headers: &HeaderMap,
+    trusted_proxies: &[IpCidr],
+) -> Option<IpAddr> {
+    let trusted = |ip: &IpAddr| trusted_proxies.iter().any(|cidr| cidr.contains(ip));
+
+    let peer = peer?;
+    if !trusted(&peer) {
+        return Some(peer);
+    }
+
+    let forwarded: Vec<IpAddr> = headers
+        .get_all(FORWARDED_FOR_HEADER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|entry| entry.trim().parse())
+        .collect::<Result<_, _>>()
+        .ok()?;
+
+    Some(
+        forwarded
+            .into_iter()
+            .rev()
+            .find(|ip| !trusted(ip))
+            .unwrap_or(peer),
+    )
+}
+
+/// Whether `ip` is in `cidrs`. An empty list allows everything; otherwise an address that can't
+/// be determined is refused.
+pub fn allows(cidrs: &[IpCidr], ip: Option<IpAddr>) -> bool {
+    cidrs.is_empty() || ip.is_some_and(|ip| cidrs.iter().any(|cidr| cidr.contains(&ip)))
+}
+
+/// Source addresses daemons may reach the server from. Checked whenever a request authenticates
+/// as a daemon, so a leaked API key is useless from anywhere else; user requests aren't affected.
+/// The server-wide allowlist from config bounds every daemon. Each organization can narrow it for
+/// its own daemons at runtime, e.g. after a daemon legitimately moves; that list is stored, so
+/// every instance enforces it and it survives restarts.
+pub struct DaemonIpAllowlist {
+    cidrs: Vec<IpCidr>,
+    trusted_proxies: Vec<IpCidr>,
+    organization_allowlists: Arc<GenericPostgresStorage<OrganizationIpAllowlist>>,
+    networks: Arc<GenericPostgresStorage<Network>>,
+}
+
+impl DaemonIpAllowlist {
+    pub fn new(
+        cidrs: Vec<IpCidr>,
+        trusted_proxies: Vec<IpCidr>,
+        organization_allowlists: Arc<GenericPostgresStorage<OrganizationIpAllowlist>>,
+        networks: Arc<GenericPostgresStorage<Network>>,
+    ) -> Self {
+        Self {
+            cidrs,
+            trusted_proxies,
+            organization_allowlists,
+            networks,
+        }
+    }
+
+    /// The address a request from `peer` with `headers` came from, see [`client_ip`]
+    pub fn client_ip(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+        client_ip(peer, headers, &self.trusted_proxies)
+    }
+
+    /// `organization_id`'s own allowlist
+    pub async fn organization_cidrs(&self, organization_id: &Uuid) -> Result<Vec<IpCidr>> {
+        Ok(self
+            .organization_allowlists
+            .get_one(EntityFilter::unfiltered().organization_id(organization_id))
+            .await?
+            .map(|allowlist| allowlist.base.cidrs)
+            .unwrap_or_default())
+    }
+
+    /// Replace `organization_id`'s own allowlist
+    pub async fn set_organization_cidrs(
+        &self,
+        organization_id: Uuid,
+        cidrs: Vec<IpCidr>,
+    ) -> Result<()> {
+        let allowlist = OrganizationIpAllowlist::new(OrganizationIpAllowlistBase {
+            organization_id,
+            cidrs,
+        });
+
+        self.organization_allowlists
+            .upsert(
+                &allowlist,
+                &["organization_id"],
+                &[("cidrs", "EXCLUDED.cidrs")],
+            )
+            .await
+    }
+
+    /// Whether `ip` may make requests for a daemon on `network_id`: the server-wide allowlist
+    /// and the network's organization's own must both allow it
+    pub async fn allows(&self, network_id: &Uuid, ip: Option<IpAddr>) -> Result<bool> {
+        if !allows(&self.cidrs, ip) {
+            return Ok(false);
+        }
+
+        let Some(network) = self.networks.get_by_id(network_id).await? else {
+            return Ok(false);
+        };
+        let cidrs = self
+            .organization_cidrs(&network.base.organization_id)
+            .await?;
+
+        Ok(allows(&cidrs, ip))
+    }
+
+    /// 403 unless the request, for a daemon on `network_id`, comes from an allowed address
+    pub async fn check_request(&self, network_id: &Uuid, parts: &Parts) -> Result<(), ApiError> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        self.check(network_id, peer, &parts.headers).await
+    }
+
+    /// 403 unless a request for a daemon on `network_id` from `peer` with `headers` comes from
+    /// an allowed address
+    pub async fn check(
+        &self,
+        network_id: &Uuid,
+        peer: Option<IpAddr>,
+        headers: &HeaderMap,
+    ) -> Result<(), ApiError> {
+        let ip = self.client_ip(peer, headers);
+
+        let allowed = self
+            .allows(network_id, ip)
+            .await
+            .map_err(|e| ApiError::internal_error(&e.to_string()))?;
+        if allowed {
+            return Ok(());
+        }
+
+        tracing::warn!(
+            source_ip = ?ip,
+            network_id = %network_id,
+            "Daemon request rejected: source address not in the daemon allowlist"
+        );
+        Err(ApiError::forbidden(
+            "Daemon requests aren't allowed from this address",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidrs(values: &[&str]) -> Vec<IpCidr> {
+        values.iter().map(|v| v.parse().unwrap()).collect()
+    }
+
+    fn forwarded(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(FORWARDED_FOR_HEADER, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_forwarded_for_only_trusted_from_proxies() {
+        let proxies = cidrs(&["10.0.0.0/24"]);
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        // Untrusted peers can't claim another address
+        assert_eq!(
+            client_ip(Some(ip("203.0.113.9")), &forwarded("192.168.1.5"), &proxies),
+            Some(ip("203.0.113.9"))
+        );
+
+        // Behind a proxy, the nearest untrusted hop wins over anything the client prepended
+        assert_eq!(
+            client_ip(
+                Some(ip("10.0.0.2")),
+                &forwarded("192.168.1.5, 198.51.100.7, 10.0.0.3"),
+                &proxies
+            ),
+            Some(ip("198.51.100.7"))
+        );
+        assert_eq!(
+            client_ip(Some(ip("10.0.0.2")), &HeaderMap::new(), &proxies),
+            Some(ip("10.0.0.2"))
+        );
+        assert_eq!(
+            client_ip(Some(ip("10.0.0.2")), &forwarded("not-an-ip"), &proxies),
+            None
+        );
+        assert_eq!(client_ip(None, &HeaderMap::new(), &proxies), None);
+    }
+
+    #[test]
+    fn test_empty_allowlist_allows_all() {
+        assert!(allows(&[], None));
+
+        let allowlist = cidrs(&["192.168.1.0/24"]);
+        assert!(allows(&allowlist, Some("192.168.1.20".parse().unwrap())));
+        assert!(!allows(&allowlist, Some("192.168.2.20".parse().unwrap())));
+        assert!(!allows(&allowlist, None));
+    }
+}
@@ -0,0 +1,52 @@
+use crate::server::{
+    auth::middleware::RequireAdmin,
+    bootstrap_tokens::r#impl::{
+        api::{BootstrapTokenResponse, CreateBootstrapTokenRequest},
+        base::BootstrapToken,
+    },
+    config::AppState,
+    shared::{
+        handlers::traits::{CrudHandlers, admin_delete_handler, get_all_handler},
+        types::api::{ApiError, ApiResponse, ApiResult},
+    },
+};
+use axum::{
+    Json, Router,
+    extract::State,
+    routing::{delete, get, post},
+};
+use std::sync::Arc;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_all_handler::<BootstrapToken>))
+        .route("/", post(create_handler))
+        .route("/{id}", delete(admin_delete_handler::<BootstrapToken>))
+}
+
+pub async fn create_handler(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Json(request): Json<CreateBootstrapTokenRequest>,
+) -> ApiResult<Json<ApiResponse<BootstrapTokenResponse>>> {
+    if !user.network_ids.contains(&request.network_id) {
+        return Err(ApiError::forbidden("You don't have access to this network"));
+    }
+
+    let service = BootstrapToken::get_service(&state);
+    let (bootstrap_token, token) = service
+        .mint(request)
+        .await
+        .map_err(|e| ApiError::bad_request(&e.to_string()))?;
+
+    tracing::info!(
+        bootstrap_token_id = %bootstrap_token.id,
+        user_id = %user.user_id,
+        "Bootstrap token created via API (token shown to user)"
+    );
+
+    Ok(Json(ApiResponse::success(BootstrapTokenResponse {
+        bootstrap_token,
+        token,
+    })))
+}
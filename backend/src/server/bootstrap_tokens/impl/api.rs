@@ -0,0 +1,26 @@
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::server::bootstrap_tokens::r#impl::base::BootstrapToken;
+
+fn default_ttl_minutes() -> i64 {
+    60
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateBootstrapTokenRequest {
+    pub name: String,
+    pub network_id: Uuid,
+    #[serde(default = "default_ttl_minutes")]
+    pub ttl_minutes: i64,
+    #[serde(default)]
+    pub allowed_ip: Option<IpAddr>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootstrapTokenResponse {
+    pub bootstrap_token: BootstrapToken,
+    pub token: String,
+}
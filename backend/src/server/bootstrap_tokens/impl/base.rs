@@ -0,0 +1,44 @@
+use std::{fmt::Display, net::IpAddr};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Short-lived, single-use token a new daemon exchanges for its permanent API key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootstrapTokenBase {
+    /// SHA-256 of the token; the plaintext is only shown once when minted
+    #[serde(skip_serializing, default)]
+    pub token_hash: String,
+    pub name: String,
+    pub network_id: Uuid,
+    /// If set, registration is only accepted from this address
+    pub allowed_ip: Option<IpAddr>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootstrapToken {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub base: BootstrapTokenBase,
+}
+
+impl BootstrapToken {
+    pub fn is_expired(&self) -> bool {
+        Utc::now() > self.base.expires_at
+    }
+
+    pub fn is_used(&self) -> bool {
+        self.base.used_at.is_some()
+    }
+}
+
+impl Display for BootstrapToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.base.name, self.id)
+    }
+}
@@ -0,0 +1,15 @@
+use crate::server::bootstrap_tokens::r#impl::base::BootstrapToken;
+use crate::server::bootstrap_tokens::service::BootstrapTokenService;
+use crate::server::shared::handlers::traits::{CrudHandlers, EntityScope};
+
+impl CrudHandlers for BootstrapToken {
+    type Service = BootstrapTokenService;
+
+    fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
+        &state.services.bootstrap_token_service
+    }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.base.network_id)
+    }
+}
@@ -0,0 +1,108 @@
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+use uuid::Uuid;
+
+use crate::server::{
+    bootstrap_tokens::r#impl::base::{BootstrapToken, BootstrapTokenBase},
+    shared::storage::traits::{SqlValue, StorableEntity},
+};
+
+impl StorableEntity for BootstrapToken {
+    type BaseData = BootstrapTokenBase;
+
+    fn table_name() -> &'static str {
+        "bootstrap_tokens"
+    }
+
+    fn get_base(&self) -> Self::BaseData {
+        self.base.clone()
+    }
+
+    fn new(base: Self::BaseData) -> Self {
+        let now = chrono::Utc::now();
+
+        Self {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: now,
+            base,
+        }
+    }
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn set_updated_at(&mut self, time: DateTime<Utc>) {
+        self.updated_at = time;
+    }
+
+    fn to_params(&self) -> Result<(Vec<&'static str>, Vec<SqlValue>), anyhow::Error> {
+        let Self {
+            id,
+            created_at,
+            updated_at,
+            base:
+                Self::BaseData {
+                    token_hash,
+                    name,
+                    network_id,
+                    allowed_ip,
+                    expires_at,
+                    used_at,
+                },
+        } = self.clone();
+
+        Ok((
+            vec![
+                "id",
+                "created_at",
+                "updated_at",
+                "token_hash",
+                "name",
+                "network_id",
+                "allowed_ip",
+                "expires_at",
+                "used_at",
+            ],
+            vec![
+                SqlValue::Uuid(id),
+                SqlValue::Timestamp(created_at),
+                SqlValue::Timestamp(updated_at),
+                SqlValue::String(token_hash),
+                SqlValue::String(name),
+                SqlValue::Uuid(network_id),
+                SqlValue::OptionalString(allowed_ip.map(|ip| ip.to_string())),
+                SqlValue::Timestamp(expires_at),
+                SqlValue::OptionTimestamp(used_at),
+            ],
+        ))
+    }
+
+    fn from_row(row: &PgRow) -> Result<Self, anyhow::Error> {
+        let allowed_ip: Option<String> = row.get("allowed_ip");
+
+        Ok(BootstrapToken {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            base: BootstrapTokenBase {
+                token_hash: row.get("token_hash"),
+                name: row.get("name"),
+                network_id: row.get("network_id"),
+                allowed_ip: allowed_ip.map(|ip| ip.parse()).transpose()?,
+                expires_at: row.get("expires_at"),
+                used_at: row.get("used_at"),
+            },
+        })
+    }
+}
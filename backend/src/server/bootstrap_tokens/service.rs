@@ -0,0 +1,159 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgConnection;
+use std::{net::IpAddr, sync::Arc};
+use uuid::Uuid;
+
+use crate::server::{
+    bootstrap_tokens::r#impl::{
+        api::CreateBootstrapTokenRequest,
+        base::{BootstrapToken, BootstrapTokenBase},
+    },
+    shared::{
+        services::traits::CrudService,
+        storage::{
+            filter::EntityFilter,
+            generic::GenericPostgresStorage,
+            traits::{SqlValue, StorableEntity, Storage},
+        },
+    },
+};
+
+pub struct BootstrapTokenService {
+    storage: Arc<GenericPostgresStorage<BootstrapToken>>,
+}
+
+#[async_trait]
+impl CrudService<BootstrapToken> for BootstrapTokenService {
+    fn storage(&self) -> &Arc<GenericPostgresStorage<BootstrapToken>> {
+        &self.storage
+    }
+}
+
+impl BootstrapTokenService {
+    pub fn new(storage: Arc<GenericPostgresStorage<BootstrapToken>>) -> Self {
+        Self { storage }
+    }
+
+    pub fn hash_token(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+
+    /// Mint a new token, returning the stored record and the plaintext token (shown only once)
+    pub async fn mint(
+        &self,
+        request: CreateBootstrapTokenRequest,
+    ) -> Result<(BootstrapToken, String)> {
+        if request.ttl_minutes <= 0 {
+            return Err(anyhow!("Bootstrap token TTL must be positive"));
+        }
+
+        let token = Uuid::new_v4().simple().to_string();
+
+        let bootstrap_token = BootstrapToken::new(BootstrapTokenBase {
+            token_hash: Self::hash_token(&token),
+            name: request.name,
+            network_id: request.network_id,
+            allowed_ip: request.allowed_ip,
+            expires_at: Utc::now() + Duration::minutes(request.ttl_minutes),
+            used_at: None,
+        });
+
+        let created = self.storage.create(&bootstrap_token).await?;
+
+        tracing::info!(
+            bootstrap_token_id = %created.id,
+            network_id = %created.base.network_id,
+            expires_at = %created.base.expires_at,
+            "Bootstrap token minted"
+        );
+
+        Ok((created, token))
+    }
+
+    /// Validate and consume a token for registering a daemon on `network_id` from `peer_ip`
+    pub async fn redeem(
+        &self,
+        token: &str,
+        network_id: Uuid,
+        peer_ip: Option<IpAddr>,
+    ) -> Result<BootstrapToken> {
+        let bootstrap_token = self.validate(token, peer_ip).await?;
+        if bootstrap_token.base.network_id != network_id {
+            return Err(anyhow!("Bootstrap token is not valid for this network"));
+        }
+
+        let mut tx = self.storage.begin().await?;
+        let redeemed = self.redeem_in(&mut tx, &bootstrap_token).await?;
+        tx.commit().await?;
+        Ok(redeemed)
+    }
+
+    /// Check a token could be redeemed by a daemon registering from `peer_ip`, without consuming
+    /// it. The daemon joins the token's network.
+    pub async fn validate(&self, token: &str, peer_ip: Option<IpAddr>) -> Result<BootstrapToken> {
+        let filter = EntityFilter::unfiltered().token_hash(Self::hash_token(token));
+        let bootstrap_token = self
+            .storage
+            .get_one(filter)
+            .await?
+            .ok_or_else(|| anyhow!("Invalid bootstrap token"))?;
+
+        if bootstrap_token.is_used() {
+            tracing::warn!(
+                bootstrap_token_id = %bootstrap_token.id,
+                "Rejected reuse of bootstrap token"
+            );
+            return Err(anyhow!("Bootstrap token has already been used"));
+        }
+
+        if bootstrap_token.is_expired() {
+            return Err(anyhow!("Bootstrap token has expired"));
+        }
+
+        if let Some(allowed_ip) = bootstrap_token.base.allowed_ip
+            && peer_ip != Some(allowed_ip)
+        {
+            tracing::warn!(
+                bootstrap_token_id = %bootstrap_token.id,
+                allowed_ip = %allowed_ip,
+                peer_ip = ?peer_ip,
+                "Rejected bootstrap token from unexpected address"
+            );
+            return Err(anyhow!("Bootstrap token is not valid for this address"));
+        }
+
+        Ok(bootstrap_token)
+    }
+
+    /// Consume a validated token on the caller's connection. The claim is a single conditional
+    /// update, so of two concurrent registrations with the same token only one gets it, and the
+    /// token stays unused if the caller's transaction rolls back.
+    pub async fn redeem_in(
+        &self,
+        conn: &mut PgConnection,
+        bootstrap_token: &BootstrapToken,
+    ) -> Result<BootstrapToken> {
+        let filter = EntityFilter::unfiltered()
+            .token_hash(bootstrap_token.base.token_hash.clone())
+            .unredeemed();
+
+        self.storage
+            .update_where_in(
+                conn,
+                filter,
+                vec![("used_at", SqlValue::Timestamp(Utc::now()))],
+            )
+            .await?
+            .pop()
+            .ok_or_else(|| {
+                tracing::warn!(
+                    bootstrap_token_id = %bootstrap_token.id,
+                    "Rejected reuse of bootstrap token"
+                );
+                anyhow!("Bootstrap token has already been used")
+            })
+    }
+}
@@ -0,0 +1,146 @@
+use serial_test::serial;
+use std::net::{IpAddr, Ipv4Addr};
+
+use crate::{
+    server::{
+        bootstrap_tokens::r#impl::api::CreateBootstrapTokenRequest,
+        shared::{services::traits::CrudService, storage::traits::Storage},
+    },
+    tests::*,
+};
+
+fn request(network_id: uuid::Uuid) -> CreateBootstrapTokenRequest {
+    CreateBootstrapTokenRequest {
+        name: "edge-daemon".to_string(),
+        network_id,
+        ttl_minutes: 60,
+        allowed_ip: None,
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_bootstrap_token_redeemed_once() {
+    let (storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+
+    let (minted, token) = services
+        .bootstrap_token_service
+        .mint(request(network.id))
+        .await
+        .unwrap();
+
+    // Only the hash is persisted
+    let stored = storage
+        .bootstrap_tokens
+        .get_by_id(&minted.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_ne!(stored.base.token_hash, token);
+
+    // A redemption whose registration rolls back leaves the token usable
+    let validated = services
+        .bootstrap_token_service
+        .validate(&token, None)
+        .await
+        .unwrap();
+    let mut tx = storage.bootstrap_tokens.begin().await.unwrap();
+    services
+        .bootstrap_token_service
+        .redeem_in(&mut tx, &validated)
+        .await
+        .unwrap();
+    tx.rollback().await.unwrap();
+
+    // Of two concurrent redemptions only one claims the token
+    let (first, second) = tokio::join!(
+        services
+            .bootstrap_token_service
+            .redeem(&token, network.id, None),
+        services
+            .bootstrap_token_service
+            .redeem(&token, network.id, None),
+    );
+    assert!(first.is_ok() != second.is_ok());
+    let redeemed = first.or(second).unwrap();
+    assert_eq!(redeemed.id, minted.id);
+    assert!(redeemed.is_used());
+
+    let reused = services
+        .bootstrap_token_service
+        .redeem(&token, network.id, None)
+        .await;
+    assert!(reused.is_err());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_bootstrap_token_rejects_expired_wrong_network_and_unpinned_ip() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let service = &services.bootstrap_token_service;
+
+    // Expired
+    let (mut expired, expired_token) = service.mint(request(network.id)).await.unwrap();
+    expired.base.expires_at = chrono::Utc::now() - chrono::Duration::minutes(1);
+    service.update(&mut expired).await.unwrap();
+    assert!(
+        service
+            .redeem(&expired_token, network.id, None)
+            .await
+            .is_err()
+    );
+
+    // Wrong network
+    let (_, token) = service.mint(request(network.id)).await.unwrap();
+    assert!(
+        service
+            .redeem(&token, uuid::Uuid::new_v4(), None)
+            .await
+            .is_err()
+    );
+
+    // IP pin
+    let pinned_ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5));
+    let (_, pinned_token) = service
+        .mint(CreateBootstrapTokenRequest {
+            allowed_ip: Some(pinned_ip),
+            ..request(network.id)
+        })
+        .await
+        .unwrap();
+    let other_ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 6));
+    assert!(
+        service
+            .redeem(&pinned_token, network.id, Some(other_ip))
+            .await
+            .is_err()
+    );
+    assert!(
+        service
+            .redeem(&pinned_token, network.id, Some(pinned_ip))
+            .await
+            .is_ok()
+    );
+}
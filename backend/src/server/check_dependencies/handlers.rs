@@ -0,0 +1,60 @@
+use crate::server::{
+    auth::middleware::RequireAdmin,
+    check_dependencies::r#impl::base::CheckDependency,
+    config::AppState,
+    shared::{
+        handlers::traits::{CrudHandlers, admin_delete_handler, get_all_handler},
+        services::traits::CrudService,
+        types::api::{ApiError, ApiResponse, ApiResult},
+    },
+};
+use axum::{
+    Json, Router,
+    extract::State,
+    routing::{delete, get, post},
+};
+use std::sync::Arc;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_all_handler::<CheckDependency>))
+        .route("/", post(create_handler))
+        .route("/{id}", delete(admin_delete_handler::<CheckDependency>))
+}
+
+/// Declare a dependency between two check targets. Dependencies that would form a cycle are
+/// rejected.
+async fn create_handler(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Json(mut dependency): Json<CheckDependency>,
+) -> ApiResult<Json<ApiResponse<CheckDependency>>> {
+    dependency.validate().map_err(|e| {
+        ApiError::bad_request(&format!("Check dependency validation failed: {}", e))
+    })?;
+
+    if !user.network_ids.contains(&dependency.base.network_id) {
+        return Err(ApiError::forbidden("You don't have access to this network"));
+    }
+
+    dependency.base.target = dependency.base.target.trim().to_string();
+    dependency.base.depends_on = dependency.base.depends_on.trim().to_string();
+
+    let service = CheckDependency::get_service(&state);
+    if service.creates_cycle(&dependency).await? {
+        return Err(ApiError::conflict(&format!(
+            "{} already depends on {}, so this would create a cycle",
+            dependency.base.depends_on, dependency.base.target
+        )));
+    }
+
+    let created = service.create(dependency).await?;
+
+    tracing::info!(
+        check_dependency_id = %created.id,
+        user_id = %user.user_id,
+        "Check dependency created via API"
+    );
+
+    Ok(Json(ApiResponse::success(created)))
+}
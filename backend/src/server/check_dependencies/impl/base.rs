@@ -0,0 +1,108 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Declares that checks of `target` can only succeed while `depends_on` is reachable, e.g. a host
+/// behind a gateway. Both are check targets as recorded on diagnostics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckDependencyBase {
+    pub network_id: Uuid,
+    pub target: String,
+    pub depends_on: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckDependency {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub base: CheckDependencyBase,
+}
+
+impl Display for CheckDependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Check dependency {} -> {}: {}",
+            self.base.target, self.base.depends_on, self.id
+        )
+    }
+}
+
+impl CheckDependencyBase {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.target.trim().is_empty() || self.depends_on.trim().is_empty() {
+            return Err("Target and dependency can't be empty".to_string());
+        }
+        if self.target.trim() == self.depends_on.trim() {
+            return Err("A target can't depend on itself".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Everything `target` depends on, directly or through other dependencies, nearest first. None
+/// when the dependencies loop back to `target`, since a cycle can't say which end is unreachable.
+pub fn upstream<'a>(dependencies: &'a [CheckDependency], target: &str) -> Option<Vec<&'a str>> {
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([target]);
+    let mut upstream = Vec::new();
+
+    while let Some(current) = queue.pop_front() {
+        for dependency in dependencies.iter().filter(|d| d.base.target == current) {
+            let next = dependency.base.depends_on.as_str();
+            if next == target {
+                return None;
+            }
+            if seen.insert(next) {
+                upstream.push(next);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    Some(upstream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::shared::storage::traits::StorableEntity;
+
+    fn dependency(target: &str, depends_on: &str) -> CheckDependency {
+        CheckDependency::new(CheckDependencyBase {
+            network_id: Uuid::nil(),
+            target: target.to_string(),
+            depends_on: depends_on.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_upstream_walks_chain_and_detects_cycles() {
+        let mut dependencies = vec![
+            dependency("host", "switch"),
+            dependency("switch", "gateway"),
+            dependency("other", "gateway"),
+        ];
+
+        assert_eq!(
+            upstream(&dependencies, "host"),
+            Some(vec!["switch", "gateway"])
+        );
+        assert_eq!(upstream(&dependencies, "gateway"), Some(vec![]));
+
+        // Closing the loop leaves every target on it without usable dependencies
+        dependencies.push(dependency("gateway", "host"));
+        assert_eq!(upstream(&dependencies, "host"), None);
+        assert_eq!(upstream(&dependencies, "gateway"), None);
+        assert_eq!(
+            upstream(&dependencies, "other"),
+            Some(vec!["gateway", "host", "switch"])
+        );
+    }
+}
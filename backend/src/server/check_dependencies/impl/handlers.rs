@@ -0,0 +1,19 @@
+use crate::server::check_dependencies::r#impl::base::CheckDependency;
+use crate::server::check_dependencies::service::CheckDependencyService;
+use crate::server::shared::handlers::traits::{CrudHandlers, EntityScope};
+
+impl CrudHandlers for CheckDependency {
+    type Service = CheckDependencyService;
+
+    fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
+        &state.services.check_dependency_service
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.base.validate()
+    }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.base.network_id)
+    }
+}
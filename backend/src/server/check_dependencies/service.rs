@@ -0,0 +1,66 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::server::{
+    check_dependencies::r#impl::base::{CheckDependency, upstream},
+    shared::{
+        services::traits::CrudService,
+        storage::{filter::EntityFilter, generic::GenericPostgresStorage, traits::Storage},
+    },
+};
+
+pub struct CheckDependencyService {
+    storage: Arc<GenericPostgresStorage<CheckDependency>>,
+}
+
+#[async_trait]
+impl CrudService<CheckDependency> for CheckDependencyService {
+    fn storage(&self) -> &Arc<GenericPostgresStorage<CheckDependency>> {
+        &self.storage
+    }
+}
+
+impl CheckDependencyService {
+    pub fn new(storage: Arc<GenericPostgresStorage<CheckDependency>>) -> Self {
+        Self { storage }
+    }
+
+    pub async fn dependencies_for_network(
+        &self,
+        network_id: &Uuid,
+    ) -> Result<Vec<CheckDependency>> {
+        self.storage
+            .get_all(EntityFilter::unfiltered().network_ids(&[*network_id]))
+            .await
+    }
+
+    /// Whether saving `candidate` would make its target depend on itself
+    pub async fn creates_cycle(&self, candidate: &CheckDependency) -> Result<bool> {
+        let mut dependencies = self
+            .dependencies_for_network(&candidate.base.network_id)
+            .await?;
+        dependencies.push(candidate.clone());
+
+        Ok(upstream(&dependencies, &candidate.base.target).is_none())
+    }
+
+    /// Targets that must be reachable for checks of `target` to succeed, nearest first. Targets
+    /// caught in a dependency cycle get none, so their alerts are never suppressed.
+    pub async fn upstream_of(&self, network_id: &Uuid, target: &str) -> Result<Vec<String>> {
+        let dependencies = self.dependencies_for_network(network_id).await?;
+
+        match upstream(&dependencies, target) {
+            Some(targets) => Ok(targets.into_iter().map(str::to_string).collect()),
+            None => {
+                tracing::warn!(
+                    network_id = %network_id,
+                    target = %target,
+                    "Check dependencies form a cycle, ignoring them for this target"
+                );
+                Ok(Vec::new())
+            }
+        }
+    }
+}
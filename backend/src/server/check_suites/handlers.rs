@@ -0,0 +1,129 @@
+use crate::server::{
+    auth::middleware::RequireAdmin,
+    check_suites::r#impl::{api::CheckSuiteRun, base::CheckSuite},
+    config::AppState,
+    daemons::r#impl::base::DaemonPaused,
+    discovery::r#impl::target_policy::ScanTargetDenied,
+    shared::{
+        handlers::traits::{
+            CrudHandlers, admin_delete_handler, get_all_handler, get_by_id_handler,
+        },
+        services::traits::CrudService,
+        types::api::{ApiError, ApiResponse, ApiResult},
+    },
+};
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{delete, get, post, put},
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_all_handler::<CheckSuite>))
+        .route("/", post(create_handler))
+        .route("/{id}", get(get_by_id_handler::<CheckSuite>))
+        .route("/{id}", put(update_handler))
+        .route("/{id}", delete(admin_delete_handler::<CheckSuite>))
+        .route("/{id}/run", post(run_handler))
+}
+
+/// Validate a suite and check it runs from a daemon on its own network, which the user can access
+async fn check_suite_access(
+    state: &AppState,
+    network_ids: &[Uuid],
+    suite: &CheckSuite,
+) -> ApiResult<()> {
+    suite
+        .validate()
+        .map_err(|e| ApiError::bad_request(&format!("Check suite validation failed: {}", e)))?;
+
+    if !network_ids.contains(&suite.base.network_id) {
+        return Err(ApiError::forbidden("You don't have access to this network"));
+    }
+
+    state
+        .services
+        .daemon_service
+        .get_by_id(&suite.base.daemon_id)
+        .await?
+        .filter(|d| d.base.network_id == suite.base.network_id)
+        .ok_or_else(|| {
+            ApiError::bad_request(&format!(
+                "Daemon {} is not on the suite's network",
+                suite.base.daemon_id
+            ))
+        })?;
+
+    Ok(())
+}
+
+async fn create_handler(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Json(mut suite): Json<CheckSuite>,
+) -> ApiResult<Json<ApiResponse<CheckSuite>>> {
+    check_suite_access(&state, &user.network_ids, &suite).await?;
+
+    suite.base.name = suite.base.name.trim().to_string();
+    suite.base.last_run_at = None;
+    let created = CheckSuite::get_service(&state).create(suite).await?;
+
+    tracing::info!(
+        check_suite_id = %created.id,
+        user_id = %user.user_id,
+        "Check suite created via API"
+    );
+
+    Ok(Json(ApiResponse::success(created)))
+}
+
+async fn update_handler(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Path(id): Path<Uuid>,
+    Json(mut suite): Json<CheckSuite>,
+) -> ApiResult<Json<ApiResponse<CheckSuite>>> {
+    let service = CheckSuite::get_service(&state);
+    let existing = service
+        .get_by_id(&id)
+        .await?
+        .filter(|s| user.network_ids.contains(&s.base.network_id))
+        .ok_or_else(|| ApiError::not_found(format!("Check suite '{}' not found", id)))?;
+
+    check_suite_access(&state, &user.network_ids, &suite).await?;
+
+    suite.id = id;
+    suite.base.name = suite.base.name.trim().to_string();
+    suite.base.last_run_at = existing.base.last_run_at;
+    let updated = service.update(&mut suite).await?;
+
+    Ok(Json(ApiResponse::success(updated)))
+}
+
+/// Run a suite now, returning its combined report
+async fn run_handler(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<ApiResponse<CheckSuiteRun>>> {
+    let service = CheckSuite::get_service(&state);
+    let suite = service
+        .get_by_id(&id)
+        .await?
+        .filter(|s| user.network_ids.contains(&s.base.network_id))
+        .ok_or_else(|| ApiError::not_found(format!("Check suite '{}' not found", id)))?;
+
+    let run = service
+        .run(&suite)
+        .await
+        .map_err(|e| match e.downcast::<ScanTargetDenied>() {
+            Ok(denied) => ApiError::forbidden(&denied.to_string()),
+            Err(e) if e.is::<DaemonPaused>() => e.into(),
+            Err(e) => ApiError::bad_gateway(&e.to_string()),
+        })?;
+
+    Ok(Json(ApiResponse::success(run)))
+}
@@ -0,0 +1,16 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::daemon::utils::network_checks::suite::SuiteReport;
+
+/// Report of one run of a suite. Each check that ran is also stored as a diagnostic under
+/// `run_id` as its correlation id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckSuiteRun {
+    pub suite_id: Uuid,
+    pub run_id: Uuid,
+    pub ran_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub report: SuiteReport,
+}
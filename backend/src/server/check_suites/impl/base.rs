@@ -0,0 +1,115 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::daemon::utils::network_checks::suite::SuiteDefinition;
+
+pub const MIN_SUITE_INTERVAL_SECS: i32 = 60;
+
+/// A named, reusable set of checks run together from one daemon, on demand or on a schedule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckSuiteBase {
+    pub network_id: Uuid,
+    pub name: String,
+    /// Daemon the checks run from
+    pub daemon_id: Uuid,
+    #[serde(flatten)]
+    pub definition: SuiteDefinition,
+    /// Run the suite every this many seconds. Without it the suite only runs on demand.
+    #[serde(default)]
+    pub interval_secs: Option<i32>,
+    /// When more scheduled suites are due than can run at once, higher priorities go first
+    #[serde(default)]
+    pub priority: i32,
+    /// Set by the server when the suite runs
+    #[serde(default)]
+    pub last_run_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckSuite {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub base: CheckSuiteBase,
+}
+
+impl Display for CheckSuite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Check suite {}: {}", self.base.name, self.id)
+    }
+}
+
+impl CheckSuiteBase {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Name can't be empty".to_string());
+        }
+        if self
+            .interval_secs
+            .is_some_and(|secs| secs < MIN_SUITE_INTERVAL_SECS)
+        {
+            return Err(format!(
+                "Suites can run at most every {} seconds",
+                MIN_SUITE_INTERVAL_SECS
+            ));
+        }
+
+        self.definition.validate()
+    }
+}
+
+impl CheckSuite {
+    /// Whether a scheduled suite's next run is due at `now`. Suites that have never run are due
+    /// straight away.
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.base.interval_secs.is_some_and(|secs| {
+            self.base
+                .last_run_at
+                .is_none_or(|last| last + Duration::seconds(secs.into()) <= now)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::utils::network_checks::suite::{SuiteCheck, SuiteMember};
+    use crate::server::shared::storage::traits::StorableEntity;
+
+    #[test]
+    fn test_only_scheduled_suites_come_due() {
+        let now = Utc::now();
+        let mut suite = CheckSuite::new(CheckSuiteBase {
+            network_id: Uuid::nil(),
+            name: "gateway".to_string(),
+            daemon_id: Uuid::nil(),
+            definition: SuiteDefinition {
+                checks: vec![SuiteMember {
+                    check: SuiteCheck::Icmp {
+                        ip: "10.0.0.1".parse().unwrap(),
+                    },
+                    advisory: false,
+                }],
+                ..Default::default()
+            },
+            interval_secs: None,
+            priority: 0,
+            last_run_at: None,
+        });
+        assert!(suite.base.validate().is_ok());
+        assert!(!suite.is_due(now));
+
+        suite.base.interval_secs = Some(300);
+        assert!(suite.is_due(now));
+        suite.base.last_run_at = Some(now - Duration::seconds(120));
+        assert!(!suite.is_due(now));
+        assert!(suite.is_due(now + Duration::seconds(180)));
+
+        suite.base.interval_secs = Some(5);
+        assert!(suite.base.validate().is_err());
+    }
+}
@@ -0,0 +1,19 @@
+use crate::server::check_suites::r#impl::base::CheckSuite;
+use crate::server::check_suites::service::CheckSuiteService;
+use crate::server::shared::handlers::traits::{CrudHandlers, EntityScope};
+
+impl CrudHandlers for CheckSuite {
+    type Service = CheckSuiteService;
+
+    fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
+        &state.services.check_suite_service
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.base.validate()
+    }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.base.network_id)
+    }
+}
@@ -0,0 +1,121 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// Suites waiting for a free worker. Higher priorities run first, but every `aging` a suite
+/// waits counts as one more priority, so a steady stream of high-priority suites can't hold a
+/// low-priority one back forever. Suites of equal priority run in the order they were queued.
+pub struct RunQueue<T> {
+    heap: BinaryHeap<Queued<T>>,
+    /// Milliseconds waited per priority gained
+    aging_ms: i64,
+    next_seq: u64,
+}
+
+struct Queued<T> {
+    /// Priority in aging steps, less the time queued. Aging is the same for every suite, so a
+    /// suite's rank relative to the others never changes while it waits.
+    rank: i64,
+    seq: u64,
+    item: T,
+}
+
+impl<T> PartialEq for Queued<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Queued<T> {}
+
+impl<T> PartialOrd for Queued<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Queued<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank
+            .cmp(&other.rank)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl<T> RunQueue<T> {
+    pub fn new(aging: Duration) -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+            aging_ms: i64::try_from(aging.as_millis()).unwrap_or(i64::MAX).max(1),
+            next_seq: 0,
+        }
+    }
+
+    pub fn push(&mut self, item: T, priority: i32, queued_at: DateTime<Utc>) {
+        let rank = i64::from(priority)
+            .saturating_mul(self.aging_ms)
+            .saturating_sub(queued_at.timestamp_millis());
+
+        self.heap.push(Queued {
+            rank,
+            seq: self.next_seq,
+            item,
+        });
+        self.next_seq += 1;
+    }
+
+    /// The suite that should run next
+    pub fn pop(&mut self) -> Option<T> {
+        self.heap.pop().map(|queued| queued.item)
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration as TimeDelta;
+
+    #[test]
+    fn test_priority_jumps_queue_but_aged_suites_still_run() {
+        let start = Utc::now();
+        let at = |secs| start + TimeDelta::seconds(secs);
+        let mut queue = RunQueue::new(Duration::from_secs(60));
+
+        // Equal priorities keep queue order
+        queue.push("first", 0, at(0));
+        queue.push("second", 0, at(0));
+        assert_eq!(queue.pop(), Some("first"));
+        assert_eq!(queue.pop(), Some("second"));
+
+        // With one worker, a later high-priority suite runs before an earlier low-priority one
+        queue.push("low", 0, at(0));
+        queue.push("high", 5, at(1));
+        assert_eq!(queue.pop(), Some("high"));
+        assert_eq!(queue.pop(), Some("low"));
+
+        // A flood of high-priority suites, one queued every time one finishes, only holds the
+        // low-priority suite back until it has waited five aging steps
+        queue.push("low", 0, at(0));
+        let mut now = 0;
+        let ran_low_at = loop {
+            queue.push("high", 5, at(now));
+            match queue.pop() {
+                Some("low") => break now,
+                Some(_) => now += 10,
+                None => unreachable!(),
+            }
+            assert!(now < 3600, "low-priority suite starved");
+        };
+        assert!((300..=310).contains(&ran_low_at), "{}", ran_low_at);
+        assert_eq!(queue.len(), 1);
+    }
+}
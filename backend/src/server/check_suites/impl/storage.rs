@@ -0,0 +1,113 @@
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+use uuid::Uuid;
+
+use crate::server::{
+    check_suites::r#impl::base::{CheckSuite, CheckSuiteBase},
+    shared::storage::traits::{SqlValue, StorableEntity},
+};
+
+impl StorableEntity for CheckSuite {
+    type BaseData = CheckSuiteBase;
+
+    fn table_name() -> &'static str {
+        "check_suites"
+    }
+
+    fn get_base(&self) -> Self::BaseData {
+        self.base.clone()
+    }
+
+    fn new(base: Self::BaseData) -> Self {
+        let now = chrono::Utc::now();
+
+        Self {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: now,
+            base,
+        }
+    }
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn set_updated_at(&mut self, time: DateTime<Utc>) {
+        self.updated_at = time;
+    }
+
+    fn to_params(&self) -> Result<(Vec<&'static str>, Vec<SqlValue>), anyhow::Error> {
+        let Self {
+            id,
+            created_at,
+            updated_at,
+            base:
+                Self::BaseData {
+                    network_id,
+                    name,
+                    daemon_id,
+                    definition,
+                    interval_secs,
+                    priority,
+                    last_run_at,
+                },
+        } = self.clone();
+
+        Ok((
+            vec![
+                "id",
+                "created_at",
+                "updated_at",
+                "network_id",
+                "name",
+                "daemon_id",
+                "definition",
+                "interval_secs",
+                "priority",
+                "last_run_at",
+            ],
+            vec![
+                SqlValue::Uuid(id),
+                SqlValue::Timestamp(created_at),
+                SqlValue::Timestamp(updated_at),
+                SqlValue::Uuid(network_id),
+                SqlValue::String(name),
+                SqlValue::Uuid(daemon_id),
+                SqlValue::Json(serde_json::to_value(definition)?),
+                SqlValue::OptionalI32(interval_secs),
+                SqlValue::I32(priority),
+                SqlValue::OptionTimestamp(last_run_at),
+            ],
+        ))
+    }
+
+    fn from_row(row: &PgRow) -> Result<Self, anyhow::Error> {
+        let definition = serde_json::from_value(row.get::<serde_json::Value, _>("definition"))
+            .map_err(|e| anyhow::anyhow!("Failed to deserialize check suite definition: {}", e))?;
+
+        Ok(CheckSuite {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            base: CheckSuiteBase {
+                network_id: row.get("network_id"),
+                name: row.get("name"),
+                daemon_id: row.get("daemon_id"),
+                definition,
+                interval_secs: row.get("interval_secs"),
+                priority: row.get("priority"),
+                last_run_at: row.get("last_run_at"),
+            },
+        })
+    }
+}
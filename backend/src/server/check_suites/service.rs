@@ -0,0 +1,225 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+
+use crate::{
+    daemon::utils::network_checks::suite::SuiteStatus,
+    server::{
+        check_suites::r#impl::{api::CheckSuiteRun, base::CheckSuite, queue::RunQueue},
+        daemons::service::DaemonService,
+        diagnostics::{
+            r#impl::base::{Diagnostic, DiagnosticBase},
+            service::DiagnosticService,
+        },
+        shared::{
+            services::traits::CrudService,
+            storage::{
+                filter::EntityFilter,
+                generic::GenericPostgresStorage,
+                traits::{SqlValue, StorableEntity, Storage},
+            },
+            sync::lock,
+        },
+    },
+};
+
+pub struct CheckSuiteService {
+    storage: Arc<GenericPostgresStorage<CheckSuite>>,
+    daemon_service: Arc<DaemonService>,
+    diagnostic_service: Arc<DiagnosticService>,
+    /// Ids of due suites waiting for a worker, read again when one picks them up
+    queue: Mutex<RunQueue<Uuid>>,
+    /// Suites queued or running, so one still waiting isn't queued again when it next comes due
+    pending: Mutex<HashSet<Uuid>>,
+    workers: Arc<Semaphore>,
+}
+
+#[async_trait]
+impl CrudService<CheckSuite> for CheckSuiteService {
+    fn storage(&self) -> &Arc<GenericPostgresStorage<CheckSuite>> {
+        &self.storage
+    }
+}
+
+impl CheckSuiteService {
+    pub fn new(
+        storage: Arc<GenericPostgresStorage<CheckSuite>>,
+        daemon_service: Arc<DaemonService>,
+        diagnostic_service: Arc<DiagnosticService>,
+        concurrency: usize,
+        aging: Duration,
+    ) -> Self {
+        Self {
+            storage,
+            daemon_service,
+            diagnostic_service,
+            queue: Mutex::new(RunQueue::new(aging)),
+            pending: Mutex::new(HashSet::new()),
+            workers: Arc::new(Semaphore::new(concurrency.max(1))),
+        }
+    }
+
+    /// Run `suite` on its daemon as one job. Every check that ran is recorded as a diagnostic
+    /// under the run's correlation id and tagged with the suite, so alerting and dependency
+    /// suppression apply to them as to any other check.
+    pub async fn run(&self, suite: &CheckSuite) -> Result<CheckSuiteRun> {
+        let daemon = self
+            .daemon_service
+            .get_by_id(&suite.base.daemon_id)
+            .await?
+            .filter(|d| d.base.network_id == suite.base.network_id)
+            .ok_or_else(|| anyhow!("Daemon {} not found", suite.base.daemon_id))?;
+
+        let ran_at = Utc::now();
+        let report = self
+            .daemon_service
+            .run_check_suite(&daemon, &suite.base.definition)
+            .await?;
+        let run_id = Uuid::new_v4();
+
+        for result in report
+            .results
+            .iter()
+            .filter(|r| r.status != SuiteStatus::Skipped)
+        {
+            let check = &result.member.check;
+            let diagnostic = DiagnosticBase {
+                network_id: suite.base.network_id,
+                daemon_id: Some(daemon.id),
+                kind: check.kind(),
+                target: check.target(),
+                success: result.outcome.is_some(),
+                detail: result.outcome.as_ref().and_then(|o| o.detail.clone()),
+                error: result.error.clone(),
+                correlation_id: Some(run_id),
+                tags: vec!["check-suite".to_string(), suite.base.name.clone()],
+                expires_at: result.expires_at,
+                suppressed_by: None,
+            };
+            if let Err(e) = self
+                .diagnostic_service
+                .record(Diagnostic::new(diagnostic))
+                .await
+            {
+                tracing::warn!(
+                    check_suite_id = %suite.id,
+                    target = %check.target(),
+                    "Failed to record check suite result: {}",
+                    e
+                );
+            }
+        }
+
+        self.mark_run(&suite.id, ran_at).await?;
+
+        tracing::info!(
+            check_suite_id = %suite.id,
+            run_id = %run_id,
+            status = ?report.status,
+            "Check suite run"
+        );
+
+        Ok(CheckSuiteRun {
+            suite_id: suite.id,
+            run_id,
+            ran_at,
+            report,
+        })
+    }
+
+    /// Record that `suite_id` ran at `ran_at`. Only `last_run_at` is written, so edits made while
+    /// the suite ran stand, and a suite deleted meanwhile stays deleted.
+    async fn mark_run(&self, suite_id: &Uuid, ran_at: DateTime<Utc>) -> Result<()> {
+        self.storage
+            .update_where(
+                EntityFilter::unfiltered().entity_id(suite_id),
+                vec![("last_run_at", SqlValue::OptionTimestamp(Some(ran_at)))],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Queue every scheduled suite that's due and start as many as there are free workers;
+    /// the rest run as workers free up, highest priority first. Returns how many were queued.
+    pub async fn run_due(self: &Arc<Self>, now: DateTime<Utc>) -> Result<usize> {
+        let due = self
+            .storage
+            .get_all(EntityFilter::unfiltered().check_suite_due(now))
+            .await?;
+
+        let queued = {
+            let mut pending = lock(&self.pending);
+            let mut queue = lock(&self.queue);
+            due.into_iter()
+                .filter(|suite| pending.insert(suite.id))
+                .map(|suite| queue.push(suite.id, suite.base.priority, now))
+                .count()
+        };
+
+        self.start_queued();
+        Ok(queued)
+    }
+
+    /// Start queued suites while there are workers free
+    fn start_queued(self: &Arc<Self>) {
+        while let Ok(permit) = self.workers.clone().try_acquire_owned() {
+            let Some(suite_id) = lock(&self.queue).pop() else {
+                return;
+            };
+
+            let service = self.clone();
+            tokio::spawn(async move {
+                service.run_scheduled(&suite_id).await;
+                drop(permit);
+                lock(&service.pending).remove(&suite_id);
+                service.start_queued();
+            });
+        }
+    }
+
+    /// Run the suite as it is now, since it may have been edited, run by hand or deleted while
+    /// queued. A suite that fails to run is still counted as run, so an unreachable daemon is
+    /// retried on the suite's schedule rather than every tick.
+    async fn run_scheduled(&self, suite_id: &Uuid) {
+        let suite = match self.get_by_id(suite_id).await {
+            Ok(Some(suite)) if suite.is_due(Utc::now()) => suite,
+            Ok(_) => {
+                tracing::debug!(
+                    check_suite_id = %suite_id,
+                    "Queued check suite was deleted, run or rescheduled meanwhile"
+                );
+                return;
+            }
+            Err(e) => {
+                tracing::warn!(
+                    check_suite_id = %suite_id,
+                    "Failed to load queued check suite: {}",
+                    e
+                );
+                return;
+            }
+        };
+
+        if let Err(e) = self.run(&suite).await {
+            tracing::warn!(
+                check_suite_id = %suite.id,
+                "Scheduled check suite failed to run: {}",
+                e
+            );
+            if let Err(e) = self.mark_run(&suite.id, Utc::now()).await {
+                tracing::error!(
+                    check_suite_id = %suite.id,
+                    "Failed to record check suite run: {}",
+                    e
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use chrono::{Duration, Utc};
+use serial_test::serial;
+use std::net::{IpAddr, Ipv4Addr};
+
+use crate::{
+    daemon::utils::network_checks::suite::{SuiteCheck, SuiteDefinition, SuiteMember},
+    server::{
+        check_suites::r#impl::base::{CheckSuite, CheckSuiteBase},
+        shared::{services::traits::CrudService, storage::traits::StorableEntity},
+    },
+    tests::*,
+};
+
+#[tokio::test]
+#[serial]
+async fn test_scheduled_runs_keep_edits_and_skip_deleted_suites() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let daemon_host = services
+        .host_service
+        .create(host(&network.id))
+        .await
+        .unwrap();
+
+    // Nothing listens on the daemon's port, so runs fail straight away
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+    let mut unreachable = daemon(&network.id, &daemon_host.id);
+    unreachable.base.ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    unreachable.base.port = port;
+    let daemon = services.daemon_service.create(unreachable).await.unwrap();
+
+    let now = Utc::now();
+    let suite = |name: &str, interval_secs, last_run_at| {
+        CheckSuite::new(CheckSuiteBase {
+            network_id: network.id,
+            name: name.to_string(),
+            daemon_id: daemon.id,
+            definition: SuiteDefinition {
+                checks: vec![SuiteMember {
+                    check: SuiteCheck::Icmp {
+                        ip: "10.0.0.1".parse().unwrap(),
+                    },
+                    advisory: false,
+                }],
+                ..Default::default()
+            },
+            interval_secs,
+            priority: 0,
+            last_run_at,
+        })
+    };
+    let service = &services.check_suite_service;
+    for unscheduled_or_recent in [
+        suite("manual", None, None),
+        suite("recent", Some(300), Some(now - Duration::seconds(60))),
+    ] {
+        service.create(unscheduled_or_recent).await.unwrap();
+    }
+    let mut edited = service
+        .create(suite("edited", Some(300), None))
+        .await
+        .unwrap();
+    let deleted = service
+        .create(suite(
+            "deleted",
+            Some(300),
+            Some(now - Duration::seconds(600)),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(service.run_due(now).await.unwrap(), 2);
+
+    // Changes made once the suites are queued survive their runs
+    edited.base.name = "renamed".to_string();
+    service.update(&mut edited).await.unwrap();
+    service.delete(&deleted.id).await.unwrap();
+
+    let mut ran = None;
+    for _ in 0..100 {
+        ran = service
+            .get_by_id(&edited.id)
+            .await
+            .unwrap()
+            .filter(|s| s.base.last_run_at.is_some());
+        if ran.is_some() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    let ran = ran.expect("queued suite wasn't run");
+    assert_eq!(ran.base.name, "renamed");
+    assert!(service.get_by_id(&deleted.id).await.unwrap().is_none());
+}
@@ -1,64 +1,142 @@
-use crate::server::{
-    auth::middleware::AuthenticatedDaemon,
-    config::AppState,
-    daemons::r#impl::{
-        api::{
-            DaemonCapabilities, DaemonRegistrationRequest, DaemonRegistrationResponse,
-            DiscoveryUpdatePayload,
+use crate::{
+    daemon::{
+        discovery::types::base::DiscoveryPhase,
+        utils::network_checks::{
+            arp::ArpScanResult,
+            suite::{SuiteCheck, SuiteCheckResult},
         },
-        base::{Daemon, DaemonBase},
     },
-    discovery::r#impl::{
-        base::{Discovery, DiscoveryBase},
-        types::{DiscoveryType, HostNamingFallback, RunType},
-    },
-    hosts::r#impl::base::{Host, HostBase},
-    shared::{
-        handlers::traits::{
-            create_handler, delete_handler, get_all_handler, get_by_id_handler, update_handler,
+    server::{
+        api_keys::r#impl::{
+            base::{ApiKey, ApiKeyBase},
+            expiry::ApiKeyExpiryWarning,
+        },
+        auth::middleware::{
+            AuthError, AuthenticatedDaemon, AuthenticatedUser, RequireAdmin, RequireOwner,
+        },
+        config::AppState,
+        daemons::r#impl::{
+            api::{
+                DaemonArpScanRequest, DaemonCapabilities, DaemonHeartbeatRequest,
+                DaemonHeartbeatResponse, DaemonListQuery, DaemonNdpScanRequest,
+                DaemonNdpScanResponse, DaemonRegistrationRequest, DaemonRegistrationResponse,
+                DaemonTestReport, DiscoveryUpdatePayload, HeartbeatPartStatus,
+                HeartbeatResultsPolicy,
+            },
+            base::{Daemon, DaemonBase, DaemonCheckTimedOut, DaemonPaused, parse_labels},
+            metrics::{DaemonMetrics, DaemonMetricsQuery, DaemonSystemSnapshot},
+            transfer::{DaemonTransfer, DaemonTransferRequest},
+        },
+        discovery::r#impl::{
+            base::{Discovery, DiscoveryBase},
+            history::SessionActor,
+            import::ndp_hosts,
+            target_policy::ScanTargetDenied,
+            types::{DiscoveryType, HostNamingFallback, RunType, ScanRate},
+        },
+        hosts::r#impl::{
+            api::HostWithServicesRequest,
+            base::{Host, HostBase},
+        },
+        shared::{
+            extractors::SignedJson,
+            handlers::traits::{create_handler, delete_handler, get_by_id_handler, update_handler},
+            services::traits::CrudService,
+            storage::{filter::EntityFilter, traits::StorableEntity},
+            types::{
+                api::{ApiError, ApiResponse, ApiResult},
+                entities::DiscoveryMetadata,
+            },
         },
-        services::traits::CrudService,
-        storage::traits::StorableEntity,
-        types::api::{ApiError, ApiResponse, ApiResult},
     },
 };
+use anyhow::anyhow;
 use axum::{
-    Router,
-    extract::{Path, State},
+    Extension, Router,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{HeaderMap, StatusCode},
     response::Json,
     routing::{delete, get, post, put},
 };
 use chrono::Utc;
-use std::sync::Arc;
+use cidr::IpCidr;
+use std::{net::SocketAddr, sync::Arc};
 use uuid::Uuid;
+use validator::Validate;
 
 pub fn create_router() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", post(create_handler::<Daemon>))
-        .route("/", get(get_all_handler::<Daemon>))
+        .route("/", get(get_daemons))
         .route("/{id}", put(update_handler::<Daemon>))
         .route("/{id}", delete(delete_handler::<Daemon>))
         .route("/{id}", get(get_by_id_handler::<Daemon>))
         .route("/register", post(register_daemon))
+        .route("/ip-allowlist", get(get_ip_allowlist))
+        .route("/ip-allowlist", put(set_ip_allowlist))
         .route("/{id}/heartbeat", post(receive_heartbeat))
+        .route("/{id}/metrics", get(get_daemon_metrics))
+        .route("/{id}/test", post(test_daemon))
+        .route("/{id}/pause", post(pause_daemon))
+        .route("/{id}/resume", post(resume_daemon))
+        .route("/{id}/transfer", post(transfer_daemon))
+        .route("/{id}/arp-scan", post(arp_scan))
+        .route("/{id}/ndp-scan", post(ndp_scan))
+        .route("/{id}/check", post(run_check))
+        .route("/{id}/auth-check", post(auth_check))
         .route("/{id}/update-capabilities", post(update_capabilities))
         .route("/{id}/request-work", post(receive_work_request))
 }
 
 const DAILY_MIDNIGHT_CRON: &str = "0 0 0 * * *";
 
-/// Register a new daemon
+/// Register a new daemon. Daemons authenticate with their API key, or with a one-time bootstrap
+/// token in which case a permanent API key is issued and returned.
 async fn register_daemon(
     State(state): State<Arc<AppState>>,
-    _daemon: AuthenticatedDaemon,
+    auth: Result<AuthenticatedDaemon, AuthError>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
     Json(request): Json<DaemonRegistrationRequest>,
 ) -> ApiResult<Json<ApiResponse<DaemonRegistrationResponse>>> {
     let service = &state.services.daemon_service;
 
+    // Tokens are checked up front but only consumed in the transaction that registers the
+    // daemon, so a registration that fails leaves its token usable
+    let (credential_network_id, bootstrap_token) = match (&request.bootstrap_token, auth) {
+        (Some(token), _) => {
+            let peer_ip = connect_info.map(|Extension(ConnectInfo(addr))| addr.ip());
+            // Pinned tokens match the client behind any trusted proxy, as the allowlist does
+            let client_ip = state
+                .services
+                .daemon_ip_allowlist
+                .client_ip(peer_ip, &headers);
+            let bootstrap_token = state
+                .services
+                .bootstrap_token_service
+                .validate(token, client_ip)
+                .await
+                .map_err(|e| ApiError::unauthorized(e.to_string()))?;
+
+            state
+                .services
+                .daemon_ip_allowlist
+                .check(&bootstrap_token.base.network_id, peer_ip, &headers)
+                .await?;
+
+            (bootstrap_token.base.network_id, Some(bootstrap_token))
+        }
+        (None, Ok(AuthenticatedDaemon(network_id))) => (network_id, None),
+        (None, Err(e)) => return Err(e.into()),
+    };
+    let network_id =
+        registration_network_id(&state, request.network_id, credential_network_id).await?;
+
     // Create a dummy host to return a host_id to the daemon
     let mut dummy_host = Host::new(HostBase::default());
-    dummy_host.base.network_id = request.network_id;
+    dummy_host.base.network_id = network_id;
     dummy_host.base.name = request.daemon_ip.to_string();
+    let dummy_host_id = dummy_host.id;
 
     let (host, _) = state
         .services
@@ -68,20 +146,96 @@ async fn register_daemon(
 
     let mut daemon = Daemon::new(DaemonBase {
         host_id: host.id,
-        network_id: request.network_id,
+        network_id,
         ip: request.daemon_ip,
         port: request.daemon_port,
         capabilities: request.capabilities.clone(),
         last_seen: Utc::now(),
         mode: request.mode,
+        version: None,
+        auth_failed_at: None,
+        paused: false,
+        labels: request.labels.clone(),
+        system_metrics: None,
+        server_key: None,
     });
 
     daemon.id = request.daemon_id;
 
-    let registered_daemon = service
-        .create(daemon)
-        .await
-        .map_err(|e| ApiError::internal_error(&format!("Failed to register daemon: {}", e)))?;
+    let registered = async {
+        let mut tx = service.storage().begin().await?;
+
+        let issued_api_key = match &bootstrap_token {
+            Some(bootstrap_token) => {
+                state
+                    .services
+                    .bootstrap_token_service
+                    .redeem_in(&mut tx, bootstrap_token)
+                    .await
+                    .map_err(|e| ApiError::unauthorized(e.to_string()))?;
+
+                let (_, api_key) = state
+                    .services
+                    .api_key_service
+                    .create_in(
+                        &mut tx,
+                        ApiKey::new(ApiKeyBase {
+                            key_hash: String::new(),
+                            name: format!("Daemon @ {}", request.daemon_ip),
+                            last_used: None,
+                            expires_at: None,
+                            network_id,
+                            is_enabled: true,
+                            rotated_at: None,
+                        }),
+                    )
+                    .await?;
+
+                Some(api_key)
+            }
+            None => None,
+        };
+
+        let registered_daemon = service
+            .storage()
+            .create_in(&mut tx, &daemon)
+            .await
+            .map_err(|e| ApiError::internal_error(&format!("Failed to register daemon: {}", e)))?;
+        tx.commit().await.map_err(anyhow::Error::from)?;
+
+        Ok::<_, ApiError>((registered_daemon, issued_api_key))
+    }
+    .await;
+
+    // A registration that loses a race for its token, or otherwise fails, doesn't leave its
+    // host behind. Only a host created here is removed, never one the registration matched.
+    let (registered_daemon, issued_api_key) = match registered {
+        Ok(registered) => registered,
+        Err(e) => {
+            if host.id == dummy_host_id
+                && let Err(cleanup) = state
+                    .services
+                    .host_service
+                    .delete_host(&host.id, true)
+                    .await
+            {
+                tracing::warn!(
+                    host_id = %host.id,
+                    "Failed to remove host of failed daemon registration: {}",
+                    cleanup
+                );
+            }
+            return Err(e);
+        }
+    };
+
+    tracing::info!(
+        daemon_id = %registered_daemon.id,
+        network_id = %network_id,
+        "Daemon registered"
+    );
+
+    let server_key = service.server_key(&registered_daemon).await?;
 
     let discovery_service = state.services.discovery_service.clone();
 
@@ -94,8 +248,8 @@ async fn register_daemon(
             },
             discovery_type: DiscoveryType::SelfReport { host_id: host.id },
             name: format!("Self Report @ {}", request.daemon_ip),
-            daemon_id: request.daemon_id,
-            network_id: request.network_id,
+            daemon_id: Some(request.daemon_id),
+            network_id,
         }))
         .await?;
 
@@ -116,8 +270,8 @@ async fn register_daemon(
                     host_naming_fallback: HostNamingFallback::BestService,
                 },
                 name: format!("Docker @ {}", request.daemon_ip),
-                daemon_id: request.daemon_id,
-                network_id: request.network_id,
+                daemon_id: Some(request.daemon_id),
+                network_id,
             }))
             .await?;
 
@@ -134,10 +288,13 @@ async fn register_daemon(
             discovery_type: DiscoveryType::Network {
                 subnet_ids: None,
                 host_naming_fallback: HostNamingFallback::BestService,
+                scan_rate: ScanRate::default(),
+                snmp_credential: None,
+                targets: Vec::new(),
             },
             name: format!("Network Scan @ {}", request.daemon_ip),
-            daemon_id: request.daemon_id,
-            network_id: request.network_id,
+            daemon_id: Some(request.daemon_id),
+            network_id,
         }))
         .await?;
 
@@ -146,9 +303,136 @@ async fn register_daemon(
     Ok(Json(ApiResponse::success(DaemonRegistrationResponse {
         daemon: registered_daemon,
         host_id: host.id,
+        api_key: issued_api_key,
+        server_key: Some(server_key),
     })))
 }
 
+/// Network a registering daemon joins: the one it asked for, which must exist, or else the
+/// server's configured default. Either way it must be the network the daemon's bootstrap token or
+/// API key is bound to.
+async fn registration_network_id(
+    state: &AppState,
+    requested: Option<Uuid>,
+    credential_network_id: Uuid,
+) -> ApiResult<Uuid> {
+    let Some(network_id) = requested.or(state.config.default_network_id) else {
+        return Err(ApiError::bad_request(
+            "No network_id provided and no default network is configured on the server",
+        ));
+    };
+
+    if state
+        .services
+        .network_service
+        .get_by_id(&network_id)
+        .await?
+        .is_none()
+    {
+        return Err(ApiError::bad_request(&format!(
+            "Network {} does not exist",
+            network_id
+        )));
+    }
+
+    if network_id != credential_network_id {
+        return Err(ApiError::bad_request(&format!(
+            "Network {} does not match the network {} the daemon's credential is bound to",
+            network_id, credential_network_id
+        )));
+    }
+
+    Ok(network_id)
+}
+
+/// Daemons in the user's networks, optionally only those with all of the given labels
+async fn get_daemons(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+    Query(query): Query<DaemonListQuery>,
+) -> ApiResult<Json<ApiResponse<Vec<Daemon>>>> {
+    let labels = query
+        .labels
+        .as_deref()
+        .map(parse_labels)
+        .transpose()
+        .map_err(|e| ApiError::bad_request(&e))?
+        .unwrap_or_default();
+
+    let daemons = state
+        .services
+        .daemon_service
+        .get_all(
+            EntityFilter::unfiltered()
+                .network_ids(&user.network_ids)
+                .labels(&labels),
+        )
+        .await?;
+
+    Ok(Json(ApiResponse::success(daemons)))
+}
+
+/// Source address ranges the organization's daemons may connect from, within the server-wide
+/// allowlist from config
+async fn get_ip_allowlist(
+    State(state): State<Arc<AppState>>,
+    RequireOwner(user): RequireOwner,
+) -> ApiResult<Json<ApiResponse<Vec<IpCidr>>>> {
+    let cidrs = state
+        .services
+        .daemon_ip_allowlist
+        .organization_cidrs(&user.organization_id)
+        .await?;
+
+    Ok(Json(ApiResponse::success(cidrs)))
+}
+
+/// Replace the organization's daemon allowlist without a restart. It only narrows the server-wide
+/// allowlist, and only for the organization's own daemons.
+async fn set_ip_allowlist(
+    State(state): State<Arc<AppState>>,
+    RequireOwner(user): RequireOwner,
+    Json(cidrs): Json<Vec<IpCidr>>,
+) -> ApiResult<Json<ApiResponse<Vec<IpCidr>>>> {
+    state
+        .services
+        .daemon_ip_allowlist
+        .set_organization_cidrs(user.organization_id, cidrs.clone())
+        .await?;
+
+    tracing::info!(
+        user_id = %user.user_id,
+        organization_id = %user.organization_id,
+        entries = %cidrs.len(),
+        "Daemon IP allowlist updated via API"
+    );
+
+    Ok(Json(ApiResponse::success(cidrs)))
+}
+
+/// Heartbeat regularity and discovery throughput of a daemon over time, from the hourly rollups
+async fn get_daemon_metrics(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<DaemonMetricsQuery>,
+) -> ApiResult<Json<ApiResponse<DaemonMetrics>>> {
+    let service = &state.services.daemon_service;
+
+    service
+        .get_by_id(&id)
+        .await?
+        .filter(|d| user.network_ids.contains(&d.base.network_id))
+        .ok_or_else(|| ApiError::not_found(format!("Daemon '{}' not found", &id)))?;
+
+    let window = query
+        .window(Utc::now())
+        .map_err(|e| ApiError::bad_request(&e))?;
+    let metrics = service.get_daemon_metrics(&id, window).await?;
+
+    Ok(Json(ApiResponse::success(metrics)))
+}
+
 async fn update_capabilities(
     State(state): State<Arc<AppState>>,
     _daemon: AuthenticatedDaemon,
@@ -175,26 +459,528 @@ async fn update_capabilities(
     Ok(Json(ApiResponse::success(())))
 }
 
-/// Receive heartbeat from daemon
+/// Receive heartbeat from daemon, optionally carrying discovery progress and results. A body-less
+/// request is still accepted as a bare heartbeat.
 async fn receive_heartbeat(
     State(state): State<Arc<AppState>>,
-    _daemon: AuthenticatedDaemon,
+    AuthenticatedDaemon(network_id): AuthenticatedDaemon,
     Path(id): Path<Uuid>,
-) -> ApiResult<Json<ApiResponse<()>>> {
+    expiry_warning: Option<Extension<ApiKeyExpiryWarning>>,
+    request: Option<SignedJson<DaemonHeartbeatRequest>>,
+) -> ApiResult<Json<ApiResponse<DaemonHeartbeatResponse>>> {
+    let request = request.map(|SignedJson(r)| r).unwrap_or_default();
+
+    let mut response = process_heartbeat(&state, id, network_id, request).await?;
+    response.api_key_expiry = expiry_warning.map(|Extension(warning)| warning);
+
+    Ok(Json(ApiResponse::success(response)))
+}
+
+/// Record a heartbeat and ingest any results attached to it. Results are validated up front and
+/// ingested before the heartbeat is recorded, so with [`HeartbeatResultsPolicy::RejectAll`] a bad
+/// results portion leaves the daemon untouched; otherwise the failure is reported per part.
+pub async fn process_heartbeat(
+    state: &AppState,
+    id: Uuid,
+    network_id: Uuid,
+    request: DaemonHeartbeatRequest,
+) -> ApiResult<DaemonHeartbeatResponse> {
     let service = &state.services.daemon_service;
 
     let mut daemon = service
         .get_by_id(&id)
         .await
         .map_err(|e| ApiError::internal_error(&format!("Failed to get daemon: {}", e)))?
+        .filter(|d| d.base.network_id == network_id)
         .ok_or_else(|| ApiError::not_found(format!("Daemon '{}' not found", &id)))?;
 
+    let has_results = request.discovery_update.is_some() || !request.hosts.is_empty();
+
+    let results = if has_results {
+        let result = match validate_heartbeat_results(state, &daemon, &request).await {
+            Ok(()) => {
+                ingest_heartbeat_results(state, request.discovery_update, request.hosts).await
+            }
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = &result {
+            tracing::warn!(daemon_id = %id, error = %e, "Heartbeat results rejected");
+
+            if request.on_results_error == HeartbeatResultsPolicy::RejectAll {
+                return Err(ApiError::new(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    format!("Heartbeat rejected, attached results are invalid: {}", e),
+                ));
+            }
+        }
+
+        Some(HeartbeatPartStatus::from_result(&result))
+    } else {
+        None
+    };
+
+    let previous_seen = daemon.base.last_seen;
     daemon.base.last_seen = Utc::now();
 
-    service
-        .update(&mut daemon)
+    let (system_metrics, system_metrics_status) = match request.system_metrics {
+        Some(metrics) => {
+            let result = metrics.check_ranges();
+            if let Err(e) = &result {
+                tracing::warn!(daemon_id = %id, error = %e, "System metrics rejected");
+            }
+            let status = HeartbeatPartStatus::from_result(&result);
+            (result.ok().map(|()| metrics), Some(status))
+        }
+        None => (None, None),
+    };
+    if let Some(metrics) = &system_metrics {
+        daemon.base.system_metrics = Some(DaemonSystemSnapshot {
+            reported_at: daemon.base.last_seen,
+            metrics: metrics.clone(),
+        });
+    }
+
+    if let Some(version) = request.version {
+        daemon.base.version = Some(version);
+    }
+    if let Some(capabilities) = request.capabilities {
+        daemon.base.capabilities = capabilities;
+    }
+
+    let daemon = service
+        .save_heartbeat(&daemon)
         .await
-        .map_err(|e| ApiError::internal_error(&format!("Failed to update heartbeat: {}", e)))?;
+        .map_err(|e| ApiError::internal_error(&format!("Failed to update heartbeat: {}", e)))?
+        .ok_or_else(|| ApiError::not_found(format!("Daemon '{}' not found", &id)))?;
+    service.record_heartbeat(&daemon, previous_seen, daemon.base.last_seen);
+    if let Some(metrics) = &system_metrics {
+        service.record_system_metrics(&daemon, metrics, daemon.base.last_seen);
+    }
+
+    Ok(DaemonHeartbeatResponse {
+        heartbeat: HeartbeatPartStatus {
+            success: true,
+            error: None,
+        },
+        results,
+        system_metrics: system_metrics_status,
+        api_key_expiry: None,
+        server_key: Some(service.server_key(&daemon).await?),
+    })
+}
+
+/// Check every result attached to a heartbeat before any is written, so a batch that's rejected
+/// leaves nothing of itself behind
+async fn validate_heartbeat_results(
+    state: &AppState,
+    daemon: &Daemon,
+    request: &DaemonHeartbeatRequest,
+) -> anyhow::Result<()> {
+    for host_request in &request.hosts {
+        let host = &host_request.host.base;
+
+        host.validate()
+            .map_err(|e| anyhow!("Host '{}' failed validation: {}", host.name, e))?;
+
+        if host.network_id != daemon.base.network_id {
+            return Err(anyhow!(
+                "Host '{}' belongs to a different network than the daemon",
+                host.name
+            ));
+        }
+
+        for service in host_request.services.iter().flatten() {
+            let name = &service.base.name;
+            service.validate().map_err(|e| {
+                anyhow!(
+                    "Service '{}' on host '{}' failed validation: {}",
+                    name,
+                    host.name,
+                    e
+                )
+            })?;
+
+            if service.base.network_id != daemon.base.network_id {
+                return Err(anyhow!(
+                    "Service '{}' on host '{}' belongs to a different network than the daemon",
+                    name,
+                    host.name
+                ));
+            }
+        }
+    }
+
+    if let Some(update) = &request.discovery_update {
+        let session = state
+            .services
+            .discovery_service
+            .get_session(&update.session_id)
+            .await
+            .ok_or_else(|| anyhow!("Discovery session '{}' not found", update.session_id))?;
+
+        if session.daemon_id != Some(daemon.id) || update.daemon_id != Some(daemon.id) {
+            return Err(anyhow!(
+                "Discovery session '{}' does not belong to this daemon",
+                update.session_id
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+async fn ingest_heartbeat_results(
+    state: &AppState,
+    discovery_update: Option<DiscoveryUpdatePayload>,
+    hosts: Vec<HostWithServicesRequest>,
+) -> anyhow::Result<()> {
+    for host_request in hosts {
+        state
+            .services
+            .host_service
+            .create_host_with_services(host_request.host, host_request.services.unwrap_or_default())
+            .await?;
+    }
+
+    if let Some(update) = discovery_update {
+        state
+            .services
+            .discovery_service
+            .update_session(update)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Run an end-to-end connectivity test against a daemon
+async fn test_daemon(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<ApiResponse<DaemonTestReport>>> {
+    let service = &state.services.daemon_service;
+
+    let daemon = service
+        .get_by_id(&id)
+        .await?
+        .filter(|d| user.network_ids.contains(&d.base.network_id))
+        .ok_or_else(|| ApiError::not_found(format!("Daemon '{}' not found", &id)))?;
+
+    let report = service.test_connectivity(&daemon).await;
+
+    Ok(Json(ApiResponse::success(report)))
+}
+
+/// Stop sending a daemon new work, e.g. during maintenance on its host. Work it's already running
+/// finishes, and it keeps heartbeating without being reported offline.
+async fn pause_daemon(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<ApiResponse<Daemon>>> {
+    set_daemon_paused(&state, &user, id, true).await
+}
+
+/// Let a paused daemon take work again, starting the next discovery session queued for it
+async fn resume_daemon(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<ApiResponse<Daemon>>> {
+    let response = set_daemon_paused(&state, &user, id, false).await?;
+
+    if let Err(e) = state
+        .services
+        .discovery_service
+        .dispatch_queued_session(&id)
+        .await
+    {
+        tracing::warn!(daemon_id = %id, "Failed to start queued discovery session: {}", e);
+    }
+
+    Ok(response)
+}
+
+async fn set_daemon_paused(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    id: Uuid,
+    paused: bool,
+) -> ApiResult<Json<ApiResponse<Daemon>>> {
+    let service = &state.services.daemon_service;
+
+    let daemon = service
+        .get_by_id(&id)
+        .await?
+        .filter(|d| user.network_ids.contains(&d.base.network_id))
+        .ok_or_else(|| ApiError::not_found(format!("Daemon '{}' not found", &id)))?;
+
+    let updated = service.set_paused(&daemon, paused).await?;
+
+    tracing::info!(
+        daemon_id = %id,
+        user_id = %user.user_id,
+        paused,
+        "Daemon pause state changed via API"
+    );
+
+    Ok(Json(ApiResponse::success(updated)))
+}
+
+/// Move a daemon, and optionally what it discovered, to another network. The caller needs access
+/// to both networks.
+async fn transfer_daemon(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Path(id): Path<Uuid>,
+    Json(request): Json<DaemonTransferRequest>,
+) -> ApiResult<Json<ApiResponse<DaemonTransfer>>> {
+    let service = &state.services.daemon_service;
+
+    let daemon = service
+        .get_by_id(&id)
+        .await?
+        .filter(|d| user.network_ids.contains(&d.base.network_id))
+        .ok_or_else(|| ApiError::not_found(format!("Daemon '{}' not found", &id)))?;
+    if !user.network_ids.contains(&request.network_id) {
+        return Err(ApiError::not_found(format!(
+            "Network '{}' not found",
+            request.network_id
+        )));
+    }
+    if daemon.base.network_id == request.network_id {
+        return Err(ApiError::bad_request(
+            "Daemon is already in the target network",
+        ));
+    }
+
+    let transfer = service
+        .transfer_ownership(
+            &daemon.id,
+            &request.network_id,
+            request.move_data,
+            user.user_id,
+        )
+        .await?;
+
+    Ok(Json(ApiResponse::success(transfer)))
+}
+
+/// Have a daemon ARP scan a range on one of its local segments, finding hosts that don't answer
+/// pings
+async fn arp_scan(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Path(id): Path<Uuid>,
+    Json(request): Json<DaemonArpScanRequest>,
+) -> ApiResult<Json<ApiResponse<ArpScanResult>>> {
+    let service = &state.services.daemon_service;
+
+    let daemon = service
+        .get_by_id(&id)
+        .await?
+        .filter(|d| user.network_ids.contains(&d.base.network_id))
+        .ok_or_else(|| ApiError::not_found(format!("Daemon '{}' not found", &id)))?;
+
+    if !daemon.base.capabilities.has_raw_socket_access {
+        return Err(ApiError::bad_request(&format!(
+            "Daemon {} can't ARP scan: it doesn't have raw socket privileges",
+            daemon.id
+        )));
+    }
+
+    let cidr = IpCidr::V4(request.cidr);
+    let is_local = service
+        .scan_target_subnets(&daemon, None)
+        .await?
+        .iter()
+        .any(|subnet| {
+            subnet.base.cidr.contains(&cidr.first_address())
+                && subnet.base.cidr.contains(&cidr.last_address())
+        });
+    if !is_local {
+        return Err(ApiError::bad_request(&format!(
+            "{} is not within a subnet daemon {} has an interface on",
+            cidr, daemon.id
+        )));
+    }
+
+    let result = service.arp_scan(&daemon, &request).await.map_err(|e| {
+        match e.downcast::<ScanTargetDenied>() {
+            Ok(denied) => ApiError::forbidden(&denied.to_string()),
+            Err(e) if e.is::<DaemonPaused>() => e.into(),
+            Err(e) => ApiError::bad_gateway(&e.to_string()),
+        }
+    })?;
+
+    Ok(Json(ApiResponse::success(result)))
+}
+
+/// Have a daemon find the IPv6 neighbors on one of its local segments, creating hosts for those
+/// in the network's subnets as discovery would
+async fn ndp_scan(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Path(id): Path<Uuid>,
+    Json(request): Json<DaemonNdpScanRequest>,
+) -> ApiResult<Json<ApiResponse<DaemonNdpScanResponse>>> {
+    let service = &state.services.daemon_service;
+
+    let daemon = service
+        .get_by_id(&id)
+        .await?
+        .filter(|d| user.network_ids.contains(&d.base.network_id))
+        .ok_or_else(|| ApiError::not_found(format!("Daemon '{}' not found", &id)))?;
+
+    if !daemon.base.capabilities.has_raw_socket_access {
+        return Err(ApiError::bad_request(&format!(
+            "Daemon {} can't NDP scan: it doesn't have raw socket privileges",
+            daemon.id
+        )));
+    }
+
+    if let Some(prefix) = request.prefix {
+        let cidr = IpCidr::V6(prefix);
+        let is_local = service
+            .scan_target_subnets(&daemon, None)
+            .await?
+            .iter()
+            .any(|subnet| {
+                subnet.base.cidr.contains(&cidr.first_address())
+                    && subnet.base.cidr.contains(&cidr.last_address())
+            });
+        if !is_local {
+            return Err(ApiError::bad_request(&format!(
+                "{} is not within a subnet daemon {} has an interface on",
+                cidr, daemon.id
+            )));
+        }
+    }
+
+    let scan = service.ndp_scan(&daemon, &request).await.map_err(|e| {
+        match e.downcast::<ScanTargetDenied>() {
+            Ok(denied) => ApiError::forbidden(&denied.to_string()),
+            Err(e) if e.is::<DaemonPaused>() => e.into(),
+            Err(e) => ApiError::bad_gateway(&e.to_string()),
+        }
+    })?;
+
+    let subnets = state
+        .services
+        .subnet_service
+        .get_all(EntityFilter::unfiltered().network_ids(&[daemon.base.network_id]))
+        .await?;
+
+    // Link-local neighbors are on the segment of the interface the scan ran from
+    let segment_ids: Vec<Uuid> = state
+        .services
+        .host_service
+        .get_by_id(&daemon.base.host_id)
+        .await?
+        .map(|host| {
+            host.base
+                .interfaces
+                .iter()
+                .filter(|i| i.base.name.as_deref() == Some(scan.interface.as_str()))
+                .map(|i| i.base.subnet_id)
+                .collect()
+        })
+        .unwrap_or_default();
+    let segment = subnets
+        .iter()
+        .filter(|s| segment_ids.contains(&s.id))
+        .max_by_key(|s| s.base.cidr.is_ipv6());
+
+    let discovery_type = DiscoveryType::Ndp {
+        interface: scan.interface.clone(),
+    };
+    let (hosts, skipped) = ndp_hosts(
+        &scan.neighbors,
+        daemon.base.network_id,
+        &subnets,
+        segment,
+        DiscoveryMetadata::new(discovery_type.clone(), Some(daemon.id)),
+    );
+    let ingested = state
+        .services
+        .discovery_service
+        .ingest_hosts(
+            daemon.base.network_id,
+            Some(daemon.id),
+            discovery_type,
+            hosts,
+            skipped,
+            SessionActor::User {
+                user_id: user.user_id,
+            },
+        )
+        .await?;
+
+    Ok(Json(ApiResponse::success(DaemonNdpScanResponse {
+        scan,
+        ingested,
+    })))
+}
+
+/// Have a daemon run one check from its vantage point, returning the result once it's done
+async fn run_check(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Path(id): Path<Uuid>,
+    Json(check): Json<SuiteCheck>,
+) -> ApiResult<Json<ApiResponse<SuiteCheckResult>>> {
+    let service = &state.services.daemon_service;
+
+    let daemon = service
+        .get_by_id(&id)
+        .await?
+        .filter(|d| user.network_ids.contains(&d.base.network_id))
+        .ok_or_else(|| ApiError::not_found(format!("Daemon '{}' not found", &id)))?;
+
+    check.validate().map_err(|e| ApiError::bad_request(&e))?;
+    if !daemon
+        .base
+        .capabilities
+        .supported_checks
+        .contains(&check.kind())
+    {
+        return Err(ApiError::bad_request(&format!(
+            "Daemon {} doesn't support {:?} checks",
+            daemon.id,
+            check.kind()
+        )));
+    }
+
+    let result = service.run_check(&daemon, &check).await.map_err(|e| {
+        if let Some(denied) = e.downcast_ref::<ScanTargetDenied>() {
+            ApiError::forbidden(&denied.to_string())
+        } else if let Some(timed_out) = e.downcast_ref::<DaemonCheckTimedOut>() {
+            ApiError::gateway_timeout(&timed_out.to_string())
+        } else if e.is::<DaemonPaused>() {
+            e.into()
+        } else {
+            ApiError::bad_gateway(&e.to_string())
+        }
+    })?;
+
+    Ok(Json(ApiResponse::success(result)))
+}
+
+/// Called by a daemon during a connectivity test to prove its API key is accepted
+async fn auth_check(
+    State(state): State<Arc<AppState>>,
+    AuthenticatedDaemon(network_id): AuthenticatedDaemon,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<ApiResponse<()>>> {
+    state
+        .services
+        .daemon_service
+        .get_by_id(&id)
+        .await?
+        .filter(|d| d.base.network_id == network_id)
+        .ok_or_else(|| ApiError::not_found(format!("Daemon '{}' not found", &id)))?;
 
     Ok(Json(ApiResponse::success(())))
 }
@@ -213,26 +999,28 @@ async fn receive_work_request(
         .map_err(|e| ApiError::internal_error(&format!("Failed to get daemon: {}", e)))?
         .ok_or_else(|| ApiError::not_found(format!("Daemon '{}' not found", &id)))?;
 
+    let previous_seen = daemon.base.last_seen;
     daemon.base.last_seen = Utc::now();
 
-    service
-        .update(&mut daemon)
+    let daemon = service
+        .save_heartbeat(&daemon)
         .await
-        .map_err(|e| ApiError::internal_error(&format!("Failed to update heartbeat: {}", e)))?;
+        .map_err(|e| ApiError::internal_error(&format!("Failed to update heartbeat: {}", e)))?
+        .ok_or_else(|| ApiError::not_found(format!("Daemon '{}' not found", &id)))?;
+    service.record_heartbeat(&daemon, previous_seen, daemon.base.last_seen);
 
-    let sessions = state
+    // A paused daemon only gets back the session it's already running
+    let session = state
         .services
         .discovery_service
-        .get_sessions_for_daemon(&daemon_id)
-        .await;
+        .next_session_for_daemon(&daemon_id)
+        .await
+        .filter(|session| !daemon.base.paused || session.phase != DiscoveryPhase::Pending);
     let cancel = state
         .services
         .discovery_service
         .pull_cancellation_for_daemon(&daemon_id)
         .await;
 
-    Ok(Json(ApiResponse::success((
-        sessions.first().cloned(),
-        cancel,
-    ))))
+    Ok(Json(ApiResponse::success((session, cancel))))
 }
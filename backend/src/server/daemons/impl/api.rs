@@ -1,15 +1,31 @@
 use std::{fmt::Display, net::IpAddr};
 
 use crate::{
-    daemon::discovery::types::base::{
-        DiscoveryPhase, DiscoverySessionInfo, DiscoverySessionUpdate,
+    daemon::{
+        discovery::types::base::{DiscoveryPhase, DiscoverySessionInfo, DiscoverySessionUpdate},
+        utils::network_checks::{
+            ndp::NdpScanResult,
+            suite::{SuiteCheck, SuiteDefinition},
+        },
     },
     server::{
-        daemons::r#impl::base::{Daemon, DaemonMode},
-        discovery::r#impl::types::DiscoveryType,
+        api_keys::r#impl::expiry::ApiKeyExpiryWarning,
+        daemons::r#impl::{
+            base::{Daemon, DaemonLabels, DaemonMode, deserialize_labels},
+            metrics::DaemonSystemMetrics,
+        },
+        diagnostics::r#impl::base::DiagnosticKind,
+        discovery::r#impl::{
+            import::DiscoveryImportResult,
+            target_policy::ScanTargetPolicy,
+            types::{DiscoveryType, TargetResolution},
+        },
+        hosts::r#impl::api::HostWithServicesRequest,
+        secrets::r#impl::base::ResolvedSecrets,
     },
 };
 use chrono::{DateTime, Utc};
+use cidr::{Ipv4Cidr, Ipv6Cidr};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -20,14 +36,45 @@ pub struct DaemonCapabilities {
     pub has_docker_socket: bool,
     #[serde(default)]
     pub interfaced_subnet_ids: Vec<Uuid>,
+    /// Host scans the daemon runs at once, used to estimate discovery durations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrent_scans: Option<usize>,
+    /// Ports the daemon probes at once on each host
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port_batch_size: Option<usize>,
+    /// Whether the daemon can open raw sockets, needed for layer 2 checks such as ARP scans
+    #[serde(default)]
+    pub has_raw_socket_access: bool,
+    /// Kinds of check the daemon runs on demand. Empty for daemons that predate on-demand checks.
+    #[serde(default)]
+    pub supported_checks: Vec<DiagnosticKind>,
+    /// Discovery protocol the daemon speaks. Unset for daemons that predate protocol versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<u32>,
+}
+
+impl DaemonCapabilities {
+    /// Discovery protocol the daemon speaks, [`LEGACY_DAEMON_PROTOCOL_VERSION`] if it never said
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version
+            .unwrap_or(LEGACY_DAEMON_PROTOCOL_VERSION)
+    }
 }
 
 impl Display for DaemonCapabilities {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "DaemonCapabilities {{ has_docker_socket: {}, interfaced_subnet_ids: {:?} }}",
-            self.has_docker_socket, self.interfaced_subnet_ids
+            "DaemonCapabilities {{ has_docker_socket: {}, interfaced_subnet_ids: {:?}, \
+             concurrent_scans: {:?}, port_batch_size: {:?}, has_raw_socket_access: {}, \
+             supported_checks: {:?}, protocol_version: {:?} }}",
+            self.has_docker_socket,
+            self.interfaced_subnet_ids,
+            self.concurrent_scans,
+            self.port_batch_size,
+            self.has_raw_socket_access,
+            self.supported_checks,
+            self.protocol_version
         )
     }
 }
@@ -36,11 +83,30 @@ impl Display for DaemonCapabilities {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DaemonRegistrationRequest {
     pub daemon_id: Uuid,
-    pub network_id: Uuid,
+    /// Network to join, which must be the one the daemon's credential is bound to. When omitted
+    /// the server's default network is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_id: Option<Uuid>,
     pub daemon_ip: IpAddr,
     pub daemon_port: u16,
     pub mode: DaemonMode,
     pub capabilities: DaemonCapabilities,
+    /// One-time token used instead of an API key when the daemon doesn't have one yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootstrap_token: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_labels",
+        skip_serializing_if = "DaemonLabels::is_empty"
+    )]
+    pub labels: DaemonLabels,
+}
+
+/// Daemons to list. `labels` is comma separated `key=value` pairs, all of which must match.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DaemonListQuery {
+    #[serde(default)]
+    pub labels: Option<String>,
 }
 
 /// Daemon registration response from server to daemon
@@ -48,13 +114,88 @@ pub struct DaemonRegistrationRequest {
 pub struct DaemonRegistrationResponse {
     pub daemon: Daemon,
     pub host_id: Uuid,
+    /// Permanent API key, only issued when registering with a bootstrap token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Key the server signs its requests to the daemon with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_key: Option<String>,
 }
 
+/// Discovery protocol this build speaks, sent with each discovery request and echoed back by the
+/// daemon. Bump it whenever a discovery request gains a field older daemons can't handle, and
+/// teach [`DaemonDiscoveryRequest::for_protocol`] to leave that field out for them.
+pub const DAEMON_PROTOCOL_VERSION: u32 = 3;
+
+/// Protocol of daemons that predate protocol versions: discovery requests carry no resolved
+/// secrets, adaptive scan rate or SNMP credential
+pub const LEGACY_DAEMON_PROTOCOL_VERSION: u32 = 1;
+
+/// First protocol whose daemons receive the secrets a discovery references. Legacy daemons would
+/// run without them, e.g. scanning SNMP with the default community, so such requests can't be
+/// down-converted.
+pub const SECRETS_PROTOCOL_VERSION: u32 = 2;
+
+/// Oldest protocol discovery requests can still be down-converted to
+pub const MIN_DAEMON_PROTOCOL_VERSION: u32 = 1;
+
+/// First protocol whose daemons scan a network discovery's explicit targets. Older daemons would
+/// ignore them and scan whole subnets, so such requests can't be down-converted.
+pub const EXPLICIT_TARGETS_PROTOCOL_VERSION: u32 = 3;
+
 /// Daemon discovery request from server to daemon
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DaemonDiscoveryRequest {
     pub session_id: Uuid,
     pub discovery_type: DiscoveryType,
+    /// Secrets the discovery type references, resolved by the server at dispatch
+    #[serde(default, skip_serializing_if = "ResolvedSecrets::is_empty")]
+    pub secrets: ResolvedSecrets,
+    /// Protocol the request was shaped for, unset when sent to a legacy daemon
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<u32>,
+}
+
+impl DaemonDiscoveryRequest {
+    /// Discovery type fields a legacy daemon doesn't know, which it would otherwise ignore or
+    /// reject
+    const NEWER_DISCOVERY_TYPE_FIELDS: [&'static str; 2] = ["scan_rate", "snmp_credential"];
+
+    /// Oldest protocol a daemon can speak and still be sent this request
+    pub fn min_protocol_version(&self) -> u32 {
+        if !self.discovery_type.explicit_targets().is_empty() {
+            EXPLICIT_TARGETS_PROTOCOL_VERSION
+        } else if !self.secrets.is_empty() {
+            SECRETS_PROTOCOL_VERSION
+        } else {
+            MIN_DAEMON_PROTOCOL_VERSION
+        }
+    }
+
+    /// The request body for a daemon speaking `version`, which must be at least
+    /// [`Self::min_protocol_version`]. Newer daemons get this server's version, so they answer
+    /// in it; legacy daemons get the unversioned shape without the fields they don't know. An
+    /// adaptive scan rate falls back to the daemon's fixed concurrency.
+    pub fn for_protocol(mut self, version: u32) -> serde_json::Result<serde_json::Value> {
+        if version > LEGACY_DAEMON_PROTOCOL_VERSION {
+            self.protocol_version = Some(version.min(DAEMON_PROTOCOL_VERSION));
+            return serde_json::to_value(self);
+        }
+
+        self.protocol_version = None;
+        self.secrets = ResolvedSecrets::default();
+        let mut body = serde_json::to_value(self)?;
+        if let Some(discovery_type) = body
+            .get_mut("discovery_type")
+            .and_then(serde_json::Value::as_object_mut)
+        {
+            for field in Self::NEWER_DISCOVERY_TYPE_FIELDS {
+                discovery_type.remove(field);
+            }
+        }
+
+        Ok(body)
+    }
 }
 
 impl From<DiscoveryUpdatePayload> for DaemonDiscoveryRequest {
@@ -62,6 +203,8 @@ impl From<DiscoveryUpdatePayload> for DaemonDiscoveryRequest {
         Self {
             session_id: payload.session_id,
             discovery_type: payload.discovery_type,
+            secrets: payload.secrets,
+            protocol_version: None,
         }
     }
 }
@@ -70,13 +213,17 @@ impl From<DiscoveryUpdatePayload> for DaemonDiscoveryRequest {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DaemonDiscoveryResponse {
     pub session_id: Uuid,
+    /// Protocol the daemon read the request as. Unset from legacy daemons.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<u32>,
 }
 
 /// Progress update from daemon to server during discovery
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiscoveryUpdatePayload {
     pub session_id: Uuid,
-    pub daemon_id: Uuid,
+    /// Daemon running the session. None for imports, which no daemon runs.
+    pub daemon_id: Option<Uuid>,
     pub network_id: Uuid,
     pub phase: DiscoveryPhase,
     pub discovery_type: DiscoveryType,
@@ -85,12 +232,27 @@ pub struct DiscoveryUpdatePayload {
     pub error: Option<String>,
     pub started_at: Option<DateTime<Utc>>,
     pub finished_at: Option<DateTime<Utc>>,
+    /// Concurrent host scans the daemon had settled on when it sent this update, for
+    /// adaptive-rate network discoveries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective_scan_rate: Option<usize>,
+    /// How each explicit target of a network discovery resolved, once the daemon has resolved
+    /// them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub target_results: Vec<TargetResolution>,
+    /// Hosts the session has created so far, reported by the daemon with each update
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub discovered_host_ids: Vec<Uuid>,
+    /// Resolved secrets, only present when a pull-mode daemon is handed the session to run.
+    /// Never kept on the stored session.
+    #[serde(default, skip_serializing_if = "ResolvedSecrets::is_empty")]
+    pub secrets: ResolvedSecrets,
 }
 
 impl DiscoveryUpdatePayload {
     pub fn new(
         session_id: Uuid,
-        daemon_id: Uuid,
+        daemon_id: Option<Uuid>,
         network_id: Uuid,
         discovery_type: DiscoveryType,
     ) -> Self {
@@ -105,6 +267,10 @@ impl DiscoveryUpdatePayload {
             error: None,
             started_at: None,
             finished_at: None,
+            effective_scan_rate: None,
+            target_results: Vec::new(),
+            discovered_host_ids: Vec::new(),
+            secrets: ResolvedSecrets::new(),
         }
     }
 
@@ -117,13 +283,170 @@ impl DiscoveryUpdatePayload {
             session_id: info.session_id,
             discovery_type,
             network_id: info.network_id,
-            daemon_id: info.daemon_id,
+            daemon_id: Some(info.daemon_id),
             phase: update.phase,
             processed: update.processed,
             total_to_process: info.total_to_process,
             error: update.error,
             started_at: info.started_at,
             finished_at: update.finished_at,
+            effective_scan_rate: None,
+            target_results: Vec::new(),
+            discovered_host_ids: Vec::new(),
+            secrets: ResolvedSecrets::new(),
+        }
+    }
+}
+
+/// Steps of a daemon connectivity test, in the order they run
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum DaemonTestStep {
+    /// Server can reach the daemon's health endpoint
+    Health,
+    /// Daemon can call back to the server with its API key
+    Auth,
+    /// Daemon can run a minimal scan (its own listen port on localhost)
+    Discovery,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DaemonTestStepResult {
+    pub step: DaemonTestStep,
+    pub success: bool,
+    pub duration_ms: u64,
+    pub message: Option<String>,
+}
+
+/// Result of a daemon connectivity test. Steps stop at the first failure, which is reported in
+/// `failed_step`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DaemonTestReport {
+    pub daemon_id: Uuid,
+    pub success: bool,
+    pub failed_step: Option<DaemonTestStep>,
+    pub steps: Vec<DaemonTestStepResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonProbeRequest {
+    pub port: u16,
+}
+
+/// ARP scan of a range on one of a daemon's local segments
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonArpScanRequest {
+    /// Interface on the daemon's host to send requests from, e.g. `eth0`
+    pub interface: String,
+    pub cidr: Ipv4Cidr,
+    /// The server's scan target policy, for the daemon to hold the range to as well. Always set
+    /// by the server; whatever a caller sends is replaced.
+    #[serde(default)]
+    pub scan_policy: Option<ScanTargetPolicy>,
+}
+
+/// NDP scan of one of a daemon's local segments
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonNdpScanRequest {
+    /// Interface on the daemon's host to scan from, e.g. `eth0`
+    pub interface: String,
+    /// On-link prefix to limit non-link-local neighbors to. Without it every neighbor that
+    /// answers is reported.
+    #[serde(default)]
+    pub prefix: Option<Ipv6Cidr>,
+    /// The server's scan target policy, for the daemon to hold the prefix and the neighbors it
+    /// reports to as well. Always set by the server; whatever a caller sends is replaced.
+    #[serde(default)]
+    pub scan_policy: Option<ScanTargetPolicy>,
+}
+
+/// A check suite for a daemon to run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonSuiteRequest {
+    pub suite: SuiteDefinition,
+    /// The server's scan target policy, for the daemon to hold every check's target to once
+    /// names are resolved. Always set by the server.
+    #[serde(default)]
+    pub scan_policy: Option<ScanTargetPolicy>,
+}
+
+/// A single check for a daemon to run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonCheckRequest {
+    pub check: SuiteCheck,
+    /// The server's scan target policy, for the daemon to hold the check's target to once a
+    /// name is resolved. Always set by the server.
+    #[serde(default)]
+    pub scan_policy: Option<ScanTargetPolicy>,
+}
+
+/// What an NDP scan found, and the hosts created from it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonNdpScanResponse {
+    pub scan: NdpScanResult,
+    /// Link-local neighbors are placed on the subnet of the interface scanned from. Others outside
+    /// every subnet of the daemon's network are listed as skipped.
+    pub ingested: DiscoveryImportResult,
+}
+
+/// What happens to a heartbeat when the results attached to it can't be ingested
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum HeartbeatResultsPolicy {
+    /// Record the heartbeat anyway and report the results failure
+    #[default]
+    KeepHeartbeat,
+    /// Reject the whole request, leaving the heartbeat and every attached result unrecorded.
+    /// Results are all validated before any is written.
+    RejectAll,
+}
+
+/// Heartbeat body. Every field is optional so a bare heartbeat is an empty request, and active
+/// daemons can piggyback discovery progress and results instead of making separate calls.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DaemonHeartbeatRequest {
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub capabilities: Option<DaemonCapabilities>,
+    #[serde(default)]
+    pub discovery_update: Option<DiscoveryUpdatePayload>,
+    #[serde(default)]
+    pub hosts: Vec<HostWithServicesRequest>,
+    #[serde(default)]
+    pub on_results_error: HeartbeatResultsPolicy,
+    /// Health of the daemon's host. Omitted by daemons that can't measure it.
+    #[serde(default)]
+    pub system_metrics: Option<DaemonSystemMetrics>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HeartbeatPartStatus {
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+impl HeartbeatPartStatus {
+    pub fn from_result<T>(result: &anyhow::Result<T>) -> Self {
+        Self {
+            success: result.is_ok(),
+            error: result.as_ref().err().map(|e| e.to_string()),
         }
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DaemonHeartbeatResponse {
+    pub heartbeat: HeartbeatPartStatus,
+    /// None when no results were attached
+    pub results: Option<HeartbeatPartStatus>,
+    /// None when no system metrics were attached. Rejected metrics are dropped, but the
+    /// heartbeat itself still counts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_metrics: Option<HeartbeatPartStatus>,
+    /// Set when the API key the daemon authenticated with is close to expiry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_expiry: Option<ApiKeyExpiryWarning>,
+    /// Key the server signs its requests to the daemon with, so daemons registered before it
+    /// was issued, or that lost it, pick it up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_key: Option<String>,
+}
@@ -1,12 +1,12 @@
-use std::{fmt::Display, net::IpAddr};
+use std::{collections::BTreeMap, fmt::Display, net::IpAddr};
 
 use chrono::{DateTime, Utc};
 use clap::ValueEnum;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, de};
 use strum::Display;
 use uuid::Uuid;
 
-use crate::server::daemons::r#impl::api::DaemonCapabilities;
+use crate::server::daemons::r#impl::{api::DaemonCapabilities, metrics::DaemonSystemSnapshot};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DaemonBase {
@@ -18,6 +18,95 @@ pub struct DaemonBase {
     #[serde(default)]
     pub capabilities: DaemonCapabilities,
     pub mode: DaemonMode,
+    /// Daemon software version, as last reported in a heartbeat
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Set when the daemon keeps rejecting the server's requests as unauthorized, so it needs
+    /// re-credentialing. Cleared once a request gets through again.
+    #[serde(default)]
+    pub auth_failed_at: Option<DateTime<Utc>>,
+    /// Paused daemons still heartbeat and keep their history, but aren't sent new work and
+    /// aren't reported as offline. Only changed by pausing or resuming the daemon, never by a
+    /// full-row update.
+    #[serde(default)]
+    pub paused: bool,
+    /// Free-form labels for organizing daemons, e.g. `site=nyc`
+    #[serde(default, deserialize_with = "deserialize_labels")]
+    pub labels: DaemonLabels,
+    /// Latest health of the daemon's host, if it reports it. Set from heartbeats.
+    #[serde(default)]
+    pub system_metrics: Option<DaemonSystemSnapshot>,
+    /// Key the server signs its requests to the daemon with, so the daemon only acts on the
+    /// server's say-so. Issued to the daemon at registration and with heartbeats; never returned
+    /// by the API and only ever written by [`DaemonService::server_key`].
+    ///
+    /// [`DaemonService::server_key`]: crate::server::daemons::service::DaemonService::server_key
+    #[serde(skip)]
+    pub server_key: Option<String>,
+}
+
+/// Daemon labels by key. Keys are trimmed and lowercased, so `Site` and `site` are the same
+/// label and can't both be given; values are kept exactly as given and only ever matched exactly.
+pub type DaemonLabels = BTreeMap<String, String>;
+
+pub fn normalize_label_key(key: &str) -> String {
+    key.trim().to_lowercase()
+}
+
+/// Labels written as comma separated `key=value` pairs, e.g. `site=nyc,env=prod`
+pub fn parse_labels(labels: &str) -> Result<DaemonLabels, String> {
+    let pairs = labels
+        .split(',')
+        .filter(|pair| !pair.trim().is_empty())
+        .map(|pair| {
+            pair.split_once('=')
+                .map(|(key, value)| (key, value.trim()))
+                .ok_or_else(|| format!("Label '{}' must be key=value", pair))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    collect_labels(pairs)
+}
+
+/// Normalize `pairs` into labels, rejecting keys that are the same once normalized rather than
+/// letting one silently replace the other
+fn collect_labels<'a>(
+    pairs: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<DaemonLabels, String> {
+    let mut labels = DaemonLabels::new();
+    for (key, value) in pairs {
+        let (normalized, value) = label(key, value)?;
+        if labels.contains_key(&normalized) {
+            return Err(format!(
+                "Label '{}' is given more than once (keys ignore case and surrounding spaces)",
+                normalized
+            ));
+        }
+        labels.insert(normalized, value);
+    }
+
+    Ok(labels)
+}
+
+fn label(key: &str, value: &str) -> Result<(String, String), String> {
+    let key = normalize_label_key(key);
+    if key.is_empty() {
+        return Err("Label keys can't be empty".to_string());
+    }
+    Ok((key, value.to_string()))
+}
+
+pub fn deserialize_labels<'de, D>(deserializer: D) -> Result<DaemonLabels, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let labels = BTreeMap::<String, String>::deserialize(deserializer)?;
+    collect_labels(
+        labels
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str())),
+    )
+    .map_err(de::Error::custom)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,6 +124,60 @@ impl Display for Daemon {
     }
 }
 
+/// A daemon answered the server with 401 or 403, so whatever authenticates the server to it is
+/// wrong or was revoked. Retrying won't help until that's fixed.
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "{}: daemon {daemon_id} rejected the server's request with HTTP {status}",
+    Self::CODE
+)]
+pub struct DaemonAuthFailed {
+    pub daemon_id: Uuid,
+    pub status: u16,
+    /// Whether the daemon has been flagged as needing re-credentialing. A lone 403 isn't.
+    pub flagged: bool,
+}
+
+impl DaemonAuthFailed {
+    pub const CODE: &'static str = "DAEMON_AUTH_FAILED";
+}
+
+/// Work was about to be sent to a paused daemon
+#[derive(Debug, thiserror::Error)]
+#[error("{}: daemon {daemon_id} is paused", Self::CODE)]
+pub struct DaemonPaused {
+    pub daemon_id: Uuid,
+}
+
+impl DaemonPaused {
+    pub const CODE: &'static str = "DAEMON_PAUSED";
+}
+
+/// A daemon speaks a discovery protocol too old for any request shape the server can send
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "{}: daemon {daemon_id} speaks discovery protocol {version}, but at least {minimum} is \
+     required. Upgrade the daemon to dispatch discoveries to it.",
+    Self::CODE
+)]
+pub struct DaemonProtocolUnsupported {
+    pub daemon_id: Uuid,
+    pub version: u32,
+    pub minimum: u32,
+}
+
+impl DaemonProtocolUnsupported {
+    pub const CODE: &'static str = "DAEMON_PROTOCOL_UNSUPPORTED";
+}
+
+/// A daemon didn't answer an on-demand check in time
+#[derive(Debug, thiserror::Error)]
+#[error("Daemon {daemon_id} didn't return the check result within {}s", timeout.as_secs())]
+pub struct DaemonCheckTimedOut {
+    pub daemon_id: Uuid,
+    pub timeout: std::time::Duration,
+}
+
 #[derive(
     Debug, Display, Copy, Clone, Serialize, Deserialize, Default, PartialEq, Eq, ValueEnum,
 )]
@@ -1,6 +1,6 @@
 use crate::server::daemons::r#impl::base::Daemon;
 use crate::server::daemons::service::DaemonService;
-use crate::server::shared::handlers::traits::CrudHandlers;
+use crate::server::shared::handlers::traits::{CrudHandlers, EntityScope};
 
 impl CrudHandlers for Daemon {
     type Service = DaemonService;
@@ -8,4 +8,8 @@ impl CrudHandlers for Daemon {
     fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
         &state.services.daemon_service
     }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.base.network_id)
+    }
 }
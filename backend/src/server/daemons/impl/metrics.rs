@@ -0,0 +1,384 @@
+use anyhow::bail;
+use std::fmt::Display;
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Width of the buckets daemon metrics are rolled up into, and so the finest resolution they can
+/// be queried at
+pub const METRICS_RESOLUTION_SECS: i64 = 60 * 60;
+
+/// Most buckets a single metrics query may return
+pub const MAX_METRICS_BUCKETS: i64 = 10_000;
+
+/// Most network interfaces a heartbeat may report, bounding the snapshot kept on the daemon
+pub const MAX_REPORTED_INTERFACES: usize = 64;
+
+/// Traffic counters of one network interface, cumulative since the daemon's host booted
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DaemonInterfaceStats {
+    pub name: String,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+}
+
+/// Health of the host a daemon runs on, as reported in its heartbeats
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DaemonSystemMetrics {
+    /// Busy share of all CPUs since the previous report, 0 to 100
+    pub cpu_percent: f64,
+    pub memory_used_bytes: u64,
+    pub memory_total_bytes: u64,
+    /// Usage of the filesystem the daemon runs from
+    pub disk_used_bytes: u64,
+    pub disk_total_bytes: u64,
+    #[serde(default)]
+    pub interfaces: Vec<DaemonInterfaceStats>,
+}
+
+fn percent(used: u64, total: u64) -> Option<f64> {
+    (total > 0).then(|| used as f64 * 100.0 / total as f64)
+}
+
+impl DaemonSystemMetrics {
+    /// Reject values that can't be real, so one bad report can't skew the rollups
+    pub fn check_ranges(&self) -> anyhow::Result<()> {
+        if !(0.0..=100.0).contains(&self.cpu_percent) {
+            bail!(
+                "cpu_percent must be between 0 and 100, got {}",
+                self.cpu_percent
+            );
+        }
+        if self.memory_used_bytes > self.memory_total_bytes {
+            bail!("memory_used_bytes exceeds memory_total_bytes");
+        }
+        if self.disk_used_bytes > self.disk_total_bytes {
+            bail!("disk_used_bytes exceeds disk_total_bytes");
+        }
+        if self.interfaces.len() > MAX_REPORTED_INTERFACES {
+            bail!(
+                "At most {} interfaces can be reported",
+                MAX_REPORTED_INTERFACES
+            );
+        }
+        if let Some(interface) = self
+            .interfaces
+            .iter()
+            .find(|i| i.name.is_empty() || i.name.len() > 64)
+        {
+            bail!("Invalid interface name '{}'", interface.name);
+        }
+        Ok(())
+    }
+
+    pub fn memory_percent(&self) -> Option<f64> {
+        percent(self.memory_used_bytes, self.memory_total_bytes)
+    }
+
+    pub fn disk_percent(&self) -> Option<f64> {
+        percent(self.disk_used_bytes, self.disk_total_bytes)
+    }
+}
+
+/// The latest system metrics a daemon reported. Only this snapshot is kept on the daemon;
+/// history lives in the rollups.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DaemonSystemSnapshot {
+    pub reported_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub metrics: DaemonSystemMetrics,
+}
+
+/// One daemon's heartbeats and discovery sessions over one [`METRICS_RESOLUTION_SECS`] bucket
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DaemonMetricRollupBase {
+    pub daemon_id: Uuid,
+    pub network_id: Uuid,
+    pub bucket_start: DateTime<Utc>,
+    pub heartbeats: i32,
+    /// Longest wait before a heartbeat in this bucket, counting from the one before it even when
+    /// that was in an earlier bucket
+    pub max_heartbeat_gap_secs: Option<i32>,
+    pub last_heartbeat_at: Option<DateTime<Utc>>,
+    /// Discovery sessions that finished in this bucket
+    pub sessions: i32,
+    /// Combined run time of those sessions
+    pub session_secs: i32,
+    /// Heartbeats in this bucket that carried system metrics
+    pub system_samples: i32,
+    pub cpu_percent_sum: f64,
+    pub max_cpu_percent: Option<f64>,
+    pub max_memory_percent: Option<f64>,
+    pub max_disk_percent: Option<f64>,
+}
+
+fn max_of(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonMetricRollup {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub base: DaemonMetricRollupBase,
+}
+
+impl Display for DaemonMetricRollup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Daemon {} metrics at {}: {}",
+            self.base.daemon_id, self.base.bucket_start, self.id
+        )
+    }
+}
+
+/// Start of the rollup bucket `time` falls in
+pub fn bucket_start(time: DateTime<Utc>) -> DateTime<Utc> {
+    time.duration_trunc(Duration::seconds(METRICS_RESOLUTION_SECS))
+        .unwrap_or(time)
+}
+
+fn secs(duration: Duration) -> i32 {
+    duration.num_seconds().clamp(0, i32::MAX as i64) as i32
+}
+
+impl DaemonMetricRollupBase {
+    pub fn empty(daemon_id: Uuid, network_id: Uuid, bucket_start: DateTime<Utc>) -> Self {
+        Self {
+            daemon_id,
+            network_id,
+            bucket_start,
+            heartbeats: 0,
+            max_heartbeat_gap_secs: None,
+            last_heartbeat_at: None,
+            sessions: 0,
+            session_secs: 0,
+            system_samples: 0,
+            cpu_percent_sum: 0.0,
+            max_cpu_percent: None,
+            max_memory_percent: None,
+            max_disk_percent: None,
+        }
+    }
+
+    pub fn add_heartbeat(&mut self, at: DateTime<Utc>, previous: Option<DateTime<Utc>>) {
+        self.heartbeats += 1;
+        if let Some(previous) = previous {
+            let gap = secs(at - previous);
+            self.max_heartbeat_gap_secs =
+                Some(self.max_heartbeat_gap_secs.map_or(gap, |g| g.max(gap)));
+        }
+        self.last_heartbeat_at = self.last_heartbeat_at.max(Some(at));
+    }
+
+    pub fn add_session(&mut self, duration: Duration) {
+        self.sessions += 1;
+        self.session_secs = self.session_secs.saturating_add(secs(duration));
+    }
+
+    pub fn add_system_metrics(&mut self, metrics: &DaemonSystemMetrics) {
+        self.system_samples += 1;
+        self.cpu_percent_sum += metrics.cpu_percent;
+        self.max_cpu_percent = max_of(self.max_cpu_percent, Some(metrics.cpu_percent));
+        self.max_memory_percent = max_of(self.max_memory_percent, metrics.memory_percent());
+        self.max_disk_percent = max_of(self.max_disk_percent, metrics.disk_percent());
+    }
+
+    /// Fold in more of the same bucket, e.g. samples that arrived after it was first rolled up
+    pub fn merge(&mut self, other: &Self) {
+        self.heartbeats += other.heartbeats;
+        self.max_heartbeat_gap_secs = self
+            .max_heartbeat_gap_secs
+            .max(other.max_heartbeat_gap_secs);
+        self.last_heartbeat_at = self.last_heartbeat_at.max(other.last_heartbeat_at);
+        self.sessions += other.sessions;
+        self.session_secs = self.session_secs.saturating_add(other.session_secs);
+        self.system_samples += other.system_samples;
+        self.cpu_percent_sum += other.cpu_percent_sum;
+        self.max_cpu_percent = max_of(self.max_cpu_percent, other.max_cpu_percent);
+        self.max_memory_percent = max_of(self.max_memory_percent, other.max_memory_percent);
+        self.max_disk_percent = max_of(self.max_disk_percent, other.max_disk_percent);
+    }
+}
+
+/// `GET /api/daemons/{id}/metrics` parameters. The window defaults to the last day, and the
+/// resolution, in seconds, to the stored one.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DaemonMetricsQuery {
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub resolution: Option<i64>,
+}
+
+/// A validated [`DaemonMetricsQuery`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DaemonMetricsWindow {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub resolution_secs: i64,
+    pub note: Option<String>,
+}
+
+impl DaemonMetricsQuery {
+    pub fn window(&self, now: DateTime<Utc>) -> Result<DaemonMetricsWindow, String> {
+        let to = self.to.unwrap_or(now);
+        let from = self.from.unwrap_or(to - Duration::days(1));
+        if from >= to {
+            return Err("`from` must be before `to`".to_string());
+        }
+
+        let (resolution_secs, note) = effective_resolution(self.resolution);
+        if (to - from).num_seconds() / resolution_secs > MAX_METRICS_BUCKETS {
+            return Err(format!(
+                "Window too large for the resolution, at most {} buckets can be returned",
+                MAX_METRICS_BUCKETS
+            ));
+        }
+
+        Ok(DaemonMetricsWindow {
+            from,
+            to,
+            resolution_secs,
+            note,
+        })
+    }
+}
+
+/// A daemon's metrics over one bucket of the requested resolution. Every field is null for a
+/// bucket with no data, e.g. while the daemon was offline, rather than interpolated.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DaemonMetricsBucket {
+    pub start: DateTime<Utc>,
+    pub heartbeats: Option<i32>,
+    pub max_heartbeat_gap_secs: Option<i32>,
+    /// Discovery sessions finished, scaled to a per-hour rate
+    pub sessions_per_hour: Option<f64>,
+    pub avg_session_secs: Option<f64>,
+    pub avg_cpu_percent: Option<f64>,
+    pub max_cpu_percent: Option<f64>,
+    pub max_memory_percent: Option<f64>,
+    pub max_disk_percent: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonMetrics {
+    pub daemon_id: Uuid,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub resolution_secs: i64,
+    /// Set when the requested resolution couldn't be served as asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    pub buckets: Vec<DaemonMetricsBucket>,
+}
+
+/// The resolution to serve for a requested one: a whole multiple of the stored resolution, never
+/// finer, with a note when it differs from what was asked for
+pub fn effective_resolution(requested: Option<i64>) -> (i64, Option<String>) {
+    let Some(requested) = requested else {
+        return (METRICS_RESOLUTION_SECS, None);
+    };
+
+    if requested < METRICS_RESOLUTION_SECS {
+        return (
+            METRICS_RESOLUTION_SECS,
+            Some(format!(
+                "Metrics are stored at {} second resolution, finer resolutions aren't available",
+                METRICS_RESOLUTION_SECS
+            )),
+        );
+    }
+
+    let rounded = (requested + METRICS_RESOLUTION_SECS - 1) / METRICS_RESOLUTION_SECS
+        * METRICS_RESOLUTION_SECS;
+    let note = (rounded != requested).then(|| {
+        format!(
+            "Resolution rounded up to {} seconds, a multiple of the stored {} second resolution",
+            rounded, METRICS_RESOLUTION_SECS
+        )
+    });
+
+    (rounded, note)
+}
+
+impl DaemonMetricsWindow {
+    fn resolution(&self) -> Duration {
+        Duration::seconds(self.resolution_secs)
+    }
+
+    /// Start of the first bucket, aligned to the resolution
+    pub fn start(&self) -> DateTime<Utc> {
+        self.from
+            .duration_trunc(self.resolution())
+            .unwrap_or(self.from)
+    }
+}
+
+/// Bucket `rollups` into consecutive buckets of the window's resolution covering it
+pub fn metrics_buckets(
+    rollups: &[DaemonMetricRollupBase],
+    window: &DaemonMetricsWindow,
+) -> Vec<DaemonMetricsBucket> {
+    let resolution = window.resolution();
+    let resolution_secs = window.resolution_secs;
+    let mut start = window.start();
+    let mut buckets = Vec::new();
+
+    while start < window.to {
+        let end = start + resolution;
+        let mut total: Option<DaemonMetricRollupBase> = None;
+        for rollup in rollups
+            .iter()
+            .filter(|r| r.bucket_start >= start && r.bucket_start < end)
+        {
+            match &mut total {
+                Some(total) => total.merge(rollup),
+                None => total = Some(rollup.clone()),
+            }
+        }
+
+        buckets.push(match total {
+            Some(total) => DaemonMetricsBucket {
+                start,
+                heartbeats: Some(total.heartbeats),
+                max_heartbeat_gap_secs: total.max_heartbeat_gap_secs,
+                sessions_per_hour: Some(
+                    total.sessions as f64 * (60 * 60) as f64 / resolution_secs as f64,
+                ),
+                avg_session_secs: (total.sessions > 0)
+                    .then(|| total.session_secs as f64 / total.sessions as f64),
+                avg_cpu_percent: (total.system_samples > 0)
+                    .then(|| total.cpu_percent_sum / total.system_samples as f64),
+                max_cpu_percent: total.max_cpu_percent,
+                max_memory_percent: total.max_memory_percent,
+                max_disk_percent: total.max_disk_percent,
+            },
+            None => DaemonMetricsBucket {
+                start,
+                heartbeats: None,
+                max_heartbeat_gap_secs: None,
+                sessions_per_hour: None,
+                avg_session_secs: None,
+                avg_cpu_percent: None,
+                max_cpu_percent: None,
+                max_memory_percent: None,
+                max_disk_percent: None,
+            },
+        });
+        start = end;
+    }
+
+    buckets
+}
@@ -1,4 +1,7 @@
 pub mod api;
 pub mod base;
 pub mod handlers;
+pub mod metrics;
+pub mod signing;
 pub mod storage;
+pub mod transfer;
@@ -0,0 +1,233 @@
+use axum::http::HeaderMap;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use uuid::Uuid;
+
+use crate::server::{
+    config::{AppState, ServerConfig},
+    shared::{sync::lock, types::api::ApiError},
+};
+use axum::extract::FromRef;
+
+pub const TIMESTAMP_HEADER: &str = "x-netvisor-timestamp";
+pub const NONCE_HEADER: &str = "x-netvisor-nonce";
+pub const SIGNATURE_HEADER: &str = "x-netvisor-signature";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The string both sides sign:
+///
+/// ```text
+/// v1\n<unix timestamp>\n<nonce>\n<lowercase hex sha256 of the exact body bytes>
+/// ```
+///
+/// The request path is deliberately left out so signatures survive path-rewriting reverse
+/// proxies; a nonce is only ever accepted once, so a signed body can't be replayed elsewhere.
+pub fn canonical_string(timestamp: i64, nonce: &str, body: &[u8]) -> String {
+    format!(
+        "v1\n{}\n{}\n{}",
+        timestamp,
+        nonce,
+        hex::encode(Sha256::digest(body))
+    )
+}
+
+fn mac(api_key: &str, timestamp: i64, nonce: &str, body: &[u8]) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(api_key.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(canonical_string(timestamp, nonce, body).as_bytes());
+    mac
+}
+
+/// Headers a daemon attaches to a signed request, HMAC-SHA256 keyed with its API key
+pub fn signature_headers(api_key: &str, body: &[u8]) -> [(&'static str, String); 3] {
+    let timestamp = Utc::now().timestamp();
+    let nonce = Uuid::new_v4().to_string();
+    let signature = hex::encode(
+        mac(api_key, timestamp, &nonce, body)
+            .finalize()
+            .into_bytes(),
+    );
+
+    [
+        (TIMESTAMP_HEADER, timestamp.to_string()),
+        (NONCE_HEADER, nonce),
+        (SIGNATURE_HEADER, signature),
+    ]
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SignatureError {
+    #[error("Request must be signed")]
+    Missing,
+    #[error("Malformed signature headers")]
+    Malformed,
+    #[error("Signature timestamp is outside the accepted window")]
+    Stale,
+    #[error("Signature nonce has already been used")]
+    Replayed,
+    #[error("Signature does not match the request body")]
+    Mismatch,
+}
+
+impl From<SignatureError> for ApiError {
+    fn from(err: SignatureError) -> Self {
+        ApiError::unauthorized(err.to_string())
+    }
+}
+
+/// Verifies signed daemon payloads and remembers recent nonces to reject replays. Clones share
+/// the nonce cache.
+#[derive(Clone)]
+pub struct ResultSignatureVerifier {
+    required: bool,
+    max_skew: Duration,
+    seen_nonces: Arc<Mutex<HashMap<String, i64>>>,
+}
+
+impl From<&ServerConfig> for ResultSignatureVerifier {
+    fn from(config: &ServerConfig) -> Self {
+        Self::new(
+            config.require_signed_results,
+            Duration::from_secs(config.signature_max_skew_secs),
+        )
+    }
+}
+
+impl FromRef<Arc<AppState>> for ResultSignatureVerifier {
+    fn from_ref(state: &Arc<AppState>) -> Self {
+        state.result_signatures.clone()
+    }
+}
+
+impl ResultSignatureVerifier {
+    pub fn new(required: bool, max_skew: Duration) -> Self {
+        Self {
+            required,
+            max_skew,
+            seen_nonces: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Check the signature headers against `body`. Unsigned requests pass unless signing is
+    /// required, so daemons predating signing keep working.
+    pub fn verify(
+        &self,
+        api_key: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<(), SignatureError> {
+        let header = |name| headers.get(name).map(|v| v.to_str());
+
+        let (timestamp, nonce, signature) = match (
+            header(TIMESTAMP_HEADER),
+            header(NONCE_HEADER),
+            header(SIGNATURE_HEADER),
+        ) {
+            (None, None, None) if !self.required => return Ok(()),
+            (Some(Ok(t)), Some(Ok(n)), Some(Ok(s))) => (t, n, s),
+            (None, None, None) => return Err(SignatureError::Missing),
+            _ => return Err(SignatureError::Malformed),
+        };
+
+        let timestamp: i64 = timestamp.parse().map_err(|_| SignatureError::Malformed)?;
+        let signature = hex::decode(signature).map_err(|_| SignatureError::Malformed)?;
+
+        let now = Utc::now().timestamp();
+        let max_skew = self.max_skew.as_secs() as i64;
+        if (now - timestamp).abs() > max_skew {
+            return Err(SignatureError::Stale);
+        }
+
+        mac(api_key, timestamp, nonce, body)
+            .verify_slice(&signature)
+            .map_err(|_| SignatureError::Mismatch)?;
+
+        // Only remember nonces of valid signatures, so garbage can't fill the cache
+        let mut seen = lock(&self.seen_nonces);
+        seen.retain(|_, ts| (now - *ts).abs() <= max_skew);
+        if seen.insert(nonce.to_string(), timestamp).is_some() {
+            return Err(SignatureError::Replayed);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    const KEY: &str = "test-api-key";
+
+    fn signed(body: &[u8]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in signature_headers(KEY, body) {
+            headers.insert(name, HeaderValue::from_str(&value).unwrap());
+        }
+        headers
+    }
+
+    fn verifier(required: bool) -> ResultSignatureVerifier {
+        ResultSignatureVerifier::new(required, Duration::from_secs(300))
+    }
+
+    #[test]
+    fn test_signed_payload_accepted_and_tampered_rejected() {
+        let body = br#"{"hosts":[{"name":"web"}]}"#;
+        let headers = signed(body);
+
+        assert_eq!(
+            verifier(true).verify(KEY, &headers, br#"{"hosts":[{"name":"evil"}]}"#),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            verifier(true).verify("other-key", &headers, body),
+            Err(SignatureError::Mismatch)
+        );
+
+        let verifier = verifier(true);
+        assert_eq!(verifier.verify(KEY, &headers, body), Ok(()));
+        // The same nonce can't be used twice
+        assert_eq!(
+            verifier.verify(KEY, &headers, body),
+            Err(SignatureError::Replayed)
+        );
+    }
+
+    #[test]
+    fn test_unsigned_and_stale_payloads() {
+        let body = b"{}";
+
+        assert_eq!(verifier(false).verify(KEY, &HeaderMap::new(), body), Ok(()));
+        assert_eq!(
+            verifier(true).verify(KEY, &HeaderMap::new(), body),
+            Err(SignatureError::Missing)
+        );
+
+        let stale = Utc::now().timestamp() - 3600;
+        let signature = hex::encode(mac(KEY, stale, "nonce", body).finalize().into_bytes());
+        let mut headers = HeaderMap::new();
+        headers.insert(TIMESTAMP_HEADER, HeaderValue::from(stale));
+        headers.insert(NONCE_HEADER, HeaderValue::from_static("nonce"));
+        headers.insert(SIGNATURE_HEADER, HeaderValue::from_str(&signature).unwrap());
+        assert_eq!(
+            verifier(false).verify(KEY, &headers, body),
+            Err(SignatureError::Stale)
+        );
+
+        // A partially signed request is never treated as unsigned
+        headers.remove(SIGNATURE_HEADER);
+        assert_eq!(
+            verifier(false).verify(KEY, &headers, body),
+            Err(SignatureError::Malformed)
+        );
+    }
+}
@@ -7,7 +7,8 @@ use uuid::Uuid;
 use crate::server::{
     daemons::r#impl::{
         api::DaemonCapabilities,
-        base::{Daemon, DaemonBase, DaemonMode},
+        base::{Daemon, DaemonBase, DaemonLabels, DaemonMode},
+        metrics::{DaemonMetricRollup, DaemonMetricRollupBase, DaemonSystemSnapshot},
     },
     shared::storage::traits::{SqlValue, StorableEntity},
 };
@@ -64,6 +65,13 @@ impl StorableEntity for Daemon {
                     capabilities,
                     last_seen,
                     mode,
+                    version,
+                    auth_failed_at,
+                    paused,
+                    labels,
+                    system_metrics,
+                    // Not written back by full-row updates, see DaemonService::server_key
+                    server_key: _,
                 },
         } = self.clone();
 
@@ -79,6 +87,11 @@ impl StorableEntity for Daemon {
                 "port",
                 "ip",
                 "mode",
+                "version",
+                "auth_failed_at",
+                "paused",
+                "labels",
+                "system_metrics",
             ],
             vec![
                 SqlValue::Uuid(id),
@@ -91,6 +104,11 @@ impl StorableEntity for Daemon {
                 SqlValue::U16(port),
                 SqlValue::IpAddr(ip),
                 SqlValue::DaemonMode(mode),
+                SqlValue::OptionalString(version),
+                SqlValue::OptionTimestamp(auth_failed_at),
+                SqlValue::Bool(paused),
+                SqlValue::Json(serde_json::to_value(labels)?),
+                SqlValue::Json(serde_json::to_value(system_metrics)?),
             ],
         ))
     }
@@ -106,6 +124,16 @@ impl StorableEntity for Daemon {
             serde_json::from_value(row.get::<serde_json::Value, _>("capabilities"))
                 .map_err(|e| anyhow::anyhow!("Failed to deserialize capabilities: {}", e))?;
 
+        let labels: DaemonLabels =
+            serde_json::from_value(row.get::<serde_json::Value, _>("labels"))
+                .map_err(|e| anyhow::anyhow!("Failed to deserialize labels: {}", e))?;
+
+        let system_metrics: Option<DaemonSystemSnapshot> = row
+            .get::<Option<serde_json::Value>, _>("system_metrics")
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("Failed to deserialize system_metrics: {}", e))?;
+
         Ok(Daemon {
             id: row.get("id"),
             created_at: row.get("created_at"),
@@ -118,6 +146,182 @@ impl StorableEntity for Daemon {
                 network_id: row.get("network_id"),
                 mode,
                 capabilities,
+                version: row.get("version"),
+                auth_failed_at: row.get("auth_failed_at"),
+                paused: row.get("paused"),
+                labels,
+                system_metrics,
+                server_key: row.get("server_key"),
+            },
+        })
+    }
+}
+
+/// How a flushed rollup is added to the row already stored for its bucket, in SQL, mirroring
+/// [`DaemonMetricRollupBase::merge`]. GREATEST skips NULLs the way the merge skips Nones.
+pub const ROLLUP_MERGE: [(&str, &str); 10] = [
+    (
+        "heartbeats",
+        "daemon_metric_rollups.heartbeats + EXCLUDED.heartbeats",
+    ),
+    (
+        "max_heartbeat_gap_secs",
+        "GREATEST(daemon_metric_rollups.max_heartbeat_gap_secs, EXCLUDED.max_heartbeat_gap_secs)",
+    ),
+    (
+        "last_heartbeat_at",
+        "GREATEST(daemon_metric_rollups.last_heartbeat_at, EXCLUDED.last_heartbeat_at)",
+    ),
+    (
+        "sessions",
+        "daemon_metric_rollups.sessions + EXCLUDED.sessions",
+    ),
+    (
+        "session_secs",
+        "LEAST(daemon_metric_rollups.session_secs::BIGINT + EXCLUDED.session_secs, 2147483647)",
+    ),
+    (
+        "system_samples",
+        "daemon_metric_rollups.system_samples + EXCLUDED.system_samples",
+    ),
+    (
+        "cpu_percent_sum",
+        "daemon_metric_rollups.cpu_percent_sum + EXCLUDED.cpu_percent_sum",
+    ),
+    (
+        "max_cpu_percent",
+        "GREATEST(daemon_metric_rollups.max_cpu_percent, EXCLUDED.max_cpu_percent)",
+    ),
+    (
+        "max_memory_percent",
+        "GREATEST(daemon_metric_rollups.max_memory_percent, EXCLUDED.max_memory_percent)",
+    ),
+    (
+        "max_disk_percent",
+        "GREATEST(daemon_metric_rollups.max_disk_percent, EXCLUDED.max_disk_percent)",
+    ),
+];
+
+impl StorableEntity for DaemonMetricRollup {
+    type BaseData = DaemonMetricRollupBase;
+
+    fn table_name() -> &'static str {
+        "daemon_metric_rollups"
+    }
+
+    fn get_base(&self) -> Self::BaseData {
+        self.base.clone()
+    }
+
+    fn new(base: Self::BaseData) -> Self {
+        let now = chrono::Utc::now();
+
+        Self {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: now,
+            base,
+        }
+    }
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn set_updated_at(&mut self, time: DateTime<Utc>) {
+        self.updated_at = time;
+    }
+
+    fn to_params(&self) -> Result<(Vec<&'static str>, Vec<SqlValue>), anyhow::Error> {
+        let Self {
+            id,
+            created_at,
+            updated_at,
+            base:
+                Self::BaseData {
+                    daemon_id,
+                    network_id,
+                    bucket_start,
+                    heartbeats,
+                    max_heartbeat_gap_secs,
+                    last_heartbeat_at,
+                    sessions,
+                    session_secs,
+                    system_samples,
+                    cpu_percent_sum,
+                    max_cpu_percent,
+                    max_memory_percent,
+                    max_disk_percent,
+                },
+        } = self.clone();
+
+        Ok((
+            vec![
+                "id",
+                "created_at",
+                "updated_at",
+                "daemon_id",
+                "network_id",
+                "bucket_start",
+                "heartbeats",
+                "max_heartbeat_gap_secs",
+                "last_heartbeat_at",
+                "sessions",
+                "session_secs",
+                "system_samples",
+                "cpu_percent_sum",
+                "max_cpu_percent",
+                "max_memory_percent",
+                "max_disk_percent",
+            ],
+            vec![
+                SqlValue::Uuid(id),
+                SqlValue::Timestamp(created_at),
+                SqlValue::Timestamp(updated_at),
+                SqlValue::Uuid(daemon_id),
+                SqlValue::Uuid(network_id),
+                SqlValue::Timestamp(bucket_start),
+                SqlValue::I32(heartbeats),
+                SqlValue::OptionalI32(max_heartbeat_gap_secs),
+                SqlValue::OptionTimestamp(last_heartbeat_at),
+                SqlValue::I32(sessions),
+                SqlValue::I32(session_secs),
+                SqlValue::I32(system_samples),
+                SqlValue::F64(cpu_percent_sum),
+                SqlValue::OptionalF64(max_cpu_percent),
+                SqlValue::OptionalF64(max_memory_percent),
+                SqlValue::OptionalF64(max_disk_percent),
+            ],
+        ))
+    }
+
+    fn from_row(row: &PgRow) -> Result<Self, anyhow::Error> {
+        Ok(DaemonMetricRollup {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            base: DaemonMetricRollupBase {
+                daemon_id: row.get("daemon_id"),
+                network_id: row.get("network_id"),
+                bucket_start: row.get("bucket_start"),
+                heartbeats: row.get("heartbeats"),
+                max_heartbeat_gap_secs: row.get("max_heartbeat_gap_secs"),
+                last_heartbeat_at: row.get("last_heartbeat_at"),
+                sessions: row.get("sessions"),
+                session_secs: row.get("session_secs"),
+                system_samples: row.get("system_samples"),
+                cpu_percent_sum: row.get("cpu_percent_sum"),
+                max_cpu_percent: row.get("max_cpu_percent"),
+                max_memory_percent: row.get("max_memory_percent"),
+                max_disk_percent: row.get("max_disk_percent"),
             },
         })
     }
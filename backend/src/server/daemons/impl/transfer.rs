@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::server::{
+    audit::service::AuditService,
+    config::ServerConfig,
+    daemons::r#impl::base::Daemon,
+    discovery::{r#impl::base::Discovery, service::DiscoveryService},
+    hosts::r#impl::base::Host,
+    networks::r#impl::Network,
+    services::r#impl::base::Service,
+    shared::{storage::generic::GenericPostgresStorage, types::entities::EntitySource},
+};
+
+/// What happens when a daemon is transferred while one of its discovery sessions is still active
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActiveDiscoveryOnTransfer {
+    /// Refuse the transfer until the session finishes
+    #[default]
+    Block,
+    /// Cancel the session and go ahead. Cancellations stand even if the transfer then fails.
+    Cancel,
+}
+
+/// A daemon can't be transferred while it's running a discovery
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "{}: daemon {daemon_id} is running discovery session {session_id}. Wait for it to finish or \
+     cancel it before transferring the daemon.",
+    Self::CODE
+)]
+pub struct DaemonTransferBlocked {
+    pub daemon_id: Uuid,
+    pub session_id: Uuid,
+}
+
+impl DaemonTransferBlocked {
+    pub const CODE: &'static str = "DAEMON_TRANSFER_BLOCKED";
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonTransferRequest {
+    pub network_id: Uuid,
+    /// Also move the hosts the daemon discovered, their services, and its past discovery
+    /// results. Otherwise they stay behind in the old network.
+    #[serde(default)]
+    pub move_data: bool,
+}
+
+/// What a transfer moved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonTransfer {
+    pub daemon: Daemon,
+    pub from_network_id: Uuid,
+    pub to_network_id: Uuid,
+    /// Includes the daemon's own host
+    pub hosts_moved: usize,
+    pub services_moved: usize,
+    pub discoveries_moved: usize,
+    pub sessions_cancelled: Vec<Uuid>,
+}
+
+/// Whether `host` was found by `daemon_id`
+pub fn discovered_by(host: &Host, daemon_id: Uuid) -> bool {
+    match &host.base.source {
+        EntitySource::Discovery { metadata } => {
+            metadata.iter().any(|m| m.daemon_id == Some(daemon_id))
+        }
+        _ => false,
+    }
+}
+
+/// Everything beyond daemons a transfer touches
+pub struct DaemonTransfers {
+    pub on_active_discovery: ActiveDiscoveryOnTransfer,
+    pub network_storage: Arc<GenericPostgresStorage<Network>>,
+    pub host_storage: Arc<GenericPostgresStorage<Host>>,
+    pub service_storage: Arc<GenericPostgresStorage<Service>>,
+    pub discovery_storage: Arc<GenericPostgresStorage<Discovery>>,
+    pub discovery_service: Arc<DiscoveryService>,
+    pub audit_service: Arc<AuditService>,
+}
+
+impl DaemonTransfers {
+    pub fn new(
+        config: Option<&ServerConfig>,
+        network_storage: Arc<GenericPostgresStorage<Network>>,
+        host_storage: Arc<GenericPostgresStorage<Host>>,
+        service_storage: Arc<GenericPostgresStorage<Service>>,
+        discovery_storage: Arc<GenericPostgresStorage<Discovery>>,
+        discovery_service: Arc<DiscoveryService>,
+        audit_service: Arc<AuditService>,
+    ) -> Self {
+        Self {
+            on_active_discovery: config
+                .map(|c| c.daemon_transfer_active_discovery)
+                .unwrap_or_default(),
+            network_storage,
+            host_storage,
+            service_storage,
+            discovery_storage,
+            discovery_service,
+            audit_service,
+        }
+    }
+}
@@ -1,3 +1,6 @@
 pub mod handlers;
 pub mod r#impl;
 pub mod service;
+
+#[cfg(test)]
+pub mod tests;
@@ -1,26 +1,126 @@
 use crate::{
-    daemon::runtime::types::InitializeDaemonRequest,
+    daemon::{
+        discovery::types::base::DiscoveryPhase,
+        runtime::types::InitializeDaemonRequest,
+        utils::{
+            network_checks::{
+                arp::ArpScanResult,
+                ndp::NdpScanResult,
+                suite::{
+                    SuiteCheck, SuiteCheckResult, SuiteDefinition, SuiteExecution, SuiteReport,
+                },
+                types::CheckOptions,
+            },
+            signing::SignedJsonExt,
+        },
+    },
     server::{
+        audit::r#impl::base::{AuditEventBase, AuditEventKind},
         daemons::r#impl::{
-            api::{DaemonDiscoveryRequest, DaemonDiscoveryResponse},
-            base::Daemon,
+            api::{
+                DAEMON_PROTOCOL_VERSION, DaemonArpScanRequest, DaemonCheckRequest,
+                DaemonDiscoveryRequest, DaemonDiscoveryResponse, DaemonNdpScanRequest,
+                DaemonProbeRequest, DaemonSuiteRequest, DaemonTestReport, DaemonTestStep,
+                DaemonTestStepResult, LEGACY_DAEMON_PROTOCOL_VERSION,
+            },
+            base::{
+                Daemon, DaemonAuthFailed, DaemonCheckTimedOut, DaemonPaused,
+                DaemonProtocolUnsupported,
+            },
+            metrics::{
+                DaemonMetricRollup, DaemonMetricRollupBase, DaemonMetrics, DaemonMetricsWindow,
+                DaemonSystemMetrics, bucket_start, metrics_buckets,
+            },
+            storage::ROLLUP_MERGE,
+            transfer::{
+                ActiveDiscoveryOnTransfer, DaemonTransfer, DaemonTransferBlocked, DaemonTransfers,
+                discovered_by,
+            },
+        },
+        discovery::{
+            r#impl::{
+                base::Discovery,
+                history::SessionActor,
+                target_policy::{ScanTargetGuard, ScanTargetPolicy},
+                types::{DiscoveryType, RunType},
+            },
+            service::DiscoveryService,
+        },
+        hosts::r#impl::{base::Host, ports::PortBase},
+        notifications::{
+            r#impl::base::{Notification, NotificationKind},
+            service::NotificationService,
+        },
+        services::r#impl::{
+            base::Service,
+            endpoints::{ApplicationProtocol, Endpoint},
         },
-        hosts::r#impl::ports::PortBase,
-        services::r#impl::endpoints::{ApplicationProtocol, Endpoint},
         shared::{
-            services::traits::CrudService, storage::generic::GenericPostgresStorage,
+            services::traits::CrudService,
+            storage::{
+                filter::EntityFilter,
+                generic::GenericPostgresStorage,
+                traits::{SqlValue, StorableEntity, Storage},
+            },
+            sync::lock,
             types::api::ApiResponse,
         },
+        subnets::r#impl::base::Subnet,
     },
 };
-use anyhow::{Error, Result};
+use anyhow::{Error, Result, anyhow};
 use async_trait::async_trait;
-use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use cidr::IpCidr;
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
 use uuid::Uuid;
 
+const TEST_STEP_TIMEOUT: Duration = Duration::from_secs(10);
+/// A daemon that hasn't been seen for this long is considered offline
+pub const DAEMON_OFFLINE_AFTER: Duration = Duration::from_secs(5 * 60);
+/// 403s in a row from a daemon before it's flagged as needing re-credentialing. A 401 is
+/// unambiguous, so flags straight away.
+const FORBIDDEN_BEFORE_FLAGGING: u32 = 2;
+
 pub struct DaemonService {
     daemon_storage: Arc<GenericPostgresStorage<Daemon>>,
+    metric_storage: Arc<GenericPostgresStorage<DaemonMetricRollup>>,
     client: reqwest::Client,
+    scan_guard: OnceLock<ScanTargetGuard>,
+    transfers: OnceLock<DaemonTransfers>,
+    pending_metrics: Mutex<PendingMetrics>,
+    /// How long rollups are kept. None keeps them forever.
+    metrics_retention: Option<chrono::Duration>,
+    notification_service: OnceLock<Arc<NotificationService>>,
+    discovery_service: OnceLock<Arc<DiscoveryService>>,
+    /// 403s in a row per daemon, reset when a request gets through
+    forbidden_streaks: Mutex<HashMap<Uuid, u32>>,
+}
+
+/// Metrics gathered since the last flush, added to the stored rollups on the next one
+#[derive(Default)]
+struct PendingMetrics {
+    buckets: HashMap<(Uuid, DateTime<Utc>), DaemonMetricRollupBase>,
+}
+
+impl PendingMetrics {
+    fn bucket(
+        &mut self,
+        daemon_id: Uuid,
+        network_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> &mut DaemonMetricRollupBase {
+        let start = bucket_start(at);
+        self.buckets
+            .entry((daemon_id, start))
+            .or_insert_with(|| DaemonMetricRollupBase::empty(daemon_id, network_id, start))
+    }
 }
 
 #[async_trait]
@@ -28,41 +128,666 @@ impl CrudService<Daemon> for DaemonService {
     fn storage(&self) -> &Arc<GenericPostgresStorage<Daemon>> {
         &self.daemon_storage
     }
+
+    /// Delete a daemon, dropping the discovery sessions queued or running on it so the slots
+    /// they hold under the discovery cap go to other daemons
+    async fn delete(&self, id: &Uuid) -> Result<()> {
+        let daemon = self
+            .get_by_id(id)
+            .await?
+            .ok_or_else(|| anyhow!("Daemon not found"))?;
+
+        tracing::info!(daemon_id = %daemon.id, "Deleting daemon");
+        self.daemon_storage.delete(id).await?;
+
+        if let Some(discovery_service) = self.discovery_service.get() {
+            discovery_service.remove_daemon_sessions(id).await;
+        }
+
+        Ok(())
+    }
+
+    /// Update a daemon, keeping its stored pause state. Pausing and resuming go through
+    /// [`DaemonService::set_paused`] so they're admin-only, logged, and resuming starts the
+    /// session queued for the daemon.
+    async fn update(&self, daemon: &mut Daemon) -> Result<Daemon> {
+        if let Some(stored) = self.get_by_id(&daemon.id).await? {
+            daemon.base.paused = stored.base.paused;
+        }
+
+        let updated = self.daemon_storage.update(daemon).await?;
+        tracing::info!(daemon_id = %updated.id, "Daemon updated");
+
+        Ok(updated)
+    }
 }
 
 impl DaemonService {
-    pub fn new(daemon_storage: Arc<GenericPostgresStorage<Daemon>>) -> Self {
+    pub fn new(
+        daemon_storage: Arc<GenericPostgresStorage<Daemon>>,
+        metric_storage: Arc<GenericPostgresStorage<DaemonMetricRollup>>,
+        metrics_retention: Option<chrono::Duration>,
+    ) -> Self {
         Self {
             daemon_storage,
+            metric_storage,
             client: reqwest::Client::new(),
+            scan_guard: OnceLock::new(),
+            transfers: OnceLock::new(),
+            pending_metrics: Mutex::new(PendingMetrics::default()),
+            metrics_retention,
+            notification_service: OnceLock::new(),
+            discovery_service: OnceLock::new(),
+            forbidden_streaks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_discovery_service(
+        &self,
+        discovery_service: Arc<DiscoveryService>,
+    ) -> Result<(), Arc<DiscoveryService>> {
+        self.discovery_service.set(discovery_service)
+    }
+
+    pub fn set_notification_service(
+        &self,
+        notification_service: Arc<NotificationService>,
+    ) -> Result<(), Arc<NotificationService>> {
+        self.notification_service.set(notification_service)
+    }
+
+    /// Notify about daemons that have gone quiet for longer than [`DAEMON_OFFLINE_AFTER`] since
+    /// the last sweep. Paused daemons are skipped. Each outage is marked on the daemon as it's
+    /// notified, so it's only notified once, across restarts and servers; a daemon seen again
+    /// after that is notified about its next outage. Run periodically by the server; returns how
+    /// many went offline.
+    pub async fn notify_offline_daemons(&self, now: DateTime<Utc>) -> Result<usize> {
+        let cutoff = now - chrono::Duration::from_std(DAEMON_OFFLINE_AFTER)?;
+        let went_offline = self
+            .daemon_storage
+            .update_where(
+                EntityFilter::unfiltered().newly_offline(cutoff),
+                vec![("offline_notified_at", SqlValue::Timestamp(now))],
+            )
+            .await?;
+
+        if let Some(notification_service) = self.notification_service.get() {
+            for daemon in &went_offline {
+                notification_service.notify(Notification::new(
+                    daemon.base.network_id,
+                    NotificationKind::DaemonOffline {
+                        daemon_id: daemon.id,
+                        last_seen: daemon.base.last_seen,
+                    },
+                ));
+            }
+        }
+
+        Ok(went_offline.len())
+    }
+
+    /// Turn a 401 or 403 from `daemon` into [`DaemonAuthFailed`], flagging the daemon and
+    /// notifying the first time it needs re-credentialing. The daemon answers 401 to requests
+    /// not signed with the server key it holds. Any other status passes, and a success clears
+    /// the flag.
+    async fn check_daemon_auth(&self, daemon: &Daemon, status: reqwest::StatusCode) -> Result<()> {
+        let rejected = matches!(
+            status,
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+        );
+        if !rejected {
+            lock(&self.forbidden_streaks).remove(&daemon.id);
+            if status.is_success() && daemon.base.auth_failed_at.is_some() {
+                let cleared = self
+                    .daemon_storage
+                    .update_where(
+                        EntityFilter::unfiltered()
+                            .entity_id(&daemon.id)
+                            .auth_failed(true),
+                        vec![("auth_failed_at", SqlValue::OptionTimestamp(None))],
+                    )
+                    .await?;
+                if !cleared.is_empty() {
+                    tracing::info!(daemon_id = %daemon.id, "Daemon accepts the server's requests again");
+                }
+            }
+            return Ok(());
+        }
+
+        let flagged = status == reqwest::StatusCode::UNAUTHORIZED || {
+            let mut streaks = lock(&self.forbidden_streaks);
+            let streak = streaks.entry(daemon.id).or_default();
+            *streak += 1;
+            *streak >= FORBIDDEN_BEFORE_FLAGGING
+        };
+
+        // Only the request that sets the flag notifies, however many race to
+        let newly_flagged = flagged
+            && daemon.base.auth_failed_at.is_none()
+            && !self
+                .daemon_storage
+                .update_where(
+                    EntityFilter::unfiltered()
+                        .entity_id(&daemon.id)
+                        .auth_failed(false),
+                    vec![(
+                        "auth_failed_at",
+                        SqlValue::OptionTimestamp(Some(Utc::now())),
+                    )],
+                )
+                .await?
+                .is_empty();
+
+        if newly_flagged {
+            tracing::warn!(
+                daemon_id = %daemon.id,
+                status = %status,
+                "Daemon rejected the server's requests, flagged for re-credentialing"
+            );
+            if let Some(notification_service) = self.notification_service.get() {
+                notification_service.notify(Notification::new(
+                    daemon.base.network_id,
+                    NotificationKind::DaemonAuthFailed {
+                        daemon_id: daemon.id,
+                        status: status.as_u16(),
+                    },
+                ));
+            }
+        }
+
+        Err(DaemonAuthFailed {
+            daemon_id: daemon.id,
+            status: status.as_u16(),
+            flagged,
+        }
+        .into())
+    }
+
+    /// Pause or resume `daemon`. Work already running on it carries on either way. Only the
+    /// pause state is written, so a heartbeat or transfer racing it isn't undone.
+    pub async fn set_paused(&self, daemon: &Daemon, paused: bool) -> Result<Daemon> {
+        let updated = self
+            .daemon_storage
+            .update_where(
+                EntityFilter::unfiltered().entity_id(&daemon.id),
+                vec![("paused", SqlValue::Bool(paused))],
+            )
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Could not find daemon {}", daemon.id))?;
+
+        tracing::info!(daemon_id = %daemon.id, paused, "Daemon pause state changed");
+        Ok(updated)
+    }
+
+    /// [`DaemonPaused`] if `daemon` shouldn't be sent work
+    pub fn check_not_paused(&self, daemon: &Daemon) -> Result<()> {
+        if daemon.base.paused {
+            return Err(DaemonPaused {
+                daemon_id: daemon.id,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// The discovery protocol to shape `request` for on `daemon`, or [`DaemonProtocolUnsupported`]
+    /// if the daemon is too old for any shape of it
+    pub fn negotiate_protocol(
+        &self,
+        daemon: &Daemon,
+        request: &DaemonDiscoveryRequest,
+    ) -> Result<u32> {
+        let version = daemon.base.capabilities.protocol_version();
+        let minimum = request.min_protocol_version();
+        if version < minimum {
+            return Err(DaemonProtocolUnsupported {
+                daemon_id: daemon.id,
+                version,
+                minimum,
+            }
+            .into());
+        }
+        Ok(version.min(DAEMON_PROTOCOL_VERSION))
+    }
+
+    /// Write what a heartbeat reports for `daemon`: when it was seen, its version, capabilities
+    /// and system metrics. Nothing else is written, so a pause, transfer or auth flag set while
+    /// the heartbeat's results were being ingested is kept. None if the daemon has since been
+    /// deleted or moved to another network.
+    pub async fn save_heartbeat(&self, daemon: &Daemon) -> Result<Option<Daemon>> {
+        let updated = self
+            .daemon_storage
+            .update_where(
+                EntityFilter::unfiltered()
+                    .entity_id(&daemon.id)
+                    .network_ids(&[daemon.base.network_id]),
+                vec![
+                    ("last_seen", SqlValue::Timestamp(daemon.base.last_seen)),
+                    (
+                        "version",
+                        SqlValue::OptionalString(daemon.base.version.clone()),
+                    ),
+                    (
+                        "capabilities",
+                        SqlValue::DaemonCapabilities(daemon.base.capabilities.clone()),
+                    ),
+                    (
+                        "system_metrics",
+                        SqlValue::Json(serde_json::to_value(&daemon.base.system_metrics)?),
+                    ),
+                ],
+            )
+            .await?;
+
+        Ok(updated.into_iter().next())
+    }
+
+    /// Count a heartbeat at `at` towards its bucket. `previous` is when the daemon was last seen
+    /// before it, as stored, so gaps spanning a server restart are still measured.
+    pub fn record_heartbeat(&self, daemon: &Daemon, previous: DateTime<Utc>, at: DateTime<Utc>) {
+        let mut pending = lock(&self.pending_metrics);
+        pending
+            .bucket(daemon.id, daemon.base.network_id, at)
+            .add_heartbeat(at, Some(previous));
+    }
+
+    /// Count system metrics a heartbeat reported towards the bucket it arrived in
+    pub fn record_system_metrics(
+        &self,
+        daemon: &Daemon,
+        metrics: &DaemonSystemMetrics,
+        at: DateTime<Utc>,
+    ) {
+        let mut pending = lock(&self.pending_metrics);
+        pending
+            .bucket(daemon.id, daemon.base.network_id, at)
+            .add_system_metrics(metrics);
+    }
+
+    /// Count a finished discovery session towards the bucket it finished in
+    pub fn record_discovery_session(
+        &self,
+        daemon_id: Uuid,
+        network_id: Uuid,
+        started_at: DateTime<Utc>,
+        finished_at: DateTime<Utc>,
+    ) {
+        let mut pending = lock(&self.pending_metrics);
+        pending
+            .bucket(daemon_id, network_id, finished_at)
+            .add_session(finished_at - started_at);
+    }
+
+    /// Add everything gathered since the last flush to the stored rollups, including buckets
+    /// still in progress, so a restart loses at most one flush interval. Rollups older than the
+    /// retention period are then deleted. Run periodically by the server; returns the number of
+    /// buckets written.
+    pub async fn flush_metrics(&self, now: DateTime<Utc>) -> Result<usize> {
+        let pending: Vec<DaemonMetricRollupBase> = {
+            let mut pending = lock(&self.pending_metrics);
+            pending.buckets.drain().map(|(_, rollup)| rollup).collect()
+        };
+
+        let mut written = 0;
+        let mut remaining = pending.into_iter();
+        while let Some(rollup) = remaining.next() {
+            if let Err(e) = self.store_rollup(&rollup).await {
+                // Keep what wasn't written for the next run, merging with anything since
+                let mut pending = lock(&self.pending_metrics);
+                for rollup in std::iter::once(rollup).chain(remaining) {
+                    pending
+                        .buckets
+                        .entry((rollup.daemon_id, rollup.bucket_start))
+                        .and_modify(|newer| newer.merge(&rollup))
+                        .or_insert(rollup);
+                }
+                return Err(e);
+            }
+            written += 1;
+        }
+
+        if let Some(retention) = self.metrics_retention {
+            let pruned = self
+                .metric_storage
+                .delete_where(EntityFilter::unfiltered().bucket_start_before(now - retention))
+                .await?;
+            if pruned > 0 {
+                tracing::debug!(pruned, "Pruned daemon metric rollups past retention");
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// Add a rollup to the stored row for its bucket in one statement, so concurrent flushes,
+    /// e.g. from several server instances, can't race each other into the unique constraint
+    async fn store_rollup(&self, rollup: &DaemonMetricRollupBase) -> Result<()> {
+        self.metric_storage
+            .upsert(
+                &DaemonMetricRollup::new(rollup.clone()),
+                &["daemon_id", "bucket_start"],
+                &ROLLUP_MERGE,
+            )
+            .await
+    }
+
+    /// A daemon's heartbeat regularity and discovery throughput over `window`, including the
+    /// buckets not rolled up yet
+    pub async fn get_daemon_metrics(
+        &self,
+        daemon_id: &Uuid,
+        window: DaemonMetricsWindow,
+    ) -> Result<DaemonMetrics> {
+        let from = window.start();
+
+        let mut rollups: Vec<DaemonMetricRollupBase> = self
+            .metric_storage
+            .get_all(
+                EntityFilter::unfiltered()
+                    .daemon_id(daemon_id)
+                    .bucket_start_between(from, window.to),
+            )
+            .await?
+            .into_iter()
+            .map(|rollup| rollup.base)
+            .collect();
+
+        rollups.extend(
+            lock(&self.pending_metrics)
+                .buckets
+                .values()
+                .filter(|r| {
+                    r.daemon_id == *daemon_id
+                        && r.bucket_start >= from
+                        && r.bucket_start < window.to
+                })
+                .cloned(),
+        );
+
+        Ok(DaemonMetrics {
+            daemon_id: *daemon_id,
+            buckets: metrics_buckets(&rollups, &window),
+            from: window.from,
+            to: window.to,
+            resolution_secs: window.resolution_secs,
+            note: window.note,
+        })
+    }
+
+    pub fn set_scan_guard(&self, guard: ScanTargetGuard) -> Result<(), ScanTargetGuard> {
+        self.scan_guard.set(guard)
+    }
+
+    /// Validate a discovery's scan targets against the scan target policy, returning the
+    /// (possibly clipped) discovery type to run
+    pub async fn check_scan_targets(
+        &self,
+        daemon: &Daemon,
+        discovery_type: DiscoveryType,
+    ) -> Result<DiscoveryType> {
+        match self.scan_guard.get() {
+            Some(guard) => guard.check(daemon, discovery_type).await,
+            None => Ok(discovery_type),
         }
     }
 
+    /// Subnets a network discovery of `subnet_ids` would scan on `daemon`
+    pub async fn scan_target_subnets(
+        &self,
+        daemon: &Daemon,
+        subnet_ids: Option<Vec<Uuid>>,
+    ) -> Result<Vec<Subnet>> {
+        match self.scan_guard.get() {
+            Some(guard) => guard.target_subnets(daemon, subnet_ids).await,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn set_transfers(&self, transfers: DaemonTransfers) -> Result<(), DaemonTransfers> {
+        self.transfers.set(transfers)
+    }
+
+    /// Move a daemon to `target_network_id` on behalf of `actor_id`. The daemon, its own host and
+    /// that host's services, and its discovery configs always move; with `move_data` so do the
+    /// hosts it discovered, their services and its past discovery results. All of it, and the
+    /// audit trail entries, is written in one transaction so a failure part way moves nothing.
+    /// Subnets are shared between daemons and stay where they are, and the daemon needs an API
+    /// key for the target network to keep reporting.
+    pub async fn transfer_ownership(
+        &self,
+        daemon_id: &Uuid,
+        target_network_id: &Uuid,
+        move_data: bool,
+        actor_id: Uuid,
+    ) -> Result<DaemonTransfer> {
+        let transfers = self
+            .transfers
+            .get()
+            .ok_or_else(|| anyhow!("Daemon transfers are not configured"))?;
+
+        let daemon = self
+            .get_by_id(daemon_id)
+            .await?
+            .ok_or_else(|| anyhow!("Could not find daemon {}", daemon_id))?;
+        let from_network_id = daemon.base.network_id;
+        if from_network_id == *target_network_id {
+            anyhow::bail!(
+                "Daemon {} is already in network {}",
+                daemon_id,
+                target_network_id
+            );
+        }
+        let from_network = transfers
+            .network_storage
+            .get_by_id(&from_network_id)
+            .await?
+            .ok_or_else(|| anyhow!("Could not find network {}", from_network_id))?;
+        let to_network = transfers
+            .network_storage
+            .get_by_id(target_network_id)
+            .await?
+            .ok_or_else(|| anyhow!("Could not find network {}", target_network_id))?;
+
+        let mut sessions_cancelled = Vec::new();
+        let active = transfers
+            .discovery_service
+            .get_sessions_for_daemon(daemon_id)
+            .await
+            .into_iter()
+            .filter(|s| {
+                !matches!(
+                    s.phase,
+                    DiscoveryPhase::Complete
+                        | DiscoveryPhase::Failed
+                        | DiscoveryPhase::Cancelled
+                        | DiscoveryPhase::CancelledWithPartialResults
+                )
+            });
+        for session in active {
+            match transfers.on_active_discovery {
+                ActiveDiscoveryOnTransfer::Block => {
+                    return Err(DaemonTransferBlocked {
+                        daemon_id: *daemon_id,
+                        session_id: session.session_id,
+                    }
+                    .into());
+                }
+                ActiveDiscoveryOnTransfer::Cancel => {
+                    transfers
+                        .discovery_service
+                        .cancel_session(
+                            session.session_id,
+                            SessionActor::User { user_id: actor_id },
+                        )
+                        .await?;
+                    sessions_cancelled.push(session.session_id);
+                }
+            }
+        }
+
+        let source = EntityFilter::unfiltered().network_ids(&[from_network_id]);
+        let mut hosts: Vec<Host> = transfers
+            .host_storage
+            .get_all(source.clone())
+            .await?
+            .into_iter()
+            .filter(|h| h.id == daemon.base.host_id || (move_data && discovered_by(h, daemon.id)))
+            .collect();
+        let host_ids: HashSet<Uuid> = hosts.iter().map(|h| h.id).collect();
+        let mut services: Vec<Service> = transfers
+            .service_storage
+            .get_all(source)
+            .await?
+            .into_iter()
+            .filter(|s| host_ids.contains(&s.base.host_id))
+            .collect();
+        let mut discoveries: Vec<Discovery> = transfers
+            .discovery_storage
+            .get_all(EntityFilter::unfiltered().daemon_id(daemon_id))
+            .await?
+            .into_iter()
+            .filter(|d| d.base.network_id == from_network_id)
+            .filter(|d| move_data || !matches!(d.base.run_type, RunType::Historical { .. }))
+            .collect();
+
+        let mut tx = self.daemon_storage.begin().await?;
+
+        // Only the network is written, and only if nothing else moved the daemon meanwhile
+        let daemon = self
+            .daemon_storage
+            .update_where_in(
+                &mut tx,
+                EntityFilter::unfiltered()
+                    .entity_id(daemon_id)
+                    .network_ids(&[from_network_id]),
+                vec![("network_id", SqlValue::Uuid(*target_network_id))],
+            )
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Daemon {} was moved by another transfer", daemon_id))?;
+        for host in &mut hosts {
+            host.base.network_id = *target_network_id;
+            transfers.host_storage.update_in(&mut tx, host).await?;
+        }
+        for service in &mut services {
+            service.base.network_id = *target_network_id;
+            transfers
+                .service_storage
+                .update_in(&mut tx, service)
+                .await?;
+        }
+        for discovery in &mut discoveries {
+            discovery.base.network_id = *target_network_id;
+            transfers
+                .discovery_storage
+                .update_in(&mut tx, discovery)
+                .await?;
+        }
+
+        let transfer = DaemonTransfer {
+            daemon,
+            from_network_id,
+            to_network_id: *target_network_id,
+            hosts_moved: hosts.len(),
+            services_moved: services.len(),
+            discoveries_moved: discoveries.len(),
+            sessions_cancelled,
+        };
+        let details = serde_json::json!({
+            "daemon_id": daemon_id,
+            "from_network_id": transfer.from_network_id,
+            "to_network_id": transfer.to_network_id,
+            "move_data": move_data,
+            "hosts_moved": transfer.hosts_moved,
+            "services_moved": transfer.services_moved,
+            "discoveries_moved": transfer.discoveries_moved,
+            "sessions_cancelled": transfer.sessions_cancelled,
+        });
+        let mut organization_ids = vec![to_network.base.organization_id];
+        if from_network.base.organization_id != to_network.base.organization_id {
+            organization_ids.push(from_network.base.organization_id);
+        }
+        for organization_id in organization_ids {
+            transfers
+                .audit_service
+                .record_in(
+                    &mut tx,
+                    AuditEventBase {
+                        organization_id,
+                        actor_id,
+                        impersonated_user_id: None,
+                        kind: AuditEventKind::DaemonTransferred,
+                        details: details.clone(),
+                    },
+                )
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        // Scheduled runs captured the old network when they were scheduled
+        for discovery in discoveries {
+            if matches!(
+                discovery.base.run_type,
+                RunType::Scheduled { enabled: true, .. }
+            ) && let Err(e) = transfers
+                .discovery_service
+                .update_discovery(discovery)
+                .await
+            {
+                tracing::error!(
+                    daemon_id = %daemon_id,
+                    "Failed to reschedule discovery after transfer: {}",
+                    e
+                );
+            }
+        }
+
+        tracing::info!(
+            daemon_id = %daemon_id,
+            from_network_id = %transfer.from_network_id,
+            to_network_id = %transfer.to_network_id,
+            hosts_moved = transfer.hosts_moved,
+            services_moved = transfer.services_moved,
+            discoveries_moved = transfer.discoveries_moved,
+            "Daemon transferred"
+        );
+        Ok(transfer)
+    }
+
     /// Send discovery request to daemon
     pub async fn send_discovery_request(
         &self,
         daemon_id: &Uuid,
-        request: DaemonDiscoveryRequest,
+        mut request: DaemonDiscoveryRequest,
     ) -> Result<(), Error> {
         let daemon = self
             .get_by_id(daemon_id)
             .await?
             .ok_or_else(|| anyhow::anyhow!("Could not find daemon {}", daemon_id))?;
+        self.check_not_paused(&daemon)?;
+        let session_id = request.session_id;
 
-        let endpoint = Endpoint {
-            ip: Some(daemon.base.ip),
-            port_base: PortBase::new_tcp(daemon.base.port),
-            protocol: ApplicationProtocol::Http,
-            path: "/api/discovery/initiate".to_string(),
-        };
+        // Last line of defence: nothing reaches a daemon without passing the target policy
+        request.discovery_type = self
+            .check_scan_targets(&daemon, request.discovery_type)
+            .await?;
+        let protocol_version = self.negotiate_protocol(&daemon, &request)?;
 
         let response = self
-            .client
-            .post(format!("{}", endpoint))
-            .json(&request)
+            .daemon_request(
+                &daemon,
+                reqwest::Method::POST,
+                "/api/discovery/initiate",
+                &request.for_protocol(protocol_version)?,
+            )?
             .send()
             .await?;
 
+        self.check_daemon_auth(&daemon, response.status()).await?;
         if !response.status().is_success() {
             anyhow::bail!(
                 "Failed to send discovery request: HTTP {}",
@@ -80,10 +805,34 @@ impl DaemonService {
             );
         }
 
+        let answered_version = api_response
+            .data
+            .and_then(|data| data.protocol_version)
+            .unwrap_or(LEGACY_DAEMON_PROTOCOL_VERSION);
+        if answered_version != protocol_version {
+            // Older daemons start whatever they're sent, so stop the scan the server is about to
+            // write off
+            if let Err(e) = self.send_discovery_cancellation(&daemon, session_id).await {
+                tracing::warn!(
+                    daemon_id = %daemon.id,
+                    session_id = %session_id,
+                    "Failed to cancel discovery sent in the wrong protocol: {}",
+                    e
+                );
+            }
+            anyhow::bail!(
+                "Daemon {} answered discovery request in protocol {} but was sent protocol {}; \
+                 it may have changed version since it last reported capabilities",
+                daemon.id,
+                answered_version,
+                protocol_version
+            );
+        }
+
         tracing::info!(
             "Discovery request sent to daemon {} for session {}",
             daemon.id,
-            request.session_id
+            session_id
         );
         Ok(())
     }
@@ -93,17 +842,13 @@ impl DaemonService {
         daemon: &Daemon,
         session_id: Uuid,
     ) -> Result<(), anyhow::Error> {
-        let endpoint = Endpoint {
-            ip: Some(daemon.base.ip),
-            port_base: PortBase::new_tcp(daemon.base.port),
-            protocol: ApplicationProtocol::Http,
-            path: "/api/discovery/cancel".to_string(),
-        };
-
         let response = self
-            .client
-            .post(format!("{}", endpoint))
-            .json(&session_id)
+            .daemon_request(
+                daemon,
+                reqwest::Method::POST,
+                "/api/discovery/cancel",
+                &session_id,
+            )?
             .send()
             .await?;
 
@@ -118,6 +863,318 @@ impl DaemonService {
         Ok(())
     }
 
+    /// Check end to end that a daemon is usable: the server can reach it, it can authenticate
+    /// back to the server, and it can run a trivial scan. Stops at the first failing step.
+    pub async fn test_connectivity(&self, daemon: &Daemon) -> DaemonTestReport {
+        let mut steps = Vec::new();
+
+        for step in [
+            DaemonTestStep::Health,
+            DaemonTestStep::Auth,
+            DaemonTestStep::Discovery,
+        ] {
+            let started = Instant::now();
+            let result = match step {
+                DaemonTestStep::Health => {
+                    self.call_daemon::<String, ()>(
+                        daemon,
+                        reqwest::Method::GET,
+                        "/api/health",
+                        None,
+                    )
+                    .await
+                }
+                DaemonTestStep::Auth => self
+                    .call_daemon::<(), ()>(
+                        daemon,
+                        reqwest::Method::POST,
+                        "/api/self-test/auth",
+                        None,
+                    )
+                    .await
+                    .map(|_| None),
+                DaemonTestStep::Discovery => self
+                    .call_daemon::<serde_json::Value, _>(
+                        daemon,
+                        reqwest::Method::POST,
+                        "/api/self-test/probe",
+                        Some(&DaemonProbeRequest {
+                            port: daemon.base.port,
+                        }),
+                    )
+                    .await
+                    .map(|_| Some(format!("Port {} reachable from daemon", daemon.base.port))),
+            };
+
+            let success = result.is_ok();
+            steps.push(DaemonTestStepResult {
+                step,
+                success,
+                duration_ms: started.elapsed().as_millis() as u64,
+                message: result.unwrap_or_else(|e| Some(e.to_string())),
+            });
+
+            if !success {
+                tracing::warn!(
+                    daemon_id = %daemon.id,
+                    step = ?step,
+                    "Daemon connectivity test failed"
+                );
+                break;
+            }
+        }
+
+        let failed_step = steps.iter().find(|s| !s.success).map(|s| s.step);
+
+        DaemonTestReport {
+            daemon_id: daemon.id,
+            success: failed_step.is_none(),
+            failed_step,
+            steps,
+        }
+    }
+
+    /// The scan target policy to send along with a network check, for the daemon to enforce too
+    fn scan_policy(&self) -> Option<ScanTargetPolicy> {
+        self.scan_guard.get().map(|guard| guard.policy().clone())
+    }
+
+    /// Have `daemon` ARP scan a range on one of its local segments. The range must be permitted
+    /// by the scan target policy.
+    pub async fn arp_scan(
+        &self,
+        daemon: &Daemon,
+        request: &DaemonArpScanRequest,
+    ) -> Result<ArpScanResult> {
+        if let Some(guard) = self.scan_guard.get() {
+            guard.check_cidr(&IpCidr::V4(request.cidr))?;
+        }
+
+        let request = DaemonArpScanRequest {
+            scan_policy: self.scan_policy(),
+            ..request.clone()
+        };
+        self.call_daemon(
+            daemon,
+            reqwest::Method::POST,
+            "/api/network-checks/arp-scan",
+            Some(&request),
+        )
+        .await?
+        .ok_or_else(|| anyhow!("Daemon {} returned no ARP scan result", daemon.id))
+    }
+
+    /// Have `daemon` find the IPv6 neighbors on one of its local segments. A prefix to limit the
+    /// scan to must be permitted by the scan target policy, and neighbors the policy doesn't
+    /// permit are dropped from the result whether or not there is one.
+    pub async fn ndp_scan(
+        &self,
+        daemon: &Daemon,
+        request: &DaemonNdpScanRequest,
+    ) -> Result<NdpScanResult> {
+        if let (Some(guard), Some(prefix)) = (self.scan_guard.get(), request.prefix) {
+            guard.check_cidr(&IpCidr::V6(prefix))?;
+        }
+
+        let request = DaemonNdpScanRequest {
+            scan_policy: self.scan_policy(),
+            ..request.clone()
+        };
+        let mut result: NdpScanResult = self
+            .call_daemon(
+                daemon,
+                reqwest::Method::POST,
+                "/api/network-checks/ndp-scan",
+                Some(&request),
+            )
+            .await?
+            .ok_or_else(|| anyhow!("Daemon {} returned no NDP scan result", daemon.id))?;
+
+        if let Some(policy) = &request.scan_policy {
+            result
+                .neighbors
+                .retain(|n| policy.permits_address(IpAddr::V6(n.ip)));
+        }
+
+        Ok(result)
+    }
+
+    /// Have `daemon` run a check suite. Checks of IP targets must be permitted by the scan target
+    /// policy. Named targets are resolved by the daemon, so it's sent the policy and checks every
+    /// address they resolve to itself.
+    pub async fn run_check_suite(
+        &self,
+        daemon: &Daemon,
+        suite: &SuiteDefinition,
+    ) -> Result<SuiteReport> {
+        if let Some(guard) = self.scan_guard.get() {
+            for ip in suite.checks.iter().filter_map(|m| m.check.ip()) {
+                guard.check_cidr(&IpCidr::new_host(ip))?;
+            }
+        }
+
+        // Each check is bounded by the daemon's check timeout, so a suite run is too
+        let check_timeout = CheckOptions::default().timeout;
+        let timeout = TEST_STEP_TIMEOUT
+            + match suite.execution {
+                SuiteExecution::Sequential => check_timeout * suite.checks.len() as u32,
+                SuiteExecution::Parallel => check_timeout,
+            };
+
+        let request = DaemonSuiteRequest {
+            suite: suite.clone(),
+            scan_policy: self.scan_policy(),
+        };
+        self.call_daemon_with_timeout(
+            daemon,
+            reqwest::Method::POST,
+            "/api/network-checks/suite",
+            Some(&request),
+            timeout,
+        )
+        .await?
+        .ok_or_else(|| anyhow!("Daemon {} returned no check suite report", daemon.id))
+    }
+
+    /// Have `daemon` run a single check, returning its result whether the check passed or not.
+    /// An IP target must be permitted by the scan target policy; a named one is resolved and
+    /// checked against it by the daemon.
+    pub async fn run_check(&self, daemon: &Daemon, check: &SuiteCheck) -> Result<SuiteCheckResult> {
+        if let (Some(guard), Some(ip)) = (self.scan_guard.get(), check.ip()) {
+            guard.check_cidr(&IpCidr::new_host(ip))?;
+        }
+
+        let timeout = TEST_STEP_TIMEOUT + CheckOptions::default().timeout;
+        let request = DaemonCheckRequest {
+            check: check.clone(),
+            scan_policy: self.scan_policy(),
+        };
+        self.call_daemon_with_timeout(
+            daemon,
+            reqwest::Method::POST,
+            "/api/network-checks/check",
+            Some(&request),
+            timeout,
+        )
+        .await
+        .map_err(|e| match e.downcast_ref::<reqwest::Error>() {
+            Some(error) if error.is_timeout() => DaemonCheckTimedOut {
+                daemon_id: daemon.id,
+                timeout,
+            }
+            .into(),
+            _ => e,
+        })?
+        .ok_or_else(|| anyhow!("Daemon {} returned no check result", daemon.id))
+    }
+
+    /// Call a daemon endpoint, failing unless it responds with a successful `ApiResponse`
+    async fn call_daemon<T: DeserializeOwned, B: Serialize>(
+        &self,
+        daemon: &Daemon,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<Option<T>> {
+        self.call_daemon_with_timeout(daemon, method, path, body, TEST_STEP_TIMEOUT)
+            .await
+    }
+
+    async fn call_daemon_with_timeout<T: DeserializeOwned, B: Serialize>(
+        &self,
+        daemon: &Daemon,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<&B>,
+        timeout: Duration,
+    ) -> Result<Option<T>> {
+        self.check_not_paused(daemon)?;
+
+        let response = self
+            .daemon_request(daemon, method, path, &body)?
+            .timeout(timeout)
+            .send()
+            .await?;
+        let status = response.status();
+        self.check_daemon_auth(daemon, status).await?;
+        let api_response: ApiResponse<T> = response
+            .json()
+            .await
+            .map_err(|_| anyhow!("Daemon returned HTTP {} with an unreadable body", status))?;
+
+        if !status.is_success() || !api_response.success {
+            return Err(anyhow!(
+                "HTTP {}: {}",
+                status,
+                api_response
+                    .error
+                    .unwrap_or_else(|| "Unknown error".to_string())
+            ));
+        }
+
+        Ok(api_response.data)
+    }
+
+    /// A request to `path` on `daemon`, signed with its server key so the daemon knows it came
+    /// from this server. Daemons that haven't been issued a key yet get it unsigned, which they
+    /// reject until their next heartbeat hands them one.
+    fn daemon_request<B: Serialize + ?Sized>(
+        &self,
+        daemon: &Daemon,
+        method: reqwest::Method,
+        path: &str,
+        body: &B,
+    ) -> Result<reqwest::RequestBuilder> {
+        let endpoint = Endpoint {
+            ip: Some(daemon.base.ip),
+            port_base: PortBase::new_tcp(daemon.base.port),
+            protocol: ApplicationProtocol::Http,
+            path: path.to_string(),
+        };
+
+        let request = self.client.request(method, format!("{}", endpoint));
+        match &daemon.base.server_key {
+            Some(server_key) => request.signed_json(server_key, body),
+            None => Ok(request.json(body)),
+        }
+    }
+
+    /// The key the server signs its requests to `daemon` with, issuing one if it has none yet.
+    /// Only the first of several racing issuers gets to write it, so the daemon is never handed
+    /// a key that's then replaced.
+    pub async fn server_key(&self, daemon: &Daemon) -> Result<String> {
+        if let Some(server_key) = &daemon.base.server_key {
+            return Ok(server_key.clone());
+        }
+
+        let issued = self
+            .daemon_storage
+            .update_where(
+                EntityFilter::unfiltered()
+                    .entity_id(&daemon.id)
+                    .without_server_key(),
+                vec![(
+                    "server_key",
+                    SqlValue::String(Uuid::new_v4().simple().to_string()),
+                )],
+            )
+            .await?
+            .into_iter()
+            .next();
+        let daemon = match issued {
+            Some(daemon) => daemon,
+            None => self
+                .get_by_id(&daemon.id)
+                .await?
+                .ok_or_else(|| anyhow!("Could not find daemon {}", daemon.id))?,
+        };
+
+        daemon
+            .base
+            .server_key
+            .ok_or_else(|| anyhow!("Daemon {} has no server key", daemon.id))
+    }
+
     pub async fn initialize_local_daemon(
         &self,
         daemon_url: String,
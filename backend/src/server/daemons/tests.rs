@@ -0,0 +1,1640 @@
+use axum::{
+    Json, Router,
+    body::{Body, to_bytes},
+    extract::ConnectInfo,
+    http::{Request, StatusCode, header},
+    middleware,
+    routing::{get, post},
+};
+use chrono::{Duration, Utc};
+use email_address::EmailAddress;
+use serial_test::serial;
+use sqlx::PgPool;
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+};
+use tower::Service;
+use uuid::Uuid;
+
+use crate::{
+    daemon::utils::network_checks::{
+        suite::{SuiteCheck, SuiteCheckResult, SuiteMember, SuiteStatus},
+        types::CheckOutcome,
+    },
+    server::{
+        api_keys::r#impl::base::{ApiKey, ApiKeyBase},
+        audit::r#impl::base::AuditEventKind,
+        auth::service::hash_password,
+        bootstrap_tokens::r#impl::api::CreateBootstrapTokenRequest,
+        config::{AppState, ServerConfig},
+        daemons::{
+            handlers::process_heartbeat,
+            r#impl::{
+                api::{
+                    DAEMON_PROTOCOL_VERSION, DaemonCheckRequest, DaemonDiscoveryRequest,
+                    DaemonHeartbeatRequest, DaemonHeartbeatResponse, DaemonRegistrationResponse,
+                    DaemonTestStep, HeartbeatResultsPolicy,
+                },
+                base::{
+                    Daemon, DaemonAuthFailed, DaemonMode, DaemonPaused, DaemonProtocolUnsupported,
+                    deserialize_labels, parse_labels,
+                },
+                metrics::{
+                    DaemonInterfaceStats, DaemonMetricsBucket, DaemonMetricsQuery,
+                    DaemonSystemMetrics,
+                },
+            },
+            service::DaemonService,
+        },
+        discovery::r#impl::types::{DiscoveryType, HostNamingFallback, ScanRate},
+        hosts::r#impl::api::HostWithServicesRequest,
+        notifications::r#impl::base::NotificationKind,
+        secrets::r#impl::base::SecretValue,
+        shared::{
+            fields::{UnknownFields, sparse_fields},
+            handlers::factory::create_router,
+            services::factory::ServiceFactory,
+            services::traits::CrudService,
+            storage::{
+                factory::StorageFactory,
+                filter::EntityFilter,
+                generic::GenericPostgresStorage,
+                traits::{StorableEntity, Storage},
+            },
+            types::api::{ApiError, ApiResponse},
+            types::entities::{DiscoveryMetadata, EntitySource},
+        },
+        users::r#impl::permissions::UserOrgPermissions,
+    },
+    tests::*,
+};
+
+/// Daemon stand-in serving the connectivity test endpoints, optionally rejecting the auth step
+async fn spawn_mock_daemon(auth_ok: bool) -> u16 {
+    let app = Router::new()
+        .route(
+            "/api/health",
+            get(|| async { Json(ApiResponse::success("Netvisor Daemon Running".to_string())) }),
+        )
+        .route(
+            "/api/self-test/auth",
+            post(move || async move {
+                if auth_ok {
+                    Ok(Json(ApiResponse::success(())))
+                } else {
+                    Err(ApiError::bad_gateway(
+                        "Server rejected API key: HTTP 401 Unauthorized: Invalid API key",
+                    ))
+                }
+            }),
+        )
+        .route(
+            "/api/self-test/probe",
+            post(|| async { Json(ApiResponse::success(())) }),
+        )
+        .route(
+            "/api/network-checks/check",
+            post(|Json(request): Json<DaemonCheckRequest>| async move {
+                let check = request.check;
+                let target = check.target();
+                Json(ApiResponse::success(SuiteCheckResult::new(
+                    &SuiteMember {
+                        check,
+                        advisory: false,
+                    },
+                    Ok(CheckOutcome {
+                        target,
+                        latency_ms: 3,
+                        proxied: false,
+                        detail: None,
+                        source: Some("10.0.0.5:41000".parse().unwrap()),
+                        expires_at: None,
+                    }),
+                )))
+            }),
+        );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    port
+}
+
+fn daemon_service() -> DaemonService {
+    // Connectivity tests only talk to the daemon, so the pool is never used
+    let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+    DaemonService::new(
+        Arc::new(GenericPostgresStorage::new(pool.clone())),
+        Arc::new(GenericPostgresStorage::new(pool)),
+        None,
+    )
+}
+
+#[tokio::test]
+async fn test_discovery_request_shaped_for_daemon_protocol() {
+    let service = daemon_service();
+    let request = DaemonDiscoveryRequest {
+        session_id: Uuid::new_v4(),
+        discovery_type: DiscoveryType::Network {
+            subnet_ids: None,
+            host_naming_fallback: HostNamingFallback::default(),
+            scan_rate: ScanRate::Adaptive {
+                initial: 4,
+                max: 32,
+            },
+            snmp_credential: Some("core-switches".to_string()),
+            targets: Vec::new(),
+        },
+        secrets: [(
+            "core-switches".to_string(),
+            SecretValue::SnmpCommunity {
+                community: "s3cret".to_string(),
+            },
+        )]
+        .into(),
+        protocol_version: None,
+    };
+
+    // A daemon that never reported a protocol can't be handed secrets, so it's turned away
+    // before the session starts rather than scanning with the default community
+    let mut daemon = daemon(&Uuid::new_v4(), &Uuid::new_v4());
+    let err = service.negotiate_protocol(&daemon, &request).unwrap_err();
+    assert!(err.is::<DaemonProtocolUnsupported>());
+
+    // Without secrets it gets none of the newer-only fields
+    let mut plain = request.clone();
+    plain.secrets.clear();
+    if let DiscoveryType::Network {
+        snmp_credential, ..
+    } = &mut plain.discovery_type
+    {
+        *snmp_credential = None;
+    }
+    let legacy = service.negotiate_protocol(&daemon, &plain).unwrap();
+    let body = plain.for_protocol(legacy).unwrap();
+    for field in ["secrets", "protocol_version"] {
+        assert!(body.get(field).is_none(), "{} sent to legacy daemon", field);
+    }
+    for field in ["scan_rate", "snmp_credential"] {
+        assert!(body["discovery_type"].get(field).is_none());
+    }
+    assert_eq!(body["discovery_type"]["type"], "Network");
+    assert!(serde_json::from_value::<DaemonDiscoveryRequest>(body).is_ok());
+
+    // A current daemon gets the full request, tagged with the protocol it's shaped for
+    daemon.base.capabilities.protocol_version = Some(DAEMON_PROTOCOL_VERSION);
+    let current = service.negotiate_protocol(&daemon, &request).unwrap();
+    let body = request.clone().for_protocol(current).unwrap();
+    assert_eq!(body["protocol_version"], DAEMON_PROTOCOL_VERSION);
+    assert_eq!(body["discovery_type"]["snmp_credential"], "core-switches");
+    assert_eq!(body["discovery_type"]["scan_rate"]["mode"], "adaptive");
+    assert!(body["secrets"].get("core-switches").is_some());
+
+    // One too old for any shape is turned away before anything is sent
+    daemon.base.capabilities.protocol_version = Some(0);
+    let err = service.negotiate_protocol(&daemon, &request).unwrap_err();
+    assert!(err.is::<DaemonProtocolUnsupported>());
+    assert!(err.to_string().contains("Upgrade the daemon"));
+}
+
+#[tokio::test]
+async fn test_daemon_connectivity_all_steps_pass() {
+    let port = spawn_mock_daemon(true).await;
+    let mut daemon = daemon(&Uuid::new_v4(), &Uuid::new_v4());
+    daemon.base.ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    daemon.base.port = port;
+
+    let report = daemon_service().test_connectivity(&daemon).await;
+
+    assert!(report.success, "{:?}", report);
+    assert_eq!(report.failed_step, None);
+    assert_eq!(
+        report.steps.iter().map(|s| s.step).collect::<Vec<_>>(),
+        vec![
+            DaemonTestStep::Health,
+            DaemonTestStep::Auth,
+            DaemonTestStep::Discovery
+        ]
+    );
+    assert!(report.steps.iter().all(|s| s.success));
+}
+
+#[tokio::test]
+async fn test_single_check_returns_daemon_result() {
+    let port = spawn_mock_daemon(true).await;
+    let mut daemon = daemon(&Uuid::new_v4(), &Uuid::new_v4());
+    daemon.base.ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    daemon.base.port = port;
+
+    let check = SuiteCheck::Tcp {
+        host: "10.0.0.20".to_string(),
+        port: 5432,
+    };
+    let result = daemon_service().run_check(&daemon, &check).await.unwrap();
+
+    assert_eq!(result.member.check, check);
+    assert_eq!(result.status, SuiteStatus::Pass);
+    assert_eq!(result.error, None);
+    let outcome = result.outcome.unwrap();
+    assert_eq!(outcome.target, "10.0.0.20:5432");
+    assert_eq!(outcome.source, Some("10.0.0.5:41000".parse().unwrap()));
+}
+
+#[tokio::test]
+async fn test_paused_daemon_is_not_dispatched_to_until_resumed() {
+    let port = spawn_mock_daemon(true).await;
+    let mut daemon = daemon(&Uuid::new_v4(), &Uuid::new_v4());
+    daemon.base.ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    daemon.base.port = port;
+    daemon.base.paused = true;
+
+    let service = daemon_service();
+    let check = SuiteCheck::Tcp {
+        host: "10.0.0.20".to_string(),
+        port: 5432,
+    };
+    let err = service.run_check(&daemon, &check).await.unwrap_err();
+    let paused = err.downcast_ref::<DaemonPaused>().unwrap();
+    assert_eq!(paused.daemon_id, daemon.id);
+    assert!(err.to_string().contains("paused"));
+
+    daemon.base.paused = false;
+    let result = service.run_check(&daemon, &check).await.unwrap();
+    assert_eq!(result.status, SuiteStatus::Pass);
+}
+
+#[tokio::test]
+async fn test_daemon_connectivity_stops_at_failed_auth() {
+    let port = spawn_mock_daemon(false).await;
+    let mut daemon = daemon(&Uuid::new_v4(), &Uuid::new_v4());
+    daemon.base.ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    daemon.base.port = port;
+
+    let report = daemon_service().test_connectivity(&daemon).await;
+
+    assert!(!report.success);
+    assert_eq!(report.failed_step, Some(DaemonTestStep::Auth));
+    // Discovery never ran
+    assert_eq!(report.steps.len(), 2);
+    assert!(report.steps[0].success);
+
+    let auth = &report.steps[1];
+    assert!(!auth.success);
+    assert!(
+        auth.message
+            .as_deref()
+            .is_some_and(|m| m.contains("Invalid API key")),
+        "{:?}",
+        auth.message
+    );
+}
+
+/// Daemon stand-in that refuses discovery requests with `status`
+async fn spawn_rejecting_daemon(status: StatusCode) -> u16 {
+    let app = Router::new().route(
+        "/api/discovery/initiate",
+        post(move || async move {
+            (
+                status,
+                Json(ApiResponse::<()>::error("Invalid credentials".to_string())),
+            )
+        }),
+    );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    port
+}
+
+#[tokio::test]
+#[serial]
+async fn test_daemons_filtered_by_labels() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let daemon_host = services
+        .host_service
+        .create(host(&network.id))
+        .await
+        .unwrap();
+
+    let mut ids = Vec::new();
+    for labels in ["site=nyc,env=prod", "site=nyc,env=dev", "site=lon,env=prod"] {
+        let mut daemon = daemon(&network.id, &daemon_host.id);
+        daemon.base.labels = parse_labels(labels).unwrap();
+        ids.push(services.daemon_service.create(daemon).await.unwrap().id);
+    }
+
+    let matching = |labels: &str| {
+        let daemon_service = services.daemon_service.clone();
+        let labels = parse_labels(labels).unwrap();
+        async move {
+            let mut found: Vec<Uuid> = daemon_service
+                .get_all(EntityFilter::unfiltered().labels(&labels))
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|d| d.id)
+                .collect();
+            found.sort();
+            found
+        }
+    };
+    let sorted = |mut ids: Vec<Uuid>| {
+        ids.sort();
+        ids
+    };
+
+    assert_eq!(matching("site=nyc").await, sorted(vec![ids[0], ids[1]]));
+    // Keys are normalized, and multiple labels must all match
+    assert_eq!(matching(" Site =nyc,ENV=prod").await, vec![ids[0]]);
+    // Values only match exactly
+    assert!(matching("site=NYC").await.is_empty());
+    assert!(matching("site=ny*").await.is_empty());
+}
+
+#[test]
+fn test_label_keys_colliding_after_normalizing_rejected() {
+    // They would otherwise silently overwrite each other
+    assert!(parse_labels("site=nyc, SITE=lon").is_err());
+    assert!(parse_labels("site=nyc,site=lon").is_err());
+    assert!(deserialize_labels(serde_json::json!({ "site": "nyc", "Site ": "lon" })).is_err());
+
+    let labels = deserialize_labels(serde_json::json!({ " Site": "nyc", "env": "prod" })).unwrap();
+    assert_eq!(labels.get("site").map(String::as_str), Some("nyc"));
+    assert_eq!(labels.len(), 2);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_daemon_auth_rejection_flags_daemon() {
+    let (_storage, services, _container) = test_services().await;
+    let mut notifications = services.notification_service.subscribe();
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let daemon_host = services
+        .host_service
+        .create(host(&network.id))
+        .await
+        .unwrap();
+
+    let mut daemons = Vec::new();
+    for status in [StatusCode::UNAUTHORIZED, StatusCode::FORBIDDEN] {
+        let mut daemon = daemon(&network.id, &daemon_host.id);
+        daemon.base.ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        daemon.base.port = spawn_rejecting_daemon(status).await;
+        daemons.push(services.daemon_service.create(daemon).await.unwrap());
+    }
+    let (unauthorized, forbidden) = (&daemons[0], &daemons[1]);
+
+    let dispatch = |daemon_id: Uuid| {
+        let daemon_service = services.daemon_service.clone();
+        async move {
+            daemon_service
+                .send_discovery_request(
+                    &daemon_id,
+                    DaemonDiscoveryRequest {
+                        session_id: Uuid::new_v4(),
+                        discovery_type: DiscoveryType::SelfReport {
+                            host_id: daemon_host.id,
+                        },
+                        secrets: Default::default(),
+                        protocol_version: None,
+                    },
+                )
+                .await
+                .unwrap_err()
+        }
+    };
+    let auth_failed_at = |daemon_id: Uuid| {
+        let daemon_service = services.daemon_service.clone();
+        async move {
+            daemon_service
+                .get_by_id(&daemon_id)
+                .await
+                .unwrap()
+                .unwrap()
+                .base
+                .auth_failed_at
+        }
+    };
+
+    // A 401 is unambiguous
+    let error = dispatch(unauthorized.id).await;
+    let failed = error.downcast_ref::<DaemonAuthFailed>().unwrap();
+    assert_eq!(failed.status, 401);
+    assert!(failed.flagged);
+    assert!(auth_failed_at(unauthorized.id).await.is_some());
+    let api_error = ApiError::from(error);
+    assert_eq!(api_error.status, StatusCode::BAD_GATEWAY);
+    assert_eq!(api_error.code, Some(DaemonAuthFailed::CODE));
+
+    match notifications.try_recv().unwrap().kind {
+        NotificationKind::DaemonAuthFailed { daemon_id, status } => {
+            assert_eq!((daemon_id, status), (unauthorized.id, 401));
+        }
+        other => panic!("unexpected notification {:?}", other),
+    }
+
+    // A lone 403 may be transient, so it's reported but only flags once it repeats
+    let error = dispatch(forbidden.id).await;
+    assert!(!error.downcast_ref::<DaemonAuthFailed>().unwrap().flagged);
+    assert!(auth_failed_at(forbidden.id).await.is_none());
+
+    let error = dispatch(forbidden.id).await;
+    assert!(error.downcast_ref::<DaemonAuthFailed>().unwrap().flagged);
+    assert!(auth_failed_at(forbidden.id).await.is_some());
+
+    // Still failing, but only notified once per daemon
+    dispatch(unauthorized.id).await;
+    let notified: Vec<_> = std::iter::from_fn(|| notifications.try_recv().ok())
+        .map(|n| n.kind)
+        .collect();
+    assert_eq!(
+        notified,
+        vec![NotificationKind::DaemonAuthFailed {
+            daemon_id: forbidden.id,
+            status: 403
+        }]
+    );
+}
+
+/// Daemon last seen an hour ago, so a heartbeat visibly moves `last_seen`
+async fn stale_daemon(state: &AppState) -> Daemon {
+    let services = &state.services;
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let daemon_host = services
+        .host_service
+        .create(host(&network.id))
+        .await
+        .unwrap();
+
+    let mut daemon = daemon(&network.id, &daemon_host.id);
+    daemon.base.last_seen = Utc::now() - Duration::hours(1);
+    services.daemon_service.create(daemon).await.unwrap()
+}
+
+fn discovered_host(network_id: &Uuid, name: &str) -> HostWithServicesRequest {
+    let mut host = host(network_id);
+    host.base.name = name.to_string();
+    HostWithServicesRequest {
+        host,
+        services: None,
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_heartbeat_with_results_updates_last_seen_and_ingests() {
+    let (state, _container) = test_app_state().await;
+    let daemon = stale_daemon(&state).await;
+    let network_id = daemon.base.network_id;
+
+    let response = process_heartbeat(
+        &state,
+        daemon.id,
+        network_id,
+        DaemonHeartbeatRequest {
+            version: Some("1.2.3".to_string()),
+            hosts: vec![discovered_host(&network_id, "heartbeat-host")],
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    assert!(response.heartbeat.success);
+    assert!(response.results.unwrap().success);
+
+    let updated = state
+        .services
+        .daemon_service
+        .get_by_id(&daemon.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(updated.base.last_seen > daemon.base.last_seen);
+    assert_eq!(updated.base.version.as_deref(), Some("1.2.3"));
+
+    // A heartbeat saved from a snapshot read before a pause doesn't resume the daemon
+    let service = &state.services.daemon_service;
+    service.set_paused(&updated, true).await.unwrap();
+    let saved = service.save_heartbeat(&updated).await.unwrap().unwrap();
+    assert!(saved.base.paused);
+
+    let hosts = state
+        .services
+        .host_service
+        .get_all(EntityFilter::unfiltered().network_ids(&[network_id]))
+        .await
+        .unwrap();
+    assert!(hosts.iter().any(|h| h.base.name == "heartbeat-host"));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_heartbeat_reports_bad_results_without_losing_heartbeat() {
+    let (state, _container) = test_app_state().await;
+    let daemon = stale_daemon(&state).await;
+    let network_id = daemon.base.network_id;
+
+    // Results for another network are rejected, but the heartbeat is kept
+    let response = process_heartbeat(
+        &state,
+        daemon.id,
+        network_id,
+        DaemonHeartbeatRequest {
+            hosts: vec![discovered_host(&Uuid::new_v4(), "foreign-host")],
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    assert!(response.heartbeat.success);
+    let results = response.results.unwrap();
+    assert!(!results.success);
+    assert!(results.error.unwrap().contains("foreign-host"));
+
+    let updated = state
+        .services
+        .daemon_service
+        .get_by_id(&daemon.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(updated.base.last_seen > daemon.base.last_seen);
+
+    // Asking for all-or-nothing rejects the heartbeat too
+    let err = process_heartbeat(
+        &state,
+        daemon.id,
+        network_id,
+        DaemonHeartbeatRequest {
+            hosts: vec![discovered_host(&Uuid::new_v4(), "foreign-host")],
+            on_results_error: HeartbeatResultsPolicy::RejectAll,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let unchanged = state
+        .services
+        .daemon_service
+        .get_by_id(&daemon.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(unchanged.base.last_seen, updated.base.last_seen);
+
+    // Nothing of a rejected batch is written, even the items ahead of the bad one
+    let mut bad_service = discovered_host(&network_id, "bad-service-host");
+    bad_service.services = Some(vec![service(&Uuid::new_v4(), &bad_service.host.id)]);
+    let err = process_heartbeat(
+        &state,
+        daemon.id,
+        network_id,
+        DaemonHeartbeatRequest {
+            hosts: vec![discovered_host(&network_id, "good-host"), bad_service],
+            on_results_error: HeartbeatResultsPolicy::RejectAll,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let hosts = state
+        .services
+        .host_service
+        .get_all(EntityFilter::unfiltered().network_ids(&[network_id]))
+        .await
+        .unwrap();
+    assert!(!hosts.iter().any(|h| h.base.name == "good-host"));
+}
+
+fn system_metrics(cpu_percent: f64) -> DaemonSystemMetrics {
+    DaemonSystemMetrics {
+        cpu_percent,
+        memory_used_bytes: 2 << 30,
+        memory_total_bytes: 8 << 30,
+        disk_used_bytes: 40 << 30,
+        disk_total_bytes: 100 << 30,
+        interfaces: vec![DaemonInterfaceStats {
+            name: "eth0".to_string(),
+            rx_bytes: 1_000,
+            tx_bytes: 2_000,
+            rx_errors: 0,
+            tx_errors: 0,
+        }],
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_heartbeat_system_metrics_update_latest_snapshot() {
+    let (state, _container) = test_app_state().await;
+    let daemon = stale_daemon(&state).await;
+    let network_id = daemon.base.network_id;
+    let get = || async {
+        state
+            .services
+            .daemon_service
+            .get_by_id(&daemon.id)
+            .await
+            .unwrap()
+            .unwrap()
+    };
+
+    let response = process_heartbeat(
+        &state,
+        daemon.id,
+        network_id,
+        DaemonHeartbeatRequest {
+            system_metrics: Some(system_metrics(42.5)),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert!(response.system_metrics.unwrap().success);
+
+    let reported = get().await;
+    let snapshot = reported.base.system_metrics.clone().unwrap();
+    assert_eq!(snapshot.metrics, system_metrics(42.5));
+    assert_eq!(snapshot.reported_at, reported.base.last_seen);
+
+    // A heartbeat without metrics leaves the snapshot alone
+    let response = process_heartbeat(
+        &state,
+        daemon.id,
+        network_id,
+        DaemonHeartbeatRequest::default(),
+    )
+    .await
+    .unwrap();
+    assert!(response.system_metrics.is_none());
+
+    let unchanged = get().await;
+    assert!(unchanged.base.last_seen > reported.base.last_seen);
+    assert_eq!(unchanged.base.system_metrics, Some(snapshot.clone()));
+
+    // Out of range metrics are rejected, but the heartbeat still counts
+    let response = process_heartbeat(
+        &state,
+        daemon.id,
+        network_id,
+        DaemonHeartbeatRequest {
+            system_metrics: Some(system_metrics(-5.0)),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert!(response.heartbeat.success);
+    assert!(!response.system_metrics.unwrap().success);
+
+    let rejected = get().await;
+    assert!(rejected.base.last_seen > unchanged.base.last_seen);
+    assert_eq!(rejected.base.system_metrics, Some(snapshot));
+}
+
+/// Register a pull-mode daemon with a bootstrap token, so no request is sent back to it
+async fn register(
+    state: &Arc<AppState>,
+    token: &str,
+    network_id: Option<Uuid>,
+) -> (StatusCode, String) {
+    register_from(state, token, network_id, None).await
+}
+
+/// [`register`] through a proxy at `peer` that forwarded the request for `forwarded_for`
+async fn register_from(
+    state: &Arc<AppState>,
+    token: &str,
+    network_id: Option<Uuid>,
+    source: Option<(&str, &str)>,
+) -> (StatusCode, String) {
+    let mut body = serde_json::json!({
+        "daemon_id": Uuid::new_v4(),
+        "daemon_ip": "192.168.1.50",
+        "daemon_port": 60073,
+        "mode": DaemonMode::Pull,
+        "capabilities": {},
+        "bootstrap_token": token,
+    });
+    if let Some(network_id) = network_id {
+        body["network_id"] = serde_json::json!(network_id);
+    }
+
+    let mut request = Request::builder()
+        .method("POST")
+        .uri("/api/daemons/register")
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some((_, forwarded_for)) = source {
+        request = request.header("x-forwarded-for", forwarded_for);
+    }
+    let mut request = request.body(Body::from(body.to_string())).unwrap();
+    if let Some((peer, _)) = source {
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::new(peer.parse().unwrap(), 40000)));
+    }
+
+    let mut app = create_router().with_state(state.clone());
+    let response = app.call(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+async fn mint_token(state: &AppState, network_id: Uuid) -> String {
+    let (_, token) = state
+        .services
+        .bootstrap_token_service
+        .mint(CreateBootstrapTokenRequest {
+            name: "edge-daemon".to_string(),
+            network_id,
+            ttl_minutes: 60,
+            allowed_ip: None,
+        })
+        .await
+        .unwrap();
+    token
+}
+
+#[tokio::test]
+#[serial]
+async fn test_registration_without_network_joins_default() {
+    let default_network_id = Uuid::new_v4();
+    let (state, _container) = test_app_state_with_config(ServerConfig {
+        default_network_id: Some(default_network_id),
+        ..Default::default()
+    })
+    .await;
+
+    let organization = state
+        .services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let other_network = state
+        .services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let mut network = network(&organization.id);
+    network.id = default_network_id;
+    let network = state
+        .services
+        .network_service
+        .create(network)
+        .await
+        .unwrap();
+    let token = mint_token(&state, network.id).await;
+
+    // A provided network must exist, even with a default to fall back on
+    let (status, body) = register(&state, &token, Some(Uuid::new_v4())).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert!(body.contains("does not exist"), "{}", body);
+
+    // And be the one the token is bound to
+    let (status, body) = register(&state, &token, Some(other_network.id)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert!(body.contains("does not match"), "{}", body);
+    assert!(
+        state
+            .services
+            .daemon_service
+            .get_all(EntityFilter::unfiltered())
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    // The token wasn't spent on the failed attempts, and without a network the daemon joins the
+    // default
+    let (status, body) = register(&state, &token, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let daemons = state
+        .services
+        .daemon_service
+        .get_all(EntityFilter::unfiltered())
+        .await
+        .unwrap();
+    assert_eq!(daemons.len(), 1);
+    assert_eq!(daemons[0].base.network_id, network.id);
+    let host = state
+        .services
+        .host_service
+        .get_by_id(&daemons[0].base.host_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(host.base.network_id, network.id);
+
+    // The daemon is issued the key the server signs its requests to it with, which the API
+    // never hands out, and heartbeats keep handing it the same one
+    let response: ApiResponse<DaemonRegistrationResponse> = serde_json::from_str(&body).unwrap();
+    let server_key = response.data.unwrap().server_key.unwrap();
+    assert_eq!(daemons[0].base.server_key.as_ref(), Some(&server_key));
+    assert!(
+        !serde_json::to_string(&daemons[0])
+            .unwrap()
+            .contains(&server_key)
+    );
+
+    let heartbeat = process_heartbeat(
+        &state,
+        daemons[0].id,
+        network.id,
+        DaemonHeartbeatRequest::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(heartbeat.server_key, Some(server_key));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_registration_without_network_or_default_rejected() {
+    let (state, _container) = test_app_state().await;
+
+    let organization = state
+        .services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = state
+        .services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let token = mint_token(&state, network.id).await;
+
+    let (status, body) = register(&state, &token, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert!(body.contains("no default network"), "{}", body);
+    assert!(
+        state
+            .services
+            .daemon_service
+            .get_all(EntityFilter::unfiltered())
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    // The token wasn't spent on the failed attempt
+    let (status, body) = register(&state, &token, Some(network.id)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_registrations_racing_on_token_leave_one_host() {
+    let (state, _container) = test_app_state().await;
+    let organization = state
+        .services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = state
+        .services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let token = mint_token(&state, network.id).await;
+
+    let ((first, _), (second, _)) = tokio::join!(
+        register(&state, &token, Some(network.id)),
+        register(&state, &token, Some(network.id))
+    );
+    let mut statuses = [first, second];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::OK, StatusCode::UNAUTHORIZED]);
+
+    // The losing registration's host is removed along with everything else it would have made
+    let daemons = state
+        .services
+        .daemon_service
+        .get_all(EntityFilter::unfiltered())
+        .await
+        .unwrap();
+    let hosts = state
+        .services
+        .host_service
+        .get_all(EntityFilter::unfiltered().network_ids(&[network.id]))
+        .await
+        .unwrap();
+    assert_eq!(daemons.len(), 1);
+    assert_eq!(
+        hosts.iter().map(|h| h.id).collect::<Vec<_>>(),
+        vec![daemons[0].base.host_id]
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_pinned_token_matches_client_behind_trusted_proxy() {
+    let (state, _container) = test_app_state_with_config(ServerConfig {
+        trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+        ..Default::default()
+    })
+    .await;
+    let organization = state
+        .services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = state
+        .services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let mint_pinned = async |allowed_ip: &str| {
+        let (_, token) = state
+            .services
+            .bootstrap_token_service
+            .mint(CreateBootstrapTokenRequest {
+                name: "edge-daemon".to_string(),
+                network_id: network.id,
+                ttl_minutes: 60,
+                allowed_ip: Some(allowed_ip.parse().unwrap()),
+            })
+            .await
+            .unwrap();
+        token
+    };
+
+    // A token pinned to the proxy isn't redeemable by whoever is behind it
+    let token = mint_pinned("10.0.0.2").await;
+    let (status, body) = register_from(
+        &state,
+        &token,
+        Some(network.id),
+        Some(("10.0.0.2", "203.0.113.9")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+
+    // A token pinned to the daemon is redeemed by it through the proxy, and only by it
+    let token = mint_pinned("203.0.113.7").await;
+    let (status, body) = register_from(
+        &state,
+        &token,
+        Some(network.id),
+        Some(("10.0.0.2", "203.0.113.9")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+    let (status, body) = register_from(
+        &state,
+        &token,
+        Some(network.id),
+        Some(("10.0.0.2", "203.0.113.7")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_offline_daemon_notified_once_per_outage_across_restarts() {
+    let (state, _container) = test_app_state().await;
+    let mut daemon = stale_daemon(&state).await;
+    let service = &state.services.daemon_service;
+    let now = Utc::now();
+
+    assert_eq!(service.notify_offline_daemons(now).await.unwrap(), 1);
+    assert_eq!(service.notify_offline_daemons(now).await.unwrap(), 0);
+
+    // The outage is recorded on the daemon, so a restarted server doesn't notify it again
+    let restarted = DaemonService::new(
+        state.storage.daemons.clone(),
+        state.storage.daemon_metrics.clone(),
+        None,
+    );
+    assert_eq!(restarted.notify_offline_daemons(now).await.unwrap(), 0);
+
+    // Seen again and then quiet once more is a new outage
+    daemon.base.last_seen = now + Duration::minutes(1);
+    service.update(&mut daemon).await.unwrap();
+    let later = now + Duration::hours(1);
+    assert_eq!(restarted.notify_offline_daemons(later).await.unwrap(), 1);
+    assert_eq!(service.notify_offline_daemons(later).await.unwrap(), 0);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_heartbeats_rolled_up_into_buckets() {
+    let (state, _container) = test_app_state().await;
+    let daemon = stale_daemon(&state).await;
+    let service = &state.services.daemon_service;
+
+    let hour = |h: i64| {
+        "2025-06-01T00:00:00Z"
+            .parse::<chrono::DateTime<Utc>>()
+            .unwrap()
+            + Duration::hours(h)
+    };
+    let at = |h: i64, m: i64| hour(h) + Duration::minutes(m);
+
+    // Hour 0: regular heartbeats and a 15 minute discovery run. Hour 1: offline. Hour 2: back
+    // after a long gap.
+    for minute in [0, 10, 20] {
+        service.record_heartbeat(
+            &daemon,
+            at(0, minute) - Duration::minutes(10),
+            at(0, minute),
+        );
+    }
+    service.record_discovery_session(daemon.id, daemon.base.network_id, at(0, 30), at(0, 45));
+    service.record_system_metrics(&daemon, &system_metrics(20.0), at(0, 10));
+    service.record_system_metrics(&daemon, &system_metrics(60.0), at(0, 20));
+    service.record_heartbeat(&daemon, at(0, 20), at(2, 5));
+
+    // Buckets still in progress are flushed too, and later samples are added to them
+    assert_eq!(service.flush_metrics(at(2, 10)).await.unwrap(), 2);
+    service.record_heartbeat(&daemon, at(2, 5), at(2, 35));
+    assert_eq!(service.flush_metrics(at(2, 50)).await.unwrap(), 1);
+    assert_eq!(service.flush_metrics(hour(3)).await.unwrap(), 0);
+
+    let query = |resolution: Option<i64>| DaemonMetricsQuery {
+        from: Some(hour(0)),
+        to: Some(hour(3)),
+        resolution,
+    };
+
+    let metrics = service
+        .get_daemon_metrics(&daemon.id, query(None).window(Utc::now()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(metrics.resolution_secs, 3600);
+    assert!(metrics.note.is_none());
+    assert_eq!(
+        metrics.buckets,
+        vec![
+            DaemonMetricsBucket {
+                start: hour(0),
+                heartbeats: Some(3),
+                max_heartbeat_gap_secs: Some(600),
+                sessions_per_hour: Some(1.0),
+                avg_session_secs: Some(900.0),
+                avg_cpu_percent: Some(40.0),
+                max_cpu_percent: Some(60.0),
+                max_memory_percent: Some(25.0),
+                max_disk_percent: Some(40.0),
+            },
+            // Offline: nulls, not interpolated
+            DaemonMetricsBucket {
+                start: hour(1),
+                heartbeats: None,
+                max_heartbeat_gap_secs: None,
+                sessions_per_hour: None,
+                avg_session_secs: None,
+                avg_cpu_percent: None,
+                max_cpu_percent: None,
+                max_memory_percent: None,
+                max_disk_percent: None,
+            },
+            DaemonMetricsBucket {
+                start: hour(2),
+                heartbeats: Some(2),
+                max_heartbeat_gap_secs: Some(105 * 60),
+                sessions_per_hour: Some(0.0),
+                avg_session_secs: None,
+                avg_cpu_percent: None,
+                max_cpu_percent: None,
+                max_memory_percent: None,
+                max_disk_percent: None,
+            },
+        ]
+    );
+
+    // Finer than stored falls back to the stored resolution, with a note
+    let metrics = service
+        .get_daemon_metrics(&daemon.id, query(Some(60)).window(Utc::now()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(metrics.resolution_secs, 3600);
+    assert!(metrics.note.is_some());
+    assert_eq!(metrics.buckets.len(), 3);
+
+    // Coarser buckets combine the stored ones
+    let metrics = service
+        .get_daemon_metrics(
+            &daemon.id,
+            query(Some(3 * 3600)).window(Utc::now()).unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(metrics.buckets.len(), 1);
+    assert_eq!(metrics.buckets[0].heartbeats, Some(5));
+    assert_eq!(metrics.buckets[0].max_heartbeat_gap_secs, Some(105 * 60));
+
+    assert!(
+        DaemonMetricsQuery {
+            from: Some(hour(3)),
+            to: Some(hour(0)),
+            resolution: None,
+        }
+        .window(Utc::now())
+        .is_err()
+    );
+
+    // Rollups past the retention period are pruned
+    service
+        .flush_metrics(hour(3) + Duration::days(91))
+        .await
+        .unwrap();
+    let metrics = service
+        .get_daemon_metrics(&daemon.id, query(None).window(Utc::now()).unwrap())
+        .await
+        .unwrap();
+    assert!(metrics.buckets.iter().all(|b| b.heartbeats.is_none()));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_daemon_requests_limited_to_allowlisted_sources() {
+    let (state, _container) = test_app_state_with_config(ServerConfig {
+        daemon_ip_allowlist: vec![
+            "192.168.1.0/24".parse().unwrap(),
+            "10.9.9.0/24".parse().unwrap(),
+        ],
+        ..Default::default()
+    })
+    .await;
+    let daemon = stale_daemon(&state).await;
+    let (_, key) = state
+        .services
+        .api_key_service
+        .create(ApiKey::new(ApiKeyBase {
+            key_hash: String::new(),
+            name: "Daemon key".to_string(),
+            last_used: None,
+            expires_at: None,
+            network_id: daemon.base.network_id,
+            is_enabled: true,
+            rotated_at: None,
+        }))
+        .await
+        .unwrap();
+
+    // The daemon's organization and another one, each with an owner
+    let network = state
+        .services
+        .network_service
+        .get_by_id(&daemon.base.network_id)
+        .await
+        .unwrap()
+        .unwrap();
+    let other_organization = state
+        .services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    for (organization_id, email) in [
+        (&network.base.organization_id, "owner@example.com"),
+        (&other_organization.id, "other-owner@example.com"),
+    ] {
+        user_with_password(&state, organization_id, email, UserOrgPermissions::Owner).await;
+    }
+    let mut app = session_app(&state);
+    let owner_cookie = login(&mut app, "owner@example.com").await;
+    let other_owner_cookie = login(&mut app, "other-owner@example.com").await;
+    let set_allowlist = async |app: &mut Router, cookie: &str, cidrs: &[&str]| {
+        let (status, _, body) = call(
+            app,
+            "PUT",
+            "/api/daemons/ip-allowlist",
+            Some(cookie),
+            Some(serde_json::json!(cidrs)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    };
+
+    let mut daemon_app = create_router().with_state(state.clone());
+    let mut heartbeat = async |source: &str| {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri(format!("/api/daemons/{}/heartbeat", daemon.id))
+            .header(header::AUTHORIZATION, format!("Bearer {}", key))
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::new(source.parse().unwrap(), 40000)));
+
+        daemon_app.call(request).await.unwrap().status()
+    };
+
+    assert_eq!(heartbeat("172.16.0.1").await, StatusCode::FORBIDDEN);
+    assert_eq!(heartbeat("192.168.1.20").await, StatusCode::OK);
+    assert_eq!(heartbeat("10.9.9.9").await, StatusCode::OK);
+
+    // The daemon moves and its owner narrows the allowlist without a restart
+    set_allowlist(&mut app, &owner_cookie, &["10.9.9.0/24"]).await;
+    assert_eq!(heartbeat("10.9.9.9").await, StatusCode::OK);
+    assert_eq!(heartbeat("192.168.1.20").await, StatusCode::FORBIDDEN);
+
+    // Another organization's owner only changes their own organization's allowlist
+    set_allowlist(&mut app, &other_owner_cookie, &[]).await;
+    set_allowlist(&mut app, &other_owner_cookie, &["192.168.1.0/24"]).await;
+    assert_eq!(heartbeat("192.168.1.20").await, StatusCode::FORBIDDEN);
+    let (_, _, body) = call(
+        &mut app,
+        "GET",
+        "/api/daemons/ip-allowlist",
+        Some(&owner_cookie),
+        None,
+    )
+    .await;
+    assert!(body.contains("10.9.9.0/24") && !body.contains("192.168.1.0/24"));
+
+    // Organizations can't widen the server-wide allowlist, and emptied theirs defers to it
+    set_allowlist(&mut app, &owner_cookie, &["0.0.0.0/0"]).await;
+    assert_eq!(heartbeat("172.16.0.1").await, StatusCode::FORBIDDEN);
+    set_allowlist(&mut app, &owner_cookie, &[]).await;
+    assert_eq!(heartbeat("192.168.1.20").await, StatusCode::OK);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_daemon_update_keeps_pause_state() {
+    let (state, _container) = test_app_state().await;
+    let daemon = stale_daemon(&state).await;
+    let network = state
+        .services
+        .network_service
+        .get_by_id(&daemon.base.network_id)
+        .await
+        .unwrap()
+        .unwrap();
+    user_with_password(
+        &state,
+        &network.base.organization_id,
+        "member@example.com",
+        UserOrgPermissions::Member,
+    )
+    .await;
+    let mut app = session_app(&state);
+    let cookie = login(&mut app, "member@example.com").await;
+    let service = &state.services.daemon_service;
+    let put = async |app: &mut Router, daemon: &Daemon, paused: bool| {
+        let mut body = serde_json::to_value(daemon).unwrap();
+        body["paused"] = serde_json::json!(paused);
+        body["port"] = serde_json::json!(daemon.base.port + 1);
+        let (status, _, body) = call(
+            app,
+            "PUT",
+            &format!("/api/daemons/{}", daemon.id),
+            Some(&cookie),
+            Some(body),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        service.get_by_id(&daemon.id).await.unwrap().unwrap()
+    };
+
+    // Updates go through, but pausing takes the admin-only pause endpoint
+    let updated = put(&mut app, &daemon, true).await;
+    assert_eq!(updated.base.port, daemon.base.port + 1);
+    assert!(!updated.base.paused);
+
+    // Nor can an update resume a paused daemon
+    let paused = service.set_paused(&updated, true).await.unwrap();
+    let updated = put(&mut app, &paused, false).await;
+    assert_eq!(updated.base.port, paused.base.port + 1);
+    assert!(updated.base.paused);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_field_selection_returns_only_requested_keys() {
+    let (state, _container) = test_app_state_with_config(ServerConfig {
+        unknown_response_fields: UnknownFields::Reject,
+        ..Default::default()
+    })
+    .await;
+    let daemon = stale_daemon(&state).await;
+    let network = state
+        .services
+        .network_service
+        .get_by_id(&daemon.base.network_id)
+        .await
+        .unwrap()
+        .unwrap();
+    state
+        .services
+        .user_service
+        .create_user_with_password(
+            EmailAddress::new_unchecked("member@example.com"),
+            hash_password("correct-horse-battery").unwrap(),
+            network.base.organization_id,
+            UserOrgPermissions::Member,
+        )
+        .await
+        .unwrap();
+
+    let mut app = create_router()
+        .layer(middleware::from_fn_with_state(state.clone(), sparse_fields))
+        .layer(state.storage.sessions.clone())
+        .with_state(state.clone());
+
+    let login = serde_json::json!({
+        "email": "member@example.com",
+        "password": "correct-horse-battery",
+    });
+    let response = app
+        .call(
+            Request::builder()
+                .method("POST")
+                .uri("/api/auth/login")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(login.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let cookie = response
+        .headers()
+        .get(header::SET_COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .unwrap()
+        .to_string();
+
+    let mut get = async |uri: &str| {
+        let response = app
+            .call(
+                Request::builder()
+                    .uri(uri)
+                    .header(header::COOKIE, &cookie)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (
+            status,
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+        )
+    };
+
+    let (status, body) =
+        get("/api/daemons?fields=id,last_seen,capabilities.has_docker_socket").await;
+    assert_eq!(status, StatusCode::OK);
+    let daemons = body["data"].as_array().unwrap();
+    assert_eq!(daemons.len(), 1);
+    let mut keys: Vec<&String> = daemons[0].as_object().unwrap().keys().collect();
+    keys.sort();
+    assert_eq!(keys, ["capabilities", "id", "last_seen"]);
+    assert_eq!(daemons[0]["id"], serde_json::json!(daemon.id));
+    let capabilities: Vec<&String> = daemons[0]["capabilities"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .collect();
+    assert_eq!(capabilities, ["has_docker_socket"]);
+
+    // Detail responses are projected too
+    let (status, body) = get(&format!("/api/daemons/{}?fields=ip", daemon.id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let keys: Vec<&String> = body["data"].as_object().unwrap().keys().collect();
+    assert_eq!(keys, ["ip"]);
+
+    let (status, body) = get("/api/daemons?fields=id,status").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("status"));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_near_expiry_key_warned_in_heartbeat() {
+    let (state, _container) = test_app_state().await;
+    let daemon = stale_daemon(&state).await;
+    let mut notifications = state.services.notification_service.subscribe();
+
+    let create = |expires_in: Duration| {
+        state
+            .services
+            .api_key_service
+            .create(ApiKey::new(ApiKeyBase {
+                key_hash: String::new(),
+                name: "Daemon key".to_string(),
+                last_used: None,
+                expires_at: Some(Utc::now() + expires_in),
+                network_id: daemon.base.network_id,
+                is_enabled: true,
+                rotated_at: None,
+            }))
+    };
+    let (expiring, expiring_key) = create(Duration::days(3)).await.unwrap();
+    let (_, fresh_key) = create(Duration::days(60)).await.unwrap();
+
+    let mut app = create_router().with_state(state.clone());
+    let mut heartbeat = async |key: &str| {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri(format!("/api/daemons/{}/heartbeat", daemon.id))
+            .header(header::AUTHORIZATION, format!("Bearer {}", key))
+            .body(Body::empty())
+            .unwrap();
+        request.extensions_mut().insert(ConnectInfo(SocketAddr::new(
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 50)),
+            40000,
+        )));
+
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice::<ApiResponse<DaemonHeartbeatResponse>>(&body)
+            .unwrap()
+            .data
+            .unwrap()
+    };
+
+    let warning = heartbeat(&expiring_key).await.api_key_expiry.unwrap();
+    assert_eq!(warning.api_key_id, expiring.id);
+    assert_eq!(
+        warning.expires_at.timestamp(),
+        expiring.base.expires_at.unwrap().timestamp()
+    );
+    assert_eq!(warning.days_left, 2);
+    assert!(heartbeat(&fresh_key).await.api_key_expiry.is_none());
+
+    // Every heartbeat carries the warning, but the network is only notified once
+    assert!(heartbeat(&expiring_key).await.api_key_expiry.is_some());
+    let notification = notifications.try_recv().unwrap();
+    assert!(matches!(
+        notification.kind,
+        NotificationKind::ApiKeyExpiring { api_key_id, .. } if api_key_id == expiring.id
+    ));
+    assert!(notifications.try_recv().is_err());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_transfer_moves_daemon_and_nodes_atomically() {
+    let (pool, database_url, _container) = setup_test_db().await;
+    let storage = StorageFactory::new(&database_url, false).await.unwrap();
+    let services = ServiceFactory::new(&storage, None).await.unwrap();
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let from = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let to = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let daemon_host = storage.hosts.create(&host(&from.id)).await.unwrap();
+    let daemon = services
+        .daemon_service
+        .create(daemon(&from.id, &daemon_host.id))
+        .await
+        .unwrap();
+
+    let mut discovered = host(&from.id);
+    discovered.base.source = EntitySource::Discovery {
+        metadata: vec![DiscoveryMetadata {
+            daemon_id: Some(daemon.id),
+            ..Default::default()
+        }],
+    };
+    let discovered = storage.hosts.create(&discovered).await.unwrap();
+    let discovered_service = storage
+        .services
+        .create(&service(&from.id, &discovered.id))
+        .await
+        .unwrap();
+
+    let network_of_host = |id: Uuid| {
+        let hosts = storage.hosts.clone();
+        async move { hosts.get_by_id(&id).await.unwrap().unwrap().base.network_id }
+    };
+    let transfer_events = || {
+        let audit_service = services.audit_service.clone();
+        async move {
+            audit_service
+                .events_for_organization(&organization.id)
+                .await
+                .unwrap()
+                .into_iter()
+                .filter(|e| e.base.kind == AuditEventKind::DaemonTransferred)
+                .count()
+        }
+    };
+
+    // Fail the transfer once the daemon and hosts have already been updated
+    sqlx::query(
+        "CREATE FUNCTION fail_service_update() RETURNS trigger AS $$ \
+         BEGIN RAISE EXCEPTION 'injected failure'; END $$ LANGUAGE plpgsql",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "CREATE TRIGGER fail_service_update BEFORE UPDATE ON services \
+         FOR EACH ROW EXECUTE FUNCTION fail_service_update()",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let user_id = Uuid::new_v4();
+    assert!(
+        services
+            .daemon_service
+            .transfer_ownership(&daemon.id, &to.id, true, user_id)
+            .await
+            .is_err()
+    );
+
+    let unmoved = services.daemon_service.get_by_id(&daemon.id).await.unwrap();
+    assert_eq!(unmoved.unwrap().base.network_id, from.id);
+    assert_eq!(network_of_host(daemon_host.id).await, from.id);
+    assert_eq!(network_of_host(discovered.id).await, from.id);
+    assert_eq!(transfer_events().await, 0);
+
+    sqlx::query("DROP TRIGGER fail_service_update ON services")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let transfer = services
+        .daemon_service
+        .transfer_ownership(&daemon.id, &to.id, true, user_id)
+        .await
+        .unwrap();
+    assert_eq!(transfer.daemon.base.network_id, to.id);
+    assert_eq!(transfer.hosts_moved, 2);
+    assert_eq!(transfer.services_moved, 1);
+
+    assert_eq!(network_of_host(daemon_host.id).await, to.id);
+    assert_eq!(network_of_host(discovered.id).await, to.id);
+    let moved_service = storage
+        .services
+        .get_by_id(&discovered_service.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(moved_service.base.network_id, to.id);
+    assert_eq!(transfer_events().await, 1);
+}
@@ -0,0 +1,103 @@
+use crate::server::{
+    auth::middleware::{AuthenticatedUser, MemberOrDaemon, RequireMember},
+    config::AppState,
+    diagnostics::r#impl::{
+        api::DiagnosticQuery,
+        base::{Diagnostic, DiagnosticBase},
+        metrics::{PROMETHEUS_CONTENT_TYPE, render_check_metrics},
+    },
+    shared::{
+        extractors::SignedJson,
+        handlers::{
+            ndjson::{accepts_ndjson, ndjson_response},
+            traits::{admin_delete_handler, get_by_id_handler},
+        },
+        services::traits::CrudService,
+        storage::{filter::EntityFilter, traits::StorableEntity},
+        types::api::{ApiError, ApiResponse, ApiResult},
+    },
+};
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+};
+use std::sync::Arc;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_diagnostics))
+        .route("/", post(create_diagnostic))
+        .route("/{id}", get(get_by_id_handler::<Diagnostic>))
+        .route("/{id}", delete(admin_delete_handler::<Diagnostic>))
+}
+
+/// Prometheus scrape endpoints, nested under `/api/metrics`
+pub fn create_metrics_router() -> Router<Arc<AppState>> {
+    Router::new().route("/checks", get(get_check_metrics))
+}
+
+/// List diagnostic results, optionally only those from one investigation (`correlation_id`) or
+/// carrying a tag. Streamed one per line with `Accept: application/x-ndjson`.
+async fn get_diagnostics(
+    State(state): State<Arc<AppState>>,
+    RequireMember(user): RequireMember,
+    Query(query): Query<DiagnosticQuery>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let mut filter = EntityFilter::unfiltered().network_ids(&user.network_ids);
+    if let Some(correlation_id) = &query.correlation_id {
+        filter = filter.correlation_id(correlation_id);
+    }
+    if let Some(tag) = &query.tag {
+        filter = filter.tag(tag);
+    }
+
+    if accepts_ndjson(&headers) {
+        return Ok(ndjson_response(
+            state.services.diagnostic_service.stream_all(filter),
+        ));
+    }
+
+    let diagnostics = state.services.diagnostic_service.get_all(filter).await?;
+
+    Ok(Json(ApiResponse::success(diagnostics)).into_response())
+}
+
+/// Record a check result, reported by a daemon or by a user running checks by hand
+async fn create_diagnostic(
+    State(state): State<Arc<AppState>>,
+    MemberOrDaemon { network_ids, .. }: MemberOrDaemon,
+    SignedJson(request): SignedJson<DiagnosticBase>,
+) -> ApiResult<(StatusCode, Json<ApiResponse<Diagnostic>>)> {
+    if !network_ids.contains(&request.network_id) {
+        return Err(ApiError::forbidden("You don't have access to this network"));
+    }
+
+    let diagnostic = state
+        .services
+        .diagnostic_service
+        .record(Diagnostic::new(request))
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(diagnostic))))
+}
+
+/// The latest result of every check in the caller's networks as Prometheus metrics. Checks that
+/// haven't produced a result yet are left out rather than reported as down.
+async fn get_check_metrics(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+) -> ApiResult<Response> {
+    let latest = state
+        .services
+        .diagnostic_service
+        .latest_per_check(&user.network_ids)
+        .await?;
+
+    let body = render_check_metrics(&latest, state.config.check_metrics_max_series);
+
+    Ok(([(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)], body).into_response())
+}
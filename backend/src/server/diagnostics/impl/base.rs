@@ -0,0 +1,74 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Which network check produced a diagnostic result
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum DiagnosticKind {
+    Tcp,
+    Tls,
+    Http,
+    Icmp,
+    ReverseDns,
+}
+
+/// Stored result of a single network check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticBase {
+    pub network_id: Uuid,
+    /// Daemon that ran the check
+    #[serde(default)]
+    pub daemon_id: Option<Uuid>,
+    pub kind: DiagnosticKind,
+    pub target: String,
+    pub success: bool,
+    /// Check-specific detail on success, e.g. the HTTP status
+    #[serde(default)]
+    pub detail: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Set by the caller to group results from one investigation, e.g. a discovery run or a
+    /// troubleshooting session
+    #[serde(default)]
+    pub correlation_id: Option<Uuid>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Expiry of the certificate presented, for TLS checks
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Set by the server on a failure while a target this one depends on is failing too. The
+    /// result is kept but its alert is suppressed as unreachable due to that dependency.
+    #[serde(default)]
+    pub suppressed_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub base: DiagnosticBase,
+}
+
+impl Diagnostic {
+    /// Whole days the certificate had left when this result was recorded
+    pub fn days_until_expiry(&self) -> Option<i64> {
+        self.base
+            .expires_at
+            .map(|expires_at| (expires_at - self.created_at).num_days())
+    }
+
+    /// A failure that raised, rather than had suppressed, an alert
+    pub fn is_alerted_failure(&self) -> bool {
+        !self.base.success && self.base.suppressed_by.is_none()
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} {}: {}", self.base.kind, self.base.target, self.id)
+    }
+}
@@ -0,0 +1,45 @@
+/// Days before expiry at which a monitored certificate raises an alert, most distant first
+pub const EXPIRY_THRESHOLDS_DAYS: [i64; 4] = [30, 14, 7, 1];
+
+/// The threshold crossed between two consecutive checks of a certificate, if any. Each threshold
+/// is crossed once on the way down, so repeated checks inside the same window stay quiet. When
+/// several are crossed at once (e.g. the first check of an already expiring certificate) only the
+/// most urgent is returned. A renewal moves `days_left` up, so the thresholds are crossed afresh
+/// as the new certificate approaches expiry.
+pub fn crossed_threshold(previous_days: Option<i64>, days_left: i64) -> Option<i64> {
+    EXPIRY_THRESHOLDS_DAYS
+        .into_iter()
+        .filter(|&threshold| days_left <= threshold)
+        .filter(|&threshold| previous_days.is_none_or(|previous| previous > threshold))
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thresholds_fire_once_on_the_way_down() {
+        let days = [40, 31, 30, 29, 15, 14, 13, 8, 7, 6, 5, 2, 1, 0];
+
+        let mut previous = None;
+        let mut fired = Vec::new();
+        for days_left in days {
+            fired.extend(crossed_threshold(previous, days_left));
+            previous = Some(days_left);
+        }
+
+        assert_eq!(fired, vec![30, 14, 7, 1]);
+    }
+
+    #[test]
+    fn test_renewal_rearms_thresholds() {
+        // Renewed at 6 days left to 90 days: nothing fires going up, 30 fires again coming down
+        assert_eq!(crossed_threshold(Some(6), 90), None);
+        assert_eq!(crossed_threshold(Some(31), 30), Some(30));
+
+        // First sighting of a nearly expired certificate only raises the most urgent alert
+        assert_eq!(crossed_threshold(None, 5), Some(7));
+        assert_eq!(crossed_threshold(None, 60), None);
+    }
+}
@@ -0,0 +1,15 @@
+use crate::server::diagnostics::r#impl::base::Diagnostic;
+use crate::server::diagnostics::service::DiagnosticService;
+use crate::server::shared::handlers::traits::{CrudHandlers, EntityScope};
+
+impl CrudHandlers for Diagnostic {
+    type Service = DiagnosticService;
+
+    fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
+        &state.services.diagnostic_service
+    }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.base.network_id)
+    }
+}
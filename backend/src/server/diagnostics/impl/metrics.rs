@@ -0,0 +1,167 @@
+use std::fmt::Write;
+
+use crate::server::diagnostics::r#impl::base::{Diagnostic, DiagnosticKind};
+
+/// Prometheus text exposition format served by `GET /api/metrics/checks`
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+impl DiagnosticKind {
+    /// Value of the `check` label on exported metrics
+    pub fn metric_label(&self) -> &'static str {
+        match self {
+            DiagnosticKind::Tcp => "tcp",
+            DiagnosticKind::Tls => "tls",
+            DiagnosticKind::Http => "http",
+            DiagnosticKind::Icmp => "icmp",
+            DiagnosticKind::ReverseDns => "reverse_dns",
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Value of the `status` label on exported metrics
+    pub fn metric_status(&self) -> &'static str {
+        if self.base.success {
+            "ok"
+        } else if self.base.suppressed_by.is_some() {
+            "suppressed"
+        } else {
+            "failed"
+        }
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Render the latest result of each check as Prometheus metrics labelled by network, node (the check
+/// target), check kind and status. At most `max_series` checks are exported so a network with
+/// many targets can't blow up the scraper's series count; how many were left out is exported too.
+pub fn render_check_metrics(latest: &[Diagnostic], max_series: usize) -> String {
+    let exported = &latest[..latest.len().min(max_series)];
+    let dropped = latest.len() - exported.len();
+    if dropped > 0 {
+        tracing::warn!(
+            exported = exported.len(),
+            dropped,
+            "Check metrics capped at check_metrics_max_series"
+        );
+    }
+
+    let labels: Vec<String> = exported
+        .iter()
+        .map(|d| {
+            format!(
+                "network_id=\"{}\",node=\"{}\",check=\"{}\",status=\"{}\"",
+                d.base.network_id,
+                escape_label(&d.base.target),
+                d.base.kind.metric_label(),
+                d.metric_status()
+            )
+        })
+        .collect();
+
+    let mut out = String::new();
+
+    out.push_str("# HELP netvisor_check_up Whether the latest result of the check succeeded\n");
+    out.push_str("# TYPE netvisor_check_up gauge\n");
+    for (diagnostic, labels) in exported.iter().zip(&labels) {
+        let _ = writeln!(
+            out,
+            "netvisor_check_up{{{}}} {}",
+            labels,
+            u8::from(diagnostic.base.success)
+        );
+    }
+
+    out.push_str(
+        "# HELP netvisor_check_last_result_timestamp_seconds When the latest result of the check \
+         was recorded\n",
+    );
+    out.push_str("# TYPE netvisor_check_last_result_timestamp_seconds gauge\n");
+    for (diagnostic, labels) in exported.iter().zip(&labels) {
+        let _ = writeln!(
+            out,
+            "netvisor_check_last_result_timestamp_seconds{{{}}} {}",
+            labels,
+            diagnostic.created_at.timestamp()
+        );
+    }
+
+    out.push_str(
+        "# HELP netvisor_check_series_dropped Checks left out of this export by \
+         check_metrics_max_series\n",
+    );
+    out.push_str("# TYPE netvisor_check_series_dropped gauge\n");
+    let _ = writeln!(out, "netvisor_check_series_dropped {}", dropped);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::server::{
+        diagnostics::r#impl::base::DiagnosticBase, shared::storage::traits::StorableEntity,
+    };
+
+    fn result(network_id: Uuid, target: &str, kind: DiagnosticKind, success: bool) -> Diagnostic {
+        Diagnostic::new(DiagnosticBase {
+            network_id,
+            daemon_id: None,
+            kind,
+            target: target.to_string(),
+            success,
+            detail: None,
+            error: (!success).then(|| "connection refused".to_string()),
+            correlation_id: None,
+            tags: Vec::new(),
+            expires_at: None,
+            suppressed_by: None,
+        })
+    }
+
+    #[test]
+    fn test_completed_check_exported_with_latest_status() {
+        let network_id = Uuid::new_v4();
+        let latest = result(network_id, "10.0.0.5:443", DiagnosticKind::Tcp, false);
+
+        let metrics = render_check_metrics(std::slice::from_ref(&latest), 100);
+        let labels = format!(
+            "network_id=\"{}\",node=\"10.0.0.5:443\",check=\"tcp\",status=\"failed\"",
+            network_id
+        );
+        assert!(metrics.contains(&format!("netvisor_check_up{{{}}} 0\n", labels)));
+        assert!(metrics.contains(&format!(
+            "netvisor_check_last_result_timestamp_seconds{{{}}} {}\n",
+            labels,
+            latest.created_at.timestamp()
+        )));
+        assert!(!metrics.contains("status=\"ok\""));
+        assert!(metrics.contains("netvisor_check_series_dropped 0\n"));
+    }
+
+    #[test]
+    fn test_series_capped_and_unrun_checks_omitted() {
+        let network_id = Uuid::new_v4();
+        let checks = vec![
+            result(network_id, "a.example", DiagnosticKind::Http, true),
+            result(network_id, "b.example", DiagnosticKind::Http, true),
+            result(network_id, "c.example", DiagnosticKind::Http, true),
+        ];
+
+        let metrics = render_check_metrics(&checks, 2);
+        assert_eq!(metrics.matches("netvisor_check_up{").count(), 2);
+        assert!(!metrics.contains("c.example"));
+        assert!(metrics.contains("netvisor_check_series_dropped 1\n"));
+
+        let empty = render_check_metrics(&[], 2);
+        assert!(!empty.contains("netvisor_check_up{"));
+    }
+}
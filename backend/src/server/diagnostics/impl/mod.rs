@@ -0,0 +1,6 @@
+pub mod api;
+pub mod base;
+pub mod expiry;
+pub mod handlers;
+pub mod metrics;
+pub mod storage;
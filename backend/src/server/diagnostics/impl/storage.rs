@@ -0,0 +1,131 @@
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+use uuid::Uuid;
+
+use crate::server::{
+    diagnostics::r#impl::base::{Diagnostic, DiagnosticBase, DiagnosticKind},
+    shared::storage::traits::{SqlValue, StorableEntity},
+};
+
+impl StorableEntity for Diagnostic {
+    type BaseData = DiagnosticBase;
+
+    fn table_name() -> &'static str {
+        "diagnostics"
+    }
+
+    fn get_base(&self) -> Self::BaseData {
+        self.base.clone()
+    }
+
+    fn new(base: Self::BaseData) -> Self {
+        let now = chrono::Utc::now();
+
+        Self {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: now,
+            base,
+        }
+    }
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn set_updated_at(&mut self, time: DateTime<Utc>) {
+        self.updated_at = time;
+    }
+
+    fn to_params(&self) -> Result<(Vec<&'static str>, Vec<SqlValue>), anyhow::Error> {
+        let Self {
+            id,
+            created_at,
+            updated_at,
+            base:
+                Self::BaseData {
+                    network_id,
+                    daemon_id,
+                    kind,
+                    target,
+                    success,
+                    detail,
+                    error,
+                    correlation_id,
+                    tags,
+                    expires_at,
+                    suppressed_by,
+                },
+        } = self.clone();
+
+        Ok((
+            vec![
+                "id",
+                "created_at",
+                "updated_at",
+                "network_id",
+                "daemon_id",
+                "kind",
+                "target",
+                "success",
+                "detail",
+                "error",
+                "correlation_id",
+                "tags",
+                "expires_at",
+                "suppressed_by",
+            ],
+            vec![
+                SqlValue::Uuid(id),
+                SqlValue::Timestamp(created_at),
+                SqlValue::Timestamp(updated_at),
+                SqlValue::Uuid(network_id),
+                SqlValue::OptionalUuid(daemon_id),
+                SqlValue::DiagnosticKind(kind),
+                SqlValue::String(target),
+                SqlValue::Bool(success),
+                SqlValue::OptionalString(detail),
+                SqlValue::OptionalString(error),
+                SqlValue::OptionalUuid(correlation_id),
+                SqlValue::Json(serde_json::to_value(tags)?),
+                SqlValue::OptionTimestamp(expires_at),
+                SqlValue::OptionalString(suppressed_by),
+            ],
+        ))
+    }
+
+    fn from_row(row: &PgRow) -> Result<Self, anyhow::Error> {
+        let kind: DiagnosticKind = serde_json::from_str(&row.get::<String, _>("kind"))
+            .map_err(|e| anyhow::anyhow!("Failed to deserialize kind: {}", e))?;
+        let tags: Vec<String> = serde_json::from_value(row.get("tags"))
+            .map_err(|e| anyhow::anyhow!("Failed to deserialize tags: {}", e))?;
+
+        Ok(Diagnostic {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            base: DiagnosticBase {
+                network_id: row.get("network_id"),
+                daemon_id: row.get("daemon_id"),
+                kind,
+                target: row.get("target"),
+                success: row.get("success"),
+                detail: row.get("detail"),
+                error: row.get("error"),
+                correlation_id: row.get("correlation_id"),
+                tags,
+                expires_at: row.get("expires_at"),
+                suppressed_by: row.get("suppressed_by"),
+            },
+        })
+    }
+}
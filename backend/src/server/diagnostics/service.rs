@@ -0,0 +1,229 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::server::{
+    check_dependencies::service::CheckDependencyService,
+    diagnostics::r#impl::{
+        base::{Diagnostic, DiagnosticKind},
+        expiry::crossed_threshold,
+    },
+    notifications::{
+        r#impl::base::{Notification, NotificationKind},
+        service::NotificationService,
+    },
+    shared::{
+        services::traits::CrudService,
+        storage::{filter::EntityFilter, generic::GenericPostgresStorage},
+    },
+};
+
+pub struct DiagnosticService {
+    storage: Arc<GenericPostgresStorage<Diagnostic>>,
+    notification_service: Arc<NotificationService>,
+    check_dependency_service: Arc<CheckDependencyService>,
+}
+
+#[async_trait]
+impl CrudService<Diagnostic> for DiagnosticService {
+    fn storage(&self) -> &Arc<GenericPostgresStorage<Diagnostic>> {
+        &self.storage
+    }
+}
+
+impl DiagnosticService {
+    pub fn new(
+        storage: Arc<GenericPostgresStorage<Diagnostic>>,
+        notification_service: Arc<NotificationService>,
+        check_dependency_service: Arc<CheckDependencyService>,
+    ) -> Self {
+        Self {
+            storage,
+            notification_service,
+            check_dependency_service,
+        }
+    }
+
+    /// Store a check result, raising any alerts it warrants against the target's history. A
+    /// failure while one of the target's dependencies is failing is stored with its alert
+    /// suppressed.
+    pub async fn record(&self, mut diagnostic: Diagnostic) -> Result<Diagnostic> {
+        diagnostic.base.suppressed_by = None;
+        if !diagnostic.base.success {
+            match self.failing_dependency(&diagnostic).await {
+                Ok(dependency) => diagnostic.base.suppressed_by = dependency,
+                Err(e) => tracing::warn!(
+                    target = %diagnostic.base.target,
+                    "Failed to evaluate check dependencies: {}",
+                    e
+                ),
+            }
+        }
+
+        let diagnostic = self.create(diagnostic).await?;
+
+        let alerts = if diagnostic.base.kind == DiagnosticKind::Tls {
+            self.check_certificate(&diagnostic).await
+        } else {
+            self.check_failure(&diagnostic).await
+        };
+        if let Err(e) = alerts {
+            tracing::warn!(
+                diagnostic_id = %diagnostic.id,
+                "Failed to evaluate alerts: {}",
+                e
+            );
+        }
+
+        Ok(diagnostic)
+    }
+
+    /// The latest result of each check, i.e. each check kind against each target, in `network_ids`,
+    /// ordered by network, target and kind. Checks that never produced a result don't appear.
+    pub async fn latest_per_check(&self, network_ids: &[Uuid]) -> Result<Vec<Diagnostic>> {
+        self.storage
+            .get_latest_per(
+                EntityFilter::unfiltered().network_ids(network_ids),
+                &["network_id", "target", "kind"],
+            )
+            .await
+    }
+
+    /// The nearest target `diagnostic`'s target depends on whose latest result is a failure
+    async fn failing_dependency(&self, diagnostic: &Diagnostic) -> Result<Option<String>> {
+        let network_id = diagnostic.base.network_id;
+        let upstream = self
+            .check_dependency_service
+            .upstream_of(&network_id, &diagnostic.base.target)
+            .await?;
+
+        for dependency in upstream {
+            let latest = self
+                .storage
+                .get_latest(
+                    EntityFilter::unfiltered()
+                        .network_ids(&[network_id])
+                        .target(&dependency),
+                )
+                .await?;
+
+            if latest.is_some_and(|d| !d.base.success) {
+                return Ok(Some(dependency));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Whether a failure of `current` should alert: not when suppressed by a dependency, and not
+    /// when the previous result already alerted. A failure that was suppressed doesn't count as
+    /// alerted, so once the dependency recovers a target that's still failing alerts on its own.
+    fn should_alert_failure(current: &Diagnostic, previous: Option<&Diagnostic>) -> bool {
+        if let Some(dependency) = &current.base.suppressed_by {
+            tracing::info!(
+                target = %current.base.target,
+                dependency = %dependency,
+                "Check failure alert suppressed, unreachable due to dependency"
+            );
+            return false;
+        }
+
+        !previous.is_some_and(Diagnostic::is_alerted_failure)
+    }
+
+    /// Alert once when a target stops answering a check
+    async fn check_failure(&self, current: &Diagnostic) -> Result<()> {
+        if current.base.success {
+            return Ok(());
+        }
+
+        let previous = self
+            .storage
+            .get_latest(
+                EntityFilter::unfiltered()
+                    .network_ids(&[current.base.network_id])
+                    .diagnostic_kind(current.base.kind)
+                    .target(&current.base.target)
+                    .exclude_entity_id(&current.id),
+            )
+            .await?;
+
+        if !Self::should_alert_failure(current, previous.as_ref()) {
+            return Ok(());
+        }
+
+        self.notification_service.notify(Notification::new(
+            current.base.network_id,
+            NotificationKind::CheckFailed {
+                target: current.base.target.clone(),
+                check: current.base.kind,
+                error: current
+                    .base
+                    .error
+                    .clone()
+                    .unwrap_or_else(|| "Unknown error".to_string()),
+            },
+        ));
+
+        Ok(())
+    }
+
+    /// Compare a TLS result with the previous one for the same target. A failed check alerts
+    /// once when the target stops answering, and any check that saw a certificate alerts as it
+    /// crosses each expiry threshold, including ones rejected for being expired or self-signed.
+    async fn check_certificate(&self, current: &Diagnostic) -> Result<()> {
+        let history = || {
+            EntityFilter::unfiltered()
+                .network_ids(&[current.base.network_id])
+                .diagnostic_kind(DiagnosticKind::Tls)
+                .target(&current.base.target)
+                .exclude_entity_id(&current.id)
+        };
+        let previous = self.storage.get_latest(history()).await?;
+
+        if !current.base.success && Self::should_alert_failure(current, previous.as_ref()) {
+            self.notification_service.notify(Notification::new(
+                current.base.network_id,
+                NotificationKind::CertificateCheckFailed {
+                    target: current.base.target.clone(),
+                    error: current
+                        .base
+                        .error
+                        .clone()
+                        .unwrap_or_else(|| "Unknown error".to_string()),
+                },
+            ));
+        }
+
+        let (Some(days_left), Some(expires_at)) =
+            (current.days_until_expiry(), current.base.expires_at)
+        else {
+            return Ok(());
+        };
+        // A previous check that saw no certificate, e.g. while the target was down, says nothing
+        // about which thresholds have already alerted, so look past it to the last one that did
+        let previous_days = match previous.as_ref().and_then(Diagnostic::days_until_expiry) {
+            Some(days) => Some(days),
+            None => self
+                .storage
+                .get_latest(history().with_expiry())
+                .await?
+                .and_then(|d| d.days_until_expiry()),
+        };
+
+        if let Some(threshold_days) = crossed_threshold(previous_days, days_left) {
+            self.notification_service.notify(Notification::new(
+                current.base.network_id,
+                NotificationKind::CertificateExpiring {
+                    target: current.base.target.clone(),
+                    threshold_days,
+                    days_left,
+                    expires_at,
+                },
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,502 @@
+use axum::{
+    body::Body,
+    http::{Request, StatusCode, header},
+};
+use chrono::{Duration, Utc};
+use flate2::{Compression, write::GzEncoder};
+use futures::StreamExt;
+use serial_test::serial;
+use std::io::Write;
+use tower::Service;
+use uuid::Uuid;
+
+use crate::{
+    server::{
+        api_keys::r#impl::base::{ApiKey, ApiKeyBase},
+        check_dependencies::r#impl::base::{CheckDependency, CheckDependencyBase},
+        config::ServerConfig,
+        daemons::r#impl::signing::signature_headers,
+        diagnostics::r#impl::base::{Diagnostic, DiagnosticBase, DiagnosticKind},
+        notifications::r#impl::base::NotificationKind,
+        shared::{
+            handlers::factory::create_router,
+            services::traits::CrudService,
+            storage::{
+                cursor::PageCursor,
+                filter::EntityFilter,
+                traits::{StorableEntity, Storage},
+            },
+        },
+    },
+    tests::*,
+};
+
+fn diagnostic(network_id: Uuid, target: &str, correlation_id: Option<Uuid>) -> Diagnostic {
+    Diagnostic::new(DiagnosticBase {
+        network_id,
+        daemon_id: None,
+        kind: DiagnosticKind::Tcp,
+        target: target.to_string(),
+        success: true,
+        detail: None,
+        error: None,
+        correlation_id,
+        tags: vec!["troubleshooting".to_string()],
+        expires_at: None,
+        suppressed_by: None,
+    })
+}
+
+#[tokio::test]
+#[serial]
+async fn test_diagnostics_queried_by_correlation_id() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+
+    let investigation = Uuid::new_v4();
+    let service = &services.diagnostic_service;
+
+    for target in ["10.0.0.1:22", "10.0.0.1:443", "10.0.0.2:80"] {
+        service
+            .create(diagnostic(network.id, target, Some(investigation)))
+            .await
+            .unwrap();
+    }
+    service
+        .create(diagnostic(network.id, "10.0.0.3:22", Some(Uuid::new_v4())))
+        .await
+        .unwrap();
+    service
+        .create(diagnostic(network.id, "10.0.0.4:22", None))
+        .await
+        .unwrap();
+
+    let results = service
+        .get_all(
+            EntityFilter::unfiltered()
+                .network_ids(&[network.id])
+                .correlation_id(&investigation),
+        )
+        .await
+        .unwrap();
+
+    let mut targets: Vec<_> = results.iter().map(|d| d.base.target.as_str()).collect();
+    targets.sort();
+    assert_eq!(targets, vec!["10.0.0.1:22", "10.0.0.1:443", "10.0.0.2:80"]);
+
+    // Tags round-trip and can be filtered on
+    let tagged = service
+        .get_all(
+            EntityFilter::unfiltered()
+                .network_ids(&[network.id])
+                .tag("troubleshooting"),
+        )
+        .await
+        .unwrap();
+    assert_eq!(tagged.len(), 5);
+    assert_eq!(tagged[0].base.tags, vec!["troubleshooting".to_string()]);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_latest_result_per_check() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let other_network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+
+    let service = &services.diagnostic_service;
+    let at = |minutes_ago: i64, mut diagnostic: Diagnostic| {
+        diagnostic.created_at = Utc::now() - Duration::minutes(minutes_ago);
+        diagnostic
+    };
+
+    let mut failed = diagnostic(network.id, "10.0.0.5:443", None);
+    failed.base.success = false;
+    let mut tls = diagnostic(network.id, "10.0.0.5:443", None);
+    tls.base.kind = DiagnosticKind::Tls;
+    for diagnostic in [
+        at(10, diagnostic(network.id, "10.0.0.5:443", None)),
+        at(5, failed),
+        at(20, diagnostic(network.id, "10.0.0.5:443", None)),
+        at(1, tls),
+        at(1, diagnostic(other_network.id, "10.0.0.5:443", None)),
+    ] {
+        service.create(diagnostic).await.unwrap();
+    }
+
+    // One per kind and target, the most recent rather than the last written
+    let latest = service.latest_per_check(&[network.id]).await.unwrap();
+    assert_eq!(latest.len(), 2);
+    let tcp = latest
+        .iter()
+        .find(|d| d.base.kind == DiagnosticKind::Tcp)
+        .unwrap();
+    assert!(!tcp.base.success);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_diagnostics_paged_by_cursor() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+
+    let service = &services.diagnostic_service;
+    let mut created = Vec::new();
+    for port in 0..5 {
+        let target = format!("10.0.0.1:{}", port);
+        created.push(
+            service
+                .create(diagnostic(network.id, &target, None))
+                .await
+                .unwrap()
+                .id,
+        );
+    }
+
+    let filter = EntityFilter::unfiltered().network_ids(&[network.id]);
+    let mut seen = Vec::new();
+    let mut after = None;
+    loop {
+        let page = service
+            .storage()
+            .get_page(filter.clone(), after, 2)
+            .await
+            .unwrap();
+        assert!(page.len() <= 2);
+        after = page.last().map(PageCursor::of);
+        seen.extend(page.iter().map(|d| d.id));
+        if page.len() < 2 {
+            break;
+        }
+    }
+
+    // Every row exactly once, in creation order
+    assert_eq!(seen, created);
+
+    let streamed: Vec<_> = service
+        .stream_all(filter)
+        .map(|d| d.unwrap().id)
+        .collect()
+        .await;
+    assert_eq!(streamed, created);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_crossing_expiry_threshold_alerts_once() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+
+    let service = &services.diagnostic_service;
+    let mut notifications = services.notification_service.subscribe();
+
+    let check = |days_left: Option<i64>| {
+        let mut diagnostic = diagnostic(network.id, "example.com:443", None);
+        diagnostic.base.kind = DiagnosticKind::Tls;
+        match days_left {
+            // Padded so whole days aren't rounded down by the time it is recorded
+            Some(days) => {
+                diagnostic.base.expires_at =
+                    Some(Utc::now() + Duration::days(days) + Duration::hours(1))
+            }
+            None => {
+                diagnostic.base.success = false;
+                diagnostic.base.error = Some("connection refused".to_string());
+            }
+        }
+        service.record(diagnostic)
+    };
+    let mut drain = || {
+        let mut received = Vec::new();
+        while let Ok(notification) = notifications.try_recv() {
+            received.push(notification.kind);
+        }
+        received
+    };
+
+    // First sighting at 8 days raises the 14 day alert only
+    check(Some(8)).await.unwrap();
+    assert_eq!(drain().len(), 1);
+
+    check(Some(6)).await.unwrap();
+    check(Some(5)).await.unwrap();
+    let received = drain();
+    assert_eq!(received.len(), 1);
+    assert!(matches!(
+        received[0],
+        NotificationKind::CertificateExpiring {
+            threshold_days: 7,
+            days_left: 6,
+            ..
+        }
+    ));
+
+    // An unreachable host is reported once, as a failure rather than as expiring
+    check(None).await.unwrap();
+    check(None).await.unwrap();
+    let received = drain();
+    assert_eq!(received.len(), 1);
+    assert!(matches!(
+        received[0],
+        NotificationKind::CertificateCheckFailed { .. }
+    ));
+
+    // Coming back with the same certificate doesn't repeat the alert from before the outage
+    check(Some(4)).await.unwrap();
+    assert!(drain().is_empty());
+
+    // A certificate rejected for having expired still reports its expiry
+    let mut expired = diagnostic(network.id, "example.com:443", None);
+    expired.base.kind = DiagnosticKind::Tls;
+    expired.base.success = false;
+    expired.base.error = Some("certificate expired".to_string());
+    expired.base.expires_at = Some(Utc::now() - Duration::days(2));
+    service.record(expired).await.unwrap();
+    let received = drain();
+    assert_eq!(received.len(), 2);
+    assert!(matches!(
+        received[0],
+        NotificationKind::CertificateCheckFailed { .. }
+    ));
+    assert!(matches!(
+        received[1],
+        NotificationKind::CertificateExpiring {
+            threshold_days: 1,
+            ..
+        }
+    ));
+
+    // Renewal is quiet, then the thresholds apply again to the new certificate
+    check(Some(90)).await.unwrap();
+    assert!(drain().is_empty());
+    check(Some(29)).await.unwrap();
+    assert!(matches!(
+        drain()[..],
+        [NotificationKind::CertificateExpiring {
+            threshold_days: 30,
+            ..
+        }]
+    ));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_failing_dependency_suppresses_dependent_alert() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+
+    let behind_gateway = CheckDependency::new(CheckDependencyBase {
+        network_id: network.id,
+        target: "10.0.0.5".to_string(),
+        depends_on: "10.0.0.1".to_string(),
+    });
+    services
+        .check_dependency_service
+        .create(behind_gateway)
+        .await
+        .unwrap();
+
+    // The reverse dependency would be a cycle
+    let reverse = CheckDependency::new(CheckDependencyBase {
+        network_id: network.id,
+        target: "10.0.0.1".to_string(),
+        depends_on: "10.0.0.5".to_string(),
+    });
+    assert!(
+        services
+            .check_dependency_service
+            .creates_cycle(&reverse)
+            .await
+            .unwrap()
+    );
+
+    let service = &services.diagnostic_service;
+    let mut notifications = services.notification_service.subscribe();
+
+    let check = |target: &str, success: bool| {
+        let mut diagnostic = diagnostic(network.id, target, None);
+        diagnostic.base.kind = DiagnosticKind::Icmp;
+        diagnostic.base.success = success;
+        if !success {
+            diagnostic.base.error = Some("host unreachable".to_string());
+        }
+        service.record(diagnostic)
+    };
+    let mut drain = || {
+        let mut received = Vec::new();
+        while let Ok(notification) = notifications.try_recv() {
+            received.push(notification.kind);
+        }
+        received
+    };
+
+    // The gateway going down alerts, the host behind it is recorded but stays quiet
+    check("10.0.0.1", false).await.unwrap();
+    let host_result = check("10.0.0.5", false).await.unwrap();
+    check("10.0.0.5", false).await.unwrap();
+
+    let received = drain();
+    assert_eq!(received.len(), 1);
+    assert!(matches!(
+        &received[0],
+        NotificationKind::CheckFailed { target, .. } if target == "10.0.0.1"
+    ));
+
+    let stored = service.get_by_id(&host_result.id).await.unwrap().unwrap();
+    assert!(!stored.base.success);
+    assert_eq!(stored.base.suppressed_by.as_deref(), Some("10.0.0.1"));
+
+    // Once the gateway recovers, the host still failing alerts on its own
+    check("10.0.0.1", true).await.unwrap();
+    check("10.0.0.5", false).await.unwrap();
+    check("10.0.0.5", false).await.unwrap();
+
+    let received = drain();
+    assert_eq!(received.len(), 1);
+    assert!(matches!(
+        &received[0],
+        NotificationKind::CheckFailed { target, .. } if target == "10.0.0.5"
+    ));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_compressed_result_submission() {
+    let (state, _container) = test_app_state_with_config(ServerConfig {
+        json_max_decompressed_bytes: 64 * 1024,
+        ..Default::default()
+    })
+    .await;
+    let services = &state.services;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let (_, key) = services
+        .api_key_service
+        .create(ApiKey::new(ApiKeyBase {
+            key_hash: String::new(),
+            name: "Daemon key".to_string(),
+            last_used: None,
+            expires_at: None,
+            network_id: network.id,
+            is_enabled: true,
+            rotated_at: None,
+        }))
+        .await
+        .unwrap();
+
+    let mut app = create_router().with_state(state.clone());
+    let mut submit = async |detail: String, encoding: &str| {
+        let mut result = diagnostic(network.id, "10.0.0.1:22", None).base;
+        result.detail = Some(detail);
+        let body = serde_json::to_vec(&result).unwrap();
+
+        // Signed before compressing, as the daemon does
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/api/diagnostics")
+            .header(header::AUTHORIZATION, format!("Bearer {}", key))
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CONTENT_ENCODING, encoding);
+        for (name, value) in signature_headers(&key, &body) {
+            request = request.header(name, value);
+        }
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&body).unwrap();
+        let request = request.body(Body::from(encoder.finish().unwrap())).unwrap();
+
+        app.call(request).await.unwrap().status()
+    };
+
+    assert_eq!(
+        submit("open".to_string(), "gzip").await,
+        StatusCode::CREATED
+    );
+    let stored = services
+        .diagnostic_service
+        .get_all(EntityFilter::unfiltered().network_ids(&[network.id]))
+        .await
+        .unwrap();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].base.detail.as_deref(), Some("open"));
+
+    // Compresses to a few hundred bytes but inflates past the cap
+    assert_eq!(
+        submit("a".repeat(1024 * 1024), "gzip").await,
+        StatusCode::PAYLOAD_TOO_LARGE
+    );
+    assert_eq!(
+        submit("open".to_string(), "br").await,
+        StatusCode::UNSUPPORTED_MEDIA_TYPE
+    );
+
+    let stored = services
+        .diagnostic_service
+        .get_all(EntityFilter::unfiltered().network_ids(&[network.id]))
+        .await
+        .unwrap();
+    assert_eq!(stored.len(), 1);
+}
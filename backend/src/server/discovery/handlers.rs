@@ -2,8 +2,15 @@ use crate::server::{
     auth::middleware::{AuthenticatedDaemon, AuthenticatedUser, RequireMember},
     config::AppState,
     daemons::r#impl::api::DiscoveryUpdatePayload,
-    discovery::r#impl::{base::Discovery, types::RunType},
+    discovery::r#impl::{
+        base::{Discovery, DiscoveryBase},
+        estimate::DiscoveryEstimate,
+        history::{SessionActor, SessionHistory},
+        import::{DiscoveryImportResult, ImportFormat, parse},
+        types::{DiscoveryType, RunType},
+    },
     shared::{
+        extractors::{JsonLimits, SignedJson},
         handlers::traits::{
             create_handler, delete_handler, get_all_handler, get_by_id_handler, update_handler,
         },
@@ -13,7 +20,9 @@ use crate::server::{
 };
 use axum::{
     Router,
-    extract::{Path, State},
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
     response::{
         Json, Sse,
         sse::{Event, KeepAlive},
@@ -22,6 +31,7 @@ use axum::{
 };
 use chrono::Utc;
 use futures::Stream;
+use serde::Deserialize;
 use std::{convert::Infallible, sync::Arc};
 use tokio::sync::broadcast;
 use uuid::Uuid;
@@ -34,8 +44,11 @@ pub fn create_router() -> Router<Arc<AppState>> {
         .route("/{id}", delete(delete_handler::<Discovery>))
         .route("/{id}", get(get_by_id_handler::<Discovery>))
         .route("/start-session", post(start_session))
+        .route("/dry-run", post(dry_run))
+        .route("/import", post(import_results))
         .route("/active-sessions", get(get_active_sessions))
         .route("/{session_id}/cancel", post(cancel_discovery))
+        .route("/{session_id}/history", get(get_session_history))
         .route("/{session_id}/update", post(receive_discovery_update))
         .route("/stream", get(discovery_stream))
 }
@@ -45,7 +58,7 @@ async fn receive_discovery_update(
     State(state): State<Arc<AppState>>,
     _daemon: AuthenticatedDaemon,
     Path(_session_id): Path<Uuid>,
-    Json(update): Json<DiscoveryUpdatePayload>,
+    SignedJson(update): SignedJson<DiscoveryUpdatePayload>,
 ) -> ApiResult<Json<ApiResponse<()>>> {
     state
         .services
@@ -56,10 +69,105 @@ async fn receive_discovery_update(
     Ok(Json(ApiResponse::success(())))
 }
 
+/// Dry run: estimated probe count and duration of a network discovery, without starting it
+async fn dry_run(
+    State(state): State<Arc<AppState>>,
+    RequireMember(user): RequireMember,
+    Json(discovery): Json<DiscoveryBase>,
+) -> ApiResult<Json<ApiResponse<DiscoveryEstimate>>> {
+    if !user.network_ids.contains(&discovery.network_id) {
+        return Err(ApiError::forbidden("You don't have access to this network"));
+    }
+    if !matches!(discovery.discovery_type, DiscoveryType::Network { .. }) {
+        return Err(ApiError::bad_request(
+            "Only network discoveries can be estimated",
+        ));
+    }
+
+    let daemon_id = discovery
+        .daemon_id
+        .ok_or_else(|| ApiError::bad_request("Discoveries need a daemon to be estimated"))?;
+    let daemon = state
+        .services
+        .daemon_service
+        .get_by_id(&daemon_id)
+        .await?
+        .filter(|daemon| daemon.base.network_id == discovery.network_id)
+        .ok_or_else(|| ApiError::not_found(format!("Daemon '{}' not found", daemon_id)))?;
+
+    let estimate = state
+        .services
+        .discovery_service
+        .estimate(&discovery)
+        .await?;
+
+    if estimate.very_large {
+        tracing::warn!(
+            daemon_id = %daemon.id,
+            probes = %estimate.probes,
+            max_duration_secs = %estimate.max_duration_secs,
+            "Dry run of a very large discovery"
+        );
+    }
+
+    Ok(Json(ApiResponse::success(estimate)))
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportQuery {
+    network_id: Uuid,
+    /// Detected from the file when not given
+    format: Option<ImportFormat>,
+}
+
+/// Import an Nmap scan run outside NetVisor, uploaded as the request body, as a completed
+/// discovery session of a network
+async fn import_results(
+    State(state): State<Arc<AppState>>,
+    RequireMember(user): RequireMember,
+    Query(query): Query<ImportQuery>,
+    headers: HeaderMap,
+    body: Body,
+) -> ApiResult<Json<ApiResponse<DiscoveryImportResult>>> {
+    if !user.network_ids.contains(&query.network_id) {
+        return Err(ApiError::forbidden("You don't have access to this network"));
+    }
+
+    let (bytes, _) = JsonLimits::from(&state.config)
+        .read_body(&headers, body)
+        .await?;
+    let format = query.format.unwrap_or_else(|| ImportFormat::detect(&bytes));
+
+    let hosts = tokio::task::spawn_blocking(move || parse(&bytes, format))
+        .await
+        .map_err(|e| ApiError::internal_error(&e.to_string()))?
+        .map_err(|e| {
+            ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Failed to parse {} file: {}", format, e),
+            )
+        })?;
+
+    let result = state
+        .services
+        .discovery_service
+        .import(
+            query.network_id,
+            format,
+            hosts,
+            SessionActor::User {
+                user_id: user.user_id,
+            },
+        )
+        .await?;
+
+    Ok(Json(ApiResponse::success(result)))
+}
+
 /// Endpoint to start a discovery session
 async fn start_session(
     State(state): State<Arc<AppState>>,
-    RequireMember(_user): RequireMember,
+    RequireMember(user): RequireMember,
     Json(discovery_id): Json<Uuid>,
 ) -> ApiResult<Json<ApiResponse<DiscoveryUpdatePayload>>> {
     let mut discovery = state
@@ -85,7 +193,12 @@ async fn start_session(
     let update = state
         .services
         .discovery_service
-        .start_session(discovery.clone())
+        .start_session_by(
+            discovery.clone(),
+            SessionActor::User {
+                user_id: user.user_id,
+            },
+        )
         .await?;
 
     state
@@ -139,15 +252,39 @@ async fn get_active_sessions(
 /// Cancel an active discovery session
 async fn cancel_discovery(
     State(state): State<Arc<AppState>>,
-    RequireMember(_user): RequireMember,
+    RequireMember(user): RequireMember,
     Path(session_id): Path<Uuid>,
 ) -> ApiResult<Json<ApiResponse<()>>> {
     state
         .services
         .discovery_service
-        .cancel_session(session_id)
+        .cancel_session(
+            session_id,
+            SessionActor::User {
+                user_id: user.user_id,
+            },
+        )
         .await?;
 
     tracing::info!("Discovery session was {} cancelled", session_id);
     Ok(Json(ApiResponse::success(())))
 }
+
+/// Ordered phase transitions of a session, including finished ones until they're cleaned up
+async fn get_session_history(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+    Path(session_id): Path<Uuid>,
+) -> ApiResult<Json<ApiResponse<SessionHistory>>> {
+    let history = state
+        .services
+        .discovery_service
+        .get_session_history(&session_id)
+        .await
+        .filter(|history| user.network_ids.contains(&history.network_id))
+        .ok_or_else(|| {
+            ApiError::not_found(format!("Discovery session '{}' not found", session_id))
+        })?;
+
+    Ok(Json(ApiResponse::success(history)))
+}
@@ -11,7 +11,8 @@ pub struct DiscoveryBase {
     pub discovery_type: DiscoveryType,
     pub run_type: RunType,
     pub name: String,
-    pub daemon_id: Uuid,
+    /// None for imported runs, which have no daemon
+    pub daemon_id: Option<Uuid>,
     pub network_id: Uuid,
 }
 
@@ -0,0 +1,212 @@
+use cidr::IpCidr;
+use serde::{Deserialize, Serialize};
+
+use crate::server::{daemons::r#impl::api::DaemonCapabilities, discovery::r#impl::types::ScanRate};
+
+/// Concurrent host scans assumed when the daemon hasn't reported its rate, matching what a daemon
+/// on a heavily file descriptor constrained system runs at
+pub const FALLBACK_CONCURRENT_SCANS: usize = 5;
+/// Ports probed at once per host when the daemon hasn't reported its batch size
+pub const FALLBACK_PORT_BATCH_SIZE: usize = 10;
+
+/// A port that answers straight away
+const PROBE_SECS_MIN: f64 = 0.005;
+/// A port that times out twice, the daemon's connect timeout with one retry
+const PROBE_SECS_MAX: f64 = 1.6;
+
+/// Scans with more probes than this are flagged as very large
+pub const VERY_LARGE_PROBES: u64 = 10_000_000;
+/// Scans which may take longer than this are flagged as very large
+pub const VERY_LARGE_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Estimated scope and duration of a discovery, computed without running it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DiscoveryEstimate {
+    /// Addresses the discovery covers
+    pub targets: u64,
+    pub ports_per_target: u64,
+    /// Total port probes, targets times ports
+    pub probes: u64,
+    /// Duration if every probe is answered straight away at the fastest rate the scan may reach
+    pub min_duration_secs: u64,
+    /// Duration if every probe times out at the slowest rate the scan may run at
+    pub max_duration_secs: u64,
+    /// Whether the daemon reported its scan rate. When it hasn't, a conservative rate is assumed.
+    pub rate_known: bool,
+    pub very_large: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Number of addresses in `cidr`, saturating for IPv6 ranges too large to count
+fn address_count(cidr: &IpCidr) -> u64 {
+    let host_bits = u32::from(cidr.family().len() - cidr.network_length());
+    1u128
+        .checked_shl(host_bits)
+        .and_then(|count| u64::try_from(count).ok())
+        .unwrap_or(u64::MAX)
+}
+
+/// Seconds to scan `targets` hosts `concurrency` at a time, with each host's ports probed
+/// `port_batch_size` at a time and every probe taking `probe_secs`
+fn duration_secs(
+    targets: u64,
+    ports: u64,
+    concurrency: usize,
+    port_batch_size: usize,
+    probe_secs: f64,
+) -> u64 {
+    let waves = targets.div_ceil(concurrency.max(1) as u64);
+    let rounds = ports.div_ceil(port_batch_size.max(1) as u64);
+
+    (waves as f64 * rounds as f64 * probe_secs).ceil() as u64
+}
+
+/// Estimate a network discovery of `subnets`, probing `ports` ports per address at `scan_rate`
+/// on a daemon with `capabilities`
+pub fn estimate(
+    subnets: &[IpCidr],
+    ports: u64,
+    scan_rate: ScanRate,
+    capabilities: &DaemonCapabilities,
+) -> DiscoveryEstimate {
+    let targets = subnets.iter().fold(0u64, |total, cidr| {
+        total.saturating_add(address_count(cidr))
+    });
+
+    estimate_addresses(targets, ports, scan_rate, capabilities)
+}
+
+/// Estimate a network discovery of `targets` addresses, e.g. an explicit target list
+pub fn estimate_addresses(
+    targets: u64,
+    ports: u64,
+    scan_rate: ScanRate,
+    capabilities: &DaemonCapabilities,
+) -> DiscoveryEstimate {
+    let mut warnings = Vec::new();
+
+    let probes = targets.saturating_mul(ports);
+
+    let rate_known = capabilities.concurrent_scans.is_some();
+    if !rate_known {
+        warnings.push(format!(
+            "The daemon hasn't reported its scan rate, assuming a conservative {} concurrent hosts",
+            FALLBACK_CONCURRENT_SCANS
+        ));
+    }
+    let concurrent_scans = capabilities
+        .concurrent_scans
+        .unwrap_or(FALLBACK_CONCURRENT_SCANS)
+        .max(1);
+    let port_batch_size = capabilities
+        .port_batch_size
+        .unwrap_or(FALLBACK_PORT_BATCH_SIZE);
+
+    // Same bounds the daemon applies: an adaptive rate starts at `initial` and can climb to `max`
+    let (slowest, fastest) = match scan_rate.capped(concurrent_scans) {
+        ScanRate::Fixed => (concurrent_scans, concurrent_scans),
+        ScanRate::Adaptive { initial, max } => (initial, max),
+    };
+
+    let min_duration_secs = duration_secs(targets, ports, fastest, port_batch_size, PROBE_SECS_MIN);
+    let max_duration_secs = duration_secs(targets, ports, slowest, port_batch_size, PROBE_SECS_MAX);
+
+    if targets == 0 {
+        warnings.push("The discovery has no subnets to scan".to_string());
+    }
+
+    let very_large = probes >= VERY_LARGE_PROBES || max_duration_secs >= VERY_LARGE_SECS;
+    if very_large {
+        warnings.push(format!(
+            "Very large scan: {} probes across {} addresses could take up to {} hours. \
+             Consider narrowing the target subnets.",
+            probes,
+            targets,
+            max_duration_secs / 3600
+        ));
+    }
+
+    DiscoveryEstimate {
+        targets,
+        ports_per_target: ports,
+        probes,
+        min_duration_secs,
+        max_duration_secs,
+        rate_known,
+        very_large,
+        warnings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(s: &str) -> IpCidr {
+        s.parse().unwrap()
+    }
+
+    fn capabilities(concurrent_scans: usize, port_batch_size: usize) -> DaemonCapabilities {
+        DaemonCapabilities {
+            concurrent_scans: Some(concurrent_scans),
+            port_batch_size: Some(port_batch_size),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_estimate_for_known_range_ports_and_rate() {
+        let estimate = estimate(
+            &[cidr("192.168.1.0/24"), cidr("10.0.0.0/28")],
+            100,
+            ScanRate::Fixed,
+            &capabilities(16, 50),
+        );
+
+        assert_eq!(estimate.targets, 272);
+        assert_eq!(estimate.probes, 27_200);
+        // 17 waves of 16 hosts, 2 rounds of 50 ports each
+        assert_eq!(estimate.min_duration_secs, 1);
+        assert_eq!(estimate.max_duration_secs, 55);
+        assert!(estimate.rate_known);
+        assert!(!estimate.very_large);
+        assert!(estimate.warnings.is_empty());
+
+        // An adaptive rate is slowest at its initial concurrency: 64 waves of 4 hosts
+        let adaptive = super::estimate(
+            &[cidr("192.168.1.0/24")],
+            100,
+            ScanRate::Adaptive {
+                initial: 4,
+                max: 64,
+            },
+            &capabilities(16, 50),
+        );
+        assert_eq!(adaptive.max_duration_secs, 205);
+    }
+
+    #[test]
+    fn test_estimate_flags_very_large_and_unknown_rate() {
+        let estimate = estimate(
+            &[cidr("10.0.0.0/8")],
+            65_535,
+            ScanRate::Fixed,
+            &DaemonCapabilities::default(),
+        );
+
+        assert_eq!(estimate.targets, 1 << 24);
+        assert!(estimate.very_large);
+        assert!(!estimate.rate_known);
+        assert_eq!(estimate.warnings.len(), 2);
+
+        let ipv6 = super::estimate(
+            &[cidr("2001:db8::/32")],
+            10,
+            ScanRate::Fixed,
+            &capabilities(16, 50),
+        );
+        assert_eq!(ipv6.targets, u64::MAX);
+        assert!(ipv6.very_large);
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::server::config::ServerConfig;
+
+/// A network's share of the discovery concurrency cap, relative to the other networks' weights
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NetworkWeight {
+    pub network_id: Uuid,
+    pub weight: u32,
+}
+
+/// How many discovery sessions may run at once across all networks, and how the slots are shared
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FairSharePolicy {
+    /// None leaves discovery uncapped
+    pub max_concurrent: Option<usize>,
+    /// Networks without a weight have weight 1
+    pub weights: Vec<NetworkWeight>,
+}
+
+impl From<&ServerConfig> for FairSharePolicy {
+    fn from(config: &ServerConfig) -> Self {
+        Self {
+            max_concurrent: config.discovery_max_concurrent_sessions,
+            weights: config.discovery_network_weights.clone(),
+        }
+    }
+}
+
+impl FairSharePolicy {
+    fn weight(&self, network_id: Uuid) -> usize {
+        self.weights
+            .iter()
+            .find(|w| w.network_id == network_id)
+            .map_or(1, |w| w.weight.max(1) as usize)
+    }
+}
+
+/// Hands out discovery slots under the global cap so each network with work waiting gets a share
+/// in proportion to its weight. A waiting session goes to the network running the fewest sessions
+/// for its weight, ties going to the network served least recently, so contending networks
+/// interleave rather than one taking every slot. Only running sessions hold slots, so a network
+/// with nothing waiting never holds back the others, and with one network it's just the cap.
+#[derive(Debug, Default)]
+pub struct FairShareLimiter {
+    policy: FairSharePolicy,
+    /// Admitted session -> its network
+    running: HashMap<Uuid, Uuid>,
+    /// Sessions waiting for a slot per network, oldest first
+    waiting: HashMap<Uuid, VecDeque<Uuid>>,
+    /// When each network was last given a slot, in admissions
+    last_served: HashMap<Uuid, u64>,
+    admissions: u64,
+}
+
+impl FairShareLimiter {
+    pub fn new(policy: FairSharePolicy) -> Self {
+        Self {
+            policy,
+            ..Self::default()
+        }
+    }
+
+    /// Whether `session_id` holds a slot and can be handed to its daemon
+    pub fn is_admitted(&self, session_id: &Uuid) -> bool {
+        self.running.contains_key(session_id)
+    }
+
+    /// Ask for a slot for `session_id`, returning the sessions admitted as a result: either it,
+    /// or nothing if it has to wait
+    pub fn request(&mut self, network_id: Uuid, session_id: Uuid) -> Vec<Uuid> {
+        if !self.is_admitted(&session_id) {
+            self.waiting
+                .entry(network_id)
+                .or_default()
+                .push_back(session_id);
+        }
+        self.admit()
+    }
+
+    /// Give up `session_id`'s slot, or its place in line if it was still waiting, returning the
+    /// sessions admitted into the freed slot
+    pub fn release(&mut self, session_id: &Uuid) -> Vec<Uuid> {
+        if self.running.remove(session_id).is_none() {
+            self.waiting.retain(|_, queue| {
+                queue.retain(|id| id != session_id);
+                !queue.is_empty()
+            });
+        }
+        self.admit()
+    }
+
+    fn admit(&mut self) -> Vec<Uuid> {
+        let capacity = self.policy.max_concurrent.unwrap_or(usize::MAX);
+        let mut admitted = Vec::new();
+
+        while self.running.len() < capacity {
+            let mut running: HashMap<Uuid, usize> = HashMap::new();
+            for network_id in self.running.values() {
+                *running.entry(*network_id).or_default() += 1;
+            }
+
+            // Fewest running per weight, compared as running_a / weight_a < running_b / weight_b
+            let Some(network_id) = self.waiting.keys().copied().min_by(|a, b| {
+                let share =
+                    |n: &Uuid| (running.get(n).copied().unwrap_or(0), self.policy.weight(*n));
+                let ((running_a, weight_a), (running_b, weight_b)) = (share(a), share(b));
+                (running_a * weight_b)
+                    .cmp(&(running_b * weight_a))
+                    .then_with(|| self.last_served.get(a).cmp(&self.last_served.get(b)))
+                    .then_with(|| a.cmp(b))
+            }) else {
+                break;
+            };
+
+            let queue = self
+                .waiting
+                .get_mut(&network_id)
+                .expect("network is waiting");
+            let session_id = queue.pop_front().expect("waiting queues are never empty");
+            if queue.is_empty() {
+                self.waiting.remove(&network_id);
+            }
+
+            self.admissions += 1;
+            self.last_served.insert(network_id, self.admissions);
+            self.running.insert(session_id, network_id);
+            admitted.push(session_id);
+        }
+
+        admitted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Queue `count` sessions for `network_id`, returning them oldest first
+    fn queue(limiter: &mut FairShareLimiter, network_id: Uuid, count: usize) -> Vec<Uuid> {
+        (0..count)
+            .map(|_| {
+                let session_id = Uuid::new_v4();
+                limiter.request(network_id, session_id);
+                session_id
+            })
+            .collect()
+    }
+
+    /// Finish running sessions oldest first until none are left, returning the network of each
+    /// session in the order it was admitted
+    fn drain(limiter: &mut FairShareLimiter, mut running: VecDeque<Uuid>) -> Vec<Uuid> {
+        let mut order: Vec<Uuid> = running.iter().map(|id| limiter.running[id]).collect();
+        while let Some(finished) = running.pop_front() {
+            for admitted in limiter.release(&finished) {
+                order.push(limiter.running[&admitted]);
+                running.push_back(admitted);
+            }
+        }
+        order
+    }
+
+    fn running(limiter: &FairShareLimiter, sessions: &[Uuid]) -> VecDeque<Uuid> {
+        sessions
+            .iter()
+            .filter(|id| limiter.is_admitted(id))
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn test_contending_networks_interleave_under_small_cap() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut limiter = FairShareLimiter::new(FairSharePolicy {
+            max_concurrent: Some(2),
+            weights: Vec::new(),
+        });
+
+        // A queues first and takes both free slots, then B contends for them
+        let a_sessions = queue(&mut limiter, a, 4);
+        let b_sessions = queue(&mut limiter, b, 4);
+        assert!(b_sessions.iter().all(|id| !limiter.is_admitted(id)));
+
+        let in_flight = running(&limiter, &a_sessions);
+        let order = drain(&mut limiter, in_flight);
+        assert_eq!(order, vec![a, a, b, a, b, a, b, b]);
+    }
+
+    #[test]
+    fn test_weights_share_slots_proportionally() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut limiter = FairShareLimiter::new(FairSharePolicy {
+            max_concurrent: Some(4),
+            weights: vec![NetworkWeight {
+                network_id: b,
+                weight: 3,
+            }],
+        });
+
+        let a_sessions = queue(&mut limiter, a, 8);
+        let b_sessions = queue(&mut limiter, b, 8);
+        let mut in_flight = running(&limiter, &a_sessions);
+        in_flight.extend(running(&limiter, &b_sessions));
+
+        // Once A's head start has run down, B holds three of the four slots
+        let order = drain(&mut limiter, in_flight);
+        let steady: Vec<_> = order[8..12].to_vec();
+        assert_eq!(steady.iter().filter(|n| **n == b).count(), 3);
+    }
+
+    #[test]
+    fn test_single_network_behaves_like_global_cap() {
+        let network_id = Uuid::new_v4();
+        let mut limiter = FairShareLimiter::new(FairSharePolicy {
+            max_concurrent: Some(2),
+            weights: Vec::new(),
+        });
+
+        let sessions = queue(&mut limiter, network_id, 3);
+        assert!(limiter.is_admitted(&sessions[0]));
+        assert!(limiter.is_admitted(&sessions[1]));
+        assert!(!limiter.is_admitted(&sessions[2]));
+
+        // A waiting session that's cancelled frees nothing; a running one frees its slot
+        assert!(limiter.release(&sessions[2]).is_empty());
+        let next = queue(&mut limiter, network_id, 1)[0];
+        assert_eq!(limiter.release(&sessions[0]), vec![next]);
+
+        // Uncapped admits everything straight away
+        let mut uncapped = FairShareLimiter::default();
+        let sessions = queue(&mut uncapped, network_id, 10);
+        assert!(sessions.iter().all(|id| uncapped.is_admitted(id)));
+    }
+}
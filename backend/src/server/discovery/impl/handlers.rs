@@ -1,6 +1,6 @@
 use crate::server::discovery::r#impl::base::Discovery;
 use crate::server::discovery::service::DiscoveryService;
-use crate::server::shared::handlers::traits::CrudHandlers;
+use crate::server::shared::handlers::traits::{CrudHandlers, EntityScope};
 
 impl CrudHandlers for Discovery {
     type Service = DiscoveryService;
@@ -8,4 +8,8 @@ impl CrudHandlers for Discovery {
     fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
         &state.services.discovery_service
     }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.base.network_id)
+    }
 }
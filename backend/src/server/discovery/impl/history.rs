@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    daemon::discovery::types::base::DiscoveryPhase,
+    server::discovery::r#impl::types::DiscoveryTypeDiscriminants,
+};
+
+/// Most transitions kept per session. Older ones are dropped first, which doesn't change the
+/// replayed phase.
+pub const MAX_SESSION_TRANSITIONS: usize = 64;
+
+/// Days after its last transition that an unfinished session's history is pruned. A session
+/// that hasn't moved for this long has been lost, e.g. along with its daemon, and won't finish.
+pub const STALLED_SESSION_DAYS: i64 = 7;
+
+/// Who moved a session to a new phase
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionActor {
+    User {
+        user_id: Uuid,
+    },
+    Daemon {
+        daemon_id: Uuid,
+    },
+    /// The server itself, e.g. a scheduled run or a session that couldn't be dispatched
+    Server,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SessionTransition {
+    pub at: DateTime<Utc>,
+    /// None for the transition that created the session
+    pub from: Option<DiscoveryPhase>,
+    pub to: DiscoveryPhase,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub actor: SessionActor,
+}
+
+/// Append-only log of a discovery session's phase transitions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionHistory {
+    pub session_id: Uuid,
+    pub network_id: Uuid,
+    /// None for imports
+    pub daemon_id: Option<Uuid>,
+    pub discovery_type: DiscoveryTypeDiscriminants,
+    pub transitions: VecDeque<SessionTransition>,
+    /// Transitions dropped to keep the log bounded
+    #[serde(default)]
+    pub dropped: usize,
+}
+
+/// The phase a session ends up in after `transitions`, in order
+pub fn replay<'a>(
+    transitions: impl IntoIterator<Item = &'a SessionTransition>,
+) -> Option<DiscoveryPhase> {
+    transitions
+        .into_iter()
+        .fold(None, |_, transition| Some(transition.to))
+}
+
+impl SessionHistory {
+    pub fn new(
+        session_id: Uuid,
+        network_id: Uuid,
+        daemon_id: Option<Uuid>,
+        discovery_type: DiscoveryTypeDiscriminants,
+    ) -> Self {
+        Self {
+            session_id,
+            network_id,
+            daemon_id,
+            discovery_type,
+            transitions: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Current phase of the session, replayed from its log
+    pub fn phase(&self) -> Option<DiscoveryPhase> {
+        replay(&self.transitions)
+    }
+
+    /// When the session reached a terminal phase, if it has
+    pub fn finished_at(&self) -> Option<DateTime<Utc>> {
+        self.transitions
+            .back()
+            .filter(|t| {
+                matches!(
+                    t.to,
+                    DiscoveryPhase::Complete
+                        | DiscoveryPhase::Failed
+                        | DiscoveryPhase::Cancelled
+                        | DiscoveryPhase::CancelledWithPartialResults
+                )
+            })
+            .map(|t| t.at)
+    }
+
+    /// When the session last changed phase
+    pub fn last_transition_at(&self) -> Option<DateTime<Utc>> {
+        self.transitions.back().map(|t| t.at)
+    }
+
+    /// Whether the session is unfinished and hasn't changed phase since `cutoff`
+    pub fn stalled_since(&self, cutoff: DateTime<Utc>) -> bool {
+        self.finished_at().is_none()
+            && self
+                .last_transition_at()
+                .is_some_and(|last_transition_at| last_transition_at < cutoff)
+    }
+
+    /// Append a transition to `to`. Updates that don't change the phase, like progress reports,
+    /// aren't transitions and are ignored; returns whether anything was recorded.
+    pub fn record(
+        &mut self,
+        to: DiscoveryPhase,
+        reason: Option<String>,
+        actor: SessionActor,
+        at: DateTime<Utc>,
+    ) -> bool {
+        let from = self.phase();
+        if from == Some(to) {
+            return false;
+        }
+
+        if self.transitions.len() >= MAX_SESSION_TRANSITIONS {
+            self.transitions.pop_front();
+            self.dropped += 1;
+        }
+        self.transitions.push_back(SessionTransition {
+            at,
+            from,
+            to,
+            reason,
+            actor,
+        });
+
+        true
+    }
+}
@@ -0,0 +1,987 @@
+use mac_address::MacAddress;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    net::IpAddr,
+};
+use uuid::Uuid;
+
+use crate::{
+    daemon::utils::network_checks::ndp::{Ipv6Scope, NdpNeighbor},
+    server::{
+        daemons::r#impl::api::DiscoveryUpdatePayload,
+        hosts::r#impl::{
+            base::{Host, HostBase, HostState},
+            identity::{canonical_ip, parse_mac},
+            interfaces::{Interface, InterfaceBase},
+            ports::{Port, PortBase, TransportProtocol},
+            targets::HostTarget,
+        },
+        shared::types::entities::{DiscoveryMetadata, EntitySource},
+        subnets::r#impl::base::Subnet,
+    },
+};
+
+/// Format of a scan result file produced outside NetVisor
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    /// `nmap -oX`
+    #[default]
+    NmapXml,
+    /// `nmap -oG`
+    NmapGreppable,
+}
+
+impl Display for ImportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportFormat::NmapXml => write!(f, "Nmap XML"),
+            ImportFormat::NmapGreppable => write!(f, "Nmap greppable"),
+        }
+    }
+}
+
+impl ImportFormat {
+    /// Guess the format of `input` from its first non-blank character
+    pub fn detect(input: &[u8]) -> Self {
+        match input.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'<') => ImportFormat::NmapXml,
+            _ => ImportFormat::NmapGreppable,
+        }
+    }
+}
+
+/// Where and why an imported file couldn't be parsed. Lines and columns count from 1.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{message} at line {line}, column {column}")]
+pub struct ImportParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl ImportParseError {
+    fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedPort {
+    pub number: u16,
+    pub protocol: TransportProtocol,
+    /// Service name the scanner guessed for the port, e.g. `ssh`
+    pub service: Option<String>,
+}
+
+/// A host reported up by an imported scan, with its open ports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedHost {
+    pub ip: IpAddr,
+    pub mac: Option<MacAddress>,
+    pub hostname: Option<String>,
+    pub ports: Vec<ImportedPort>,
+}
+
+impl ImportedHost {
+    /// The host as discovery would have reported it, or None when its address isn't in any of
+    /// the network's subnets
+    pub fn to_host(
+        &self,
+        network_id: Uuid,
+        subnets: &[Subnet],
+        metadata: DiscoveryMetadata,
+    ) -> Option<Host> {
+        let subnet = subnets
+            .iter()
+            .filter(|s| s.base.network_id == network_id)
+            .find(|s| s.base.cidr.contains(&self.ip))?;
+
+        let (name, target) = match &self.hostname {
+            Some(hostname) => (hostname.clone(), HostTarget::Hostname),
+            None => (self.ip.to_string(), HostTarget::None),
+        };
+
+        Some(Host::new(HostBase {
+            name,
+            network_id,
+            hostname: self.hostname.clone(),
+            description: None,
+            target,
+            interfaces: vec![Interface::new(InterfaceBase {
+                subnet_id: subnet.id,
+                ip_address: self.ip,
+                mac_address: self.mac,
+                name: None,
+            })],
+            services: Vec::new(),
+            ports: self
+                .ports
+                .iter()
+                .map(|p| Port::new(PortBase::new(p.number, p.protocol)))
+                .collect(),
+            source: EntitySource::Discovery {
+                metadata: vec![metadata],
+            },
+            virtualization: None,
+            hidden: false,
+            state: HostState::Pending,
+            group_ids: Vec::new(),
+            tags: Vec::new(),
+            ip_conflicts: Vec::new(),
+        }))
+    }
+}
+
+/// Hosts for the neighbors an NDP scan found, one per link-layer address with an interface for
+/// each of its addresses, named after the address's scope. Link-local addresses are in no subnet,
+/// so go on `segment`, the subnet of the interface the scan ran from. Other addresses outside
+/// every subnet of the network are returned as skipped, as are link-local ones without a segment.
+pub fn ndp_hosts(
+    neighbors: &[NdpNeighbor],
+    network_id: Uuid,
+    subnets: &[Subnet],
+    segment: Option<&Subnet>,
+    metadata: DiscoveryMetadata,
+) -> (Vec<Host>, Vec<IpAddr>) {
+    let mut by_mac: BTreeMap<MacAddress, Vec<&NdpNeighbor>> = BTreeMap::new();
+    for neighbor in neighbors {
+        by_mac.entry(neighbor.mac).or_default().push(neighbor);
+    }
+
+    let mut hosts = Vec::new();
+    let mut skipped = Vec::new();
+
+    for (mac, neighbors) in by_mac {
+        let mut interfaces = Vec::new();
+        for neighbor in &neighbors {
+            let ip = IpAddr::V6(neighbor.ip);
+            let subnet = match neighbor.scope {
+                Ipv6Scope::LinkLocal => segment,
+                Ipv6Scope::UniqueLocal | Ipv6Scope::Global => subnets
+                    .iter()
+                    .filter(|s| s.base.network_id == network_id)
+                    .find(|s| s.base.cidr.contains(&ip)),
+            };
+
+            match subnet {
+                Some(subnet) => interfaces.push(Interface::new(InterfaceBase {
+                    subnet_id: subnet.id,
+                    ip_address: ip,
+                    mac_address: Some(mac),
+                    name: Some(neighbor.scope.to_string()),
+                })),
+                None => skipped.push(ip),
+            }
+        }
+
+        // Prefer a routable address to name the host by
+        let Some(primary) = interfaces
+            .iter()
+            .find(|i| !matches!(i.base.ip_address, IpAddr::V6(ip) if ip.is_unicast_link_local()))
+            .or(interfaces.first())
+            .map(|i| i.base.ip_address)
+        else {
+            continue;
+        };
+
+        hosts.push(Host::new(HostBase {
+            name: primary.to_string(),
+            network_id,
+            hostname: None,
+            description: neighbors
+                .iter()
+                .any(|n| n.router)
+                .then(|| "IPv6 router".to_string()),
+            target: HostTarget::None,
+            interfaces,
+            services: Vec::new(),
+            ports: Vec::new(),
+            source: EntitySource::Discovery {
+                metadata: vec![metadata.clone()],
+            },
+            virtualization: None,
+            hidden: false,
+            state: HostState::Pending,
+            group_ids: Vec::new(),
+            tags: Vec::new(),
+            ip_conflicts: Vec::new(),
+        }));
+    }
+
+    (hosts, skipped)
+}
+
+/// Outcome of importing a scan result file as a discovery session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveryImportResult {
+    pub session: DiscoveryUpdatePayload,
+    pub imported: Vec<Uuid>,
+    /// Addresses of hosts outside every subnet of the network, which weren't imported
+    pub skipped: Vec<IpAddr>,
+}
+
+/// Parse the hosts which are up, and their open ports, from a scan result file
+pub fn parse(input: &[u8], format: ImportFormat) -> Result<Vec<ImportedHost>, ImportParseError> {
+    let input = std::str::from_utf8(input).map_err(|e| {
+        let (line, column) = position(&input[..e.valid_up_to()]);
+        ImportParseError::new(line, column, "Invalid UTF-8")
+    })?;
+
+    match format {
+        ImportFormat::NmapXml => parse_nmap_xml(input),
+        ImportFormat::NmapGreppable => parse_nmap_greppable(input),
+    }
+}
+
+/// Line and column just past the end of `consumed`
+fn position(consumed: &[u8]) -> (usize, usize) {
+    let line = consumed.iter().filter(|b| **b == b'\n').count() + 1;
+    let line_start = consumed
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let column = String::from_utf8_lossy(&consumed[line_start..])
+        .chars()
+        .count()
+        + 1;
+
+    (line, column)
+}
+
+fn parse_protocol(protocol: &str) -> Option<TransportProtocol> {
+    match protocol {
+        "tcp" => Some(TransportProtocol::Tcp),
+        "udp" => Some(TransportProtocol::Udp),
+        _ => None,
+    }
+}
+
+fn parse_nmap_xml(input: &str) -> Result<Vec<ImportedHost>, ImportParseError> {
+    let root = XmlReader::new(input).read_document()?;
+    if root.name != "nmaprun" {
+        return Err(root.error(format!(
+            "Expected an <nmaprun> document, found <{}>",
+            root.name
+        )));
+    }
+
+    let mut hosts = Vec::new();
+    for element in root.children_named("host") {
+        let is_up = element
+            .children_named("status")
+            .next()
+            .and_then(|s| s.attr("state"))
+            .is_none_or(|state| state == "up");
+        if !is_up {
+            continue;
+        }
+
+        let mut ip = None;
+        let mut mac = None;
+        for address in element.children_named("address") {
+            let addr = address.required_attr("addr")?;
+            match address.attr("addrtype") {
+                Some("ipv4" | "ipv6") => {
+                    ip = Some(canonical_ip(addr.parse::<IpAddr>().map_err(|_| {
+                        address.error(format!("Invalid IP address '{}'", addr))
+                    })?));
+                }
+                Some("mac") => {
+                    mac =
+                        Some(parse_mac(addr).map_err(|_| {
+                            address.error(format!("Invalid MAC address '{}'", addr))
+                        })?);
+                }
+                _ => {}
+            }
+        }
+        let Some(ip) = ip else {
+            continue;
+        };
+
+        // Prefer a name the user gave nmap over one it looked up
+        let hostnames: Vec<&XmlElement> = element
+            .children_named("hostnames")
+            .flat_map(|h| h.children_named("hostname"))
+            .collect();
+        let hostname = hostnames
+            .iter()
+            .find(|h| h.attr("type") == Some("user"))
+            .or(hostnames.first())
+            .and_then(|h| h.attr("name"))
+            .map(str::to_string);
+
+        let mut ports = Vec::new();
+        for port in element
+            .children_named("ports")
+            .flat_map(|p| p.children_named("port"))
+        {
+            let is_open = port
+                .children_named("state")
+                .next()
+                .and_then(|s| s.attr("state"))
+                == Some("open");
+            let Some(protocol) = parse_protocol(port.required_attr("protocol")?) else {
+                continue;
+            };
+            if !is_open {
+                continue;
+            }
+
+            let portid = port.required_attr("portid")?;
+            let number = portid
+                .parse::<u16>()
+                .ok()
+                .filter(|n| *n != 0)
+                .ok_or_else(|| port.error(format!("Invalid port number '{}'", portid)))?;
+            let service = port
+                .children_named("service")
+                .next()
+                .and_then(|s| s.attr("name"))
+                .map(str::to_string);
+
+            ports.push(ImportedPort {
+                number,
+                protocol,
+                service,
+            });
+        }
+
+        hosts.push(ImportedHost {
+            ip,
+            mac,
+            hostname,
+            ports,
+        });
+    }
+
+    Ok(hosts)
+}
+
+fn parse_nmap_greppable(input: &str) -> Result<Vec<ImportedHost>, ImportParseError> {
+    // A host gets a line for its status and another for its ports, so merge them by address
+    let mut hosts: Vec<ImportedHost> = Vec::new();
+    let mut index: HashMap<IpAddr, usize> = HashMap::new();
+    let mut down: Vec<IpAddr> = Vec::new();
+
+    for (i, line) in input.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut offset = 0;
+        let mut fields = Vec::new();
+        for field in line.split('\t') {
+            fields.push((offset, field));
+            offset += field.len() + 1;
+        }
+        let column = |offset: usize| line[..offset].chars().count() + 1;
+
+        let (_, first) = fields[0];
+        let Some(host_field) = first.strip_prefix("Host: ") else {
+            return Err(ImportParseError::new(
+                line_number,
+                1,
+                "Expected a 'Host:' line",
+            ));
+        };
+        let (ip, hostname) = match host_field.split_once(' ') {
+            Some((ip, rest)) => (
+                ip,
+                rest.trim().trim_start_matches('(').trim_end_matches(')'),
+            ),
+            None => (host_field, ""),
+        };
+        let ip = canonical_ip(ip.parse::<IpAddr>().map_err(|_| {
+            ImportParseError::new(
+                line_number,
+                column("Host: ".len()),
+                format!("Invalid IP address '{}'", ip),
+            )
+        })?);
+
+        let host = *index.entry(ip).or_insert_with(|| {
+            hosts.push(ImportedHost {
+                ip,
+                mac: None,
+                hostname: None,
+                ports: Vec::new(),
+            });
+            hosts.len() - 1
+        });
+        let host = &mut hosts[host];
+        if !hostname.is_empty() {
+            host.hostname = Some(hostname.to_string());
+        }
+
+        for (field_offset, field) in &fields[1..] {
+            if let Some(status) = field.strip_prefix("Status: ") {
+                if status.trim() != "Up" {
+                    down.push(ip);
+                }
+            } else if let Some(list) = field.strip_prefix("Ports: ") {
+                let mut entry_offset = field_offset + "Ports: ".len();
+                for entry in list.split(',') {
+                    let start = entry_offset + (entry.len() - entry.trim_start().len());
+                    entry_offset += entry.len() + 1;
+
+                    // number/state/protocol/owner/service/rpc info/version/
+                    let parts: Vec<&str> = entry.trim().split('/').collect();
+                    if parts.len() < 5 {
+                        return Err(ImportParseError::new(
+                            line_number,
+                            column(start),
+                            format!("Malformed port entry '{}'", entry.trim()),
+                        ));
+                    }
+                    let number = parts[0]
+                        .parse::<u16>()
+                        .ok()
+                        .filter(|n| *n != 0)
+                        .ok_or_else(|| {
+                            ImportParseError::new(
+                                line_number,
+                                column(start),
+                                format!("Invalid port number '{}'", parts[0]),
+                            )
+                        })?;
+                    let Some(protocol) = parse_protocol(parts[2]) else {
+                        continue;
+                    };
+                    if parts[1] != "open" {
+                        continue;
+                    }
+
+                    host.ports.push(ImportedPort {
+                        number,
+                        protocol,
+                        service: Some(parts[4]).filter(|s| !s.is_empty()).map(str::to_string),
+                    });
+                }
+            }
+        }
+    }
+
+    hosts.retain(|h| !down.contains(&h.ip));
+    Ok(hosts)
+}
+
+/// Element of a parsed XML document, with where it started for error reporting
+#[derive(Debug)]
+struct XmlElement {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<XmlElement>,
+    line: usize,
+    column: usize,
+}
+
+impl XmlElement {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn required_attr(&self, name: &str) -> Result<&str, ImportParseError> {
+        self.attr(name).ok_or_else(|| {
+            self.error(format!(
+                "<{}> is missing its '{}' attribute",
+                self.name, name
+            ))
+        })
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlElement> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    fn error(&self, message: String) -> ImportParseError {
+        ImportParseError::new(self.line, self.column, message)
+    }
+}
+
+/// Just enough of an XML parser for scanner output: elements, attributes, comments, processing
+/// instructions, a doctype and CDATA. Text content is skipped. Elements are kept on an explicit
+/// stack rather than parsed recursively so deeply nested input can't overflow the stack.
+struct XmlReader<'a> {
+    input: &'a str,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> XmlReader<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input: input.strip_prefix('\u{feff}').unwrap_or(input),
+            pos: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> ImportParseError {
+        ImportParseError::new(self.line, self.column, message)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            s.chars().for_each(|_| {
+                self.bump();
+            });
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), ImportParseError> {
+        if self.eat(s) {
+            Ok(())
+        } else {
+            Err(self.error(match self.peek() {
+                Some(c) => format!("Expected '{}', found '{}'", s, c),
+                None => format!("Expected '{}', found end of document", s),
+            }))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    /// Consume up to and including `end`, returning what came before it
+    fn take_until(&mut self, end: &str, what: &str) -> Result<&'a str, ImportParseError> {
+        let (line, column) = (self.line, self.column);
+        let start = self.pos;
+        let Some(len) = self.rest().find(end) else {
+            return Err(ImportParseError::new(
+                line,
+                column,
+                format!("Unterminated {}", what),
+            ));
+        };
+
+        while self.pos < start + len {
+            self.bump();
+        }
+        self.eat(end);
+        Ok(&self.input[start..start + len])
+    }
+
+    fn read_name(&mut self) -> Result<&'a str, ImportParseError> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+        {
+            self.bump();
+        }
+
+        if self.pos == start {
+            return Err(self.error(match self.peek() {
+                Some(c) => format!("Expected a name, found '{}'", c),
+                None => "Expected a name, found end of document".to_string(),
+            }));
+        }
+        Ok(&self.input[start..self.pos])
+    }
+
+    /// Skip a comment, processing instruction, doctype or CDATA section, if one starts here
+    fn skip_markup(&mut self) -> Result<bool, ImportParseError> {
+        if self.eat("<!--") {
+            self.take_until("-->", "comment")?;
+        } else if self.eat("<![CDATA[") {
+            self.take_until("]]>", "CDATA section")?;
+        } else if self.eat("<?") {
+            self.take_until("?>", "processing instruction")?;
+        } else if self.eat("<!DOCTYPE") {
+            self.take_until(">", "doctype")?;
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn read_attribute_value(&mut self) -> Result<String, ImportParseError> {
+        let quote = match self.peek() {
+            Some(q @ ('"' | '\'')) => q,
+            _ => return Err(self.error("Expected a quoted attribute value")),
+        };
+        self.bump();
+
+        let (line, column) = (self.line, self.column);
+        let raw = self.take_until(&quote.to_string(), "attribute value")?;
+        if raw.contains('<') {
+            return Err(ImportParseError::new(
+                line,
+                column,
+                "'<' is not allowed in attribute values",
+            ));
+        }
+
+        decode_entities(raw).map_err(|message| ImportParseError::new(line, column, message))
+    }
+
+    /// Read a start tag after its `<`, returning the element and whether it was self-closing
+    fn read_start_tag(&mut self) -> Result<(XmlElement, bool), ImportParseError> {
+        let (line, column) = (self.line, self.column - 1);
+        let name = self.read_name()?.to_string();
+        let mut attributes = Vec::new();
+
+        loop {
+            let had_space = self.peek().is_some_and(char::is_whitespace);
+            self.skip_whitespace();
+
+            if self.eat("/>") {
+                return Ok((
+                    XmlElement {
+                        name,
+                        attributes,
+                        children: Vec::new(),
+                        line,
+                        column,
+                    },
+                    true,
+                ));
+            }
+            if self.eat(">") {
+                return Ok((
+                    XmlElement {
+                        name,
+                        attributes,
+                        children: Vec::new(),
+                        line,
+                        column,
+                    },
+                    false,
+                ));
+            }
+            if self.peek().is_none() {
+                return Err(self.error(format!("Unterminated <{}> tag", name)));
+            }
+            if !had_space {
+                return Err(self.error("Expected whitespace between attributes"));
+            }
+
+            let attribute = self.read_name()?.to_string();
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let value = self.read_attribute_value()?;
+            attributes.push((attribute, value));
+        }
+    }
+
+    fn read_document(mut self) -> Result<XmlElement, ImportParseError> {
+        let mut stack: Vec<XmlElement> = Vec::new();
+        let mut root = None;
+
+        loop {
+            if stack.is_empty() {
+                self.skip_whitespace();
+                if self.peek().is_none() {
+                    break;
+                }
+                if self.skip_markup()? {
+                    continue;
+                }
+                if root.is_some() {
+                    return Err(self.error("Unexpected content after the root element"));
+                }
+                self.expect("<")?;
+            } else {
+                // Text content isn't needed
+                while self.peek().is_some_and(|c| c != '<') {
+                    self.bump();
+                }
+                if self.peek().is_none() {
+                    let open = stack.last().map(|e| e.name.as_str()).unwrap_or_default();
+                    return Err(self.error(format!(
+                        "Unexpected end of document, <{}> is not closed",
+                        open
+                    )));
+                }
+                if self.skip_markup()? {
+                    continue;
+                }
+
+                if self.eat("</") {
+                    let (line, column) = (self.line, self.column - 2);
+                    let name = self.read_name()?;
+                    self.skip_whitespace();
+                    self.expect(">")?;
+
+                    let element = stack.pop().unwrap_or_else(|| unreachable!());
+                    if element.name != name {
+                        return Err(ImportParseError::new(
+                            line,
+                            column,
+                            format!(
+                                "Closing </{}> does not match <{}> opened at line {}, column {}",
+                                name, element.name, element.line, element.column
+                            ),
+                        ));
+                    }
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(element),
+                        None => root = Some(element),
+                    }
+                    continue;
+                }
+                self.expect("<")?;
+            }
+
+            let (element, closed) = self.read_start_tag()?;
+            match (closed, stack.last_mut()) {
+                (false, _) => stack.push(element),
+                (true, Some(parent)) => parent.children.push(element),
+                (true, None) => root = Some(element),
+            }
+        }
+
+        root.ok_or_else(|| self.error("Document has no root element"))
+    }
+}
+
+fn decode_entities(raw: &str) -> Result<String, String> {
+    if !raw.contains('&') {
+        return Ok(raw.to_string());
+    }
+
+    let mut decoded = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find(';') else {
+            return Err("Unterminated entity reference".to_string());
+        };
+        let entity = &rest[start + 1..start + end];
+
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse::<u32>()))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        decoded.push(c.ok_or_else(|| format!("Unknown entity '&{};'", entity))?);
+        rest = &rest[start + end + 1..];
+    }
+    decoded.push_str(rest);
+
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE nmaprun>
+<nmaprun scanner="nmap" args="nmap -oX - 192.168.1.0/24">
+  <host>
+    <status state="up" reason="arp-response"/>
+    <address addr="192.168.1.10" addrtype="ipv4"/>
+    <address addr="AA:BB:CC:DD:EE:FF" addrtype="mac" vendor="Acme &amp; Co"/>
+    <hostnames><hostname name="nas.lan" type="PTR"/></hostnames>
+    <ports>
+      <extraports state="closed" count="997"/>
+      <port protocol="tcp" portid="22"><state state="open"/><service name="ssh"/></port>
+      <port protocol="tcp" portid="25"><state state="filtered"/></port>
+      <port protocol="udp" portid="161"><state state="open"/><service name="snmp"/></port>
+    </ports>
+  </host>
+  <host><status state="down"/><address addr="192.168.1.11" addrtype="ipv4"/></host>
+</nmaprun>
+"#;
+
+    #[test]
+    fn test_nmap_formats_parse_to_same_hosts() {
+        let hosts = parse(XML.as_bytes(), ImportFormat::NmapXml).unwrap();
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].ip, "192.168.1.10".parse::<IpAddr>().unwrap());
+        assert_eq!(hosts[0].hostname.as_deref(), Some("nas.lan"));
+        assert!(hosts[0].mac.is_some());
+
+        let greppable = "# Nmap 7.94 scan initiated\n\
+            Host: 192.168.1.10 (nas.lan)\tStatus: Up\n\
+            Host: 192.168.1.10 (nas.lan)\tPorts: 22/open/tcp//ssh///, 25/filtered/tcp//smtp///, 161/open/udp//snmp///\tIgnored State: closed (997)\n\
+            Host: 192.168.1.11 ()\tStatus: Down\n";
+        let grepped = parse(greppable.as_bytes(), ImportFormat::NmapGreppable).unwrap();
+        assert_eq!(
+            grepped,
+            vec![ImportedHost {
+                mac: None,
+                ..hosts[0].clone()
+            }]
+        );
+        assert_eq!(
+            grepped[0].ports,
+            vec![
+                ImportedPort {
+                    number: 22,
+                    protocol: TransportProtocol::Tcp,
+                    service: Some("ssh".to_string()),
+                },
+                ImportedPort {
+                    number: 161,
+                    protocol: TransportProtocol::Udp,
+                    service: Some("snmp".to_string()),
+                },
+            ]
+        );
+
+        assert_eq!(ImportFormat::detect(XML.as_bytes()), ImportFormat::NmapXml);
+        assert_eq!(
+            ImportFormat::detect(greppable.as_bytes()),
+            ImportFormat::NmapGreppable
+        );
+    }
+
+    #[test]
+    fn test_malformed_input_reports_location() {
+        let mismatched = "<nmaprun>\n  <host>\n  </hots>\n</nmaprun>";
+        let err = parse(mismatched.as_bytes(), ImportFormat::NmapXml).unwrap_err();
+        assert_eq!((err.line, err.column), (3, 3));
+        assert!(err.message.contains("opened at line 2, column 3"));
+
+        let unclosed = "<nmaprun>\n<host>";
+        let err = parse(unclosed.as_bytes(), ImportFormat::NmapXml).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 7));
+
+        let unquoted = "<nmaprun>\n  <host state=up/>\n</nmaprun>";
+        let err = parse(unquoted.as_bytes(), ImportFormat::NmapXml).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 15));
+
+        let bad_port = "<nmaprun><host><address addr=\"10.0.0.1\" addrtype=\"ipv4\"/>\n<ports><port protocol=\"tcp\" portid=\"99999\"><state state=\"open\"/></port></ports></host></nmaprun>";
+        let err = parse(bad_port.as_bytes(), ImportFormat::NmapXml).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 8));
+
+        let err = parse(
+            b"Host: 10.0.0.1 ()\tPorts: 22/open/tcp//ssh///, x/open/tcp////",
+            ImportFormat::NmapGreppable,
+        )
+        .unwrap_err();
+        assert_eq!((err.line, err.column), (1, 47));
+
+        let err = parse(b"<nmaprun>\n\xff</nmaprun>", ImportFormat::NmapXml).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+    }
+
+    #[test]
+    fn test_ndp_neighbors_keep_scope_and_link_local_lands_on_segment() {
+        let network_id = Uuid::new_v4();
+        let v6_subnet = |cidr: &str| {
+            let mut subnet = crate::tests::subnet(&network_id);
+            subnet.base.cidr = cidr.parse().unwrap();
+            subnet
+        };
+        let segment = v6_subnet("2001:db8:1::/64");
+        let subnets = vec![segment.clone(), v6_subnet("fd00:1::/64")];
+
+        let neighbor = |ip: &str, mac: u8, router: bool| {
+            let ip: std::net::Ipv6Addr = ip.parse().unwrap();
+            NdpNeighbor {
+                ip,
+                mac: MacAddress::new([2, 0, 0, 0, 0, mac]),
+                scope: Ipv6Scope::from(&ip),
+                router,
+            }
+        };
+        let neighbors = vec![
+            neighbor("fe80::1", 1, true),
+            neighbor("2001:db8:1::1", 1, false),
+            neighbor("fd00:1::1", 1, false),
+            neighbor("fe80::2", 2, false),
+            neighbor("2001:db8:9::2", 2, false),
+        ];
+        let metadata = DiscoveryMetadata::new(
+            crate::server::discovery::r#impl::types::DiscoveryType::Ndp {
+                interface: "eth0".to_string(),
+            },
+            Some(Uuid::new_v4()),
+        );
+
+        let (hosts, skipped) = ndp_hosts(
+            &neighbors,
+            network_id,
+            &subnets,
+            Some(&segment),
+            metadata.clone(),
+        );
+        assert_eq!(skipped, vec!["2001:db8:9::2".parse::<IpAddr>().unwrap()]);
+        assert_eq!(hosts.len(), 2);
+
+        // One host per link-layer address, named by a routable address and labelled a router
+        let router = &hosts[0];
+        assert_eq!(router.base.name, "2001:db8:1::1");
+        assert_eq!(router.base.description.as_deref(), Some("IPv6 router"));
+        let interfaces: Vec<_> = router
+            .base
+            .interfaces
+            .iter()
+            .map(|i| (i.base.name.as_deref().unwrap(), i.base.subnet_id))
+            .collect();
+        assert_eq!(
+            interfaces,
+            vec![
+                ("link-local", segment.id),
+                ("global", segment.id),
+                ("unique-local", subnets[1].id),
+            ]
+        );
+
+        // A neighbor seen only by its link-local address is still captured on the segment
+        let other = &hosts[1];
+        assert_eq!(other.base.name, "fe80::2");
+        assert_eq!(other.base.description, None);
+        assert_eq!(other.base.interfaces.len(), 1);
+        assert_eq!(other.base.interfaces[0].base.subnet_id, segment.id);
+
+        // Without a segment link-local addresses have nowhere to go
+        let (hosts, skipped) = ndp_hosts(&neighbors, network_id, &subnets, None, metadata);
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(skipped.len(), 3);
+    }
+}
@@ -1,4 +1,10 @@
 pub mod base;
+pub mod estimate;
+pub mod fair_share;
 pub mod handlers;
+pub mod history;
+pub mod import;
+pub mod retention;
 pub mod storage;
+pub mod target_policy;
 pub mod types;
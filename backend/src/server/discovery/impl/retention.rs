@@ -0,0 +1,151 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::server::{config::ServerConfig, discovery::r#impl::types::DiscoveryTypeDiscriminants};
+
+/// Longest a finished session's live state is held in memory. Its stored result is what's kept
+/// for the retention period.
+pub const SESSION_STATE_HOURS: u64 = 24;
+
+/// How long finished sessions of one discovery type are kept
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SessionRetentionRule {
+    pub discovery_type: DiscoveryTypeDiscriminants,
+    pub hours: u64,
+}
+
+/// How long finished discovery sessions, their histories and their results are kept
+#[derive(Debug, Clone)]
+pub struct SessionRetentionPolicy {
+    /// Retention of sessions no rule matches. None keeps them forever.
+    pub default_hours: Option<u64>,
+    /// The first rule for a session's type wins
+    pub rules: Vec<SessionRetentionRule>,
+    /// Never prune each daemon's most recent successful session, however old
+    pub keep_latest_successful: bool,
+}
+
+impl Default for SessionRetentionPolicy {
+    fn default() -> Self {
+        Self {
+            default_hours: None,
+            rules: Vec::new(),
+            keep_latest_successful: true,
+        }
+    }
+}
+
+impl From<&ServerConfig> for SessionRetentionPolicy {
+    fn from(config: &ServerConfig) -> Self {
+        Self {
+            default_hours: config.discovery_retention_hours,
+            rules: config.discovery_retention_rules.clone(),
+            keep_latest_successful: config.discovery_keep_latest_successful,
+        }
+    }
+}
+
+/// The discovery types a retention period applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RetentionScope {
+    Only(DiscoveryTypeDiscriminants),
+    /// Every type without a rule of its own
+    AllExcept(Vec<DiscoveryTypeDiscriminants>),
+}
+
+/// What retention needs to know about a finished session
+#[derive(Debug, Clone, Copy)]
+pub struct FinishedSession {
+    /// Whatever the caller keys the session by, returned if it's expired
+    pub id: Uuid,
+    /// What ran the session, whose latest success `keep_latest_successful` keeps: its daemon,
+    /// or for an import its network
+    pub source: Uuid,
+    pub discovery_type: DiscoveryTypeDiscriminants,
+    pub succeeded: bool,
+    pub finished_at: DateTime<Utc>,
+}
+
+fn hours_to_duration(hours: u64) -> Duration {
+    i64::try_from(hours)
+        .ok()
+        .and_then(Duration::try_hours)
+        .unwrap_or(Duration::MAX)
+}
+
+impl SessionRetentionPolicy {
+    /// None when sessions of `discovery_type` are kept forever
+    pub fn retention_for(&self, discovery_type: DiscoveryTypeDiscriminants) -> Option<Duration> {
+        let hours = self
+            .rules
+            .iter()
+            .find(|rule| rule.discovery_type == discovery_type)
+            .map(|rule| rule.hours)
+            .or(self.default_hours)?;
+
+        Some(hours_to_duration(hours))
+    }
+
+    /// Every configured retention period and the types it applies to, for pruning stored results.
+    /// Types covered by neither a rule nor the default aren't in any of them.
+    pub fn periods(&self) -> Vec<(RetentionScope, Duration)> {
+        let mut ruled = Vec::new();
+        let mut periods = Vec::new();
+
+        for rule in &self.rules {
+            if ruled.contains(&rule.discovery_type) {
+                continue;
+            }
+            ruled.push(rule.discovery_type);
+            periods.push((
+                RetentionScope::Only(rule.discovery_type),
+                hours_to_duration(rule.hours),
+            ));
+        }
+
+        if let Some(hours) = self.default_hours {
+            periods.push((RetentionScope::AllExcept(ruled), hours_to_duration(hours)));
+        }
+
+        periods
+    }
+
+    /// Ids of the in-memory `sessions` whose live state has outlived their retention at `now`,
+    /// which is never longer than `SESSION_STATE_HOURS`
+    pub fn expired(&self, sessions: &[FinishedSession], now: DateTime<Utc>) -> HashSet<Uuid> {
+        let cap = hours_to_duration(SESSION_STATE_HOURS);
+
+        let mut latest_successful: HashMap<Uuid, &FinishedSession> = HashMap::new();
+        if self.keep_latest_successful {
+            for session in sessions.iter().filter(|s| s.succeeded) {
+                latest_successful
+                    .entry(session.source)
+                    .and_modify(|latest| {
+                        if session.finished_at > latest.finished_at {
+                            *latest = session;
+                        }
+                    })
+                    .or_insert(session);
+            }
+        }
+
+        sessions
+            .iter()
+            .filter(|s| {
+                latest_successful
+                    .get(&s.source)
+                    .is_none_or(|latest| latest.id != s.id)
+            })
+            .filter(|s| {
+                let retention = self
+                    .retention_for(s.discovery_type)
+                    .map_or(cap, |retention| retention.min(cap));
+                now - s.finished_at > retention
+            })
+            .map(|s| s.id)
+            .collect()
+    }
+}
@@ -81,7 +81,7 @@ impl StorableEntity for Discovery {
                 SqlValue::Timestamp(updated_at),
                 SqlValue::String(name),
                 SqlValue::Uuid(network_id),
-                SqlValue::Uuid(daemon_id),
+                SqlValue::OptionalUuid(daemon_id),
                 SqlValue::RunType(run_type),
                 SqlValue::DiscoveryType(discovery_type),
             ],
@@ -0,0 +1,440 @@
+use anyhow::Result;
+use cidr::IpCidr;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, net::IpAddr, sync::Arc};
+use uuid::Uuid;
+
+use crate::server::{
+    config::ServerConfig,
+    daemons::r#impl::base::Daemon,
+    discovery::r#impl::types::{DiscoveryType, normalize_targets},
+    hosts::r#impl::base::Host,
+    shared::storage::{filter::EntityFilter, generic::GenericPostgresStorage, traits::Storage},
+    subnets::r#impl::base::Subnet,
+};
+
+/// What an empty allowlist means. Left unset it allows everything, with a warning at startup.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyAllowlist {
+    AllowAll,
+    DenyAll,
+}
+
+/// What happens to a target which is only partly inside policy
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PartialOverlap {
+    /// Reject the whole request
+    #[default]
+    Reject,
+    /// Drop out-of-policy targets and scan the rest
+    Clip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetVerdict {
+    Allowed,
+    /// Some but not all addresses are out of policy
+    Partial,
+    Denied,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Scan target {target} is not permitted by the scan target policy")]
+pub struct ScanTargetDenied {
+    pub target: String,
+}
+
+/// Which address ranges daemons may be asked to scan. Deny entries always win over allow
+/// entries. Sent along with network checks so the daemon enforces it too.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanTargetPolicy {
+    pub allow: Vec<IpCidr>,
+    pub deny: Vec<IpCidr>,
+    pub empty_allowlist: EmptyAllowlist,
+    pub partial_overlap: PartialOverlap,
+    /// Most concurrent host scans an adaptive-rate discovery may ramp up to
+    pub max_scan_rate: usize,
+}
+
+impl From<&ServerConfig> for ScanTargetPolicy {
+    fn from(config: &ServerConfig) -> Self {
+        Self {
+            allow: config.scan_allow_cidrs.clone(),
+            deny: config.scan_deny_cidrs.clone(),
+            empty_allowlist: config
+                .scan_empty_allowlist
+                .unwrap_or(EmptyAllowlist::AllowAll),
+            partial_overlap: config.scan_partial_overlap,
+            max_scan_rate: config.discovery_max_scan_rate,
+        }
+    }
+}
+
+impl ScanTargetPolicy {
+    /// The policy when there's no config at all, which refuses nothing
+    pub fn unrestricted() -> Self {
+        Self {
+            allow: Vec::new(),
+            deny: Vec::new(),
+            empty_allowlist: EmptyAllowlist::AllowAll,
+            partial_overlap: PartialOverlap::default(),
+            max_scan_rate: DEFAULT_MAX_SCAN_RATE,
+        }
+    }
+}
+
+pub const DEFAULT_MAX_SCAN_RATE: usize = 64;
+
+fn covers(outer: &IpCidr, inner: &IpCidr) -> bool {
+    outer.contains(&inner.first_address()) && outer.contains(&inner.last_address())
+}
+
+fn overlaps(a: &IpCidr, b: &IpCidr) -> bool {
+    a.contains(&b.first_address()) || b.contains(&a.first_address())
+}
+
+impl ScanTargetPolicy {
+    /// Whether the policy can ever refuse a target
+    pub fn is_restrictive(&self) -> bool {
+        !self.deny.is_empty()
+            || !self.allow.is_empty()
+            || self.empty_allowlist == EmptyAllowlist::DenyAll
+    }
+
+    pub fn evaluate(&self, target: &IpCidr) -> TargetVerdict {
+        if self.deny.iter().any(|d| covers(d, target)) {
+            return TargetVerdict::Denied;
+        }
+        if self.deny.iter().any(|d| overlaps(d, target)) {
+            return TargetVerdict::Partial;
+        }
+
+        if self.allow.is_empty() {
+            return match self.empty_allowlist {
+                EmptyAllowlist::AllowAll => TargetVerdict::Allowed,
+                EmptyAllowlist::DenyAll => TargetVerdict::Denied,
+            };
+        }
+
+        if self.allow.iter().any(|a| covers(a, target)) {
+            TargetVerdict::Allowed
+        } else if self.allow.iter().any(|a| overlaps(a, target)) {
+            TargetVerdict::Partial
+        } else {
+            TargetVerdict::Denied
+        }
+    }
+
+    /// Check a bare address range, such as an ARP scan's, against the policy. Ranges the policy
+    /// only partly allows are refused, as there are no subnets to clip them to.
+    pub fn check_cidr(&self, cidr: &IpCidr) -> Result<(), ScanTargetDenied> {
+        match self.evaluate(cidr) {
+            TargetVerdict::Allowed => Ok(()),
+            TargetVerdict::Partial | TargetVerdict::Denied => Err(ScanTargetDenied {
+                target: cidr.to_string(),
+            }),
+        }
+    }
+
+    /// Whether a single address, such as a neighbor a scan turned up, may be reported
+    pub fn permits_address(&self, ip: IpAddr) -> bool {
+        self.check_cidr(&IpCidr::new_host(ip)).is_ok()
+    }
+
+    /// Apply the policy to a set of subnets, returning the ids of those that may be scanned.
+    /// Subnets can't be scanned in part, so clipping drops partially allowed subnets entirely
+    /// rather than risk touching a denied address.
+    pub fn permitted_subnets(&self, subnets: &[Subnet]) -> Result<Vec<Uuid>, ScanTargetDenied> {
+        let mut permitted = Vec::new();
+
+        for subnet in subnets {
+            match (self.evaluate(&subnet.base.cidr), self.partial_overlap) {
+                (TargetVerdict::Allowed, _) => permitted.push(subnet.id),
+                (verdict, PartialOverlap::Clip) => {
+                    tracing::info!(
+                        subnet_id = %subnet.id,
+                        cidr = %subnet.base.cidr,
+                        ?verdict,
+                        "Clipping subnet from discovery request"
+                    );
+                }
+                (_, PartialOverlap::Reject) => {
+                    return Err(ScanTargetDenied {
+                        target: subnet.base.cidr.to_string(),
+                    });
+                }
+            }
+        }
+
+        if permitted.is_empty() && !subnets.is_empty() {
+            return Err(ScanTargetDenied {
+                target: subnets
+                    .iter()
+                    .map(|s| s.base.cidr.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            });
+        }
+
+        Ok(permitted)
+    }
+
+    /// Apply the policy to a discovery's explicit targets, returning those that may be scanned.
+    /// IPs are checked as single addresses. Hostnames pass here, as only the daemon resolves
+    /// them, and are held to the permitted subnets the request is pinned to.
+    pub fn permitted_targets(&self, targets: &[String]) -> Result<Vec<String>, ScanTargetDenied> {
+        let mut permitted = Vec::new();
+
+        for target in targets {
+            let Ok(ip) = target.parse::<IpAddr>() else {
+                permitted.push(target.clone());
+                continue;
+            };
+
+            match (self.evaluate(&IpCidr::new_host(ip)), self.partial_overlap) {
+                (TargetVerdict::Allowed, _) => permitted.push(target.clone()),
+                (_, PartialOverlap::Clip) => {
+                    tracing::info!(target = %target, "Clipping target from discovery request");
+                }
+                (_, PartialOverlap::Reject) => {
+                    return Err(ScanTargetDenied {
+                        target: target.clone(),
+                    });
+                }
+            }
+        }
+
+        if permitted.is_empty() && !targets.is_empty() {
+            return Err(ScanTargetDenied {
+                target: targets.join(", "),
+            });
+        }
+
+        Ok(permitted)
+    }
+}
+
+/// Enforces the [`ScanTargetPolicy`] on discovery requests before they reach a daemon
+pub struct ScanTargetGuard {
+    policy: ScanTargetPolicy,
+    subnet_storage: Arc<GenericPostgresStorage<Subnet>>,
+    host_storage: Arc<GenericPostgresStorage<Host>>,
+}
+
+impl ScanTargetGuard {
+    pub fn new(
+        policy: ScanTargetPolicy,
+        subnet_storage: Arc<GenericPostgresStorage<Subnet>>,
+        host_storage: Arc<GenericPostgresStorage<Host>>,
+    ) -> Self {
+        Self {
+            policy,
+            subnet_storage,
+            host_storage,
+        }
+    }
+
+    /// Subnets a network discovery targets: `subnet_ids` when given, otherwise the subnets the
+    /// daemon's host has interfaces on
+    pub async fn target_subnets(
+        &self,
+        daemon: &Daemon,
+        subnet_ids: Option<Vec<Uuid>>,
+    ) -> Result<Vec<Subnet>> {
+        let subnet_ids = match subnet_ids {
+            Some(ids) => ids,
+            None => self
+                .host_storage
+                .get_by_id(&daemon.base.host_id)
+                .await?
+                .map(|host| {
+                    host.base
+                        .interfaces
+                        .iter()
+                        .map(|i| i.base.subnet_id)
+                        .collect::<HashSet<_>>()
+                        .into_iter()
+                        .collect()
+                })
+                .unwrap_or_default(),
+        };
+
+        if subnet_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.subnet_storage
+            .get_all(EntityFilter::unfiltered().entity_ids(&subnet_ids))
+            .await
+    }
+
+    pub fn policy(&self) -> &ScanTargetPolicy {
+        &self.policy
+    }
+
+    /// [`ScanTargetPolicy::check_cidr`]
+    pub fn check_cidr(&self, cidr: &IpCidr) -> Result<(), ScanTargetDenied> {
+        self.policy.check_cidr(cidr)
+    }
+
+    /// Check the targets of a discovery for `daemon`, returning the discovery type to dispatch.
+    ///
+    /// Network discoveries without explicit subnets target the daemon's own interfaced subnets;
+    /// under a restrictive policy those are resolved here and pinned explicitly, so what the
+    /// daemon scans is exactly what was checked. Explicit targets are deduplicated and, under a
+    /// restrictive policy, checked too. Adaptive scan rates are capped to the policy's maximum.
+    pub async fn check(
+        &self,
+        daemon: &Daemon,
+        discovery_type: DiscoveryType,
+    ) -> Result<DiscoveryType> {
+        let DiscoveryType::Network {
+            subnet_ids,
+            host_naming_fallback,
+            scan_rate,
+            snmp_credential,
+            targets,
+        } = discovery_type
+        else {
+            return Ok(discovery_type);
+        };
+        let scan_rate = scan_rate.capped(self.policy.max_scan_rate);
+        let targets = normalize_targets(&targets);
+
+        if !self.policy.is_restrictive() {
+            return Ok(DiscoveryType::Network {
+                subnet_ids,
+                host_naming_fallback,
+                scan_rate,
+                snmp_credential,
+                targets,
+            });
+        }
+
+        let targets = self.policy.permitted_targets(&targets)?;
+        let subnets = self.target_subnets(daemon, subnet_ids).await?;
+        let permitted = self.policy.permitted_subnets(&subnets)?;
+
+        Ok(DiscoveryType::Network {
+            subnet_ids: Some(permitted),
+            host_naming_fallback,
+            scan_rate,
+            snmp_credential,
+            targets,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(s: &str) -> IpCidr {
+        s.parse().unwrap()
+    }
+
+    fn subnet(s: &str) -> Subnet {
+        let mut subnet = crate::tests::subnet(&Uuid::new_v4());
+        subnet.base.cidr = cidr(s);
+        subnet
+    }
+
+    fn policy() -> ScanTargetPolicy {
+        ScanTargetPolicy {
+            allow: vec![cidr("10.0.0.0/8"), cidr("192.168.0.0/16")],
+            deny: vec![cidr("10.99.0.0/16")],
+            ..ScanTargetPolicy::unrestricted()
+        }
+    }
+
+    #[test]
+    fn test_target_verdicts() {
+        let policy = policy();
+
+        assert_eq!(
+            policy.evaluate(&cidr("10.1.2.0/24")),
+            TargetVerdict::Allowed
+        );
+        assert_eq!(
+            policy.evaluate(&cidr("10.99.1.0/24")),
+            TargetVerdict::Denied
+        );
+        // Straddles the denied range
+        assert_eq!(
+            policy.evaluate(&cidr("10.96.0.0/12")),
+            TargetVerdict::Partial
+        );
+        // Outside every allowed range
+        assert_eq!(policy.evaluate(&cidr("8.8.8.0/24")), TargetVerdict::Denied);
+        // Only partly inside an allowed range
+        assert_eq!(
+            policy.evaluate(&cidr("192.0.0.0/8")),
+            TargetVerdict::Partial
+        );
+
+        let mut open = ScanTargetPolicy::unrestricted();
+        assert!(!open.is_restrictive());
+        assert_eq!(open.evaluate(&cidr("8.8.8.0/24")), TargetVerdict::Allowed);
+        open.empty_allowlist = EmptyAllowlist::DenyAll;
+        assert_eq!(open.evaluate(&cidr("8.8.8.0/24")), TargetVerdict::Denied);
+    }
+
+    #[test]
+    fn test_denied_subnet_rejected_or_clipped() {
+        let allowed = subnet("192.168.1.0/24");
+        let denied = subnet("10.99.5.0/24");
+        let mut policy = policy();
+
+        assert_eq!(
+            policy
+                .permitted_subnets(std::slice::from_ref(&allowed))
+                .unwrap(),
+            vec![allowed.id]
+        );
+        assert!(
+            policy
+                .permitted_subnets(&[allowed.clone(), denied.clone()])
+                .is_err()
+        );
+
+        policy.partial_overlap = PartialOverlap::Clip;
+        assert_eq!(
+            policy
+                .permitted_subnets(&[allowed.clone(), denied.clone()])
+                .unwrap(),
+            vec![allowed.id]
+        );
+        // Clipping everything away is still a rejection
+        assert!(policy.permitted_subnets(&[denied]).is_err());
+    }
+
+    #[test]
+    fn test_explicit_targets_checked_by_address() {
+        let targets = |targets: &[&str]| targets.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        let mut policy = policy();
+
+        // Hostnames are left for the daemon to hold to the pinned subnets
+        assert_eq!(
+            policy
+                .permitted_targets(&targets(&["10.1.2.3", "nas.local"]))
+                .unwrap(),
+            targets(&["10.1.2.3", "nas.local"])
+        );
+        assert!(
+            policy
+                .permitted_targets(&targets(&["10.1.2.3", "10.99.0.7"]))
+                .is_err()
+        );
+
+        policy.partial_overlap = PartialOverlap::Clip;
+        assert_eq!(
+            policy
+                .permitted_targets(&targets(&["10.1.2.3", "10.99.0.7"]))
+                .unwrap(),
+            targets(&["10.1.2.3"])
+        );
+        assert!(policy.permitted_targets(&targets(&["8.8.8.8"])).is_err());
+    }
+}
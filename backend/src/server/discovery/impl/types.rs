@@ -1,11 +1,13 @@
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use serde::Serialize;
+use std::{collections::HashSet, net::IpAddr};
 use strum::{Display, EnumDiscriminants, EnumIter, IntoStaticStr};
 use uuid::Uuid;
 
 use crate::server::{
     daemons::r#impl::api::DiscoveryUpdatePayload,
+    discovery::r#impl::import::ImportFormat,
     shared::{
         entities::Entity,
         types::metadata::{EntityMetadataProvider, HasId, TypeMetadataProvider},
@@ -25,6 +27,7 @@ use crate::server::{
     EnumDiscriminants,
     EnumIter,
 )]
+#[strum_discriminants(derive(Hash, Serialize, Deserialize))]
 #[serde(tag = "type")]
 pub enum DiscoveryType {
     SelfReport {
@@ -35,12 +38,30 @@ pub enum DiscoveryType {
         subnet_ids: Option<Vec<Uuid>>,
         #[serde(default)]
         host_naming_fallback: HostNamingFallback,
+        #[serde(default)]
+        scan_rate: ScanRate,
+        /// Name of a stored SNMP community secret to probe with, instead of `public`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        snmp_credential: Option<String>,
+        /// IPs or hostnames to scan instead of whole subnets. Each must fall inside one of the
+        /// targeted subnets; hostnames are resolved by the daemon.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        targets: Vec<String>,
     },
     Docker {
         host_id: Uuid,
         #[serde(default)]
         host_naming_fallback: HostNamingFallback,
     },
+    /// Results imported from a scan run outside NetVisor. Never sent to a daemon.
+    Import {
+        format: ImportFormat,
+    },
+    /// IPv6 neighbors found by an NDP scan a daemon ran on the server's behalf. Never sent to a
+    /// daemon as a discovery.
+    Ndp {
+        interface: String,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Copy, Deserialize, Eq, PartialEq, Hash, Display, Default)]
@@ -50,6 +71,77 @@ pub enum HostNamingFallback {
     BestService,
 }
 
+/// How many hosts a network discovery probes at once
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Hash, Default)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ScanRate {
+    /// The daemon's configured concurrency for the whole scan
+    #[default]
+    Fixed,
+    /// Start at `initial` concurrent host scans and ramp up towards `max` while hosts answer,
+    /// backing off when probes are lost
+    Adaptive { initial: usize, max: usize },
+}
+
+impl ScanRate {
+    /// Limit an adaptive rate to at most `cap` concurrent host scans, keeping it well formed
+    pub fn capped(self, cap: usize) -> Self {
+        match self {
+            ScanRate::Fixed => ScanRate::Fixed,
+            ScanRate::Adaptive { initial, max } => {
+                let max = max.clamp(1, cap.max(1));
+                ScanRate::Adaptive {
+                    initial: initial.clamp(1, max),
+                    max,
+                }
+            }
+        }
+    }
+}
+
+/// An explicit discovery target as the daemon resolved it. Exactly one of `address` and `error`
+/// is set.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TargetResolution {
+    pub target: String,
+    #[serde(default)]
+    pub address: Option<IpAddr>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl TargetResolution {
+    pub fn resolved(target: &str, address: IpAddr) -> Self {
+        Self {
+            target: target.to_string(),
+            address: Some(address),
+            error: None,
+        }
+    }
+
+    pub fn failed(target: &str, error: impl Into<String>) -> Self {
+        Self {
+            target: target.to_string(),
+            address: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// Explicit targets trimmed and deduplicated, keeping first-seen order. Hostnames compare
+/// case-insensitively and IPs by address.
+pub fn normalize_targets(targets: &[String]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    targets
+        .iter()
+        .map(|target| match target.trim().parse::<IpAddr>() {
+            Ok(ip) => ip.to_string(),
+            Err(_) => target.trim().to_lowercase(),
+        })
+        .filter(|target| !target.is_empty() && seen.insert(target.clone()))
+        .collect()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum RunType {
@@ -59,13 +151,35 @@ pub enum RunType {
         enabled: bool,
     },
     Historical {
-        results: DiscoveryUpdatePayload,
+        results: Box<DiscoveryUpdatePayload>,
     },
     AdHoc {
         last_run: Option<DateTime<Utc>>,
     },
 }
 
+impl DiscoveryType {
+    /// IPs or hostnames a network discovery scans instead of whole subnets, if any
+    pub fn explicit_targets(&self) -> &[String] {
+        match self {
+            DiscoveryType::Network { targets, .. } => targets,
+            _ => &[],
+        }
+    }
+
+    /// Names of the stored secrets this discovery needs resolved before it's dispatched
+    pub fn secret_names(&self) -> impl Iterator<Item = &str> {
+        let snmp_credential = match self {
+            DiscoveryType::Network {
+                snmp_credential, ..
+            } => snmp_credential.as_deref(),
+            _ => None,
+        };
+
+        snmp_credential.into_iter()
+    }
+}
+
 impl HasId for DiscoveryType {
     fn id(&self) -> &'static str {
         self.into()
@@ -97,6 +211,12 @@ impl TypeMetadataProvider for DiscoveryType {
             DiscoveryType::SelfReport { .. } => {
                 "The daemon reports its own host configuration and network details"
             }
+            DiscoveryType::Import { .. } => {
+                "Hosts and open ports imported from a scan run outside NetVisor"
+            }
+            DiscoveryType::Ndp { .. } => {
+                "IPv6 neighbors found by an NDP scan of one of the daemon's local segments"
+            }
         }
     }
 }
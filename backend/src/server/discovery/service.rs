@@ -7,13 +7,35 @@ use crate::server::shared::storage::traits::{StorableEntity, Storage};
 use anyhow::anyhow;
 use anyhow::{Error, Result};
 use async_trait::async_trait;
-use chrono::Utc;
-use std::{collections::HashMap, sync::Arc};
+use chrono::{Duration, Utc};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex, OnceLock},
+};
 use tokio::sync::{RwLock, broadcast};
 use tokio_cron_scheduler::{Job, JobScheduler};
 use uuid::Uuid;
 
-use crate::server::discovery::r#impl::base::Discovery;
+use crate::server::discovery::r#impl::{
+    base::{Discovery, DiscoveryBase},
+    estimate::{DiscoveryEstimate, estimate, estimate_addresses},
+    fair_share::{FairShareLimiter, FairSharePolicy},
+    history::{STALLED_SESSION_DAYS, SessionActor, SessionHistory},
+    import::{DiscoveryImportResult, ImportFormat, ImportedHost},
+    retention::{FinishedSession, RetentionScope, SessionRetentionPolicy},
+    types::DiscoveryType,
+};
+use crate::server::discovery_webhooks::service::DiscoveryWebhookService;
+use crate::server::hosts::{r#impl::base::Host, service::HostService};
+use crate::server::secrets::{
+    r#impl::base::{ResolvedSecrets, SecretError},
+    service::SecretService,
+};
+use crate::server::services::r#impl::base::Service;
+use crate::server::shared::sync::lock;
+use crate::server::shared::types::entities::DiscoveryMetadata;
+use crate::server::subnets::service::SubnetService;
 use crate::{
     daemon::discovery::types::base::DiscoveryPhase,
     server::daemons::{
@@ -29,8 +51,18 @@ pub struct DiscoveryService {
     sessions: RwLock<HashMap<Uuid, DiscoveryUpdatePayload>>, // session_id -> session state mapping
     daemon_sessions: RwLock<HashMap<Uuid, Vec<Uuid>>>,       // daemon_id -> session_id mapping
     daemon_pull_cancellations: RwLock<HashMap<Uuid, bool>>, // daemon_id -> boolean mapping for pull mode cancellations of current session on daemon
+    /// Phase transitions per session, only written while holding the `sessions` write lock so
+    /// their order matches the order the transitions were applied in
+    histories: RwLock<HashMap<Uuid, SessionHistory>>,
+    /// Slots under the global discovery cap. A session asks for one when it reaches the head of
+    /// its daemon's queue, and is only handed to the daemon once it has one.
+    limiter: Mutex<FairShareLimiter>,
     update_tx: broadcast::Sender<DiscoveryUpdatePayload>,
     scheduler: Option<Arc<RwLock<JobScheduler>>>,
+    secret_service: OnceLock<Arc<SecretService>>,
+    host_service: OnceLock<Arc<HostService>>,
+    subnet_service: OnceLock<Arc<SubnetService>>,
+    discovery_webhook_service: OnceLock<Arc<DiscoveryWebhookService>>,
 }
 
 #[async_trait]
@@ -44,6 +76,7 @@ impl DiscoveryService {
     pub async fn new(
         discovery_storage: Arc<GenericPostgresStorage<Discovery>>,
         daemon_service: Arc<DaemonService>,
+        fair_share: FairSharePolicy,
     ) -> Result<Arc<Self>> {
         let (tx, _rx) = broadcast::channel(100); // Buffer 100 messages
         let scheduler = JobScheduler::new().await?;
@@ -54,11 +87,43 @@ impl DiscoveryService {
             sessions: RwLock::new(HashMap::new()),
             daemon_sessions: RwLock::new(HashMap::new()),
             daemon_pull_cancellations: RwLock::new(HashMap::new()),
+            histories: RwLock::new(HashMap::new()),
+            limiter: Mutex::new(FairShareLimiter::new(fair_share)),
             update_tx: tx,
             scheduler: Some(Arc::new(RwLock::new(scheduler))),
+            secret_service: OnceLock::new(),
+            host_service: OnceLock::new(),
+            subnet_service: OnceLock::new(),
+            discovery_webhook_service: OnceLock::new(),
         }))
     }
 
+    pub fn set_secret_service(
+        &self,
+        secret_service: Arc<SecretService>,
+    ) -> Result<(), Arc<SecretService>> {
+        self.secret_service.set(secret_service)
+    }
+
+    pub fn set_host_service(&self, host_service: Arc<HostService>) -> Result<(), Arc<HostService>> {
+        self.host_service.set(host_service)
+    }
+
+    pub fn set_subnet_service(
+        &self,
+        subnet_service: Arc<SubnetService>,
+    ) -> Result<(), Arc<SubnetService>> {
+        self.subnet_service.set(subnet_service)
+    }
+
+    pub fn set_discovery_webhook_service(
+        &self,
+        discovery_webhook_service: Arc<DiscoveryWebhookService>,
+    ) -> Result<(), Arc<DiscoveryWebhookService>> {
+        self.discovery_webhook_service
+            .set(discovery_webhook_service)
+    }
+
     /// Create a new scheduled discovery
     pub async fn create_discovery(self: &Arc<Self>, discovery: Discovery) -> Result<Discovery> {
         let mut created_discovery = if discovery.id == Uuid::nil() {
@@ -292,17 +357,21 @@ impl DiscoveryService {
 
     /// Get session state
     pub async fn get_session(&self, session_id: &Uuid) -> Option<DiscoveryUpdatePayload> {
-        self.sessions.read().await.get(session_id).cloned()
+        let session = self.sessions.read().await.get(session_id).cloned()?;
+        self.with_logged_phases(vec![session]).await.pop()
     }
 
     /// Get session state
     pub async fn get_all_sessions(&self, network_ids: &[Uuid]) -> Vec<DiscoveryUpdatePayload> {
-        let all_sessions = self.sessions.read().await;
-        all_sessions
+        let sessions = self
+            .sessions
+            .read()
+            .await
             .values()
             .filter(|v| network_ids.contains(&v.network_id))
             .cloned()
-            .collect()
+            .collect();
+        self.with_logged_phases(sessions).await
     }
 
     pub async fn get_sessions_for_daemon(&self, daemon_id: &Uuid) -> Vec<DiscoveryUpdatePayload> {
@@ -312,13 +381,33 @@ impl DiscoveryService {
             .cloned()
             .unwrap_or_default();
 
-        let all_sessions = self.sessions.read().await;
-
-        all_sessions
+        let sessions = self
+            .sessions
+            .read()
+            .await
             .iter()
             .filter(|(session_id, _)| session_ids.contains(session_id))
             .map(|(_, session)| session.clone())
-            .collect()
+            .collect();
+        self.with_logged_phases(sessions).await
+    }
+
+    /// Sessions with their phase replayed from their histories, which are the record of what
+    /// phase each session is in
+    async fn with_logged_phases(
+        &self,
+        mut sessions: Vec<DiscoveryUpdatePayload>,
+    ) -> Vec<DiscoveryUpdatePayload> {
+        let histories = self.histories.read().await;
+        for session in &mut sessions {
+            if let Some(phase) = histories
+                .get(&session.session_id)
+                .and_then(SessionHistory::phase)
+            {
+                session.phase = phase;
+            }
+        }
+        sessions
     }
 
     pub async fn pull_cancellation_for_daemon(&self, daemon_id: &Uuid) -> bool {
@@ -326,64 +415,359 @@ impl DiscoveryService {
         daemon_cancellation_ids.remove(daemon_id).unwrap_or(false)
     }
 
-    /// Create a new discovery session
+    /// Session for a pull-mode daemon to run, with the secrets it references resolved
+    pub async fn next_session_for_daemon(
+        &self,
+        daemon_id: &Uuid,
+    ) -> Option<DiscoveryUpdatePayload> {
+        let session_id = self
+            .daemon_sessions
+            .read()
+            .await
+            .get(daemon_id)
+            .and_then(|queue| queue.first().copied())?;
+        if !lock(&self.limiter).is_admitted(&session_id) {
+            return None;
+        }
+        let session = self.get_session(&session_id).await?;
+
+        match self.resolve_secrets(&session).await {
+            Ok(secrets) => Some(DiscoveryUpdatePayload { secrets, ..session }),
+            Err(e) => {
+                if let Err(e) = self.fail_session(&session, &e).await {
+                    tracing::error!(
+                        session_id = %session.session_id,
+                        "Failed to fail discovery session: {}",
+                        e
+                    );
+                }
+                None
+            }
+        }
+    }
+
+    /// Resolve the secrets a session's discovery references, at the point it's handed to a daemon
+    async fn resolve_secrets(&self, session: &DiscoveryUpdatePayload) -> Result<ResolvedSecrets> {
+        let names: Vec<&str> = session.discovery_type.secret_names().collect();
+        if names.is_empty() {
+            return Ok(ResolvedSecrets::new());
+        }
+
+        let secret_service = self
+            .secret_service
+            .get()
+            .ok_or(SecretError::NotConfigured)?;
+
+        secret_service.resolve_all(&session.network_id, names).await
+    }
+
+    /// End a session that couldn't be handed to its daemon, e.g. because a secret it references
+    /// was deleted, so the reason shows on the session rather than it waiting forever
+    async fn fail_session(&self, session: &DiscoveryUpdatePayload, error: &Error) -> Result<()> {
+        tracing::warn!(
+            session_id = %session.session_id,
+            error = %error,
+            "Discovery session failed before dispatch"
+        );
+
+        let mut failed = session.clone();
+        failed.phase = DiscoveryPhase::Failed;
+        failed.error = Some(error.to_string());
+        failed.finished_at = Some(Utc::now());
+
+        Box::pin(self.update_session_by(failed, SessionActor::Server)).await
+    }
+
+    /// Send a session to a push-mode daemon, resolving its secrets first. The session is failed if
+    /// it can't be sent.
+    async fn dispatch_session(
+        &self,
+        daemon_id: &Uuid,
+        session: &DiscoveryUpdatePayload,
+    ) -> Result<()> {
+        let secrets = match self.resolve_secrets(session).await {
+            Ok(secrets) => secrets,
+            Err(e) => {
+                self.fail_session(session, &e).await?;
+                return Err(e);
+            }
+        };
+
+        let sent = self
+            .daemon_service
+            .send_discovery_request(
+                daemon_id,
+                DaemonDiscoveryRequest {
+                    session_id: session.session_id,
+                    discovery_type: session.discovery_type.clone(),
+                    secrets,
+                    protocol_version: None,
+                },
+            )
+            .await;
+
+        // A session the daemon never got would otherwise hold its slot under the cap forever
+        if let Err(e) = &sent {
+            self.fail_session(session, e).await?;
+        }
+        sent
+    }
+
+    /// Send a push-mode daemon the session at the head of its queue, if it's still waiting to be
+    /// sent, e.g. after the daemon is resumed
+    pub async fn dispatch_queued_session(&self, daemon_id: &Uuid) -> Result<()> {
+        let Some(daemon) = self.daemon_service.get_by_id(daemon_id).await? else {
+            return Ok(());
+        };
+        if daemon.base.mode != DaemonMode::Push || daemon.base.paused {
+            return Ok(());
+        }
+
+        let next_session_id = self
+            .daemon_sessions
+            .read()
+            .await
+            .get(daemon_id)
+            .and_then(|queue| queue.first().copied());
+        let next_session = match next_session_id {
+            Some(session_id) => self.get_session(&session_id).await,
+            None => None,
+        };
+
+        match next_session {
+            Some(session)
+                if session.phase == DiscoveryPhase::Pending
+                    && lock(&self.limiter).is_admitted(&session.session_id) =>
+            {
+                self.dispatch_session(daemon_id, &session).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Give up the slots under the discovery cap held by sessions that have been removed, handing
+    /// any sessions admitted into them to their daemons
+    async fn release_slots(&self, session_ids: &[Uuid]) {
+        let admitted: Vec<Uuid> = {
+            let mut limiter = lock(&self.limiter);
+            session_ids
+                .iter()
+                .flat_map(|session_id| limiter.release(session_id))
+                .collect()
+        };
+        self.dispatch_admitted(admitted).await;
+    }
+
+    /// Drop every session queued or running on `daemon_id`, e.g. once the daemon is deleted,
+    /// freeing the slots they held
+    pub async fn remove_daemon_sessions(&self, daemon_id: &Uuid) {
+        let removed = {
+            let mut sessions = self.sessions.write().await;
+            let mut daemon_sessions = self.daemon_sessions.write().await;
+            self.daemon_pull_cancellations
+                .write()
+                .await
+                .remove(daemon_id);
+
+            let removed = daemon_sessions.remove(daemon_id).unwrap_or_default();
+            for session_id in &removed {
+                sessions.remove(session_id);
+            }
+            removed
+        };
+
+        if !removed.is_empty() {
+            tracing::info!(
+                daemon_id = %daemon_id,
+                "Dropped {} discovery sessions of removed daemon",
+                removed.len()
+            );
+        }
+        self.release_slots(&removed).await;
+    }
+
+    /// Hand sessions just given a slot under the discovery cap to their daemons. Pull-mode and
+    /// paused daemons pick theirs up on their next pull or when resumed.
+    async fn dispatch_admitted(&self, admitted: Vec<Uuid>) {
+        for session_id in admitted {
+            let Some(daemon_id) = self
+                .get_session(&session_id)
+                .await
+                .and_then(|session| session.daemon_id)
+            else {
+                continue;
+            };
+            if let Err(e) = self.dispatch_queued_session(&daemon_id).await {
+                tracing::error!(
+                    session_id = %session_id,
+                    "Failed to dispatch discovery session: {}",
+                    e
+                );
+            }
+        }
+    }
+
+    /// Estimate the scope and duration of a network discovery without running it. Targets are
+    /// checked against the scan target policy first, so the estimate covers what would actually
+    /// be scanned.
+    pub async fn estimate(&self, discovery: &DiscoveryBase) -> Result<DiscoveryEstimate> {
+        let daemon_id = discovery
+            .daemon_id
+            .ok_or_else(|| anyhow!("Only discoveries run by a daemon can be estimated"))?;
+        let daemon = self
+            .daemon_service
+            .get_by_id(&daemon_id)
+            .await?
+            .ok_or_else(|| anyhow!("Could not find daemon {}", daemon_id))?;
+
+        let DiscoveryType::Network {
+            subnet_ids,
+            scan_rate,
+            targets,
+            ..
+        } = self
+            .daemon_service
+            .check_scan_targets(&daemon, discovery.discovery_type.clone())
+            .await?
+        else {
+            return Err(anyhow!("Only network discoveries can be estimated"));
+        };
+        let ports = Service::all_discovery_ports().len() as u64;
+
+        if !targets.is_empty() {
+            return Ok(estimate_addresses(
+                targets.len() as u64,
+                ports,
+                scan_rate,
+                &daemon.base.capabilities,
+            ));
+        }
+
+        let subnets: Vec<_> = self
+            .daemon_service
+            .scan_target_subnets(&daemon, subnet_ids)
+            .await?
+            .into_iter()
+            .map(|subnet| subnet.base.cidr)
+            .collect();
+
+        Ok(estimate(
+            &subnets,
+            ports,
+            scan_rate,
+            &daemon.base.capabilities,
+        ))
+    }
+
+    /// Phase transitions of a session, kept as long as sessions are
+    pub async fn get_session_history(&self, session_id: &Uuid) -> Option<SessionHistory> {
+        self.histories.read().await.get(session_id).cloned()
+    }
+
+    /// Move a session to `to` by appending the transition to its history, then take the
+    /// session's phase from the history so the two can't disagree. Callers hold the `sessions`
+    /// write lock.
+    async fn transition(
+        &self,
+        session: &mut DiscoveryUpdatePayload,
+        to: DiscoveryPhase,
+        reason: Option<String>,
+        actor: SessionActor,
+    ) {
+        let mut histories = self.histories.write().await;
+        let history = histories.entry(session.session_id).or_insert_with(|| {
+            SessionHistory::new(
+                session.session_id,
+                session.network_id,
+                session.daemon_id,
+                (&session.discovery_type).into(),
+            )
+        });
+        let recorded = history.record(to, reason, actor, Utc::now());
+        session.phase = history.phase().unwrap_or(to);
+
+        if recorded {
+            tracing::debug!(
+                session_id = %session.session_id,
+                phase = %session.phase,
+                ?actor,
+                "Discovery session transitioned"
+            );
+        }
+    }
+
+    /// Create a new discovery session on behalf of the server, e.g. for a scheduled run
     pub async fn start_session(
         &self,
         discovery: Discovery,
+    ) -> Result<DiscoveryUpdatePayload, anyhow::Error> {
+        self.start_session_by(discovery, SessionActor::Server).await
+    }
+
+    /// Create a new discovery session, recording `actor` as having started it
+    pub async fn start_session_by(
+        &self,
+        discovery: Discovery,
+        actor: SessionActor,
     ) -> Result<DiscoveryUpdatePayload, anyhow::Error> {
         let session_id = Uuid::new_v4();
 
-        let session_payload = DiscoveryUpdatePayload::new(
+        let daemon_id = discovery
+            .base
+            .daemon_id
+            .ok_or_else(|| anyhow!("Discovery {} has no daemon to run it", discovery.id))?;
+
+        // Check targets before queueing, so pull-mode daemons never receive a disallowed session
+        let daemon = self
+            .daemon_service
+            .get_by_id(&daemon_id)
+            .await?
+            .ok_or_else(|| anyhow!("Could not find daemon {}", daemon_id))?;
+        self.daemon_service.check_not_paused(&daemon)?;
+        let discovery_type = self
+            .daemon_service
+            .check_scan_targets(&daemon, discovery.base.discovery_type.clone())
+            .await?;
+
+        let mut session_payload = DiscoveryUpdatePayload::new(
             session_id,
-            discovery.base.daemon_id,
+            Some(daemon_id),
             discovery.base.network_id,
-            discovery.base.discovery_type.clone(),
+            discovery_type,
         );
 
         // Add to session map
-        self.sessions
-            .write()
-            .await
-            .insert(session_id, session_payload.clone());
+        let mut sessions = self.sessions.write().await;
+        self.transition(&mut session_payload, DiscoveryPhase::Pending, None, actor)
+            .await;
+        sessions.insert(session_id, session_payload.clone());
+        drop(sessions);
 
         // Check if daemon has any sessions running
-        let daemon_is_running_discovery = if let Some(daemon_sessions) = self
-            .daemon_sessions
-            .read()
-            .await
-            .get(&discovery.base.daemon_id)
-        {
-            !daemon_sessions.is_empty()
-        } else {
-            false
-        };
+        let daemon_is_running_discovery =
+            if let Some(daemon_sessions) = self.daemon_sessions.read().await.get(&daemon_id) {
+                !daemon_sessions.is_empty()
+            } else {
+                false
+            };
 
         // Add session to queue
         self.daemon_sessions
             .write()
             .await
-            .entry(discovery.base.daemon_id)
+            .entry(daemon_id)
             .or_default()
             .push(session_id);
 
-        let daemon_is_push = self
-            .daemon_service
-            .get_by_id(&discovery.base.daemon_id)
-            .await?
-            .map(|d| d.base.mode == DaemonMode::Push)
-            .unwrap_or(false);
-
-        // Initiate session on daemon if none are running and daemon is push
-        if !daemon_is_running_discovery && daemon_is_push {
-            self.daemon_service
-                .send_discovery_request(
-                    &discovery.base.daemon_id,
-                    DaemonDiscoveryRequest {
-                        discovery_type: discovery.base.discovery_type,
-                        session_id,
-                    },
-                )
-                .await?;
+        let daemon_is_push = daemon.base.mode == DaemonMode::Push;
+
+        // Initiate session on daemon if none are running, it gets a slot and daemon is push
+        if !daemon_is_running_discovery {
+            let admitted = lock(&self.limiter).request(discovery.base.network_id, session_id);
+
+            if admitted.contains(&session_id) && daemon_is_push {
+                self.dispatch_session(&daemon_id, &session_payload).await?;
+            }
         }
 
         let _ = self.update_tx.send(session_payload.clone());
@@ -391,21 +775,195 @@ impl DiscoveryService {
         tracing::info!(
             "Created discovery session {} for daemon {}",
             session_id,
-            discovery.base.daemon_id
+            daemon_id
         );
         Ok(session_payload)
     }
 
-    /// Update progress for a session
+    /// Record hosts from a scan run outside NetVisor as a discovery session of `network_id`. The
+    /// session has no daemon; it's opened already scanning, each host is created the same way a
+    /// daemon's results are, and it's then completed like any other so a historical run is kept.
+    pub async fn import(
+        &self,
+        network_id: Uuid,
+        format: ImportFormat,
+        hosts: Vec<ImportedHost>,
+        actor: SessionActor,
+    ) -> Result<DiscoveryImportResult> {
+        self.ingest(
+            network_id,
+            None,
+            DiscoveryType::Import { format },
+            hosts,
+            actor,
+        )
+        .await
+    }
+
+    /// Record `hosts` found outside a daemon discovery session as a session of `discovery_type`,
+    /// creating hosts for those in one of the network's subnets. `daemon_id` is the daemon that
+    /// found them, if any did.
+    pub async fn ingest(
+        &self,
+        network_id: Uuid,
+        daemon_id: Option<Uuid>,
+        discovery_type: DiscoveryType,
+        hosts: Vec<ImportedHost>,
+        actor: SessionActor,
+    ) -> Result<DiscoveryImportResult> {
+        let subnet_service = self
+            .subnet_service
+            .get()
+            .ok_or_else(|| anyhow!("Subnet service not initialized"))?;
+        let subnets = subnet_service
+            .get_all(EntityFilter::unfiltered().network_ids(&[network_id]))
+            .await?;
+
+        let metadata = DiscoveryMetadata::new(discovery_type.clone(), daemon_id);
+        let mut converted = Vec::new();
+        let mut skipped = Vec::new();
+        for host in &hosts {
+            match host.to_host(network_id, &subnets, metadata.clone()) {
+                Some(host) => converted.push(host),
+                None => skipped.push(host.ip),
+            }
+        }
+
+        self.ingest_hosts(
+            network_id,
+            daemon_id,
+            discovery_type,
+            converted,
+            skipped,
+            actor,
+        )
+        .await
+    }
+
+    /// Record hosts already built from results found outside a daemon discovery session as a
+    /// session of `discovery_type`. `skipped` are the addresses that couldn't be placed in the
+    /// network, reported back as they are.
+    pub async fn ingest_hosts(
+        &self,
+        network_id: Uuid,
+        daemon_id: Option<Uuid>,
+        discovery_type: DiscoveryType,
+        hosts: Vec<Host>,
+        skipped: Vec<IpAddr>,
+        actor: SessionActor,
+    ) -> Result<DiscoveryImportResult> {
+        let host_service = self
+            .host_service
+            .get()
+            .ok_or_else(|| anyhow!("Host service not initialized"))?;
+
+        let mut session = DiscoveryUpdatePayload::new(
+            Uuid::new_v4(),
+            daemon_id,
+            network_id,
+            discovery_type.clone(),
+        );
+        session.total_to_process = hosts.len();
+        session.started_at = Some(Utc::now());
+
+        let mut sessions = self.sessions.write().await;
+        self.transition(&mut session, DiscoveryPhase::Scanning, None, actor)
+            .await;
+        sessions.insert(session.session_id, session.clone());
+        drop(sessions);
+        let _ = self.update_tx.send(session.clone());
+
+        let mut imported = Vec::new();
+
+        for host in hosts {
+            match host_service
+                .create_host_with_services(host, Vec::new())
+                .await
+            {
+                Ok((created, _)) => {
+                    imported.push(created.id);
+                    session.discovered_host_ids.push(created.id);
+                }
+                Err(e) => {
+                    session.error = Some(e.to_string());
+                    break;
+                }
+            }
+            session.processed += 1;
+        }
+
+        session.phase = match session.error {
+            Some(_) => DiscoveryPhase::Failed,
+            None => DiscoveryPhase::Complete,
+        };
+        session.finished_at = Some(Utc::now());
+        self.update_session_by(session.clone(), actor).await?;
+
+        tracing::info!(
+            session_id = %session.session_id,
+            network_id = %network_id,
+            %discovery_type,
+            imported = %imported.len(),
+            skipped = %skipped.len(),
+            "Ingested discovery results"
+        );
+
+        Ok(DiscoveryImportResult {
+            session,
+            imported,
+            skipped,
+        })
+    }
+
+    /// Update progress for a session, as reported by its daemon
     pub async fn update_session(&self, update: DiscoveryUpdatePayload) -> Result<(), Error> {
+        let actor = match update.daemon_id {
+            Some(daemon_id) => SessionActor::Daemon { daemon_id },
+            None => SessionActor::Server,
+        };
+        self.update_session_by(update, actor).await
+    }
+
+    /// Update progress for a session, recording `actor` for any phase change. The hosts the
+    /// session reported are kept across updates, and a cancelled session that found any is marked
+    /// as cancelled with partial results.
+    pub async fn update_session_by(
+        &self,
+        mut update: DiscoveryUpdatePayload,
+        actor: SessionActor,
+    ) -> Result<(), Error> {
         tracing::debug!("Updated session {:?}", update);
 
+        // Secrets only ever travel to the daemon, never back into session state
+        update.secrets.clear();
+
         let mut sessions = self.sessions.write().await;
 
         let session = sessions
             .get_mut(&update.session_id)
             .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
 
+        // Only some updates carry the daemon's scan rate and target results, keep the last ones
+        // reported
+        if update.effective_scan_rate.is_none() {
+            update.effective_scan_rate = session.effective_scan_rate;
+        }
+        if update.target_results.is_empty() {
+            update.target_results = std::mem::take(&mut session.target_results);
+        }
+        // Hosts are reported as they're found, and a daemon that can't be reached to cancel
+        // reports nothing more
+        let mut discovered_host_ids = std::mem::take(&mut session.discovered_host_ids);
+        for host_id in std::mem::take(&mut update.discovered_host_ids) {
+            if !discovered_host_ids.contains(&host_id) {
+                discovered_host_ids.push(host_id);
+            }
+        }
+        update.discovered_host_ids = discovered_host_ids;
+        if update.phase == DiscoveryPhase::Cancelled && !update.discovered_host_ids.is_empty() {
+            update.phase = DiscoveryPhase::CancelledWithPartialResults;
+        }
+
         let daemon_id = session.daemon_id;
         tracing::debug!(
             "Updated session {}: {} ({}/{})",
@@ -415,16 +973,34 @@ impl DiscoveryService {
             update.total_to_process
         );
 
+        let to = update.phase;
+        let reason = update.error.clone();
+        self.transition(&mut update, to, reason, actor).await;
         let _ = self.update_tx.send(update.clone());
 
         *session = update.clone();
 
         let is_terminal = matches!(
             session.phase,
-            DiscoveryPhase::Cancelled | DiscoveryPhase::Complete | DiscoveryPhase::Failed
+            DiscoveryPhase::Cancelled
+                | DiscoveryPhase::CancelledWithPartialResults
+                | DiscoveryPhase::Complete
+                | DiscoveryPhase::Failed
         );
 
         if is_terminal {
+            // Imports have no daemon to attribute the run to
+            if let (Some(daemon_id), Some(started_at), Some(finished_at)) =
+                (daemon_id, session.started_at, session.finished_at)
+            {
+                self.daemon_service.record_discovery_session(
+                    daemon_id,
+                    session.network_id,
+                    started_at,
+                    finished_at,
+                );
+            }
+
             // Create historical discovery record
             let historical_discovery = Discovery {
                 id: Uuid::new_v4(),
@@ -433,16 +1009,22 @@ impl DiscoveryService {
                 base: crate::server::discovery::r#impl::base::DiscoveryBase {
                     daemon_id: session.daemon_id,
                     network_id: session.network_id,
-                    name: "Discovery Run".to_string(),
+                    name: match &session.discovery_type {
+                        DiscoveryType::Import { format } => format!("{} Import", format),
+                        DiscoveryType::Ndp { interface } => format!("NDP Scan ({})", interface),
+                        _ => "Discovery Run".to_string(),
+                    },
                     discovery_type: session.discovery_type.clone(),
                     run_type: RunType::Historical {
-                        results: session.clone(),
+                        results: Box::new(session.clone()),
                     },
                 },
             };
 
             // User cancelled session, but it finished before we could send cancellation so remove key so it doesn't cancel upcoming sessions
-            self.pull_cancellation_for_daemon(&session.daemon_id).await;
+            if let Some(daemon_id) = daemon_id {
+                self.pull_cancellation_for_daemon(&daemon_id).await;
+            }
 
             // Save to database
             if let Err(e) = self.discovery_storage.create(&historical_discovery).await {
@@ -459,25 +1041,52 @@ impl DiscoveryService {
                 );
             }
 
+            // Off the sessions lock, as full payloads have to look up the session's hosts
+            if let Some(webhook_service) = self.discovery_webhook_service.get() {
+                let webhook_service = webhook_service.clone();
+                let session = session.clone();
+                let discovery_id = historical_discovery.id;
+                tokio::spawn(async move {
+                    if let Err(e) = webhook_service.dispatch(&session, discovery_id).await {
+                        tracing::error!(
+                            session_id = %session.session_id,
+                            "Failed to send discovery webhooks: {}",
+                            e
+                        );
+                    }
+                });
+            }
+
             // Get next session info BEFORE trying to send request
-            let next_session_info = if let Some(daemon_sessions) = self
-                .daemon_sessions
-                .write()
-                .await
-                .get_mut(&session.daemon_id)
+            let next_session_info = match daemon_id {
+                Some(daemon_id) => self
+                    .daemon_sessions
+                    .write()
+                    .await
+                    .get_mut(&daemon_id)
+                    .and_then(|daemon_sessions| {
+                        daemon_sessions.retain(|s| *s != session.session_id);
+
+                        daemon_sessions.first().copied()
+                    }),
+                None => None,
+            };
+
+            // Get info about next session if it exists
+            let next_session_info = match next_session_info
+                .and_then(|next_session_id| sessions.get_mut(&next_session_id))
             {
-                daemon_sessions.retain(|s| *s != session.session_id);
-
-                // Get info about next session if it exists
-                daemon_sessions
-                    .first()
-                    .and_then(|next_session_id| sessions.get_mut(next_session_id))
-                    .map(|next_session| {
-                        next_session.phase = DiscoveryPhase::Pending;
-                        (next_session.discovery_type.clone(), next_session.session_id)
-                    })
-            } else {
-                None
+                Some(next_session) => {
+                    self.transition(
+                        next_session,
+                        DiscoveryPhase::Pending,
+                        None,
+                        SessionActor::Server,
+                    )
+                    .await;
+                    Some(next_session.clone())
+                }
+                None => None,
             };
 
             // Remove the completed session
@@ -486,36 +1095,52 @@ impl DiscoveryService {
             // Drop the sessions lock before sending the request
             drop(sessions);
 
+            // Free the session's slot and ask for one for the daemon's next session
+            let admitted = {
+                let mut limiter = lock(&self.limiter);
+                let mut admitted = limiter.release(&update.session_id);
+                if let Some(next_session) = &next_session_info {
+                    admitted
+                        .extend(limiter.request(next_session.network_id, next_session.session_id));
+                }
+                admitted
+            };
+
             // If any in queue and daemon is running push mode, initiate next session
             // If daemon is pull mode, it will request next session on its next pull
-            let daemon_is_push = self
-                .daemon_service
-                .get_by_id(&daemon_id)
-                .await?
-                .map(|d| d.base.mode == DaemonMode::Push)
-                .unwrap_or(false);
+            // If daemon is paused, the next session waits until it's resumed
+            let daemon_is_push = match daemon_id {
+                Some(daemon_id) => self
+                    .daemon_service
+                    .get_by_id(&daemon_id)
+                    .await?
+                    .map(|d| d.base.mode == DaemonMode::Push && !d.base.paused)
+                    .unwrap_or(false),
+                None => false,
+            };
 
-            if let Some((discovery_type, session_id)) = next_session_info
+            let next_session = next_session_info
+                .filter(|next_session| admitted.contains(&next_session.session_id));
+            let others: Vec<Uuid> = admitted
+                .into_iter()
+                .filter(|id| next_session.as_ref().map(|s| s.session_id) != Some(*id))
+                .collect();
+            self.dispatch_admitted(others).await;
+
+            if let Some(next_session) = next_session
+                && let Some(daemon_id) = daemon_id
                 && daemon_is_push
             {
                 tracing::debug!("Starting next session");
 
-                self.daemon_service
-                    .send_discovery_request(
-                        &daemon_id,
-                        DaemonDiscoveryRequest {
-                            discovery_type,
-                            session_id,
-                        },
-                    )
-                    .await?;
+                self.dispatch_session(&daemon_id, &next_session).await?;
             }
         }
 
         Ok(())
     }
 
-    pub async fn cancel_session(&self, session_id: Uuid) -> Result<(), Error> {
+    pub async fn cancel_session(&self, session_id: Uuid, actor: SessionActor) -> Result<(), Error> {
         // Get the session
         let session = match self.get_session(&session_id).await {
             Some(session) => session,
@@ -524,8 +1149,11 @@ impl DiscoveryService {
             }
         };
 
+        // Imports run on the server, and finish before they could be cancelled
+        let Some(daemon_id) = session.daemon_id else {
+            return Err(anyhow!("Session '{}' isn't run by a daemon", session_id));
+        };
         let network_id = session.network_id;
-        let daemon_id = session.daemon_id;
         let phase = session.phase;
 
         // Handle based on current phase
@@ -536,21 +1164,45 @@ impl DiscoveryService {
                 let mut daemon_sessions = self.daemon_sessions.write().await;
 
                 // Remove from sessions map
-                sessions.remove(&session_id);
+                if let Some(mut cancelled) = sessions.remove(&session_id) {
+                    self.transition(
+                        &mut cancelled,
+                        DiscoveryPhase::Cancelled,
+                        Some("Cancelled before starting".to_string()),
+                        actor,
+                    )
+                    .await;
+                }
 
-                // Remove from daemon queue
+                // Remove from daemon queue, letting the next session ask for a slot if this one
+                // was at the head
+                let mut next_session = None;
                 if let Some(queue) = daemon_sessions.get_mut(&daemon_id) {
+                    let was_head = queue.first() == Some(&session_id);
                     queue.retain(|id| *id != session_id);
+                    if was_head {
+                        next_session = queue.first().copied();
+                    }
                 }
 
                 drop(sessions);
                 drop(daemon_sessions);
 
+                let admitted = {
+                    let mut limiter = lock(&self.limiter);
+                    let mut admitted = limiter.release(&session_id);
+                    if let Some(next_session) = next_session {
+                        admitted.extend(limiter.request(network_id, next_session));
+                    }
+                    admitted
+                };
+                self.dispatch_admitted(admitted).await;
+
                 // Broadcast cancellation update so frontend knows
                 let cancelled_update = DiscoveryUpdatePayload {
                     session_id,
                     network_id,
-                    daemon_id,
+                    daemon_id: Some(daemon_id),
                     phase: DiscoveryPhase::Cancelled,
                     processed: 0,
                     total_to_process: session.total_to_process,
@@ -558,6 +1210,10 @@ impl DiscoveryService {
                     started_at: session.started_at,
                     finished_at: Some(Utc::now()),
                     discovery_type: session.discovery_type,
+                    effective_scan_rate: session.effective_scan_rate,
+                    target_results: session.target_results,
+                    discovered_host_ids: Vec::new(),
+                    secrets: ResolvedSecrets::new(),
                 };
                 let _ = self.update_tx.send(cancelled_update);
 
@@ -575,17 +1231,28 @@ impl DiscoveryService {
                 if let Some(daemon) = self.daemon_service.get_by_id(&daemon_id).await? {
                     match daemon.base.mode {
                         DaemonMode::Push => {
-                            self.daemon_service
+                            if let Err(e) = self
+                                .daemon_service
                                 .send_discovery_cancellation(&daemon, session_id)
                                 .await
-                                .map_err(|e| {
-                                    anyhow!(
-                                        "Failed to send discovery cancellation to daemon {} for session {}: {}",
-                                        daemon_id,
-                                        session_id,
-                                        e
-                                    )
-                                })?;
+                            {
+                                // The daemon can't report anything more, so the session ends
+                                // with the hosts it had already reported
+                                tracing::warn!(
+                                    "Daemon {} unreachable cancelling session {}, cancelling it \
+                                     with the results reported so far: {}",
+                                    daemon_id,
+                                    session_id,
+                                    e
+                                );
+
+                                let mut cancelled = session;
+                                cancelled.phase = DiscoveryPhase::Cancelled;
+                                cancelled.error =
+                                    Some(format!("Daemon unreachable during cancellation: {}", e));
+                                cancelled.finished_at = Some(Utc::now());
+                                return self.update_session_by(cancelled, actor).await;
+                            }
 
                             tracing::info!(
                                 "Cancellation request sent to daemon {} for active session {}",
@@ -620,7 +1287,10 @@ impl DiscoveryService {
             }
 
             // Terminal phases: already done
-            DiscoveryPhase::Complete | DiscoveryPhase::Failed | DiscoveryPhase::Cancelled => {
+            DiscoveryPhase::Complete
+            | DiscoveryPhase::Failed
+            | DiscoveryPhase::Cancelled
+            | DiscoveryPhase::CancelledWithPartialResults => {
                 tracing::info!(
                     "Session {} is already in terminal state: {}, nothing to cancel",
                     session_id,
@@ -631,32 +1301,109 @@ impl DiscoveryService {
         }
     }
 
-    /// Cleanup old completed sessions (call periodically)
-    pub async fn cleanup_old_sessions(&self, max_age_hours: i64) {
-        let cutoff = Utc::now() - chrono::Duration::hours(max_age_hours);
+    /// Prune finished sessions, their histories and their historical results once they've
+    /// outlived the retention `policy` gives their type, and sessions that have stalled without
+    /// finishing (call periodically)
+    pub async fn cleanup_old_sessions(&self, policy: &SessionRetentionPolicy) {
+        let now = Utc::now();
+
+        // Historical results are what's left of sessions in the database, and are only pruned
+        // for the types a retention period is configured for
+        for (scope, retention) in policy.periods() {
+            let Some(cutoff) = now.checked_sub_signed(retention) else {
+                continue;
+            };
+
+            let mut filter = EntityFilter::unfiltered()
+                .historical_discovery()
+                .finished_before(cutoff);
+            filter = match &scope {
+                RetentionScope::Only(discovery_type) => filter.discovery_types(&[*discovery_type]),
+                RetentionScope::AllExcept(discovery_types) => {
+                    filter.exclude_discovery_types(discovery_types)
+                }
+            };
+            if policy.keep_latest_successful {
+                filter = filter.exclude_latest_successful_discovery();
+            }
+
+            match self.discovery_storage.delete_where(filter).await {
+                Ok(0) => {}
+                Ok(pruned) => tracing::debug!("Pruned {} historical discoveries", pruned),
+                Err(e) => tracing::error!("Failed to prune historical discoveries: {}", e),
+            }
+        }
+
         let mut sessions = self.sessions.write().await;
         let mut daemon_sessions = self.daemon_sessions.write().await;
         let mut daemon_pull_cancellations = self.daemon_pull_cancellations.write().await;
 
-        let mut to_remove = Vec::new();
-        for (session_id, session) in sessions.iter() {
-            if let Some(finished_at) = session.finished_at
-                && finished_at < cutoff
-            {
-                to_remove.push(*session_id);
-            }
+        let finished: Vec<FinishedSession> = sessions
+            .values()
+            .filter_map(|session| {
+                Some(FinishedSession {
+                    id: session.session_id,
+                    // Imports have no daemon
+                    source: session.daemon_id.unwrap_or(session.network_id),
+                    discovery_type: (&session.discovery_type).into(),
+                    succeeded: session.phase == DiscoveryPhase::Complete,
+                    finished_at: session.finished_at?,
+                })
+            })
+            .collect();
+
+        // Histories outlive their sessions, which are dropped as soon as they finish
+        let mut histories = self.histories.write().await;
+        let finished_histories: Vec<FinishedSession> = histories
+            .values()
+            .filter_map(|history| {
+                Some(FinishedSession {
+                    id: history.session_id,
+                    // Imports have no daemon
+                    source: history.daemon_id.unwrap_or(history.network_id),
+                    discovery_type: history.discovery_type,
+                    succeeded: history.phase() == Some(DiscoveryPhase::Complete),
+                    finished_at: history.finished_at()?,
+                })
+            })
+            .collect();
+        let expired_histories = policy.expired(&finished_histories, now);
+        histories.retain(|session_id, _| !expired_histories.contains(session_id));
+
+        // Sessions that never finish would otherwise be kept forever
+        let stalled_cutoff = now - Duration::days(STALLED_SESSION_DAYS);
+        let stalled: Vec<Uuid> = histories
+            .values()
+            .filter(|history| history.stalled_since(stalled_cutoff))
+            .map(|history| history.session_id)
+            .collect();
+        for session_id in &stalled {
+            histories.remove(session_id);
+            tracing::debug!("Pruned stalled discovery session {}", session_id);
         }
 
-        for session_id in to_remove {
+        let mut removed = Vec::new();
+        for session_id in policy.expired(&finished, now).into_iter().chain(stalled) {
             if let Some(session) = sessions.remove(&session_id) {
-                daemon_pull_cancellations.remove(&session.daemon_id);
+                if let Some(daemon_id) = session.daemon_id {
+                    daemon_pull_cancellations.remove(&daemon_id);
 
-                if let Some(daemon_sessions) = daemon_sessions.get_mut(&session.daemon_id) {
-                    daemon_sessions.retain(|s| *s != session.session_id);
+                    if let Some(daemon_sessions) = daemon_sessions.get_mut(&daemon_id) {
+                        daemon_sessions.retain(|s| *s != session.session_id);
+                    }
                 }
 
                 tracing::debug!("Cleaned up old discovery session {}", session_id);
+                removed.push(session_id);
             }
         }
+
+        drop((
+            sessions,
+            daemon_sessions,
+            daemon_pull_cancellations,
+            histories,
+        ));
+        self.release_slots(&removed).await;
     }
 }
@@ -0,0 +1,1038 @@
+use serial_test::serial;
+
+use crate::{
+    daemon::discovery::types::base::DiscoveryPhase,
+    server::{
+        config::ServerConfig,
+        daemons::r#impl::{
+            api::{DaemonDiscoveryRequest, DiscoveryUpdatePayload},
+            base::DaemonMode,
+        },
+        discovery::r#impl::{
+            base::{Discovery, DiscoveryBase},
+            estimate::FALLBACK_CONCURRENT_SCANS,
+            history::{STALLED_SESSION_DAYS, SessionActor, SessionHistory},
+            import::{ImportFormat, parse},
+            retention::{SessionRetentionPolicy, SessionRetentionRule},
+            target_policy::{EmptyAllowlist, ScanTargetGuard, ScanTargetPolicy},
+            types::{
+                DiscoveryType, DiscoveryTypeDiscriminants, HostNamingFallback, RunType, ScanRate,
+            },
+        },
+        hosts::r#impl::ports::TransportProtocol,
+        services::r#impl::base::Service,
+        shared::{
+            services::{factory::ServiceFactory, traits::CrudService},
+            storage::{filter::EntityFilter, traits::StorableEntity},
+            types::entities::{DiscoveryMetadata, EntitySource},
+        },
+    },
+    tests::*,
+};
+
+#[tokio::test]
+#[serial]
+async fn test_scan_target_policy_enforced_before_dispatch() {
+    let (storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+
+    let allowed = services
+        .subnet_service
+        .create(subnet(&network.id))
+        .await
+        .unwrap();
+    let mut denied = subnet(&network.id);
+    denied.base.cidr = "10.20.0.0/16".parse().unwrap();
+    let denied = services.subnet_service.create(denied).await.unwrap();
+
+    let mut daemon_host = host(&network.id);
+    daemon_host.base.interfaces = vec![interface(&allowed.id), interface(&denied.id)];
+    let daemon_host = services.host_service.create(daemon_host).await.unwrap();
+    let daemon = daemon(&network.id, &daemon_host.id);
+
+    let guard = ScanTargetGuard::new(
+        ScanTargetPolicy {
+            deny: vec!["10.0.0.0/8".parse().unwrap()],
+            ..ScanTargetPolicy::unrestricted()
+        },
+        storage.subnets.clone(),
+        storage.hosts.clone(),
+    );
+
+    let network_scan = |subnet_ids| DiscoveryType::Network {
+        subnet_ids,
+        host_naming_fallback: HostNamingFallback::default(),
+        scan_rate: ScanRate::default(),
+        snmp_credential: None,
+        targets: Vec::new(),
+    };
+
+    let err = guard
+        .check(&daemon, network_scan(Some(vec![denied.id])))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("10.20.0.0/16"), "{}", err);
+
+    let checked = guard
+        .check(&daemon, network_scan(Some(vec![allowed.id])))
+        .await
+        .unwrap();
+    assert_eq!(checked, network_scan(Some(vec![allowed.id])));
+
+    // Without explicit subnets the daemon's interfaced subnets are checked, and include a denied one
+    assert!(guard.check(&daemon, network_scan(None)).await.is_err());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_adaptive_scan_rate_round_trips_and_is_recorded() {
+    let (storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let daemon_host = services
+        .host_service
+        .create(host(&network.id))
+        .await
+        .unwrap();
+    let mut pull_daemon = daemon(&network.id, &daemon_host.id);
+    pull_daemon.base.mode = DaemonMode::Pull;
+    let daemon = services.daemon_service.create(pull_daemon).await.unwrap();
+
+    let adaptive_scan = |initial, max| DiscoveryType::Network {
+        subnet_ids: None,
+        host_naming_fallback: HostNamingFallback::default(),
+        scan_rate: ScanRate::Adaptive { initial, max },
+        snmp_credential: None,
+        targets: Vec::new(),
+    };
+
+    // Adaptive parameters survive the trip to the daemon, and older requests stay fixed-rate
+    let request = DaemonDiscoveryRequest {
+        session_id: uuid::Uuid::new_v4(),
+        discovery_type: adaptive_scan(4, 32),
+        secrets: Default::default(),
+        protocol_version: None,
+    };
+    let json = serde_json::to_value(&request).unwrap();
+    assert_eq!(
+        json["discovery_type"]["scan_rate"],
+        serde_json::json!({"mode": "adaptive", "initial": 4, "max": 32})
+    );
+    let parsed: DaemonDiscoveryRequest = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed.discovery_type, adaptive_scan(4, 32));
+
+    let legacy: DiscoveryType =
+        serde_json::from_str(r#"{"type": "Network", "subnet_ids": null}"#).unwrap();
+    assert_eq!(
+        legacy,
+        DiscoveryType::Network {
+            subnet_ids: None,
+            host_naming_fallback: HostNamingFallback::default(),
+            scan_rate: ScanRate::Fixed,
+            snmp_credential: None,
+            targets: Vec::new(),
+        }
+    );
+
+    // The server caps how far a daemon may ramp up
+    let guard = ScanTargetGuard::new(
+        ScanTargetPolicy {
+            max_scan_rate: 16,
+            ..ScanTargetPolicy::unrestricted()
+        },
+        storage.subnets.clone(),
+        storage.hosts.clone(),
+    );
+    assert_eq!(
+        guard.check(&daemon, adaptive_scan(64, 100)).await.unwrap(),
+        adaptive_scan(16, 16)
+    );
+
+    let session = services
+        .discovery_service
+        .start_session(Discovery::new(DiscoveryBase {
+            discovery_type: adaptive_scan(4, 32),
+            run_type: RunType::AdHoc { last_run: None },
+            name: "Adaptive scan".to_string(),
+            daemon_id: Some(daemon.id),
+            network_id: network.id,
+        }))
+        .await
+        .unwrap();
+
+    let mut update = session.clone();
+    update.phase = DiscoveryPhase::Scanning;
+    update.effective_scan_rate = Some(12);
+    services
+        .discovery_service
+        .update_session(update.clone())
+        .await
+        .unwrap();
+
+    // Updates without a rate keep the last one the daemon reported
+    update.effective_scan_rate = None;
+    update.processed = 10;
+    services
+        .discovery_service
+        .update_session(update.clone())
+        .await
+        .unwrap();
+    let current = services
+        .discovery_service
+        .get_session(&session.session_id)
+        .await
+        .unwrap();
+    assert_eq!(current.effective_scan_rate, Some(12));
+
+    update.phase = DiscoveryPhase::Complete;
+    update.effective_scan_rate = Some(20);
+    services
+        .discovery_service
+        .update_session(update)
+        .await
+        .unwrap();
+
+    let historical = services
+        .discovery_service
+        .get_all(EntityFilter::unfiltered().network_ids(&[network.id]))
+        .await
+        .unwrap()
+        .into_iter()
+        .find_map(|d| match d.base.run_type {
+            RunType::Historical { results } => Some(results),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(historical.effective_scan_rate, Some(20));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_dry_run_estimates_daemon_interfaced_subnets() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let lan = services
+        .subnet_service
+        .create(subnet(&network.id))
+        .await
+        .unwrap();
+
+    let mut daemon_host = host(&network.id);
+    daemon_host.base.interfaces = vec![interface(&lan.id)];
+    let daemon_host = services.host_service.create(daemon_host).await.unwrap();
+    let mut daemon = services
+        .daemon_service
+        .create(daemon(&network.id, &daemon_host.id))
+        .await
+        .unwrap();
+
+    let discovery = DiscoveryBase {
+        discovery_type: DiscoveryType::Network {
+            subnet_ids: None,
+            host_naming_fallback: HostNamingFallback::default(),
+            scan_rate: ScanRate::default(),
+            snmp_credential: None,
+            targets: Vec::new(),
+        },
+        run_type: RunType::AdHoc { last_run: None },
+        name: "Dry run".to_string(),
+        daemon_id: Some(daemon.id),
+        network_id: network.id,
+    };
+    let ports = Service::all_discovery_ports().len() as u64;
+
+    // Without a reported rate the conservative fallback is used
+    let estimate = services
+        .discovery_service
+        .estimate(&discovery)
+        .await
+        .unwrap();
+    assert_eq!(estimate.targets, 256);
+    assert_eq!(estimate.probes, 256 * ports);
+    assert!(!estimate.rate_known);
+    assert!(!estimate.very_large);
+
+    daemon.base.capabilities.concurrent_scans = Some(FALLBACK_CONCURRENT_SCANS * 4);
+    daemon.base.capabilities.port_batch_size = Some(200);
+    services.daemon_service.update(&mut daemon).await.unwrap();
+
+    let faster = services
+        .discovery_service
+        .estimate(&discovery)
+        .await
+        .unwrap();
+    assert!(faster.rate_known);
+    assert_eq!(faster.probes, estimate.probes);
+    assert!(faster.max_duration_secs < estimate.max_duration_secs);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_session_transitions_logged_in_order() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let daemon_host = services
+        .host_service
+        .create(host(&network.id))
+        .await
+        .unwrap();
+    let mut pull_daemon = daemon(&network.id, &daemon_host.id);
+    pull_daemon.base.mode = DaemonMode::Pull;
+    let daemon = services.daemon_service.create(pull_daemon).await.unwrap();
+    let user_id = uuid::Uuid::new_v4();
+
+    let session = services
+        .discovery_service
+        .start_session_by(
+            Discovery::new(DiscoveryBase {
+                discovery_type: DiscoveryType::Network {
+                    subnet_ids: None,
+                    host_naming_fallback: HostNamingFallback::default(),
+                    scan_rate: ScanRate::default(),
+                    snmp_credential: None,
+                    targets: Vec::new(),
+                },
+                run_type: RunType::AdHoc { last_run: None },
+                name: "Scan".to_string(),
+                daemon_id: Some(daemon.id),
+                network_id: network.id,
+            }),
+            SessionActor::User { user_id },
+        )
+        .await
+        .unwrap();
+
+    // Start, running with progress reports, then the daemon gives up on a timeout
+    let mut update = session.clone();
+    update.phase = DiscoveryPhase::Scanning;
+    for processed in [1, 2] {
+        update.processed = processed;
+        services
+            .discovery_service
+            .update_session(update.clone())
+            .await
+            .unwrap();
+    }
+    update.phase = DiscoveryPhase::Failed;
+    update.error = Some("Discovery timed out".to_string());
+    update.finished_at = Some(chrono::Utc::now());
+    services
+        .discovery_service
+        .update_session(update)
+        .await
+        .unwrap();
+
+    let history = services
+        .discovery_service
+        .get_session_history(&session.session_id)
+        .await
+        .unwrap();
+    let by_daemon = SessionActor::Daemon {
+        daemon_id: daemon.id,
+    };
+    let transitions: Vec<_> = history
+        .transitions
+        .iter()
+        .map(|t| (t.from, t.to, t.reason.as_deref(), t.actor))
+        .collect();
+    assert_eq!(
+        transitions,
+        vec![
+            (
+                None,
+                DiscoveryPhase::Pending,
+                None,
+                SessionActor::User { user_id }
+            ),
+            (
+                Some(DiscoveryPhase::Pending),
+                DiscoveryPhase::Scanning,
+                None,
+                by_daemon
+            ),
+            (
+                Some(DiscoveryPhase::Scanning),
+                DiscoveryPhase::Failed,
+                Some("Discovery timed out"),
+                by_daemon
+            ),
+        ]
+    );
+    assert!(
+        history
+            .transitions
+            .iter()
+            .zip(history.transitions.iter().skip(1))
+            .all(|(a, b)| a.at <= b.at)
+    );
+    assert_eq!(history.phase(), Some(DiscoveryPhase::Failed));
+
+    // Histories are retained like sessions, past the session itself finishing
+    let retained_for = |hours| SessionRetentionPolicy {
+        default_hours: Some(hours),
+        ..Default::default()
+    };
+    services
+        .discovery_service
+        .cleanup_old_sessions(&retained_for(1))
+        .await;
+    assert!(
+        services
+            .discovery_service
+            .get_session_history(&session.session_id)
+            .await
+            .is_some()
+    );
+    services
+        .discovery_service
+        .cleanup_old_sessions(&retained_for(0))
+        .await;
+    assert!(
+        services
+            .discovery_service
+            .get_session_history(&session.session_id)
+            .await
+            .is_none()
+    );
+}
+
+#[test]
+fn test_unfinished_histories_stall_after_their_last_transition() {
+    let now = chrono::Utc::now();
+    let cutoff = now - chrono::Duration::days(STALLED_SESSION_DAYS);
+    let mut history = SessionHistory::new(
+        uuid::Uuid::new_v4(),
+        uuid::Uuid::new_v4(),
+        Some(uuid::Uuid::new_v4()),
+        DiscoveryTypeDiscriminants::Network,
+    );
+    let started_at = cutoff - chrono::Duration::hours(1);
+    history.record(
+        DiscoveryPhase::Pending,
+        None,
+        SessionActor::Server,
+        started_at,
+    );
+    history.record(
+        DiscoveryPhase::Scanning,
+        None,
+        SessionActor::Server,
+        started_at,
+    );
+    assert!(history.stalled_since(cutoff));
+
+    // Moving since the cutoff keeps it, and so does finishing, which retention covers instead
+    let mut moving = history.clone();
+    moving.record(DiscoveryPhase::Starting, None, SessionActor::Server, now);
+    assert!(!moving.stalled_since(cutoff));
+    history.record(
+        DiscoveryPhase::Complete,
+        None,
+        SessionActor::Server,
+        started_at,
+    );
+    assert!(!history.stalled_since(cutoff));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_nmap_import_creates_completed_session() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let subnet = services
+        .subnet_service
+        .create(subnet(&network.id))
+        .await
+        .unwrap();
+
+    let xml = r#"<?xml version="1.0"?>
+<nmaprun scanner="nmap">
+  <host>
+    <status state="up"/>
+    <address addr="192.168.1.20" addrtype="ipv4"/>
+    <hostnames><hostname name="printer.lan" type="PTR"/></hostnames>
+    <ports>
+      <port protocol="tcp" portid="80"><state state="open"/><service name="http"/></port>
+      <port protocol="tcp" portid="631"><state state="open"/><service name="ipp"/></port>
+      <port protocol="tcp" portid="8080"><state state="closed"/></port>
+    </ports>
+  </host>
+  <host>
+    <status state="up"/>
+    <address addr="192.168.1.30" addrtype="ipv4"/>
+    <ports><port protocol="udp" portid="53"><state state="open"/></port></ports>
+  </host>
+  <host>
+    <status state="up"/>
+    <address addr="10.1.1.1" addrtype="ipv4"/>
+  </host>
+</nmaprun>"#;
+
+    let user_id = uuid::Uuid::new_v4();
+    let import = || async {
+        let hosts = parse(xml.as_bytes(), ImportFormat::NmapXml).unwrap();
+        services
+            .discovery_service
+            .import(
+                network.id,
+                ImportFormat::NmapXml,
+                hosts,
+                SessionActor::User { user_id },
+            )
+            .await
+            .unwrap()
+    };
+    let result = import().await;
+
+    assert_eq!(result.session.phase, DiscoveryPhase::Complete);
+    assert_eq!(result.session.total_to_process, 3);
+    assert_eq!(result.session.daemon_id, None);
+    assert_eq!(result.imported.len(), 2);
+    // Outside every subnet of the network
+    assert_eq!(
+        result.skipped,
+        vec!["10.1.1.1".parse::<std::net::IpAddr>().unwrap()]
+    );
+
+    let hosts = services
+        .host_service
+        .get_all(EntityFilter::unfiltered().network_ids(&[network.id]))
+        .await
+        .unwrap();
+    let ports = |ip: &str| {
+        let host = hosts
+            .iter()
+            .find(|h| h.base.interfaces[0].base.ip_address.to_string() == ip)
+            .unwrap();
+        assert_eq!(host.base.interfaces[0].base.subnet_id, subnet.id);
+        let mut ports: Vec<_> = host
+            .base
+            .ports
+            .iter()
+            .map(|p| (p.base.number(), p.base.protocol()))
+            .collect();
+        ports.sort();
+        (host.base.name.clone(), ports)
+    };
+    assert_eq!(
+        ports("192.168.1.20"),
+        (
+            "printer.lan".to_string(),
+            vec![(80, TransportProtocol::Tcp), (631, TransportProtocol::Tcp)]
+        )
+    );
+    assert_eq!(
+        ports("192.168.1.30"),
+        (
+            "192.168.1.30".to_string(),
+            vec![(53, TransportProtocol::Udp)]
+        )
+    );
+
+    // Finished like any other session, with a historical run kept
+    assert!(
+        services
+            .discovery_service
+            .get_session(&result.session.session_id)
+            .await
+            .is_none()
+    );
+    let history = services
+        .discovery_service
+        .get_session_history(&result.session.session_id)
+        .await
+        .unwrap();
+    assert_eq!(history.phase(), Some(DiscoveryPhase::Complete));
+    let runs = services
+        .discovery_service
+        .get_all(EntityFilter::unfiltered().network_ids(&[network.id]))
+        .await
+        .unwrap();
+    let run = runs
+        .iter()
+        .find(|d| matches!(&d.base.run_type, RunType::Historical { results } if results.session_id == result.session.session_id))
+        .unwrap();
+    assert_eq!(
+        run.base.discovery_type,
+        DiscoveryType::Import {
+            format: ImportFormat::NmapXml
+        }
+    );
+
+    // Importing the same scan again reconciles with the hosts already there
+    let again = import().await;
+    assert_eq!(again.imported, result.imported);
+    let count = services
+        .host_service
+        .get_all(EntityFilter::unfiltered().network_ids(&[network.id]))
+        .await
+        .unwrap()
+        .len();
+    assert_eq!(count, hosts.len());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_historical_sessions_pruned_per_discovery_type() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let host = services
+        .host_service
+        .create(host(&network.id))
+        .await
+        .unwrap();
+    let first = services
+        .daemon_service
+        .create(daemon(&network.id, &host.id))
+        .await
+        .unwrap();
+    let second = services
+        .daemon_service
+        .create(daemon(&network.id, &host.id))
+        .await
+        .unwrap();
+
+    let network_scan = DiscoveryType::Network {
+        subnet_ids: None,
+        host_naming_fallback: HostNamingFallback::default(),
+        scan_rate: ScanRate::default(),
+        snmp_credential: None,
+        targets: Vec::new(),
+    };
+    let self_report = DiscoveryType::SelfReport { host_id: host.id };
+
+    let other_network = services
+        .network_service
+        .create(crate::tests::network(&organization.id))
+        .await
+        .unwrap();
+    let import = DiscoveryType::Import {
+        format: ImportFormat::NmapXml,
+    };
+
+    let discovery_service = services.discovery_service.clone();
+    let finished = |network_id: uuid::Uuid,
+                    daemon_id: Option<uuid::Uuid>,
+                    discovery_type: DiscoveryType,
+                    phase: DiscoveryPhase,
+                    hours_ago: i64| {
+        let discovery_service = discovery_service.clone();
+        async move {
+            let finished_at = chrono::Utc::now() - chrono::Duration::hours(hours_ago);
+            let mut results = DiscoveryUpdatePayload::new(
+                uuid::Uuid::new_v4(),
+                daemon_id,
+                network_id,
+                discovery_type.clone(),
+            );
+            results.phase = phase;
+            results.started_at = Some(finished_at);
+            results.finished_at = Some(finished_at);
+
+            discovery_service
+                .create(Discovery::new(DiscoveryBase {
+                    discovery_type,
+                    run_type: RunType::Historical {
+                        results: Box::new(results),
+                    },
+                    name: "Discovery Run".to_string(),
+                    daemon_id,
+                    network_id,
+                }))
+                .await
+                .unwrap()
+                .id
+        }
+    };
+
+    use DiscoveryPhase::{Complete, Failed};
+    let network_id = network.id;
+    let (first_id, second_id) = (Some(first.id), Some(second.id));
+    let latest_scan = finished(network_id, first_id, network_scan.clone(), Complete, 10).await;
+    let recent_scan = finished(network_id, first_id, network_scan.clone(), Complete, 48).await;
+    let old_scan = finished(network_id, first_id, network_scan.clone(), Failed, 96).await;
+    let old_report = finished(network_id, first_id, self_report.clone(), Complete, 30).await;
+    // The second daemon's only successful session, kept however old
+    let only_report = finished(network_id, second_id, self_report.clone(), Complete, 100).await;
+    let failed_report = finished(network_id, second_id, self_report.clone(), Failed, 50).await;
+    // Imports have no daemon, so each network's latest import is kept instead
+    let latest_import = finished(network_id, None, import.clone(), Complete, 100).await;
+    let old_import = finished(network_id, None, import.clone(), Complete, 120).await;
+    let other_import = finished(other_network.id, None, import.clone(), Complete, 200).await;
+
+    let remaining = || async {
+        let mut remaining: Vec<uuid::Uuid> = services
+            .discovery_service
+            .get_all(EntityFilter::unfiltered().historical_discovery())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|d| d.id)
+            .collect();
+        remaining.sort();
+        remaining
+    };
+    let all = remaining().await;
+    assert_eq!(all.len(), 9);
+
+    // Without a retention period configured, history is kept forever
+    services
+        .discovery_service
+        .cleanup_old_sessions(&SessionRetentionPolicy::default())
+        .await;
+    assert_eq!(remaining().await, all);
+
+    services
+        .discovery_service
+        .cleanup_old_sessions(&SessionRetentionPolicy {
+            default_hours: Some(24),
+            rules: vec![SessionRetentionRule {
+                discovery_type: DiscoveryTypeDiscriminants::Network,
+                hours: 72,
+            }],
+            keep_latest_successful: true,
+        })
+        .await;
+
+    let mut expected = vec![
+        latest_scan,
+        recent_scan,
+        only_report,
+        latest_import,
+        other_import,
+    ];
+    expected.sort();
+    assert_eq!(remaining().await, expected);
+
+    for pruned in [old_scan, old_report, failed_report, old_import] {
+        assert!(
+            services
+                .discovery_service
+                .get_by_id(&pruned)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_cancelled_session_keeps_partial_results() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let daemon_host = services
+        .host_service
+        .create(host(&network.id))
+        .await
+        .unwrap();
+    let mut pull_daemon = daemon(&network.id, &daemon_host.id);
+    pull_daemon.base.mode = DaemonMode::Pull;
+    let mut daemon = services.daemon_service.create(pull_daemon).await.unwrap();
+
+    let discovery_service = services.discovery_service.clone();
+    let scanning = || {
+        let discovery_service = discovery_service.clone();
+        let (daemon_id, network_id) = (daemon.id, network.id);
+        async move {
+            let session = discovery_service
+                .start_session(Discovery::new(DiscoveryBase {
+                    discovery_type: DiscoveryType::Network {
+                        subnet_ids: None,
+                        host_naming_fallback: HostNamingFallback::default(),
+                        scan_rate: ScanRate::default(),
+                        snmp_credential: None,
+                        targets: Vec::new(),
+                    },
+                    run_type: RunType::AdHoc { last_run: None },
+                    name: "Scan".to_string(),
+                    daemon_id: Some(daemon_id),
+                    network_id,
+                }))
+                .await
+                .unwrap();
+
+            let mut update = session;
+            update.phase = DiscoveryPhase::Scanning;
+            update.started_at = Some(chrono::Utc::now());
+            discovery_service
+                .update_session(update.clone())
+                .await
+                .unwrap();
+            update
+        }
+    };
+    let found_host = |name: &str| {
+        let mut found = host(&network.id);
+        found.base.name = name.to_string();
+        found.base.source = EntitySource::Discovery {
+            metadata: vec![DiscoveryMetadata {
+                daemon_id: Some(daemon.id),
+                ..Default::default()
+            }],
+        };
+        found
+    };
+    // The daemon reports the hosts it has created with each progress update
+    let report = |mut session: DiscoveryUpdatePayload, host_ids: Vec<uuid::Uuid>| {
+        let discovery_service = discovery_service.clone();
+        async move {
+            session.discovered_host_ids = host_ids;
+            discovery_service
+                .update_session(session.clone())
+                .await
+                .unwrap();
+            session
+        }
+    };
+    let historical = |session_id: uuid::Uuid| {
+        let discovery_service = discovery_service.clone();
+        async move {
+            discovery_service
+                .get_all(EntityFilter::unfiltered().historical_discovery())
+                .await
+                .unwrap()
+                .into_iter()
+                .find_map(|d| match d.base.run_type {
+                    RunType::Historical { results } if results.session_id == session_id => {
+                        Some(results)
+                    }
+                    _ => None,
+                })
+                .unwrap()
+        }
+    };
+
+    // The daemon reported a host before being cancelled, so it's kept with the session. A host
+    // the session didn't report isn't, even though it was discovered while the session ran.
+    let session = scanning().await;
+    let partial = services
+        .host_service
+        .create(found_host("partial-host"))
+        .await
+        .unwrap();
+    services
+        .host_service
+        .create(found_host("unrelated-host"))
+        .await
+        .unwrap();
+    let mut session = report(session, vec![partial.id]).await;
+    session.phase = DiscoveryPhase::Cancelled;
+    session.finished_at = Some(chrono::Utc::now());
+    report(session.clone(), vec![partial.id]).await;
+
+    let results = historical(session.session_id).await;
+    assert_eq!(results.phase, DiscoveryPhase::CancelledWithPartialResults);
+    assert_eq!(results.discovered_host_ids, vec![partial.id]);
+
+    // Cancelled before finding anything is a plain cancellation
+    let mut session = scanning().await;
+    session.phase = DiscoveryPhase::Cancelled;
+    session.finished_at = Some(chrono::Utc::now());
+    report(session.clone(), Vec::new()).await;
+
+    let results = historical(session.session_id).await;
+    assert_eq!(results.phase, DiscoveryPhase::Cancelled);
+    assert!(results.discovered_host_ids.is_empty());
+
+    // A daemon that can't be reached to cancel can't report anything more, but what it had
+    // already reported is kept
+    let session = scanning().await;
+    let reported = services
+        .host_service
+        .create(found_host("reported-host"))
+        .await
+        .unwrap();
+    let session = report(session, vec![reported.id]).await;
+    daemon.base.mode = DaemonMode::Push;
+    daemon.base.ip = "127.0.0.1".parse().unwrap();
+    daemon.base.port = 9;
+    services.daemon_service.update(&mut daemon).await.unwrap();
+    discovery_service
+        .cancel_session(session.session_id, SessionActor::Server)
+        .await
+        .unwrap();
+
+    let results = historical(session.session_id).await;
+    assert_eq!(results.phase, DiscoveryPhase::CancelledWithPartialResults);
+    assert!(results.error.unwrap().contains("unreachable"));
+    assert_eq!(results.discovered_host_ids, vec![reported.id]);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_discovery_slots_freed_when_dispatch_fails_or_daemon_deleted() {
+    let (storage, _container) = test_storage().await;
+    let services = ServiceFactory::new(
+        &storage,
+        Some(ServerConfig {
+            discovery_max_concurrent_sessions: Some(1),
+            scan_empty_allowlist: Some(EmptyAllowlist::AllowAll),
+            ..Default::default()
+        }),
+    )
+    .await
+    .unwrap();
+    let discovery_service = &services.discovery_service;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let daemon_host = services
+        .host_service
+        .create(host(&network.id))
+        .await
+        .unwrap();
+
+    let create_daemon = |mode| {
+        let mut daemon = daemon(&network.id, &daemon_host.id);
+        daemon.base.mode = mode;
+        daemon.base.ip = "127.0.0.1".parse().unwrap();
+        daemon.base.port = 9;
+        services.daemon_service.create(daemon)
+    };
+    let scan = |daemon_id| {
+        Discovery::new(DiscoveryBase {
+            discovery_type: DiscoveryType::Network {
+                subnet_ids: None,
+                host_naming_fallback: HostNamingFallback::default(),
+                scan_rate: ScanRate::default(),
+                snmp_credential: None,
+                targets: Vec::new(),
+            },
+            run_type: RunType::AdHoc { last_run: None },
+            name: "Network scan".to_string(),
+            daemon_id: Some(daemon_id),
+            network_id: network.id,
+        })
+    };
+
+    // The only slot goes to a session its daemon can't be reached to start, which fails it
+    let unreachable = create_daemon(DaemonMode::Push).await.unwrap();
+    assert!(
+        discovery_service
+            .start_session(scan(unreachable.id))
+            .await
+            .is_err()
+    );
+    assert!(
+        discovery_service
+            .get_sessions_for_daemon(&unreachable.id)
+            .await
+            .is_empty()
+    );
+
+    // So the next session still gets it
+    let first = create_daemon(DaemonMode::Pull).await.unwrap();
+    let running = discovery_service
+        .start_session(scan(first.id))
+        .await
+        .unwrap();
+    let handed_out = discovery_service
+        .next_session_for_daemon(&first.id)
+        .await
+        .unwrap();
+    assert_eq!(handed_out.session_id, running.session_id);
+
+    let second = create_daemon(DaemonMode::Pull).await.unwrap();
+    let waiting = discovery_service
+        .start_session(scan(second.id))
+        .await
+        .unwrap();
+    assert!(
+        discovery_service
+            .next_session_for_daemon(&second.id)
+            .await
+            .is_none()
+    );
+
+    // Deleting the daemon holding the slot drops its session and frees the slot
+    services.daemon_service.delete(&first.id).await.unwrap();
+    assert!(
+        discovery_service
+            .get_session(&running.session_id)
+            .await
+            .is_none()
+    );
+    let handed_out = discovery_service
+        .next_session_for_daemon(&second.id)
+        .await
+        .unwrap();
+    assert_eq!(handed_out.session_id, waiting.session_id);
+}
@@ -0,0 +1,110 @@
+use crate::server::{
+    auth::middleware::RequireAdmin,
+    config::AppState,
+    discovery_webhooks::r#impl::base::DiscoveryWebhook,
+    shared::{
+        handlers::traits::{CrudHandlers, admin_delete_handler, get_all_handler},
+        services::traits::CrudService,
+        types::api::{ApiError, ApiResponse, ApiResult},
+    },
+};
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{delete, get, post, put},
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_all_handler::<DiscoveryWebhook>))
+        .route("/", post(create_handler))
+        .route("/{id}", put(update_handler))
+        .route("/{id}", delete(admin_delete_handler::<DiscoveryWebhook>))
+}
+
+/// Validate a webhook and check it's for a daemon on its own network, which the user can access,
+/// and that its signing secret can sign payloads
+async fn check_webhook(
+    state: &AppState,
+    network_ids: &[Uuid],
+    webhook: &DiscoveryWebhook,
+) -> ApiResult<()> {
+    webhook.validate().map_err(|e| {
+        ApiError::bad_request(&format!("Discovery webhook validation failed: {}", e))
+    })?;
+
+    if !network_ids.contains(&webhook.base.network_id) {
+        return Err(ApiError::forbidden("You don't have access to this network"));
+    }
+
+    state
+        .services
+        .daemon_service
+        .get_by_id(&webhook.base.daemon_id)
+        .await?
+        .filter(|d| d.base.network_id == webhook.base.network_id)
+        .ok_or_else(|| {
+            ApiError::bad_request(&format!(
+                "Daemon {} is not on the webhook's network",
+                webhook.base.daemon_id
+            ))
+        })?;
+
+    DiscoveryWebhook::get_service(state)
+        .signing_key(webhook)
+        .await
+        .map_err(|e| ApiError::bad_request(&e.to_string()))?;
+
+    Ok(())
+}
+
+async fn create_handler(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Json(webhook): Json<DiscoveryWebhook>,
+) -> ApiResult<Json<ApiResponse<DiscoveryWebhook>>> {
+    check_webhook(&state, &user.network_ids, &webhook).await?;
+
+    let created = DiscoveryWebhook::get_service(&state)
+        .create(webhook)
+        .await?;
+
+    tracing::info!(
+        discovery_webhook_id = %created.id,
+        daemon_id = %created.base.daemon_id,
+        user_id = %user.user_id,
+        "Discovery webhook created via API"
+    );
+
+    Ok(Json(ApiResponse::success(created)))
+}
+
+async fn update_handler(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Path(id): Path<Uuid>,
+    Json(mut webhook): Json<DiscoveryWebhook>,
+) -> ApiResult<Json<ApiResponse<DiscoveryWebhook>>> {
+    let service = DiscoveryWebhook::get_service(&state);
+    let existing = service
+        .get_by_id(&id)
+        .await?
+        .filter(|existing| user.network_ids.contains(&existing.base.network_id))
+        .ok_or_else(|| ApiError::not_found(format!("Discovery webhook '{}' not found", id)))?;
+
+    check_webhook(&state, &user.network_ids, &webhook).await?;
+
+    webhook.id = id;
+    webhook.created_at = existing.created_at;
+    let updated = service.update(&mut webhook).await?;
+
+    tracing::info!(
+        discovery_webhook_id = %updated.id,
+        user_id = %user.user_id,
+        "Discovery webhook updated via API"
+    );
+
+    Ok(Json(ApiResponse::success(updated)))
+}
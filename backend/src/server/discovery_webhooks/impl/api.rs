@@ -0,0 +1,31 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    daemon::discovery::types::base::DiscoveryPhase,
+    server::{discovery::r#impl::types::DiscoveryType, hosts::r#impl::base::Host},
+};
+
+/// Body of a discovery webhook, signed like daemon results are, with the webhook's secret as
+/// the key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveryWebhookPayload {
+    pub webhook_id: Uuid,
+    pub session_id: Uuid,
+    pub daemon_id: Uuid,
+    pub network_id: Uuid,
+    pub discovery_type: DiscoveryType,
+    pub phase: DiscoveryPhase,
+    pub processed: usize,
+    pub total_to_process: usize,
+    pub error: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Historical record of the session
+    pub discovery_id: Uuid,
+    pub results_url: String,
+    /// Only sent by webhooks configured for full payloads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hosts: Option<Vec<Host>>,
+}
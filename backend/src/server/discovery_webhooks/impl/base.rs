@@ -0,0 +1,75 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use strum::{Display as StrumDisplay, EnumString};
+use uuid::Uuid;
+
+/// A URL the server POSTs to whenever one of a daemon's discovery sessions finishes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveryWebhookBase {
+    pub network_id: Uuid,
+    pub daemon_id: Uuid,
+    pub url: String,
+    /// Name of a stored token secret payloads are signed with
+    pub signing_secret: String,
+    #[serde(default)]
+    pub payload: WebhookPayloadMode,
+    /// Also fire for cancelled sessions, not just completed and failed ones
+    #[serde(default)]
+    pub include_cancelled: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// How much of a finished session a webhook carries
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, StrumDisplay, EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum WebhookPayloadMode {
+    /// The session summary and a link to its historical record
+    #[default]
+    Summary,
+    /// The summary plus the hosts the daemon reported during the session
+    Full,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveryWebhook {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub base: DiscoveryWebhookBase,
+}
+
+impl Display for DiscoveryWebhook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Discovery webhook for daemon {}: {}",
+            self.base.daemon_id, self.id
+        )
+    }
+}
+
+impl DiscoveryWebhookBase {
+    pub fn validate(&self) -> Result<(), String> {
+        let parsed =
+            reqwest::Url::parse(&self.url).map_err(|e| format!("Invalid webhook URL: {}", e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err("Webhook URLs must be http or https".to_string());
+        }
+        if self.signing_secret.trim().is_empty() {
+            return Err("Webhooks need a signing secret".to_string());
+        }
+
+        Ok(())
+    }
+}
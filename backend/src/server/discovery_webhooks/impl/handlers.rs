@@ -0,0 +1,19 @@
+use crate::server::discovery_webhooks::r#impl::base::DiscoveryWebhook;
+use crate::server::discovery_webhooks::service::DiscoveryWebhookService;
+use crate::server::shared::handlers::traits::{CrudHandlers, EntityScope};
+
+impl CrudHandlers for DiscoveryWebhook {
+    type Service = DiscoveryWebhookService;
+
+    fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
+        &state.services.discovery_webhook_service
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.base.validate()
+    }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.base.network_id)
+    }
+}
@@ -0,0 +1,115 @@
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+use uuid::Uuid;
+
+use crate::server::{
+    discovery_webhooks::r#impl::base::{DiscoveryWebhook, DiscoveryWebhookBase},
+    shared::storage::traits::{SqlValue, StorableEntity},
+};
+
+impl StorableEntity for DiscoveryWebhook {
+    type BaseData = DiscoveryWebhookBase;
+
+    fn table_name() -> &'static str {
+        "discovery_webhooks"
+    }
+
+    fn get_base(&self) -> Self::BaseData {
+        self.base.clone()
+    }
+
+    fn new(base: Self::BaseData) -> Self {
+        let now = chrono::Utc::now();
+
+        Self {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: now,
+            base,
+        }
+    }
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn set_updated_at(&mut self, time: DateTime<Utc>) {
+        self.updated_at = time;
+    }
+
+    fn to_params(&self) -> Result<(Vec<&'static str>, Vec<SqlValue>), anyhow::Error> {
+        let Self {
+            id,
+            created_at,
+            updated_at,
+            base:
+                Self::BaseData {
+                    network_id,
+                    daemon_id,
+                    url,
+                    signing_secret,
+                    payload,
+                    include_cancelled,
+                    enabled,
+                },
+        } = self.clone();
+
+        Ok((
+            vec![
+                "id",
+                "created_at",
+                "updated_at",
+                "network_id",
+                "daemon_id",
+                "url",
+                "signing_secret",
+                "payload",
+                "include_cancelled",
+                "enabled",
+            ],
+            vec![
+                SqlValue::Uuid(id),
+                SqlValue::Timestamp(created_at),
+                SqlValue::Timestamp(updated_at),
+                SqlValue::Uuid(network_id),
+                SqlValue::Uuid(daemon_id),
+                SqlValue::String(url),
+                SqlValue::String(signing_secret),
+                SqlValue::String(payload.to_string()),
+                SqlValue::Bool(include_cancelled),
+                SqlValue::Bool(enabled),
+            ],
+        ))
+    }
+
+    fn from_row(row: &PgRow) -> Result<Self, anyhow::Error> {
+        let payload = row
+            .get::<String, _>("payload")
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Failed to parse webhook payload mode: {}", e))?;
+
+        Ok(DiscoveryWebhook {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            base: DiscoveryWebhookBase {
+                network_id: row.get("network_id"),
+                daemon_id: row.get("daemon_id"),
+                url: row.get("url"),
+                signing_secret: row.get("signing_secret"),
+                payload,
+                include_cancelled: row.get("include_cancelled"),
+                enabled: row.get("enabled"),
+            },
+        })
+    }
+}
@@ -0,0 +1,226 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use axum::http::header;
+use chrono::Utc;
+use std::{
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+use uuid::Uuid;
+
+use crate::{
+    daemon::discovery::types::base::DiscoveryPhase,
+    server::{
+        daemons::r#impl::{api::DiscoveryUpdatePayload, signing::signature_headers},
+        discovery_webhooks::r#impl::{
+            api::DiscoveryWebhookPayload,
+            base::{DiscoveryWebhook, WebhookPayloadMode},
+        },
+        hosts::r#impl::base::Host,
+        notifications::{r#impl::outbox::DeliveryTarget, service::NotificationService},
+        secrets::service::SecretService,
+        shared::{
+            base_url::normalize_base_url,
+            outbound::OutboundClient,
+            services::traits::CrudService,
+            storage::{filter::EntityFilter, generic::GenericPostgresStorage, traits::Storage},
+        },
+    },
+};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Tells registered URLs about a daemon's discovery sessions as they finish. Deliveries go
+/// through the notification outbox, which retries failed ones.
+pub struct DiscoveryWebhookService {
+    storage: Arc<GenericPostgresStorage<DiscoveryWebhook>>,
+    host_storage: Arc<GenericPostgresStorage<Host>>,
+    secret_service: Arc<SecretService>,
+    notification_service: OnceLock<Arc<NotificationService>>,
+    public_url: String,
+    client: OutboundClient,
+}
+
+#[async_trait]
+impl CrudService<DiscoveryWebhook> for DiscoveryWebhookService {
+    fn storage(&self) -> &Arc<GenericPostgresStorage<DiscoveryWebhook>> {
+        &self.storage
+    }
+}
+
+impl DiscoveryWebhookService {
+    pub fn new(
+        storage: Arc<GenericPostgresStorage<DiscoveryWebhook>>,
+        host_storage: Arc<GenericPostgresStorage<Host>>,
+        secret_service: Arc<SecretService>,
+        public_url: String,
+        allow_internal_targets: bool,
+    ) -> Self {
+        Self {
+            storage,
+            host_storage,
+            secret_service,
+            notification_service: OnceLock::new(),
+            public_url: normalize_base_url(&public_url),
+            client: OutboundClient::new(WEBHOOK_TIMEOUT, allow_internal_targets),
+        }
+    }
+
+    pub fn set_notification_service(
+        &self,
+        notification_service: Arc<NotificationService>,
+    ) -> Result<(), Arc<NotificationService>> {
+        self.notification_service.set(notification_service)
+    }
+
+    /// The key a webhook's payloads are signed with, from the token secret it names
+    pub async fn signing_key(&self, webhook: &DiscoveryWebhook) -> Result<String> {
+        self.secret_service
+            .resolve(&webhook.base.network_id, &webhook.base.signing_secret)
+            .await?
+            .token()
+            .map(str::to_string)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Secret '{}' is not a token, so can't sign webhooks",
+                    webhook.base.signing_secret
+                )
+            })
+    }
+
+    /// Hosts `daemon_id` reported while `session` ran
+    async fn session_hosts(
+        &self,
+        session: &DiscoveryUpdatePayload,
+        daemon_id: &Uuid,
+    ) -> Result<Vec<Host>> {
+        let (Some(started_at), Some(finished_at)) = (session.started_at, session.finished_at)
+        else {
+            return Ok(Vec::new());
+        };
+
+        self.host_storage
+            .get_all(
+                EntityFilter::unfiltered()
+                    .network_ids(&[session.network_id])
+                    .discovered_by(daemon_id, started_at, finished_at),
+            )
+            .await
+    }
+
+    /// Send `session`, which just finished and was recorded as `discovery_id`, to the webhooks
+    /// registered for its daemon. Cancelled sessions only go to webhooks that opted in. Returns
+    /// how many webhooks deliveries were queued for.
+    pub async fn dispatch(
+        &self,
+        session: &DiscoveryUpdatePayload,
+        discovery_id: Uuid,
+    ) -> Result<usize> {
+        // Webhooks are registered per daemon, so imports have none
+        let Some(daemon_id) = session.daemon_id else {
+            return Ok(0);
+        };
+        let webhooks: Vec<DiscoveryWebhook> = self
+            .storage
+            .get_all(EntityFilter::unfiltered().daemon_id(&daemon_id))
+            .await?
+            .into_iter()
+            .filter(|w| w.base.enabled && w.base.network_id == session.network_id)
+            .filter(|w| {
+                !matches!(
+                    session.phase,
+                    DiscoveryPhase::Cancelled | DiscoveryPhase::CancelledWithPartialResults
+                ) || w.base.include_cancelled
+            })
+            .collect();
+
+        if webhooks.is_empty() {
+            return Ok(0);
+        }
+        let notification_service = self
+            .notification_service
+            .get()
+            .ok_or_else(|| anyhow!("Notifications aren't set up, so webhooks can't be queued"))?;
+
+        let hosts = if webhooks
+            .iter()
+            .any(|w| w.base.payload == WebhookPayloadMode::Full)
+        {
+            Some(self.session_hosts(session, &daemon_id).await?)
+        } else {
+            None
+        };
+
+        for webhook in &webhooks {
+            let payload = DiscoveryWebhookPayload {
+                webhook_id: webhook.id,
+                session_id: session.session_id,
+                daemon_id,
+                network_id: session.network_id,
+                discovery_type: session.discovery_type.clone(),
+                phase: session.phase,
+                processed: session.processed,
+                total_to_process: session.total_to_process,
+                error: session.error.clone(),
+                started_at: session.started_at,
+                finished_at: session.finished_at,
+                discovery_id,
+                results_url: format!("{}/api/discovery/{}", self.public_url, discovery_id),
+                hosts: match webhook.base.payload {
+                    WebhookPayloadMode::Full => hosts.clone(),
+                    WebhookPayloadMode::Summary => None,
+                },
+            };
+            notification_service
+                .enqueue(
+                    DeliveryTarget::DiscoveryWebhook {
+                        webhook_id: webhook.id,
+                        payload: serde_json::to_value(&payload)?,
+                    },
+                    Utc::now(),
+                )
+                .await?;
+        }
+
+        Ok(webhooks.len())
+    }
+
+    /// POST `body` to the webhook once, signed with its key
+    pub async fn deliver(&self, webhook: &DiscoveryWebhook, body: Vec<u8>) -> Result<()> {
+        let key = self.signing_key(webhook).await?;
+
+        let mut request = self
+            .client
+            .post(&webhook.base.url)?
+            .header(header::CONTENT_TYPE, "application/json");
+        for (name, value) in signature_headers(&key, &body) {
+            request = request.header(name, value);
+        }
+
+        request.body(body).send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    /// Send an outbox delivery to `webhook_id` if it still exists and is enabled, returning
+    /// whether it was sent
+    pub async fn deliver_queued(
+        &self,
+        webhook_id: &Uuid,
+        payload: &serde_json::Value,
+    ) -> Result<bool> {
+        match self.storage.get_by_id(webhook_id).await? {
+            Some(webhook) if webhook.base.enabled => {
+                self.deliver(&webhook, serde_json::to_vec(payload)?).await?;
+                tracing::debug!(webhook_id = %webhook.id, "Delivered discovery webhook");
+                Ok(true)
+            }
+            _ => {
+                tracing::info!(
+                    webhook_id = %webhook_id,
+                    "Dropping delivery to a disabled or deleted discovery webhook"
+                );
+                Ok(false)
+            }
+        }
+    }
+}
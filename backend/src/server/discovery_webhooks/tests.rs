@@ -0,0 +1,188 @@
+use axum::{Router, body::Bytes, http::HeaderMap, routing::post};
+use serial_test::serial;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::{
+    daemon::discovery::types::base::DiscoveryPhase,
+    server::{
+        config::ServerConfig,
+        daemons::r#impl::{base::DaemonMode, signing::ResultSignatureVerifier},
+        discovery::r#impl::{
+            base::{Discovery, DiscoveryBase},
+            history::SessionActor,
+            target_policy::EmptyAllowlist,
+            types::{DiscoveryType, HostNamingFallback, RunType, ScanRate},
+        },
+        discovery_webhooks::r#impl::{
+            api::DiscoveryWebhookPayload,
+            base::{DiscoveryWebhook, DiscoveryWebhookBase, WebhookPayloadMode},
+        },
+        secrets::r#impl::{api::CreateSecretRequest, base::SecretValue},
+        shared::{
+            services::{factory::ServiceFactory, traits::CrudService},
+            storage::traits::StorableEntity,
+        },
+    },
+    tests::*,
+};
+
+const SIGNING_TOKEN: &str = "webhook-signing-token";
+
+/// A local endpoint that passes on every request it receives
+async fn receiver() -> (String, mpsc::UnboundedReceiver<(HeaderMap, Bytes)>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let app = Router::new().route(
+        "/hook",
+        post(move |headers: HeaderMap, body: Bytes| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send((headers, body));
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    (url, rx)
+}
+
+#[tokio::test]
+#[serial]
+async fn test_completed_discovery_delivers_signed_payload() {
+    let (storage, _container) = test_storage().await;
+    let services = ServiceFactory::new(
+        &storage,
+        Some(ServerConfig {
+            secrets_key: Some("test-secrets-key".to_string()),
+            // The receiver listens on loopback
+            allow_internal_webhook_targets: true,
+            scan_empty_allowlist: Some(EmptyAllowlist::AllowAll),
+            ..Default::default()
+        }),
+    )
+    .await
+    .unwrap();
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let daemon_host = services
+        .host_service
+        .create(host(&network.id))
+        .await
+        .unwrap();
+    let mut pull_daemon = daemon(&network.id, &daemon_host.id);
+    pull_daemon.base.mode = DaemonMode::Pull;
+    let daemon = services.daemon_service.create(pull_daemon).await.unwrap();
+
+    services
+        .secret_service
+        .create_secret(CreateSecretRequest {
+            name: "webhook-key".to_string(),
+            network_id: network.id,
+            value: SecretValue::Token {
+                token: SIGNING_TOKEN.to_string(),
+            },
+        })
+        .await
+        .unwrap();
+    let (url, mut received) = receiver().await;
+    let webhook = services
+        .discovery_webhook_service
+        .create(DiscoveryWebhook::new(DiscoveryWebhookBase {
+            network_id: network.id,
+            daemon_id: daemon.id,
+            url,
+            signing_secret: "webhook-key".to_string(),
+            payload: WebhookPayloadMode::Summary,
+            include_cancelled: false,
+            enabled: true,
+        }))
+        .await
+        .unwrap();
+
+    let start = || async {
+        services
+            .discovery_service
+            .start_session_by(
+                Discovery::new(DiscoveryBase {
+                    discovery_type: DiscoveryType::Network {
+                        subnet_ids: None,
+                        host_naming_fallback: HostNamingFallback::default(),
+                        scan_rate: ScanRate::default(),
+                        snmp_credential: None,
+                        targets: Vec::new(),
+                    },
+                    run_type: RunType::AdHoc { last_run: None },
+                    name: "Scan".to_string(),
+                    daemon_id: Some(daemon.id),
+                    network_id: network.id,
+                }),
+                SessionActor::Server,
+            )
+            .await
+            .unwrap()
+    };
+    let finish = |mut update: crate::server::daemons::r#impl::api::DiscoveryUpdatePayload,
+                  phase| {
+        update.phase = phase;
+        update.started_at = Some(chrono::Utc::now());
+        update.finished_at = Some(chrono::Utc::now());
+        update
+    };
+
+    // The webhook didn't opt in to cancelled sessions
+    let cancelled = start().await;
+    services
+        .discovery_service
+        .update_session(finish(cancelled, DiscoveryPhase::Cancelled))
+        .await
+        .unwrap();
+
+    let completed = start().await;
+    let mut update = finish(completed.clone(), DiscoveryPhase::Complete);
+    update.processed = 12;
+    update.total_to_process = 12;
+    services
+        .discovery_service
+        .update_session(update)
+        .await
+        .unwrap();
+
+    let (headers, body) = tokio::time::timeout(Duration::from_secs(10), received.recv())
+        .await
+        .expect("webhook wasn't delivered")
+        .unwrap();
+
+    ResultSignatureVerifier::new(true, Duration::from_secs(300))
+        .verify(SIGNING_TOKEN, &headers, &body)
+        .expect("payload isn't signed with the webhook's key");
+
+    let payload: DiscoveryWebhookPayload = serde_json::from_slice(&body).unwrap();
+    assert_eq!(payload.webhook_id, webhook.id);
+    assert_eq!(payload.session_id, completed.session_id);
+    assert_eq!(payload.phase, DiscoveryPhase::Complete);
+    assert_eq!(payload.processed, 12);
+    assert!(
+        payload
+            .results_url
+            .ends_with(&format!("/api/discovery/{}", payload.discovery_id))
+    );
+    assert!(payload.hosts.is_none());
+
+    assert!(
+        tokio::time::timeout(Duration::from_millis(500), received.recv())
+            .await
+            .is_err(),
+        "cancelled session was delivered"
+    );
+}
@@ -36,6 +36,15 @@ impl EmailService {
         Ok(EmailService { mailer, from })
     }
 
+    /// Whether the SMTP relay accepts a connection, without sending anything
+    pub async fn test_connection(&self) -> Result<()> {
+        match self.mailer.test_connection().await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(anyhow!("SMTP relay refused the connection")),
+            Err(e) => Err(anyhow!("Failed to connect to SMTP relay: {}", e)),
+        }
+    }
+
     /// Send an HTML email
     pub async fn send_email(&self, to: EmailAddress, subject: &str, html_body: &str) -> Result<()> {
         let to_mbox = Mailbox::new(
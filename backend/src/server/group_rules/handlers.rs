@@ -0,0 +1,145 @@
+use crate::server::{
+    auth::middleware::{AuthenticatedUser, RequireAdmin, RequireMember},
+    config::AppState,
+    group_rules::r#impl::{
+        api::{GroupRulePreview, PreviewGroupRuleRequest, ReevaluateQuery},
+        base::{GroupRule, GroupRuleBase},
+    },
+    shared::{
+        handlers::traits::{CrudHandlers, admin_delete_handler, get_all_handler},
+        services::traits::CrudService,
+        storage::traits::StorableEntity,
+        types::api::{ApiError, ApiResponse, ApiResult},
+    },
+};
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    routing::{delete, get, post, put},
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_all_handler::<GroupRule>))
+        .route("/", post(create_handler))
+        .route("/preview", post(preview_handler))
+        .route("/{id}", put(update_handler))
+        .route("/{id}", delete(admin_delete_handler::<GroupRule>))
+}
+
+/// Reject rules outside the user's networks or pointing at a group in another network
+async fn check_rule(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    rule: &GroupRuleBase,
+) -> ApiResult<()> {
+    rule.matcher
+        .validate()
+        .map_err(|e| ApiError::bad_request(&format!("Group rule validation failed: {}", e)))?;
+
+    if !user.network_ids.contains(&rule.network_id) {
+        return Err(ApiError::forbidden("You don't have access to this network"));
+    }
+
+    match state
+        .services
+        .group_service
+        .get_by_id(&rule.group_id)
+        .await?
+    {
+        Some(group) if group.base.network_id == rule.network_id => Ok(()),
+        _ => Err(ApiError::bad_request(&format!(
+            "Group {} does not exist on this network",
+            rule.group_id
+        ))),
+    }
+}
+
+/// Re-run a network's rules against its existing hosts when asked to
+async fn reevaluate(state: &AppState, query: &ReevaluateQuery, network_id: &Uuid) -> ApiResult<()> {
+    if query.reevaluate {
+        let updated = state
+            .services
+            .host_service
+            .reevaluate_groups(network_id)
+            .await?;
+
+        tracing::info!(
+            network_id = %network_id,
+            updated_hosts = %updated,
+            "Re-evaluated group rules"
+        );
+    }
+
+    Ok(())
+}
+
+async fn create_handler(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Query(query): Query<ReevaluateQuery>,
+    Json(rule): Json<GroupRule>,
+) -> ApiResult<Json<ApiResponse<GroupRule>>> {
+    check_rule(&state, &user, &rule.base).await?;
+
+    let created = GroupRule::get_service(&state).create(rule).await?;
+    reevaluate(&state, &query, &created.base.network_id).await?;
+
+    tracing::info!(
+        group_rule_id = %created.id,
+        user_id = %user.user_id,
+        "Group rule created via API"
+    );
+
+    Ok(Json(ApiResponse::success(created)))
+}
+
+async fn update_handler(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ReevaluateQuery>,
+    Json(mut rule): Json<GroupRule>,
+) -> ApiResult<Json<ApiResponse<GroupRule>>> {
+    let service = GroupRule::get_service(&state);
+
+    let existing = service
+        .get_by_id(&id)
+        .await?
+        .filter(|existing| user.network_ids.contains(&existing.base.network_id))
+        .ok_or_else(|| ApiError::not_found(format!("Group rule '{}' not found", id)))?;
+    check_rule(&state, &user, &rule.base).await?;
+
+    rule.id = id;
+    rule.created_at = existing.created_at;
+    let updated = service.update(&mut rule).await?;
+    reevaluate(&state, &query, &updated.base.network_id).await?;
+
+    tracing::info!(
+        group_rule_id = %updated.id,
+        user_id = %user.user_id,
+        "Group rule updated via API"
+    );
+
+    Ok(Json(ApiResponse::success(updated)))
+}
+
+/// Dry run: which hosts a rule would assign, and which would be left ungrouped, if it were saved
+async fn preview_handler(
+    State(state): State<Arc<AppState>>,
+    RequireMember(user): RequireMember,
+    Json(request): Json<PreviewGroupRuleRequest>,
+) -> ApiResult<Json<ApiResponse<GroupRulePreview>>> {
+    check_rule(&state, &user, &request.base).await?;
+
+    let mut candidate = GroupRule::new(request.base);
+    if let Some(id) = request.id {
+        candidate.id = id;
+    }
+
+    let preview = GroupRule::get_service(&state).preview(&candidate).await?;
+
+    Ok(Json(ApiResponse::success(preview)))
+}
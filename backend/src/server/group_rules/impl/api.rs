@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::server::group_rules::r#impl::base::GroupRuleBase;
+
+/// Whether saving a rule should also re-run the network's rules against its existing hosts
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ReevaluateQuery {
+    #[serde(default)]
+    pub reevaluate: bool,
+}
+
+/// Hosts a rule would affect if it were saved, given the network's other rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupRulePreview {
+    /// Hosts the rule would assign to its group
+    pub matched: Vec<Uuid>,
+    /// Hosts no rule would assign, including this one
+    pub ungrouped: Vec<Uuid>,
+}
+
+/// Rule to preview. Pass the id of a saved rule to preview changes to it in place of the saved
+/// version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviewGroupRuleRequest {
+    #[serde(default)]
+    pub id: Option<Uuid>,
+    #[serde(flatten)]
+    pub base: GroupRuleBase,
+}
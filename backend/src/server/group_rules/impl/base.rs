@@ -0,0 +1,127 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use cidr::IpCidr;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::server::hosts::r#impl::base::Host;
+
+/// Admin-defined rule assigning hosts that match it to a node group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupRuleBase {
+    pub name: String,
+    pub network_id: Uuid,
+    /// Group matching hosts are assigned to
+    pub group_id: Uuid,
+    /// Rules are evaluated lowest priority first
+    #[serde(default)]
+    pub priority: i32,
+    pub matcher: GroupRuleMatch,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupRule {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub base: GroupRuleBase,
+}
+
+impl Display for GroupRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Group rule {}: {}", self.base.name, self.id)
+    }
+}
+
+/// What a host has to look like to match a rule
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GroupRuleMatch {
+    /// Any of the host's interfaces has an address in `cidr`
+    Cidr { cidr: IpCidr },
+    /// The host's hostname, or its name when it has none, matches a case-insensitive glob where
+    /// `*` is any run of characters and `?` any single one
+    Hostname { pattern: String },
+    /// The host has every one of `ports` open
+    OpenPorts { ports: Vec<u16> },
+    /// The host is tagged `tag`, ignoring case
+    Tag { tag: String },
+}
+
+impl GroupRuleMatch {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            GroupRuleMatch::Cidr { .. } => Ok(()),
+            GroupRuleMatch::Hostname { pattern } if pattern.trim().is_empty() => {
+                Err("Hostname pattern can't be empty".to_string())
+            }
+            GroupRuleMatch::Hostname { .. } => Ok(()),
+            GroupRuleMatch::OpenPorts { ports } if ports.is_empty() => {
+                Err("Open port rules need at least one port".to_string())
+            }
+            GroupRuleMatch::OpenPorts { .. } => Ok(()),
+            GroupRuleMatch::Tag { tag } if tag.trim().is_empty() => {
+                Err("Tag can't be empty".to_string())
+            }
+            GroupRuleMatch::Tag { .. } => Ok(()),
+        }
+    }
+
+    pub fn matches(&self, host: &Host) -> bool {
+        match self {
+            GroupRuleMatch::Cidr { cidr } => host
+                .base
+                .interfaces
+                .iter()
+                .any(|i| cidr.contains(&i.base.ip_address)),
+            GroupRuleMatch::Hostname { pattern } => {
+                let name = host.base.hostname.as_deref().unwrap_or(&host.base.name);
+                glob_regex(pattern).is_some_and(|re| re.is_match(name))
+            }
+            GroupRuleMatch::OpenPorts { ports } => ports
+                .iter()
+                .all(|port| host.base.ports.iter().any(|p| p.base.number() == *port)),
+            GroupRuleMatch::Tag { tag } => host
+                .base
+                .tags
+                .iter()
+                .any(|t| t.trim().eq_ignore_ascii_case(tag.trim())),
+        }
+    }
+}
+
+fn glob_regex(pattern: &str) -> Option<Regex> {
+    let pattern = regex::escape(pattern.trim())
+        .replace(r"\*", ".*")
+        .replace(r"\?", ".");
+
+    Regex::new(&format!("(?i)^{}$", pattern)).ok()
+}
+
+/// How many rules may assign a host
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupRuleMode {
+    /// The host joins the group of the first rule it matches
+    #[default]
+    FirstMatch,
+    /// The host joins the group of every rule it matches
+    AllMatches,
+}
+
+/// Rules `host` is assigned by, out of `rules` already in evaluation order
+pub fn matching_rules<'a>(
+    rules: &'a [GroupRule],
+    host: &Host,
+    mode: GroupRuleMode,
+) -> Vec<&'a GroupRule> {
+    let mut matching = rules.iter().filter(|rule| rule.base.matcher.matches(host));
+
+    match mode {
+        GroupRuleMode::FirstMatch => matching.next().into_iter().collect(),
+        GroupRuleMode::AllMatches => matching.collect(),
+    }
+}
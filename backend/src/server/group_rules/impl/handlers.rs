@@ -0,0 +1,19 @@
+use crate::server::group_rules::r#impl::base::GroupRule;
+use crate::server::group_rules::service::GroupRuleService;
+use crate::server::shared::handlers::traits::{CrudHandlers, EntityScope};
+
+impl CrudHandlers for GroupRule {
+    type Service = GroupRuleService;
+
+    fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
+        &state.services.group_rule_service
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.base.matcher.validate()
+    }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.base.network_id)
+    }
+}
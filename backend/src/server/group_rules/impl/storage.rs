@@ -0,0 +1,106 @@
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+use uuid::Uuid;
+
+use crate::server::{
+    group_rules::r#impl::base::{GroupRule, GroupRuleBase, GroupRuleMatch},
+    shared::storage::traits::{SqlValue, StorableEntity},
+};
+
+impl StorableEntity for GroupRule {
+    type BaseData = GroupRuleBase;
+
+    fn table_name() -> &'static str {
+        "group_rules"
+    }
+
+    fn get_base(&self) -> Self::BaseData {
+        self.base.clone()
+    }
+
+    fn new(base: Self::BaseData) -> Self {
+        let now = chrono::Utc::now();
+
+        Self {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: now,
+            base,
+        }
+    }
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn set_updated_at(&mut self, time: DateTime<Utc>) {
+        self.updated_at = time;
+    }
+
+    fn to_params(&self) -> Result<(Vec<&'static str>, Vec<SqlValue>), anyhow::Error> {
+        let Self {
+            id,
+            created_at,
+            updated_at,
+            base:
+                Self::BaseData {
+                    name,
+                    network_id,
+                    group_id,
+                    priority,
+                    matcher,
+                },
+        } = self.clone();
+
+        Ok((
+            vec![
+                "id",
+                "created_at",
+                "updated_at",
+                "name",
+                "network_id",
+                "group_id",
+                "priority",
+                "matcher",
+            ],
+            vec![
+                SqlValue::Uuid(id),
+                SqlValue::Timestamp(created_at),
+                SqlValue::Timestamp(updated_at),
+                SqlValue::String(name),
+                SqlValue::Uuid(network_id),
+                SqlValue::Uuid(group_id),
+                SqlValue::I32(priority),
+                SqlValue::Json(serde_json::to_value(matcher)?),
+            ],
+        ))
+    }
+
+    fn from_row(row: &PgRow) -> Result<Self, anyhow::Error> {
+        let matcher: GroupRuleMatch =
+            serde_json::from_value(row.get::<serde_json::Value, _>("matcher"))
+                .map_err(|e| anyhow::anyhow!("Failed to deserialize matcher: {}", e))?;
+
+        Ok(GroupRule {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            base: GroupRuleBase {
+                name: row.get("name"),
+                network_id: row.get("network_id"),
+                group_id: row.get("group_id"),
+                priority: row.get("priority"),
+                matcher,
+            },
+        })
+    }
+}
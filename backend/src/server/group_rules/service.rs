@@ -0,0 +1,108 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::server::{
+    group_rules::r#impl::{
+        api::GroupRulePreview,
+        base::{GroupRule, GroupRuleMode, matching_rules},
+    },
+    hosts::r#impl::base::Host,
+    shared::{
+        services::traits::CrudService,
+        storage::{filter::EntityFilter, generic::GenericPostgresStorage, traits::Storage},
+    },
+};
+
+pub struct GroupRuleService {
+    storage: Arc<GenericPostgresStorage<GroupRule>>,
+    host_storage: Arc<GenericPostgresStorage<Host>>,
+    mode: GroupRuleMode,
+}
+
+#[async_trait]
+impl CrudService<GroupRule> for GroupRuleService {
+    fn storage(&self) -> &Arc<GenericPostgresStorage<GroupRule>> {
+        &self.storage
+    }
+}
+
+impl GroupRuleService {
+    pub fn new(
+        storage: Arc<GenericPostgresStorage<GroupRule>>,
+        host_storage: Arc<GenericPostgresStorage<Host>>,
+        mode: GroupRuleMode,
+    ) -> Self {
+        Self {
+            storage,
+            host_storage,
+            mode,
+        }
+    }
+
+    /// A network's rules in evaluation order: by priority, then oldest first
+    pub async fn rules_for_network(&self, network_id: &Uuid) -> Result<Vec<GroupRule>> {
+        let mut rules = self
+            .storage
+            .get_all(EntityFilter::unfiltered().network_ids(&[*network_id]))
+            .await?;
+        rules.sort_by_key(|rule| rule.base.priority);
+
+        Ok(rules)
+    }
+
+    /// Add `host` to the groups of the rules it matches. Existing memberships are kept, so a host
+    /// is never pulled out of a group it was put in by hand. Returns whether anything changed.
+    pub async fn assign_groups(&self, host: &mut Host) -> Result<bool> {
+        let rules = self.rules_for_network(&host.base.network_id).await?;
+        let mut changed = false;
+
+        for rule in matching_rules(&rules, host, self.mode) {
+            if !host.base.group_ids.contains(&rule.base.group_id) {
+                tracing::debug!(
+                    host_id = %host.id,
+                    group_id = %rule.base.group_id,
+                    rule_id = %rule.id,
+                    "Host assigned to group by rule"
+                );
+                host.base.group_ids.push(rule.base.group_id);
+                changed = true;
+            }
+        }
+
+        Ok(changed)
+    }
+
+    /// Which of its network's hosts `candidate` would assign if saved, without saving it
+    pub async fn preview(&self, candidate: &GroupRule) -> Result<GroupRulePreview> {
+        let mut rules: Vec<GroupRule> = self
+            .rules_for_network(&candidate.base.network_id)
+            .await?
+            .into_iter()
+            .filter(|rule| rule.id != candidate.id)
+            .collect();
+        rules.push(candidate.clone());
+        rules.sort_by_key(|rule| rule.base.priority);
+
+        let hosts = self
+            .host_storage
+            .get_all(EntityFilter::unfiltered().network_ids(&[candidate.base.network_id]))
+            .await?;
+
+        let mut preview = GroupRulePreview {
+            matched: Vec::new(),
+            ungrouped: Vec::new(),
+        };
+        for host in hosts {
+            let matching = matching_rules(&rules, &host, self.mode);
+            if matching.is_empty() {
+                preview.ungrouped.push(host.id);
+            } else if matching.iter().any(|rule| rule.id == candidate.id) {
+                preview.matched.push(host.id);
+            }
+        }
+
+        Ok(preview)
+    }
+}
@@ -0,0 +1,241 @@
+use mac_address::MacAddress;
+use serial_test::serial;
+use std::net::{IpAddr, Ipv4Addr};
+
+use crate::{
+    server::{
+        group_rules::r#impl::base::{
+            GroupRule, GroupRuleBase, GroupRuleMatch, GroupRuleMode, matching_rules,
+        },
+        hosts::r#impl::{
+            base::Host,
+            ports::{Port, PortBase},
+        },
+        shared::{services::traits::CrudService, storage::traits::StorableEntity},
+    },
+    tests::*,
+};
+
+fn rule(network_id: uuid::Uuid, group_id: uuid::Uuid, matcher: GroupRuleMatch) -> GroupRule {
+    GroupRule::new(GroupRuleBase {
+        name: "Rule".to_string(),
+        network_id,
+        group_id,
+        priority: 0,
+        matcher,
+    })
+}
+
+/// Host with a single interface at `ip`, distinct from every other host built here
+fn host_at(network_id: &uuid::Uuid, ip: [u8; 4]) -> Host {
+    let mut host = host(network_id);
+    host.base.interfaces[0].base.ip_address = IpAddr::V4(Ipv4Addr::from(ip));
+    host.base.interfaces[0].base.mac_address =
+        Some(MacAddress::new([2, 0, ip[0], ip[1], ip[2], ip[3]]));
+    host.base.hostname = Some(format!("host-{}.lan", ip[3]));
+    host
+}
+
+#[tokio::test]
+#[serial]
+async fn test_hosts_assigned_to_groups_by_rule() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let servers = services
+        .group_service
+        .create(group(&network.id))
+        .await
+        .unwrap();
+
+    // Hosts from before the rule existed are left alone until re-evaluated
+    let existing = services
+        .host_service
+        .create_host(host_at(&network.id, [10, 20, 0, 5]))
+        .await
+        .unwrap();
+
+    let cidr_rule = services
+        .group_rule_service
+        .create(rule(
+            network.id,
+            servers.id,
+            GroupRuleMatch::Cidr {
+                cidr: "10.20.0.0/16".parse().unwrap(),
+            },
+        ))
+        .await
+        .unwrap();
+
+    let matching = services
+        .host_service
+        .create_host(host_at(&network.id, [10, 20, 1, 7]))
+        .await
+        .unwrap();
+    let other = services
+        .host_service
+        .create_host(host_at(&network.id, [192, 168, 1, 9]))
+        .await
+        .unwrap();
+
+    assert_eq!(matching.base.group_ids, vec![servers.id]);
+    assert!(other.base.group_ids.is_empty());
+    let existing = services
+        .host_service
+        .get_by_id(&existing.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(existing.base.group_ids.is_empty());
+
+    // Dry run of a rule that isn't saved
+    let preview = services
+        .group_rule_service
+        .preview(&rule(
+            network.id,
+            servers.id,
+            GroupRuleMatch::Hostname {
+                pattern: "HOST-9.*".to_string(),
+            },
+        ))
+        .await
+        .unwrap();
+    assert_eq!(preview.matched, vec![other.id]);
+    assert!(preview.ungrouped.is_empty());
+
+    let preview = services
+        .group_rule_service
+        .preview(&cidr_rule)
+        .await
+        .unwrap();
+    let mut matched = preview.matched.clone();
+    matched.sort();
+    let mut expected = vec![existing.id, matching.id];
+    expected.sort();
+    assert_eq!(matched, expected);
+    assert_eq!(preview.ungrouped, vec![other.id]);
+
+    assert_eq!(
+        services
+            .host_service
+            .reevaluate_groups(&network.id)
+            .await
+            .unwrap(),
+        1
+    );
+    let existing = services
+        .host_service
+        .get_by_id(&existing.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(existing.base.group_ids, vec![servers.id]);
+    let other = services
+        .host_service
+        .get_by_id(&other.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(other.base.group_ids.is_empty());
+
+    // Deleting the group takes it out of its hosts
+    services.group_service.delete(&servers.id).await.unwrap();
+    for host_id in [existing.id, matching.id] {
+        let host = services
+            .host_service
+            .get_by_id(&host_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(host.base.group_ids.is_empty());
+    }
+}
+
+#[test]
+fn test_rules_evaluated_first_match_or_all_matches() {
+    let network_id = uuid::Uuid::new_v4();
+    let mut host = host_at(&network_id, [10, 20, 1, 7]);
+    host.base.ports = vec![
+        Port::new(PortBase::new_tcp(22)),
+        Port::new(PortBase::new_tcp(443)),
+    ];
+    host.base.tags = vec!["Printer".to_string()];
+
+    let mut ssh = rule(
+        network_id,
+        uuid::Uuid::new_v4(),
+        GroupRuleMatch::OpenPorts {
+            ports: vec![22, 443],
+        },
+    );
+    ssh.base.priority = 10;
+    let mut subnet = rule(
+        network_id,
+        uuid::Uuid::new_v4(),
+        GroupRuleMatch::Cidr {
+            cidr: "10.20.0.0/16".parse().unwrap(),
+        },
+    );
+    subnet.base.priority = 1;
+    let web = rule(
+        network_id,
+        uuid::Uuid::new_v4(),
+        GroupRuleMatch::OpenPorts {
+            ports: vec![443, 8443],
+        },
+    );
+    let printers = rule(
+        network_id,
+        uuid::Uuid::new_v4(),
+        GroupRuleMatch::Tag {
+            tag: "printer".to_string(),
+        },
+    );
+    let scanners = rule(
+        network_id,
+        uuid::Uuid::new_v4(),
+        GroupRuleMatch::Tag {
+            tag: "scanner".to_string(),
+        },
+    );
+    let rules = vec![subnet.clone(), ssh.clone(), web, printers.clone(), scanners];
+
+    let ids = |mode| {
+        matching_rules(&rules, &host, mode)
+            .into_iter()
+            .map(|r| r.id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ids(GroupRuleMode::FirstMatch), vec![subnet.id]);
+    assert_eq!(
+        ids(GroupRuleMode::AllMatches),
+        vec![subnet.id, ssh.id, printers.id]
+    );
+
+    assert!(
+        GroupRuleMatch::OpenPorts { ports: vec![] }
+            .validate()
+            .is_err()
+    );
+    assert!(
+        GroupRuleMatch::Hostname {
+            pattern: " ".to_string()
+        }
+        .validate()
+        .is_err()
+    );
+    assert!(
+        GroupRuleMatch::Tag { tag: String::new() }
+            .validate()
+            .is_err()
+    );
+}
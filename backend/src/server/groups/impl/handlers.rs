@@ -1,6 +1,6 @@
 use crate::server::{
     groups::{r#impl::base::Group, service::GroupService},
-    shared::handlers::traits::CrudHandlers,
+    shared::handlers::traits::{CrudHandlers, EntityScope},
 };
 
 impl CrudHandlers for Group {
@@ -9,4 +9,8 @@ impl CrudHandlers for Group {
     fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
         &state.services.group_service
     }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.base.network_id)
+    }
 }
@@ -1,13 +1,20 @@
+use anyhow::Result;
 use async_trait::async_trait;
 use std::sync::Arc;
+use uuid::Uuid;
 
 use crate::server::{
     groups::r#impl::base::Group,
-    shared::{services::traits::CrudService, storage::generic::GenericPostgresStorage},
+    hosts::r#impl::base::Host,
+    shared::{
+        services::traits::CrudService,
+        storage::{filter::EntityFilter, generic::GenericPostgresStorage, traits::Storage},
+    },
 };
 
 pub struct GroupService {
     group_storage: Arc<GenericPostgresStorage<Group>>,
+    host_storage: Arc<GenericPostgresStorage<Host>>,
 }
 
 #[async_trait]
@@ -15,10 +22,43 @@ impl CrudService<Group> for GroupService {
     fn storage(&self) -> &Arc<GenericPostgresStorage<Group>> {
         &self.group_storage
     }
+
+    /// Delete the group and take it out of its hosts' `group_ids`. The group, and with it the
+    /// rules assigning it, goes first so nothing can add it back afterwards.
+    async fn delete(&self, id: &Uuid) -> Result<()> {
+        let group = self
+            .get_by_id(id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Group not found"))?;
+
+        self.group_storage.delete(id).await?;
+        let affected_hosts = self
+            .host_storage
+            .remove_array_element_where(
+                EntityFilter::unfiltered().group_id(id),
+                "group_ids",
+                &id.to_string(),
+            )
+            .await?;
+
+        tracing::info!(
+            group_id = %group.id,
+            group_name = %group.base.name,
+            affected_hosts = %affected_hosts,
+            "Group deleted"
+        );
+        Ok(())
+    }
 }
 
 impl GroupService {
-    pub fn new(group_storage: Arc<GenericPostgresStorage<Group>>) -> Self {
-        Self { group_storage }
+    pub fn new(
+        group_storage: Arc<GenericPostgresStorage<Group>>,
+        host_storage: Arc<GenericPostgresStorage<Host>>,
+    ) -> Self {
+        Self {
+            group_storage,
+            host_storage,
+        }
     }
 }
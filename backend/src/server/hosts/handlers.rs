@@ -1,19 +1,29 @@
-use crate::server::auth::middleware::{MemberOrDaemon, RequireMember};
-use crate::server::shared::handlers::traits::{CrudHandlers, get_all_handler, get_by_id_handler};
+use crate::server::auth::middleware::{
+    AuthenticatedUser, MemberOrDaemon, RequireAdmin, RequireMember,
+};
+use crate::server::shared::extractors::{LimitedJson, SignedJson};
+use crate::server::shared::handlers::{
+    ndjson::{accepts_ndjson, ndjson_response},
+    traits::{CrudHandlers, get_by_id_handler},
+};
 use crate::server::shared::services::traits::CrudService;
 use crate::server::shared::storage::filter::EntityFilter;
 use crate::server::shared::storage::traits::StorableEntity;
 use crate::server::{
     config::AppState,
-    hosts::r#impl::{api::HostWithServicesRequest, base::Host},
+    hosts::r#impl::{
+        api::{HostListQuery, HostStateRequest, HostWithServicesRequest},
+        base::{Host, HostState},
+    },
     services::r#impl::base::Service,
     shared::types::api::{ApiError, ApiResponse, ApiResult},
 };
 use axum::routing::{delete, get};
 use axum::{
     Router,
-    extract::{Path, State},
-    response::Json,
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    response::{IntoResponse, Json, Response},
     routing::{post, put},
 };
 use futures::future::try_join_all;
@@ -24,21 +34,72 @@ use validator::Validate;
 
 pub fn create_router() -> Router<Arc<AppState>> {
     Router::new()
-        .route("/", get(get_all_handler::<Host>))
+        .route("/", get(get_all_hosts))
         .route("/{id}", delete(delete_handler))
         .route("/{id}", get(get_by_id_handler::<Host>))
         .route("/", post(create_host))
         .route("/{id}", put(update_host))
+        .route("/{id}/state", put(set_host_state))
         .route(
             "/{destination_host}/consolidate/{other_host}",
             put(consolidate_hosts),
         )
 }
 
+/// Retired hosts are left out unless `include_retired` is set. With `Accept:
+/// application/x-ndjson` hosts are streamed one per line instead of returned as one array.
+async fn get_all_hosts(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+    Query(query): Query<HostListQuery>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let mut filter = EntityFilter::unfiltered().network_ids(&user.network_ids);
+    if !query.include_retired {
+        filter = filter.exclude_host_state(HostState::Retired);
+    }
+
+    if accepts_ndjson(&headers) {
+        return Ok(ndjson_response(
+            state.services.host_service.stream_all(filter),
+        ));
+    }
+
+    let hosts = state.services.host_service.get_all(filter).await?;
+
+    Ok(Json(ApiResponse::success(hosts)).into_response())
+}
+
+async fn set_host_state(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Path(id): Path<Uuid>,
+    Json(request): Json<HostStateRequest>,
+) -> ApiResult<Json<ApiResponse<Host>>> {
+    let host_service = &state.services.host_service;
+
+    let host = host_service
+        .get_by_id(&id)
+        .await?
+        .filter(|h| user.network_ids.contains(&h.base.network_id))
+        .ok_or_else(|| ApiError::not_found(format!("Host '{}' not found", id)))?;
+
+    if !host.base.state.can_transition_to(request.state) && host.base.state != request.state {
+        return Err(ApiError::conflict(&format!(
+            "Can't move host from {:?} to {:?}",
+            host.base.state, request.state
+        )));
+    }
+
+    let updated_host = host_service.set_state(&id, request.state).await?;
+
+    Ok(Json(ApiResponse::success(updated_host)))
+}
+
 async fn create_host(
     State(state): State<Arc<AppState>>,
     MemberOrDaemon { .. }: MemberOrDaemon,
-    Json(request): Json<HostWithServicesRequest>,
+    SignedJson(request): SignedJson<HostWithServicesRequest>,
 ) -> ApiResult<Json<ApiResponse<HostWithServicesRequest>>> {
     let host_service = &state.services.host_service;
 
@@ -67,7 +128,7 @@ async fn create_host(
 async fn update_host(
     State(state): State<Arc<AppState>>,
     RequireMember(_user): RequireMember,
-    Json(mut request): Json<HostWithServicesRequest>,
+    LimitedJson(mut request): LimitedJson<HostWithServicesRequest>,
 ) -> ApiResult<Json<ApiResponse<Host>>> {
     let host_service = &state.services.host_service;
     let service_service = &state.services.service_service;
@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 
-use crate::server::{hosts::r#impl::base::Host, services::r#impl::base::Service};
+use crate::server::{
+    hosts::r#impl::base::{Host, HostState},
+    services::r#impl::base::Service,
+};
 
 /// None in services = don't do anything to services, no services to create or update
 /// Some(vec!()) = delete all services
@@ -10,3 +13,14 @@ pub struct HostWithServicesRequest {
     #[serde(default)]
     pub services: Option<Vec<Service>>,
 }
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HostListQuery {
+    #[serde(default)]
+    pub include_retired: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostStateRequest {
+    pub state: HostState,
+}
@@ -1,3 +1,5 @@
+use crate::server::hosts::r#impl::conflicts::IpConflict;
+use crate::server::hosts::r#impl::identity::HostIdentityKey;
 use crate::server::hosts::r#impl::virtualization::HostVirtualization;
 use crate::server::shared::types::api::deserialize_empty_string_as_none;
 use crate::server::shared::types::entities::EntitySource;
@@ -14,11 +16,36 @@ use std::{hash::Hash, net::IpAddr};
 use uuid::Uuid;
 use validator::Validate;
 
-static INVALID_MACS_BYTES: &[[u8; 6]; 2] = &[
+pub(crate) static INVALID_MACS_BYTES: &[[u8; 6]; 2] = &[
     [0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
     [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
 ];
 
+/// Where a host is in its lifecycle
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Default, PartialEq, Eq, Hash)]
+pub enum HostState {
+    /// Auto-created by discovery and awaiting review
+    Pending,
+    #[default]
+    Active,
+    /// Decommissioned. Hidden from default lists but kept for history.
+    Retired,
+}
+
+impl HostState {
+    /// Whether a user may move a host from this state to `next`. Retired hosts only go back to
+    /// pending through rediscovery.
+    pub fn can_transition_to(&self, next: HostState) -> bool {
+        matches!(
+            (self, next),
+            (HostState::Pending, HostState::Active)
+                | (HostState::Pending, HostState::Retired)
+                | (HostState::Active, HostState::Retired)
+                | (HostState::Retired, HostState::Active)
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Validate, Deserialize, Eq, PartialEq, Hash)]
 pub struct HostBase {
     #[validate(length(min = 0, max = 100))]
@@ -35,6 +62,17 @@ pub struct HostBase {
     pub source: EntitySource,
     pub virtualization: Option<HostVirtualization>,
     pub hidden: bool,
+    #[serde(default)]
+    pub state: HostState,
+    /// Node groups the host belongs to, set by hand or by group rules
+    #[serde(default)]
+    pub group_ids: Vec<Uuid>,
+    /// Free-form tags, e.g. `printer`, which group rules can match on
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// IPs other devices were recently seen on too. Set by discovery.
+    #[serde(default)]
+    pub ip_conflicts: Vec<IpConflict>,
 }
 
 impl Default for HostBase {
@@ -51,6 +89,10 @@ impl Default for HostBase {
             source: EntitySource::Unknown,
             virtualization: None,
             hidden: false,
+            state: HostState::default(),
+            group_ids: Vec::new(),
+            tags: Vec::new(),
+            ip_conflicts: Vec::new(),
         }
     }
 }
@@ -94,36 +136,7 @@ impl Hash for Host {
 
 impl PartialEq for Host {
     fn eq(&self, other: &Self) -> bool {
-        let network_match = self.base.network_id == other.base.network_id;
-        let invalid_macs = INVALID_MACS_BYTES.map(MacAddress::new);
-        let macs_a: Vec<Option<MacAddress>> = self
-            .base
-            .interfaces
-            .iter()
-            .map(|s| s.base.mac_address)
-            .collect();
-        let macs_b: Vec<Option<MacAddress>> = other
-            .base
-            .interfaces
-            .iter()
-            .map(|s| s.base.mac_address)
-            .collect();
-
-        let mac_match = macs_a.iter().any(|mac_a| {
-            macs_b.iter().any(|mac_b| match (mac_a, mac_b) {
-                (Some(a), Some(b)) => !invalid_macs.contains(a) && a == b,
-                (_, _) => false,
-            })
-        });
-
-        let subnet_ip_match = self.base.interfaces.iter().any(|subnet_a| {
-            other.base.interfaces.iter().any(|subnet_b| {
-                subnet_a.base.subnet_id == subnet_b.base.subnet_id
-                    && subnet_a.base.ip_address == subnet_b.base.ip_address
-            })
-        });
-
-        self.id == other.id || (network_match && mac_match) || (network_match && subnet_ip_match)
+        self.same_identity(other, HostIdentityKey::default())
     }
 }
 
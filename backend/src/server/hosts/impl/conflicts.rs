@@ -0,0 +1,274 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use cidr::IpCidr;
+use mac_address::MacAddress;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, net::IpAddr, sync::Arc};
+use uuid::Uuid;
+
+use crate::server::{
+    config::ServerConfig,
+    hosts::r#impl::interfaces::Interface,
+    shared::storage::{
+        filter::EntityFilter,
+        generic::GenericPostgresStorage,
+        traits::{StorableEntity, Storage},
+    },
+};
+
+/// Another device seen on one of a host's IPs within the conflict window. Devices are told apart
+/// by MAC, so when the network's identity key merged both into one host, `other_host_id` is the
+/// host itself.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct IpConflict {
+    pub ip: IpAddr,
+    /// This host's MAC on the IP
+    pub mac: MacAddress,
+    pub other_host_id: Uuid,
+    pub other_mac: MacAddress,
+    pub detected_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IpConflictPolicy {
+    /// How recently two MACs must both have been seen on an IP to conflict. Zero disables
+    /// detection.
+    pub window: Duration,
+    /// Addresses shared on purpose, e.g. VRRP/HSRP virtual IPs, which never conflict
+    pub exempt: Vec<IpCidr>,
+}
+
+impl Default for IpConflictPolicy {
+    fn default() -> Self {
+        Self {
+            window: Duration::hours(24),
+            exempt: Vec::new(),
+        }
+    }
+}
+
+impl From<&ServerConfig> for IpConflictPolicy {
+    fn from(config: &ServerConfig) -> Self {
+        Self {
+            window: Duration::seconds(config.ip_conflict_window_secs as i64),
+            exempt: config.ip_conflict_exempt_cidrs.clone(),
+        }
+    }
+}
+
+/// A MAC discovery saw on an IP, and when it was last seen there
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpClaimBase {
+    pub network_id: Uuid,
+    pub ip: IpAddr,
+    pub mac: MacAddress,
+    /// The host the MAC was last reported on
+    pub host_id: Uuid,
+    pub seen_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpClaim {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub base: IpClaimBase,
+}
+
+impl Display for IpClaim {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "IP claim {} on {}: {}",
+            self.base.mac, self.base.ip, self.id
+        )
+    }
+}
+
+/// How a claim seen again is merged into the stored one
+const CLAIM_MERGE: [(&str, &str); 2] = [
+    ("host_id", "EXCLUDED.host_id"),
+    ("seen_at", "GREATEST(ip_claims.seen_at, EXCLUDED.seen_at)"),
+];
+
+/// Spots two devices claiming the same address from the MACs discovery recently saw on each IP
+/// of each network. Claims are stored, so conflicts spanning a restart or another server are
+/// still found. Only IP to MAC matters, so a device whose IP changes (DHCP churn) never
+/// conflicts with itself, and an address handed to a new device only conflicts if the old one
+/// was also seen on it within the window.
+pub struct IpConflictDetector {
+    policy: IpConflictPolicy,
+    storage: Arc<GenericPostgresStorage<IpClaim>>,
+}
+
+impl IpConflictDetector {
+    pub fn new(policy: IpConflictPolicy, storage: Arc<GenericPostgresStorage<IpClaim>>) -> Self {
+        Self { policy, storage }
+    }
+
+    /// The IP and MAC of each interface that can conflict. Interfaces without a MAC can't be
+    /// told apart from other devices, and exempt addresses are shared on purpose.
+    fn claimable(&self, interfaces: &[Interface]) -> Vec<(IpAddr, MacAddress)> {
+        interfaces
+            .iter()
+            .filter_map(|i| i.base.mac_address.map(|mac| (i.base.ip_address, mac)))
+            .filter(|(ip, _)| !self.policy.exempt.iter().any(|cidr| cidr.contains(ip)))
+            .collect()
+    }
+
+    /// Record that discovery saw `host_id` on `interfaces` in `network_id`, returning a conflict
+    /// for each interface IP another MAC was also seen on within the window
+    pub async fn observe(
+        &self,
+        network_id: Uuid,
+        host_id: Uuid,
+        interfaces: &[Interface],
+        now: DateTime<Utc>,
+    ) -> Result<Vec<IpConflict>> {
+        if self.policy.window <= Duration::zero() {
+            return Ok(Vec::new());
+        }
+
+        let window_start = now - self.policy.window;
+        self.storage
+            .delete_where(
+                EntityFilter::unfiltered()
+                    .network_ids(&[network_id])
+                    .seen_by(window_start),
+            )
+            .await?;
+
+        let observed = self.claimable(interfaces);
+        if observed.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let ips: Vec<IpAddr> = observed.iter().map(|(ip, _)| *ip).collect();
+        let claims = self
+            .storage
+            .get_all(
+                EntityFilter::unfiltered()
+                    .network_ids(&[network_id])
+                    .claimed_ips(&ips)
+                    .seen_after(window_start),
+            )
+            .await?;
+        let conflicts = conflicting_claims(&claims, &observed, now);
+
+        for (ip, mac) in observed {
+            let claim = IpClaim::new(IpClaimBase {
+                network_id,
+                ip,
+                mac,
+                host_id,
+                seen_at: now,
+            });
+            self.storage
+                .upsert(&claim, &["network_id", "ip", "mac"], &CLAIM_MERGE)
+                .await?;
+        }
+
+        Ok(conflicts)
+    }
+}
+
+/// A conflict for each of `claims` on an `observed` IP by a different MAC
+fn conflicting_claims(
+    claims: &[IpClaim],
+    observed: &[(IpAddr, MacAddress)],
+    now: DateTime<Utc>,
+) -> Vec<IpConflict> {
+    observed
+        .iter()
+        .flat_map(|&(ip, mac)| {
+            claims
+                .iter()
+                .filter(move |c| c.base.ip == ip && c.base.mac != mac)
+                .map(move |c| IpConflict {
+                    ip,
+                    mac,
+                    other_host_id: c.base.host_id,
+                    other_mac: c.base.mac,
+                    detected_at: now,
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::hosts::r#impl::interfaces::InterfaceBase;
+    use sqlx::PgPool;
+
+    fn interface(ip: &str, mac: Option<[u8; 6]>) -> Interface {
+        Interface::new(InterfaceBase {
+            subnet_id: Uuid::nil(),
+            ip_address: ip.parse().unwrap(),
+            mac_address: mac.map(MacAddress::new),
+            name: None,
+        })
+    }
+
+    fn claim(ip: &str, mac: [u8; 6], host_id: Uuid) -> IpClaim {
+        IpClaim::new(IpClaimBase {
+            network_id: Uuid::nil(),
+            ip: ip.parse().unwrap(),
+            mac: MacAddress::new(mac),
+            host_id,
+            seen_at: Utc::now(),
+        })
+    }
+
+    #[test]
+    fn test_other_macs_on_an_ip_conflict_but_the_same_mac_does_not() {
+        let now = Utc::now();
+        let (host_a, host_b) = (Uuid::new_v4(), Uuid::new_v4());
+        let (mac_a, mac_b) = ([2, 0, 0, 0, 0, 1], [2, 0, 0, 0, 0, 2]);
+        let claims = vec![
+            claim("10.0.0.5", mac_a, host_a),
+            claim("10.0.0.6", mac_a, host_a),
+        ];
+
+        // The same device seen again, wherever it moved to, is normal
+        let observed = [("10.0.0.6".parse().unwrap(), MacAddress::new(mac_a))];
+        assert!(conflicting_claims(&claims, &observed, now).is_empty());
+
+        // A second device on an address the first was recently seen on conflicts
+        let observed = [("10.0.0.5".parse().unwrap(), MacAddress::new(mac_b))];
+        let conflicts = conflicting_claims(&claims, &observed, now);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].ip, "10.0.0.5".parse::<IpAddr>().unwrap());
+        assert_eq!(conflicts[0].mac, MacAddress::new(mac_b));
+        assert_eq!(conflicts[0].other_host_id, host_a);
+        assert_eq!(conflicts[0].other_mac, MacAddress::new(mac_a));
+        assert_ne!(conflicts[0].other_host_id, host_b);
+    }
+
+    #[tokio::test]
+    async fn test_exempt_and_macless_interfaces_never_conflict() {
+        // Choosing what to claim doesn't touch storage
+        let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let detector = IpConflictDetector::new(
+            IpConflictPolicy {
+                exempt: vec!["10.0.0.1/32".parse().unwrap()],
+                ..IpConflictPolicy::default()
+            },
+            Arc::new(GenericPostgresStorage::new(pool)),
+        );
+
+        let claimable = detector.claimable(&[
+            interface("10.0.0.1", Some([2, 0, 0, 0, 0, 1])),
+            interface("10.0.0.2", None),
+            interface("10.0.0.3", Some([2, 0, 0, 0, 0, 3])),
+        ]);
+        assert_eq!(
+            claimable,
+            vec![(
+                "10.0.0.3".parse().unwrap(),
+                MacAddress::new([2, 0, 0, 0, 0, 3])
+            )]
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+use uuid::Uuid;
+
+use crate::server::{
+    config::ServerConfig,
+    hosts::r#impl::base::Host,
+    services::r#impl::{base::Service, bindings::Binding},
+    shared::sync::lock,
+};
+
+/// How long a finding is remembered for within-session deduplication. Sessions don't outlive
+/// this (see `DiscoveryService::cleanup_old_sessions`).
+const SESSION_RETENTION_HOURS: i64 = 24;
+
+/// Which parts of a finding identify it
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupKey {
+    #[default]
+    HostPortService,
+    HostPort,
+    HostService,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResultDedupPolicy {
+    pub key: DedupKey,
+    /// Cross-session window. Zero only deduplicates within a session.
+    pub window: Duration,
+}
+
+impl Default for ResultDedupPolicy {
+    fn default() -> Self {
+        Self {
+            key: DedupKey::default(),
+            window: Duration::zero(),
+        }
+    }
+}
+
+impl From<&ServerConfig> for ResultDedupPolicy {
+    fn from(config: &ServerConfig) -> Self {
+        Self {
+            key: config.discovery_dedup_key,
+            window: Duration::seconds(config.discovery_dedup_window_secs as i64),
+        }
+    }
+}
+
+/// One (host, port, service) observation from a discovery result
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Finding {
+    pub host: String,
+    pub port: Option<String>,
+    pub service: Option<String>,
+}
+
+impl Finding {
+    /// Identify a host by its network and addresses, which are stable across submissions
+    /// unlike the ids a daemon assigns
+    fn host_identity(host: &Host) -> String {
+        let mut addresses: Vec<String> = host
+            .base
+            .interfaces
+            .iter()
+            .map(|i| format!("{}/{}", i.base.subnet_id, i.base.ip_address))
+            .collect();
+        addresses.sort_unstable();
+
+        format!("{}:{}", host.base.network_id, addresses.join(","))
+    }
+
+    /// Findings for one service on `host`: one per port it's bound to, or a single portless
+    /// one if it's only bound to interfaces
+    pub fn for_service(host: &Host, service: &Service) -> Vec<Finding> {
+        let identity = Self::host_identity(host);
+        let name = service.base.service_definition.id().to_string();
+
+        let ports: Vec<String> = service
+            .base
+            .bindings
+            .iter()
+            .filter_map(|b| match b {
+                Binding::Port { port_id, .. } => host.get_port(port_id).map(|p| p.to_string()),
+                Binding::Interface { .. } => None,
+            })
+            .collect();
+
+        if ports.is_empty() {
+            return vec![Finding {
+                host: identity,
+                port: None,
+                service: Some(name),
+            }];
+        }
+
+        ports
+            .into_iter()
+            .map(|port| Finding {
+                host: identity.clone(),
+                port: Some(port),
+                service: Some(name.clone()),
+            })
+            .collect()
+    }
+
+    /// Findings for ports on `host` which no service is bound to, or for the bare host if it
+    /// has nothing else to report
+    pub fn for_host(host: &Host, services: &[Service]) -> Vec<Finding> {
+        let identity = Self::host_identity(host);
+        let bound: HashSet<Uuid> = services
+            .iter()
+            .flat_map(|s| &s.base.bindings)
+            .filter_map(|b| match b {
+                Binding::Port { port_id, .. } => Some(*port_id),
+                Binding::Interface { .. } => None,
+            })
+            .collect();
+
+        let findings: Vec<Finding> = host
+            .base
+            .ports
+            .iter()
+            .filter(|p| !bound.contains(&p.id))
+            .map(|p| Finding {
+                host: identity.clone(),
+                port: Some(p.to_string()),
+                service: None,
+            })
+            .collect();
+
+        if findings.is_empty() && services.is_empty() {
+            return vec![Finding {
+                host: identity,
+                port: None,
+                service: None,
+            }];
+        }
+
+        findings
+    }
+
+    /// The parts of this finding which `key` compares
+    fn keyed(&self, key: DedupKey) -> Finding {
+        Finding {
+            host: self.host.clone(),
+            port: self.port.clone().filter(|_| key != DedupKey::HostService),
+            service: self.service.clone().filter(|_| key != DedupKey::HostPort),
+        }
+    }
+}
+
+struct Seen {
+    at: DateTime<Utc>,
+    session_id: Option<Uuid>,
+}
+
+/// Remembers recently ingested findings so retried or overlapping submissions aren't counted
+/// twice. A finding is a duplicate if it was already seen in the same discovery session, or
+/// within the policy window in any session; anything older is a legitimate re-observation.
+#[derive(Default)]
+pub struct ResultDeduplicator {
+    policy: ResultDedupPolicy,
+    seen: Mutex<HashMap<Finding, Seen>>,
+}
+
+impl ResultDeduplicator {
+    pub fn new(policy: ResultDedupPolicy) -> Self {
+        Self {
+            policy,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_duplicate(
+        &self,
+        finding: &Finding,
+        session_id: Option<Uuid>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let seen = lock(&self.seen);
+
+        seen.get(&finding.keyed(self.policy.key))
+            .is_some_and(|seen| {
+                (session_id.is_some() && seen.session_id == session_id)
+                    || now - seen.at < self.policy.window
+            })
+    }
+
+    /// Whether every finding is a duplicate. An empty set never is.
+    pub fn all_duplicates(
+        &self,
+        findings: &[Finding],
+        session_id: Option<Uuid>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        !findings.is_empty()
+            && findings
+                .iter()
+                .all(|f| self.is_duplicate(f, session_id, now))
+    }
+
+    pub fn record(&self, findings: &[Finding], session_id: Option<Uuid>, now: DateTime<Utc>) {
+        let retention = self
+            .policy
+            .window
+            .max(Duration::hours(SESSION_RETENTION_HOURS));
+        let mut seen = lock(&self.seen);
+
+        seen.retain(|_, seen| now - seen.at < retention);
+        for finding in findings {
+            seen.insert(
+                finding.keyed(self.policy.key),
+                Seen {
+                    at: now,
+                    session_id,
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(port: &str, service: &str) -> Finding {
+        Finding {
+            host: "host".to_string(),
+            port: Some(port.to_string()),
+            service: Some(service.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_duplicates_within_session_and_window() {
+        let now = Utc::now();
+        let session = Some(Uuid::new_v4());
+        let ssh = finding("22/tcp", "Ssh");
+
+        // Session only
+        let dedup = ResultDeduplicator::default();
+        dedup.record(std::slice::from_ref(&ssh), session, now);
+        assert!(dedup.is_duplicate(&ssh, session, now + Duration::hours(1)));
+        assert!(!dedup.is_duplicate(&ssh, Some(Uuid::new_v4()), now));
+        assert!(!dedup.is_duplicate(&ssh, None, now));
+
+        // Across sessions within the window, but not after a gap longer than it
+        let dedup = ResultDeduplicator::new(ResultDedupPolicy {
+            key: DedupKey::HostPortService,
+            window: Duration::minutes(10),
+        });
+        dedup.record(std::slice::from_ref(&ssh), session, now);
+        assert!(dedup.is_duplicate(&ssh, None, now + Duration::minutes(5)));
+        assert!(!dedup.is_duplicate(&ssh, None, now + Duration::minutes(15)));
+    }
+
+    #[test]
+    fn test_dedup_key_selects_compared_parts() {
+        let now = Utc::now();
+        let session = Some(Uuid::new_v4());
+        let policy = |key| ResultDedupPolicy {
+            key,
+            window: Duration::zero(),
+        };
+
+        let dedup = ResultDeduplicator::new(policy(DedupKey::HostPortService));
+        dedup.record(&[finding("80/tcp", "Nginx")], session, now);
+        assert!(!dedup.is_duplicate(&finding("80/tcp", "Apache"), session, now));
+
+        let dedup = ResultDeduplicator::new(policy(DedupKey::HostPort));
+        dedup.record(&[finding("80/tcp", "Nginx")], session, now);
+        assert!(dedup.is_duplicate(&finding("80/tcp", "Apache"), session, now));
+        assert!(!dedup.is_duplicate(&finding("8080/tcp", "Nginx"), session, now));
+
+        let dedup = ResultDeduplicator::new(policy(DedupKey::HostService));
+        dedup.record(&[finding("80/tcp", "Nginx")], session, now);
+        assert!(dedup.is_duplicate(&finding("8080/tcp", "Nginx"), session, now));
+    }
+}
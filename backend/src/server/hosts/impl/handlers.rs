@@ -1,6 +1,6 @@
 use crate::server::{
     hosts::{r#impl::base::Host, service::HostService},
-    shared::handlers::traits::CrudHandlers,
+    shared::handlers::traits::{CrudHandlers, EntityScope},
 };
 
 impl CrudHandlers for Host {
@@ -9,4 +9,8 @@ impl CrudHandlers for Host {
     fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
         &state.services.host_service
     }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.base.network_id)
+    }
 }
@@ -0,0 +1,196 @@
+use std::net::IpAddr;
+
+use mac_address::{MacAddress, MacParseError};
+use serde::{Deserialize, Serialize};
+use strum::{Display as StrumDisplay, EnumString};
+
+use crate::server::hosts::r#impl::base::{Host, INVALID_MACS_BYTES};
+
+/// What identifies the same host across creates, imports and discovery runs on a network
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    StrumDisplay,
+    EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum HostIdentityKey {
+    /// A shared MAC address, or failing that the same IP on the same subnet
+    #[default]
+    Mac,
+    /// The same IP on the same subnet only, for networks where MACs are reused (e.g. by VMs)
+    Ip,
+    /// The same hostname, falling back to `Mac` for hosts without one
+    Hostname,
+}
+
+/// Lowercase, trimmed and without the root label's trailing dot, so `Host-01.lan.` and
+/// `host-01.lan` are the same name. Blank hostnames are no hostname.
+pub fn canonical_hostname(hostname: &str) -> Option<String> {
+    let hostname = hostname.trim().trim_end_matches('.');
+    (!hostname.is_empty()).then(|| hostname.to_lowercase())
+}
+
+/// IPv4-mapped IPv6 addresses (`::ffff:192.0.2.1`) as the IPv4 address they map. IPv6 addresses
+/// are otherwise already compared by value, so zero padding and `::` compression don't matter.
+pub fn canonical_ip(ip: IpAddr) -> IpAddr {
+    ip.to_canonical()
+}
+
+/// Parse a MAC address in any common notation: colon or dash separated, Cisco's dotted
+/// `aabb.ccdd.eeff`, or bare hex digits, in either case
+pub fn parse_mac(mac: &str) -> Result<MacAddress, MacParseError> {
+    let mac = mac.trim();
+    if mac.len() == 14 && mac.matches('.').count() == 2 {
+        return mac.replace('.', "").parse();
+    }
+    mac.parse()
+}
+
+fn is_valid_mac(mac: &MacAddress) -> bool {
+    !INVALID_MACS_BYTES.contains(&mac.bytes())
+}
+
+impl Host {
+    /// Normalize the host's identifying fields in place, so differently formatted reports of the
+    /// same host compare and store the same
+    pub fn canonicalize(&mut self) {
+        self.base.hostname = self.base.hostname.as_deref().and_then(canonical_hostname);
+        for interface in &mut self.base.interfaces {
+            interface.base.ip_address = canonical_ip(interface.base.ip_address);
+        }
+    }
+
+    /// Whether `other` is the same host as this one when hosts are identified by `key`
+    pub fn same_identity(&self, other: &Host, key: HostIdentityKey) -> bool {
+        if self.id == other.id {
+            return true;
+        }
+        if self.base.network_id != other.base.network_id {
+            return false;
+        }
+
+        let mac_match = || {
+            self.base.interfaces.iter().any(|a| {
+                other.base.interfaces.iter().any(|b| {
+                    match (a.base.mac_address, b.base.mac_address) {
+                        (Some(a), Some(b)) => is_valid_mac(&a) && a == b,
+                        _ => false,
+                    }
+                })
+            })
+        };
+        let subnet_ip_match = || {
+            self.base.interfaces.iter().any(|a| {
+                other.base.interfaces.iter().any(|b| {
+                    a.base.subnet_id == b.base.subnet_id
+                        && canonical_ip(a.base.ip_address) == canonical_ip(b.base.ip_address)
+                })
+            })
+        };
+
+        match key {
+            HostIdentityKey::Mac => mac_match() || subnet_ip_match(),
+            HostIdentityKey::Ip => subnet_ip_match(),
+            HostIdentityKey::Hostname => {
+                let hostnames = (
+                    self.base.hostname.as_deref().and_then(canonical_hostname),
+                    other.base.hostname.as_deref().and_then(canonical_hostname),
+                );
+                match hostnames {
+                    (Some(a), Some(b)) => a == b,
+                    _ => self.same_identity(other, HostIdentityKey::Mac),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{host, interface};
+    use std::net::Ipv6Addr;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_identifiers_normalize_to_one_form() {
+        assert_eq!(
+            canonical_hostname("Host-01.lan."),
+            Some("host-01.lan".into())
+        );
+        assert_eq!(
+            canonical_hostname("  HOST-01 "),
+            canonical_hostname("host-01")
+        );
+        assert_eq!(canonical_hostname(" . "), None);
+
+        let macs = [
+            "AA:BB:CC:DD:EE:FF",
+            "aa-bb-cc-dd-ee-ff",
+            "aabb.ccdd.eeff",
+            "AABBCCDDEEFF",
+            " aa:bb:cc:dd:ee:ff\n",
+        ];
+        for mac in macs {
+            assert_eq!(
+                parse_mac(mac).unwrap(),
+                MacAddress::new([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]),
+                "{mac}"
+            );
+        }
+        assert!(parse_mac("aabb.ccdd").is_err());
+
+        let padded: IpAddr = "2001:0db8::0001".parse().unwrap();
+        let compressed: IpAddr = "2001:db8::1".parse().unwrap();
+        assert_eq!(canonical_ip(padded), canonical_ip(compressed));
+        assert_eq!(canonical_ip(padded).to_string(), "2001:db8::1");
+
+        let mapped = IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0xffff, 0xc000, 0x0201));
+        assert_eq!(canonical_ip(mapped), "192.0.2.1".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_identity_key_selects_what_matches() {
+        let network_id = Uuid::new_v4();
+        let subnet_id = Uuid::new_v4();
+
+        let mut a = host(&network_id);
+        a.base.hostname = Some("Host-01".into());
+        a.base.interfaces = vec![interface(&subnet_id)];
+
+        // Same MAC, different IP and differently cased hostname
+        let mut b = host(&network_id);
+        b.base.hostname = Some("host-01.".into());
+        let mut iface = interface(&subnet_id);
+        iface.base.ip_address = "192.168.1.200".parse().unwrap();
+        b.base.interfaces = vec![iface];
+
+        assert!(a.same_identity(&b, HostIdentityKey::Mac));
+        assert!(!a.same_identity(&b, HostIdentityKey::Ip));
+        assert!(a.same_identity(&b, HostIdentityKey::Hostname));
+
+        b.base.hostname = Some("host-02".into());
+        assert!(!a.same_identity(&b, HostIdentityKey::Hostname));
+
+        // Hosts without hostnames fall back to matching by MAC
+        b.base.hostname = None;
+        assert!(a.same_identity(&b, HostIdentityKey::Hostname));
+
+        // The same IP written as an IPv4-mapped IPv6 address
+        let mut c = host(&network_id);
+        let mut iface = interface(&subnet_id);
+        iface.base.mac_address = None;
+        iface.base.ip_address = "::ffff:192.168.1.100".parse().unwrap();
+        c.base.interfaces = vec![iface];
+        assert!(a.same_identity(&c, HostIdentityKey::Ip));
+    }
+}
@@ -1,6 +1,9 @@
 pub mod api;
 pub mod base;
+pub mod conflicts;
+pub mod dedup;
 pub mod handlers;
+pub mod identity;
 pub mod interfaces;
 pub mod ports;
 pub mod storage;
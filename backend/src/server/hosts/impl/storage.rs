@@ -1,11 +1,14 @@
 use chrono::{DateTime, Utc};
+use mac_address::MacAddress;
 use sqlx::Row;
 use sqlx::postgres::PgRow;
+use std::net::IpAddr;
 use uuid::Uuid;
 
 use crate::server::{
     hosts::r#impl::{
-        base::{Host, HostBase},
+        base::{Host, HostBase, HostState},
+        conflicts::{IpClaim, IpClaimBase, IpConflict},
         interfaces::Interface,
         ports::Port,
         targets::HostTarget,
@@ -73,6 +76,10 @@ impl StorableEntity for Host {
                     services,
                     ports,
                     virtualization,
+                    state,
+                    group_ids,
+                    tags,
+                    ip_conflicts,
                 },
         } = self.clone();
 
@@ -92,6 +99,10 @@ impl StorableEntity for Host {
                 "ports",
                 "virtualization",
                 "interfaces",
+                "state",
+                "group_ids",
+                "tags",
+                "ip_conflicts",
             ],
             vec![
                 SqlValue::Uuid(id),
@@ -108,6 +119,10 @@ impl StorableEntity for Host {
                 SqlValue::Ports(ports),
                 SqlValue::OptionalHostVirtualization(virtualization),
                 SqlValue::Interfaces(interfaces),
+                SqlValue::HostState(state),
+                SqlValue::UuidArray(group_ids),
+                SqlValue::Json(serde_json::to_value(tags)?),
+                SqlValue::Json(serde_json::to_value(ip_conflicts)?),
             ],
         ))
     }
@@ -130,6 +145,16 @@ impl StorableEntity for Host {
         let virtualization: Option<HostVirtualization> =
             serde_json::from_value(row.get::<serde_json::Value, _>("virtualization"))
                 .map_err(|e| anyhow::anyhow!("Failed to deserialize virtualization: {}", e))?;
+        let state: HostState = serde_json::from_str(&row.get::<String, _>("state"))
+            .map_err(|e| anyhow::anyhow!("Failed to deserialize state: {}", e))?;
+        let group_ids: Vec<Uuid> =
+            serde_json::from_value(row.get::<serde_json::Value, _>("group_ids"))
+                .map_err(|e| anyhow::anyhow!("Failed to deserialize group_ids: {}", e))?;
+        let tags: Vec<String> = serde_json::from_value(row.get::<serde_json::Value, _>("tags"))
+            .map_err(|e| anyhow::anyhow!("Failed to deserialize tags: {}", e))?;
+        let ip_conflicts: Vec<IpConflict> =
+            serde_json::from_value(row.get::<serde_json::Value, _>("ip_conflicts"))
+                .map_err(|e| anyhow::anyhow!("Failed to deserialize ip_conflicts: {}", e))?;
 
         Ok(Host {
             id: row.get("id"),
@@ -147,6 +172,110 @@ impl StorableEntity for Host {
                 ports,
                 virtualization,
                 interfaces,
+                state,
+                group_ids,
+                tags,
+                ip_conflicts,
+            },
+        })
+    }
+}
+
+impl StorableEntity for IpClaim {
+    type BaseData = IpClaimBase;
+
+    fn table_name() -> &'static str {
+        "ip_claims"
+    }
+
+    fn get_base(&self) -> Self::BaseData {
+        self.base.clone()
+    }
+
+    fn new(base: Self::BaseData) -> Self {
+        let now = chrono::Utc::now();
+
+        Self {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: now,
+            base,
+        }
+    }
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn set_updated_at(&mut self, time: DateTime<Utc>) {
+        self.updated_at = time;
+    }
+
+    fn to_params(&self) -> Result<(Vec<&'static str>, Vec<SqlValue>), anyhow::Error> {
+        let Self {
+            id,
+            created_at,
+            updated_at,
+            base:
+                Self::BaseData {
+                    network_id,
+                    ip,
+                    mac,
+                    host_id,
+                    seen_at,
+                },
+        } = self.clone();
+
+        Ok((
+            vec![
+                "id",
+                "created_at",
+                "updated_at",
+                "network_id",
+                "ip",
+                "mac",
+                "host_id",
+                "seen_at",
+            ],
+            vec![
+                SqlValue::Uuid(id),
+                SqlValue::Timestamp(created_at),
+                SqlValue::Timestamp(updated_at),
+                SqlValue::Uuid(network_id),
+                SqlValue::IpAddr(ip),
+                SqlValue::String(mac.to_string()),
+                SqlValue::Uuid(host_id),
+                SqlValue::Timestamp(seen_at),
+            ],
+        ))
+    }
+
+    fn from_row(row: &PgRow) -> Result<Self, anyhow::Error> {
+        let ip: IpAddr = serde_json::from_str(&row.get::<String, _>("ip"))
+            .map_err(|e| anyhow::anyhow!("Failed to deserialize ip: {}", e))?;
+        let mac: MacAddress = row
+            .get::<String, _>("mac")
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Failed to parse mac: {}", e))?;
+
+        Ok(IpClaim {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            base: IpClaimBase {
+                network_id: row.get("network_id"),
+                ip,
+                mac,
+                host_id: row.get("host_id"),
+                seen_at: row.get("seen_at"),
             },
         })
     }
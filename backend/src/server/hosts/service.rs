@@ -1,27 +1,58 @@
-use crate::server::{
-    daemons::service::DaemonService,
-    hosts::r#impl::base::Host,
-    services::{r#impl::base::Service, service::ServiceService},
-    shared::{
-        services::traits::CrudService,
-        storage::{filter::EntityFilter, generic::GenericPostgresStorage, traits::Storage},
-        types::entities::{EntitySource, EntitySourceDiscriminants},
+use crate::{
+    daemon::discovery::types::base::DiscoveryPhase,
+    server::{
+        daemons::service::DaemonService,
+        discovery::service::DiscoveryService,
+        group_rules::service::GroupRuleService,
+        hosts::r#impl::{
+            base::{Host, HostState},
+            conflicts::{IpConflict, IpConflictDetector},
+            dedup::{Finding, ResultDeduplicator},
+            identity::HostIdentityKey,
+            interfaces::Interface,
+        },
+        networks::r#impl::Network,
+        notifications::{
+            r#impl::base::{Notification, NotificationKind},
+            service::NotificationService,
+        },
+        services::{r#impl::base::Service, service::ServiceService},
+        shared::{
+            services::traits::CrudService,
+            storage::{
+                filter::EntityFilter,
+                generic::GenericPostgresStorage,
+                traits::{SqlValue, Storage},
+            },
+            types::entities::{EntitySource, EntitySourceDiscriminants},
+        },
     },
 };
 use anyhow::{Error, Result, anyhow};
 use async_trait::async_trait;
+use chrono::Utc;
 use futures::future::{join_all, try_join_all};
 use itertools::{Either, Itertools};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    sync::{Arc, OnceLock},
+};
 use strum::IntoDiscriminant;
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
 pub struct HostService {
     storage: Arc<GenericPostgresStorage<Host>>,
+    network_storage: Arc<GenericPostgresStorage<Network>>,
     service_service: Arc<ServiceService>,
     daemon_service: Arc<DaemonService>,
     host_locks: Arc<Mutex<HashMap<Uuid, Arc<Mutex<()>>>>>,
+    deduplicator: OnceLock<ResultDeduplicator>,
+    ip_conflicts: OnceLock<IpConflictDetector>,
+    discovery_service: OnceLock<Arc<DiscoveryService>>,
+    group_rule_service: OnceLock<Arc<GroupRuleService>>,
+    notification_service: OnceLock<Arc<NotificationService>>,
 }
 
 #[async_trait]
@@ -34,17 +65,148 @@ impl CrudService<Host> for HostService {
 impl HostService {
     pub fn new(
         storage: Arc<GenericPostgresStorage<Host>>,
+        network_storage: Arc<GenericPostgresStorage<Network>>,
         service_service: Arc<ServiceService>,
         daemon_service: Arc<DaemonService>,
     ) -> Self {
         Self {
             storage,
+            network_storage,
             service_service,
             daemon_service,
             host_locks: Arc::new(Mutex::new(HashMap::new())),
+            deduplicator: OnceLock::new(),
+            ip_conflicts: OnceLock::new(),
+            discovery_service: OnceLock::new(),
+            group_rule_service: OnceLock::new(),
+            notification_service: OnceLock::new(),
+        }
+    }
+
+    pub fn set_deduplicator(
+        &self,
+        deduplicator: ResultDeduplicator,
+    ) -> Result<(), ResultDeduplicator> {
+        self.deduplicator.set(deduplicator)
+    }
+
+    pub fn set_ip_conflict_detector(
+        &self,
+        detector: IpConflictDetector,
+    ) -> Result<(), IpConflictDetector> {
+        self.ip_conflicts.set(detector)
+    }
+
+    pub fn set_discovery_service(
+        &self,
+        discovery_service: Arc<DiscoveryService>,
+    ) -> Result<(), Arc<DiscoveryService>> {
+        self.discovery_service.set(discovery_service)
+    }
+
+    pub fn set_group_rule_service(
+        &self,
+        group_rule_service: Arc<GroupRuleService>,
+    ) -> Result<(), Arc<GroupRuleService>> {
+        self.group_rule_service.set(group_rule_service)
+    }
+
+    pub fn set_notification_service(
+        &self,
+        notification_service: Arc<NotificationService>,
+    ) -> Result<(), Arc<NotificationService>> {
+        self.notification_service.set(notification_service)
+    }
+
+    /// Add `host` to the groups of the rules it matches, returning whether it changed
+    async fn assign_groups(&self, host: &mut Host) -> Result<bool> {
+        match self.group_rule_service.get() {
+            Some(group_rule_service) => group_rule_service.assign_groups(host).await,
+            None => Ok(false),
         }
     }
 
+    /// Re-run a network's group rules against its hosts, returning how many joined a group
+    pub async fn reevaluate_groups(&self, network_id: &Uuid) -> Result<usize> {
+        let hosts = self
+            .storage
+            .get_all(EntityFilter::unfiltered().network_ids(&[*network_id]))
+            .await?;
+        let mut updated = 0;
+
+        for host in hosts {
+            let lock = self.get_host_lock(&host.id).await;
+            let _guard = lock.lock().await;
+
+            // Re-read under the lock so a concurrent update isn't overwritten
+            let Some(mut host) = self.get_by_id(&host.id).await? else {
+                continue;
+            };
+            if self.assign_groups(&mut host).await? {
+                self.storage.update(&mut host).await?;
+                updated += 1;
+            }
+        }
+
+        Ok(updated)
+    }
+
+    /// The running discovery session of the daemon which discovered `host`, if any
+    async fn discovery_session(&self, host: &Host) -> Option<Uuid> {
+        let EntitySource::Discovery { metadata } = &host.base.source else {
+            return None;
+        };
+        let daemon_id = metadata.first()?.daemon_id?;
+
+        self.discovery_service
+            .get()?
+            .get_sessions_for_daemon(&daemon_id)
+            .await
+            .into_iter()
+            .find(|s| {
+                matches!(
+                    s.phase,
+                    DiscoveryPhase::Starting | DiscoveryPhase::Started | DiscoveryPhase::Scanning
+                )
+            })
+            .map(|s| s.session_id)
+    }
+
+    /// How hosts on `network_id` are recognized as the same host
+    async fn identity_key(&self, network_id: &Uuid) -> Result<HostIdentityKey> {
+        Ok(self
+            .network_storage
+            .get_by_id(network_id)
+            .await?
+            .map(|n| n.base.identity_key)
+            .unwrap_or_default())
+    }
+
+    /// The stored copy of `host` and its services
+    async fn get_stored_host_with_services(
+        &self,
+        host: &Host,
+    ) -> Result<Option<(Host, Vec<Service>)>> {
+        let key = self.identity_key(&host.base.network_id).await?;
+        let filter = EntityFilter::unfiltered().network_ids(&[host.base.network_id]);
+        let Some(existing) = self
+            .storage
+            .get_all(filter)
+            .await?
+            .into_iter()
+            .find(|h| host.same_identity(h, key))
+        else {
+            return Ok(None);
+        };
+
+        let services = self
+            .service_service
+            .get_all(EntityFilter::unfiltered().host_id(&existing.id))
+            .await?;
+
+        Ok(Some((existing, services)))
+    }
+
     async fn get_host_lock(&self, host_id: &Uuid) -> Arc<Mutex<()>> {
         let mut locks = self.host_locks.lock().await;
         locks
@@ -55,9 +217,54 @@ impl HostService {
 
     pub async fn create_host_with_services(
         &self,
-        host: Host,
-        services: Vec<Service>,
+        mut host: Host,
+        mut services: Vec<Service>,
     ) -> Result<(Host, Vec<Service>)> {
+        host.canonicalize();
+
+        // Drop findings a retried or overlapping submission has already reported
+        let is_discovered = host.base.source.discriminant() == EntitySourceDiscriminants::Discovery;
+        let dedup = match self.deduplicator.get() {
+            Some(deduplicator) if is_discovered => {
+                let session_id = self.discovery_session(&host).await;
+                let now = Utc::now();
+
+                let host_findings = Finding::for_host(&host, &services);
+                let service_findings: Vec<Vec<Finding>> = services
+                    .iter()
+                    .map(|s| Finding::for_service(&host, s))
+                    .collect();
+                let findings: Vec<Finding> = host_findings
+                    .iter()
+                    .chain(service_findings.iter().flatten())
+                    .cloned()
+                    .collect();
+
+                if deduplicator.all_duplicates(&findings, session_id, now)
+                    && let Some(stored) = self.get_stored_host_with_services(&host).await?
+                {
+                    tracing::debug!(
+                        host_id = %stored.0.id,
+                        host_name = %stored.0.base.name,
+                        "Ignoring duplicate discovery result"
+                    );
+                    return Ok(stored);
+                }
+
+                let (duplicate, fresh): (Vec<_>, Vec<_>) = services
+                    .into_iter()
+                    .zip(&service_findings)
+                    .partition(|(_, f)| deduplicator.all_duplicates(f, session_id, now));
+                host.base
+                    .services
+                    .retain(|id| !duplicate.iter().any(|(s, _)| &s.id == id));
+                services = fresh.into_iter().map(|(s, _)| s).collect();
+
+                Some((deduplicator, findings, session_id, now))
+            }
+            _ => None,
+        };
+
         // Create host first (handles duplicates via upsert_host)
         let mut created_host = self.create_host(host.clone()).await?;
 
@@ -96,11 +303,17 @@ impl HostService {
             "Created host with services"
         );
 
+        if let Some((deduplicator, findings, session_id, now)) = dedup {
+            deduplicator.record(&findings, session_id, now);
+        }
+
         Ok((host_with_final_services, created_services))
     }
 
     /// Create a new host
-    pub async fn create_host(&self, host: Host) -> Result<Host> {
+    pub async fn create_host(&self, mut host: Host) -> Result<Host> {
+        host.canonicalize();
+
         // Manually created and needs actual UUID
         let host = if host.id == Uuid::nil() {
             Host::new(host.base.clone())
@@ -113,15 +326,21 @@ impl HostService {
 
         tracing::trace!("Creating host {:?}", host);
 
+        let key = self.identity_key(&host.base.network_id).await?;
         let filter = EntityFilter::unfiltered().network_ids(&[host.base.network_id]);
         let all_hosts = self.storage.get_all(filter).await?;
 
-        let host_from_storage = match all_hosts.into_iter().find(|h| host.eq(h)) {
-            // If both are from discovery, or if they have the same ID, upsert data
+        let is_discovered = host.base.source.discriminant() == EntitySourceDiscriminants::Discovery;
+        let reported = is_discovered.then(|| host.base.interfaces.clone());
+
+        let host_from_storage = match all_hosts.into_iter().find(|h| host.same_identity(h, key)) {
+            // If both are from discovery, or if they have the same ID, upsert data. Retired hosts
+            // are always upserted on rediscovery so their history is kept.
             Some(existing_host)
-                if (host.base.source.discriminant() == EntitySourceDiscriminants::Discovery
-                    && existing_host.base.source.discriminant()
-                        == EntitySourceDiscriminants::Discovery)
+                if (is_discovered
+                    && (existing_host.base.source.discriminant()
+                        == EntitySourceDiscriminants::Discovery
+                        || existing_host.base.state == HostState::Retired))
                     || host.id == existing_host.id =>
             {
                 tracing::warn!(
@@ -135,14 +354,137 @@ impl HostService {
                 self.upsert_host(existing_host, host).await?
             }
             _ => {
+                let mut host = host;
+                // Discovered hosts need review before they're considered active
+                if is_discovered {
+                    host.base.state = HostState::Pending;
+                }
+                self.assign_groups(&mut host).await?;
+
                 self.storage.create(&host).await?;
                 tracing::info!("Created host {}: {}", host.base.name, host.id);
+                if let Some(notification_service) = self.notification_service.get() {
+                    notification_service.notify(Notification::new(
+                        host.base.network_id,
+                        NotificationKind::NewHost {
+                            host_id: host.id,
+                            name: host.base.name.clone(),
+                        },
+                    ));
+                }
                 tracing::trace!("Result: {:?}", host);
                 host
             }
         };
 
-        Ok(host_from_storage)
+        // Flagging takes the other hosts' locks too
+        drop(_guard);
+        match reported {
+            Some(reported) => self.flag_ip_conflicts(host_from_storage, &reported).await,
+            None => Ok(host_from_storage),
+        }
+    }
+
+    /// Check the interfaces discovery just reported `host` on for IP conflicts, flagging them on
+    /// `host` and on the other hosts involved and notifying about any not already flagged.
+    /// Conflicts previously flagged on the reported IPs are cleared once they no longer hold.
+    /// Each host is read again under its lock and only its conflicts are written, so edits made
+    /// since `host` was read stand.
+    async fn flag_ip_conflicts(&self, host: Host, reported: &[Interface]) -> Result<Host> {
+        let Some(detector) = self.ip_conflicts.get() else {
+            return Ok(host);
+        };
+
+        let found = detector
+            .observe(host.base.network_id, host.id, reported, Utc::now())
+            .await?;
+        let reported_ips: HashSet<IpAddr> = reported.iter().map(|i| i.base.ip_address).collect();
+        let same = |a: &IpConflict, b: &IpConflict| {
+            a.ip == b.ip && a.other_host_id == b.other_host_id && a.other_mac == b.other_mac
+        };
+
+        let (host, new) = {
+            let lock = self.get_host_lock(&host.id).await;
+            let _guard = lock.lock().await;
+            let Some(mut current) = self.storage.get_by_id(&host.id).await? else {
+                return Ok(host);
+            };
+
+            let previous = &current.base.ip_conflicts;
+            let (ongoing, new): (Vec<IpConflict>, Vec<IpConflict>) = found
+                .into_iter()
+                .partition(|c| previous.iter().any(|p| same(p, c)));
+            // Ongoing conflicts keep their original detection time
+            let mut conflicts: Vec<IpConflict> = previous
+                .iter()
+                .filter(|p| !reported_ips.contains(&p.ip) || ongoing.iter().any(|c| same(p, c)))
+                .cloned()
+                .collect();
+            conflicts.extend(new.iter().cloned());
+
+            if conflicts != current.base.ip_conflicts {
+                self.set_ip_conflicts(&current.id, &conflicts).await?;
+                current.base.ip_conflicts = conflicts;
+            }
+            (current, new)
+        };
+
+        for conflict in new {
+            tracing::warn!(
+                ip = %conflict.ip,
+                host_id = %host.id,
+                other_host_id = %conflict.other_host_id,
+                "IP conflict detected"
+            );
+
+            let lock = self.get_host_lock(&conflict.other_host_id).await;
+            let _guard = lock.lock().await;
+            if conflict.other_host_id != host.id
+                && let Some(mut other) = self.storage.get_by_id(&conflict.other_host_id).await?
+                && !other.base.ip_conflicts.iter().any(|c| {
+                    c.ip == conflict.ip && c.other_host_id == host.id && c.other_mac == conflict.mac
+                })
+            {
+                other.base.ip_conflicts.push(IpConflict {
+                    ip: conflict.ip,
+                    mac: conflict.other_mac,
+                    other_host_id: host.id,
+                    other_mac: conflict.mac,
+                    detected_at: conflict.detected_at,
+                });
+                self.set_ip_conflicts(&other.id, &other.base.ip_conflicts)
+                    .await?;
+            }
+
+            if let Some(notification_service) = self.notification_service.get() {
+                notification_service.notify(Notification::new(
+                    host.base.network_id,
+                    NotificationKind::IpConflict {
+                        ip: conflict.ip,
+                        host_id: host.id,
+                        mac: conflict.mac,
+                        other_host_id: conflict.other_host_id,
+                        other_mac: conflict.other_mac,
+                    },
+                ));
+            }
+        }
+
+        Ok(host)
+    }
+
+    /// Write only `host_id`'s IP conflicts. Callers hold the host's lock.
+    async fn set_ip_conflicts(&self, host_id: &Uuid, conflicts: &[IpConflict]) -> Result<()> {
+        self.storage
+            .update_where(
+                EntityFilter::unfiltered().entity_id(host_id),
+                vec![(
+                    "ip_conflicts",
+                    SqlValue::Json(serde_json::to_value(conflicts)?),
+                )],
+            )
+            .await?;
+        Ok(())
     }
 
     pub async fn update_host(&self, mut host: Host) -> Result<Host, Error> {
@@ -150,12 +492,17 @@ impl HostService {
         let _guard = lock.lock().await;
 
         tracing::trace!("Updating host {:?}", host);
+        host.canonicalize();
 
         let current_host = self
             .get_by_id(&host.id)
             .await?
             .ok_or_else(|| anyhow!("Host '{}' not found", host.id))?;
 
+        // State only changes through set_state and rediscovery, and conflicts through discovery
+        host.base.state = current_host.base.state;
+        host.base.ip_conflicts = current_host.base.ip_conflicts.clone();
+
         self.update_host_services(&current_host, &host).await?;
 
         self.storage.update(&mut host).await?;
@@ -172,6 +519,8 @@ impl HostService {
         let mut port_updates = 0;
         let mut hostname_update = false;
         let mut description_update = false;
+        let is_discovered =
+            new_host_data.base.source.discriminant() == EntitySourceDiscriminants::Discovery;
 
         tracing::trace!(
             "Upserting new host data {:?} to host {:?}",
@@ -199,8 +548,23 @@ impl HostService {
             }
         }
 
-        existing_host.base.services =
-            [existing_host.base.services, new_host_data.base.services].concat();
+        for group_id in new_host_data.base.group_ids {
+            if !existing_host.base.group_ids.contains(&group_id) {
+                existing_host.base.group_ids.push(group_id);
+            }
+        }
+
+        for tag in new_host_data.base.tags {
+            if !existing_host.base.tags.contains(&tag) {
+                existing_host.base.tags.push(tag);
+            }
+        }
+
+        for service_id in new_host_data.base.services {
+            if !existing_host.base.services.contains(&service_id) {
+                existing_host.base.services.push(service_id);
+            }
+        }
 
         // Update other fields if they have more information
         if existing_host.base.hostname.is_none() && new_host_data.base.hostname.is_some() {
@@ -244,6 +608,20 @@ impl HostService {
             (existing_source, _) => existing_source,
         };
 
+        // A retired host showing up again may be a returned device, so flag it for review rather
+        // than silently reactivating it
+        if existing_host.base.state == HostState::Retired && is_discovered {
+            tracing::warn!(
+                host_id = %existing_host.id,
+                host_name = %existing_host.base.name,
+                "Retired host was rediscovered, moving back to pending"
+            );
+            existing_host.base.state = HostState::Pending;
+        }
+
+        // Ports or interfaces found this time may match rules the host didn't before
+        self.assign_groups(&mut existing_host).await?;
+
         // Update the existing host
         self.storage.update(&mut existing_host).await?;
         let mut data = Vec::new();
@@ -280,6 +658,43 @@ impl HostService {
         Ok(existing_host)
     }
 
+    /// Move a host to a new lifecycle state, e.g. promoting a discovered host to active
+    pub async fn set_state(&self, id: &Uuid, state: HostState) -> Result<Host> {
+        let lock = self.get_host_lock(id).await;
+        let _guard = lock.lock().await;
+
+        let mut host = self
+            .get_by_id(id)
+            .await?
+            .ok_or_else(|| anyhow!("Host '{}' not found", id))?;
+
+        if host.base.state == state {
+            return Ok(host);
+        }
+
+        if !host.base.state.can_transition_to(state) {
+            return Err(anyhow!(
+                "Can't move host from {:?} to {:?}",
+                host.base.state,
+                state
+            ));
+        }
+
+        let previous = host.base.state;
+        host.base.state = state;
+        self.storage.update(&mut host).await?;
+
+        tracing::info!(
+            host_id = %host.id,
+            host_name = %host.base.name,
+            from = ?previous,
+            to = ?state,
+            "Host state changed"
+        );
+
+        Ok(host)
+    }
+
     pub async fn consolidate_hosts(
         &self,
         destination_host: Host,
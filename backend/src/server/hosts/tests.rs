@@ -1,10 +1,22 @@
+use mac_address::MacAddress;
 use serial_test::serial;
+use std::{net::IpAddr, sync::Arc};
+use uuid::Uuid;
 
 use crate::{
     server::{
-        services::r#impl::bindings::Binding,
+        config::ServerConfig,
+        hosts::{
+            r#impl::{
+                base::{Host, HostState},
+                identity::HostIdentityKey,
+            },
+            service::HostService,
+        },
+        notifications::r#impl::base::NotificationKind,
+        services::r#impl::{bindings::Binding, patterns::MatchDetails},
         shared::{
-            services::traits::CrudService,
+            services::{factory::ServiceFactory, traits::CrudService},
             storage::{filter::EntityFilter, traits::Storage},
             types::entities::{DiscoveryMetadata, EntitySource},
         },
@@ -199,3 +211,399 @@ async fn test_host_consolidation() {
 
     assert_eq!(svc_after.base.host_id, consolidated.id);
 }
+
+#[tokio::test]
+#[serial]
+async fn test_host_state_transitions() {
+    let (_, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+
+    // Discovery creates hosts as pending, whatever state they arrive with
+    let mut discovered = host(&network.id);
+    discovered.base.source = EntitySource::Discovery {
+        metadata: vec![DiscoveryMetadata::default()],
+    };
+    let created = services.host_service.create_host(discovered).await.unwrap();
+    assert_eq!(created.base.state, HostState::Pending);
+
+    let host_service = &services.host_service;
+
+    let promoted = host_service
+        .set_state(&created.id, HostState::Active)
+        .await
+        .unwrap();
+    assert_eq!(promoted.base.state, HostState::Active);
+
+    assert!(
+        host_service
+            .set_state(&created.id, HostState::Pending)
+            .await
+            .is_err()
+    );
+
+    // Regular updates can't change state
+    let mut edited = promoted.clone();
+    edited.base.state = HostState::Retired;
+    let edited = host_service.update_host(edited).await.unwrap();
+    assert_eq!(edited.base.state, HostState::Active);
+
+    host_service
+        .set_state(&created.id, HostState::Retired)
+        .await
+        .unwrap();
+
+    // Retired hosts are excluded from default lists but kept
+    let filter = EntityFilter::unfiltered().network_ids(&[network.id]);
+    let listed = host_service
+        .get_all(filter.clone().exclude_host_state(HostState::Retired))
+        .await
+        .unwrap();
+    assert!(listed.iter().all(|h| h.id != created.id));
+
+    let all = host_service.get_all(filter).await.unwrap();
+    assert!(all.iter().any(|h| h.id == created.id));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_retired_host_rediscovered_returns_to_pending() {
+    let (storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+
+    // Manually added host, later retired
+    let manual = services
+        .host_service
+        .create_host(host(&network.id))
+        .await
+        .unwrap();
+    assert_eq!(manual.base.state, HostState::Active);
+    services
+        .host_service
+        .set_state(&manual.id, HostState::Retired)
+        .await
+        .unwrap();
+
+    // A daemon finds the same device again
+    let mut rediscovered = host(&network.id);
+    rediscovered.base.source = EntitySource::Discovery {
+        metadata: vec![DiscoveryMetadata::default()],
+    };
+    let (resurfaced, _) = services
+        .host_service
+        .create_host_with_services(rediscovered, vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(resurfaced.id, manual.id);
+    assert_eq!(resurfaced.base.state, HostState::Pending);
+
+    let hosts = storage
+        .hosts
+        .get_all(EntityFilter::unfiltered().network_ids(&[network.id]))
+        .await
+        .unwrap();
+    assert_eq!(hosts.iter().filter(|h| h.id == manual.id).count(), 1);
+    assert_eq!(
+        hosts.iter().find(|h| h.id == manual.id).unwrap().base.state,
+        HostState::Pending
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_resubmitted_discovery_result_stored_once() {
+    let (storage, _container) = test_storage().await;
+    let config = ServerConfig {
+        discovery_dedup_window_secs: 300,
+        ..ServerConfig::default()
+    };
+    let services = ServiceFactory::new(&storage, Some(config)).await.unwrap();
+    // Only deduplicates within a session, and there's no session here
+    let undeduplicated = ServiceFactory::new(&storage, None).await.unwrap();
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let subnet = services
+        .subnet_service
+        .create(subnet(&network.id))
+        .await
+        .unwrap();
+
+    let mut discovered = host(&network.id);
+    discovered.base.interfaces = vec![interface(&subnet.id)];
+    discovered.base.source = EntitySource::Discovery {
+        metadata: vec![DiscoveryMetadata::default()],
+    };
+    let mut ssh = service(&network.id, &discovered.id);
+    ssh.base.bindings = vec![Binding::new_port(
+        discovered.base.ports[0].id,
+        Some(discovered.base.interfaces[0].id),
+    )];
+    ssh.base.source = EntitySource::DiscoveryWithMatch {
+        metadata: vec![DiscoveryMetadata::default()],
+        details: MatchDetails::new_certain("Test"),
+    };
+    discovered.base.services = vec![ssh.id];
+
+    let submit = |host_service: Arc<HostService>| {
+        let (host, service) = (discovered.clone(), ssh.clone());
+        async move {
+            host_service
+                .create_host_with_services(host, vec![service])
+                .await
+        }
+    };
+
+    let (first, first_services) = submit(services.host_service.clone()).await.unwrap();
+    // A retry of the same submission
+    let (second, second_services) = submit(services.host_service.clone()).await.unwrap();
+    assert_eq!(second.id, first.id);
+    assert_eq!(
+        second_services.iter().map(|s| s.id).collect::<Vec<_>>(),
+        first_services.iter().map(|s| s.id).collect::<Vec<_>>()
+    );
+
+    let network_filter = EntityFilter::unfiltered().network_ids(&[network.id]);
+    let hosts = storage.hosts.get_all(network_filter.clone()).await.unwrap();
+    assert_eq!(hosts.len(), 1);
+    assert_eq!(hosts[0].base.services, vec![first_services[0].id]);
+    let metadata_len = |host: &Host| match &host.base.source {
+        EntitySource::Discovery { metadata } => metadata.len(),
+        _ => 0,
+    };
+    assert_eq!(metadata_len(&hosts[0]), 1);
+    assert_eq!(
+        storage
+            .services
+            .get_all(network_filter.clone())
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // Outside the window the same finding is a re-observation and is recorded
+    submit(undeduplicated.host_service.clone()).await.unwrap();
+    let hosts = storage.hosts.get_all(network_filter.clone()).await.unwrap();
+    assert_eq!(hosts.len(), 1);
+    assert_eq!(hosts[0].base.services.len(), 1);
+    assert_eq!(metadata_len(&hosts[0]), 2);
+    assert_eq!(
+        storage
+            .services
+            .get_all(network_filter)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_differently_formatted_identities_are_one_host() {
+    let (storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let mut by_hostname = network(&organization.id);
+    by_hostname.base.identity_key = HostIdentityKey::Hostname;
+    let by_hostname = services.network_service.create(by_hostname).await.unwrap();
+    let by_mac = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    assert_eq!(
+        storage
+            .networks
+            .get_by_id(&by_hostname.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .base
+            .identity_key,
+        HostIdentityKey::Hostname
+    );
+
+    let subnet_id = Uuid::new_v4();
+    let discovered = |network_id, hostname: &str, ip: &str, mac: Option<[u8; 6]>| {
+        let mut host = host(&network_id);
+        host.base.hostname = Some(hostname.to_string());
+        host.base.interfaces[0].base.subnet_id = subnet_id;
+        host.base.interfaces[0].base.ip_address = ip.parse().unwrap();
+        host.base.interfaces[0].base.mac_address = mac.map(MacAddress::new);
+        host.base.source = EntitySource::Discovery {
+            metadata: vec![DiscoveryMetadata::default()],
+        };
+        host
+    };
+    let create = |host: Host| {
+        let host_service = services.host_service.clone();
+        async move { host_service.create_host(host).await.unwrap() }
+    };
+
+    // Keyed by hostname, case and a trailing dot don't make a new host, even at another address
+    let first = create(discovered(by_hostname.id, "Host-01", "10.0.0.1", None)).await;
+    let second = create(discovered(by_hostname.id, "host-01.", "10.0.0.2", None)).await;
+    assert_eq!(first.id, second.id);
+    assert_eq!(second.base.hostname.as_deref(), Some("host-01"));
+    assert_eq!(second.base.interfaces.len(), 2);
+
+    // Keyed by MAC, padded and compressed IPv6 are the same address
+    let first = create(discovered(by_mac.id, "a", "2001:0db8::0001", None)).await;
+    let second = create(discovered(by_mac.id, "b", "2001:db8::1", None)).await;
+    assert_eq!(first.id, second.id);
+    assert_eq!(second.base.interfaces.len(), 1);
+
+    // As are IPv4 and IPv4-mapped IPv6 reports of one address
+    let mac = Some([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+    let first = create(discovered(by_mac.id, "c", "192.0.2.1", mac)).await;
+    let second = create(discovered(by_mac.id, "d", "::ffff:192.0.2.1", None)).await;
+    assert_eq!(first.id, second.id);
+    assert_eq!(
+        second.base.interfaces[0].base.ip_address.to_string(),
+        "192.0.2.1"
+    );
+
+    for (network_id, expected) in [(by_hostname.id, 1), (by_mac.id, 2)] {
+        let filter = EntityFilter::unfiltered().network_ids(&[network_id]);
+        assert_eq!(storage.hosts.get_all(filter).await.unwrap().len(), expected);
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_two_macs_on_one_ip_flag_both_hosts() {
+    let (storage, services, _container) = test_services().await;
+    let mut notifications = services.notification_service.subscribe();
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    // Identify by hostname so the two devices stay separate hosts
+    let mut network = network(&organization.id);
+    network.base.identity_key = HostIdentityKey::Hostname;
+    let network = services.network_service.create(network).await.unwrap();
+
+    let discovered = |hostname: &str, ip: [u8; 4], mac: [u8; 6]| {
+        let mut host = host(&network.id);
+        host.base.hostname = Some(hostname.to_string());
+        host.base.interfaces[0].base.ip_address = IpAddr::from(ip);
+        host.base.interfaces[0].base.mac_address = Some(MacAddress::new(mac));
+        host.base.source = EntitySource::Discovery {
+            metadata: vec![DiscoveryMetadata::default()],
+        };
+        host
+    };
+    let (mac_a, mac_b) = ([2, 0, 0, 0, 0, 1], [2, 0, 0, 0, 0, 2]);
+
+    services
+        .host_service
+        .create_host(discovered("a.lan", [10, 0, 0, 5], mac_a))
+        .await
+        .unwrap();
+    // The same device moving to another address is DHCP churn, not a conflict
+    let a = services
+        .host_service
+        .create_host(discovered("a.lan", [10, 0, 0, 6], mac_a))
+        .await
+        .unwrap();
+    assert!(a.base.ip_conflicts.is_empty());
+
+    let b = services
+        .host_service
+        .create_host(discovered("b.lan", [10, 0, 0, 6], mac_b))
+        .await
+        .unwrap();
+    assert_eq!(b.base.ip_conflicts.len(), 1);
+    assert_eq!(b.base.ip_conflicts[0].other_host_id, a.id);
+
+    let a = storage.hosts.get_by_id(&a.id).await.unwrap().unwrap();
+    assert_eq!(a.base.ip_conflicts.len(), 1);
+    assert_eq!(a.base.ip_conflicts[0].other_host_id, b.id);
+    assert_eq!(a.base.ip_conflicts[0].mac, MacAddress::new(mac_a));
+
+    let conflicts: Vec<_> = std::iter::from_fn(|| notifications.try_recv().ok())
+        .filter(|n| matches!(n.kind, NotificationKind::IpConflict { .. }))
+        .collect();
+    assert_eq!(conflicts.len(), 1);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_ip_conflicts_found_across_server_instances() {
+    let (storage, services, _container) = test_services().await;
+    // A restarted server, or another one behind the same database
+    let restarted = ServiceFactory::new(&storage, None).await.unwrap();
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let mut network = network(&organization.id);
+    network.base.identity_key = HostIdentityKey::Hostname;
+    let network = services.network_service.create(network).await.unwrap();
+
+    let discovered = |hostname: &str, mac: [u8; 6]| {
+        let mut host = host(&network.id);
+        host.base.hostname = Some(hostname.to_string());
+        host.base.interfaces[0].base.ip_address = IpAddr::from([10, 0, 0, 7]);
+        host.base.interfaces[0].base.mac_address = Some(MacAddress::new(mac));
+        host.base.source = EntitySource::Discovery {
+            metadata: vec![DiscoveryMetadata::default()],
+        };
+        host
+    };
+
+    let a = services
+        .host_service
+        .create_host(discovered("a.lan", [2, 0, 0, 0, 0, 1]))
+        .await
+        .unwrap();
+    let b = restarted
+        .host_service
+        .create_host(discovered("b.lan", [2, 0, 0, 0, 0, 2]))
+        .await
+        .unwrap();
+
+    assert_eq!(b.base.ip_conflicts.len(), 1);
+    assert_eq!(b.base.ip_conflicts[0].other_host_id, a.id);
+    let a = storage.hosts.get_by_id(&a.id).await.unwrap().unwrap();
+    assert_eq!(a.base.ip_conflicts.len(), 1);
+    assert_eq!(a.base.ip_conflicts[0].other_host_id, b.id);
+}
@@ -1,15 +1,26 @@
 pub mod api_keys;
+pub mod audit;
 pub mod auth;
 pub mod billing;
+pub mod bootstrap_tokens;
+pub mod check_dependencies;
+pub mod check_suites;
 pub mod config;
 pub mod daemons;
+pub mod diagnostics;
 pub mod discovery;
+pub mod discovery_webhooks;
 pub mod email;
 pub mod github;
+pub mod group_rules;
 pub mod groups;
 pub mod hosts;
 pub mod networks;
+pub mod notification_channels;
+pub mod notification_subscriptions;
+pub mod notifications;
 pub mod organizations;
+pub mod secrets;
 pub mod services;
 pub mod shared;
 pub mod subnets;
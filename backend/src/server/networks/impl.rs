@@ -1,6 +1,10 @@
 use std::fmt::Display;
 
-use crate::server::{networks::service::NetworkService, shared::handlers::traits::CrudHandlers};
+use crate::server::{
+    hosts::r#impl::identity::HostIdentityKey,
+    networks::service::NetworkService,
+    shared::handlers::traits::{CrudHandlers, EntityScope},
+};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::Row;
@@ -16,6 +20,9 @@ pub struct NetworkBase {
     pub name: String,
     pub is_default: bool,
     pub organization_id: Uuid,
+    /// How hosts on this network are recognized as the same host when reported again
+    #[serde(default)]
+    pub identity_key: HostIdentityKey,
 }
 
 impl NetworkBase {
@@ -24,6 +31,7 @@ impl NetworkBase {
             name: "My Network".to_string(),
             is_default: false,
             organization_id,
+            identity_key: HostIdentityKey::default(),
         }
     }
 }
@@ -49,6 +57,10 @@ impl CrudHandlers for Network {
     fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
         &state.services.network_service
     }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.id)
+    }
 }
 
 impl StorableEntity for Network {
@@ -98,6 +110,7 @@ impl StorableEntity for Network {
                     name,
                     organization_id,
                     is_default,
+                    identity_key,
                 },
         } = self.clone();
 
@@ -109,6 +122,7 @@ impl StorableEntity for Network {
                 "name",
                 "organization_id",
                 "is_default",
+                "identity_key",
             ],
             vec![
                 SqlValue::Uuid(id),
@@ -117,6 +131,7 @@ impl StorableEntity for Network {
                 SqlValue::String(name),
                 SqlValue::Uuid(organization_id),
                 SqlValue::Bool(is_default),
+                SqlValue::String(identity_key.to_string()),
             ],
         ))
     }
@@ -130,6 +145,7 @@ impl StorableEntity for Network {
                 name: row.get("name"),
                 organization_id: row.get("organization_id"),
                 is_default: row.get("is_default"),
+                identity_key: row.get::<String, _>("identity_key").parse()?,
             },
         })
     }
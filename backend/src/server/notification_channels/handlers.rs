@@ -0,0 +1,85 @@
+use crate::server::{
+    auth::middleware::RequireAdmin,
+    config::AppState,
+    notification_channels::r#impl::base::NotificationChannel,
+    shared::{
+        handlers::traits::{CrudHandlers, admin_delete_handler, get_all_handler},
+        services::traits::CrudService,
+        types::api::{ApiError, ApiResponse, ApiResult},
+    },
+};
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{delete, get, post, put},
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_all_handler::<NotificationChannel>))
+        .route("/", post(create_handler))
+        .route("/{id}", put(update_handler))
+        .route("/{id}", delete(admin_delete_handler::<NotificationChannel>))
+}
+
+async fn create_handler(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Json(channel): Json<NotificationChannel>,
+) -> ApiResult<Json<ApiResponse<NotificationChannel>>> {
+    channel.validate().map_err(|e| {
+        ApiError::bad_request(&format!("Notification channel validation failed: {}", e))
+    })?;
+
+    if !user.network_ids.contains(&channel.base.network_id) {
+        return Err(ApiError::forbidden("You don't have access to this network"));
+    }
+
+    let created = NotificationChannel::get_service(&state)
+        .create(channel)
+        .await?;
+
+    tracing::info!(
+        notification_channel_id = %created.id,
+        user_id = %user.user_id,
+        "Notification channel created via API"
+    );
+
+    Ok(Json(ApiResponse::success(created)))
+}
+
+async fn update_handler(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Path(id): Path<Uuid>,
+    Json(mut channel): Json<NotificationChannel>,
+) -> ApiResult<Json<ApiResponse<NotificationChannel>>> {
+    channel.validate().map_err(|e| {
+        ApiError::bad_request(&format!("Notification channel validation failed: {}", e))
+    })?;
+
+    let service = NotificationChannel::get_service(&state);
+
+    let existing = service
+        .get_by_id(&id)
+        .await?
+        .filter(|existing| user.network_ids.contains(&existing.base.network_id))
+        .ok_or_else(|| ApiError::not_found(format!("Notification channel '{}' not found", id)))?;
+    if !user.network_ids.contains(&channel.base.network_id) {
+        return Err(ApiError::forbidden("You don't have access to this network"));
+    }
+
+    channel.id = id;
+    channel.created_at = existing.created_at;
+    let updated = service.update(&mut channel).await?;
+
+    tracing::info!(
+        notification_channel_id = %updated.id,
+        user_id = %user.user_id,
+        "Notification channel updated via API"
+    );
+
+    Ok(Json(ApiResponse::success(updated)))
+}
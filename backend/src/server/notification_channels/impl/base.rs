@@ -0,0 +1,66 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use email_address::EmailAddress;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A destination notifications can be delivered to, which users pick when subscribing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationChannelBase {
+    pub name: String,
+    pub network_id: Uuid,
+    pub target: ChannelTarget,
+    /// Disabled channels are skipped when delivering
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationChannel {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub base: NotificationChannelBase,
+}
+
+impl Display for NotificationChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Notification channel {}: {}", self.base.name, self.id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChannelTarget {
+    Email {
+        address: EmailAddress,
+    },
+    /// The notification is POSTed as JSON
+    Webhook {
+        url: String,
+    },
+}
+
+impl NotificationChannelBase {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Channel name can't be empty".to_string());
+        }
+
+        if let ChannelTarget::Webhook { url } = &self.target {
+            let parsed =
+                reqwest::Url::parse(url).map_err(|e| format!("Invalid webhook URL: {}", e))?;
+            if !matches!(parsed.scheme(), "http" | "https") {
+                return Err("Webhook URLs must be http or https".to_string());
+            }
+        }
+
+        Ok(())
+    }
+}
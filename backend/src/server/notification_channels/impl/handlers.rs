@@ -0,0 +1,19 @@
+use crate::server::notification_channels::r#impl::base::NotificationChannel;
+use crate::server::notification_channels::service::NotificationChannelService;
+use crate::server::shared::handlers::traits::{CrudHandlers, EntityScope};
+
+impl CrudHandlers for NotificationChannel {
+    type Service = NotificationChannelService;
+
+    fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
+        &state.services.notification_channel_service
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.base.validate()
+    }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.base.network_id)
+    }
+}
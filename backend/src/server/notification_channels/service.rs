@@ -0,0 +1,24 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::server::{
+    notification_channels::r#impl::base::NotificationChannel,
+    shared::{services::traits::CrudService, storage::generic::GenericPostgresStorage},
+};
+
+pub struct NotificationChannelService {
+    storage: Arc<GenericPostgresStorage<NotificationChannel>>,
+}
+
+#[async_trait]
+impl CrudService<NotificationChannel> for NotificationChannelService {
+    fn storage(&self) -> &Arc<GenericPostgresStorage<NotificationChannel>> {
+        &self.storage
+    }
+}
+
+impl NotificationChannelService {
+    pub fn new(storage: Arc<GenericPostgresStorage<NotificationChannel>>) -> Self {
+        Self { storage }
+    }
+}
@@ -0,0 +1,100 @@
+use crate::server::{
+    auth::middleware::AuthenticatedUser,
+    config::AppState,
+    notification_subscriptions::r#impl::base::NotificationSubscription,
+    shared::{
+        handlers::traits::CrudHandlers,
+        services::traits::CrudService,
+        types::api::{ApiError, ApiResponse, ApiResult},
+    },
+};
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{delete, get, post},
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_all_handler))
+        .route("/", post(create_handler))
+        .route("/{id}", delete(delete_handler))
+}
+
+/// The calling user's own subscriptions
+async fn get_all_handler(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+) -> ApiResult<Json<ApiResponse<Vec<NotificationSubscription>>>> {
+    let subscriptions = NotificationSubscription::get_service(&state)
+        .subscriptions_for_user(&user.user_id)
+        .await?;
+
+    Ok(Json(ApiResponse::success(subscriptions)))
+}
+
+/// Subscribe the calling user to an event type on channels in their networks
+async fn create_handler(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+    Json(mut subscription): Json<NotificationSubscription>,
+) -> ApiResult<Json<ApiResponse<NotificationSubscription>>> {
+    subscription.validate().map_err(|e| {
+        ApiError::bad_request(&format!(
+            "Notification subscription validation failed: {}",
+            e
+        ))
+    })?;
+    subscription.base.user_id = user.user_id;
+
+    for channel_id in &subscription.base.channel_ids {
+        match state
+            .services
+            .notification_channel_service
+            .get_by_id(channel_id)
+            .await?
+        {
+            Some(channel) if user.network_ids.contains(&channel.base.network_id) => {}
+            _ => {
+                return Err(ApiError::bad_request(&format!(
+                    "Notification channel {} does not exist",
+                    channel_id
+                )));
+            }
+        }
+    }
+
+    let created = NotificationSubscription::get_service(&state)
+        .create(subscription)
+        .await?;
+
+    tracing::info!(
+        notification_subscription_id = %created.id,
+        user_id = %user.user_id,
+        event = %created.base.event,
+        "Notification subscription created via API"
+    );
+
+    Ok(Json(ApiResponse::success(created)))
+}
+
+async fn delete_handler(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<ApiResponse<()>>> {
+    let service = NotificationSubscription::get_service(&state);
+
+    service
+        .get_by_id(&id)
+        .await?
+        .filter(|subscription| subscription.base.user_id == user.user_id)
+        .ok_or_else(|| {
+            ApiError::not_found(format!("Notification subscription '{}' not found", id))
+        })?;
+    service.delete(&id).await?;
+
+    Ok(Json(ApiResponse::success(())))
+}
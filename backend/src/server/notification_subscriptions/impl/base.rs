@@ -0,0 +1,164 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Duration, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::server::notifications::r#impl::base::NotificationEvent;
+
+/// A user's request to be told about an event type on the channels they picked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationSubscriptionBase {
+    /// Set from the authenticated user, whatever the request says
+    #[serde(default)]
+    pub user_id: Uuid,
+    pub event: NotificationEvent,
+    pub channel_ids: Vec<Uuid>,
+    /// Window during which non-critical notifications are held back
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+}
+
+/// A daily window, in the user's time zone, during which non-critical notifications aren't sent
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QuietHours {
+    /// Local time the window opens
+    pub start: NaiveTime,
+    /// Local time the window closes. Earlier than `start` for windows spanning midnight.
+    pub end: NaiveTime,
+    /// IANA time zone of `start` and `end`, e.g. `Europe/London`
+    pub timezone: Tz,
+    #[serde(default)]
+    pub mode: QuietHoursMode,
+}
+
+/// What happens to notifications fired during quiet hours
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QuietHoursMode {
+    /// Deliver them when the window closes
+    #[default]
+    Defer,
+    /// Drop them
+    Suppress,
+}
+
+impl QuietHours {
+    /// When the window `at` falls in closes, or None when `at` is outside quiet hours
+    pub fn ends_after(&self, at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let local = at.with_timezone(&self.timezone);
+        let (date, time) = (local.date_naive(), local.time());
+
+        let end_date = if self.start < self.end {
+            if time < self.start || time >= self.end {
+                return None;
+            }
+            date
+        } else if time >= self.start {
+            // Spans midnight, so closes tomorrow
+            date.succ_opt()?
+        } else if time < self.end {
+            date
+        } else {
+            return None;
+        };
+
+        Some(self.resolve(end_date.and_time(self.end)))
+    }
+
+    /// The instant local time `local` is reached. A time skipped by a DST change is reached at
+    /// the end of the gap; a time that happens twice, the first time.
+    fn resolve(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        let mut candidate = local;
+        for _ in 0..24 * 60 {
+            if let Some(instant) = self.timezone.from_local_datetime(&candidate).earliest() {
+                return instant.with_timezone(&Utc);
+            }
+            candidate += Duration::minutes(1);
+        }
+
+        local.and_utc()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationSubscription {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub base: NotificationSubscriptionBase,
+}
+
+impl Display for NotificationSubscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Notification subscription to {} for user {}: {}",
+            self.base.event, self.base.user_id, self.id
+        )
+    }
+}
+
+impl NotificationSubscriptionBase {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.channel_ids.is_empty() {
+            return Err("Subscriptions need at least one channel".to_string());
+        }
+        if self
+            .quiet_hours
+            .as_ref()
+            .is_some_and(|quiet_hours| quiet_hours.start == quiet_hours.end)
+        {
+            return Err("Quiet hours can't start and end at the same time".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overnight(timezone: Tz) -> QuietHours {
+        QuietHours {
+            start: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+            timezone,
+            mode: QuietHoursMode::Defer,
+        }
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_quiet_hours_follow_local_time_across_dst() {
+        let quiet_hours = overnight(chrono_tz::America::New_York);
+
+        // 23:00 EST, closing at 07:00 the next morning, which is after clocks go forward
+        assert_eq!(
+            quiet_hours.ends_after(utc("2025-03-09T04:00:00Z")),
+            Some(utc("2025-03-09T11:00:00Z"))
+        );
+        // 06:30 EDT is still quiet; 07:30 EDT, or 11:30 UTC, isn't
+        assert_eq!(
+            quiet_hours.ends_after(utc("2025-03-09T10:30:00Z")),
+            Some(utc("2025-03-09T11:00:00Z"))
+        );
+        assert_eq!(quiet_hours.ends_after(utc("2025-03-09T11:30:00Z")), None);
+        // 21:59 EDT is before the window opens
+        assert_eq!(quiet_hours.ends_after(utc("2025-06-02T01:59:00Z")), None);
+
+        // Clocks go from 02:00 to 03:00, so a window closing at 02:30 closes at 03:00
+        let mut skipped = quiet_hours.clone();
+        skipped.end = NaiveTime::from_hms_opt(2, 30, 0).unwrap();
+        assert_eq!(
+            skipped.ends_after(utc("2025-03-09T05:00:00Z")),
+            Some(utc("2025-03-09T07:00:00Z"))
+        );
+    }
+}
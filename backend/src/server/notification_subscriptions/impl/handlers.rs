@@ -0,0 +1,19 @@
+use crate::server::notification_subscriptions::r#impl::base::NotificationSubscription;
+use crate::server::notification_subscriptions::service::NotificationSubscriptionService;
+use crate::server::shared::handlers::traits::{CrudHandlers, EntityScope};
+
+impl CrudHandlers for NotificationSubscription {
+    type Service = NotificationSubscriptionService;
+
+    fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
+        &state.services.notification_subscription_service
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.base.validate()
+    }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::User(self.base.user_id)
+    }
+}
@@ -0,0 +1,114 @@
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+use uuid::Uuid;
+
+use crate::server::{
+    notification_subscriptions::r#impl::base::{
+        NotificationSubscription, NotificationSubscriptionBase,
+    },
+    shared::storage::traits::{SqlValue, StorableEntity},
+};
+
+impl StorableEntity for NotificationSubscription {
+    type BaseData = NotificationSubscriptionBase;
+
+    fn table_name() -> &'static str {
+        "notification_subscriptions"
+    }
+
+    fn get_base(&self) -> Self::BaseData {
+        self.base.clone()
+    }
+
+    fn new(base: Self::BaseData) -> Self {
+        let now = chrono::Utc::now();
+
+        Self {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: now,
+            base,
+        }
+    }
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn set_updated_at(&mut self, time: DateTime<Utc>) {
+        self.updated_at = time;
+    }
+
+    fn to_params(&self) -> Result<(Vec<&'static str>, Vec<SqlValue>), anyhow::Error> {
+        let Self {
+            id,
+            created_at,
+            updated_at,
+            base:
+                Self::BaseData {
+                    user_id,
+                    event,
+                    channel_ids,
+                    quiet_hours,
+                },
+        } = self.clone();
+
+        Ok((
+            vec![
+                "id",
+                "created_at",
+                "updated_at",
+                "user_id",
+                "event",
+                "channel_ids",
+                "quiet_hours",
+            ],
+            vec![
+                SqlValue::Uuid(id),
+                SqlValue::Timestamp(created_at),
+                SqlValue::Timestamp(updated_at),
+                SqlValue::Uuid(user_id),
+                SqlValue::String(event.to_string()),
+                SqlValue::UuidArray(channel_ids),
+                SqlValue::Json(serde_json::to_value(quiet_hours)?),
+            ],
+        ))
+    }
+
+    fn from_row(row: &PgRow) -> Result<Self, anyhow::Error> {
+        let event = row
+            .get::<String, _>("event")
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Failed to parse notification event: {}", e))?;
+        let channel_ids: Vec<Uuid> =
+            serde_json::from_value(row.get::<serde_json::Value, _>("channel_ids"))
+                .map_err(|e| anyhow::anyhow!("Failed to deserialize channel_ids: {}", e))?;
+        let quiet_hours = row
+            .get::<Option<serde_json::Value>, _>("quiet_hours")
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("Failed to deserialize quiet_hours: {}", e))?
+            .flatten();
+
+        Ok(NotificationSubscription {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            base: NotificationSubscriptionBase {
+                user_id: row.get("user_id"),
+                event,
+                channel_ids,
+                quiet_hours,
+            },
+        })
+    }
+}
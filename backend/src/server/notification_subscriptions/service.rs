@@ -0,0 +1,54 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::server::{
+    notification_subscriptions::r#impl::base::NotificationSubscription,
+    notifications::r#impl::base::NotificationEvent,
+    shared::{
+        services::traits::CrudService,
+        storage::{filter::EntityFilter, generic::GenericPostgresStorage, traits::Storage},
+    },
+};
+
+pub struct NotificationSubscriptionService {
+    storage: Arc<GenericPostgresStorage<NotificationSubscription>>,
+}
+
+#[async_trait]
+impl CrudService<NotificationSubscription> for NotificationSubscriptionService {
+    fn storage(&self) -> &Arc<GenericPostgresStorage<NotificationSubscription>> {
+        &self.storage
+    }
+}
+
+impl NotificationSubscriptionService {
+    pub fn new(storage: Arc<GenericPostgresStorage<NotificationSubscription>>) -> Self {
+        Self { storage }
+    }
+
+    pub async fn subscriptions_for_user(
+        &self,
+        user_id: &Uuid,
+    ) -> Result<Vec<NotificationSubscription>> {
+        self.storage
+            .get_all(EntityFilter::unfiltered().user_id(user_id))
+            .await
+    }
+
+    /// Subscriptions to `event` of the enabled users in `organization_id`
+    pub async fn subscriptions_for_event(
+        &self,
+        event: NotificationEvent,
+        organization_id: &Uuid,
+    ) -> Result<Vec<NotificationSubscription>> {
+        self.storage
+            .get_all(
+                EntityFilter::unfiltered()
+                    .notification_event(event)
+                    .enabled_user_in_organization(organization_id),
+            )
+            .await
+    }
+}
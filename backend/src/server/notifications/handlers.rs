@@ -0,0 +1,58 @@
+use crate::server::{auth::middleware::AuthenticatedUser, config::AppState};
+use axum::{
+    Router,
+    extract::State,
+    response::{
+        Sse,
+        sse::{Event, KeepAlive},
+    },
+    routing::get,
+};
+use futures::Stream;
+use std::{convert::Infallible, sync::Arc};
+use tokio::sync::broadcast;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+    Router::new().route("/stream", get(notification_stream))
+}
+
+/// Live notifications for the user's networks, limited to the event types they subscribe to
+async fn notification_stream(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let mut rx = state.services.notification_service.subscribe();
+    let subscription_service = state.services.notification_subscription_service.clone();
+
+    let stream = async_stream::stream! {
+        loop {
+            match rx.recv().await {
+                Ok(notification) if user.network_ids.contains(&notification.network_id) => {
+                    let subscribed = subscription_service
+                        .subscriptions_for_user(&user.user_id)
+                        .await
+                        .map(|subscriptions| {
+                            subscriptions
+                                .iter()
+                                .any(|s| s.base.event == notification.kind.event())
+                        })
+                        .unwrap_or(false);
+                    if !subscribed {
+                        continue;
+                    }
+
+                    let json = serde_json::to_string(&notification).unwrap_or_default();
+                    yield Ok(Event::default().data(json));
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::warn!("Notification stream lagged by {} messages", n);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
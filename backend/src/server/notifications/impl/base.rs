@@ -0,0 +1,199 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use mac_address::MacAddress;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, net::IpAddr};
+use strum::{Display as StrumDisplay, EnumString};
+use uuid::Uuid;
+
+use crate::server::diagnostics::r#impl::base::DiagnosticKind;
+
+/// What a notification is about
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// A monitored certificate crossed one of the expiry warning thresholds
+    CertificateExpiring {
+        target: String,
+        threshold_days: i64,
+        days_left: i64,
+        expires_at: DateTime<Utc>,
+    },
+    /// A monitored certificate couldn't be checked, so its expiry is unknown
+    CertificateCheckFailed { target: String, error: String },
+    /// A check that was passing started failing
+    CheckFailed {
+        target: String,
+        check: DiagnosticKind,
+        error: String,
+    },
+    /// A daemon stopped sending heartbeats
+    DaemonOffline {
+        daemon_id: Uuid,
+        last_seen: DateTime<Utc>,
+    },
+    /// A daemon keeps rejecting the server's requests as unauthorized
+    DaemonAuthFailed { daemon_id: Uuid, status: u16 },
+    /// A host was seen for the first time
+    NewHost { host_id: Uuid, name: String },
+    /// A daemon API key is about to expire and should be rotated
+    ApiKeyExpiring {
+        api_key_id: Uuid,
+        name: String,
+        expires_at: DateTime<Utc>,
+    },
+    /// Two devices were seen on the same IP within the conflict window
+    IpConflict {
+        ip: IpAddr,
+        host_id: Uuid,
+        mac: MacAddress,
+        other_host_id: Uuid,
+        other_mac: MacAddress,
+    },
+}
+
+impl NotificationKind {
+    /// The event type users subscribe to for this kind of notification
+    pub fn event(&self) -> NotificationEvent {
+        match self {
+            NotificationKind::CertificateExpiring { .. }
+            | NotificationKind::CertificateCheckFailed { .. }
+            | NotificationKind::CheckFailed { .. } => NotificationEvent::CheckCritical,
+            NotificationKind::DaemonOffline { .. } => NotificationEvent::DaemonOffline,
+            NotificationKind::DaemonAuthFailed { .. } => NotificationEvent::DaemonAuthFailed,
+            NotificationKind::NewHost { .. } => NotificationEvent::NewHost,
+            NotificationKind::ApiKeyExpiring { .. } => NotificationEvent::ApiKeyExpiring,
+            NotificationKind::IpConflict { .. } => NotificationEvent::IpConflict,
+        }
+    }
+
+    pub fn severity(&self) -> NotificationSeverity {
+        match self {
+            NotificationKind::CheckFailed { .. }
+            | NotificationKind::DaemonOffline { .. }
+            | NotificationKind::DaemonAuthFailed { .. } => NotificationSeverity::Critical,
+            NotificationKind::CertificateExpiring { .. }
+            | NotificationKind::CertificateCheckFailed { .. }
+            | NotificationKind::ApiKeyExpiring { .. }
+            | NotificationKind::IpConflict { .. } => NotificationSeverity::Warning,
+            NotificationKind::NewHost { .. } => NotificationSeverity::Info,
+        }
+    }
+}
+
+/// How urgent a notification is. Critical notifications are delivered even during quiet hours.
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, StrumDisplay,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum NotificationSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+/// Event types users can subscribe to
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, StrumDisplay, EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum NotificationEvent {
+    DaemonOffline,
+    DaemonAuthFailed,
+    /// Failing checks and certificates that are expiring or can't be checked
+    CheckCritical,
+    NewHost,
+    ApiKeyExpiring,
+    IpConflict,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Notification {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub network_id: Uuid,
+    #[serde(flatten)]
+    pub kind: NotificationKind,
+}
+
+/// `at` as local time in `timezone`, with its offset so it can't be mistaken for another zone
+pub fn display_time(at: &DateTime<Utc>, timezone: Tz) -> String {
+    at.with_timezone(&timezone)
+        .format("%Y-%m-%d %H:%M:%S %:z")
+        .to_string()
+}
+
+impl Notification {
+    pub fn new(network_id: Uuid, kind: NotificationKind) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            created_at: Utc::now(),
+            network_id,
+            kind,
+        }
+    }
+
+    /// Human-readable message, with times shown in `timezone`
+    pub fn message(&self, timezone: Tz) -> String {
+        match &self.kind {
+            NotificationKind::CertificateExpiring {
+                target,
+                threshold_days,
+                days_left,
+                ..
+            } => format!(
+                "Certificate for {} expires in {} days (under {} days)",
+                target, days_left, threshold_days
+            ),
+            NotificationKind::CertificateCheckFailed { target, error } => {
+                format!("Certificate check for {} failed: {}", target, error)
+            }
+            NotificationKind::CheckFailed {
+                target,
+                check,
+                error,
+            } => format!("{:?} check for {} failed: {}", check, target, error),
+            NotificationKind::DaemonOffline {
+                daemon_id,
+                last_seen,
+            } => format!(
+                "Daemon {} is offline, last seen {}",
+                daemon_id,
+                display_time(last_seen, timezone)
+            ),
+            NotificationKind::DaemonAuthFailed { daemon_id, status } => format!(
+                "Daemon {} rejected the server's requests with HTTP {}, its credentials need \
+                 updating",
+                daemon_id, status
+            ),
+            NotificationKind::NewHost { host_id, name } => {
+                format!("New host {} ({})", name, host_id)
+            }
+            NotificationKind::ApiKeyExpiring {
+                name, expires_at, ..
+            } => format!(
+                "API key {} expires at {}, rotate it before then",
+                name,
+                display_time(expires_at, timezone)
+            ),
+            NotificationKind::IpConflict {
+                ip,
+                host_id,
+                mac,
+                other_host_id,
+                other_mac,
+            } => format!(
+                "IP {} is claimed by both host {} ({}) and host {} ({})",
+                ip, host_id, mac, other_host_id, other_mac
+            ),
+        }
+    }
+}
+
+impl Display for Notification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message(Tz::UTC))
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::server::notifications::r#impl::base::Notification;
+
+/// Where an outbox delivery goes. The channel or webhook is looked up again when sending, so ones
+/// disabled in the meantime are skipped and deleted ones take their deliveries with them.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DeliveryTarget {
+    Channel {
+        channel_id: Uuid,
+        notification: Notification,
+    },
+    DiscoveryWebhook {
+        webhook_id: Uuid,
+        payload: serde_json::Value,
+    },
+}
+
+/// A delivery waiting in the outbox until `due_at`, either held back by quiet hours or retrying
+/// after a failed attempt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeferredDeliveryBase {
+    pub target: DeliveryTarget,
+    pub due_at: DateTime<Utc>,
+    /// Failed attempts so far
+    pub attempts: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeferredDelivery {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub base: DeferredDeliveryBase,
+}
+
+impl Display for DeliveryTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeliveryTarget::Channel {
+                channel_id,
+                notification,
+            } => write!(
+                f,
+                "notification {} to channel {}",
+                notification.id, channel_id
+            ),
+            DeliveryTarget::DiscoveryWebhook { webhook_id, .. } => {
+                write!(f, "discovery webhook {}", webhook_id)
+            }
+        }
+    }
+}
+
+impl Display for DeferredDelivery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Deferred delivery of {}: {}", self.base.target, self.id)
+    }
+}
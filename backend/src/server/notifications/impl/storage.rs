@@ -0,0 +1,132 @@
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+use uuid::Uuid;
+
+use crate::server::{
+    notifications::r#impl::outbox::{DeferredDelivery, DeferredDeliveryBase, DeliveryTarget},
+    shared::storage::traits::{SqlValue, StorableEntity},
+};
+
+impl StorableEntity for DeferredDelivery {
+    type BaseData = DeferredDeliveryBase;
+
+    fn table_name() -> &'static str {
+        "notification_outbox"
+    }
+
+    fn get_base(&self) -> Self::BaseData {
+        self.base.clone()
+    }
+
+    fn new(base: Self::BaseData) -> Self {
+        let now = chrono::Utc::now();
+
+        Self {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: now,
+            base,
+        }
+    }
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn set_updated_at(&mut self, time: DateTime<Utc>) {
+        self.updated_at = time;
+    }
+
+    fn to_params(&self) -> Result<(Vec<&'static str>, Vec<SqlValue>), anyhow::Error> {
+        let Self {
+            id,
+            created_at,
+            updated_at,
+            base:
+                Self::BaseData {
+                    target,
+                    due_at,
+                    attempts,
+                },
+        } = self.clone();
+
+        let (channel_id, notification, webhook_id, payload) = match target {
+            DeliveryTarget::Channel {
+                channel_id,
+                notification,
+            } => (
+                Some(channel_id),
+                serde_json::to_value(notification)?,
+                None,
+                serde_json::Value::Null,
+            ),
+            DeliveryTarget::DiscoveryWebhook {
+                webhook_id,
+                payload,
+            } => (None, serde_json::Value::Null, Some(webhook_id), payload),
+        };
+
+        Ok((
+            vec![
+                "id",
+                "created_at",
+                "updated_at",
+                "channel_id",
+                "notification",
+                "webhook_id",
+                "payload",
+                "due_at",
+                "attempts",
+            ],
+            vec![
+                SqlValue::Uuid(id),
+                SqlValue::Timestamp(created_at),
+                SqlValue::Timestamp(updated_at),
+                SqlValue::OptionalUuid(channel_id),
+                SqlValue::Json(notification),
+                SqlValue::OptionalUuid(webhook_id),
+                SqlValue::Json(payload),
+                SqlValue::Timestamp(due_at),
+                SqlValue::I32(attempts),
+            ],
+        ))
+    }
+
+    fn from_row(row: &PgRow) -> Result<Self, anyhow::Error> {
+        let target = match (
+            row.get::<Option<Uuid>, _>("channel_id"),
+            row.get::<Option<Uuid>, _>("webhook_id"),
+        ) {
+            (Some(channel_id), None) => DeliveryTarget::Channel {
+                channel_id,
+                notification: serde_json::from_value(row.get("notification"))
+                    .map_err(|e| anyhow::anyhow!("Failed to parse deferred notification: {}", e))?,
+            },
+            (None, Some(webhook_id)) => DeliveryTarget::DiscoveryWebhook {
+                webhook_id,
+                payload: row.get("payload"),
+            },
+            _ => return Err(anyhow::anyhow!("Outbox delivery has no single target")),
+        };
+
+        Ok(DeferredDelivery {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            base: DeferredDeliveryBase {
+                target,
+                due_at: row.get("due_at"),
+                attempts: row.get("attempts"),
+            },
+        })
+    }
+}
@@ -0,0 +1,525 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::server::{
+    discovery_webhooks::service::DiscoveryWebhookService,
+    email::service::EmailService,
+    networks::r#impl::Network,
+    notification_channels::r#impl::base::{ChannelTarget, NotificationChannel},
+    notification_subscriptions::{
+        r#impl::base::QuietHoursMode, service::NotificationSubscriptionService,
+    },
+    notifications::r#impl::{
+        base::{Notification, NotificationSeverity},
+        outbox::{DeferredDelivery, DeferredDeliveryBase, DeliveryTarget},
+    },
+    shared::{
+        outbound::OutboundClient,
+        storage::{
+            filter::EntityFilter,
+            generic::GenericPostgresStorage,
+            traits::{SqlValue, StorableEntity, Storage},
+        },
+    },
+};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most deferred deliveries claimed from the outbox at a time
+const OUTBOX_BATCH: usize = 100;
+
+/// How long a claimed delivery is held back from other attempts while it's being sent
+const CLAIM_LEASE: chrono::Duration = chrono::Duration::minutes(5);
+
+/// Waits before each retry of a failed delivery, which is given up on after the last
+const RETRY_DELAYS: [chrono::Duration; 3] = [
+    chrono::Duration::seconds(5),
+    chrono::Duration::seconds(30),
+    chrono::Duration::seconds(120),
+];
+
+/// Fans notifications out to whoever is listening, and delivers them to subscribed users on the
+/// channels they picked. Notifications aren't stored, so anything sent while nobody is subscribed
+/// is only logged. Deliveries go through the outbox, where those deferred by quiet hours or
+/// waiting to be retried stay until [`Self::send_due`] picks them up.
+pub struct NotificationService {
+    sender: broadcast::Sender<Notification>,
+    router: OnceLock<Arc<NotificationRouter>>,
+    /// Zone times are shown in when notifications are logged
+    display_timezone: Tz,
+}
+
+impl Default for NotificationService {
+    fn default() -> Self {
+        Self::new(Tz::UTC)
+    }
+}
+
+impl NotificationService {
+    pub fn new(display_timezone: Tz) -> Self {
+        let (sender, _rx) = broadcast::channel(100);
+        Self {
+            sender,
+            router: OnceLock::new(),
+            display_timezone,
+        }
+    }
+
+    pub fn set_router(
+        &self,
+        router: Arc<NotificationRouter>,
+    ) -> Result<(), Arc<NotificationRouter>> {
+        self.router.set(router)
+    }
+
+    pub fn notify(&self, notification: Notification) {
+        tracing::warn!(
+            network_id = %notification.network_id,
+            notification_id = %notification.id,
+            "{}",
+            notification.message(self.display_timezone)
+        );
+
+        if let Some(router) = self.router.get() {
+            let router = router.clone();
+            let notification = notification.clone();
+            tokio::spawn(async move { router.route(&notification).await });
+        }
+
+        // Only fails when there are no subscribers
+        let _ = self.sender.send(notification);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Notification> {
+        self.sender.subscribe()
+    }
+
+    /// Send outbox deliveries that are due by `now`, returning how many went out
+    pub async fn send_due(&self, now: DateTime<Utc>) -> Result<usize> {
+        match self.router.get() {
+            Some(router) => router.send_due(now).await,
+            None => Ok(0),
+        }
+    }
+
+    /// Queue a delivery in the outbox, attempting it right away if it's due by now
+    pub async fn enqueue(&self, target: DeliveryTarget, due_at: DateTime<Utc>) -> Result<()> {
+        self.router
+            .get()
+            .ok_or_else(|| anyhow!("Notification routing isn't set up"))?
+            .enqueue(target, due_at)
+            .await
+    }
+
+    /// Who `notification` would be delivered to, and on which channels
+    pub async fn deliveries(&self, notification: &Notification) -> Result<Vec<Delivery>> {
+        match self.router.get() {
+            Some(router) => router.deliveries(notification).await,
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+/// A notification going to one subscribed user on one of their channels
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    pub user_id: Uuid,
+    pub channel: NotificationChannel,
+    /// When the user's quiet hours close, for non-critical notifications fired during them
+    pub deferred_until: Option<DateTime<Utc>>,
+}
+
+/// Routes notifications to the users subscribed to their event type. Users without subscriptions
+/// get nothing.
+pub struct NotificationRouter {
+    subscription_service: Arc<NotificationSubscriptionService>,
+    channel_storage: Arc<GenericPostgresStorage<NotificationChannel>>,
+    outbox_storage: Arc<GenericPostgresStorage<DeferredDelivery>>,
+    network_storage: Arc<GenericPostgresStorage<Network>>,
+    email_service: Option<Arc<EmailService>>,
+    /// Zone times are shown in for messages people read. Webhook payloads stay UTC.
+    display_timezone: Tz,
+    discovery_webhook_service: OnceLock<Arc<DiscoveryWebhookService>>,
+    client: OutboundClient,
+}
+
+impl NotificationRouter {
+    pub fn new(
+        subscription_service: Arc<NotificationSubscriptionService>,
+        channel_storage: Arc<GenericPostgresStorage<NotificationChannel>>,
+        outbox_storage: Arc<GenericPostgresStorage<DeferredDelivery>>,
+        network_storage: Arc<GenericPostgresStorage<Network>>,
+        email_service: Option<Arc<EmailService>>,
+        display_timezone: Tz,
+    ) -> Self {
+        Self {
+            subscription_service,
+            channel_storage,
+            outbox_storage,
+            network_storage,
+            email_service,
+            display_timezone,
+            discovery_webhook_service: OnceLock::new(),
+            client: OutboundClient::new(WEBHOOK_TIMEOUT, false),
+        }
+    }
+
+    /// Let webhook channels call loopback, private and link-local addresses
+    pub fn allow_internal_targets(mut self, allow: bool) -> Self {
+        self.client = OutboundClient::new(WEBHOOK_TIMEOUT, allow);
+        self
+    }
+
+    pub fn set_discovery_webhook_service(
+        &self,
+        discovery_webhook_service: Arc<DiscoveryWebhookService>,
+    ) -> Result<(), Arc<DiscoveryWebhookService>> {
+        self.discovery_webhook_service
+            .set(discovery_webhook_service)
+    }
+
+    /// Subscribers to the notification's event who can see its network, each with the channels
+    /// they chose. Channels that were deleted, disabled, or belong to another organization are
+    /// skipped, as are subscribers whose quiet hours suppress the notification. Quiet hours are
+    /// checked at the time the notification fired, and never hold back critical notifications.
+    pub async fn deliveries(&self, notification: &Notification) -> Result<Vec<Delivery>> {
+        let Some(network) = self
+            .network_storage
+            .get_by_id(&notification.network_id)
+            .await?
+        else {
+            return Ok(Vec::new());
+        };
+        let organization_id = network.base.organization_id;
+
+        let subscriptions = self
+            .subscription_service
+            .subscriptions_for_event(notification.kind.event(), &organization_id)
+            .await?;
+
+        let channel_ids: Vec<Uuid> = subscriptions
+            .iter()
+            .flat_map(|subscription| subscription.base.channel_ids.iter().copied())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let channels: HashMap<Uuid, NotificationChannel> = if channel_ids.is_empty() {
+            HashMap::new()
+        } else {
+            self.channel_storage
+                .get_all(
+                    EntityFilter::unfiltered()
+                        .entity_ids(&channel_ids)
+                        .network_in_organization(&organization_id),
+                )
+                .await?
+                .into_iter()
+                .map(|channel| (channel.id, channel))
+                .collect()
+        };
+
+        let mut deliveries = Vec::new();
+        for subscription in subscriptions {
+            let user_id = subscription.base.user_id;
+            let quiet_until = subscription
+                .base
+                .quiet_hours
+                .as_ref()
+                .filter(|_| notification.kind.severity() < NotificationSeverity::Critical)
+                .and_then(|quiet_hours| {
+                    quiet_hours
+                        .ends_after(notification.created_at)
+                        .map(|end| (quiet_hours.mode, end))
+                });
+            let deferred_until = match quiet_until {
+                Some((QuietHoursMode::Suppress, _)) => {
+                    tracing::debug!(
+                        subscription_id = %subscription.id,
+                        notification_id = %notification.id,
+                        "Notification suppressed by quiet hours"
+                    );
+                    continue;
+                }
+                Some((QuietHoursMode::Defer, end)) => Some(end),
+                None => None,
+            };
+
+            for channel_id in &subscription.base.channel_ids {
+                match channels.get(channel_id) {
+                    Some(channel) if channel.base.enabled => {
+                        deliveries.push(Delivery {
+                            user_id,
+                            channel: channel.clone(),
+                            deferred_until,
+                        });
+                    }
+                    Some(_) => tracing::warn!(
+                        subscription_id = %subscription.id,
+                        channel_id = %channel_id,
+                        "Skipping disabled notification channel"
+                    ),
+                    None => tracing::warn!(
+                        subscription_id = %subscription.id,
+                        channel_id = %channel_id,
+                        "Skipping deleted or foreign notification channel"
+                    ),
+                }
+            }
+        }
+
+        Ok(deliveries)
+    }
+
+    /// Deliver `notification` to its subscribers. A channel several users picked is only sent
+    /// to once, as soon as any of them wants it.
+    async fn route(self: &Arc<Self>, notification: &Notification) {
+        let deliveries = match self.deliveries(notification).await {
+            Ok(deliveries) => deliveries,
+            Err(e) => {
+                tracing::error!(
+                    notification_id = %notification.id,
+                    error = %e,
+                    "Failed to resolve notification subscribers"
+                );
+                return;
+            }
+        };
+
+        let mut by_channel: HashMap<Uuid, Delivery> = HashMap::new();
+        for delivery in deliveries {
+            match by_channel.get_mut(&delivery.channel.id) {
+                // None, sending now, sorts before any deferral
+                Some(earliest) if earliest.deferred_until <= delivery.deferred_until => {}
+                Some(earliest) => *earliest = delivery,
+                None => {
+                    by_channel.insert(delivery.channel.id, delivery);
+                }
+            }
+        }
+
+        let now = Utc::now();
+        for delivery in by_channel.into_values() {
+            if let Some(due) = delivery.deferred_until {
+                tracing::info!(
+                    notification_id = %notification.id,
+                    channel_id = %delivery.channel.id,
+                    %due,
+                    "Deferring notification until quiet hours end"
+                );
+            }
+
+            let target = DeliveryTarget::Channel {
+                channel_id: delivery.channel.id,
+                notification: notification.clone(),
+            };
+            if let Err(e) = self
+                .enqueue(target, delivery.deferred_until.unwrap_or(now))
+                .await
+            {
+                tracing::error!(
+                    notification_id = %notification.id,
+                    channel_id = %delivery.channel.id,
+                    error = %e,
+                    "Failed to queue notification"
+                );
+            }
+        }
+    }
+
+    /// Put a delivery in the outbox, due at `due_at`. One that's already due is attempted right
+    /// away, and retried from the outbox if that fails.
+    async fn enqueue(
+        self: &Arc<Self>,
+        target: DeliveryTarget,
+        due_at: DateTime<Utc>,
+    ) -> Result<()> {
+        let deferred = self
+            .outbox_storage
+            .create(&DeferredDelivery::new(DeferredDeliveryBase {
+                target,
+                due_at,
+                attempts: 0,
+            }))
+            .await?;
+
+        if due_at <= Utc::now() {
+            let router = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = router.attempt(&deferred, Utc::now()).await {
+                    tracing::warn!(
+                        delivery_id = %deferred.id,
+                        error = %e,
+                        "Failed to attempt outbox delivery"
+                    );
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Attempt the outbox's deliveries due by `now`, returning how many went out
+    async fn send_due(&self, now: DateTime<Utc>) -> Result<usize> {
+        let mut sent = 0;
+        loop {
+            let due = self
+                .outbox_storage
+                .get_page(EntityFilter::unfiltered().due_by(now), None, OUTBOX_BATCH)
+                .await?;
+            let batch_len = due.len();
+
+            for deferred in &due {
+                if self.attempt(deferred, now).await? {
+                    sent += 1;
+                }
+            }
+
+            if batch_len < OUTBOX_BATCH {
+                return Ok(sent);
+            }
+        }
+    }
+
+    /// Claim `deferred` and send it, returning whether it went out. Claiming pushes it back by
+    /// [`CLAIM_LEASE`], so no other attempt picks it up meanwhile, and a server that stops
+    /// mid-attempt leaves it to be retried. Failed attempts are retried after
+    /// [`RETRY_DELAYS`], then given up on.
+    async fn attempt(&self, deferred: &DeferredDelivery, now: DateTime<Utc>) -> Result<bool> {
+        let Some(claimed) = self
+            .outbox_storage
+            .update_where(
+                EntityFilter::unfiltered()
+                    .entity_id(&deferred.id)
+                    .due_by(now),
+                vec![("due_at", SqlValue::Timestamp(now + CLAIM_LEASE))],
+            )
+            .await?
+            .pop()
+        else {
+            return Ok(false);
+        };
+
+        let error = match self.deliver_target(&claimed.base.target).await {
+            Ok(sent) => {
+                self.outbox_storage.delete(&claimed.id).await?;
+                return Ok(sent);
+            }
+            Err(e) => e,
+        };
+
+        let attempts = claimed.base.attempts + 1;
+        match RETRY_DELAYS.get(claimed.base.attempts as usize) {
+            Some(delay) => {
+                tracing::warn!(
+                    delivery_id = %claimed.id,
+                    error = %error,
+                    "Delivery of {} failed, retrying in {:?}",
+                    claimed.base.target,
+                    delay
+                );
+                self.outbox_storage
+                    .update_where(
+                        EntityFilter::unfiltered().entity_id(&claimed.id),
+                        vec![
+                            ("due_at", SqlValue::Timestamp(now + *delay)),
+                            ("attempts", SqlValue::I32(attempts)),
+                        ],
+                    )
+                    .await?;
+            }
+            None => {
+                tracing::error!(
+                    delivery_id = %claimed.id,
+                    error = %error,
+                    "Giving up on delivery of {} after {} attempts",
+                    claimed.base.target,
+                    attempts
+                );
+                self.outbox_storage.delete(&claimed.id).await?;
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Send to `target` if it still exists and is enabled, returning whether it was sent
+    async fn deliver_target(&self, target: &DeliveryTarget) -> Result<bool> {
+        match target {
+            DeliveryTarget::Channel {
+                channel_id,
+                notification,
+            } => match self.channel_storage.get_by_id(channel_id).await? {
+                Some(channel) if channel.base.enabled => {
+                    self.deliver(&channel, notification).await?;
+                    Ok(true)
+                }
+                _ => {
+                    tracing::info!(
+                        notification_id = %notification.id,
+                        channel_id = %channel_id,
+                        "Dropping notification for a disabled or deleted channel"
+                    );
+                    Ok(false)
+                }
+            },
+            DeliveryTarget::DiscoveryWebhook {
+                webhook_id,
+                payload,
+            } => {
+                let webhook_service = self
+                    .discovery_webhook_service
+                    .get()
+                    .ok_or_else(|| anyhow!("Discovery webhooks aren't set up"))?;
+                webhook_service.deliver_queued(webhook_id, payload).await
+            }
+        }
+    }
+
+    async fn deliver(
+        &self,
+        channel: &NotificationChannel,
+        notification: &Notification,
+    ) -> Result<()> {
+        match &channel.base.target {
+            ChannelTarget::Email { address } => {
+                let Some(email_service) = &self.email_service else {
+                    tracing::warn!(
+                        channel_id = %channel.id,
+                        "Email notification channel used but SMTP isn't configured"
+                    );
+                    return Ok(());
+                };
+
+                let body = notification
+                    .message(self.display_timezone)
+                    .replace('&', "&amp;")
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;");
+                email_service
+                    .send_email(
+                        address.clone(),
+                        &format!("NetVisor: {}", notification.kind.event()),
+                        &format!("<p>{}</p>", body),
+                    )
+                    .await
+            }
+            ChannelTarget::Webhook { url } => {
+                self.client
+                    .post(url)?
+                    .json(notification)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                Ok(())
+            }
+        }
+    }
+}
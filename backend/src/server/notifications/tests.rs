@@ -0,0 +1,415 @@
+use email_address::EmailAddress;
+use serial_test::serial;
+
+use crate::{
+    server::{
+        diagnostics::r#impl::base::DiagnosticKind,
+        notification_channels::r#impl::base::{
+            ChannelTarget, NotificationChannel, NotificationChannelBase,
+        },
+        notification_subscriptions::r#impl::base::{
+            NotificationSubscription, NotificationSubscriptionBase, QuietHours, QuietHoursMode,
+        },
+        notifications::r#impl::{
+            base::{Notification, NotificationEvent, NotificationKind},
+            outbox::DeliveryTarget,
+        },
+        shared::{
+            services::traits::CrudService,
+            storage::{
+                filter::EntityFilter,
+                traits::{StorableEntity, Storage},
+            },
+        },
+    },
+    tests::*,
+};
+
+fn webhook(network_id: uuid::Uuid, name: &str) -> NotificationChannel {
+    NotificationChannel::new(NotificationChannelBase {
+        name: name.to_string(),
+        network_id,
+        target: ChannelTarget::Webhook {
+            url: format!("https://hooks.example.com/{}", name),
+        },
+        enabled: true,
+    })
+}
+
+fn subscription(
+    user_id: uuid::Uuid,
+    event: NotificationEvent,
+    channel_ids: Vec<uuid::Uuid>,
+) -> NotificationSubscription {
+    NotificationSubscription::new(NotificationSubscriptionBase {
+        user_id,
+        event,
+        channel_ids,
+        quiet_hours: None,
+    })
+}
+
+#[tokio::test]
+#[serial]
+async fn test_notifications_routed_to_subscribers_on_their_channels() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+
+    let mut users = Vec::new();
+    for name in ["ops", "dev", "quiet"] {
+        let mut user = user(&organization.id);
+        user.base.email = EmailAddress::new_unchecked(format!("{}@example.com", name));
+        users.push(services.user_service.create(user).await.unwrap());
+    }
+    let (ops, dev, quiet) = (&users[0], &users[1], &users[2]);
+
+    let pager = services
+        .notification_channel_service
+        .create(webhook(network.id, "pager"))
+        .await
+        .unwrap();
+    let chat = services
+        .notification_channel_service
+        .create(webhook(network.id, "chat"))
+        .await
+        .unwrap();
+    let mut disabled = webhook(network.id, "disabled");
+    disabled.base.enabled = false;
+    let disabled = services
+        .notification_channel_service
+        .create(disabled)
+        .await
+        .unwrap();
+    let deleted = services
+        .notification_channel_service
+        .create(webhook(network.id, "deleted"))
+        .await
+        .unwrap();
+    services
+        .notification_channel_service
+        .delete(&deleted.id)
+        .await
+        .unwrap();
+
+    for subscription in [
+        subscription(
+            ops.id,
+            NotificationEvent::CheckCritical,
+            vec![pager.id, disabled.id, deleted.id],
+        ),
+        subscription(ops.id, NotificationEvent::NewHost, vec![chat.id]),
+        subscription(dev.id, NotificationEvent::NewHost, vec![chat.id]),
+    ] {
+        services
+            .notification_subscription_service
+            .create(subscription)
+            .await
+            .unwrap();
+    }
+
+    // Only ops subscribes to failing checks, and only the pager channel is usable
+    let check_failed = Notification::new(
+        network.id,
+        NotificationKind::CheckFailed {
+            target: "10.0.0.1".to_string(),
+            check: DiagnosticKind::Tcp,
+            error: "timed out".to_string(),
+        },
+    );
+    let deliveries = services
+        .notification_service
+        .deliveries(&check_failed)
+        .await
+        .unwrap();
+    let routed: Vec<_> = deliveries
+        .iter()
+        .map(|d| (d.user_id, d.channel.id))
+        .collect();
+    assert_eq!(routed, vec![(ops.id, pager.id)]);
+
+    let new_host = Notification::new(
+        network.id,
+        NotificationKind::NewHost {
+            host_id: uuid::Uuid::new_v4(),
+            name: "printer".to_string(),
+        },
+    );
+    let mut recipients: Vec<_> = services
+        .notification_service
+        .deliveries(&new_host)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|d| (d.user_id, d.channel.id))
+        .collect();
+    recipients.sort();
+    let mut expected = vec![(ops.id, chat.id), (dev.id, chat.id)];
+    expected.sort();
+    assert_eq!(recipients, expected);
+
+    // A user without subscriptions gets nothing, and nobody subscribes to offline daemons
+    assert!(
+        recipients
+            .iter()
+            .chain(&routed)
+            .all(|(user_id, _)| *user_id != quiet.id)
+    );
+    let daemon_offline = Notification::new(
+        network.id,
+        NotificationKind::DaemonOffline {
+            daemon_id: uuid::Uuid::new_v4(),
+            last_seen: chrono::Utc::now(),
+        },
+    );
+    assert!(
+        services
+            .notification_service
+            .deliveries(&daemon_offline)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_quiet_hours_defer_warnings_but_not_critical_alerts() {
+    let (_storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let user = services
+        .user_service
+        .create(user(&organization.id))
+        .await
+        .unwrap();
+    let pager = services
+        .notification_channel_service
+        .create(webhook(network.id, "pager"))
+        .await
+        .unwrap();
+
+    let mut overnight = subscription(user.id, NotificationEvent::CheckCritical, vec![pager.id]);
+    overnight.base.quiet_hours = Some(QuietHours {
+        start: chrono::NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+        end: chrono::NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+        timezone: chrono_tz::Europe::Berlin,
+        mode: QuietHoursMode::Defer,
+    });
+    services
+        .notification_subscription_service
+        .create(overnight)
+        .await
+        .unwrap();
+
+    // 03:00 in Berlin, where quiet hours close at 07:00 local, 06:00 UTC
+    let fired_at: chrono::DateTime<chrono::Utc> = "2025-01-15T02:00:00Z".parse().unwrap();
+    let quiet_hours_end: chrono::DateTime<chrono::Utc> = "2025-01-15T06:00:00Z".parse().unwrap();
+    let at_3am = |kind| {
+        let mut notification = Notification::new(network.id, kind);
+        notification.created_at = fired_at;
+        notification
+    };
+
+    let warning = at_3am(NotificationKind::CertificateExpiring {
+        target: "example.com:443".to_string(),
+        threshold_days: 14,
+        days_left: 10,
+        expires_at: fired_at + chrono::Duration::days(10),
+    });
+    let deliveries = services
+        .notification_service
+        .deliveries(&warning)
+        .await
+        .unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].deferred_until, Some(quiet_hours_end));
+
+    let critical = at_3am(NotificationKind::CheckFailed {
+        target: "10.0.0.1".to_string(),
+        check: DiagnosticKind::Tcp,
+        error: "timed out".to_string(),
+    });
+    let deliveries = services
+        .notification_service
+        .deliveries(&critical)
+        .await
+        .unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].channel.id, pager.id);
+    assert_eq!(deliveries[0].deferred_until, None);
+
+    // Once quiet hours are over, warnings go straight out
+    let mut daytime = warning.clone();
+    daytime.created_at = quiet_hours_end;
+    let deliveries = services
+        .notification_service
+        .deliveries(&daytime)
+        .await
+        .unwrap();
+    assert_eq!(deliveries[0].deferred_until, None);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_deferred_notifications_kept_in_outbox_until_due() {
+    let (storage, services, _container) = test_services().await;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let user = services
+        .user_service
+        .create(user(&organization.id))
+        .await
+        .unwrap();
+    let mut pager = services
+        .notification_channel_service
+        .create(webhook(network.id, "pager"))
+        .await
+        .unwrap();
+
+    let mut overnight = subscription(user.id, NotificationEvent::CheckCritical, vec![pager.id]);
+    overnight.base.quiet_hours = Some(QuietHours {
+        start: chrono::NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+        end: chrono::NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+        timezone: chrono_tz::Europe::Berlin,
+        mode: QuietHoursMode::Defer,
+    });
+    services
+        .notification_subscription_service
+        .create(overnight)
+        .await
+        .unwrap();
+
+    let fired_at: chrono::DateTime<chrono::Utc> = "2025-01-15T02:00:00Z".parse().unwrap();
+    let quiet_hours_end: chrono::DateTime<chrono::Utc> = "2025-01-15T06:00:00Z".parse().unwrap();
+    let mut warning = Notification::new(
+        network.id,
+        NotificationKind::CertificateExpiring {
+            target: "example.com:443".to_string(),
+            threshold_days: 14,
+            days_left: 10,
+            expires_at: fired_at + chrono::Duration::days(10),
+        },
+    );
+    warning.created_at = fired_at;
+    services.notification_service.notify(warning.clone());
+
+    // Routing runs in the background, so wait for the deferral to land in the outbox
+    let mut outbox = Vec::new();
+    for _ in 0..50 {
+        outbox = storage
+            .notification_outbox
+            .get_all(EntityFilter::unfiltered())
+            .await
+            .unwrap();
+        if !outbox.is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(outbox.len(), 1);
+    assert_eq!(
+        outbox[0].base.target,
+        DeliveryTarget::Channel {
+            channel_id: pager.id,
+            notification: warning,
+        }
+    );
+    assert_eq!(outbox[0].base.due_at, quiet_hours_end);
+
+    // Nothing goes out before quiet hours end
+    let sent = services
+        .notification_service
+        .send_due(fired_at)
+        .await
+        .unwrap();
+    assert_eq!(sent, 0);
+    assert_eq!(
+        storage
+            .notification_outbox
+            .get_all(EntityFilter::unfiltered())
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // The channel is read again when sending, so disabling it in the meantime drops the delivery
+    pager.base.enabled = false;
+    services
+        .notification_channel_service
+        .update(&mut pager)
+        .await
+        .unwrap();
+    let sent = services
+        .notification_service
+        .send_due(quiet_hours_end)
+        .await
+        .unwrap();
+    assert_eq!(sent, 0);
+    assert!(
+        storage
+            .notification_outbox
+            .get_all(EntityFilter::unfiltered())
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_timestamps_serialize_as_utc_and_render_in_display_zone() {
+    let last_seen: chrono::DateTime<chrono::Utc> = "2025-07-01T12:30:15.250Z".parse().unwrap();
+    let notification = Notification::new(
+        uuid::Uuid::new_v4(),
+        NotificationKind::DaemonOffline {
+            daemon_id: uuid::Uuid::new_v4(),
+            last_seen,
+        },
+    );
+
+    // API payloads are RFC 3339 in UTC, and round-trip without losing precision
+    let json = serde_json::to_value(&notification).unwrap();
+    assert_eq!(json["last_seen"], "2025-07-01T12:30:15.250Z");
+    let parsed: Notification = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed, notification);
+
+    // People see local time with its offset
+    assert!(
+        notification
+            .message(chrono_tz::Europe::Berlin)
+            .ends_with("last seen 2025-07-01 14:30:15 +02:00")
+    );
+    assert!(
+        notification
+            .to_string()
+            .ends_with("last seen 2025-07-01 12:30:15 +00:00")
+    );
+}
@@ -1,6 +1,6 @@
 use crate::server::{
     organizations::{r#impl::base::Organization, service::OrganizationService},
-    shared::handlers::traits::CrudHandlers,
+    shared::handlers::traits::{CrudHandlers, EntityScope},
 };
 
 impl CrudHandlers for Organization {
@@ -9,4 +9,8 @@ impl CrudHandlers for Organization {
     fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
         &state.services.organization_service
     }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Organization(self.id)
+    }
 }
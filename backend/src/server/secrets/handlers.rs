@@ -0,0 +1,44 @@
+use crate::server::{
+    auth::middleware::RequireAdmin,
+    config::AppState,
+    secrets::r#impl::{api::CreateSecretRequest, base::Secret},
+    shared::{
+        handlers::traits::{CrudHandlers, admin_delete_handler, get_all_handler},
+        types::api::{ApiError, ApiResponse, ApiResult},
+    },
+};
+use axum::{
+    Json, Router,
+    extract::State,
+    routing::{delete, get, post},
+};
+use std::sync::Arc;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_all_handler::<Secret>))
+        .route("/", post(create_handler))
+        .route("/{id}", delete(admin_delete_handler::<Secret>))
+}
+
+/// Register a named secret. Its value is never returned, including in this response.
+pub async fn create_handler(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    Json(request): Json<CreateSecretRequest>,
+) -> ApiResult<Json<ApiResponse<Secret>>> {
+    if !user.network_ids.contains(&request.network_id) {
+        return Err(ApiError::forbidden("You don't have access to this network"));
+    }
+
+    let service = Secret::get_service(&state);
+    let secret = service.create_secret(request).await?;
+
+    tracing::info!(
+        secret_id = %secret.id,
+        user_id = %user.user_id,
+        "Secret created via API"
+    );
+
+    Ok(Json(ApiResponse::success(secret)))
+}
@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::server::secrets::r#impl::base::SecretValue;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateSecretRequest {
+    pub name: String,
+    pub network_id: Uuid,
+    pub value: SecretValue,
+}
@@ -0,0 +1,84 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use strum::{EnumDiscriminants, IntoDiscriminant};
+use uuid::Uuid;
+
+/// Named credential stored encrypted, which requests reference by name instead of carrying it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretBase {
+    pub name: String,
+    pub network_id: Uuid,
+    pub kind: SecretKind,
+    /// Value encrypted with the server's secrets key. Never returned by the API.
+    #[serde(skip_serializing, default)]
+    pub ciphertext: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Secret {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub base: SecretBase,
+}
+
+impl Display for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.base.name, self.id)
+    }
+}
+
+/// Plaintext of a secret. Only ever serialized to register it and to hand it to a daemon; its
+/// `Debug` output is redacted so it can't leak into logs.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, EnumDiscriminants)]
+#[strum_discriminants(name(SecretKind), derive(Serialize, Deserialize, Hash))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[strum_discriminants(serde(rename_all = "snake_case"))]
+pub enum SecretValue {
+    SnmpCommunity { community: String },
+    BasicAuth { username: String, password: String },
+    Token { token: String },
+}
+
+impl SecretValue {
+    pub fn snmp_community(&self) -> Option<&str> {
+        match self {
+            SecretValue::SnmpCommunity { community } => Some(community),
+            _ => None,
+        }
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        match self {
+            SecretValue::Token { token } => Some(token),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}(***REDACTED***)", self.discriminant())
+    }
+}
+
+/// Secrets resolved for a request to a daemon, keyed by the name they're referenced by
+pub type ResolvedSecrets = BTreeMap<String, SecretValue>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SecretError {
+    #[error("The secrets store is not configured, set a secrets key on the server")]
+    NotConfigured,
+
+    #[error("Secret names can't be empty")]
+    EmptyName,
+
+    #[error("Secret '{name}' does not exist")]
+    NotFound { name: String },
+
+    #[error("A secret named '{name}' already exists on this network")]
+    NameTaken { name: String },
+}
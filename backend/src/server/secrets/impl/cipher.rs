@@ -0,0 +1,93 @@
+use anyhow::{Result, anyhow};
+use ring::{
+    aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    rand::{SecureRandom, SystemRandom},
+};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// AES-256-GCM with a key derived from the server's secrets key. Ciphertexts are bound to the
+/// secret's id, so one row's value can't be swapped into another.
+pub struct SecretCipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl SecretCipher {
+    pub fn new(secrets_key: &str) -> Self {
+        let key = UnboundKey::new(&AES_256_GCM, &Sha256::digest(secrets_key.as_bytes()))
+            .expect("SHA-256 digest is a valid AES-256 key");
+
+        Self {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        }
+    }
+
+    /// Hex encoded nonce followed by the sealed value
+    pub fn encrypt(&self, id: &Uuid, plaintext: &[u8]) -> Result<String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("Failed to generate nonce"))?;
+
+        let mut sealed = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(id.as_bytes()),
+                &mut sealed,
+            )
+            .map_err(|_| anyhow!("Failed to encrypt secret"))?;
+
+        Ok(hex::encode([nonce.as_slice(), &sealed].concat()))
+    }
+
+    pub fn decrypt(&self, id: &Uuid, ciphertext: &str) -> Result<Vec<u8>> {
+        let bytes = hex::decode(ciphertext).map_err(|_| anyhow!("Malformed secret ciphertext"))?;
+        if bytes.len() < NONCE_LEN {
+            return Err(anyhow!("Malformed secret ciphertext"));
+        }
+        let (nonce, sealed) = bytes.split_at(NONCE_LEN);
+
+        let mut sealed = sealed.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(
+                Nonce::try_assume_unique_for_key(nonce)
+                    .map_err(|_| anyhow!("Malformed secret ciphertext"))?,
+                Aad::from(id.as_bytes()),
+                &mut sealed,
+            )
+            .map_err(|_| {
+                anyhow!("Failed to decrypt secret, the server's secrets key may have changed")
+            })?;
+
+        Ok(plaintext.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_bound_to_key_and_id() {
+        let cipher = SecretCipher::new("server-key");
+        let id = Uuid::new_v4();
+
+        let ciphertext = cipher.encrypt(&id, b"community").unwrap();
+        assert!(!ciphertext.contains(&hex::encode(b"community")));
+        assert_eq!(cipher.decrypt(&id, &ciphertext).unwrap(), b"community");
+
+        // Same value encrypts differently each time
+        assert_ne!(cipher.encrypt(&id, b"community").unwrap(), ciphertext);
+
+        assert!(cipher.decrypt(&Uuid::new_v4(), &ciphertext).is_err());
+        assert!(
+            SecretCipher::new("other-key")
+                .decrypt(&id, &ciphertext)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,15 @@
+use crate::server::secrets::r#impl::base::Secret;
+use crate::server::secrets::service::SecretService;
+use crate::server::shared::handlers::traits::{CrudHandlers, EntityScope};
+
+impl CrudHandlers for Secret {
+    type Service = SecretService;
+
+    fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
+        &state.services.secret_service
+    }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.base.network_id)
+    }
+}
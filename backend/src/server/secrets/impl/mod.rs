@@ -0,0 +1,5 @@
+pub mod api;
+pub mod base;
+pub mod cipher;
+pub mod handlers;
+pub mod storage;
@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+use uuid::Uuid;
+
+use crate::server::{
+    secrets::r#impl::base::{Secret, SecretBase, SecretKind},
+    shared::storage::traits::{SqlValue, StorableEntity},
+};
+
+impl StorableEntity for Secret {
+    type BaseData = SecretBase;
+
+    fn table_name() -> &'static str {
+        "secrets"
+    }
+
+    fn get_base(&self) -> Self::BaseData {
+        self.base.clone()
+    }
+
+    fn new(base: Self::BaseData) -> Self {
+        let now = chrono::Utc::now();
+
+        Self {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: now,
+            base,
+        }
+    }
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn set_updated_at(&mut self, time: DateTime<Utc>) {
+        self.updated_at = time;
+    }
+
+    fn to_params(&self) -> Result<(Vec<&'static str>, Vec<SqlValue>), anyhow::Error> {
+        let Self {
+            id,
+            created_at,
+            updated_at,
+            base:
+                Self::BaseData {
+                    name,
+                    network_id,
+                    kind,
+                    ciphertext,
+                },
+        } = self.clone();
+
+        Ok((
+            vec![
+                "id",
+                "created_at",
+                "updated_at",
+                "name",
+                "network_id",
+                "kind",
+                "ciphertext",
+            ],
+            vec![
+                SqlValue::Uuid(id),
+                SqlValue::Timestamp(created_at),
+                SqlValue::Timestamp(updated_at),
+                SqlValue::String(name),
+                SqlValue::Uuid(network_id),
+                SqlValue::SecretKind(kind),
+                SqlValue::String(ciphertext),
+            ],
+        ))
+    }
+
+    fn from_row(row: &PgRow) -> Result<Self, anyhow::Error> {
+        let kind: SecretKind = serde_json::from_str(&row.get::<String, _>("kind"))
+            .map_err(|e| anyhow::anyhow!("Failed to deserialize kind: {}", e))?;
+
+        Ok(Secret {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            base: SecretBase {
+                name: row.get("name"),
+                network_id: row.get("network_id"),
+                kind,
+                ciphertext: row.get("ciphertext"),
+            },
+        })
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use strum::IntoDiscriminant;
+use uuid::Uuid;
+
+use crate::server::{
+    secrets::r#impl::{
+        api::CreateSecretRequest,
+        base::{ResolvedSecrets, Secret, SecretBase, SecretError, SecretValue},
+        cipher::SecretCipher,
+    },
+    shared::{
+        services::traits::CrudService,
+        storage::{
+            filter::EntityFilter,
+            generic::{GenericPostgresStorage, is_unique_violation},
+            traits::{StorableEntity, Storage},
+        },
+    },
+};
+
+pub struct SecretService {
+    storage: Arc<GenericPostgresStorage<Secret>>,
+    /// None when the server has no secrets key, in which case secrets can't be stored or used
+    cipher: Option<SecretCipher>,
+}
+
+#[async_trait]
+impl CrudService<Secret> for SecretService {
+    fn storage(&self) -> &Arc<GenericPostgresStorage<Secret>> {
+        &self.storage
+    }
+}
+
+impl SecretService {
+    pub fn new(storage: Arc<GenericPostgresStorage<Secret>>, secrets_key: Option<&str>) -> Self {
+        Self {
+            storage,
+            cipher: secrets_key.map(SecretCipher::new),
+        }
+    }
+
+    fn cipher(&self) -> Result<&SecretCipher, SecretError> {
+        self.cipher.as_ref().ok_or(SecretError::NotConfigured)
+    }
+
+    async fn get_by_name(&self, network_id: &Uuid, name: &str) -> Result<Option<Secret>> {
+        self.storage
+            .get_one(
+                EntityFilter::unfiltered()
+                    .network_ids(&[*network_id])
+                    .name(name),
+            )
+            .await
+    }
+
+    /// Store a secret under a name unique to its network
+    pub async fn create_secret(&self, request: CreateSecretRequest) -> Result<Secret> {
+        let cipher = self.cipher()?;
+
+        let name = request.name.trim().to_string();
+        if name.is_empty() {
+            return Err(SecretError::EmptyName.into());
+        }
+        if self
+            .get_by_name(&request.network_id, &name)
+            .await?
+            .is_some()
+        {
+            return Err(SecretError::NameTaken { name }.into());
+        }
+
+        let mut secret = Secret::new(SecretBase {
+            name,
+            network_id: request.network_id,
+            kind: request.value.discriminant(),
+            ciphertext: String::new(),
+        });
+        secret.base.ciphertext =
+            cipher.encrypt(&secret.id, &serde_json::to_vec(&request.value)?)?;
+
+        // A concurrent create can slip past the check above, so the unique index has the last word
+        let created = self.storage.create(&secret).await.map_err(|e| {
+            if is_unique_violation(&e) {
+                SecretError::NameTaken {
+                    name: secret.base.name.clone(),
+                }
+                .into()
+            } else {
+                e
+            }
+        })?;
+
+        tracing::info!(
+            secret_id = %created.id,
+            secret_name = %created.base.name,
+            network_id = %created.base.network_id,
+            "Secret created"
+        );
+
+        Ok(created)
+    }
+
+    /// Decrypt the secret called `name` on a network
+    pub async fn resolve(&self, network_id: &Uuid, name: &str) -> Result<SecretValue> {
+        let cipher = self.cipher()?;
+
+        let secret =
+            self.get_by_name(network_id, name)
+                .await?
+                .ok_or_else(|| SecretError::NotFound {
+                    name: name.to_string(),
+                })?;
+
+        let plaintext = cipher.decrypt(&secret.id, &secret.base.ciphertext)?;
+
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    /// Resolve every secret a request references, failing on the first one that's missing
+    pub async fn resolve_all<'a>(
+        &self,
+        network_id: &Uuid,
+        names: impl IntoIterator<Item = &'a str>,
+    ) -> Result<ResolvedSecrets> {
+        let mut resolved = ResolvedSecrets::new();
+
+        for name in names {
+            if !resolved.contains_key(name) {
+                resolved.insert(name.to_string(), self.resolve(network_id, name).await?);
+            }
+        }
+
+        Ok(resolved)
+    }
+}
@@ -0,0 +1,333 @@
+use axum::http::StatusCode;
+use email_address::EmailAddress;
+use serial_test::serial;
+
+use crate::{
+    daemon::discovery::types::base::DiscoveryPhase,
+    server::{
+        auth::service::hash_password,
+        config::ServerConfig,
+        daemons::r#impl::base::DaemonMode,
+        discovery::r#impl::{
+            base::{Discovery, DiscoveryBase},
+            target_policy::EmptyAllowlist,
+            types::{DiscoveryType, HostNamingFallback, RunType, ScanRate},
+        },
+        secrets::r#impl::{
+            api::CreateSecretRequest,
+            base::{SecretError, SecretValue},
+        },
+        shared::{
+            handlers::factory::create_router,
+            services::{factory::ServiceFactory, traits::CrudService},
+            storage::{
+                filter::EntityFilter,
+                traits::{StorableEntity, Storage},
+            },
+        },
+        users::r#impl::permissions::UserOrgPermissions,
+    },
+    tests::*,
+};
+
+const COMMUNITY: &str = "s3cr3t-community";
+
+fn secrets_config() -> ServerConfig {
+    ServerConfig {
+        secrets_key: Some("test-secrets-key".to_string()),
+        scan_empty_allowlist: Some(EmptyAllowlist::AllowAll),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_secret_value_never_returned_after_create() {
+    let (state, _container) = test_app_state_with_config(secrets_config()).await;
+    let services = &state.services;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    for (email, permissions) in [
+        ("owner@example.com", UserOrgPermissions::Owner),
+        ("member@example.com", UserOrgPermissions::Member),
+    ] {
+        services
+            .user_service
+            .create_user_with_password(
+                EmailAddress::new_unchecked(email),
+                hash_password(PASSWORD).unwrap(),
+                organization.id,
+                permissions,
+            )
+            .await
+            .unwrap();
+    }
+
+    let mut app = create_router()
+        .layer(state.storage.sessions.clone())
+        .with_state(state.clone());
+
+    let owner_cookie = &login(&mut app, "owner@example.com").await;
+    let member_cookie = &login(&mut app, "member@example.com").await;
+
+    let create = serde_json::json!({
+        "name": "core-switch",
+        "network_id": network.id,
+        "value": { "kind": "snmp_community", "community": COMMUNITY },
+    });
+
+    // Only admins can register secrets
+    let (status, _, _) = call(
+        &mut app,
+        "POST",
+        "/api/secrets",
+        Some(member_cookie),
+        Some(create.clone()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _, body) = call(
+        &mut app,
+        "POST",
+        "/api/secrets",
+        Some(owner_cookie),
+        Some(create.clone()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body.contains("core-switch"));
+    assert!(body.contains("snmp_community"));
+    assert!(!body.contains(COMMUNITY));
+    assert!(!body.contains("ciphertext"));
+
+    let (status, _, body) = call(
+        &mut app,
+        "POST",
+        "/api/secrets",
+        Some(owner_cookie),
+        Some(create),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+
+    for cookie in [owner_cookie, member_cookie] {
+        let (status, _, body) = call(&mut app, "GET", "/api/secrets", Some(cookie), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("core-switch"));
+        assert!(!body.contains(COMMUNITY));
+        assert!(!body.contains("ciphertext"));
+    }
+
+    // Nor is it stored in the clear
+    let stored = state
+        .storage
+        .secrets
+        .get_all(EntityFilter::unfiltered())
+        .await
+        .unwrap();
+    assert_eq!(stored.len(), 1);
+    assert!(!stored[0].base.ciphertext.is_empty());
+    assert!(!stored[0].base.ciphertext.contains(COMMUNITY));
+    assert!(!stored[0].base.ciphertext.contains(&hex::encode(COMMUNITY)));
+
+    // Deleting is admin-only, and secrets on other organizations' networks don't exist to them
+    let other_organization = services
+        .organization_service
+        .create(crate::tests::organization())
+        .await
+        .unwrap();
+    let other_network = services
+        .network_service
+        .create(crate::tests::network(&other_organization.id))
+        .await
+        .unwrap();
+    let foreign = services
+        .secret_service
+        .create_secret(CreateSecretRequest {
+            name: "core-switch".to_string(),
+            network_id: other_network.id,
+            value: SecretValue::SnmpCommunity {
+                community: COMMUNITY.to_string(),
+            },
+        })
+        .await
+        .unwrap();
+
+    let uri = format!("/api/secrets/{}", foreign.id);
+    let (status, _, _) = call(&mut app, "DELETE", &uri, Some(owner_cookie), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let uri = format!("/api/secrets/{}", stored[0].id);
+    let (status, _, _) = call(&mut app, "DELETE", &uri, Some(member_cookie), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _, _) = call(&mut app, "DELETE", &uri, Some(owner_cookie), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let remaining = state
+        .storage
+        .secrets
+        .get_all(EntityFilter::unfiltered())
+        .await
+        .unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].id, foreign.id);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_secrets_resolved_by_name_at_dispatch() {
+    let (storage, _container) = test_storage().await;
+    let services = ServiceFactory::new(&storage, Some(secrets_config()))
+        .await
+        .unwrap();
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let other_network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    let daemon_host = services
+        .host_service
+        .create(host(&network.id))
+        .await
+        .unwrap();
+    let mut pull_daemon = daemon(&network.id, &daemon_host.id);
+    pull_daemon.base.mode = DaemonMode::Pull;
+    let daemon = services.daemon_service.create(pull_daemon).await.unwrap();
+
+    let value = SecretValue::SnmpCommunity {
+        community: COMMUNITY.to_string(),
+    };
+    let secret = services
+        .secret_service
+        .create_secret(CreateSecretRequest {
+            name: " core-switch ".to_string(),
+            network_id: network.id,
+            value: value.clone(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(secret.base.name, "core-switch");
+
+    // Names are unique per network, not globally
+    let err = services
+        .secret_service
+        .create_secret(CreateSecretRequest {
+            name: "core-switch".to_string(),
+            network_id: network.id,
+            value: value.clone(),
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<SecretError>(),
+        Some(SecretError::NameTaken { .. })
+    ));
+    services
+        .secret_service
+        .create_secret(CreateSecretRequest {
+            name: "core-switch".to_string(),
+            network_id: other_network.id,
+            value: value.clone(),
+        })
+        .await
+        .unwrap();
+
+    let scan = || {
+        Discovery::new(DiscoveryBase {
+            discovery_type: DiscoveryType::Network {
+                subnet_ids: None,
+                host_naming_fallback: HostNamingFallback::default(),
+                scan_rate: ScanRate::default(),
+                snmp_credential: Some("core-switch".to_string()),
+                targets: Vec::new(),
+            },
+            run_type: RunType::AdHoc { last_run: None },
+            name: "SNMP scan".to_string(),
+            daemon_id: Some(daemon.id),
+            network_id: network.id,
+        })
+    };
+
+    let session = services
+        .discovery_service
+        .start_session(scan())
+        .await
+        .unwrap();
+    assert!(session.secrets.is_empty());
+
+    // The daemon receives the value, but the session itself never holds it
+    let work = services
+        .discovery_service
+        .next_session_for_daemon(&daemon.id)
+        .await
+        .unwrap();
+    assert_eq!(work.session_id, session.session_id);
+    assert_eq!(work.secrets.get("core-switch"), Some(&value));
+    let stored = services
+        .discovery_service
+        .get_session(&session.session_id)
+        .await
+        .unwrap();
+    assert!(stored.secrets.is_empty());
+
+    let mut update = work.clone();
+    update.phase = DiscoveryPhase::Complete;
+    services
+        .discovery_service
+        .update_session(update)
+        .await
+        .unwrap();
+
+    // Once the secret is deleted, sessions referencing it fail rather than being handed out
+    services.secret_service.delete(&secret.id).await.unwrap();
+    let session = services
+        .discovery_service
+        .start_session(scan())
+        .await
+        .unwrap();
+    assert!(
+        services
+            .discovery_service
+            .next_session_for_daemon(&daemon.id)
+            .await
+            .is_none()
+    );
+
+    let failed = services
+        .discovery_service
+        .get_all(EntityFilter::unfiltered().network_ids(&[network.id]))
+        .await
+        .unwrap()
+        .into_iter()
+        .find_map(|d| match d.base.run_type {
+            RunType::Historical { results } if results.session_id == session.session_id => {
+                Some(results)
+            }
+            _ => None,
+        })
+        .unwrap();
+    assert!(matches!(failed.phase, DiscoveryPhase::Failed));
+    assert!(failed.error.unwrap().contains("core-switch"));
+    assert!(failed.secrets.is_empty());
+}
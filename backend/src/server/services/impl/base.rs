@@ -380,7 +380,8 @@ impl Service {
                 )
             };
 
-            let discovery_metadata = DiscoveryMetadata::new(discovery_type.clone(), *daemon_id);
+            let discovery_metadata =
+                DiscoveryMetadata::new(discovery_type.clone(), Some(*daemon_id));
 
             let bindings: Vec<Binding> = if !result.ports.is_empty() {
                 result
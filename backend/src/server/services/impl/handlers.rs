@@ -1,6 +1,6 @@
 use crate::server::{
     services::{r#impl::base::Service, service::ServiceService},
-    shared::handlers::traits::CrudHandlers,
+    shared::handlers::traits::{CrudHandlers, EntityScope},
 };
 
 impl CrudHandlers for Service {
@@ -9,4 +9,8 @@ impl CrudHandlers for Service {
     fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
         &state.services.service_service
     }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.base.network_id)
+    }
 }
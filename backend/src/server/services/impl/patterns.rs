@@ -795,7 +795,7 @@ mod tests {
     use std::collections::HashMap;
     use std::net::IpAddr;
 
-    use crate::server::discovery::r#impl::types::{DiscoveryType, HostNamingFallback};
+    use crate::server::discovery::r#impl::types::{DiscoveryType, HostNamingFallback, ScanRate};
     use crate::server::services::r#impl::base::Service;
     use crate::server::services::r#impl::virtualization::ServiceVirtualization;
     use crate::tests::{network, organization};
@@ -861,6 +861,9 @@ mod tests {
                 discovery_type: DiscoveryType::Network {
                     subnet_ids: None,
                     host_naming_fallback: HostNamingFallback::BestService,
+                    scan_rate: ScanRate::default(),
+                    snmp_credential: None,
+                    targets: Vec::new(),
                 },
                 gateway_ips: vec![],
                 endpoint_responses,
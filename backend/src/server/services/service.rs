@@ -90,6 +90,9 @@ impl ServiceService {
                     service,
                     existing_service,
                 );
+                // upsert_service takes the existing service's lock, which is this one when a
+                // daemon resubmits a service it already reported
+                drop(_guard);
                 self.upsert_service(existing_service, service).await?
             }
             _ => {
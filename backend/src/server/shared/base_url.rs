@@ -0,0 +1,139 @@
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{HeaderMap, header, request::Parts},
+};
+use cidr::IpCidr;
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Once,
+};
+
+use crate::server::config::AppState;
+
+const FORWARDED_PROTO_HEADER: &str = "x-forwarded-proto";
+
+static UNCONFIGURED_WARNING: Once = Once::new();
+
+/// `url` without trailing slashes, so paths can be appended as `{base}/api/...`
+pub fn normalize_base_url(url: &str) -> String {
+    url.trim().trim_end_matches('/').to_string()
+}
+
+/// The externally-visible base URL for links back to this server. A configured `public_base_url`
+/// always wins; otherwise it's rebuilt from the request's `Host`, which the client controls, so a
+/// warning is logged the first time that happens. `X-Forwarded-Proto` is only believed from
+/// `trusted_proxies`.
+pub fn request_base_url(
+    configured: Option<&str>,
+    peer: Option<IpAddr>,
+    headers: &HeaderMap,
+    trusted_proxies: &[IpCidr],
+) -> Option<String> {
+    if let Some(configured) = configured {
+        return Some(normalize_base_url(configured));
+    }
+
+    let host = headers.get(header::HOST)?.to_str().ok()?;
+
+    UNCONFIGURED_WARNING.call_once(|| {
+        tracing::warn!(
+            "public_base_url is not set; building links from the request's Host header, which \
+             clients can spoof"
+        );
+    });
+
+    let from_trusted_proxy =
+        peer.is_some_and(|peer| trusted_proxies.iter().any(|cidr| cidr.contains(&peer)));
+    let scheme = headers
+        .get(FORWARDED_PROTO_HEADER)
+        .filter(|_| from_trusted_proxy)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .map(str::trim)
+        .filter(|proto| matches!(*proto, "http" | "https"))
+        .unwrap_or("http");
+
+    Some(normalize_base_url(&format!("{}://{}", scheme, host)))
+}
+
+/// The base URL links in a response should use (see [`request_base_url`]). None when it isn't
+/// configured and the request has no usable `Host`.
+pub struct PublicBaseUrl(pub Option<String>);
+
+impl PublicBaseUrl {
+    /// `path` (starting with `/`) as an absolute URL
+    pub fn join(&self, path: &str) -> Option<String> {
+        self.0.as_ref().map(|base| format!("{}{}", base, path))
+    }
+}
+
+impl<S> FromRequestParts<S> for PublicBaseUrl
+where
+    S: Send + Sync + AsRef<AppState>,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = &state.as_ref().config;
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        Ok(PublicBaseUrl(request_base_url(
+            config.public_base_url.as_deref(),
+            peer,
+            &parts.headers,
+            &config.trusted_proxies,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(values: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in values {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_configured_url_wins_and_forwarded_proto_needs_trusted_proxy() {
+        let proxies: Vec<IpCidr> = vec!["10.0.0.0/24".parse().unwrap()];
+        let request = headers(&[("host", "attacker.example"), ("x-forwarded-proto", "https")]);
+
+        assert_eq!(
+            request_base_url(
+                Some("https://netvisor.example.com//"),
+                None,
+                &request,
+                &proxies
+            ),
+            Some("https://netvisor.example.com".to_string())
+        );
+
+        // Without a configured URL, only a trusted proxy can upgrade the scheme
+        assert_eq!(
+            request_base_url(
+                None,
+                Some("203.0.113.9".parse().unwrap()),
+                &request,
+                &proxies
+            ),
+            Some("http://attacker.example".to_string())
+        );
+        assert_eq!(
+            request_base_url(None, Some("10.0.0.2".parse().unwrap()), &request, &proxies),
+            Some("https://attacker.example".to_string())
+        );
+        assert_eq!(
+            request_base_url(None, None, &HeaderMap::new(), &proxies),
+            None
+        );
+    }
+}
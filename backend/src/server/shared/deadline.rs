@@ -0,0 +1,103 @@
+use crate::server::{config::AppState, shared::types::api::ApiError};
+use axum::{
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{future::Future, sync::Arc, time::Duration};
+use tokio::time::Instant;
+
+/// Header clients can use to set their own budget (milliseconds) for a request
+pub const DEADLINE_HEADER: &str = "x-request-timeout-ms";
+
+/// Routes which hold the connection open indefinitely and never get a deadline
+const STREAMING_PATHS: &[&str] = &["/api/discovery/stream", "/api/notifications/stream"];
+
+tokio::task_local! {
+    static REQUEST_DEADLINE: RequestDeadline;
+}
+
+/// Absolute point in time by which a request must be fully handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestDeadline(pub Instant);
+
+impl RequestDeadline {
+    pub fn after(budget: Duration) -> Self {
+        Self(Instant::now() + budget)
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+
+    /// Deadline of the request currently being handled on this task, if any
+    pub fn current() -> Option<Self> {
+        REQUEST_DEADLINE.try_with(|d| *d).ok()
+    }
+
+    /// Run `fut` with this deadline visible to everything it calls (storage in particular)
+    pub async fn scope<F: Future>(self, fut: F) -> F::Output {
+        REQUEST_DEADLINE.scope(self, fut).await
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Request deadline exceeded")]
+pub struct DeadlineExceeded;
+
+/// Await `fut`, giving up once the current request deadline passes
+pub async fn enforce<F, T, E>(fut: F) -> Result<T, anyhow::Error>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Into<anyhow::Error>,
+{
+    match RequestDeadline::current() {
+        Some(deadline) => match tokio::time::timeout_at(deadline.0, fut).await {
+            Ok(result) => result.map_err(Into::into),
+            Err(_) => Err(DeadlineExceeded.into()),
+        },
+        None => fut.await.map_err(Into::into),
+    }
+}
+
+/// Attach a deadline to each API request, taken from [`DEADLINE_HEADER`] or the configured
+/// default. The header can only shorten the configured budget, never extend it.
+pub async fn request_deadline(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let is_streaming = STREAMING_PATHS.contains(&request.uri().path())
+        || request
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("text/event-stream"));
+
+    if is_streaming {
+        return next.run(request).await;
+    }
+
+    let requested = request
+        .headers()
+        .get(DEADLINE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+
+    let budget_ms = match (requested, state.config.request_timeout_ms) {
+        (Some(requested), Some(default)) => Some(requested.min(default)),
+        (requested, default) => requested.or(default),
+    };
+
+    let Some(budget_ms) = budget_ms else {
+        return next.run(request).await;
+    };
+
+    let deadline = RequestDeadline::after(Duration::from_millis(budget_ms));
+
+    match tokio::time::timeout_at(deadline.0, deadline.scope(next.run(request))).await {
+        Ok(response) => response,
+        Err(_) => ApiError::from(anyhow::Error::from(DeadlineExceeded)).into_response(),
+    }
+}
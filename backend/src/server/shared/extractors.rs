@@ -0,0 +1,450 @@
+use crate::server::{
+    auth::middleware::api_key_from_headers,
+    config::{AppState, ServerConfig},
+    daemons::r#impl::signing::ResultSignatureVerifier,
+    shared::types::api::ApiError,
+};
+use axum::{
+    Extension, Json,
+    body::{Body, Bytes},
+    extract::{FromRef, FromRequest, OptionalFromRequest, Request},
+    http::{HeaderMap, StatusCode, header},
+};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use serde::de::DeserializeOwned;
+use std::io::Read;
+use std::sync::Arc;
+
+/// Limits applied to JSON request bodies before they are deserialized.
+///
+/// Defaults come from the server config. A route which legitimately takes large payloads can
+/// override them by layering an `Extension(JsonLimits { .. })` onto the route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonLimits {
+    pub max_bytes: usize,
+    /// Cap on a compressed body once inflated, which then stands in for `max_bytes`
+    pub max_decompressed_bytes: usize,
+    pub max_depth: usize,
+    pub max_array_len: usize,
+}
+
+impl From<&ServerConfig> for JsonLimits {
+    fn from(config: &ServerConfig) -> Self {
+        Self {
+            max_bytes: config.json_max_bytes,
+            max_decompressed_bytes: config.json_max_decompressed_bytes,
+            max_depth: config.json_max_depth,
+            max_array_len: config.json_max_array_len,
+        }
+    }
+}
+
+impl FromRef<Arc<AppState>> for JsonLimits {
+    fn from_ref(state: &Arc<AppState>) -> Self {
+        JsonLimits::from(&state.config)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum JsonLimitExceeded {
+    #[error("Request body exceeds the maximum size of {0} bytes")]
+    TooLarge(usize),
+    #[error("JSON nesting exceeds the maximum depth of {0}")]
+    TooDeep(usize),
+    #[error("JSON array exceeds the maximum length of {0}")]
+    ArrayTooLong(usize),
+}
+
+impl From<JsonLimitExceeded> for ApiError {
+    fn from(err: JsonLimitExceeded) -> Self {
+        let status = match err {
+            JsonLimitExceeded::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            JsonLimitExceeded::TooDeep(_) | JsonLimitExceeded::ArrayTooLong(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+        };
+        ApiError::new(status, err.to_string())
+    }
+}
+
+/// `Content-Encoding` of a request body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Identity,
+    Gzip,
+    Deflate,
+}
+
+impl ContentEncoding {
+    /// The body's encoding, or 415 for one the server can't decode
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, ApiError> {
+        let Some(value) = headers.get(header::CONTENT_ENCODING) else {
+            return Ok(Self::Identity);
+        };
+
+        match value
+            .to_str()
+            .map(|v| v.trim().to_ascii_lowercase())
+            .as_deref()
+        {
+            Ok("" | "identity") => Ok(Self::Identity),
+            Ok("gzip" | "x-gzip") => Ok(Self::Gzip),
+            Ok("deflate") => Ok(Self::Deflate),
+            _ => Err(ApiError::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!(
+                    "Unsupported Content-Encoding {:?}, expected gzip or deflate",
+                    value
+                ),
+            )),
+        }
+    }
+
+    /// Inflate `bytes`, giving up as soon as the output passes `max_bytes` so a decompression
+    /// bomb is never expanded in full
+    pub fn decode(self, bytes: Bytes, max_bytes: usize) -> Result<Bytes, ApiError> {
+        let decoder: Box<dyn Read + '_> = match self {
+            Self::Identity => return Ok(bytes),
+            Self::Gzip => Box::new(GzDecoder::new(&bytes[..])),
+            Self::Deflate => Box::new(ZlibDecoder::new(&bytes[..])),
+        };
+
+        let mut decoded = Vec::new();
+        decoder
+            .take(max_bytes as u64 + 1)
+            .read_to_end(&mut decoded)
+            .map_err(|e| ApiError::bad_request(&format!("Failed to decode request body: {}", e)))?;
+
+        if decoded.len() > max_bytes {
+            return Err(JsonLimitExceeded::TooLarge(max_bytes).into());
+        }
+
+        Ok(decoded.into())
+    }
+}
+
+impl JsonLimits {
+    /// Limits for `req`: a per-route override if one is layered on, else the state's defaults
+    fn for_request<S>(req: &Request, state: &S) -> Self
+    where
+        JsonLimits: FromRef<S>,
+    {
+        req.extensions()
+            .get::<JsonLimits>()
+            .copied()
+            .unwrap_or_else(|| JsonLimits::from_ref(state))
+    }
+
+    /// Scan the raw bytes for nesting depth and array length without building any values, so
+    /// abusive payloads are rejected before deserialization allocates for them. Syntax errors are
+    /// left for the deserializer to report.
+    pub fn check(&self, bytes: &[u8]) -> Result<(), JsonLimitExceeded> {
+        if bytes.len() > self.max_bytes {
+            return Err(JsonLimitExceeded::TooLarge(self.max_bytes));
+        }
+
+        // One entry per open container: Some(separators seen) for arrays, None for objects
+        let mut open: Vec<Option<usize>> = Vec::new();
+        let mut in_string = false;
+        let mut escaped = false;
+
+        for &byte in bytes {
+            if in_string {
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+
+            match byte {
+                b'"' => in_string = true,
+                b'[' | b'{' => {
+                    open.push((byte == b'[').then_some(0));
+                    if open.len() > self.max_depth {
+                        return Err(JsonLimitExceeded::TooDeep(self.max_depth));
+                    }
+                }
+                b']' | b'}' => {
+                    open.pop();
+                }
+                b',' => {
+                    if let Some(Some(separators)) = open.last_mut() {
+                        *separators += 1;
+                        // n separators means n + 1 elements
+                        if *separators >= self.max_array_len {
+                            return Err(JsonLimitExceeded::ArrayTooLong(self.max_array_len));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Buffer a body and decode it per its `Content-Encoding`, returning it with the limits that
+    /// apply to the decoded bytes. The body as sent is capped at `max_bytes` either way.
+    pub async fn read_body(
+        self,
+        headers: &HeaderMap,
+        body: Body,
+    ) -> Result<(Bytes, Self), ApiError> {
+        let encoding = ContentEncoding::from_headers(headers)?;
+
+        // Stop reading as soon as the size limit is hit rather than buffering the whole body
+        let bytes = axum::body::to_bytes(body, self.max_bytes)
+            .await
+            .map_err(|_| JsonLimitExceeded::TooLarge(self.max_bytes))?;
+
+        if encoding == ContentEncoding::Identity {
+            return Ok((bytes, self));
+        }
+
+        let max_bytes = self.max_decompressed_bytes;
+        let bytes = tokio::task::spawn_blocking(move || encoding.decode(bytes, max_bytes))
+            .await
+            .map_err(|e| ApiError::internal_error(&e.to_string()))??;
+
+        Ok((bytes, Self { max_bytes, ..self }))
+    }
+}
+
+/// Drop-in replacement for axum's `Json` extractor which enforces [`JsonLimits`] on the raw body
+/// before deserializing it. Gzip and deflate bodies are accepted with a matching
+/// `Content-Encoding` and decoded first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LimitedJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for LimitedJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+    JsonLimits: FromRef<S>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let limits = JsonLimits::for_request(&req, state);
+
+        let is_json = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<mime_guess::mime::Mime>().ok())
+            .is_some_and(|mime| {
+                mime.type_() == "application"
+                    && (mime.subtype() == "json" || mime.suffix().is_some_and(|s| s == "json"))
+            });
+        if !is_json {
+            return Err(ApiError::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Expected request with `Content-Type: application/json`".to_string(),
+            ));
+        }
+
+        let (parts, body) = req.into_parts();
+        let (bytes, limits) = limits.read_body(&parts.headers, body).await?;
+
+        limits.check(&bytes)?;
+
+        let Json(value) = Json::<T>::from_bytes(&bytes)
+            .map_err(|rejection| ApiError::new(rejection.status(), rejection.body_text()))?;
+
+        Ok(LimitedJson(value))
+    }
+}
+
+/// Like axum's `Option<Json<T>>`: `None` when the request has no body content type at all
+impl<T, S> OptionalFromRequest<S> for LimitedJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+    JsonLimits: FromRef<S>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Option<Self>, Self::Rejection> {
+        if req.headers().get(header::CONTENT_TYPE).is_none() {
+            return Ok(None);
+        }
+
+        <Self as FromRequest<S>>::from_request(req, state)
+            .await
+            .map(Some)
+    }
+}
+
+/// [`LimitedJson`] for daemon result payloads, which also verifies the payload signature (see
+/// [`ResultSignatureVerifier`]). Requests authenticated by session rather than API key carry no
+/// signature and are passed straight through.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SignedJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for SignedJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+    JsonLimits: FromRef<S>,
+    ResultSignatureVerifier: FromRef<S>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let api_key = api_key_from_headers(req.headers())
+            .ok()
+            .flatten()
+            .map(str::to_owned);
+
+        let Some(api_key) = api_key else {
+            let LimitedJson(value) =
+                <LimitedJson<T> as FromRequest<S>>::from_request(req, state).await?;
+            return Ok(SignedJson(value));
+        };
+
+        // The signature covers the exact body bytes before any compression, so buffer and decode
+        // them before deserializing
+        let limits = JsonLimits::for_request(&req, state);
+        let (mut parts, body) = req.into_parts();
+        let (bytes, limits) = limits.read_body(&parts.headers, body).await?;
+
+        ResultSignatureVerifier::from_ref(state).verify(&api_key, &parts.headers, &bytes)?;
+
+        // Hand on the decoded body, along with the limits that apply to it
+        parts.headers.remove(header::CONTENT_ENCODING);
+        parts.extensions.insert(limits);
+        let req = Request::from_parts(parts, Body::from(bytes));
+        let LimitedJson(value) =
+            <LimitedJson<T> as FromRequest<S>>::from_request(req, state).await?;
+        Ok(SignedJson(value))
+    }
+}
+
+impl<T, S> OptionalFromRequest<S> for SignedJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+    JsonLimits: FromRef<S>,
+    ResultSignatureVerifier: FromRef<S>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Option<Self>, Self::Rejection> {
+        if req.headers().get(header::CONTENT_TYPE).is_none() {
+            return Ok(None);
+        }
+
+        <Self as FromRequest<S>>::from_request(req, state)
+            .await
+            .map(Some)
+    }
+}
+
+/// Override the JSON limits for a single route, e.g. `post(handler).layer(json_limits(..))`
+pub fn json_limits(limits: JsonLimits) -> Extension<JsonLimits> {
+    Extension(limits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, routing::post};
+    use serde::{Deserialize, Deserializer};
+    use std::cell::Cell;
+    use tower::Service;
+
+    thread_local! {
+        // Tests run on a current-thread runtime, so handlers share the test's thread
+        static DESERIALIZED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Records whether deserialization was ever attempted
+    struct Tracked;
+
+    impl<'de> Deserialize<'de> for Tracked {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            DESERIALIZED.set(DESERIALIZED.get() + 1);
+            serde::de::IgnoredAny::deserialize(deserializer)?;
+            Ok(Tracked)
+        }
+    }
+
+    fn app() -> Router {
+        let limits = JsonLimits {
+            max_bytes: 1024,
+            max_decompressed_bytes: 4096,
+            max_depth: 4,
+            max_array_len: 10,
+        };
+
+        Router::new()
+            .route("/", post(|LimitedJson(_): LimitedJson<Tracked>| async {}))
+            .route(
+                "/import",
+                post(|LimitedJson(_): LimitedJson<Tracked>| async {}).layer(json_limits(
+                    JsonLimits {
+                        max_array_len: 1_000,
+                        max_bytes: 16 * 1024,
+                        ..limits
+                    },
+                )),
+            )
+            .with_state(limits)
+    }
+
+    async fn post_json(uri: &str, body: String) -> StatusCode {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+
+        app().call(request).await.unwrap().status()
+    }
+
+    fn array_of(len: usize) -> String {
+        format!("[{}]", vec!["1"; len].join(","))
+    }
+
+    #[tokio::test]
+    async fn test_over_limit_payloads_rejected_before_deserializing() {
+        let before = DESERIALIZED.get();
+
+        let too_deep = format!("{}{}", "[".repeat(5), "]".repeat(5));
+        assert_eq!(
+            post_json("/", too_deep).await,
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+
+        assert_eq!(
+            post_json("/", array_of(11)).await,
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+
+        let too_large = format!("\"{}\"", "a".repeat(2048));
+        assert_eq!(
+            post_json("/", too_large).await,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+
+        assert_eq!(DESERIALIZED.get(), before);
+
+        // Brackets inside strings don't count towards depth
+        let nested_in_string = format!("{{\"a\": \"{}\"}}", "[[[[[[");
+        assert_eq!(post_json("/", nested_in_string).await, StatusCode::OK);
+        assert_eq!(post_json("/", array_of(10)).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_route_can_override_limits() {
+        assert_eq!(post_json("/import", array_of(500)).await, StatusCode::OK);
+        assert_eq!(
+            post_json("/", array_of(500)).await,
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use crate::server::{config::AppState, shared::types::api::ApiError};
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::{Method, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Query parameter selecting which fields of a response to return
+pub const FIELDS_PARAM: &str = "fields";
+
+/// Routes whose responses can be projected with [`FIELDS_PARAM`]
+const FIELD_SELECTABLE_PATHS: &[&str] = &["/api/daemons", "/api/hosts", "/api/diagnostics"];
+
+/// Largest response body buffered for projection. Bigger responses are returned whole.
+const MAX_PROJECTED_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// What happens when a request selects a field the response doesn't have
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnknownFields {
+    #[default]
+    Ignore,
+    Reject,
+}
+
+/// Requested fields as a tree of dot path segments. A node without children selects the whole
+/// value at that path.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FieldSelection(BTreeMap<String, FieldSelection>);
+
+impl FieldSelection {
+    /// Parse a comma separated list of dot paths, e.g. `id,capabilities.has_docker_socket`.
+    /// None when nothing is selected.
+    pub fn parse(fields: &str) -> Option<Self> {
+        let mut selection = Self::default();
+
+        for path in fields.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let mut node = &mut selection;
+            for segment in path.split('.').filter(|s| !s.is_empty()) {
+                node = node.0.entry(segment.to_string()).or_default();
+            }
+        }
+
+        (!selection.0.is_empty()).then_some(selection)
+    }
+
+    /// Keep only the selected fields of `value`. Arrays are projected element by element, and a
+    /// path reaching past a scalar keeps the scalar.
+    pub fn project(&self, value: Value) -> Value {
+        if self.0.is_empty() {
+            return value;
+        }
+
+        match value {
+            Value::Array(items) => {
+                Value::Array(items.into_iter().map(|item| self.project(item)).collect())
+            }
+            Value::Object(mut object) => {
+                let mut projected = Map::new();
+                for (key, selection) in &self.0 {
+                    if let Some(field) = object.remove(key) {
+                        projected.insert(key.clone(), selection.project(field));
+                    }
+                }
+                Value::Object(projected)
+            }
+            other => other,
+        }
+    }
+
+    /// Selected paths which don't exist in `value`. A path counts as existing when any element of
+    /// a list has it, and nothing is unknown in an empty list.
+    pub fn unknown_paths(&self, value: &Value) -> Vec<String> {
+        let mut unknown = Vec::new();
+        self.collect_unknown(value, "", &mut unknown);
+        unknown
+    }
+
+    fn collect_unknown(&self, value: &Value, prefix: &str, unknown: &mut Vec<String>) {
+        for (key, selection) in &self.0 {
+            let path = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", prefix, key)
+            };
+
+            let candidates: Vec<&Value> = match value {
+                Value::Array(items) if items.is_empty() => continue,
+                Value::Array(items) => items.iter().filter_map(|i| i.get(key)).collect(),
+                Value::Object(object) => object.get(key).into_iter().collect(),
+                _ => Vec::new(),
+            };
+
+            if candidates.is_empty() {
+                unknown.push(path);
+                continue;
+            }
+
+            // Any element that has the nested paths is enough
+            let nested = candidates
+                .into_iter()
+                .map(|candidate| {
+                    let mut nested = Vec::new();
+                    selection.collect_unknown(candidate, &path, &mut nested);
+                    nested
+                })
+                .min_by_key(Vec::len)
+                .unwrap_or_default();
+            unknown.extend(nested);
+        }
+    }
+}
+
+fn requested_fields(request: &Request) -> Option<FieldSelection> {
+    let query = request.uri().query()?;
+
+    url::form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == FIELDS_PARAM)
+        .and_then(|(_, fields)| FieldSelection::parse(&fields))
+}
+
+/// Project the `data` of JSON responses to the fields requested with [`FIELDS_PARAM`], so clients
+/// that only need a few fields of heavy objects don't download all of them
+pub async fn sparse_fields(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let selectable = request.method() == Method::GET
+        && FIELD_SELECTABLE_PATHS
+            .iter()
+            .any(|path| request.uri().path().starts_with(path));
+
+    let Some(selection) = requested_fields(&request).filter(|_| selectable) else {
+        return next.run(request).await;
+    };
+
+    let response = next.run(request).await;
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let too_large = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<usize>().ok())
+        .is_some_and(|len| len > MAX_PROJECTED_BODY_BYTES);
+    if !response.status().is_success() || !is_json || too_large {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_PROJECTED_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return ApiError::internal_error(&format!("Failed to read response: {}", e))
+                .into_response();
+        }
+    };
+    let Ok(mut json) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+
+    if let Some(data) = json.get_mut("data").map(Value::take) {
+        if state.config.unknown_response_fields == UnknownFields::Reject {
+            let unknown = selection.unknown_paths(&data);
+            if !unknown.is_empty() {
+                return ApiError::bad_request(&format!("Unknown fields: {}", unknown.join(", ")))
+                    .into_response();
+            }
+        }
+
+        json["data"] = selection.project(data);
+    }
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(json.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_nested_fields_projected() {
+        let selection =
+            FieldSelection::parse("id, capabilities.has_docker_socket,missing").unwrap();
+        let daemons = json!([
+            {
+                "id": 1,
+                "ip": "10.0.0.1",
+                "capabilities": {"has_docker_socket": true, "interfaced_subnet_ids": []}
+            },
+            {"id": 2, "ip": "10.0.0.2", "capabilities": null}
+        ]);
+
+        assert_eq!(
+            selection.unknown_paths(&daemons),
+            vec!["missing".to_string()]
+        );
+        assert_eq!(
+            selection.project(daemons),
+            json!([
+                {"id": 1, "capabilities": {"has_docker_socket": true}},
+                {"id": 2, "capabilities": null}
+            ])
+        );
+
+        assert!(FieldSelection::parse(" , ").is_none());
+        let nested = FieldSelection::parse("capabilities.nope").unwrap();
+        assert_eq!(
+            nested.unknown_paths(&json!({"capabilities": {"has_docker_socket": true}})),
+            vec!["capabilities.nope".to_string()]
+        );
+    }
+}
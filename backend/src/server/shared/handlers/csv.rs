@@ -0,0 +1,113 @@
+use axum::{
+    body::{Body, Bytes},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use futures::{Stream, StreamExt};
+
+pub const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// A type that can be written as one CSV record under fixed column headers
+pub trait CsvRow {
+    fn headers() -> &'static [&'static str];
+    fn fields(&self) -> Vec<String>;
+}
+
+/// Quote `field` if it holds a separator, quote or line break, doubling any quotes
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn record<'a>(fields: impl IntoIterator<Item = &'a str>) -> Bytes {
+    let mut line = fields.into_iter().map(escape).collect::<Vec<_>>().join(",");
+    line.push_str("\r\n");
+    Bytes::from(line)
+}
+
+/// Respond with a CSV header line followed by one record per row as `rows` yields them, so
+/// memory use doesn't grow with the result size. CSV has no way to report an error in-band, so a
+/// mid-stream error aborts the response and the client sees a truncated download rather than a
+/// silently incomplete file.
+pub fn csv_response<T, S>(filename: &str, rows: S) -> Response
+where
+    T: CsvRow + Send + 'static,
+    S: Stream<Item = Result<T, anyhow::Error>> + Send + 'static,
+{
+    let body = async_stream::stream! {
+        yield Ok(record(T::headers().iter().copied()));
+
+        let mut rows = std::pin::pin!(rows);
+        let mut count = 0;
+        while let Some(row) = rows.next().await {
+            match row {
+                Ok(row) => {
+                    count += 1;
+                    let fields = row.fields();
+                    yield Ok(record(fields.iter().map(String::as_str)));
+                }
+                Err(e) => {
+                    tracing::error!("CSV stream aborted after {} rows: {}", count, e);
+                    yield Err(std::io::Error::other(e.to_string()));
+                    break;
+                }
+            }
+        }
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, CSV_CONTENT_TYPE.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+
+    struct Row(&'static str, &'static str);
+
+    impl CsvRow for Row {
+        fn headers() -> &'static [&'static str] {
+            &["name", "note"]
+        }
+
+        fn fields(&self) -> Vec<String> {
+            vec![self.0.to_string(), self.1.to_string()]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_csv_response_escapes_fields() {
+        let rows = futures::stream::iter([
+            Ok(Row("plain", "a, b")),
+            Ok(Row("quoted \"x\"", "line\nbreak")),
+        ]);
+        let response = csv_response("rows.csv", rows);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], CSV_CONTENT_TYPE);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            "name,note\r\nplain,\"a, b\"\r\n\"quoted \"\"x\"\"\",\"line\nbreak\"\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_csv_is_just_headers() {
+        let response = csv_response("rows.csv", futures::stream::empty::<anyhow::Result<Row>>());
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"name,note\r\n");
+    }
+}
@@ -2,7 +2,7 @@ use crate::server::api_keys::r#impl::base::{ApiKey, ApiKeyBase};
 use crate::server::auth::middleware::{AuthenticatedUser, RequireOwner};
 use crate::server::billing::types::base::BillingPlan;
 use crate::server::billing::types::features::Feature;
-use crate::server::config::PublicConfigResponse;
+use crate::server::config::{PublicConfigResponse, ServerFeatures};
 use crate::server::discovery::r#impl::types::DiscoveryType;
 use crate::server::github::handlers::get_stars;
 use crate::server::groups::r#impl::types::GroupType;
@@ -12,6 +12,7 @@ use crate::server::organizations::r#impl::base::Organization;
 use crate::server::services::definitions::ServiceDefinitionRegistry;
 use crate::server::shared::entities::Entity;
 use crate::server::shared::services::traits::CrudService;
+use crate::server::shared::storage::health::{HEALTH_CHECK_TIMEOUT, StorageHealth};
 use crate::server::shared::storage::traits::StorableEntity;
 use crate::server::shared::types::api::{ApiError, ApiResult};
 use crate::server::shared::types::metadata::{MetadataProvider, MetadataRegistry};
@@ -19,16 +20,26 @@ use crate::server::subnets::r#impl::types::SubnetType;
 use crate::server::topology::types::edges::EdgeType;
 use crate::server::users::r#impl::permissions::UserOrgPermissions;
 use crate::server::{
-    auth::handlers as auth_handlers, billing::handlers as billing_handlers, config::AppState,
-    daemons::handlers as daemon_handlers, discovery::handlers as discovery_handlers,
-    groups::handlers as group_handlers, hosts::handlers as host_handlers,
-    networks::handlers as network_handlers, organizations::handlers as organization_handlers,
+    audit::handlers as audit_handlers, auth::handlers as auth_handlers,
+    billing::handlers as billing_handlers, bootstrap_tokens::handlers as bootstrap_token_handlers,
+    check_dependencies::handlers as check_dependency_handlers,
+    check_suites::handlers as check_suite_handlers, config::AppState,
+    daemons::handlers as daemon_handlers, diagnostics::handlers as diagnostic_handlers,
+    discovery::handlers as discovery_handlers,
+    discovery_webhooks::handlers as discovery_webhook_handlers,
+    group_rules::handlers as group_rule_handlers, groups::handlers as group_handlers,
+    hosts::handlers as host_handlers, networks::handlers as network_handlers,
+    notification_channels::handlers as notification_channel_handlers,
+    notification_subscriptions::handlers as notification_subscription_handlers,
+    notifications::handlers as notification_handlers,
+    organizations::handlers as organization_handlers, secrets::handlers as secret_handlers,
     services::handlers as service_handlers, shared::types::api::ApiResponse,
     subnets::handlers as subnet_handlers, topology::handlers as topology_handlers,
     users::handlers as user_handlers,
 };
 use anyhow::anyhow;
-use axum::extract::State;
+use axum::extract::{OriginalUri, State};
+use axum::http::{Method, StatusCode};
 use axum::routing::post;
 use axum::{Json, Router, routing::get};
 use serde::{Deserialize, Serialize};
@@ -36,24 +47,72 @@ use std::sync::Arc;
 use strum::{IntoDiscriminant, IntoEnumIterator};
 
 pub fn create_router() -> Router<Arc<AppState>> {
+    Router::new().nest("/api", create_api_router())
+}
+
+/// All API routes, relative to `/api`. Unmatched paths and methods get JSON errors here so they
+/// never fall through to the web UI fallback.
+fn create_api_router() -> Router<Arc<AppState>> {
     Router::new()
-        .nest("/api/hosts", host_handlers::create_router())
-        .nest("/api/groups", group_handlers::create_router())
-        .nest("/api/daemons", daemon_handlers::create_router())
-        .nest("/api/discovery", discovery_handlers::create_router())
-        .nest("/api/subnets", subnet_handlers::create_router())
-        .nest("/api/topology", topology_handlers::create_router())
-        .nest("/api/services", service_handlers::create_router())
-        .nest("/api/networks", network_handlers::create_router())
-        .nest("/api/users", user_handlers::create_router())
-        .nest("/api/billing", billing_handlers::create_router())
-        .nest("/api/auth", auth_handlers::create_router())
-        .nest("/api/organizations", organization_handlers::create_router())
-        .route("/api/health", get(get_health))
-        .route("/api/metadata", get(get_metadata_registry))
-        .route("/api/config", get(get_public_config))
-        .route("/api/github-stars", get(get_stars))
-        .route("/api/onboarding", post(onboarding))
+        .nest("/audit", audit_handlers::create_router())
+        .nest("/hosts", host_handlers::create_router())
+        .nest("/groups", group_handlers::create_router())
+        .nest("/group-rules", group_rule_handlers::create_router())
+        .nest("/daemons", daemon_handlers::create_router())
+        .nest("/discovery", discovery_handlers::create_router())
+        .nest(
+            "/discovery-webhooks",
+            discovery_webhook_handlers::create_router(),
+        )
+        .nest("/diagnostics", diagnostic_handlers::create_router())
+        .nest("/metrics", diagnostic_handlers::create_metrics_router())
+        .nest(
+            "/check-dependencies",
+            check_dependency_handlers::create_router(),
+        )
+        .nest("/check-suites", check_suite_handlers::create_router())
+        .nest("/secrets", secret_handlers::create_router())
+        .nest("/subnets", subnet_handlers::create_router())
+        .nest("/topology", topology_handlers::create_router())
+        .nest("/services", service_handlers::create_router())
+        .nest("/networks", network_handlers::create_router())
+        .nest("/users", user_handlers::create_router())
+        .nest("/notifications", notification_handlers::create_router())
+        .nest(
+            "/notification-channels",
+            notification_channel_handlers::create_router(),
+        )
+        .nest(
+            "/notification-subscriptions",
+            notification_subscription_handlers::create_router(),
+        )
+        .nest("/billing", billing_handlers::create_router())
+        .nest(
+            "/bootstrap-tokens",
+            bootstrap_token_handlers::create_router(),
+        )
+        .nest("/auth", auth_handlers::create_router())
+        .nest("/organizations", organization_handlers::create_router())
+        .route("/health", get(get_health))
+        .route("/ready", get(get_ready))
+        .route("/metadata", get(get_metadata_registry))
+        .route("/config", get(get_public_config))
+        .route("/features", get(get_features))
+        .route("/github-stars", get(get_stars))
+        .route("/onboarding", post(onboarding))
+        .method_not_allowed_fallback(api_method_not_allowed)
+        .fallback(api_not_found)
+}
+
+async fn api_not_found(OriginalUri(uri): OriginalUri) -> ApiError {
+    ApiError::not_found(format!("No API route for {}", uri.path()))
+}
+
+async fn api_method_not_allowed(method: Method, OriginalUri(uri): OriginalUri) -> ApiError {
+    ApiError::new(
+        StatusCode::METHOD_NOT_ALLOWED,
+        format!("Method {} not allowed for {}", method, uri.path()),
+    )
 }
 
 async fn get_metadata_registry(_user: AuthenticatedUser) -> Json<ApiResponse<MetadataRegistry>> {
@@ -84,17 +143,39 @@ async fn get_health() -> Json<ApiResponse<String>> {
     Json(ApiResponse::success("Netvisor Server Running".to_string()))
 }
 
+/// Ready once every storage component answers, with each component's status so a failing table
+/// or connection can be pinned down
+async fn get_ready(
+    State(state): State<Arc<AppState>>,
+) -> (StatusCode, Json<ApiResponse<StorageHealth>>) {
+    let health = state.storage.health_check(HEALTH_CHECK_TIMEOUT).await;
+
+    if health.healthy {
+        return (StatusCode::OK, Json(ApiResponse::success(health)));
+    }
+
+    let error = format!(
+        "Storage unavailable: {}",
+        health.unhealthy_components().join(", ")
+    );
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(ApiResponse {
+            success: false,
+            data: Some(health),
+            error: Some(error),
+            code: None,
+        }),
+    )
+}
+
 pub async fn get_public_config(
     State(state): State<Arc<AppState>>,
 ) -> Json<ApiResponse<PublicConfigResponse>> {
     Json(ApiResponse::success(PublicConfigResponse {
         server_port: state.config.server_port,
         disable_registration: state.config.disable_registration,
-        oidc_enabled: state.config.oidc_client_id.is_some()
-            && state.config.oidc_client_secret.is_some()
-            && state.config.oidc_issuer_url.is_some()
-            && state.config.oidc_provider_name.is_some()
-            && state.config.oidc_redirect_url.is_some(),
+        oidc_enabled: state.config.oidc_enabled(),
         oidc_provider_name: state
             .config
             .oidc_provider_name
@@ -102,14 +183,15 @@ pub async fn get_public_config(
             .unwrap_or("OIDC Provider".to_string()),
         billing_enabled: state.config.stripe_secret.is_some(),
         has_integrated_daemon: state.config.integrated_daemon_url.is_some(),
-        has_email_service: state.config.smtp_password.is_some()
-            && state.config.smtp_username.is_some()
-            && state.config.smtp_email.is_some()
-            && state.config.smtp_relay.is_some(),
+        has_email_service: state.config.email_enabled(),
         public_url: state.config.public_url.clone(),
     }))
 }
 
+pub async fn get_features(State(state): State<Arc<AppState>>) -> Json<ApiResponse<ServerFeatures>> {
+    Json(ApiResponse::success(ServerFeatures::from(&state.config)))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OnboardingRequest {
     pub organization_name: String,
@@ -156,23 +238,24 @@ pub async fn onboarding(
     }
 
     if let Some(integrated_daemon_url) = &state.config.integrated_daemon_url {
-        let api_key = state
+        let (_, api_key) = state
             .services
             .api_key_service
             .create(ApiKey::new(ApiKeyBase {
-                key: "".to_string(),
+                key_hash: String::new(),
                 name: "Integrated Daemon API Key".to_string(),
                 last_used: None,
                 expires_at: None,
                 network_id: network.id,
                 is_enabled: true,
+                rotated_at: None,
             }))
             .await?;
 
         state
             .services
             .daemon_service
-            .initialize_local_daemon(integrated_daemon_url.clone(), network.id, api_key.base.key)
+            .initialize_local_daemon(integrated_daemon_url.clone(), network.id, api_key)
             .await?;
     }
 
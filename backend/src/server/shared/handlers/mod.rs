@@ -1,3 +1,7 @@
 pub mod cache;
+pub mod csv;
 pub mod factory;
+pub mod ndjson;
+#[cfg(test)]
+pub mod tests;
 pub mod traits;
@@ -0,0 +1,220 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, header},
+    response::{IntoResponse, Response},
+};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Whether the client asked for `Accept: application/x-ndjson`
+pub fn accepts_ndjson(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|v| v.split(';').next())
+        .any(|mime| mime.trim().eq_ignore_ascii_case(NDJSON_CONTENT_TYPE))
+}
+
+/// Last line of every NDJSON stream, wrapped as `{"_stream": {...}}` so it can't be mistaken for
+/// a row. A stream that ends without one was cut off.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum StreamEnd {
+    Complete { count: u64 },
+    Error { count: u64, error: String },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StreamTrailer {
+    #[serde(rename = "_stream")]
+    pub end: StreamEnd,
+}
+
+fn line<T: Serialize>(value: &T) -> Result<Bytes, serde_json::Error> {
+    let mut bytes = serde_json::to_vec(value)?;
+    bytes.push(b'\n');
+    Ok(Bytes::from(bytes))
+}
+
+/// Respond with one JSON object per line as `rows` yields them, finishing with a
+/// [`StreamTrailer`]. Rows are serialized and sent as they arrive, so memory use doesn't grow
+/// with the result size. Once the body has started the status can't change, so a mid-stream
+/// error is reported in the trailer instead.
+pub fn ndjson_response<T, S>(rows: S) -> Response
+where
+    T: Serialize + Send + 'static,
+    S: Stream<Item = Result<T, anyhow::Error>> + Send + 'static,
+{
+    let body = async_stream::stream! {
+        let mut rows = std::pin::pin!(rows);
+        let mut count = 0;
+
+        let end = loop {
+            let error = match rows.next().await {
+                None => break StreamEnd::Complete { count },
+                Some(Ok(row)) => match line(&row) {
+                    Ok(bytes) => {
+                        count += 1;
+                        yield Ok::<_, Infallible>(bytes);
+                        continue;
+                    }
+                    Err(e) => anyhow::Error::from(e),
+                },
+                Some(Err(e)) => e,
+            };
+
+            tracing::error!("NDJSON stream aborted after {} rows: {}", count, error);
+            break StreamEnd::Error { count, error: error.to_string() };
+        };
+
+        yield Ok(line(&StreamTrailer { end }).unwrap_or_default());
+    };
+
+    (
+        [(header::CONTENT_TYPE, NDJSON_CONTENT_TYPE)],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::shared::storage::cursor::{PageCursor, paginate};
+    use chrono::{DateTime, Utc};
+    use std::cell::Cell;
+    use uuid::Uuid;
+
+    thread_local! {
+        // Rows currently alive and the most ever alive at once
+        static LIVE: Cell<usize> = const { Cell::new(0) };
+        static PEAK: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[derive(Serialize)]
+    struct Row {
+        id: Uuid,
+        created_at: DateTime<Utc>,
+        payload: String,
+    }
+
+    impl Row {
+        fn new(index: usize) -> Self {
+            LIVE.set(LIVE.get() + 1);
+            PEAK.set(PEAK.get().max(LIVE.get()));
+            Self {
+                id: Uuid::new_v4(),
+                created_at: DateTime::<Utc>::UNIX_EPOCH + chrono::Duration::seconds(index as i64),
+                payload: "x".repeat(256),
+            }
+        }
+    }
+
+    impl Drop for Row {
+        fn drop(&mut self) {
+            LIVE.set(LIVE.get() - 1);
+        }
+    }
+
+    fn key(row: &Row) -> PageCursor {
+        PageCursor {
+            created_at: row.created_at,
+            id: row.id,
+        }
+    }
+
+    /// A table of `total` rows served a page at a time, failing on page `fail_on` if set
+    fn table(
+        total: usize,
+        page_size: usize,
+        fail_on: Option<usize>,
+    ) -> impl Stream<Item = Result<Row, anyhow::Error>> {
+        let mut served = 0;
+        let mut page = 0;
+        paginate(page_size, key, move |after| {
+            assert_eq!(after.is_some(), served > 0);
+            page += 1;
+            let result = if fail_on == Some(page) {
+                Err(anyhow::anyhow!("connection reset"))
+            } else {
+                let rows: Vec<_> = (served..total.min(served + page_size))
+                    .map(Row::new)
+                    .collect();
+                served += rows.len();
+                Ok(rows)
+            };
+            async move { result }
+        })
+    }
+
+    /// Read the body chunk by chunk, as a client would, returning the line count and last line
+    async fn consume(response: Response) -> (usize, String) {
+        let mut body = response.into_body().into_data_stream();
+        let mut lines = 0;
+        let mut last = String::new();
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.unwrap();
+            lines += chunk.iter().filter(|&&b| b == b'\n').count();
+            last = String::from_utf8(chunk.to_vec()).unwrap();
+        }
+        (lines, last)
+    }
+
+    #[tokio::test]
+    async fn test_streaming_keeps_memory_flat() {
+        PEAK.set(0);
+        let page_size = 100;
+
+        let response = ndjson_response(table(10_000, page_size, None));
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            NDJSON_CONTENT_TYPE
+        );
+
+        let (lines, last) = consume(response).await;
+
+        // Every row plus the trailer, with no more than about a page alive at any point
+        assert_eq!(lines, 10_001);
+        assert!(PEAK.get() <= page_size + 1, "peak {} rows", PEAK.get());
+        assert_eq!(LIVE.get(), 0);
+
+        let trailer: StreamTrailer = serde_json::from_str(&last).unwrap();
+        assert_eq!(trailer.end, StreamEnd::Complete { count: 10_000 });
+    }
+
+    #[tokio::test]
+    async fn test_mid_stream_error_ends_with_error_trailer() {
+        let response = ndjson_response(table(10_000, 100, Some(3)));
+
+        let (lines, last) = consume(response).await;
+        assert_eq!(lines, 201);
+
+        let trailer: StreamTrailer = serde_json::from_str(&last).unwrap();
+        assert_eq!(
+            trailer.end,
+            StreamEnd::Error {
+                count: 200,
+                error: "connection reset".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_accepts_ndjson() {
+        let mut headers = HeaderMap::new();
+        assert!(!accepts_ndjson(&headers));
+
+        headers.insert(
+            header::ACCEPT,
+            "application/json, application/x-ndjson;q=0.9"
+                .parse()
+                .unwrap(),
+        );
+        assert!(accepts_ndjson(&headers));
+    }
+}
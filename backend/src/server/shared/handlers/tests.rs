@@ -0,0 +1,231 @@
+use axum::{
+    body::{Body, to_bytes},
+    http::{Request, StatusCode, header},
+    middleware,
+};
+use serial_test::serial;
+use tower::Service;
+
+use crate::{
+    server::{
+        bootstrap_tokens::r#impl::api::CreateBootstrapTokenRequest,
+        config::ServerConfig,
+        groups::r#impl::base::Group,
+        shared::{
+            handlers::factory::create_router, services::traits::CrudService, slo::track_slo,
+            types::api::ApiResponse,
+        },
+        users::r#impl::permissions::UserOrgPermissions,
+    },
+    tests::*,
+};
+
+#[tokio::test]
+#[serial]
+async fn test_unknown_api_routes_return_json_errors() {
+    let (state, _container) = test_app_state().await;
+
+    // Mirror the server setup: API routes plus a catch-all for the web UI
+    let mut app = create_router()
+        .route_layer(middleware::from_fn_with_state(state.slo.clone(), track_slo))
+        .with_state(state.clone())
+        .fallback(|| async { "<html>spa</html>" });
+
+    let (status, _, body) = call(&mut app, "GET", "/api/does-not-exist", None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let response: ApiResponse<()> = serde_json::from_str(&body).unwrap();
+    assert!(!response.success);
+    assert!(response.error.unwrap().contains("/api/does-not-exist"));
+
+    let (status, _, body) = call(&mut app, "DELETE", "/api/health", None, None).await;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    let response: ApiResponse<()> = serde_json::from_str(&body).unwrap();
+    assert!(!response.success);
+    assert!(response.error.unwrap().contains("DELETE"));
+
+    let (status, _, body) = call(&mut app, "GET", "/some/page", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "<html>spa</html>");
+
+    let (status, _, _) = call(&mut app, "GET", "/api/health", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(state.slo.snapshot().contains_key("/api/health"));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_created_resource_location_uses_configured_base_url() {
+    let (state, _container) = test_app_state_with_config(ServerConfig {
+        public_base_url: Some("https://netvisor.example.com/".to_string()),
+        ..Default::default()
+    })
+    .await;
+    let services = &state.services;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let network = services
+        .network_service
+        .create(network(&organization.id))
+        .await
+        .unwrap();
+    user_with_password(
+        &state,
+        &organization.id,
+        "member@example.com",
+        UserOrgPermissions::Member,
+    )
+    .await;
+
+    let mut app = session_app(&state);
+    let cookie = login(&mut app, "member@example.com").await;
+
+    // The request's own host must not leak into the link
+    let create = Request::builder()
+        .method("POST")
+        .uri("/api/groups")
+        .header(header::HOST, "internal:60072")
+        .header(header::COOKIE, cookie)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            serde_json::to_string(&group(&network.id)).unwrap(),
+        ))
+        .unwrap();
+    let response = app.call(create).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let location = response.headers()[header::LOCATION]
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let created: ApiResponse<Group> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        location,
+        format!(
+            "https://netvisor.example.com/api/groups/{}",
+            created.data.unwrap().id
+        )
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_generic_handlers_hide_entities_on_other_networks() {
+    let (state, _container) = test_app_state().await;
+    let services = &state.services;
+    let (network_id, foreign_network_id) = owned_and_foreign_networks(&state).await;
+
+    let own = services
+        .group_service
+        .create(group(&network_id))
+        .await
+        .unwrap();
+    let foreign = services
+        .group_service
+        .create(group(&foreign_network_id))
+        .await
+        .unwrap();
+
+    let mut app = session_app(&state);
+    let owner = login(&mut app, "owner@example.com").await;
+    let member = login(&mut app, "member@example.com").await;
+
+    let (status, _, body) = call(&mut app, "GET", "/api/groups", Some(&member), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(&own.id.to_string()));
+    assert!(!body.contains(&foreign.id.to_string()));
+
+    let foreign_uri = format!("/api/groups/{}", foreign.id);
+    let foreign_body = serde_json::to_value(&foreign).unwrap();
+    for (method, body) in [("GET", None), ("PUT", Some(foreign_body)), ("DELETE", None)] {
+        let (status, _, _) = call(&mut app, method, &foreign_uri, Some(&member), body).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{} {}", method, foreign_uri);
+    }
+
+    // Nor can entities be created on, or moved to, a network the caller can't see
+    let body = serde_json::to_value(group(&foreign_network_id)).unwrap();
+    let (status, _, _) = call(&mut app, "POST", "/api/groups", Some(&member), Some(body)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let mut moved = own.clone();
+    moved.base.network_id = foreign_network_id;
+    let own_uri = format!("/api/groups/{}", own.id);
+    let body = serde_json::to_value(&moved).unwrap();
+    let (status, _, _) = call(&mut app, "PUT", &own_uri, Some(&member), Some(body)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    assert_eq!(
+        services
+            .group_service
+            .get_by_id(&foreign.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .base
+            .network_id,
+        foreign_network_id
+    );
+    assert_eq!(
+        services
+            .group_service
+            .get_by_id(&own.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .base
+            .network_id,
+        network_id
+    );
+
+    // Admin-only deletes are scoped the same way
+    let (token, _) = services
+        .bootstrap_token_service
+        .mint(CreateBootstrapTokenRequest {
+            name: "edge-daemon".to_string(),
+            network_id,
+            ttl_minutes: 60,
+            allowed_ip: None,
+        })
+        .await
+        .unwrap();
+    let (foreign_token, _) = services
+        .bootstrap_token_service
+        .mint(CreateBootstrapTokenRequest {
+            name: "edge-daemon".to_string(),
+            network_id: foreign_network_id,
+            ttl_minutes: 60,
+            allowed_ip: None,
+        })
+        .await
+        .unwrap();
+
+    let uri = format!("/api/bootstrap-tokens/{}", foreign_token.id);
+    let (status, _, _) = call(&mut app, "DELETE", &uri, Some(&owner), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let uri = format!("/api/bootstrap-tokens/{}", token.id);
+    let (status, _, _) = call(&mut app, "DELETE", &uri, Some(&member), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _, _) = call(&mut app, "DELETE", &uri, Some(&owner), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let bootstrap_tokens = &services.bootstrap_token_service;
+    assert!(
+        bootstrap_tokens
+            .get_by_id(&token.id)
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        bootstrap_tokens
+            .get_by_id(&foreign_token.id)
+            .await
+            .unwrap()
+            .is_some()
+    );
+}
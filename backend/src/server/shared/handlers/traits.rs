@@ -1,7 +1,8 @@
 use crate::server::{
-    auth::middleware::{AuthenticatedUser, RequireMember},
+    auth::middleware::{AuthenticatedUser, RequireAdmin, RequireMember},
     config::AppState,
     shared::{
+        base_url::PublicBaseUrl,
         services::traits::CrudService,
         storage::{filter::EntityFilter, traits::StorableEntity},
         types::api::{ApiError, ApiResponse, ApiResult},
@@ -10,7 +11,8 @@ use crate::server::{
 use async_trait::async_trait;
 use axum::{
     Router,
-    extract::{Path, State},
+    extract::{OriginalUri, Path, State},
+    http::{HeaderMap, HeaderValue, header},
     response::Json,
     routing::{delete, get, post, put},
 };
@@ -37,6 +39,31 @@ where
     fn validate(&self) -> Result<(), String> {
         Ok(())
     }
+
+    /// Who may reach the entity through the generic handlers
+    fn scope(&self) -> EntityScope;
+}
+
+/// Who may reach an entity. The generic handlers report entities outside the caller's scope as
+/// missing, so they don't reveal that they exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityScope {
+    /// Callers with access to the network
+    Network(Uuid),
+    /// Members of the organization
+    Organization(Uuid),
+    /// Only the user themselves
+    User(Uuid),
+}
+
+impl EntityScope {
+    pub fn visible_to(&self, user: &AuthenticatedUser) -> bool {
+        match self {
+            Self::Network(network_id) => user.network_ids.contains(network_id),
+            Self::Organization(organization_id) => user.organization_id == *organization_id,
+            Self::User(user_id) => user.user_id == *user_id,
+        }
+    }
 }
 
 /// Create a standard CRUD router
@@ -52,11 +79,14 @@ where
         .route("/{id}", get(get_by_id_handler::<T>))
 }
 
+/// Creates the entity, pointing `Location` at it
 pub async fn create_handler<T>(
     State(state): State<Arc<AppState>>,
     RequireMember(user): RequireMember,
+    base_url: PublicBaseUrl,
+    OriginalUri(uri): OriginalUri,
     Json(request): Json<T>,
-) -> ApiResult<Json<ApiResponse<T>>>
+) -> ApiResult<(HeaderMap, Json<ApiResponse<T>>)>
 where
     T: CrudHandlers + 'static,
 {
@@ -74,6 +104,13 @@ where
         )));
     }
 
+    if !request.scope().visible_to(&user) {
+        return Err(ApiError::forbidden(&format!(
+            "You don't have access to where this {} would be created",
+            T::entity_name()
+        )));
+    }
+
     tracing::debug!(
         entity_type = T::table_name(),
         user_id = %user.user_id,
@@ -98,7 +135,19 @@ where
         "Entity created via API"
     );
 
-    Ok(Json(ApiResponse::success(created)))
+    let mut headers = HeaderMap::new();
+    if let Some(location) = base_url
+        .join(&format!(
+            "{}/{}",
+            uri.path().trim_end_matches('/'),
+            created.id()
+        ))
+        .and_then(|location| HeaderValue::from_str(&location).ok())
+    {
+        headers.insert(header::LOCATION, location);
+    }
+
+    Ok((headers, Json(ApiResponse::success(created))))
 }
 
 pub async fn get_all_handler<T>(
@@ -118,7 +167,7 @@ where
     let network_filter = EntityFilter::unfiltered().network_ids(&user.network_ids);
 
     let service = T::get_service(&state);
-    let entities = service.get_all(network_filter).await.map_err(|e| {
+    let mut entities = service.get_all(network_filter).await.map_err(|e| {
         tracing::error!(
             entity_type = T::table_name(),
             user_id = %user.user_id,
@@ -127,6 +176,8 @@ where
         );
         ApiError::internal_error(&e.to_string())
     })?;
+    // The filter is skipped for callers without networks, so scope is checked here as well
+    entities.retain(|entity| entity.scope().visible_to(&user));
 
     tracing::debug!(
         entity_type = T::table_name(),
@@ -167,6 +218,7 @@ where
             );
             ApiError::internal_error(&e.to_string())
         })?
+        .filter(|entity| entity.scope().visible_to(&user))
         .ok_or_else(|| {
             tracing::warn!(
                 entity_type = T::table_name(),
@@ -203,9 +255,16 @@ where
         "Update request received"
     );
 
+    if request.id() != id {
+        return Err(ApiError::bad_request(&format!(
+            "{} id in the body doesn't match the one in the path",
+            T::entity_name()
+        )));
+    }
+
     let service = T::get_service(&state);
 
-    // Verify entity exists
+    // Verify entity exists and the caller can see it
     service
         .get_by_id(&id)
         .await
@@ -219,6 +278,7 @@ where
             );
             ApiError::internal_error(&e.to_string())
         })?
+        .filter(|entity| entity.scope().visible_to(&user))
         .ok_or_else(|| {
             tracing::warn!(
                 entity_type = T::table_name(),
@@ -229,6 +289,13 @@ where
             ApiError::not_found(format!("{} '{}' not found", T::entity_name(), id))
         })?;
 
+    if !request.scope().visible_to(&user) {
+        return Err(ApiError::forbidden(&format!(
+            "You don't have access to where this {} would be moved",
+            T::entity_name()
+        )));
+    }
+
     let updated = service.update(&mut request).await.map_err(|e| {
         tracing::error!(
             entity_type = T::table_name(),
@@ -252,7 +319,7 @@ where
 
 pub async fn delete_handler<T>(
     State(state): State<Arc<AppState>>,
-    RequireMember(_user): RequireMember,
+    RequireMember(user): RequireMember,
     Path(id): Path<Uuid>,
 ) -> ApiResult<Json<ApiResponse<()>>>
 where
@@ -260,7 +327,7 @@ where
 {
     let service = T::get_service(&state);
 
-    // Verify entity exists and log the deletion attempt
+    // Verify entity exists and the caller can see it, and log the deletion attempt
     let entity = service
         .get_by_id(&id)
         .await
@@ -273,10 +340,12 @@ where
             );
             ApiError::internal_error(&e.to_string())
         })?
+        .filter(|entity| entity.scope().visible_to(&user))
         .ok_or_else(|| {
             tracing::warn!(
                 entity_type = T::table_name(),
                 entity_id = %id,
+                user_id = %user.user_id,
                 "Entity not found for deletion"
             );
             ApiError::not_found(format!("{} '{}' not found", T::entity_name(), id))
@@ -286,6 +355,7 @@ where
         entity_type = T::table_name(),
         entity_id = %id,
         entity_name = %entity,
+        user_id = %user.user_id,
         "Delete request received"
     );
 
@@ -301,3 +371,15 @@ where
 
     Ok(Json(ApiResponse::success(())))
 }
+
+/// [`delete_handler`] for entities only admins may delete
+pub async fn admin_delete_handler<T>(
+    state: State<Arc<AppState>>,
+    RequireAdmin(user): RequireAdmin,
+    id: Path<Uuid>,
+) -> ApiResult<Json<ApiResponse<()>>>
+where
+    T: CrudHandlers + 'static,
+{
+    delete_handler::<T>(state, RequireMember(user), id).await
+}
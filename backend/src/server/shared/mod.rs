@@ -1,5 +1,13 @@
+pub mod base_url;
+pub mod deadline;
 pub mod entities;
+pub mod extractors;
+pub mod fields;
 pub mod handlers;
+pub mod outbound;
+pub mod self_test;
 pub mod services;
+pub mod slo;
 pub mod storage;
+pub mod sync;
 pub mod types;
@@ -0,0 +1,168 @@
+use anyhow::{Result, anyhow, bail};
+use reqwest::{
+    RequestBuilder,
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect,
+};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+/// Whether `ip` is on the server's own machine or network rather than the internet: loopback,
+/// private, link-local (which includes cloud metadata endpoints), shared address space,
+/// unspecified, broadcast or multicast
+pub fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_internal_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_internal_v4(mapped),
+            None => is_internal_v6(ip),
+        },
+    }
+}
+
+fn is_internal_v4(ip: Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        // 0.0.0.0/8, which some systems route to the local machine
+        || first == 0
+        // 100.64.0.0/10, carrier-grade NAT
+        || (first == 100 && (second & 0xc0) == 64)
+}
+
+fn is_internal_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // fc00::/7, unique local
+        || (first & 0xfe00) == 0xfc00
+        // fe80::/10, link-local
+        || (first & 0xffc0) == 0xfe80
+}
+
+/// Resolves names the way the system does, leaving out internal addresses. Checked when
+/// connecting, so a name can't pass a check and then resolve somewhere else.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| !is_internal(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} only resolves to internal addresses", host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// HTTP client for URLs users register, such as webhooks, which mustn't be usable to reach the
+/// server's own network. Internal targets are refused unless `allow_internal` is set.
+#[derive(Clone)]
+pub struct OutboundClient {
+    client: reqwest::Client,
+    allow_internal: bool,
+}
+
+impl OutboundClient {
+    pub fn new(timeout: Duration, allow_internal: bool) -> Self {
+        let builder = reqwest::Client::builder().timeout(timeout);
+        let builder = if allow_internal {
+            builder
+        } else {
+            // Redirects could point anywhere, including literal internal addresses
+            builder
+                .dns_resolver(Arc::new(PublicResolver))
+                .redirect(redirect::Policy::none())
+        };
+
+        Self {
+            client: builder.build().unwrap_or_default(),
+            allow_internal,
+        }
+    }
+
+    /// Reject URLs that aren't http(s), or that name an internal address directly. Names are
+    /// checked when they're resolved.
+    pub fn check_url(&self, url: &str) -> Result<()> {
+        let parsed = url::Url::parse(url).map_err(|e| anyhow!("Invalid URL: {}", e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            bail!("Only http and https URLs can be called");
+        }
+        if self.allow_internal {
+            return Ok(());
+        }
+
+        let internal = match parsed.host() {
+            Some(url::Host::Ipv4(ip)) => is_internal(IpAddr::V4(ip)),
+            Some(url::Host::Ipv6(ip)) => is_internal(IpAddr::V6(ip)),
+            Some(url::Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+            None => true,
+        };
+        if internal {
+            bail!("URLs pointing at internal addresses can't be called");
+        }
+
+        Ok(())
+    }
+
+    pub fn post(&self, url: &str) -> Result<RequestBuilder> {
+        self.check_url(url)?;
+        Ok(self.client.post(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_internal_targets_refused_unless_allowed() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(is_internal(ip.parse().unwrap()), "{} isn't internal", ip);
+        }
+        for ip in ["93.184.216.34", "100.128.0.1", "2606:4700::1111"] {
+            assert!(!is_internal(ip.parse().unwrap()), "{} is internal", ip);
+        }
+
+        let client = OutboundClient::new(Duration::from_secs(1), false);
+        assert!(client.check_url("https://hooks.example.com/x").is_ok());
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://[::1]/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://localhost/hook",
+            "ftp://hooks.example.com/x",
+        ] {
+            assert!(client.check_url(url).is_err(), "{} allowed", url);
+        }
+
+        let client = OutboundClient::new(Duration::from_secs(1), true);
+        assert!(client.check_url("http://127.0.0.1:8080/hook").is_ok());
+        assert!(client.check_url("ftp://hooks.example.com/x").is_err());
+    }
+}
@@ -1,15 +1,45 @@
 use crate::server::{
-    api_keys::service::ApiKeyService,
-    auth::{oidc::OidcService, service::AuthService},
+    api_keys::{r#impl::expiry::ApiKeyExpiryPolicy, service::ApiKeyService},
+    audit::service::AuditService,
+    auth::{
+        impersonation::{ImpersonationPolicy, Impersonations},
+        lockout::LoginBackoff,
+        oidc::OidcService,
+        service::AuthService,
+        sessions::SessionLimiter,
+        source_ip::DaemonIpAllowlist,
+    },
     billing::service::BillingService,
+    bootstrap_tokens::service::BootstrapTokenService,
+    check_dependencies::service::CheckDependencyService,
+    check_suites::service::CheckSuiteService,
     config::ServerConfig,
-    daemons::service::DaemonService,
-    discovery::service::DiscoveryService,
+    daemons::{r#impl::transfer::DaemonTransfers, service::DaemonService},
+    diagnostics::service::DiagnosticService,
+    discovery::{
+        r#impl::{
+            fair_share::FairSharePolicy,
+            target_policy::{ScanTargetGuard, ScanTargetPolicy},
+        },
+        service::DiscoveryService,
+    },
+    discovery_webhooks::service::DiscoveryWebhookService,
     email::service::EmailService,
+    group_rules::service::GroupRuleService,
     groups::service::GroupService,
-    hosts::service::HostService,
+    hosts::{
+        r#impl::{
+            conflicts::{IpConflictDetector, IpConflictPolicy},
+            dedup::{ResultDedupPolicy, ResultDeduplicator},
+        },
+        service::HostService,
+    },
     networks::service::NetworkService,
+    notification_channels::service::NotificationChannelService,
+    notification_subscriptions::service::NotificationSubscriptionService,
+    notifications::service::{NotificationRouter, NotificationService},
     organizations::service::OrganizationService,
+    secrets::service::SecretService,
     services::service::ServiceService,
     shared::storage::factory::StorageFactory,
     subnets::service::SubnetService,
@@ -17,7 +47,7 @@ use crate::server::{
     users::service::UserService,
 };
 use anyhow::Result;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 pub struct ServiceFactory {
     pub user_service: Arc<UserService>,
@@ -30,8 +60,20 @@ pub struct ServiceFactory {
     pub topology_service: Arc<TopologyService>,
     pub service_service: Arc<ServiceService>,
     pub discovery_service: Arc<DiscoveryService>,
+    pub discovery_webhook_service: Arc<DiscoveryWebhookService>,
     pub api_key_service: Arc<ApiKeyService>,
+    pub bootstrap_token_service: Arc<BootstrapTokenService>,
+    pub diagnostic_service: Arc<DiagnosticService>,
+    pub check_dependency_service: Arc<CheckDependencyService>,
+    pub check_suite_service: Arc<CheckSuiteService>,
+    pub notification_service: Arc<NotificationService>,
+    pub notification_channel_service: Arc<NotificationChannelService>,
+    pub notification_subscription_service: Arc<NotificationSubscriptionService>,
     pub organization_service: Arc<OrganizationService>,
+    pub secret_service: Arc<SecretService>,
+    pub daemon_ip_allowlist: Arc<DaemonIpAllowlist>,
+    pub group_rule_service: Arc<GroupRuleService>,
+    pub audit_service: Arc<AuditService>,
     pub oidc_service: Option<Arc<OidcService>>,
     pub billing_service: Option<Arc<BillingService>>,
     pub email_service: Option<Arc<EmailService>>,
@@ -39,15 +81,75 @@ pub struct ServiceFactory {
 
 impl ServiceFactory {
     pub async fn new(storage: &StorageFactory, config: Option<ServerConfig>) -> Result<Self> {
-        let api_key_service = Arc::new(ApiKeyService::new(storage.api_keys.clone()));
-        let daemon_service = Arc::new(DaemonService::new(storage.daemons.clone()));
-        let group_service = Arc::new(GroupService::new(storage.groups.clone()));
+        let api_key_service = Arc::new(ApiKeyService::new(
+            storage.api_keys.clone(),
+            config
+                .as_ref()
+                .map(ApiKeyExpiryPolicy::from)
+                .unwrap_or_default(),
+        ));
+        let bootstrap_token_service =
+            Arc::new(BootstrapTokenService::new(storage.bootstrap_tokens.clone()));
+        let daemon_service = Arc::new(DaemonService::new(
+            storage.daemons.clone(),
+            storage.daemon_metrics.clone(),
+            config
+                .as_ref()
+                .map_or_else(
+                    || ServerConfig::default().daemon_metrics_retention_days,
+                    |c| c.daemon_metrics_retention_days,
+                )
+                .map(|days| chrono::Duration::days(days as i64)),
+        ));
+        let display_timezone = config
+            .as_ref()
+            .map(|c| c.display_timezone)
+            .unwrap_or_default();
+        let notification_service = Arc::new(NotificationService::new(display_timezone));
+        let check_dependency_service = Arc::new(CheckDependencyService::new(
+            storage.check_dependencies.clone(),
+        ));
+        let diagnostic_service = Arc::new(DiagnosticService::new(
+            storage.diagnostics.clone(),
+            notification_service.clone(),
+            check_dependency_service.clone(),
+        ));
+        let check_suite_service = Arc::new(CheckSuiteService::new(
+            storage.check_suites.clone(),
+            daemon_service.clone(),
+            diagnostic_service.clone(),
+            config
+                .as_ref()
+                .map(|c| c.check_suite_concurrency)
+                .unwrap_or_else(|| ServerConfig::default().check_suite_concurrency),
+            Duration::from_secs(
+                config
+                    .as_ref()
+                    .map(|c| c.check_suite_aging_secs)
+                    .unwrap_or_else(|| ServerConfig::default().check_suite_aging_secs),
+            ),
+        ));
+        let group_service = Arc::new(GroupService::new(
+            storage.groups.clone(),
+            storage.hosts.clone(),
+        ));
         let organization_service =
             Arc::new(OrganizationService::new(storage.organizations.clone()));
+        let secret_service = Arc::new(SecretService::new(
+            storage.secrets.clone(),
+            config.as_ref().and_then(|c| c.secrets_key.as_deref()),
+        ));
 
         // Already implements Arc internally due to scheduler + sessions
-        let discovery_service =
-            DiscoveryService::new(storage.discovery.clone(), daemon_service.clone()).await?;
+        let discovery_service = DiscoveryService::new(
+            storage.discovery.clone(),
+            daemon_service.clone(),
+            config
+                .as_ref()
+                .map(FairSharePolicy::from)
+                .unwrap_or_default(),
+        )
+        .await?;
 
         let service_service = Arc::new(ServiceService::new(
             storage.services.clone(),
@@ -56,6 +158,7 @@ impl ServiceFactory {
 
         let host_service = Arc::new(HostService::new(
             storage.hosts.clone(),
+            storage.networks.clone(),
             service_service.clone(),
             daemon_service.clone(),
         ));
@@ -65,7 +168,61 @@ impl ServiceFactory {
             host_service.clone(),
         ));
 
+        let group_rule_service = Arc::new(GroupRuleService::new(
+            storage.group_rules.clone(),
+            storage.hosts.clone(),
+            config
+                .as_ref()
+                .map(|c| c.group_rule_mode)
+                .unwrap_or_default(),
+        ));
+
         let _ = service_service.set_host_service(host_service.clone());
+        let _ = host_service.set_discovery_service(discovery_service.clone());
+        let _ = daemon_service.set_discovery_service(discovery_service.clone());
+        let _ = host_service.set_group_rule_service(group_rule_service.clone());
+        let _ = discovery_service.set_secret_service(secret_service.clone());
+        let _ = discovery_service.set_host_service(host_service.clone());
+        let _ = discovery_service.set_subnet_service(subnet_service.clone());
+        let allow_internal_webhook_targets = config
+            .as_ref()
+            .is_some_and(|c| c.allow_internal_webhook_targets);
+        let discovery_webhook_service = Arc::new(DiscoveryWebhookService::new(
+            storage.discovery_webhooks.clone(),
+            storage.hosts.clone(),
+            secret_service.clone(),
+            config
+                .as_ref()
+                .map(|c| c.public_base_url.clone().unwrap_or(c.public_url.clone()))
+                .unwrap_or_else(|| ServerConfig::default().public_url),
+            allow_internal_webhook_targets,
+        ));
+        let _ = discovery_service.set_discovery_webhook_service(discovery_webhook_service.clone());
+        let _ = discovery_webhook_service.set_notification_service(notification_service.clone());
+        let _ = host_service.set_notification_service(notification_service.clone());
+        let _ = daemon_service.set_notification_service(notification_service.clone());
+        let _ = api_key_service.set_notification_service(notification_service.clone());
+        let _ = host_service.set_deduplicator(ResultDeduplicator::new(
+            config
+                .as_ref()
+                .map(ResultDedupPolicy::from)
+                .unwrap_or_default(),
+        ));
+        let _ = host_service.set_ip_conflict_detector(IpConflictDetector::new(
+            config
+                .as_ref()
+                .map(IpConflictPolicy::from)
+                .unwrap_or_default(),
+            storage.ip_claims.clone(),
+        ));
+        let _ = daemon_service.set_scan_guard(ScanTargetGuard::new(
+            config
+                .as_ref()
+                .map(ScanTargetPolicy::from)
+                .unwrap_or_else(ScanTargetPolicy::unrestricted),
+            storage.subnets.clone(),
+            storage.hosts.clone(),
+        ));
 
         let topology_service = Arc::new(TopologyService::new(
             host_service.clone(),
@@ -108,10 +265,74 @@ impl ServiceFactory {
             None
         });
 
+        let audit_service = Arc::new(AuditService::new(storage.audit_events.clone()));
         let auth_service = Arc::new(AuthService::new(
             user_service.clone(),
             organization_service.clone(),
             email_service.clone(),
+            config
+                .as_ref()
+                .map(|c| c.claim_unnamed_seed_users)
+                .unwrap_or(true),
+            config.as_ref().map(LoginBackoff::from).unwrap_or_default(),
+            SessionLimiter::new(
+                Arc::new(storage.session_store.clone()),
+                storage.user_sessions.clone(),
+                config.as_ref().and_then(|c| c.max_sessions_per_user),
+                config
+                    .as_ref()
+                    .map(|c| c.session_limit_policy)
+                    .unwrap_or_default(),
+            ),
+            Impersonations::new(
+                config
+                    .as_ref()
+                    .map(ImpersonationPolicy::from)
+                    .unwrap_or_default(),
+                audit_service.clone(),
+            ),
+        ));
+
+        let _ = daemon_service.set_transfers(DaemonTransfers::new(
+            config.as_ref(),
+            storage.networks.clone(),
+            storage.hosts.clone(),
+            storage.services.clone(),
+            storage.discovery.clone(),
+            discovery_service.clone(),
+            audit_service.clone(),
+        ));
+
+        let notification_channel_service = Arc::new(NotificationChannelService::new(
+            storage.notification_channels.clone(),
+        ));
+        let notification_subscription_service = Arc::new(NotificationSubscriptionService::new(
+            storage.notification_subscriptions.clone(),
+        ));
+        let notification_router = NotificationRouter::new(
+            notification_subscription_service.clone(),
+            storage.notification_channels.clone(),
+            storage.notification_outbox.clone(),
+            storage.networks.clone(),
+            email_service.clone(),
+            display_timezone,
+        )
+        .allow_internal_targets(allow_internal_webhook_targets);
+        let _ =
+            notification_router.set_discovery_webhook_service(discovery_webhook_service.clone());
+        let _ = notification_service.set_router(Arc::new(notification_router));
+
+        let daemon_ip_allowlist = Arc::new(DaemonIpAllowlist::new(
+            config
+                .as_ref()
+                .map(|c| c.daemon_ip_allowlist.clone())
+                .unwrap_or_default(),
+            config
+                .as_ref()
+                .map(|c| c.trusted_proxies.clone())
+                .unwrap_or_default(),
+            storage.daemon_ip_allowlists.clone(),
+            storage.networks.clone(),
         ));
 
         let oidc_service = config.and_then(|c| {
@@ -151,8 +372,20 @@ impl ServiceFactory {
             topology_service,
             service_service,
             discovery_service,
+            discovery_webhook_service,
             api_key_service,
+            bootstrap_token_service,
+            diagnostic_service,
+            check_dependency_service,
+            check_suite_service,
+            notification_service,
+            notification_channel_service,
+            notification_subscription_service,
             organization_service,
+            secret_service,
+            daemon_ip_allowlist,
+            group_rule_service,
+            audit_service,
             oidc_service,
             billing_service,
             email_service,
@@ -1,8 +1,10 @@
 use async_trait::async_trait;
+use futures::{StreamExt, stream::BoxStream};
 use std::{fmt::Display, sync::Arc};
 use uuid::Uuid;
 
 use crate::server::shared::storage::{
+    cursor::{PageCursor, STREAM_PAGE_SIZE, paginate},
     filter::EntityFilter,
     generic::GenericPostgresStorage,
     traits::{StorableEntity, Storage},
@@ -28,6 +30,17 @@ where
         self.storage().get_all(filter).await
     }
 
+    /// Stream all entities matching filter, a page at a time rather than loading them all
+    fn stream_all(&self, filter: EntityFilter) -> BoxStream<'static, Result<T, anyhow::Error>> {
+        let storage = self.storage().clone();
+        paginate(STREAM_PAGE_SIZE, PageCursor::of, move |after| {
+            let storage = storage.clone();
+            let filter = filter.clone();
+            async move { storage.get_page(filter, after, STREAM_PAGE_SIZE).await }
+        })
+        .boxed()
+    }
+
     /// Get one entities with filter
     async fn get_one(&self, filter: EntityFilter) -> Result<Option<T>, anyhow::Error> {
         self.storage().get_one(filter).await
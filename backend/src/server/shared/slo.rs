@@ -0,0 +1,210 @@
+use crate::server::shared::sync::lock;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Routes which are expected to be slow and get their own latency budget instead of the default
+const ROUTE_BUDGETS: &[(&str, Duration)] = &[
+    ("/api/discovery/start-session", Duration::from_secs(10)),
+    ("/api/discovery/import", Duration::from_secs(30)),
+    ("/api/daemons/register", Duration::from_secs(10)),
+    ("/api/onboarding", Duration::from_secs(10)),
+    (
+        "/api/hosts/{destination_host}/consolidate/{other_host}",
+        Duration::from_secs(10),
+    ),
+    ("/api/topology", Duration::from_secs(5)),
+];
+
+/// Routes which are never tracked, e.g. long-lived streams
+const UNTRACKED_ROUTES: &[&str] = &["/api/discovery/stream", "/api/notifications/stream"];
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RouteSloStats {
+    pub total: u64,
+    pub within_slo: u64,
+}
+
+impl RouteSloStats {
+    pub fn compliance(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.within_slo as f64 / self.total as f64
+        }
+    }
+}
+
+/// Tracks per-route latency against an SLO budget and logs requests which exceed it
+pub struct SloTracker {
+    default_budget: Duration,
+    stats: Mutex<HashMap<String, RouteSloStats>>,
+}
+
+impl SloTracker {
+    pub fn new(default_budget: Duration) -> Self {
+        Self {
+            default_budget,
+            stats: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn budget_for(&self, route: &str) -> Duration {
+        ROUTE_BUDGETS
+            .iter()
+            .find(|(r, _)| *r == route)
+            .map(|(_, budget)| *budget)
+            .unwrap_or(self.default_budget)
+    }
+
+    /// Record a completed request, returning whether it met its budget
+    pub fn record(&self, route: &str, elapsed: Duration) -> bool {
+        let within_slo = elapsed <= self.budget_for(route);
+
+        let mut stats = lock(&self.stats);
+        let entry = stats.entry(route.to_string()).or_default();
+        entry.total += 1;
+        if within_slo {
+            entry.within_slo += 1;
+        }
+
+        within_slo
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, RouteSloStats> {
+        lock(&self.stats).clone()
+    }
+}
+
+/// Must be added with `route_layer` so the matched route template is available
+pub async fn track_slo(
+    State(tracker): State<Arc<SloTracker>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(route) = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_owned())
+    else {
+        return next.run(request).await;
+    };
+
+    if UNTRACKED_ROUTES.contains(&route.as_str()) {
+        return next.run(request).await;
+    }
+
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let method = request.method().clone();
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let elapsed = started.elapsed();
+
+    if !tracker.record(&route, elapsed) {
+        tracing::warn!(
+            route = %route,
+            method = %method,
+            duration_ms = elapsed.as_millis() as u64,
+            budget_ms = tracker.budget_for(&route).as_millis() as u64,
+            request_id = %request_id,
+            status = response.status().as_u16(),
+            "Slow request exceeded latency SLO"
+        );
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, middleware, routing::get};
+    use std::io::Write;
+    use tower::Service;
+
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_handler_logs_warning_and_fast_handler_does_not() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let tracker = Arc::new(SloTracker::new(Duration::from_millis(20)));
+        let mut app = Router::new()
+            .route("/fast", get(|| async { "fast" }))
+            .route(
+                "/slow/{id}",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_millis(60)).await;
+                    "slow"
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(tracker.clone(), track_slo));
+
+        let request = |uri: &str| {
+            Request::builder()
+                .uri(uri)
+                .header(REQUEST_ID_HEADER, format!("req-{}", uri.len()))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        app.call(request("/fast")).await.unwrap();
+        let fast_logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(!fast_logs.contains("Slow request"));
+
+        app.call(request("/slow/1")).await.unwrap();
+        let slow_logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(slow_logs.contains("WARN"));
+        assert!(slow_logs.contains("Slow request exceeded latency SLO"));
+        assert!(slow_logs.contains("route=/slow/{id}"));
+        assert!(slow_logs.contains("request_id=req-7"));
+
+        let stats = tracker.snapshot();
+        assert_eq!(stats["/fast"].compliance(), 1.0);
+        assert_eq!(stats["/slow/{id}"].compliance(), 0.0);
+    }
+
+    #[test]
+    fn test_known_slow_routes_get_own_budget() {
+        let tracker = SloTracker::new(Duration::from_millis(100));
+
+        assert!(tracker.record("/api/discovery/start-session", Duration::from_secs(2)));
+        assert!(tracker.record("/api/discovery/import", Duration::from_secs(15)));
+        assert!(!tracker.record("/api/hosts", Duration::from_secs(2)));
+    }
+}
@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use std::{fmt::Display, future::Future, str::FromStr};
+use uuid::Uuid;
+
+use crate::server::shared::storage::traits::StorableEntity;
+
+/// Rows fetched per page when streaming a whole table
+pub const STREAM_PAGE_SIZE: usize = 500;
+
+/// Keyset position of the last row seen. Rows are ordered by `(created_at, id)`, so the next page
+/// is everything strictly after this pair, which stays correct while rows are inserted and
+/// deleted between pages (unlike an OFFSET).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl PageCursor {
+    pub fn of<T: StorableEntity>(entity: &T) -> Self {
+        Self {
+            created_at: entity.created_at(),
+            id: entity.id(),
+        }
+    }
+}
+
+/// Written as `<created_at in unix microseconds>-<id>` when handed to API clients to resume
+/// from, which round-trips exactly since Postgres stores timestamps to the microsecond
+impl Display for PageCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.created_at.timestamp_micros(), self.id)
+    }
+}
+
+impl FromStr for PageCursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow::anyhow!("Invalid cursor '{}'", s);
+        let (micros, id) = s.split_once('-').ok_or_else(invalid)?;
+        Ok(Self {
+            created_at: micros
+                .parse()
+                .ok()
+                .and_then(DateTime::from_timestamp_micros)
+                .ok_or_else(invalid)?,
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Stream every row by fetching `page_size` rows at a time, so at most one page is held in memory
+/// however large the result. `fetch` is given the cursor of the last row of the previous page;
+/// a short page ends the stream. A fetch error is yielded and ends the stream.
+pub fn paginate<T, F, Fut>(
+    page_size: usize,
+    key: fn(&T) -> PageCursor,
+    mut fetch: F,
+) -> impl Stream<Item = Result<T, anyhow::Error>>
+where
+    F: FnMut(Option<PageCursor>) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, anyhow::Error>>,
+{
+    async_stream::try_stream! {
+        let mut after = None;
+        loop {
+            let page = fetch(after).await?;
+            let exhausted = page.len() < page_size;
+            after = page.last().map(key);
+
+            for row in page {
+                yield row;
+            }
+
+            if exhausted {
+                break;
+            }
+        }
+    }
+}
@@ -5,63 +5,135 @@ use tower_sessions::{Expiry, SessionManagerLayer};
 use tower_sessions_sqlx_store::PostgresStore;
 
 use crate::server::{
-    api_keys::r#impl::base::ApiKey, daemons::r#impl::base::Daemon,
-    discovery::r#impl::base::Discovery, groups::r#impl::base::Group, hosts::r#impl::base::Host,
-    networks::r#impl::Network, organizations::r#impl::base::Organization,
-    services::r#impl::base::Service, shared::storage::generic::GenericPostgresStorage,
-    subnets::r#impl::base::Subnet, users::r#impl::base::User,
+    api_keys::r#impl::base::ApiKey,
+    audit::r#impl::base::AuditEvent,
+    auth::r#impl::base::{OrganizationIpAllowlist, UserSession},
+    bootstrap_tokens::r#impl::base::BootstrapToken,
+    check_dependencies::r#impl::base::CheckDependency,
+    check_suites::r#impl::base::CheckSuite,
+    daemons::r#impl::{base::Daemon, metrics::DaemonMetricRollup},
+    diagnostics::r#impl::base::Diagnostic,
+    discovery::r#impl::base::Discovery,
+    discovery_webhooks::r#impl::base::DiscoveryWebhook,
+    group_rules::r#impl::base::GroupRule,
+    groups::r#impl::base::Group,
+    hosts::r#impl::{base::Host, conflicts::IpClaim},
+    networks::r#impl::Network,
+    notification_channels::r#impl::base::NotificationChannel,
+    notification_subscriptions::r#impl::base::NotificationSubscription,
+    notifications::r#impl::outbox::DeferredDelivery,
+    organizations::r#impl::base::Organization,
+    secrets::r#impl::base::Secret,
+    services::r#impl::base::Service,
+    shared::storage::{generic::GenericPostgresStorage, pools::DatabasePools},
+    subnets::r#impl::base::Subnet,
+    users::r#impl::base::User,
 };
 
 pub struct StorageFactory {
     pub sessions: SessionManagerLayer<PostgresStore>,
+    pub session_store: PostgresStore,
     pub api_keys: Arc<GenericPostgresStorage<ApiKey>>,
+    pub bootstrap_tokens: Arc<GenericPostgresStorage<BootstrapToken>>,
     pub users: Arc<GenericPostgresStorage<User>>,
+    pub user_sessions: Arc<GenericPostgresStorage<UserSession>>,
+    pub daemon_ip_allowlists: Arc<GenericPostgresStorage<OrganizationIpAllowlist>>,
     pub networks: Arc<GenericPostgresStorage<Network>>,
     pub hosts: Arc<GenericPostgresStorage<Host>>,
+    pub ip_claims: Arc<GenericPostgresStorage<IpClaim>>,
     pub groups: Arc<GenericPostgresStorage<Group>>,
+    pub group_rules: Arc<GenericPostgresStorage<GroupRule>>,
     pub daemons: Arc<GenericPostgresStorage<Daemon>>,
+    pub daemon_metrics: Arc<GenericPostgresStorage<DaemonMetricRollup>>,
     pub subnets: Arc<GenericPostgresStorage<Subnet>>,
     pub services: Arc<GenericPostgresStorage<Service>>,
     pub organizations: Arc<GenericPostgresStorage<Organization>>,
     pub discovery: Arc<GenericPostgresStorage<Discovery>>,
+    pub discovery_webhooks: Arc<GenericPostgresStorage<DiscoveryWebhook>>,
+    pub diagnostics: Arc<GenericPostgresStorage<Diagnostic>>,
+    pub check_dependencies: Arc<GenericPostgresStorage<CheckDependency>>,
+    pub check_suites: Arc<GenericPostgresStorage<CheckSuite>>,
+    pub secrets: Arc<GenericPostgresStorage<Secret>>,
+    pub notification_channels: Arc<GenericPostgresStorage<NotificationChannel>>,
+    pub notification_subscriptions: Arc<GenericPostgresStorage<NotificationSubscription>>,
+    pub notification_outbox: Arc<GenericPostgresStorage<DeferredDelivery>>,
+    pub audit_events: Arc<GenericPostgresStorage<AuditEvent>>,
 }
 
-pub async fn create_session_store(
-    db_pool: Pool<Postgres>,
-    use_secure: bool,
-) -> Result<SessionManagerLayer<PostgresStore>> {
+pub async fn create_session_store(db_pool: Pool<Postgres>) -> Result<PostgresStore> {
     let session_store = PostgresStore::new(db_pool.clone());
 
     session_store.migrate().await?;
 
-    Ok(SessionManagerLayer::new(session_store)
+    Ok(session_store)
+}
+
+pub fn create_session_layer(
+    session_store: PostgresStore,
+    use_secure: bool,
+) -> SessionManagerLayer<PostgresStore> {
+    SessionManagerLayer::new(session_store)
         .with_expiry(Expiry::OnInactivity(time::Duration::days(30))) // 30 days
         .with_name("session_id")
         .with_secure(use_secure)
         .with_http_only(true)
-        .with_same_site(tower_sessions::cookie::SameSite::Lax))
+        .with_same_site(tower_sessions::cookie::SameSite::Lax)
 }
 
 impl StorageFactory {
     pub async fn new(database_url: &str, use_secure_session_cookies: bool) -> Result<Self> {
         let pool = PgPool::connect(database_url).await?;
 
+        Self::from_pools(
+            DatabasePools::primary_only(pool),
+            use_secure_session_cookies,
+        )
+        .await
+    }
+
+    /// Build storage on top of already connected pools. Migrations and sessions always use the
+    /// primary.
+    pub async fn from_pools(
+        pools: DatabasePools,
+        use_secure_session_cookies: bool,
+    ) -> Result<Self> {
+        let pool = pools.primary().clone();
+
         sqlx::migrate!("./migrations").run(&pool).await?;
 
-        let sessions = create_session_store(pool.clone(), use_secure_session_cookies).await?;
+        let session_store = create_session_store(pool).await?;
+        let sessions = create_session_layer(session_store.clone(), use_secure_session_cookies);
+
+        let pools = Arc::new(pools);
 
         Ok(Self {
             sessions,
-            discovery: Arc::new(GenericPostgresStorage::new(pool.clone())),
-            organizations: Arc::new(GenericPostgresStorage::new(pool.clone())),
-            api_keys: Arc::new(GenericPostgresStorage::new(pool.clone())),
-            users: Arc::new(GenericPostgresStorage::new(pool.clone())),
-            networks: Arc::new(GenericPostgresStorage::new(pool.clone())),
-            hosts: Arc::new(GenericPostgresStorage::new(pool.clone())),
-            groups: Arc::new(GenericPostgresStorage::new(pool.clone())),
-            daemons: Arc::new(GenericPostgresStorage::new(pool.clone())),
-            subnets: Arc::new(GenericPostgresStorage::new(pool.clone())),
-            services: Arc::new(GenericPostgresStorage::new(pool.clone())),
+            session_store,
+            discovery: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            discovery_webhooks: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            organizations: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            api_keys: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            bootstrap_tokens: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            users: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            user_sessions: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            daemon_ip_allowlists: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            networks: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            hosts: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            ip_claims: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            groups: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            group_rules: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            daemons: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            daemon_metrics: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            subnets: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            services: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            diagnostics: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            check_dependencies: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            check_suites: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            secrets: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            notification_channels: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            notification_subscriptions: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            notification_outbox: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
+            audit_events: Arc::new(GenericPostgresStorage::with_pools(pools.clone())),
         })
     }
 }
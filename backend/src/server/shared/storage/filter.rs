@@ -1,8 +1,16 @@
+use chrono::{DateTime, Utc};
 use email_address::EmailAddress;
+use std::{collections::BTreeMap, net::IpAddr};
 use uuid::Uuid;
 
 use crate::server::{
-    shared::storage::traits::SqlValue, users::r#impl::permissions::UserOrgPermissions,
+    audit::r#impl::base::AuditEventKind,
+    diagnostics::r#impl::base::DiagnosticKind,
+    discovery::r#impl::types::DiscoveryTypeDiscriminants,
+    hosts::r#impl::base::HostState,
+    notifications::r#impl::base::NotificationEvent,
+    shared::storage::{cursor::PageCursor, traits::SqlValue},
+    users::r#impl::permissions::UserOrgPermissions,
 };
 
 /// Builder pattern for common WHERE clauses
@@ -27,6 +35,13 @@ impl EntityFilter {
         self
     }
 
+    pub fn exclude_entity_id(mut self, id: &Uuid) -> Self {
+        self.conditions
+            .push(format!("id <> ${}", self.values.len() + 1));
+        self.values.push(SqlValue::Uuid(*id));
+        self
+    }
+
     pub fn entity_ids(mut self, ids: &[Uuid]) -> Self {
         if ids.is_empty() {
             return self;
@@ -76,6 +91,13 @@ impl EntityFilter {
         self
     }
 
+    pub fn session_id(mut self, id: &str) -> Self {
+        self.conditions
+            .push(format!("session_id = ${}", self.values.len() + 1));
+        self.values.push(SqlValue::String(id.to_string()));
+        self
+    }
+
     pub fn host_id(mut self, id: &Uuid) -> Self {
         self.conditions
             .push(format!("host_id = ${}", self.values.len() + 1));
@@ -83,10 +105,290 @@ impl EntityFilter {
         self
     }
 
-    pub fn api_key(mut self, api_key: String) -> Self {
+    /// Daemons the server hasn't issued a request signing key to yet
+    pub fn without_server_key(mut self) -> Self {
+        self.conditions.push("server_key IS NULL".to_string());
+        self
+    }
+
+    /// Daemons currently flagged, or not, as rejecting the server's requests
+    pub fn auth_failed(mut self, failed: bool) -> Self {
+        self.conditions.push(if failed {
+            "auth_failed_at IS NOT NULL".to_string()
+        } else {
+            "auth_failed_at IS NULL".to_string()
+        });
+        self
+    }
+
+    /// Bootstrap tokens that are neither used nor expired
+    pub fn unredeemed(mut self) -> Self {
+        self.conditions
+            .push("used_at IS NULL AND expires_at > NOW()".to_string());
+        self
+    }
+
+    pub fn api_key_hash(mut self, key_hash: String) -> Self {
+        self.conditions
+            .push(format!("key_hash = ${}", self.values.len() + 1));
+        self.values.push(SqlValue::String(key_hash));
+        self
+    }
+
+    pub fn token_hash(mut self, token_hash: String) -> Self {
+        self.conditions
+            .push(format!("token_hash = ${}", self.values.len() + 1));
+        self.values.push(SqlValue::String(token_hash));
+        self
+    }
+
+    pub fn exclude_host_state(mut self, state: HostState) -> Self {
+        self.conditions
+            .push(format!("state != ${}", self.values.len() + 1));
+        self.values.push(SqlValue::HostState(state));
+        self
+    }
+
+    /// Rows whose `correlation_id` equals `id`. Rows without one never match.
+    pub fn correlation_id(mut self, id: &Uuid) -> Self {
+        self.conditions
+            .push(format!("correlation_id = ${}", self.values.len() + 1));
+        self.values.push(SqlValue::Uuid(*id));
+        self
+    }
+
+    /// Hosts in the node group `group_id`
+    pub fn group_id(mut self, group_id: &Uuid) -> Self {
         self.conditions
-            .push(format!("key = ${}", self.values.len() + 1));
-        self.values.push(SqlValue::String(api_key));
+            .push(format!("group_ids ? ${}", self.values.len() + 1));
+        self.values.push(SqlValue::String(group_id.to_string()));
+        self
+    }
+
+    /// Rows whose JSONB `tags` array contains `tag`
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.conditions
+            .push(format!("tags ? ${}", self.values.len() + 1));
+        self.values.push(SqlValue::String(tag.to_string()));
+        self
+    }
+
+    /// Rows whose JSONB `labels` object has every one of `labels`, each with exactly that value
+    pub fn labels(mut self, labels: &BTreeMap<String, String>) -> Self {
+        if labels.is_empty() {
+            return self;
+        }
+
+        self.conditions
+            .push(format!("labels @> ${}", self.values.len() + 1));
+        self.values.push(SqlValue::Json(serde_json::json!(labels)));
+        self
+    }
+
+    pub fn diagnostic_kind(mut self, kind: DiagnosticKind) -> Self {
+        self.conditions
+            .push(format!("kind = ${}", self.values.len() + 1));
+        self.values.push(SqlValue::DiagnosticKind(kind));
+        self
+    }
+
+    /// Diagnostics that recorded a certificate expiry
+    pub fn with_expiry(mut self) -> Self {
+        self.conditions.push("expires_at IS NOT NULL".to_string());
+        self
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.conditions
+            .push(format!("name = ${}", self.values.len() + 1));
+        self.values.push(SqlValue::String(name.to_string()));
+        self
+    }
+
+    pub fn target(mut self, target: &str) -> Self {
+        self.conditions
+            .push(format!("target = ${}", self.values.len() + 1));
+        self.values.push(SqlValue::String(target.to_string()));
+        self
+    }
+
+    pub fn daemon_id(mut self, id: &Uuid) -> Self {
+        self.conditions
+            .push(format!("daemon_id = ${}", self.values.len() + 1));
+        self.values.push(SqlValue::Uuid(*id));
+        self
+    }
+
+    /// Rollups whose bucket starts within `from..to`
+    pub fn bucket_start_between(mut self, from: DateTime<Utc>, to: DateTime<Utc>) -> Self {
+        self.conditions.push(format!(
+            "bucket_start >= ${} AND bucket_start < ${}",
+            self.values.len() + 1,
+            self.values.len() + 2
+        ));
+        self.values.push(SqlValue::Timestamp(from));
+        self.values.push(SqlValue::Timestamp(to));
+        self
+    }
+
+    pub fn bucket_start_before(mut self, cutoff: DateTime<Utc>) -> Self {
+        self.conditions
+            .push(format!("bucket_start < ${}", self.values.len() + 1));
+        self.values.push(SqlValue::Timestamp(cutoff));
+        self
+    }
+
+    /// Unpaused daemons last seen before `cutoff` that haven't been notified as offline since
+    pub fn newly_offline(mut self, cutoff: DateTime<Utc>) -> Self {
+        self.conditions.push(format!(
+            "NOT paused AND last_seen < ${} \
+             AND (offline_notified_at IS NULL OR offline_notified_at < last_seen)",
+            self.values.len() + 1
+        ));
+        self.values.push(SqlValue::Timestamp(cutoff));
+        self
+    }
+
+    /// Hosts `daemon_id` reported between `from` and `to`, inclusive
+    pub fn discovered_by(
+        mut self,
+        daemon_id: &Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Self {
+        let n = self.values.len();
+        self.conditions.push(format!(
+            "source->>'type' = 'Discovery' AND EXISTS (
+                SELECT 1 FROM jsonb_array_elements(source->'metadata') AS m
+                WHERE m->>'daemon_id' = ${}
+                    AND (m->>'date')::timestamptz BETWEEN ${} AND ${}
+            )",
+            n + 1,
+            n + 2,
+            n + 3
+        ));
+        self.values.push(SqlValue::String(daemon_id.to_string()));
+        self.values.push(SqlValue::Timestamp(from));
+        self.values.push(SqlValue::Timestamp(to));
+        self
+    }
+
+    /// Scheduled check suites whose next run is due at `now`, as `CheckSuite::is_due` decides
+    pub fn check_suite_due(mut self, now: DateTime<Utc>) -> Self {
+        self.conditions.push(format!(
+            "interval_secs IS NOT NULL \
+             AND (last_run_at IS NULL OR last_run_at + make_interval(secs => interval_secs) <= ${})",
+            self.values.len() + 1
+        ));
+        self.values.push(SqlValue::Timestamp(now));
+        self
+    }
+
+    /// IP claims on any of `ips`
+    pub fn claimed_ips(mut self, ips: &[IpAddr]) -> Self {
+        let placeholders: Vec<String> = ips
+            .iter()
+            .map(|ip| {
+                self.values.push(SqlValue::IpAddr(*ip));
+                format!("${}", self.values.len())
+            })
+            .collect();
+        self.conditions.push(if placeholders.is_empty() {
+            "FALSE".to_string()
+        } else {
+            format!("ip IN ({})", placeholders.join(", "))
+        });
+        self
+    }
+
+    /// IP claims last seen after `since`
+    pub fn seen_after(mut self, since: DateTime<Utc>) -> Self {
+        self.conditions
+            .push(format!("seen_at > ${}", self.values.len() + 1));
+        self.values.push(SqlValue::Timestamp(since));
+        self
+    }
+
+    /// IP claims last seen at or before `cutoff`
+    pub fn seen_by(mut self, cutoff: DateTime<Utc>) -> Self {
+        self.conditions
+            .push(format!("seen_at <= ${}", self.values.len() + 1));
+        self.values.push(SqlValue::Timestamp(cutoff));
+        self
+    }
+
+    /// Deferred deliveries due at or before `at`
+    pub fn due_by(mut self, at: DateTime<Utc>) -> Self {
+        self.conditions
+            .push(format!("due_at <= ${}", self.values.len() + 1));
+        self.values.push(SqlValue::Timestamp(at));
+        self
+    }
+
+    /// Rows created at or after `from`
+    pub fn created_from(mut self, from: DateTime<Utc>) -> Self {
+        self.conditions
+            .push(format!("created_at >= ${}", self.values.len() + 1));
+        self.values.push(SqlValue::Timestamp(from));
+        self
+    }
+
+    /// Rows created strictly before `to`
+    pub fn created_before(mut self, to: DateTime<Utc>) -> Self {
+        self.conditions
+            .push(format!("created_at < ${}", self.values.len() + 1));
+        self.values.push(SqlValue::Timestamp(to));
+        self
+    }
+
+    pub fn actor_id(mut self, id: &Uuid) -> Self {
+        self.conditions
+            .push(format!("actor_id = ${}", self.values.len() + 1));
+        self.values.push(SqlValue::Uuid(*id));
+        self
+    }
+
+    pub fn notification_event(mut self, event: NotificationEvent) -> Self {
+        self.conditions
+            .push(format!("event = ${}", self.values.len() + 1));
+        self.values.push(SqlValue::String(event.to_string()));
+        self
+    }
+
+    /// Rows belonging to an enabled user of `organization_id`
+    pub fn enabled_user_in_organization(mut self, organization_id: &Uuid) -> Self {
+        self.conditions.push(format!(
+            "user_id IN (SELECT id FROM users WHERE organization_id = ${} AND enabled)",
+            self.values.len() + 1
+        ));
+        self.values.push(SqlValue::Uuid(*organization_id));
+        self
+    }
+
+    /// Rows on any of `organization_id`'s networks
+    pub fn network_in_organization(mut self, organization_id: &Uuid) -> Self {
+        self.conditions.push(format!(
+            "network_id IN (SELECT id FROM networks WHERE organization_id = ${})",
+            self.values.len() + 1
+        ));
+        self.values.push(SqlValue::Uuid(*organization_id));
+        self
+    }
+
+    pub fn audit_event_kind(mut self, kind: AuditEventKind) -> Self {
+        self.conditions
+            .push(format!("kind = ${}", self.values.len() + 1));
+        self.values.push(SqlValue::String(kind.to_string()));
+        self
+    }
+
+    /// Rows ordered after `cursor` by `(created_at, id)`
+    pub fn after(mut self, cursor: &PageCursor) -> Self {
+        let n = self.values.len();
+        self.conditions
+            .push(format!("(created_at, id) > (${}, ${})", n + 1, n + 2));
+        self.values.push(SqlValue::Timestamp(cursor.created_at));
+        self.values.push(SqlValue::Uuid(cursor.id));
         self
     }
 
@@ -98,6 +400,77 @@ impl EntityFilter {
         self
     }
 
+    pub fn historical_discovery(mut self) -> Self {
+        self.conditions
+            .push("run_type->>'type' = 'Historical'".to_string());
+        self
+    }
+
+    /// Historical discoveries of one of `types`
+    pub fn discovery_types(mut self, types: &[DiscoveryTypeDiscriminants]) -> Self {
+        let placeholders = self.push_discovery_types(types);
+        self.conditions.push(format!(
+            "discovery_type->>'type' IN ({})",
+            placeholders.join(", ")
+        ));
+        self
+    }
+
+    /// Historical discoveries of none of `types`
+    pub fn exclude_discovery_types(mut self, types: &[DiscoveryTypeDiscriminants]) -> Self {
+        if types.is_empty() {
+            return self;
+        }
+
+        let placeholders = self.push_discovery_types(types);
+        self.conditions.push(format!(
+            "discovery_type->>'type' NOT IN ({})",
+            placeholders.join(", ")
+        ));
+        self
+    }
+
+    fn push_discovery_types(&mut self, types: &[DiscoveryTypeDiscriminants]) -> Vec<String> {
+        types
+            .iter()
+            .map(|discovery_type| {
+                self.values
+                    .push(SqlValue::String(format!("{:?}", discovery_type)));
+                format!("${}", self.values.len())
+            })
+            .collect()
+    }
+
+    /// Historical discoveries that finished strictly before `cutoff`
+    pub fn finished_before(mut self, cutoff: DateTime<Utc>) -> Self {
+        self.conditions.push(format!(
+            "COALESCE((run_type->'results'->>'finished_at')::timestamptz, created_at) < ${}",
+            self.values.len() + 1
+        ));
+        self.values.push(SqlValue::Timestamp(cutoff));
+        self
+    }
+
+    /// Leaves out the most recent successful historical discovery of each daemon, and of each
+    /// network's imports of a format, which have no daemon
+    pub fn exclude_latest_successful_discovery(mut self) -> Self {
+        self.conditions.push(
+            "id NOT IN (
+                SELECT DISTINCT ON (
+                    COALESCE(daemon_id::text, network_id::text || '/' || (discovery_type->>'format'))
+                ) id
+                FROM discovery
+                WHERE run_type->>'type' = 'Historical'
+                    AND run_type->'results'->>'phase' = 'Complete'
+                ORDER BY
+                    COALESCE(daemon_id::text, network_id::text || '/' || (discovery_type->>'format')),
+                    COALESCE((run_type->'results'->>'finished_at')::timestamptz, created_at) DESC
+            )"
+            .to_string(),
+        );
+        self
+    }
+
     pub fn oidc_subject(mut self, subject: String) -> Self {
         self.conditions
             .push(format!("oidc_subject = ${}", self.values.len() + 1));
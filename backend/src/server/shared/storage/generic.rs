@@ -1,15 +1,90 @@
-use crate::server::shared::storage::{
-    filter::EntityFilter,
-    traits::{SqlValue, StorableEntity, Storage},
+use crate::server::shared::{
+    deadline::{self, DeadlineExceeded, RequestDeadline},
+    storage::{
+        cursor::PageCursor,
+        filter::EntityFilter,
+        pools::DatabasePools,
+        traits::{SqlValue, StorableEntity, Storage},
+    },
 };
 use async_trait::async_trait;
 use chrono::Utc;
-use sqlx::{PgPool, Postgres, postgres::PgArguments};
-use std::{fmt::Display, marker::PhantomData};
+use sqlx::{
+    PgConnection, PgPool, Postgres, Transaction, pool::PoolConnection, postgres::PgArguments,
+};
+use std::{fmt::Display, marker::PhantomData, sync::Arc};
 use uuid::Uuid;
 
+/// SQLSTATE Postgres reports when a statement is cancelled by `statement_timeout`
+const QUERY_CANCELED: &str = "57014";
+
+/// Connection used for a single storage call. When a request deadline is active the call runs
+/// in a transaction with `statement_timeout` set to the remaining budget, so Postgres cancels the
+/// query itself rather than letting it run on after the client has given up.
+enum ScopedConnection {
+    Pooled(PoolConnection<Postgres>),
+    Deadline(Transaction<'static, Postgres>),
+}
+
+impl ScopedConnection {
+    async fn acquire(pool: &PgPool) -> Result<Self, anyhow::Error> {
+        let Some(deadline) = RequestDeadline::current() else {
+            return Ok(Self::Pooled(pool.acquire().await?));
+        };
+
+        let mut tx = deadline::enforce(pool.begin()).await?;
+
+        // Sub-millisecond budgets would round to 0, which Postgres treats as "no timeout"
+        let timeout_ms = deadline.remaining().as_millis().max(1);
+        deadline::enforce(
+            sqlx::query(&format!("SET LOCAL statement_timeout = {}", timeout_ms)).execute(&mut *tx),
+        )
+        .await?;
+
+        Ok(Self::Deadline(tx))
+    }
+
+    fn conn(&mut self) -> &mut PgConnection {
+        match self {
+            Self::Pooled(conn) => conn,
+            Self::Deadline(tx) => tx,
+        }
+    }
+
+    async fn finish(self) -> Result<(), anyhow::Error> {
+        if let Self::Deadline(tx) = self {
+            deadline::enforce(tx.commit()).await?;
+        }
+        Ok(())
+    }
+
+    /// Await a query on this connection, mapping deadline cancellations to [`DeadlineExceeded`]
+    async fn run<F, T>(fut: F) -> Result<T, anyhow::Error>
+    where
+        F: std::future::Future<Output = Result<T, sqlx::Error>>,
+    {
+        deadline::enforce(async {
+            fut.await.map_err(|e| match &e {
+                sqlx::Error::Database(db) if db.code().as_deref() == Some(QUERY_CANCELED) => {
+                    anyhow::Error::from(DeadlineExceeded)
+                }
+                _ => e.into(),
+            })
+        })
+        .await
+    }
+}
+
+/// Whether a storage call failed because a row collided with a unique constraint, e.g. two
+/// concurrent creates racing past the same "is this name taken" check
+pub fn is_unique_violation(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<sqlx::Error>()
+        .and_then(|e| e.as_database_error())
+        .is_some_and(|db| db.is_unique_violation())
+}
+
 pub struct GenericPostgresStorage<T: StorableEntity> {
-    pool: PgPool,
+    pools: Arc<DatabasePools>,
     _phantom: PhantomData<T>,
 }
 
@@ -18,12 +93,230 @@ where
     T: Display,
 {
     pub fn new(pool: PgPool) -> Self {
+        Self::with_pools(Arc::new(DatabasePools::primary_only(pool)))
+    }
+
+    pub fn with_pools(pools: Arc<DatabasePools>) -> Self {
         Self {
-            pool,
+            pools,
             _phantom: PhantomData,
         }
     }
 
+    /// Cheapest query that proves the table is reachable, for health checks. Checks the primary,
+    /// and the read replica too when there is one, since either being down breaks requests.
+    pub async fn ping(&self) -> Result<(), anyhow::Error> {
+        let query_str = format!("SELECT 1 FROM {} LIMIT 1", T::table_name());
+
+        for pool in std::iter::once(self.pools.primary()).chain(self.pools.replica()) {
+            let mut conn = ScopedConnection::acquire(pool).await?;
+            ScopedConnection::run(sqlx::query(&query_str).fetch_optional(conn.conn())).await?;
+            conn.finish().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Most recently created row matching `filter`
+    pub async fn get_latest(&self, filter: EntityFilter) -> Result<Option<T>, anyhow::Error> {
+        let query_str = format!(
+            "SELECT * FROM {} {} ORDER BY created_at DESC, id DESC LIMIT 1",
+            T::table_name(),
+            filter.to_where_clause()
+        );
+
+        let mut query = sqlx::query(&query_str);
+        for value in filter.values() {
+            query = Self::bind_value(query, value)?;
+        }
+
+        let mut conn = ScopedConnection::acquire(self.pools.reader()).await?;
+        let row = ScopedConnection::run(query.fetch_optional(conn.conn())).await?;
+        conn.finish().await?;
+
+        row.map(|r| T::from_row(&r)).transpose()
+    }
+
+    /// Most recently created row for each distinct value of `columns` among the rows matching
+    /// `filter`, ordered by `columns`
+    pub async fn get_latest_per(
+        &self,
+        filter: EntityFilter,
+        columns: &[&str],
+    ) -> Result<Vec<T>, anyhow::Error> {
+        let columns = columns.join(", ");
+        let query_str = format!(
+            "SELECT DISTINCT ON ({columns}) * FROM {} {} ORDER BY {columns}, created_at DESC, id DESC",
+            T::table_name(),
+            filter.to_where_clause()
+        );
+
+        let mut query = sqlx::query(&query_str);
+        for value in filter.values() {
+            query = Self::bind_value(query, value)?;
+        }
+
+        let mut conn = ScopedConnection::acquire(self.pools.reader()).await?;
+        let rows = ScopedConnection::run(query.fetch_all(conn.conn())).await?;
+        conn.finish().await?;
+
+        rows.into_iter().map(|r| T::from_row(&r)).collect()
+    }
+
+    /// Start a transaction on the writer pool. Every storage shares the same pools, so one
+    /// transaction can be passed to the `*_in` calls of several storages to write them atomically.
+    pub async fn begin(&self) -> Result<Transaction<'static, Postgres>, anyhow::Error> {
+        Ok(self.pools.writer().begin().await?)
+    }
+
+    /// [`Storage::create`] on the caller's connection, e.g. inside a transaction from [`Self::begin`]
+    pub async fn create_in(&self, conn: &mut PgConnection, entity: &T) -> Result<T, anyhow::Error> {
+        let (columns, values) = entity.to_params()?;
+        let query_str = Self::build_insert_query(&columns);
+
+        let mut query = sqlx::query(&query_str);
+        for value in &values {
+            query = Self::bind_value(query, value)?;
+        }
+
+        ScopedConnection::run(query.execute(conn)).await?;
+
+        tracing::info!("Created {}: {}", T::table_name(), entity);
+        Ok(entity.clone())
+    }
+
+    /// [`Storage::create`], or when a row with the same `conflict` columns already exists, set
+    /// `on_conflict` on it instead: `(column, expression)` pairs whose expressions can refer to
+    /// the existing row by the table name and to the new one as `EXCLUDED`. It's one statement,
+    /// so concurrent writers can't race each other the way a get-then-create can.
+    pub async fn upsert(
+        &self,
+        entity: &T,
+        conflict: &[&str],
+        on_conflict: &[(&str, &str)],
+    ) -> Result<(), anyhow::Error> {
+        let (columns, values) = entity.to_params()?;
+        let set_clauses: Vec<String> = std::iter::once("updated_at = NOW()".to_string())
+            .chain(
+                on_conflict
+                    .iter()
+                    .map(|(column, expression)| format!("{} = {}", column, expression)),
+            )
+            .collect();
+        let query_str = format!(
+            "{} ON CONFLICT ({}) DO UPDATE SET {}",
+            Self::build_insert_query(&columns),
+            conflict.join(", "),
+            set_clauses.join(", ")
+        );
+
+        let mut query = sqlx::query(&query_str);
+        for value in &values {
+            query = Self::bind_value(query, value)?;
+        }
+
+        let mut conn = ScopedConnection::acquire(self.pools.writer()).await?;
+        ScopedConnection::run(query.execute(conn.conn())).await?;
+        conn.finish().await?;
+
+        Ok(())
+    }
+
+    /// Remove `element` from the JSONB array `column` of every row matching `filter`, returning
+    /// how many rows changed. It's one statement, so it can't race writers the way a
+    /// get-then-update can.
+    pub async fn remove_array_element_where(
+        &self,
+        filter: EntityFilter,
+        column: &str,
+        element: &str,
+    ) -> Result<u64, anyhow::Error> {
+        let query_str = format!(
+            "UPDATE {} SET updated_at = NOW(), {} = {} - ${} {}",
+            T::table_name(),
+            column,
+            column,
+            filter.values().len() + 1,
+            filter.to_where_clause()
+        );
+
+        let mut query = sqlx::query(&query_str);
+        for value in filter.values() {
+            query = Self::bind_value(query, value)?;
+        }
+        query = query.bind(element);
+
+        let mut conn = ScopedConnection::acquire(self.pools.writer()).await?;
+        let result = ScopedConnection::run(query.execute(conn.conn())).await?;
+        conn.finish().await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// [`Storage::update`] on the caller's connection, e.g. inside a transaction from [`Self::begin`]
+    pub async fn update_in(
+        &self,
+        conn: &mut PgConnection,
+        entity: &mut T,
+    ) -> Result<T, anyhow::Error> {
+        entity.set_updated_at(Utc::now());
+
+        let (columns, values) = entity.to_params()?;
+        let query_str = Self::build_update_query(&columns);
+
+        let mut query = sqlx::query(&query_str);
+        for value in &values {
+            query = Self::bind_value(query, value)?;
+        }
+
+        ScopedConnection::run(query.execute(conn)).await?;
+
+        tracing::info!("Updated {}", entity);
+        Ok(entity.clone())
+    }
+
+    /// [`Storage::update_where`] on the caller's connection, e.g. inside a transaction from
+    /// [`Self::begin`]
+    pub async fn update_where_in(
+        &self,
+        conn: &mut PgConnection,
+        filter: EntityFilter,
+        fields: Vec<(&'static str, SqlValue)>,
+    ) -> Result<Vec<T>, anyhow::Error> {
+        let query_str = Self::build_update_where_query(&filter, &fields);
+
+        let mut query = sqlx::query(&query_str);
+        for value in filter.values().iter().chain(fields.iter().map(|(_, v)| v)) {
+            query = Self::bind_value(query, value)?;
+        }
+
+        let rows = ScopedConnection::run(query.fetch_all(conn)).await?;
+        rows.into_iter().map(|r| T::from_row(&r)).collect()
+    }
+
+    /// Generate a partial UPDATE query, the filter's values bound before the fields'
+    fn build_update_where_query(
+        filter: &EntityFilter,
+        fields: &[(&'static str, SqlValue)],
+    ) -> String {
+        let offset = filter.values().len();
+        let set_clauses: Vec<String> = std::iter::once("updated_at = NOW()".to_string())
+            .chain(
+                fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (col, _))| format!("{} = ${}", col, offset + i + 1)),
+            )
+            .collect();
+
+        format!(
+            "UPDATE {} SET {} {} RETURNING *",
+            T::table_name(),
+            set_clauses.join(", "),
+            filter.to_where_clause()
+        )
+    }
+
     /// Generate INSERT query dynamically
     fn build_insert_query(columns: &[&str]) -> String {
         let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("${}", i)).collect();
@@ -63,6 +356,9 @@ where
             SqlValue::String(v) => query.bind(v),
             SqlValue::U16(v) => query.bind(Into::<i32>::into(*v)),
             SqlValue::I32(v) => query.bind(v),
+            SqlValue::OptionalI32(v) => query.bind(v),
+            SqlValue::F64(v) => query.bind(v),
+            SqlValue::OptionalF64(v) => query.bind(v),
             SqlValue::Bool(v) => query.bind(v),
             SqlValue::Json(v) => query.bind(v),
             SqlValue::Timestamp(v) => query.bind(v),
@@ -87,6 +383,9 @@ where
             SqlValue::Email(v) => query.bind(v.as_str()),
             SqlValue::UserOrgPermissions(v) => query.bind(v.as_str()),
             SqlValue::DaemonMode(v) => query.bind(serde_json::to_string(v)?),
+            SqlValue::HostState(v) => query.bind(serde_json::to_string(v)?),
+            SqlValue::DiagnosticKind(v) => query.bind(serde_json::to_string(v)?),
+            SqlValue::SecretKind(v) => query.bind(serde_json::to_string(v)?),
             SqlValue::OptionBillingPlan(v) => query.bind(serde_json::to_value(v)?),
             SqlValue::OptionBillingPlanStatus(v) => query.bind(serde_json::to_string(v)?),
             SqlValue::EdgeStyle(v) => query.bind(v.to_string()),
@@ -110,7 +409,10 @@ where
             query = Self::bind_value(query, value)?;
         }
 
-        query.execute(&self.pool).await?;
+        let mut conn = ScopedConnection::acquire(self.pools.writer()).await?;
+        ScopedConnection::run(query.execute(conn.conn())).await?;
+        conn.finish().await?;
+
         tracing::info!("Created {}: {}", T::table_name(), entity);
         Ok(entity.clone())
     }
@@ -133,7 +435,9 @@ where
             query = Self::bind_value(query, value)?;
         }
 
-        let row = query.fetch_optional(&self.pool).await?;
+        let mut conn = ScopedConnection::acquire(self.pools.reader()).await?;
+        let row = ScopedConnection::run(query.fetch_optional(conn.conn())).await?;
+        conn.finish().await?;
 
         let result = row.map(|r| T::from_row(&r)).transpose()?;
 
@@ -152,7 +456,40 @@ where
             query = Self::bind_value(query, value)?;
         }
 
-        let rows = query.fetch_all(&self.pool).await?;
+        let mut conn = ScopedConnection::acquire(self.pools.reader()).await?;
+        let rows = ScopedConnection::run(query.fetch_all(conn.conn())).await?;
+        conn.finish().await?;
+
+        rows.into_iter().map(|r| T::from_row(&r)).collect()
+    }
+
+    async fn get_page(
+        &self,
+        filter: EntityFilter,
+        after: Option<PageCursor>,
+        limit: usize,
+    ) -> Result<Vec<T>, anyhow::Error> {
+        let filter = match &after {
+            Some(cursor) => filter.after(cursor),
+            None => filter,
+        };
+
+        let query_str = format!(
+            "SELECT * FROM {} {} ORDER BY created_at ASC, id ASC LIMIT {}",
+            T::table_name(),
+            filter.to_where_clause(),
+            limit
+        );
+
+        let mut query = sqlx::query(&query_str);
+        for value in filter.values() {
+            query = Self::bind_value(query, value)?;
+        }
+
+        let mut conn = ScopedConnection::acquire(self.pools.reader()).await?;
+        let rows = ScopedConnection::run(query.fetch_all(conn.conn())).await?;
+        conn.finish().await?;
+
         rows.into_iter().map(|r| T::from_row(&r)).collect()
     }
 
@@ -169,17 +506,60 @@ where
 
         tracing::info!("Updated {}", entity);
 
-        query.execute(&self.pool).await?;
+        let mut conn = ScopedConnection::acquire(self.pools.writer()).await?;
+        ScopedConnection::run(query.execute(conn.conn())).await?;
+        conn.finish().await?;
+
         Ok(entity.clone())
     }
 
+    async fn update_where(
+        &self,
+        filter: EntityFilter,
+        fields: Vec<(&'static str, SqlValue)>,
+    ) -> Result<Vec<T>, anyhow::Error> {
+        let query_str = Self::build_update_where_query(&filter, &fields);
+
+        let mut query = sqlx::query(&query_str);
+        for value in filter.values().iter().chain(fields.iter().map(|(_, v)| v)) {
+            query = Self::bind_value(query, value)?;
+        }
+
+        let mut conn = ScopedConnection::acquire(self.pools.writer()).await?;
+        let rows = ScopedConnection::run(query.fetch_all(conn.conn())).await?;
+        conn.finish().await?;
+
+        rows.into_iter().map(|r| T::from_row(&r)).collect()
+    }
+
     async fn delete(&self, id: &Uuid) -> Result<(), anyhow::Error> {
         let query_str = format!("DELETE FROM {} WHERE id = $1", T::table_name());
 
-        sqlx::query(&query_str).bind(id).execute(&self.pool).await?;
+        let mut conn = ScopedConnection::acquire(self.pools.writer()).await?;
+        ScopedConnection::run(sqlx::query(&query_str).bind(id).execute(conn.conn())).await?;
+        conn.finish().await?;
 
         tracing::info!("Deleted {} with id: {}", T::table_name(), id);
 
         Ok(())
     }
+
+    async fn delete_where(&self, filter: EntityFilter) -> Result<u64, anyhow::Error> {
+        let query_str = format!(
+            "DELETE FROM {} {}",
+            T::table_name(),
+            filter.to_where_clause()
+        );
+
+        let mut query = sqlx::query(&query_str);
+        for value in filter.values() {
+            query = Self::bind_value(query, value)?;
+        }
+
+        let mut conn = ScopedConnection::acquire(self.pools.writer()).await?;
+        let result = ScopedConnection::run(query.execute(conn.conn())).await?;
+        conn.finish().await?;
+
+        Ok(result.rows_affected())
+    }
 }
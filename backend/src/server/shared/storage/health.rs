@@ -0,0 +1,100 @@
+use futures::future::{BoxFuture, join_all};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use crate::server::shared::storage::{
+    factory::StorageFactory, generic::GenericPostgresStorage, traits::StorableEntity,
+};
+
+/// How long each storage component has to answer a readiness check
+pub const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ComponentHealth {
+    /// Table the component stores its entities in
+    pub component: String,
+    pub healthy: bool,
+    pub latency_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StorageHealth {
+    /// Whether every component is healthy
+    pub healthy: bool,
+    pub components: Vec<ComponentHealth>,
+}
+
+impl StorageHealth {
+    pub fn unhealthy_components(&self) -> Vec<&str> {
+        self.components
+            .iter()
+            .filter(|c| !c.healthy)
+            .map(|c| c.component.as_str())
+            .collect()
+    }
+}
+
+fn check<T>(
+    storage: &GenericPostgresStorage<T>,
+    timeout: Duration,
+) -> BoxFuture<'_, ComponentHealth>
+where
+    T: StorableEntity + Display,
+{
+    Box::pin(async move {
+        let started = Instant::now();
+        let error = match tokio::time::timeout(timeout, storage.ping()).await {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(_) => Some(format!("No response within {:?}", timeout)),
+        };
+
+        ComponentHealth {
+            component: T::table_name().to_string(),
+            healthy: error.is_none(),
+            latency_ms: started.elapsed().as_millis() as u64,
+            error,
+        }
+    })
+}
+
+impl StorageFactory {
+    /// Ping every storage component at once, each bounded by `timeout`, so one bad table or
+    /// connection is reported on its own rather than failing the whole check
+    pub async fn health_check(&self, timeout: Duration) -> StorageHealth {
+        let components = join_all([
+            check(&self.api_keys, timeout),
+            check(&self.bootstrap_tokens, timeout),
+            check(&self.users, timeout),
+            check(&self.networks, timeout),
+            check(&self.hosts, timeout),
+            check(&self.groups, timeout),
+            check(&self.group_rules, timeout),
+            check(&self.daemons, timeout),
+            check(&self.daemon_metrics, timeout),
+            check(&self.subnets, timeout),
+            check(&self.services, timeout),
+            check(&self.organizations, timeout),
+            check(&self.discovery, timeout),
+            check(&self.diagnostics, timeout),
+            check(&self.check_dependencies, timeout),
+            check(&self.check_suites, timeout),
+            check(&self.discovery_webhooks, timeout),
+            check(&self.secrets, timeout),
+            check(&self.notification_channels, timeout),
+            check(&self.notification_subscriptions, timeout),
+            check(&self.audit_events, timeout),
+        ])
+        .await;
+
+        StorageHealth {
+            healthy: components.iter().all(|c| c.healthy),
+            components,
+        }
+    }
+}
@@ -1,6 +1,10 @@
+pub mod cursor;
 pub mod factory;
 pub mod filter;
 pub mod generic;
+pub mod health;
+pub mod pools;
 pub mod seed_data;
+#[cfg(test)]
 pub mod tests;
 pub mod traits;
@@ -0,0 +1,162 @@
+use anyhow::Result;
+use axum::{extract::Request, http::header, middleware::Next, response::Response};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+use crate::server::auth::middleware::api_key_from_headers;
+use crate::server::shared::sync::lock;
+
+/// Cookie holding the login session id
+const SESSION_COOKIE: &str = "session_id";
+
+tokio::task_local! {
+    static STORAGE_CLIENT: StorageClient;
+    static READ_FROM_PRIMARY: ();
+}
+
+/// Run `fut` with all of its reads going to the primary, for lookups that decide whether a
+/// caller is still allowed in and so mustn't see a replica's stale copy
+pub async fn read_from_primary<F: Future>(fut: F) -> F::Output {
+    READ_FROM_PRIMARY.scope((), fut).await
+}
+
+/// Who storage is being used on behalf of, so only their own writes route their reads to the
+/// primary. Storage used outside any client, e.g. by background tasks, shares one window.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct StorageClient(pub String);
+
+impl StorageClient {
+    /// The client of the request being handled on this task, if any
+    pub fn current() -> Self {
+        STORAGE_CLIENT.try_with(Clone::clone).unwrap_or_default()
+    }
+
+    /// Run `fut` on behalf of this client
+    pub async fn scope<F: Future>(self, fut: F) -> F::Output {
+        STORAGE_CLIENT.scope(self, fut).await
+    }
+
+    /// The client making `request`: its login session, else its API key however it's presented,
+    /// else other credentials, else just the request itself. Session ids and credentials are
+    /// hashed so they aren't kept in memory.
+    pub(crate) fn of(request: &Request) -> Self {
+        let headers = request.headers();
+        let session = headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(';'))
+            .filter_map(|c| c.trim().split_once('='))
+            .find(|(name, _)| *name == SESSION_COOKIE)
+            .map(|(_, id)| id);
+
+        if let Some(session) = session {
+            return Self(format!("session:{}", digest(session.as_bytes())));
+        }
+        if let Ok(Some(api_key)) = api_key_from_headers(headers) {
+            return Self(format!("api-key:{}", digest(api_key.as_bytes())));
+        }
+        if let Some(credentials) = headers.get(header::AUTHORIZATION) {
+            return Self(format!("credentials:{}", digest(credentials.as_bytes())));
+        }
+        Self(format!("request:{}", Uuid::new_v4()))
+    }
+}
+
+fn digest(value: &[u8]) -> String {
+    hex::encode(Sha256::digest(value))
+}
+
+/// Handle each API request on behalf of the client making it, for read-your-writes routing
+pub async fn track_storage_client(request: Request, next: Next) -> Response {
+    StorageClient::of(&request).scope(next.run(request)).await
+}
+
+/// Primary pool plus an optional read replica pool.
+///
+/// Writes always go to the primary. Reads go to the replica unless none is configured, they run
+/// under [`read_from_primary`], or the same [`StorageClient`] wrote within the read-your-writes
+/// window, in which case the replica may not have caught up yet and the primary is used instead.
+pub struct DatabasePools {
+    primary: PgPool,
+    read: Option<PgPool>,
+    read_your_writes: Duration,
+    last_writes: Mutex<HashMap<StorageClient, Instant>>,
+}
+
+impl DatabasePools {
+    pub fn new(primary: PgPool, read: Option<PgPool>, read_your_writes: Duration) -> Self {
+        Self {
+            primary,
+            read,
+            read_your_writes,
+            last_writes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn primary_only(primary: PgPool) -> Self {
+        Self::new(primary, None, Duration::ZERO)
+    }
+
+    pub async fn connect(
+        database_url: &str,
+        read_url: Option<&str>,
+        read_your_writes: Duration,
+    ) -> Result<Self> {
+        let primary = PgPool::connect(database_url).await?;
+        let read = match read_url {
+            Some(url) => Some(PgPool::connect(url).await?),
+            None => None,
+        };
+
+        Ok(Self::new(primary, read, read_your_writes))
+    }
+
+    pub fn primary(&self) -> &PgPool {
+        &self.primary
+    }
+
+    /// Read replica pool, when one is configured
+    pub fn replica(&self) -> Option<&PgPool> {
+        self.read.as_ref()
+    }
+
+    /// Pool to use for a write, recording it against the current client for read-your-writes
+    /// routing
+    pub fn writer(&self) -> &PgPool {
+        if self.read.is_some() {
+            let mut last_writes = lock(&self.last_writes);
+            // Forget clients whose window has passed, so the map only holds recent writers
+            last_writes.retain(|_, at| at.elapsed() < self.read_your_writes);
+            last_writes.insert(StorageClient::current(), Instant::now());
+        }
+        &self.primary
+    }
+
+    /// Pool to use for a read
+    pub fn reader(&self) -> &PgPool {
+        let Some(read) = &self.read else {
+            return &self.primary;
+        };
+        if READ_FROM_PRIMARY.try_with(|_| ()).is_ok() {
+            return &self.primary;
+        }
+
+        let recently_written = lock(&self.last_writes)
+            .get(&StorageClient::current())
+            .is_some_and(|at| at.elapsed() < self.read_your_writes);
+
+        if recently_written {
+            &self.primary
+        } else {
+            read
+        }
+    }
+}
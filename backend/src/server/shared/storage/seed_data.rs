@@ -4,7 +4,7 @@ use uuid::Uuid;
 
 use crate::server::{
     hosts::r#impl::{
-        base::{Host, HostBase},
+        base::{Host, HostBase, HostState},
         interfaces::{Interface, InterfaceBase},
         ports::{Port, PortBase},
         targets::HostTarget,
@@ -93,6 +93,10 @@ pub fn create_remote_host(remote_subnet: &Subnet, network_id: Uuid) -> (Host, Se
         source: EntitySource::System,
         virtualization: None,
         hidden: false,
+        state: HostState::Active,
+        group_ids: Vec::new(),
+        tags: Vec::new(),
+        ip_conflicts: Vec::new(),
     };
 
     let mut host = Host::new(base);
@@ -135,6 +139,10 @@ pub fn create_internet_connectivity_host(
         source: EntitySource::System,
         virtualization: None,
         hidden: false,
+        state: HostState::Active,
+        group_ids: Vec::new(),
+        tags: Vec::new(),
+        ip_conflicts: Vec::new(),
     };
 
     let mut host = Host::new(base);
@@ -175,6 +183,10 @@ pub fn create_public_dns_host(internet_subnet: &Subnet, network_id: Uuid) -> (Ho
         source: EntitySource::System,
         virtualization: None,
         hidden: false,
+        state: HostState::Active,
+        group_ids: Vec::new(),
+        tags: Vec::new(),
+        ip_conflicts: Vec::new(),
     };
 
     let mut host = Host::new(base);
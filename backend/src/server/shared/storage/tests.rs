@@ -1,3 +1,29 @@
+use crate::{
+    server::shared::{
+        deadline::{DeadlineExceeded, RequestDeadline},
+        storage::{
+            factory::StorageFactory,
+            filter::EntityFilter,
+            generic::GenericPostgresStorage,
+            pools::{DatabasePools, StorageClient, read_from_primary},
+            traits::Storage,
+        },
+        types::api::ApiError,
+    },
+    tests::{host, network, organization, setup_test_db},
+};
+use axum::{
+    body::Body,
+    http::{Request, StatusCode, header},
+};
+use serial_test::serial;
+use sqlx::PgPool;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use testcontainers::{ContainerAsync, GenericImage};
+
 #[tokio::test]
 pub async fn test_database_schema_backward_compatibility() {
     use crate::tests::SERVER_DB_FIXTURE;
@@ -103,3 +129,233 @@ pub async fn test_database_schema_backward_compatibility() {
         panic!("No database fixture found at {}", SERVER_DB_FIXTURE);
     }
 }
+
+#[tokio::test]
+#[serial]
+async fn test_storage_query_cancelled_at_request_deadline() {
+    let (pool, database_url, _container) = setup_test_db().await;
+    let storage = StorageFactory::new(&database_url, false).await.unwrap();
+
+    // Hold an exclusive lock so any read of hosts blocks until it is cancelled
+    let mut locker = pool.begin().await.unwrap();
+    sqlx::query("LOCK TABLE hosts IN ACCESS EXCLUSIVE MODE")
+        .execute(&mut *locker)
+        .await
+        .unwrap();
+
+    let started = Instant::now();
+    let err = RequestDeadline::after(Duration::from_millis(200))
+        .scope(storage.hosts.get_all(EntityFilter::unfiltered()))
+        .await
+        .unwrap_err();
+
+    assert!(err.is::<DeadlineExceeded>(), "unexpected error: {}", err);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(ApiError::from(err).status, StatusCode::GATEWAY_TIMEOUT);
+
+    // The statement was cancelled server side rather than left waiting on the lock
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let still_running: i64 = sqlx::query_scalar(
+        "SELECT count(*) FROM pg_stat_activity WHERE state = 'active' AND query LIKE 'SELECT * FROM hosts%'",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(still_running, 0);
+
+    locker.rollback().await.unwrap();
+}
+
+async fn replica_storage(
+    read_your_writes: Duration,
+) -> (StorageFactory, PgPool, PgPool, ContainerAsync<GenericImage>) {
+    let (primary, database_url, container) = setup_test_db().await;
+    let read = PgPool::connect(&database_url).await.unwrap();
+
+    let pools = DatabasePools::new(primary.clone(), Some(read.clone()), read_your_writes);
+    let storage = StorageFactory::from_pools(pools, false).await.unwrap();
+
+    (storage, primary, read, container)
+}
+
+#[tokio::test]
+#[serial]
+async fn test_reads_use_read_pool() {
+    let (storage, primary, _read, _container) = replica_storage(Duration::ZERO).await;
+
+    let organization = storage.organizations.create(&organization()).await.unwrap();
+    let network = storage
+        .networks
+        .create(&network(&organization.id))
+        .await
+        .unwrap();
+    let host = storage.hosts.create(&host(&network.id)).await.unwrap();
+
+    // With the primary gone, reads can only succeed if they are served by the read pool
+    primary.close().await;
+
+    let fetched = storage.hosts.get_by_id(&host.id).await.unwrap();
+    assert_eq!(fetched.map(|h| h.id), Some(host.id));
+    assert!(storage.hosts.create(&host).await.is_err());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_reads_after_write_use_primary_within_window() {
+    let (storage, _primary, read, _container) = replica_storage(Duration::from_secs(60)).await;
+
+    let organization = storage.organizations.create(&organization()).await.unwrap();
+
+    // Inside the read-your-writes window the read pool must not be used
+    read.close().await;
+
+    let fetched = storage
+        .organizations
+        .get_by_id(&organization.id)
+        .await
+        .unwrap();
+    assert_eq!(fetched.map(|o| o.id), Some(organization.id));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_only_the_writing_client_reads_from_primary() {
+    let (storage, primary, _read, _container) = replica_storage(Duration::from_secs(60)).await;
+    let writer = StorageClient("session:writer".to_string());
+    let other = StorageClient("session:other".to_string());
+
+    let organization = writer
+        .clone()
+        .scope(storage.organizations.create(&organization()))
+        .await
+        .unwrap();
+
+    // With the primary gone, only reads served by the read pool succeed
+    primary.close().await;
+
+    let fetched = other
+        .scope(storage.organizations.get_by_id(&organization.id))
+        .await
+        .unwrap();
+    assert_eq!(fetched.map(|o| o.id), Some(organization.id));
+    assert!(
+        writer
+            .scope(storage.organizations.get_by_id(&organization.id))
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_reads_under_read_from_primary_skip_the_read_pool() {
+    let (storage, _primary, read, _container) = replica_storage(Duration::ZERO).await;
+
+    let organization = storage.organizations.create(&organization()).await.unwrap();
+
+    // With the read pool gone, only reads served by the primary succeed
+    read.close().await;
+
+    let fetched = read_from_primary(storage.organizations.get_by_id(&organization.id))
+        .await
+        .unwrap();
+    assert_eq!(fetched.map(|o| o.id), Some(organization.id));
+    assert!(
+        storage
+            .organizations
+            .get_by_id(&organization.id)
+            .await
+            .is_err()
+    );
+}
+
+#[test]
+fn test_storage_client_keyed_on_hashed_credentials() {
+    let client = |name: &str, value: &str| {
+        StorageClient::of(
+            &Request::builder()
+                .header(name, value)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    // An API key is the same client whichever header presents it
+    let api_key = client("x-api-key", "nv_daemon_key");
+    assert_eq!(
+        api_key,
+        client(header::AUTHORIZATION.as_str(), "Bearer nv_daemon_key")
+    );
+    assert_ne!(api_key, client("x-api-key", "nv_other_key"));
+
+    // Neither session ids nor keys are kept as given
+    let session = client(
+        header::COOKIE.as_str(),
+        "theme=dark; session_id=live-session",
+    );
+    assert!(session.0.starts_with("session:"));
+    for client in [&session, &api_key] {
+        assert!(!client.0.contains("live-session") && !client.0.contains("nv_daemon_key"));
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_health_check_reports_failing_component() {
+    let (_pool, database_url, _container) = setup_test_db().await;
+    let mut storage = StorageFactory::new(&database_url, false).await.unwrap();
+
+    let health = storage.health_check(Duration::from_secs(2)).await;
+    assert!(health.healthy);
+    assert!(
+        health
+            .components
+            .iter()
+            .any(|c| c.component == "diagnostics")
+    );
+
+    // Diagnostics alone loses its connection
+    let closed = PgPool::connect(&database_url).await.unwrap();
+    closed.close().await;
+    storage.diagnostics = Arc::new(GenericPostgresStorage::with_pools(Arc::new(
+        DatabasePools::primary_only(closed),
+    )));
+
+    let health = storage.health_check(Duration::from_secs(2)).await;
+    assert!(!health.healthy);
+    assert_eq!(health.unhealthy_components(), vec!["diagnostics"]);
+
+    let diagnostics = health
+        .components
+        .iter()
+        .find(|c| c.component == "diagnostics")
+        .unwrap();
+    assert!(diagnostics.error.is_some());
+    let hosts = health
+        .components
+        .iter()
+        .find(|c| c.component == "hosts")
+        .unwrap();
+    assert!(hosts.healthy);
+    assert!(hosts.error.is_none());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_health_check_covers_primary_and_replica() {
+    let (storage, _primary, read, _container) = replica_storage(Duration::ZERO).await;
+
+    let health = storage.health_check(Duration::from_secs(2)).await;
+    assert!(health.healthy);
+
+    // Losing only the replica is unhealthy
+    read.close().await;
+    let health = storage.health_check(Duration::from_secs(2)).await;
+    assert!(!health.healthy);
+
+    // As is losing only the primary, which reads alone would never notice
+    let (storage, primary, _read, _container) = replica_storage(Duration::ZERO).await;
+    primary.close().await;
+    let health = storage.health_check(Duration::from_secs(2)).await;
+    assert!(!health.healthy);
+}
@@ -11,15 +11,21 @@ use uuid::Uuid;
 use crate::server::{
     billing::types::base::BillingPlan,
     daemons::r#impl::{api::DaemonCapabilities, base::DaemonMode},
+    diagnostics::r#impl::base::DiagnosticKind,
     discovery::r#impl::types::{DiscoveryType, RunType},
     groups::r#impl::types::GroupType,
     hosts::r#impl::{
-        interfaces::Interface, ports::Port, targets::HostTarget, virtualization::HostVirtualization,
+        base::HostState, interfaces::Interface, ports::Port, targets::HostTarget,
+        virtualization::HostVirtualization,
     },
+    secrets::r#impl::base::SecretKind,
     services::r#impl::{
         bindings::Binding, definitions::ServiceDefinition, virtualization::ServiceVirtualization,
     },
-    shared::{storage::filter::EntityFilter, types::entities::EntitySource},
+    shared::{
+        storage::{cursor::PageCursor, filter::EntityFilter},
+        types::entities::EntitySource,
+    },
     subnets::r#impl::types::SubnetType,
     topology::types::edges::EdgeStyle,
     users::r#impl::permissions::UserOrgPermissions,
@@ -31,8 +37,25 @@ pub trait Storage<T: StorableEntity>: Send + Sync {
     async fn get_by_id(&self, id: &Uuid) -> Result<Option<T>, anyhow::Error>;
     async fn get_all(&self, filter: EntityFilter) -> Result<Vec<T>, anyhow::Error>;
     async fn get_one(&self, filter: EntityFilter) -> Result<Option<T>, anyhow::Error>;
+    /// Up to `limit` rows after `after`, in `(created_at, id)` order
+    async fn get_page(
+        &self,
+        filter: EntityFilter,
+        after: Option<PageCursor>,
+        limit: usize,
+    ) -> Result<Vec<T>, anyhow::Error>;
     async fn update(&self, entity: &mut T) -> Result<T, anyhow::Error>;
+    /// Set only `fields`, plus `updated_at`, on every row matching `filter`, returning the rows
+    /// as updated. Unlike [`Storage::update`] this never writes back columns the caller didn't
+    /// change, so it can't undo a concurrent write to them.
+    async fn update_where(
+        &self,
+        filter: EntityFilter,
+        fields: Vec<(&'static str, SqlValue)>,
+    ) -> Result<Vec<T>, anyhow::Error>;
     async fn delete(&self, id: &Uuid) -> Result<(), anyhow::Error>;
+    /// Delete every row matching `filter`, returning how many were deleted
+    async fn delete_where(&self, filter: EntityFilter) -> Result<u64, anyhow::Error>;
 }
 
 pub trait StorableEntity: Sized + Clone + Send + Sync + 'static {
@@ -67,6 +90,9 @@ pub enum SqlValue {
     String(String),
     OptionalString(Option<String>),
     I32(i32),
+    OptionalI32(Option<i32>),
+    F64(f64),
+    OptionalF64(Option<f64>),
     U16(u16),
     Bool(bool),
     Json(serde_json::Value),
@@ -94,4 +120,7 @@ pub enum SqlValue {
     OptionBillingPlanStatus(Option<SubscriptionStatus>),
     EdgeStyle(EdgeStyle),
     DaemonMode(DaemonMode),
+    HostState(HostState),
+    DiagnosticKind(DiagnosticKind),
+    SecretKind(SecretKind),
 }
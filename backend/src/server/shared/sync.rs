@@ -0,0 +1,8 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Lock `mutex`, carrying on with its data if an earlier holder panicked. The mutexes this is
+/// used for guard caches and counters, which stay usable after a panic mid-update, so one panic
+/// shouldn't take every later caller down with it.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -1,3 +1,13 @@
+use crate::server::{
+    auth::impersonation::ImpersonationError,
+    daemons::r#impl::{
+        base::{DaemonAuthFailed, DaemonPaused, DaemonProtocolUnsupported},
+        transfer::DaemonTransferBlocked,
+    },
+    discovery::r#impl::target_policy::ScanTargetDenied,
+    secrets::r#impl::base::SecretError,
+    shared::deadline::DeadlineExceeded,
+};
 use axum::{Json, http::StatusCode, response::Response};
 use serde::{Deserialize, Deserializer, Serialize, de::DeserializeOwned};
 
@@ -8,6 +18,9 @@ pub struct ApiResponse<T> {
     pub success: bool,
     pub data: Option<T>,
     pub error: Option<String>,
+    /// Machine-readable code for errors clients are expected to act on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
 }
 
 impl<T> ApiResponse<T> {
@@ -16,6 +29,7 @@ impl<T> ApiResponse<T> {
             success: true,
             data: Some(data),
             error: None,
+            code: None,
         }
     }
 
@@ -24,6 +38,7 @@ impl<T> ApiResponse<T> {
             success: false,
             data: None,
             error: Some(message),
+            code: None,
         }
     }
 }
@@ -32,11 +47,21 @@ impl<T> ApiResponse<T> {
 pub struct ApiError {
     pub status: StatusCode,
     pub message: String,
+    pub code: Option<&'static str>,
 }
 
 impl ApiError {
     pub fn new(status: StatusCode, message: String) -> Self {
-        Self { status, message }
+        Self {
+            status,
+            message,
+            code: None,
+        }
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
     }
 
     pub fn conflict(message: &str) -> Self {
@@ -62,17 +87,73 @@ impl ApiError {
     pub fn unauthorized(message: String) -> Self {
         Self::new(StatusCode::UNAUTHORIZED, message.to_string())
     }
+
+    pub fn bad_gateway(message: &str) -> Self {
+        Self::new(StatusCode::BAD_GATEWAY, message.to_string())
+    }
+
+    pub fn gateway_timeout(message: &str) -> Self {
+        Self::new(StatusCode::GATEWAY_TIMEOUT, message.to_string())
+    }
 }
 
 impl axum::response::IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        let response = ApiResponse::<()>::error(self.message);
+        let mut response = ApiResponse::<()>::error(self.message);
+        response.code = self.code.map(str::to_string);
         (self.status, Json(response)).into_response()
     }
 }
 
 impl From<anyhow::Error> for ApiError {
     fn from(err: anyhow::Error) -> Self {
+        if err.is::<DeadlineExceeded>() {
+            tracing::warn!("Request deadline exceeded");
+            return Self::new(StatusCode::GATEWAY_TIMEOUT, err.to_string());
+        }
+
+        if err.is::<ScanTargetDenied>() {
+            return Self::forbidden(&err.to_string());
+        }
+
+        if err.is::<DaemonAuthFailed>() {
+            return Self::bad_gateway(&err.to_string()).with_code(DaemonAuthFailed::CODE);
+        }
+
+        if err.is::<DaemonPaused>() {
+            return Self::conflict(&err.to_string()).with_code(DaemonPaused::CODE);
+        }
+
+        if err.is::<DaemonProtocolUnsupported>() {
+            return Self::conflict(&err.to_string()).with_code(DaemonProtocolUnsupported::CODE);
+        }
+
+        if err.is::<DaemonTransferBlocked>() {
+            return Self::conflict(&err.to_string()).with_code(DaemonTransferBlocked::CODE);
+        }
+
+        if let Some(e) = err.downcast_ref::<SecretError>() {
+            return match e {
+                SecretError::NameTaken { .. } => Self::conflict(&e.to_string()),
+                _ => Self::bad_request(&e.to_string()),
+            };
+        }
+
+        if let Some(e) = err.downcast_ref::<ImpersonationError>() {
+            return match e {
+                ImpersonationError::Nested | ImpersonationError::Disabled => {
+                    Self::conflict(&e.to_string())
+                }
+                ImpersonationError::SelfTarget => Self::bad_request(&e.to_string()),
+                ImpersonationError::NotPermitted | ImpersonationError::Forbidden => {
+                    Self::forbidden(&e.to_string())
+                }
+                ImpersonationError::RateLimited(_) => {
+                    Self::new(StatusCode::TOO_MANY_REQUESTS, e.to_string())
+                }
+            };
+        }
+
         tracing::error!("Internal error: {}", err);
         Self::internal_error(&err.to_string())
     }
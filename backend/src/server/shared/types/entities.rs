@@ -1,5 +1,5 @@
 use crate::server::discovery::r#impl::types::DiscoveryType;
-use crate::server::discovery::r#impl::types::HostNamingFallback;
+use crate::server::discovery::r#impl::types::{HostNamingFallback, ScanRate};
 use crate::server::services::r#impl::patterns::MatchDetails;
 use chrono::DateTime;
 use chrono::Utc;
@@ -29,12 +29,13 @@ pub enum EntitySource {
 pub struct DiscoveryMetadata {
     #[serde(flatten)]
     pub discovery_type: DiscoveryType,
-    pub daemon_id: Uuid,
+    /// None for imported results
+    pub daemon_id: Option<Uuid>,
     pub date: DateTime<Utc>,
 }
 
 impl DiscoveryMetadata {
-    pub fn new(discovery_type: DiscoveryType, daemon_id: Uuid) -> Self {
+    pub fn new(discovery_type: DiscoveryType, daemon_id: Option<Uuid>) -> Self {
         Self {
             discovery_type,
             daemon_id,
@@ -49,8 +50,11 @@ impl Default for DiscoveryMetadata {
             discovery_type: DiscoveryType::Network {
                 subnet_ids: None,
                 host_naming_fallback: HostNamingFallback::BestService,
+                scan_rate: ScanRate::default(),
+                snmp_credential: None,
+                targets: Vec::new(),
             },
-            daemon_id: Uuid::new_v4(),
+            daemon_id: Some(Uuid::new_v4()),
             date: Utc::now(),
         }
     }
@@ -91,7 +91,10 @@ impl Subnet {
                     name: cidr.to_string(),
                     subnet_type,
                     source: EntitySource::Discovery {
-                        metadata: vec![DiscoveryMetadata::new(discovery_type.clone(), daemon_id)],
+                        metadata: vec![DiscoveryMetadata::new(
+                            discovery_type.clone(),
+                            Some(daemon_id),
+                        )],
                     },
                 }))
             }
@@ -1,5 +1,5 @@
 use crate::server::{
-    shared::handlers::traits::CrudHandlers,
+    shared::handlers::traits::{CrudHandlers, EntityScope},
     subnets::{r#impl::base::Subnet, service::SubnetService},
 };
 
@@ -9,4 +9,8 @@ impl CrudHandlers for Subnet {
     fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
         &state.services.subnet_service
     }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Network(self.base.network_id)
+    }
 }
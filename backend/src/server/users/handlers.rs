@@ -1,9 +1,12 @@
-use crate::server::auth::middleware::{AuthenticatedUser, RequireAdmin, RequireMember};
+use crate::server::auth::impersonation::Impersonation;
+use crate::server::auth::middleware::{NotImpersonated, RequireAdmin, RequireMember};
 use crate::server::shared::handlers::traits::{CrudHandlers, delete_handler, get_by_id_handler};
 use crate::server::shared::storage::filter::EntityFilter;
 use crate::server::shared::types::api::ApiError;
+use crate::server::users::r#impl::api::UserEnabledRequest;
 use crate::server::users::r#impl::base::User;
 use crate::server::users::r#impl::permissions::UserOrgPermissions;
+use crate::server::users::service::LastEnabledAdmin;
 use crate::server::{
     config::AppState,
     shared::{
@@ -13,9 +16,11 @@ use crate::server::{
 };
 use anyhow::anyhow;
 use axum::extract::Path;
-use axum::routing::{delete, get, put};
+use axum::routing::{delete, get, post, put};
 use axum::{Router, extract::State, response::Json};
+use chrono::Utc;
 use std::sync::Arc;
+use tower_sessions::Session;
 use uuid::Uuid;
 
 pub fn create_router() -> Router<Arc<AppState>> {
@@ -24,6 +29,8 @@ pub fn create_router() -> Router<Arc<AppState>> {
         .route("/{id}", put(update_user))
         .route("/{id}", delete(delete_user))
         .route("/{id}", get(get_by_id_handler::<User>))
+        .route("/{id}/enabled", put(set_user_enabled))
+        .route("/{id}/impersonate", post(impersonate_user))
 }
 
 pub async fn get_all_users(
@@ -81,7 +88,7 @@ pub async fn delete_user(
 
 pub async fn update_user(
     State(state): State<Arc<AppState>>,
-    user: AuthenticatedUser,
+    NotImpersonated(user): NotImpersonated,
     Path(id): Path<Uuid>,
     Json(mut request): Json<User>,
 ) -> ApiResult<Json<ApiResponse<User>>> {
@@ -109,6 +116,16 @@ pub async fn update_user(
         ));
     }
 
+    if request.base.enabled != existing.base.enabled {
+        return Err(ApiError::forbidden(
+            "You cannot enable or disable your own account",
+        ));
+    }
+
+    // Never sent to clients, so not part of the request. Passwords change through their own route.
+    request.base.sessions_revoked_at = existing.base.sessions_revoked_at;
+    request.base.password_hash = existing.base.password_hash;
+
     let updated = service
         .update(&mut request)
         .await
@@ -116,3 +133,85 @@ pub async fn update_user(
 
     Ok(Json(ApiResponse::success(updated)))
 }
+
+/// Enable or disable a user in the admin's organization. Disabling also ends the user's sessions.
+pub async fn set_user_enabled(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(admin): RequireAdmin,
+    Path(id): Path<Uuid>,
+    Json(request): Json<UserEnabledRequest>,
+) -> ApiResult<Json<ApiResponse<User>>> {
+    let user_service = &state.services.user_service;
+
+    let target = user_service
+        .get_by_id(&id)
+        .await?
+        .filter(|u| u.base.organization_id == admin.organization_id)
+        .ok_or_else(|| ApiError::not_found(format!("User '{}' not found", id)))?;
+
+    if target.id == admin.user_id {
+        return Err(ApiError::forbidden(
+            "You cannot enable or disable your own account",
+        ));
+    }
+
+    if admin.permissions < target.base.permissions {
+        return Err(ApiError::unauthorized(
+            "You can only enable or disable users with lower permissions than you".to_string(),
+        ));
+    }
+
+    let user = if request.enabled {
+        user_service.enable_user(&id).await?
+    } else {
+        let user = user_service.disable_user(&id).await.map_err(|e| {
+            match e.downcast_ref::<LastEnabledAdmin>() {
+                Some(last) => ApiError::conflict(&last.to_string()),
+                None => e.into(),
+            }
+        })?;
+
+        state
+            .services
+            .auth_service
+            .sessions
+            .end_all(&user.id, None)
+            .await?;
+        user
+    };
+
+    tracing::info!(
+        admin_id = %admin.user_id,
+        user_id = %user.id,
+        enabled = user.base.enabled,
+        "User enabled state changed"
+    );
+
+    Ok(Json(ApiResponse::success(user)))
+}
+
+/// Act as a user of the admin's organization with lower permissions, for support. The admin's
+/// session is switched to the user until the impersonation is ended or times out.
+pub async fn impersonate_user(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(admin): RequireAdmin,
+    session: Session,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<ApiResponse<Impersonation>>> {
+    let target = state
+        .services
+        .user_service
+        .get_by_id(&id)
+        .await?
+        .filter(|u| u.base.organization_id == admin.organization_id)
+        .ok_or_else(|| ApiError::not_found(format!("User '{}' not found", id)))?;
+
+    let impersonation = state
+        .services
+        .auth_service
+        .impersonations
+        .start(&session, &admin, &target, Utc::now())
+        .await?;
+
+    Ok(Json(ApiResponse::success(impersonation)))
+}
@@ -28,6 +28,16 @@ pub struct UserBase {
     pub oidc_subject: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub oidc_linked_at: Option<DateTime<Utc>>,
+    /// Disabled users can't log in and their existing sessions are rejected
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Sessions started before this are rejected; see `UserService::revoke_sessions`
+    #[serde(default, skip_serializing)]
+    pub sessions_revoked_at: Option<DateTime<Utc>>,
+}
+
+fn default_enabled() -> bool {
+    true
 }
 
 impl Default for UserBase {
@@ -40,23 +50,65 @@ impl Default for UserBase {
             oidc_linked_at: None,
             oidc_provider: None,
             oidc_subject: None,
+            enabled: true,
+            sessions_revoked_at: None,
         }
     }
 }
 
 impl UserBase {
+    /// Domain used for placeholder emails on unnamed seed users
+    pub const SEED_EMAIL_DOMAIN: &'static str = "netvisor.io";
+
     pub fn new_seed(organization_id: Uuid) -> Self {
         Self {
-            email: EmailAddress::new_unchecked(format!("{}@netvisor.io", Uuid::new_v4())),
+            email: EmailAddress::new_unchecked(format!(
+                "{}@{}",
+                Uuid::new_v4(),
+                Self::SEED_EMAIL_DOMAIN
+            )),
             permissions: UserOrgPermissions::Owner,
             organization_id,
             password_hash: None,
             oidc_linked_at: None,
             oidc_provider: None,
             oidc_subject: None,
+            enabled: true,
+            sessions_revoked_at: None,
+        }
+    }
+
+    /// Seed user pre-provisioned for a specific email, claimable only by that email
+    pub fn new_named_seed(
+        email: EmailAddress,
+        organization_id: Uuid,
+        permissions: UserOrgPermissions,
+    ) -> Self {
+        Self {
+            email,
+            permissions,
+            organization_id,
+            password_hash: None,
+            oidc_linked_at: None,
+            oidc_provider: None,
+            oidc_subject: None,
+            enabled: true,
+            sessions_revoked_at: None,
         }
     }
 
+    /// Seed users have no credentials attached and are waiting to be claimed
+    pub fn is_seed(&self) -> bool {
+        self.password_hash.is_none() && self.oidc_subject.is_none()
+    }
+
+    /// Whether this is a seed user with a generated placeholder email rather than a named one
+    pub fn is_unnamed_seed(&self) -> bool {
+        self.is_seed()
+            && self.email.domain() == Self::SEED_EMAIL_DOMAIN
+            && Uuid::parse_str(self.email.local_part()).is_ok()
+    }
+
     pub fn new_oidc(
         email: EmailAddress,
         oidc_subject: String,
@@ -72,6 +124,8 @@ impl UserBase {
             organization_id,
             oidc_provider,
             oidc_subject: Some(oidc_subject),
+            enabled: true,
+            sessions_revoked_at: None,
         }
     }
 
@@ -89,6 +143,8 @@ impl UserBase {
             oidc_linked_at: None,
             oidc_provider: None,
             oidc_subject: None,
+            enabled: true,
+            sessions_revoked_at: None,
         }
     }
 }
@@ -167,6 +223,8 @@ impl StorableEntity for User {
                     organization_id,
                     oidc_provider,
                     oidc_subject,
+                    enabled,
+                    sessions_revoked_at,
                 },
         } = self.clone();
 
@@ -182,6 +240,8 @@ impl StorableEntity for User {
                 "oidc_subject",
                 "permissions",
                 "organization_id",
+                "enabled",
+                "sessions_revoked_at",
             ],
             vec![
                 SqlValue::Uuid(id),
@@ -194,6 +254,8 @@ impl StorableEntity for User {
                 SqlValue::OptionalString(oidc_subject),
                 SqlValue::UserOrgPermissions(permissions),
                 SqlValue::Uuid(organization_id),
+                SqlValue::Bool(enabled),
+                SqlValue::OptionTimestamp(sessions_revoked_at),
             ],
         ))
     }
@@ -219,6 +281,8 @@ impl StorableEntity for User {
                 oidc_linked_at: row.get("oidc_linked_at"),
                 oidc_provider: row.get("oidc_provider"),
                 oidc_subject: row.get("oidc_subject"),
+                enabled: row.get("enabled"),
+                sessions_revoked_at: row.get("sessions_revoked_at"),
             },
         })
     }
@@ -1,5 +1,5 @@
 use crate::server::{
-    shared::handlers::traits::CrudHandlers,
+    shared::handlers::traits::{CrudHandlers, EntityScope},
     users::{r#impl::base::User, service::UserService},
 };
 
@@ -9,4 +9,8 @@ impl CrudHandlers for User {
     fn get_service(state: &crate::server::config::AppState) -> &Self::Service {
         &state.services.user_service
     }
+
+    fn scope(&self) -> EntityScope {
+        EntityScope::Organization(self.base.organization_id)
+    }
 }
@@ -19,6 +19,10 @@ use email_address::EmailAddress;
 use std::sync::Arc;
 use uuid::Uuid;
 
+#[derive(Debug, thiserror::Error)]
+#[error("Can't disable the last enabled admin in an organization")]
+pub struct LastEnabledAdmin;
+
 pub struct UserService {
     user_storage: Arc<GenericPostgresStorage<User>>,
 }
@@ -48,6 +52,80 @@ impl UserService {
         self.user_storage.get_all(filter).await
     }
 
+    /// Disable a user so they can no longer log in or use existing sessions. Refuses to disable
+    /// the organization's last enabled admin, which would leave nobody able to re-enable anyone.
+    pub async fn disable_user(&self, user_id: &Uuid) -> Result<User> {
+        let mut user = self
+            .get_by_id(user_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+
+        if !user.base.enabled {
+            return Ok(user);
+        }
+
+        if user.base.permissions >= UserOrgPermissions::Admin {
+            let other_admins = self
+                .user_storage
+                .get_all(EntityFilter::unfiltered().organization_id(&user.base.organization_id))
+                .await?
+                .into_iter()
+                .filter(|u| {
+                    u.id != user.id
+                        && u.base.enabled
+                        && u.base.permissions >= UserOrgPermissions::Admin
+                })
+                .count();
+
+            if other_admins == 0 {
+                return Err(LastEnabledAdmin.into());
+            }
+        }
+
+        user.base.enabled = false;
+        self.user_storage.update(&mut user).await?;
+        tracing::warn!(user_id = %user.id, "User disabled");
+
+        Ok(user)
+    }
+
+    /// Reject every session in `organization_id` started before now. Returns the users affected.
+    pub async fn revoke_sessions(&self, organization_id: &Uuid) -> Result<Vec<User>> {
+        let now = chrono::Utc::now();
+        let mut users = self
+            .user_storage
+            .get_all(EntityFilter::unfiltered().organization_id(organization_id))
+            .await?;
+
+        for user in &mut users {
+            user.base.sessions_revoked_at = Some(now);
+            self.user_storage.update(user).await?;
+        }
+
+        tracing::warn!(
+            organization_id = %organization_id,
+            user_count = users.len(),
+            "Revoked all sessions in organization"
+        );
+
+        Ok(users)
+    }
+
+    pub async fn enable_user(&self, user_id: &Uuid) -> Result<User> {
+        let mut user = self
+            .get_by_id(user_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+
+        if !user.base.enabled {
+            user.base.enabled = true;
+            self.user_storage.update(&mut user).await?;
+            tracing::info!(user_id = %user.id, "User enabled");
+        }
+
+        Ok(user)
+    }
+
     /// Create a new user
     pub async fn create_user(&self, user: User) -> Result<User, Error> {
         let existing_user = self
@@ -0,0 +1,443 @@
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Request, StatusCode, header},
+    middleware,
+};
+use email_address::EmailAddress;
+use serial_test::serial;
+use std::sync::Arc;
+use tower::Service;
+use uuid::Uuid;
+
+use crate::{
+    server::{
+        audit::r#impl::base::{AuditEventBase, AuditEventKind},
+        auth::{
+            impersonation::{IMPERSONATED_BY_HEADER, audit_impersonation},
+            service::hash_password,
+        },
+        config::{AppState, ServerConfig},
+        shared::{handlers::factory::create_router, services::traits::CrudService},
+        users::{
+            r#impl::{base::User, permissions::UserOrgPermissions},
+            service::LastEnabledAdmin,
+        },
+    },
+    tests::*,
+};
+
+/// Router with sessions and impersonation auditing, as served
+fn impersonation_app(state: &Arc<AppState>) -> Router {
+    create_router()
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            audit_impersonation,
+        ))
+        .layer(state.storage.sessions.clone())
+        .with_state(state.clone())
+}
+
+/// Create users with `PASSWORD` in a new organization, returning the organization id
+async fn create_users(state: &AppState, users: &[(&str, UserOrgPermissions)]) -> (Uuid, Vec<User>) {
+    let services = &state.services;
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+
+    let mut created = Vec::new();
+    for (email, permissions) in users {
+        let user = services
+            .user_service
+            .create_user_with_password(
+                EmailAddress::new_unchecked(*email),
+                hash_password(PASSWORD).unwrap(),
+                organization.id,
+                *permissions,
+            )
+            .await
+            .unwrap();
+        created.push(user);
+    }
+    (organization.id, created)
+}
+
+#[tokio::test]
+#[serial]
+async fn test_disabled_user_cannot_login_or_use_session() {
+    let (state, _container) = test_app_state().await;
+    let services = &state.services;
+
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+
+    let create = |email: &str, permissions| {
+        services.user_service.create_user_with_password(
+            EmailAddress::new_unchecked(email),
+            hash_password(PASSWORD).unwrap(),
+            organization.id,
+            permissions,
+        )
+    };
+    let owner: User = create("owner@example.com", UserOrgPermissions::Owner)
+        .await
+        .unwrap();
+    let member: User = create("member@example.com", UserOrgPermissions::Member)
+        .await
+        .unwrap();
+
+    let mut app = create_router()
+        .layer(state.storage.sessions.clone())
+        .with_state(state.clone());
+
+    let owner_cookie = login(&mut app, "owner@example.com").await;
+    let member_cookie = login(&mut app, "member@example.com").await;
+
+    let (status, _, _) = call(&mut app, "GET", "/api/users", Some(&member_cookie), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let disable = serde_json::json!({ "enabled": false });
+    let uri = format!("/api/users/{}/enabled", member.id);
+    let (status, _, _) = call(&mut app, "PUT", &uri, Some(&owner_cookie), Some(disable)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The member's existing session no longer authenticates
+    let (status, _, _) = call(&mut app, "GET", "/api/users", Some(&member_cookie), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Logging in fails with the same error as a wrong password
+    let (status, _, body) = try_login(&mut app, "member@example.com").await;
+    assert_ne!(status, StatusCode::OK);
+    assert!(body.contains("Invalid email or password"));
+
+    // The owner is the only enabled admin left, so can't be disabled
+    let err = services
+        .user_service
+        .disable_user(&owner.id)
+        .await
+        .unwrap_err();
+    assert!(err.is::<LastEnabledAdmin>());
+
+    let enable = serde_json::json!({ "enabled": true });
+    let (status, _, _) = call(&mut app, "PUT", &uri, Some(&owner_cookie), Some(enable)).await;
+    assert_eq!(status, StatusCode::OK);
+    login(&mut app, "member@example.com").await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_revoke_all_sessions_rejects_existing_cookies() {
+    let (state, _container) = test_app_state().await;
+    let services = &state.services;
+
+    let other_organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+    let organization = services
+        .organization_service
+        .create(organization())
+        .await
+        .unwrap();
+
+    for (email, organization_id, permissions) in [
+        (
+            "owner@example.com",
+            organization.id,
+            UserOrgPermissions::Owner,
+        ),
+        (
+            "member@example.com",
+            organization.id,
+            UserOrgPermissions::Member,
+        ),
+        (
+            "other@example.com",
+            other_organization.id,
+            UserOrgPermissions::Owner,
+        ),
+    ] {
+        services
+            .user_service
+            .create_user_with_password(
+                EmailAddress::new_unchecked(email),
+                hash_password(PASSWORD).unwrap(),
+                organization_id,
+                permissions,
+            )
+            .await
+            .unwrap();
+    }
+
+    let mut app = create_router()
+        .layer(state.storage.sessions.clone())
+        .with_state(state.clone());
+
+    let mut cookie = async |email| login(&mut app, email).await;
+    let owner_cookie = cookie("owner@example.com").await;
+    let owner_other_cookie = cookie("owner@example.com").await;
+    let member_cookie = cookie("member@example.com").await;
+    let other_cookie = cookie("other@example.com").await;
+
+    // Only admins can revoke
+    let revoke = "/api/auth/sessions/revoke-all";
+    let (status, _, _) = call(&mut app, "POST", revoke, Some(&member_cookie), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _, body) = call(&mut app, "POST", revoke, Some(&owner_cookie), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("\"users\":2"));
+
+    for (cookie, expected) in [
+        (&member_cookie, StatusCode::UNAUTHORIZED),
+        (&owner_other_cookie, StatusCode::UNAUTHORIZED),
+        // The admin's current session is preserved by default
+        (&owner_cookie, StatusCode::OK),
+        // Other organizations are untouched
+        (&other_cookie, StatusCode::OK),
+    ] {
+        let (status, _, _) = call(&mut app, "GET", "/api/users", Some(cookie), None).await;
+        assert_eq!(status, expected);
+        let (status, _, _) = call(&mut app, "POST", "/api/auth/me", Some(cookie), None).await;
+        assert_eq!(status, expected);
+    }
+
+    // Logging in again works as normal
+    let member_cookie = login(&mut app, "member@example.com").await;
+    let (status, _, _) = call(&mut app, "GET", "/api/users", Some(&member_cookie), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let body = serde_json::json!({ "preserve_current_session": false });
+    let (status, _, _) = call(&mut app, "POST", revoke, Some(&owner_cookie), Some(body)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _, _) = call(&mut app, "GET", "/api/users", Some(&owner_cookie), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_impersonation_is_marked_restricted_and_audited() {
+    let (state, _container) = test_app_state().await;
+    let (organization_id, users) = create_users(
+        &state,
+        &[
+            ("owner@example.com", UserOrgPermissions::Owner),
+            ("admin@example.com", UserOrgPermissions::Admin),
+            ("other-admin@example.com", UserOrgPermissions::Admin),
+            ("member@example.com", UserOrgPermissions::Member),
+        ],
+    )
+    .await;
+    let [owner, admin, other_admin, member] = users.try_into().unwrap();
+
+    let mut app = impersonation_app(&state);
+    let admin_cookie = login(&mut app, "admin@example.com").await;
+    let owner_cookie = login(&mut app, "owner@example.com").await;
+    let impersonate = |id: Uuid| format!("/api/users/{}/impersonate", id);
+
+    // Admins can't impersonate themselves or their peers
+    let (status, _, _) = call(
+        &mut app,
+        "POST",
+        &impersonate(admin.id),
+        Some(&admin_cookie),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _, _) = call(
+        &mut app,
+        "POST",
+        &impersonate(other_admin.id),
+        Some(&admin_cookie),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _, _) = call(
+        &mut app,
+        "POST",
+        &impersonate(member.id),
+        Some(&admin_cookie),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The admin's session now acts as the member, and is marked as impersonated
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/auth/me")
+        .header(header::COOKIE, &admin_cookie)
+        .body(Body::empty())
+        .unwrap();
+    let response = app.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(IMPERSONATED_BY_HEADER).unwrap(),
+        &admin.id.to_string()
+    );
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert!(String::from_utf8_lossy(&body).contains("member@example.com"));
+
+    // Credentials can't be changed on the user's behalf
+    let update = serde_json::json!({ "password": "hijacked-password-123" });
+    let (status, _, _) = call(
+        &mut app,
+        "POST",
+        "/api/auth/update",
+        Some(&admin_cookie),
+        Some(update),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let mut record = serde_json::to_value(&member).unwrap();
+    record["email"] = "hijacked@example.com".into();
+    let (status, _, _) = call(
+        &mut app,
+        "PUT",
+        &format!("/api/users/{}", member.id),
+        Some(&admin_cookie),
+        Some(record),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // An owner impersonating an admin can't use the admin's privileged actions or nest
+    let (status, _, _) = call(
+        &mut app,
+        "POST",
+        &impersonate(other_admin.id),
+        Some(&owner_cookie),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _, _) = call(
+        &mut app,
+        "POST",
+        &impersonate(member.id),
+        Some(&owner_cookie),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let revoke = "/api/auth/sessions/revoke-all";
+    let (status, _, _) = call(&mut app, "POST", revoke, Some(&owner_cookie), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Ending hands the session back to the admin
+    let end = "/api/auth/impersonation/end";
+    let (status, _, _) = call(&mut app, "POST", end, Some(&admin_cookie), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _, body) = call(&mut app, "POST", "/api/auth/me", Some(&admin_cookie), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("admin@example.com"));
+
+    let events = state
+        .services
+        .audit_service
+        .events_for_organization(&organization_id)
+        .await
+        .unwrap();
+    let admin_events: Vec<_> = events
+        .iter()
+        .filter(|e| e.base.actor_id == admin.id)
+        .collect();
+    assert!(
+        admin_events
+            .iter()
+            .all(|e| e.base.impersonated_user_id == Some(member.id))
+    );
+    for kind in [
+        AuditEventKind::ImpersonationStarted,
+        AuditEventKind::ImpersonatedRequest,
+        AuditEventKind::ImpersonationEnded,
+    ] {
+        assert!(admin_events.iter().any(|e| e.base.kind == kind));
+    }
+    assert!(events.iter().any(
+        |e| e.base.actor_id == owner.id && e.base.impersonated_user_id == Some(other_admin.id)
+    ));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_impersonation_expires_and_is_rate_limited() {
+    let (state, _container) = test_app_state_with_config(ServerConfig {
+        impersonation_ttl_secs: 0,
+        impersonation_max_per_hour: 1,
+        ..Default::default()
+    })
+    .await;
+    let (_, users) = create_users(
+        &state,
+        &[
+            ("admin@example.com", UserOrgPermissions::Admin),
+            ("member@example.com", UserOrgPermissions::Member),
+        ],
+    )
+    .await;
+
+    let mut app = impersonation_app(&state);
+    let cookie = login(&mut app, "admin@example.com").await;
+    let impersonate = format!("/api/users/{}/impersonate", users[1].id);
+
+    let (status, _, _) = call(&mut app, "POST", &impersonate, Some(&cookie), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The expired impersonation is rejected once, then the session is the admin's again
+    let (status, _, _) = call(&mut app, "POST", "/api/auth/me", Some(&cookie), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _, body) = call(&mut app, "POST", "/api/auth/me", Some(&cookie), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("admin@example.com"));
+
+    let (status, _, _) = call(&mut app, "POST", &impersonate, Some(&cookie), None).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_impersonation_rate_limit_counts_starts_from_other_servers() {
+    let (state, _container) = test_app_state_with_config(ServerConfig {
+        impersonation_max_per_hour: 1,
+        ..Default::default()
+    })
+    .await;
+    let (organization_id, users) = create_users(
+        &state,
+        &[
+            ("admin@example.com", UserOrgPermissions::Admin),
+            ("member@example.com", UserOrgPermissions::Member),
+        ],
+    )
+    .await;
+
+    // Started on another instance, or before a restart
+    state
+        .services
+        .audit_service
+        .record(AuditEventBase {
+            organization_id,
+            actor_id: users[0].id,
+            impersonated_user_id: Some(users[1].id),
+            kind: AuditEventKind::ImpersonationStarted,
+            details: serde_json::json!({}),
+        })
+        .await
+        .unwrap();
+
+    let mut app = impersonation_app(&state);
+    let cookie = login(&mut app, "admin@example.com").await;
+    let impersonate = format!("/api/users/{}/impersonate", users[1].id);
+    let (status, _, _) = call(&mut app, "POST", &impersonate, Some(&cookie), None).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+}
@@ -1,15 +1,17 @@
 use crate::server::{
+    auth::service::hash_password,
     config::{AppState, ServerConfig},
     daemons::r#impl::{
         api::DaemonCapabilities,
         base::{Daemon, DaemonBase, DaemonMode},
+        signing::ResultSignatureVerifier,
     },
     groups::r#impl::{
         base::{Group, GroupBase},
         types::GroupType,
     },
     hosts::r#impl::{
-        base::{Host, HostBase},
+        base::{Host, HostBase, HostState},
         interfaces::{Interface, InterfaceBase},
         ports::{Port, PortBase},
         targets::HostTarget,
@@ -21,7 +23,8 @@ use crate::server::{
         r#impl::base::{Service, ServiceBase},
     },
     shared::{
-        services::factory::ServiceFactory,
+        services::{factory::ServiceFactory, traits::CrudService},
+        slo::SloTracker,
         storage::{factory::StorageFactory, traits::StorableEntity},
         types::entities::EntitySource,
     },
@@ -30,18 +33,28 @@ use crate::server::{
         types::SubnetType,
     },
     topology::types::edges::EdgeStyle,
-    users::r#impl::base::{User, UserBase},
+    users::r#impl::{
+        base::{User, UserBase},
+        permissions::UserOrgPermissions,
+    },
+};
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{HeaderMap, Request, StatusCode, header},
 };
-use axum::Router;
 use chrono::Utc;
 use cidr::IpCidr;
 use cidr::Ipv4Cidr;
+use email_address::EmailAddress;
 use mac_address::MacAddress;
 use sqlx::PgPool;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::sync::Arc;
+use std::time::Duration;
 use testcontainers::{ContainerAsync, GenericImage, ImageExt, core::WaitFor, runners::AsyncRunner};
+use tower::Service as _;
 use uuid::Uuid;
 
 pub mod dependencies;
@@ -49,6 +62,9 @@ pub mod dependencies;
 pub const DAEMON_CONFIG_FIXTURE: &str = "src/tests/daemon_config.json";
 pub const SERVER_DB_FIXTURE: &str = "src/tests/netvisor.sql";
 
+/// Password for users tests create to log in through the API
+pub const PASSWORD: &str = "correct-horse-battery";
+
 pub async fn setup_test_db() -> (PgPool, String, ContainerAsync<GenericImage>) {
     let postgres_image = GenericImage::new("postgres", "17-alpine")
         .with_wait_for(WaitFor::message_on_stderr(
@@ -102,6 +118,10 @@ pub fn host(network_id: &Uuid) -> Host {
         source: EntitySource::System,
         virtualization: None,
         hidden: false,
+        state: HostState::Active,
+        group_ids: Vec::new(),
+        tags: Vec::new(),
+        ip_conflicts: Vec::new(),
     })
 }
 
@@ -165,7 +185,18 @@ pub fn daemon(network_id: &Uuid, host_id: &Uuid) -> Daemon {
         capabilities: DaemonCapabilities {
             has_docker_socket: false,
             interfaced_subnet_ids: Vec::new(),
+            concurrent_scans: None,
+            port_batch_size: None,
+            has_raw_socket_access: false,
+            supported_checks: Vec::new(),
+            protocol_version: None,
         },
+        version: None,
+        auth_failed_at: None,
+        paused: false,
+        labels: Default::default(),
+        system_metrics: None,
+        server_key: None,
     })
 }
 
@@ -174,6 +205,153 @@ pub async fn test_services() -> (StorageFactory, ServiceFactory, ContainerAsync<
     let services = ServiceFactory::new(&storage, None).await.unwrap();
     (storage, services, _container)
 }
+
+pub async fn test_app_state() -> (Arc<AppState>, ContainerAsync<GenericImage>) {
+    let (storage, services, _container) = test_services().await;
+    (
+        app_state(storage, services, ServerConfig::default()),
+        _container,
+    )
+}
+
+/// App state whose services are built from a non-default config
+pub async fn test_app_state_with_config(
+    config: ServerConfig,
+) -> (Arc<AppState>, ContainerAsync<GenericImage>) {
+    let (storage, _container) = test_storage().await;
+    let services = ServiceFactory::new(&storage, Some(config.clone()))
+        .await
+        .unwrap();
+    (app_state(storage, services, config), _container)
+}
+
+fn app_state(
+    storage: StorageFactory,
+    services: ServiceFactory,
+    config: ServerConfig,
+) -> Arc<AppState> {
+    let slo = Arc::new(SloTracker::new(Duration::from_millis(
+        config.slow_request_threshold_ms,
+    )));
+    let result_signatures = ResultSignatureVerifier::from(&config);
+
+    Arc::new(AppState {
+        config,
+        storage,
+        services,
+        slo,
+        result_signatures,
+    })
+}
+
+/// The API router with sessions, as served
+pub fn session_app(state: &Arc<AppState>) -> Router {
+    crate::server::shared::handlers::factory::create_router()
+        .layer(state.storage.sessions.clone())
+        .with_state(state.clone())
+}
+
+/// Create a user in `organization_id` who can log in with `PASSWORD`
+pub async fn user_with_password(
+    state: &AppState,
+    organization_id: &Uuid,
+    email: &str,
+    permissions: UserOrgPermissions,
+) -> User {
+    state
+        .services
+        .user_service
+        .create_user_with_password(
+            EmailAddress::new_unchecked(email),
+            hash_password(PASSWORD).unwrap(),
+            *organization_id,
+            permissions,
+        )
+        .await
+        .unwrap()
+}
+
+/// A network whose organization has `owner@example.com` and `member@example.com`, who log in with
+/// `PASSWORD`, and a network in another organization neither can see. Returns both network ids.
+pub async fn owned_and_foreign_networks(state: &AppState) -> (Uuid, Uuid) {
+    let services = &state.services;
+
+    let mut network_ids = Vec::new();
+    for _ in 0..2 {
+        let organization = services
+            .organization_service
+            .create(organization())
+            .await
+            .unwrap();
+        let network = services
+            .network_service
+            .create(network(&organization.id))
+            .await
+            .unwrap();
+        network_ids.push((organization.id, network.id));
+    }
+    let [(organization_id, network_id), (_, foreign_network_id)] = network_ids.try_into().unwrap();
+
+    for (email, permissions) in [
+        ("owner@example.com", UserOrgPermissions::Owner),
+        ("member@example.com", UserOrgPermissions::Member),
+    ] {
+        user_with_password(state, &organization_id, email, permissions).await;
+    }
+
+    (network_id, foreign_network_id)
+}
+
+/// Send a JSON request through `app`, authenticated with a session `cookie` if given
+pub async fn call(
+    app: &mut Router,
+    method: &str,
+    uri: &str,
+    cookie: Option<&str>,
+    body: Option<serde_json::Value>,
+) -> (StatusCode, HeaderMap, String) {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(cookie) = cookie {
+        request = request.header(header::COOKIE, cookie);
+    }
+    let request = match body {
+        Some(body) => request
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+
+    let response = app.call(request).await.unwrap();
+    let (status, headers) = (response.status(), response.headers().clone());
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+    (status, headers, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// The session cookie a response set, ready to send back
+pub fn session_cookie(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::SET_COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(str::to_owned)
+}
+
+/// Log in with `PASSWORD`, returning the status, session cookie and body whether or not it worked
+pub async fn try_login(app: &mut Router, email: &str) -> (StatusCode, Option<String>, String) {
+    let body = serde_json::json!({ "email": email, "password": PASSWORD });
+    let (status, headers, body) = call(app, "POST", "/api/auth/login", None, Some(body)).await;
+    (status, session_cookie(&headers), body)
+}
+
+/// Log in with `PASSWORD`, returning the session cookie
+pub async fn login(app: &mut Router, email: &str) -> String {
+    let (status, cookie, body) = try_login(app, email).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    cookie.unwrap()
+}
+
 pub async fn setup_test_app() -> Router<Arc<AppState>> {
     let config = ServerConfig::default();
 
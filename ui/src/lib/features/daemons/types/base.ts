@@ -5,10 +5,31 @@ export interface DaemonBase {
 	port: number;
 	last_seen: string;
 	mode: 'Pull' | 'Push';
+	paused: boolean;
+	labels: Record<string, string>;
 	capabilities: {
 		has_docker_socket: boolean;
 		interfaced_subnet_ids: string[];
 	};
+	system_metrics: DaemonSystemSnapshot | null;
+}
+
+export interface DaemonInterfaceStats {
+	name: string;
+	rx_bytes: number;
+	tx_bytes: number;
+	rx_errors: number;
+	tx_errors: number;
+}
+
+export interface DaemonSystemSnapshot {
+	reported_at: string;
+	cpu_percent: number;
+	memory_used_bytes: number;
+	memory_total_bytes: number;
+	disk_used_bytes: number;
+	disk_total_bytes: number;
+	interfaces: DaemonInterfaceStats[];
 }
 
 export interface Daemon extends DaemonBase {
@@ -60,6 +60,10 @@ export function startDiscoverySSE() {
 					getDaemons();
 				} else if (update.phase === 'Cancelled') {
 					pushWarning(`Discovery cancelled`);
+				} else if (update.phase === 'CancelledWithPartialResults') {
+					pushWarning(`Discovery cancelled, partial results kept`);
+					getHosts();
+					getServices();
 				} else if (update.phase === 'Failed' && update.error) {
 					pushError(`Discovery error: ${update.error}`, -1);
 				}
@@ -68,6 +72,7 @@ export function startDiscoverySSE() {
 				if (
 					update.phase === 'Complete' ||
 					update.phase === 'Cancelled' ||
+					update.phase === 'CancelledWithPartialResults' ||
 					update.phase === 'Failed'
 				) {
 					cancelling.update((c) => {
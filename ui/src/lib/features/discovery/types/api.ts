@@ -9,24 +9,51 @@ export interface DiscoverySessionRequest {
 
 export interface DiscoveryUpdatePayload {
 	session_id: string;
-	daemon_id: string;
+	/** Null for imports, which no daemon runs */
+	daemon_id: string | null;
 	discovery_type: DiscoveryType;
-	phase: 'Pending' | 'Starting' | 'Started' | 'Scanning' | 'Complete' | 'Failed' | 'Cancelled';
+	phase:
+		| 'Pending'
+		| 'Starting'
+		| 'Started'
+		| 'Scanning'
+		| 'Complete'
+		| 'Failed'
+		| 'Cancelled'
+		| 'CancelledWithPartialResults';
 	processed?: number;
 	total_to_process?: number;
 	error?: string;
 	started_at?: string;
 	finished_at?: string;
+	effective_scan_rate?: number;
+	/** How each explicit target resolved, for network discoveries given a target list */
+	target_results?: TargetResolution[];
+	/** Hosts the session found, set once it completes or is cancelled */
+	discovered_host_ids?: string[];
 }
 
-export type DiscoveryType = Network | Docker | SelfReport;
+export interface TargetResolution {
+	target: string;
+	address?: string;
+	error?: string;
+}
+
+export type DiscoveryType = Network | Docker | SelfReport | Import | Ndp;
 
 export interface Network {
 	type: 'Network';
 	subnet_ids: string[];
 	host_naming_fallback: 'Ip' | 'BestService';
+	scan_rate?: ScanRate;
+	/** Name of the secret holding the SNMP community to probe hosts with */
+	snmp_credential?: string;
+	/** IPs or hostnames to scan instead of whole subnets */
+	targets?: string[];
 }
 
+export type ScanRate = { mode: 'fixed' } | { mode: 'adaptive'; initial: number; max: number };
+
 export interface Docker {
 	type: 'Docker';
 	host_id: string;
@@ -37,3 +64,15 @@ export interface SelfReport {
 	type: 'SelfReport';
 	host_id: string;
 }
+
+/** Results of a scan run outside NetVisor, uploaded to /api/discovery/import */
+export interface Import {
+	type: 'Import';
+	format: 'nmap_xml' | 'nmap_greppable';
+}
+
+/** IPv6 neighbors found by an NDP scan, run via /api/daemons/{id}/ndp-scan */
+export interface Ndp {
+	type: 'Ndp';
+	interface: string;
+}
@@ -7,7 +7,8 @@ export interface Discovery {
 	discovery_type: DiscoveryType;
 	run_type: RunType;
 	name: string;
-	daemon_id: string;
+	/** Null for imported runs */
+	daemon_id: string | null;
 	network_id: string;
 }
 
@@ -23,6 +23,17 @@ export interface Host {
 	source: EntitySource;
 	network_id: string;
 	hidden: boolean;
+	group_ids?: string[];
+	tags?: string[];
+	ip_conflicts?: IpConflict[];
+}
+
+export interface IpConflict {
+	ip: string;
+	mac: string;
+	other_host_id: string;
+	other_mac: string;
+	detected_at: string;
 }
 
 export interface ProxmoxVirtualization {
@@ -62,6 +62,7 @@ export function createEmptyNetworkFormData(): Network {
 		created_at: utcTimeZoneSentinel,
 		updated_at: utcTimeZoneSentinel,
 		is_default: false,
-		organization_id: uuidv4Sentinel
+		organization_id: uuidv4Sentinel,
+		identity_key: 'mac'
 	};
 }
@@ -1,3 +1,5 @@
+export type HostIdentityKey = 'mac' | 'ip' | 'hostname';
+
 export interface Network {
 	id: string;
 	created_at: string;
@@ -5,4 +7,5 @@ export interface Network {
 	name: string;
 	is_default: boolean;
 	organization_id: string;
+	identity_key: HostIdentityKey;
 }
@@ -41,6 +41,8 @@ interface ApiResponse<T> {
 	success: boolean;
 	data?: T;
 	error?: string;
+	/** Machine-readable code for errors the client is expected to act on, e.g. DAEMON_AUTH_FAILED */
+	code?: string;
 }
 
 interface RequestCache {